@@ -0,0 +1,35 @@
+//! Embeds the build information reported by `hera --version` and `hera_buildInfo`.
+
+use std::{env, process::Command};
+
+/// Runs git with `args` in the crate directory, returning its trimmed output.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn main() {
+    let sha = git(&["rev-parse", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=HERA_GIT_SHA={sha}");
+    println!("cargo:rustc-env=HERA_GIT_SHA_SHORT={}", &sha[..sha.len().min(8)]);
+
+    let profile = env::var("PROFILE").unwrap_or_else(|_| "unknown".to_string());
+    println!("cargo:rustc-env=HERA_BUILD_PROFILE={profile}");
+
+    let mut features: Vec<_> = env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_").map(|name| name.to_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=HERA_FEATURES={}", features.join(","));
+
+    // Rebuild on new commits and branch switches.
+    if let Some(git_dir) = git(&["rev-parse", "--absolute-git-dir"]) {
+        println!("cargo:rerun-if-changed={git_dir}/HEAD");
+        if let Some(head) = git(&["symbolic-ref", "HEAD"]) {
+            println!("cargo:rerun-if-changed={git_dir}/{head}");
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
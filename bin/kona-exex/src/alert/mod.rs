@@ -0,0 +1,161 @@
+//! Alert notifications of critical node events, posted to a webhook.
+//!
+//! Operators without a monitoring stack get notified of derivation divergences, validation
+//! failures, prolonged stalls, deep reorgs and invalid output proposals through a single webhook
+//! URL. The JSON body carries the message as `text`, understood by Slack incoming webhooks and
+//! by the Telegram `sendMessage` method with the `chat_id` set in the URL, and as `content` for
+//! Discord.
+
+use std::{fmt, time::Duration};
+
+use alloy_primitives::B256;
+use eyre::{Result, WrapErr};
+use metrics::counter;
+use reqwest::Client;
+use serde_json::json;
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::{protocol::L2BlockInfo, shadow::Divergence, tenant};
+
+mod stall;
+pub use stall::StallMonitor;
+
+/// The default reorg depth above which reorgs are alerted, in L2 blocks.
+pub const DEFAULT_ALERT_REORG_DEPTH: u64 = 3;
+
+/// A critical event worth notifying the operator of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Alert {
+    /// Hera diverged from the op-node it shadows.
+    Divergence(Divergence),
+    /// Attributes derived on top of `parent` failed validation.
+    ValidationFailure {
+        /// The parent the attributes were derived on.
+        parent: L2BlockInfo,
+        /// Why validation failed.
+        reason: String,
+    },
+    /// The safe head has not advanced for `duration`.
+    Stall {
+        /// The safe head derivation is stuck at.
+        safe_head: L2BlockInfo,
+        /// How long the safe head has not advanced for.
+        duration: Duration,
+    },
+    /// A reorg of the L2 chain `depth` blocks deep.
+    Reorg {
+        /// The number of L2 blocks reorged out.
+        depth: u64,
+        /// A description of the reorg.
+        detail: String,
+    },
+    /// An output root proposed on L1 does not match the local output.
+    OutputMismatch {
+        /// The L1 block the proposal was included in.
+        l1_block: u64,
+        /// The L2 block the output was proposed for.
+        l2_block: u64,
+        /// The proposed output root.
+        proposed: B256,
+        /// The output root computed locally.
+        local: B256,
+    },
+}
+
+impl Alert {
+    /// Returns the kind of the alert.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Divergence(_) => "divergence",
+            Self::ValidationFailure { .. } => "validation_failure",
+            Self::Stall { .. } => "stall",
+            Self::Reorg { .. } => "reorg",
+            Self::OutputMismatch { .. } => "output_mismatch",
+        }
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Divergence(Divergence::BlockHash { head, number, local, remote }) => write!(
+                f,
+                "Hera diverged from op-node on the {head} block hash at {number}: {local} vs \
+                 {remote}"
+            ),
+            Self::Divergence(Divergence::OutputRoot { head, number, local, remote }) => write!(
+                f,
+                "Hera diverged from op-node on the {head} output root at {number}: {local} vs \
+                 {remote}"
+            ),
+            Self::ValidationFailure { parent, reason } => {
+                write!(f, "Attributes derived on top of {parent} failed validation: {reason}")
+            }
+            Self::Stall { safe_head, duration } => {
+                write!(f, "Safe head stalled at {safe_head} for {}s", duration.as_secs())
+            }
+            Self::Reorg { depth, detail } => write!(f, "L2 reorg {depth} blocks deep: {detail}"),
+            Self::OutputMismatch { l1_block, l2_block, proposed, local } => write!(
+                f,
+                "Output root proposed in L1 block {l1_block} for L2 block {l2_block} is \
+                 {proposed}, expected {local}"
+            ),
+        }
+    }
+}
+
+/// Posts alerts to a webhook.
+#[derive(Debug, Clone)]
+pub struct WebhookAlerter {
+    client: Client,
+    url: Url,
+    reorg_depth: u64,
+}
+
+impl WebhookAlerter {
+    /// Creates an alerter posting to `url`.
+    pub fn new(url: Url) -> Self {
+        Self { client: Client::new(), url, reorg_depth: DEFAULT_ALERT_REORG_DEPTH }
+    }
+
+    /// Only alerts reorgs deeper than `depth` L2 blocks.
+    pub const fn with_reorg_depth(mut self, depth: u64) -> Self {
+        self.reorg_depth = depth;
+        self
+    }
+
+    /// Posts the alert in the background. Failures are logged, never returned, so alerting
+    /// cannot hold up the node.
+    pub fn notify(&self, alert: Alert) {
+        if let Alert::Reorg { depth, .. } = alert {
+            if depth <= self.reorg_depth {
+                return;
+            }
+        }
+        let alerter = self.clone();
+        tenant::spawn(async move {
+            if let Err(err) = alerter.send(&alert).await {
+                warn!(target: "hera::alert", %err, kind = alert.kind(), "Failed to post alert");
+                counter!("hera_alert_errors_total").increment(1);
+            }
+        });
+    }
+
+    /// Posts the alert and waits for the webhook to accept it.
+    pub async fn send(&self, alert: &Alert) -> Result<()> {
+        let text = alert.to_string();
+        let body = json!({ "text": text, "content": text, "kind": alert.kind() });
+        self.client
+            .post(self.url.clone())
+            .json(&body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .wrap_err("alert webhook request failed")?;
+        counter!("hera_alerts_total", "kind" => alert.kind()).increment(1);
+        debug!(target: "hera::alert", kind = alert.kind(), "Posted alert");
+        Ok(())
+    }
+}
+
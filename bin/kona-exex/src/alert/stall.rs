@@ -0,0 +1,68 @@
+//! Detection of a stalled safe head.
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::{sync::watch, time::Instant};
+use tracing::{info, warn};
+
+use crate::{
+    alert::{Alert, WebhookAlerter},
+    rpc::SyncStatus,
+};
+
+/// Alerts once the safe head has not advanced for a timeout, and again for every further
+/// timeout it stays stalled.
+#[derive(Debug)]
+pub struct StallMonitor {
+    status: watch::Receiver<SyncStatus>,
+    timeout: Duration,
+    alerter: Arc<WebhookAlerter>,
+}
+
+impl StallMonitor {
+    /// Creates a monitor following the sync status through `status`.
+    pub const fn new(
+        status: watch::Receiver<SyncStatus>,
+        timeout: Duration,
+        alerter: Arc<WebhookAlerter>,
+    ) -> Self {
+        Self { status, timeout, alerter }
+    }
+
+    /// Watches the safe head until the sync status is dropped.
+    pub async fn run(self) {
+        let alerter = self.alerter.clone();
+        self.watch(|alert| alerter.notify(alert)).await;
+    }
+
+    /// Watches the safe head until the sync status is dropped, handing stall alerts to
+    /// `on_stall`.
+    pub(crate) async fn watch(mut self, mut on_stall: impl FnMut(Alert)) {
+        let mut safe_head = self.status.borrow_and_update().safe_l2;
+        let mut since = Instant::now();
+        let mut next_alert = since + self.timeout;
+        loop {
+            match tokio::time::timeout_at(next_alert, self.status.changed()).await {
+                Ok(Err(_)) => return,
+                Ok(Ok(())) => {
+                    let head = self.status.borrow_and_update().safe_l2;
+                    if head == safe_head {
+                        continue;
+                    }
+                    if since.elapsed() >= self.timeout {
+                        info!(target: "hera::alert", safe_head = %head, "Safe head advancing again");
+                    }
+                    safe_head = head;
+                    since = Instant::now();
+                    next_alert = since + self.timeout;
+                }
+                Err(_) => {
+                    let duration = since.elapsed();
+                    warn!(target: "hera::alert", %safe_head, ?duration, "Safe head stalled");
+                    on_stall(Alert::Stall { safe_head, duration });
+                    next_alert += self.timeout;
+                }
+            }
+        }
+    }
+}
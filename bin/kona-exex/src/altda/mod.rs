@@ -0,0 +1,343 @@
+//! Data availability challenges of Alt-DA chains.
+//!
+//! On Alt-DA chains, the batcher posts commitments to its data instead of the data itself. Anyone
+//! can challenge a commitment on the `DataAvailabilityChallenge` contract within the challenge
+//! window following its inclusion, and the challenge must then be resolved within the resolve
+//! window by posting the data on L1. A challenge left unresolved expires: its data is deemed
+//! unavailable, and the L2 blocks derived from it are reorged out.
+//!
+//! The [`ChallengeTracker`] follows the commitments of the derivation window through these
+//! states, and the [`DaChallengeWatcher`] feeds it from the L1 blocks Hera receives.
+//!
+//! Hera does not fetch the data of commitments from a DA server yet, so batcher transactions
+//! carrying commitments derive no blocks. Expired challenges still reset derivation to before
+//! the commitment, as the specification requires.
+
+use std::{collections::BTreeMap, fmt};
+
+use alloy_primitives::{b256, Bytes, Log, B256, U256};
+use eyre::{bail, ensure, eyre, Result, WrapErr};
+use metrics::{counter, gauge};
+use tracing::{debug, warn};
+
+use crate::config::RollupConfig;
+
+mod watcher;
+pub use watcher::DaChallengeWatcher;
+
+/// Version byte of batcher transactions carrying an Alt-DA commitment instead of frames.
+pub const ALT_DA_TX_VERSION: u8 = 0x01;
+
+/// Topic of the `ChallengeStatusChanged(uint256,bytes,uint8)` event of the
+/// `DataAvailabilityChallenge` contract.
+pub const CHALLENGE_STATUS_CHANGED_TOPIC: B256 =
+    b256!("c5d8c630ba2fdacb1db24c4599df78c7fb8cf97b5aecde34939597f6697bb1ad");
+
+/// Returns the commitment carried by the calldata of a batcher transaction, if it carries one.
+///
+/// Commitments start with their type, followed by the commitment itself, e.g. the keccak256
+/// hash of the data for the only type the challenge contract supports.
+pub fn decode_commitment(calldata: &[u8]) -> Option<Bytes> {
+    match calldata.split_first() {
+        Some((&ALT_DA_TX_VERSION, commitment)) if !commitment.is_empty() => {
+            Some(Bytes::copy_from_slice(commitment))
+        }
+        _ => None,
+    }
+}
+
+/// The challenge and resolve windows of an Alt-DA chain, in L1 blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChallengeWindows {
+    /// The number of blocks after its inclusion during which a commitment can be challenged.
+    pub challenge: u64,
+    /// The number of blocks after a challenge during which it can be resolved.
+    pub resolve: u64,
+}
+
+impl ChallengeWindows {
+    /// Returns the windows of `config`, if it is an Alt-DA chain with both windows set.
+    pub const fn from_config(config: &RollupConfig) -> Option<Self> {
+        match (config.da_challenge_address, config.da_challenge_window, config.da_resolve_window) {
+            (Some(_), Some(challenge), Some(resolve)) => Some(Self { challenge, resolve }),
+            _ => None,
+        }
+    }
+}
+
+/// The status of a challenge, as emitted by the challenge contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChallengeStatus {
+    /// The commitment was never challenged.
+    Uninitialized,
+    /// The challenge awaits resolution.
+    Active,
+    /// The data was posted on L1, resolving the challenge.
+    Resolved,
+    /// The challenge was not resolved in time.
+    Expired,
+}
+
+impl ChallengeStatus {
+    /// Returns the status numbered `value` by the contract.
+    pub fn from_u8(value: u8) -> Result<Self> {
+        Ok(match value {
+            0 => Self::Uninitialized,
+            1 => Self::Active,
+            2 => Self::Resolved,
+            3 => Self::Expired,
+            _ => bail!("unknown challenge status {value}"),
+        })
+    }
+}
+
+impl fmt::Display for ChallengeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Uninitialized => "uninitialized",
+            Self::Active => "active",
+            Self::Resolved => "resolved",
+            Self::Expired => "expired",
+        })
+    }
+}
+
+/// A `ChallengeStatusChanged` event of the challenge contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChallengeEvent {
+    /// The L1 block the challenged commitment was included in.
+    pub inclusion_block: u64,
+    /// The challenged commitment.
+    pub commitment: Bytes,
+    /// The new status of the challenge.
+    pub status: ChallengeStatus,
+}
+
+impl ChallengeEvent {
+    /// Decodes a `ChallengeStatusChanged` event.
+    pub fn decode(log: &Log) -> Result<Self> {
+        let topics = log.topics();
+        ensure!(topics.len() == 2, "expected 2 challenge status topics, got {}", topics.len());
+        ensure!(topics[0] == CHALLENGE_STATUS_CHANGED_TOPIC, "not a challenge status event");
+        let inclusion_block = u64::try_from(U256::from_be_bytes(topics[1].0))
+            .wrap_err("challenged block number overflows u64")?;
+
+        let data = &log.data.data;
+        let word = |index: usize| -> Result<U256> {
+            data.get(index * 32..(index + 1) * 32)
+                .map(U256::from_be_slice)
+                .ok_or_else(|| eyre!("challenge status event data is too short"))
+        };
+        let status = u8::try_from(word(1)?).wrap_err("challenge status overflows u8")?;
+        let offset = usize::try_from(word(0)?).wrap_err("invalid commitment offset")?;
+        ensure!(offset % 32 == 0, "invalid commitment offset {offset}");
+        let len = usize::try_from(word(offset / 32)?).wrap_err("invalid commitment length")?;
+        let commitment = data
+            .get(offset + 32..)
+            .and_then(|tail| tail.get(..len))
+            .ok_or_else(|| eyre!("challenged commitment is truncated"))?;
+        Ok(Self {
+            inclusion_block,
+            commitment: Bytes::copy_from_slice(commitment),
+            status: ChallengeStatus::from_u8(status)?,
+        })
+    }
+}
+
+/// A commitment whose challenge expired, making its data unavailable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpiredCommitment {
+    /// The L1 block the commitment was included in.
+    pub inclusion_block: u64,
+    /// The commitment.
+    pub commitment: Bytes,
+}
+
+/// The challenge of a tracked commitment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Challenge {
+    status: ChallengeStatus,
+    /// The L1 block the challenge was made in.
+    challenged_at: u64,
+}
+
+/// Follows the commitments of the derivation window through their challenges.
+///
+/// Commitments are tracked from their inclusion until they can no longer be challenged, or
+/// until their challenge is resolved or expires. Challenges of untracked commitments are ignored.
+#[derive(Debug)]
+pub struct ChallengeTracker {
+    windows: ChallengeWindows,
+    commitments: BTreeMap<(u64, Bytes), Option<Challenge>>,
+}
+
+impl ChallengeTracker {
+    /// Creates a tracker for a chain with the given windows.
+    pub const fn new(windows: ChallengeWindows) -> Self {
+        Self { windows, commitments: BTreeMap::new() }
+    }
+
+    /// Tracks `commitment`, included in the L1 block `inclusion_block`.
+    pub fn track(&mut self, commitment: Bytes, inclusion_block: u64) {
+        self.commitments.entry((inclusion_block, commitment)).or_insert(None);
+    }
+
+    /// Applies a challenge event emitted in the L1 block `block`.
+    pub fn apply(&mut self, event: &ChallengeEvent, block: u64) {
+        let key = (event.inclusion_block, event.commitment.clone());
+        let Some(challenge) = self.commitments.get_mut(&key) else {
+            let commitment = &event.commitment;
+            debug!(target: "hera::altda", %commitment, "Ignoring untracked commitment challenge");
+            return;
+        };
+        counter!("hera_altda_challenges_total", "status" => event.status.to_string()).increment(1);
+        match event.status {
+            ChallengeStatus::Active => {
+                warn!(
+                    target: "hera::altda",
+                    commitment = %event.commitment,
+                    inclusion_block = event.inclusion_block,
+                    "Commitment challenged"
+                );
+                *challenge =
+                    Some(Challenge { status: ChallengeStatus::Active, challenged_at: block });
+            }
+            ChallengeStatus::Resolved => {
+                let challenged_at = challenge.map_or(block, |challenge| challenge.challenged_at);
+                *challenge = Some(Challenge { status: ChallengeStatus::Resolved, challenged_at });
+            }
+            ChallengeStatus::Uninitialized | ChallengeStatus::Expired => {}
+        }
+    }
+
+    /// Moves the tracker to the L1 block `block`, returning the commitments whose challenge
+    /// expired with it, and dropping those that can no longer change status.
+    pub fn advance(&mut self, block: u64) -> Vec<ExpiredCommitment> {
+        let ChallengeWindows { challenge: challenge_window, resolve: resolve_window } =
+            self.windows;
+        let mut expired = Vec::new();
+        self.commitments.retain(|(inclusion_block, commitment), challenge| match challenge {
+            None => block <= inclusion_block + challenge_window,
+            Some(Challenge { status: ChallengeStatus::Active, challenged_at }) => {
+                if block > *challenged_at + resolve_window {
+                    expired.push(ExpiredCommitment {
+                        inclusion_block: *inclusion_block,
+                        commitment: commitment.clone(),
+                    });
+                    false
+                } else {
+                    true
+                }
+            }
+            Some(_) => false,
+        });
+        if !expired.is_empty() {
+            counter!("hera_altda_expired_commitments_total").increment(expired.len() as u64);
+        }
+        gauge!("hera_altda_tracked_commitments").set(self.commitments.len() as f64);
+        expired
+    }
+
+    /// Forgets the commitments included and the challenges made from the L1 block `block`
+    /// onwards, which were reorged out.
+    pub fn revert(&mut self, block: u64) {
+        self.commitments.retain(|(inclusion_block, _), challenge| {
+            if challenge.is_some_and(|challenge| challenge.challenged_at >= block) {
+                *challenge = None;
+            }
+            *inclusion_block < block
+        });
+    }
+
+    /// Returns the number of tracked commitments.
+    pub fn len(&self) -> usize {
+        self.commitments.len()
+    }
+
+    /// Returns true if no commitment is tracked.
+    pub fn is_empty(&self) -> bool {
+        self.commitments.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{keccak256, Address, LogData};
+
+    use super::*;
+
+    /// Encodes a `ChallengeStatusChanged` event.
+    pub(super) fn event_log(
+        contract: Address,
+        inclusion_block: u64,
+        commitment: &[u8],
+        status: u8,
+    ) -> Log {
+        let mut data = U256::from(64).to_be_bytes::<32>().to_vec();
+        data.extend_from_slice(&U256::from(status).to_be_bytes::<32>());
+        data.extend_from_slice(&U256::from(commitment.len()).to_be_bytes::<32>());
+        data.extend_from_slice(commitment);
+        data.resize(data.len().next_multiple_of(32), 0);
+        let topics = vec![CHALLENGE_STATUS_CHANGED_TOPIC, U256::from(inclusion_block).into()];
+        Log { address: contract, data: LogData::new(topics, data.into()).unwrap() }
+    }
+
+    #[test]
+    fn decodes_challenge_events() {
+        assert_eq!(
+            keccak256("ChallengeStatusChanged(uint256,bytes,uint8)"),
+            CHALLENGE_STATUS_CHANGED_TOPIC
+        );
+        let commitment = [&[0][..], &[0xab; 32]].concat();
+        let event = ChallengeEvent::decode(&event_log(Address::ZERO, 7, &commitment, 1)).unwrap();
+        assert_eq!(
+            event,
+            ChallengeEvent {
+                inclusion_block: 7,
+                commitment: commitment.clone().into(),
+                status: ChallengeStatus::Active,
+            }
+        );
+        assert!(ChallengeEvent::decode(&event_log(Address::ZERO, 7, &commitment, 4)).is_err());
+
+        assert_eq!(decode_commitment(&[&[1][..], &commitment].concat()), Some(commitment.into()));
+        assert_eq!(decode_commitment(&[0, 1, 2]), None);
+        assert_eq!(decode_commitment(&[1]), None);
+    }
+
+    #[test]
+    fn expires_unresolved_challenges() {
+        let mut tracker = ChallengeTracker::new(ChallengeWindows { challenge: 10, resolve: 5 });
+        let event = |inclusion_block, commitment: u8, status| ChallengeEvent {
+            inclusion_block,
+            commitment: Bytes::from(vec![commitment]),
+            status,
+        };
+        tracker.track(Bytes::from(vec![1]), 100);
+        tracker.track(Bytes::from(vec![2]), 100);
+        tracker.track(Bytes::from(vec![3]), 101);
+
+        // Commitment 1 is challenged then resolved, commitment 2 is challenged and left alone.
+        tracker.apply(&event(100, 1, ChallengeStatus::Active), 105);
+        tracker.apply(&event(100, 2, ChallengeStatus::Active), 106);
+        tracker.apply(&event(100, 1, ChallengeStatus::Resolved), 108);
+        assert_eq!(tracker.advance(108), []);
+        assert_eq!(tracker.len(), 2);
+
+        // Commitment 3 can no longer be challenged past its window.
+        assert_eq!(tracker.advance(111), []);
+        assert_eq!(
+            tracker.advance(112),
+            [ExpiredCommitment { inclusion_block: 100, commitment: Bytes::from(vec![2]) }]
+        );
+        assert!(tracker.is_empty());
+
+        // Reorged challenges and commitments are forgotten.
+        tracker.track(Bytes::from(vec![4]), 120);
+        tracker.track(Bytes::from(vec![5]), 125);
+        tracker.apply(&event(120, 4, ChallengeStatus::Active), 126);
+        tracker.revert(125);
+        assert_eq!(tracker.advance(132), []);
+        assert_eq!(tracker.len(), 0);
+    }
+}
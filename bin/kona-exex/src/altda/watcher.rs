@@ -0,0 +1,110 @@
+//! Watching L1 for the commitments of the batcher and their challenges.
+
+use std::sync::Arc;
+
+use alloy_primitives::Address;
+use eyre::{ensure, eyre, Result, WrapErr};
+
+use crate::{
+    altda::{
+        decode_commitment, ChallengeEvent, ChallengeTracker, ChallengeWindows, ExpiredCommitment,
+        CHALLENGE_STATUS_CHANGED_TOPIC,
+    },
+    config::RollupConfig,
+    driver::DerivationStart,
+    l1::ChainProvider,
+    output::L2StateProvider,
+    protocol::{BlockInfo, L2BlockInfo},
+    safedb::SafeDb,
+};
+
+/// Feeds a [`ChallengeTracker`] with the commitments sent to the batch inbox and the challenge
+/// events of the challenge contract, block by block.
+///
+/// Commitments are tracked from every transaction sent to the batch inbox: the batcher address
+/// is only checked by derivation, so a commitment sent by anyone else can at worst cause a
+/// needless reset if challenged. Resets start from the safe head recorded in the [`SafeDb`]
+/// before the inclusion of the expired commitment.
+#[derive(Debug)]
+pub struct DaChallengeWatcher {
+    l1: Arc<dyn ChainProvider>,
+    l2: Arc<dyn L2StateProvider>,
+    safe_db: Arc<SafeDb>,
+    contract: Address,
+    batch_inbox: Address,
+    tracker: ChallengeTracker,
+}
+
+impl DaChallengeWatcher {
+    /// Creates a watcher of the Alt-DA chain of `config`, reading L1 blocks from `l1`.
+    pub fn new(
+        config: &RollupConfig,
+        l1: Arc<dyn ChainProvider>,
+        l2: Arc<dyn L2StateProvider>,
+        safe_db: Arc<SafeDb>,
+    ) -> Result<Self> {
+        let contract = config.da_challenge_address.ok_or_else(|| eyre!("not an Alt-DA chain"))?;
+        let windows = ChallengeWindows::from_config(config)
+            .ok_or_else(|| eyre!("Alt-DA chain without challenge and resolve windows"))?;
+        Ok(Self {
+            l1,
+            l2,
+            safe_db,
+            contract,
+            batch_inbox: config.batch_inbox_address,
+            tracker: ChallengeTracker::new(windows),
+        })
+    }
+
+    /// Returns the tracker.
+    pub const fn tracker(&self) -> &ChallengeTracker {
+        &self.tracker
+    }
+
+    /// Reads the commitments and challenges of the L1 block `block`, returning the commitments
+    /// whose challenge expired with it.
+    pub async fn process(&mut self, block: &BlockInfo) -> Result<Vec<ExpiredCommitment>> {
+        let transactions = self.l1.transactions_by_hash(block.hash).await?;
+        for tx in transactions.iter().filter(|tx| tx.to == Some(self.batch_inbox)) {
+            if let Some(commitment) = decode_commitment(&tx.input) {
+                self.tracker.track(commitment, block.number);
+            }
+        }
+
+        let receipts = self.l1.receipts_by_hash(block.hash).await?;
+        let events = receipts.iter().filter(|receipt| receipt.status()).flat_map(|receipt| {
+            receipt.logs().iter().filter(|log| {
+                log.address == self.contract &&
+                    log.topics().first() == Some(&CHALLENGE_STATUS_CHANGED_TOPIC)
+            })
+        });
+        for log in events {
+            let event = ChallengeEvent::decode(log)
+                .wrap_err_with(|| format!("invalid challenge event in L1 block {block}"))?;
+            self.tracker.apply(&event, block.number);
+        }
+        Ok(self.tracker.advance(block.number))
+    }
+
+    /// Forgets what was read from the L1 block `block` onwards, which was reorged out.
+    pub fn revert(&mut self, block: u64) {
+        self.tracker.revert(block);
+    }
+
+    /// Returns the safe head and L1 origin to derive again from, so that the blocks derived from
+    /// the L1 block `inclusion_block` onwards are reorged out.
+    pub async fn reset_target(&self, inclusion_block: u64) -> Result<(L2BlockInfo, BlockInfo)> {
+        let before = self
+            .safe_db
+            .safe_head_at(inclusion_block.saturating_sub(1))?
+            .ok_or_else(|| eyre!("no safe head recorded before L1 block {inclusion_block}"))?;
+        let start = DerivationStart { l2_block: before.safe_head.number, l1_origin: None };
+        let (safe_head, l1_origin) = start.resolve(self.l2.as_ref(), self.l1.as_ref()).await?;
+        ensure!(
+            safe_head.block_info.hash == before.safe_head.hash,
+            "safe head {} recorded before L1 block {inclusion_block} is no longer canonical",
+            before.safe_head.number
+        );
+        Ok((safe_head, l1_origin))
+    }
+}
@@ -0,0 +1,309 @@
+//! An append-only audit log of node events, stored in SQLite for post-incident analysis.
+//!
+//! Every derived block, head advancement, validation failure and reorg is recorded with the
+//! wall-clock time it happened at. The log is read back with the `hera audit` command.
+
+use std::{
+    fmt,
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{Result, WrapErr};
+use rusqlite::{params, params_from_iter, types::Value, Connection};
+use serde::Serialize;
+
+use crate::protocol::L2BlockInfo;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    recorded_at INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    l2_number INTEGER,
+    l2_hash TEXT,
+    l1_origin_number INTEGER,
+    l1_origin_hash TEXT,
+    detail TEXT
+);
+CREATE INDEX IF NOT EXISTS events_l2_number ON events (l2_number);
+CREATE INDEX IF NOT EXISTS events_recorded_at ON events (recorded_at);
+";
+
+/// The L2 head an [`AuditEvent::HeadAdvanced`] refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadKind {
+    /// The unsafe head.
+    Unsafe,
+    /// The safe head.
+    Safe,
+    /// The finalized head.
+    Finalized,
+}
+
+impl fmt::Display for HeadKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Unsafe => "unsafe",
+            Self::Safe => "safe",
+            Self::Finalized => "finalized",
+        })
+    }
+}
+
+/// An event recorded in the audit log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditEvent {
+    /// Derivation produced attributes that validated into this block.
+    DerivedBlock(L2BlockInfo),
+    /// A head of the L2 chain moved to a new block.
+    HeadAdvanced {
+        /// The head that moved.
+        head: HeadKind,
+        /// The new head.
+        block: L2BlockInfo,
+    },
+    /// Attributes derived on top of `parent` failed validation.
+    ValidationFailure {
+        /// The parent the attributes were derived on.
+        parent: L2BlockInfo,
+        /// Why validation failed.
+        reason: String,
+    },
+    /// A reorg of the L1 or L2 chain, at `block` if known.
+    Reorg {
+        /// The L2 block the reorg was detected at, if any.
+        block: Option<L2BlockInfo>,
+        /// A description of the reorg.
+        detail: String,
+    },
+}
+
+impl AuditEvent {
+    /// Returns the kind of the event, as stored in the log.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::DerivedBlock(_) => "derived_block",
+            Self::HeadAdvanced { .. } => "head_advanced",
+            Self::ValidationFailure { .. } => "validation_failure",
+            Self::Reorg { .. } => "reorg",
+        }
+    }
+
+    const fn block(&self) -> Option<&L2BlockInfo> {
+        match self {
+            Self::DerivedBlock(block) | Self::HeadAdvanced { block, .. } => Some(block),
+            Self::ValidationFailure { parent, .. } => Some(parent),
+            Self::Reorg { block, .. } => block.as_ref(),
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        match self {
+            Self::DerivedBlock(_) => None,
+            Self::HeadAdvanced { head, .. } => Some(head.to_string()),
+            Self::ValidationFailure { reason, .. } => Some(reason.clone()),
+            Self::Reorg { detail, .. } => Some(detail.clone()),
+        }
+    }
+}
+
+/// An event read back from the audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    /// The row ID, increasing with time.
+    pub id: i64,
+    /// When the event was recorded, in milliseconds since the Unix epoch.
+    pub recorded_at: i64,
+    /// The event kind, see [`AuditEvent::kind`].
+    pub kind: String,
+    /// The number of the L2 block of the event, if any.
+    pub l2_number: Option<u64>,
+    /// The hash of the L2 block of the event, if any.
+    pub l2_hash: Option<String>,
+    /// The L1 origin number of the L2 block of the event, if any.
+    pub l1_origin_number: Option<u64>,
+    /// The L1 origin hash of the L2 block of the event, if any.
+    pub l1_origin_hash: Option<String>,
+    /// Event-specific details: the head kind, the validation failure or the reorg.
+    pub detail: Option<String>,
+}
+
+/// A filter over the audit log. Unset fields match every event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditQuery {
+    /// Only return events of this kind.
+    pub kind: Option<String>,
+    /// Only return events at or above this L2 block number.
+    pub from_block: Option<u64>,
+    /// Only return events at or below this L2 block number.
+    pub to_block: Option<u64>,
+    /// Only return events recorded at or after this time, in milliseconds since the Unix epoch.
+    pub since: Option<i64>,
+    /// Maximum number of events returned, the most recent ones.
+    pub limit: Option<u64>,
+}
+
+/// The SQLite audit log.
+#[derive(Debug)]
+pub struct AuditLog {
+    conn: Mutex<Connection>,
+}
+
+impl AuditLog {
+    /// Opens the audit log at `path`, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let conn = Connection::open(path)
+            .wrap_err_with(|| format!("failed to open audit log {}", path.display()))?;
+        conn.execute_batch(SCHEMA).wrap_err("failed to create audit log schema")?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Records an event, timestamped with the current time.
+    pub fn record(&self, event: &AuditEvent) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let block = event.block();
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO events \
+                 (recorded_at, kind, l2_number, l2_hash, l1_origin_number, l1_origin_hash, detail) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    now as i64,
+                    event.kind(),
+                    block.map(|block| block.block_info.number),
+                    block.map(|block| block.block_info.hash.to_string()),
+                    block.map(|block| block.l1_origin.number),
+                    block.map(|block| block.l1_origin.hash.to_string()),
+                    event.detail(),
+                ],
+            )
+            .wrap_err_with(|| format!("failed to record {} audit event", event.kind()))?;
+        Ok(())
+    }
+
+    /// Returns the events matching `query`, oldest first.
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        if let Some(kind) = &query.kind {
+            conditions.push("kind = ?");
+            values.push(Value::Text(kind.clone()));
+        }
+        if let Some(from) = query.from_block {
+            conditions.push("l2_number >= ?");
+            values.push(Value::Integer(from as i64));
+        }
+        if let Some(to) = query.to_block {
+            conditions.push("l2_number <= ?");
+            values.push(Value::Integer(to as i64));
+        }
+        if let Some(since) = query.since {
+            conditions.push("recorded_at >= ?");
+            values.push(Value::Integer(since));
+        }
+        let mut sql = String::from(
+            "SELECT id, recorded_at, kind, l2_number, l2_hash, l1_origin_number, l1_origin_hash, \
+             detail FROM events",
+        );
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY id DESC");
+        if let Some(limit) = query.limit {
+            sql.push_str(&format!(" LIMIT {limit}"));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&sql).wrap_err("invalid audit query")?;
+        let mut records = statement
+            .query_map(params_from_iter(values), |row| {
+                Ok(AuditRecord {
+                    id: row.get(0)?,
+                    recorded_at: row.get(1)?,
+                    kind: row.get(2)?,
+                    l2_number: row.get(3)?,
+                    l2_hash: row.get(4)?,
+                    l1_origin_number: row.get(5)?,
+                    l1_origin_hash: row.get(6)?,
+                    detail: row.get(7)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .wrap_err("failed to query the audit log")?;
+        records.reverse();
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::B256;
+
+    use super::*;
+    use crate::protocol::{BlockId, BlockInfo};
+
+    fn block(number: u64) -> L2BlockInfo {
+        L2BlockInfo::new(
+            BlockInfo::new(B256::with_last_byte(number as u8), number, B256::ZERO, 2 * number),
+            BlockId::new(B256::repeat_byte(0xaa), number / 6),
+            number % 6,
+        )
+    }
+
+    fn audit_log() -> AuditLog {
+        let log = AuditLog::open(":memory:").unwrap();
+        log.record(&AuditEvent::DerivedBlock(block(1))).unwrap();
+        log.record(&AuditEvent::HeadAdvanced { head: HeadKind::Safe, block: block(1) }).unwrap();
+        log.record(&AuditEvent::ValidationFailure {
+            parent: block(7),
+            reason: "timestamp mismatch".into(),
+        })
+        .unwrap();
+        log.record(&AuditEvent::Reorg { block: None, detail: "L1 reorg".into() }).unwrap();
+        log
+    }
+
+    #[test]
+    fn records_events_in_order() {
+        let records = audit_log().query(&AuditQuery::default()).unwrap();
+        let kinds: Vec<_> = records.iter().map(|record| record.kind.as_str()).collect();
+        assert_eq!(kinds, ["derived_block", "head_advanced", "validation_failure", "reorg"]);
+        assert!(records.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+        let safe = &records[1];
+        assert_eq!(safe.l2_number, Some(1));
+        assert_eq!(safe.l2_hash, Some(B256::with_last_byte(1).to_string()));
+        assert_eq!(safe.l1_origin_hash, Some(B256::repeat_byte(0xaa).to_string()));
+        assert_eq!(safe.detail.as_deref(), Some("safe"));
+        assert_eq!(records[2].l1_origin_number, Some(1));
+        assert_eq!(records[2].detail.as_deref(), Some("timestamp mismatch"));
+        assert_eq!(records[3].l2_number, None);
+    }
+
+    #[test]
+    fn filters_events() {
+        let log = audit_log();
+        let query = |query: AuditQuery| {
+            let records = log.query(&query).unwrap();
+            records.into_iter().map(|record| record.kind).collect::<Vec<_>>()
+        };
+
+        let kind = AuditQuery { kind: Some("reorg".into()), ..Default::default() };
+        assert_eq!(query(kind), ["reorg"]);
+        // Events without an L2 block never match a block range.
+        let range = AuditQuery { from_block: Some(2), to_block: Some(7), ..Default::default() };
+        assert_eq!(query(range), ["validation_failure"]);
+        // The limit keeps the most recent events, still returned oldest first.
+        let limit = AuditQuery { limit: Some(2), ..Default::default() };
+        assert_eq!(query(limit), ["validation_failure", "reorg"]);
+        let future = AuditQuery { since: Some(i64::MAX), ..Default::default() };
+        assert!(query(future).is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+//! Outgoing channels, compressing batches and splitting them into frames.
+
+use alloy_primitives::Bytes;
+use alloy_rlp::Header;
+use eyre::{bail, ensure, Result};
+use metrics::{counter, gauge};
+use tracing::debug;
+
+use crate::{
+    batcher::Compression,
+    protocol::{frame::FRAME_OVERHEAD, Batch, ChannelId, Frame},
+};
+
+/// A channel being filled with batches by the batcher.
+///
+/// The compressed size is measured by recompressing the channel whenever its uncompressed size
+/// grew by more than a tenth since the last measurement, and extrapolated in between. The final
+/// output may therefore slightly exceed the target size, in which case it takes an extra frame.
+#[derive(Debug)]
+pub struct ChannelOut {
+    id: ChannelId,
+    compression: Compression,
+    max_size: usize,
+    opened_at: u64,
+    data: Vec<u8>,
+    batches: usize,
+    measured_len: usize,
+    measured_compressed_len: usize,
+}
+
+impl ChannelOut {
+    /// Opens an empty channel in the L1 block `opened_at`, holding at most `max_size` compressed
+    /// bytes.
+    pub const fn new(
+        id: ChannelId,
+        compression: Compression,
+        max_size: usize,
+        opened_at: u64,
+    ) -> Self {
+        Self {
+            id,
+            compression,
+            max_size,
+            opened_at,
+            data: Vec::new(),
+            batches: 0,
+            measured_len: 0,
+            measured_compressed_len: 0,
+        }
+    }
+
+    /// Returns the channel ID.
+    pub const fn id(&self) -> ChannelId {
+        self.id
+    }
+
+    /// Returns the L1 block number the channel was opened in.
+    pub const fn opened_at(&self) -> u64 {
+        self.opened_at
+    }
+
+    /// Returns the number of batches in the channel.
+    pub const fn batch_count(&self) -> usize {
+        self.batches
+    }
+
+    /// Returns whether the channel holds no batch.
+    pub const fn is_empty(&self) -> bool {
+        self.batches == 0
+    }
+
+    /// Adds a batch to the channel. Returns `false`, leaving the channel untouched, if the batch
+    /// would take the channel past its maximum compressed size.
+    ///
+    /// A batch too large for even an empty channel is an error.
+    pub fn add_batch(&mut self, batch: &Batch) -> Result<bool> {
+        let encoded = batch.encode();
+        let len = self.data.len();
+        Header { list: false, payload_length: encoded.len() }.encode(&mut self.data);
+        self.data.extend_from_slice(&encoded);
+
+        let estimate = if self.measured_len == 0 || self.data.len() > self.measured_len * 11 / 10 {
+            let compressed = self.compression.compress(&self.data)?.len();
+            self.measured_len = self.data.len();
+            self.measured_compressed_len = compressed;
+            compressed
+        } else {
+            self.measured_compressed_len * self.data.len() / self.measured_len
+        };
+
+        if estimate > self.max_size {
+            self.data.truncate(len);
+            if self.batches == 0 {
+                bail!(
+                    "batch of {} bytes compresses to {estimate} bytes, above the channel size {}",
+                    encoded.len(),
+                    self.max_size
+                );
+            }
+            return Ok(false);
+        }
+        self.batches += 1;
+        Ok(true)
+    }
+
+    /// Compresses the channel and splits it into frames of at most `frame_size` encoded bytes.
+    pub fn into_frames(self, frame_size: usize) -> Result<Vec<Frame>> {
+        ensure!(frame_size > FRAME_OVERHEAD, "frame size {frame_size} leaves no room for data");
+        ensure!(!self.is_empty(), "cannot output an empty channel");
+        let compressed = self.compression.compress(&self.data)?;
+
+        let ratio = compressed.len() as f64 / self.data.len() as f64;
+        gauge!("hera_batcher_compression_ratio").set(ratio);
+        counter!("hera_batcher_uncompressed_bytes_total").increment(self.data.len() as u64);
+        counter!("hera_batcher_compressed_bytes_total").increment(compressed.len() as u64);
+        debug!(
+            target: "hera::batcher",
+            channel = %self.id,
+            batches = self.batches,
+            uncompressed = self.data.len(),
+            compressed = compressed.len(),
+            ratio,
+            compression = %self.compression,
+            "Closed channel"
+        );
+
+        let chunks: Vec<_> = compressed.chunks(frame_size - FRAME_OVERHEAD).collect();
+        ensure!(chunks.len() <= u16::MAX as usize, "channel needs too many frames");
+        let last = chunks.len() - 1;
+        Ok(chunks
+            .into_iter()
+            .enumerate()
+            .map(|(number, chunk)| Frame {
+                id: self.id,
+                number: number as u16,
+                data: Bytes::copy_from_slice(chunk),
+                is_last: number == last,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{keccak256, B256};
+
+    use super::*;
+    use crate::protocol::{
+        batch::SingleBatch,
+        channel::{decompress_channel, BatchReader, Channel, MAX_RLP_BYTES_PER_CHANNEL_BEDROCK},
+        BlockInfo,
+    };
+
+    const ID: ChannelId = ChannelId([7; 16]);
+
+    /// A batch of `len` bytes of incompressible transaction data.
+    fn batch(number: u64, len: usize) -> Batch {
+        let mut data = Vec::with_capacity(len + 32);
+        let mut hash = B256::left_padding_from(&number.to_be_bytes());
+        while data.len() < len {
+            hash = keccak256(hash);
+            data.extend_from_slice(hash.as_slice());
+        }
+        data.truncate(len);
+        Batch::Single(SingleBatch {
+            epoch_num: number,
+            timestamp: 2 * number,
+            transactions: vec![data.into()],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn frames_decode_back_into_the_batches() {
+        let batches: Vec<_> = (0..4).map(|number| batch(number, 500)).collect();
+        let mut channel = ChannelOut::new(ID, Compression::default(), 10_000, 1);
+        for batch in &batches {
+            assert!(channel.add_batch(batch).unwrap());
+        }
+        assert_eq!(channel.batch_count(), 4);
+
+        let frames = channel.into_frames(600).unwrap();
+        assert!(frames.len() > 1);
+        let mut reassembled = Channel::new(ID, BlockInfo::default());
+        for (number, frame) in frames.into_iter().enumerate() {
+            assert_eq!(frame.number as usize, number);
+            assert!(frame.size() <= 600);
+            reassembled.add_frame(frame, BlockInfo::default()).unwrap();
+        }
+        assert!(reassembled.is_ready());
+
+        let data = reassembled.frame_data().unwrap();
+        let data = decompress_channel(&data, MAX_RLP_BYTES_PER_CHANNEL_BEDROCK, false).unwrap();
+        let decoded = BatchReader::new(&data).collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(decoded, batches);
+    }
+
+    #[test]
+    fn refuses_batches_past_the_maximum_size() {
+        let mut channel = ChannelOut::new(ID, Compression::default(), 1_000, 1);
+        assert!(channel.add_batch(&batch(0, 600)).unwrap());
+        assert!(!channel.add_batch(&batch(1, 600)).unwrap());
+        assert_eq!(channel.batch_count(), 1);
+
+        // The refused batch left no trace in the output.
+        let frames = channel.into_frames(2_000).unwrap();
+        let data = decompress_channel(&frames[0].data, MAX_RLP_BYTES_PER_CHANNEL_BEDROCK, false);
+        let decoded = BatchReader::new(&data.unwrap()).collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(decoded, [batch(0, 600)]);
+
+        let mut channel = ChannelOut::new(ID, Compression::default(), 1_000, 1);
+        assert!(channel.add_batch(&batch(0, 2_000)).is_err());
+        assert!(channel.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_outputs() {
+        let channel = ChannelOut::new(ID, Compression::default(), 1_000, 1);
+        assert!(channel.into_frames(1_000).is_err());
+
+        let mut channel = ChannelOut::new(ID, Compression::default(), 1_000, 1);
+        channel.add_batch(&batch(0, 10)).unwrap();
+        assert!(channel.into_frames(FRAME_OVERHEAD).is_err());
+    }
+}
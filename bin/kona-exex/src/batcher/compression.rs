@@ -0,0 +1,147 @@
+//! Channel compression.
+
+use std::{fmt, io::Write, str::FromStr};
+
+use eyre::{bail, Result, WrapErr};
+
+use crate::protocol::channel::CHANNEL_VERSION_BROTLI;
+
+/// Default zlib compression level, the highest supported by `miniz_oxide`.
+pub const DEFAULT_ZLIB_LEVEL: u32 = 10;
+
+/// Default brotli quality, matching op-batcher's `brotli-10`.
+pub const DEFAULT_BROTLI_LEVEL: u32 = 10;
+
+/// Brotli window size, as the base-2 logarithm of its size in bytes.
+const BROTLI_WINDOW_BITS: u32 = 24;
+
+/// The compression algorithm of channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionAlgo {
+    /// Zlib, supported at any time.
+    Zlib,
+    /// Brotli, only valid once Fjord is active.
+    Brotli,
+}
+
+impl CompressionAlgo {
+    /// Returns the default level of the algorithm.
+    pub const fn default_level(&self) -> u32 {
+        match self {
+            Self::Zlib => DEFAULT_ZLIB_LEVEL,
+            Self::Brotli => DEFAULT_BROTLI_LEVEL,
+        }
+    }
+
+    /// Returns the highest level of the algorithm.
+    pub const fn max_level(&self) -> u32 {
+        match self {
+            Self::Zlib => 10,
+            Self::Brotli => 11,
+        }
+    }
+}
+
+impl fmt::Display for CompressionAlgo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Zlib => "zlib",
+            Self::Brotli => "brotli",
+        })
+    }
+}
+
+impl FromStr for CompressionAlgo {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "zlib" => Ok(Self::Zlib),
+            "brotli" => Ok(Self::Brotli),
+            _ => bail!("unknown compression algorithm {s}, expected zlib or brotli"),
+        }
+    }
+}
+
+/// A compression algorithm with its level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compression {
+    /// The algorithm.
+    pub algo: CompressionAlgo,
+    /// The level, from 0 to the algorithm's maximum.
+    pub level: u32,
+}
+
+impl Compression {
+    /// Creates a compression setting, checking the level is supported by the algorithm.
+    pub fn new(algo: CompressionAlgo, level: u32) -> Result<Self> {
+        if level > algo.max_level() {
+            bail!("{algo} compression level {level} above the maximum {}", algo.max_level());
+        }
+        Ok(Self { algo, level })
+    }
+
+    /// Compresses the encoded batches of a channel, prefixing brotli output with its channel
+    /// version byte.
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match self.algo {
+            CompressionAlgo::Zlib => {
+                Ok(miniz_oxide::deflate::compress_to_vec_zlib(data, self.level as u8))
+            }
+            CompressionAlgo::Brotli => {
+                let mut out = vec![CHANNEL_VERSION_BROTLI];
+                let mut writer =
+                    brotli::CompressorWriter::new(&mut out, 4096, self.level, BROTLI_WINDOW_BITS);
+                writer.write_all(data).wrap_err("brotli compression failed")?;
+                drop(writer);
+                Ok(out)
+            }
+        }
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self { algo: CompressionAlgo::Zlib, level: DEFAULT_ZLIB_LEVEL }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.algo, self.level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::channel::{decompress_channel, MAX_RLP_BYTES_PER_CHANNEL_FJORD};
+
+    #[test]
+    fn parses_and_validates_settings() {
+        assert_eq!("zlib".parse::<CompressionAlgo>().unwrap(), CompressionAlgo::Zlib);
+        assert_eq!("brotli".parse::<CompressionAlgo>().unwrap(), CompressionAlgo::Brotli);
+        assert!("zstd".parse::<CompressionAlgo>().is_err());
+
+        assert!(Compression::new(CompressionAlgo::Zlib, 11).is_err());
+        let brotli = Compression::new(CompressionAlgo::Brotli, 11).unwrap();
+        assert_eq!(brotli.to_string(), "brotli-11");
+        assert_eq!(Compression::default().to_string(), "zlib-10");
+    }
+
+    #[test]
+    fn output_decompresses_as_a_channel() {
+        let data: Vec<u8> = (0..10_000u32).flat_map(|i| (i % 251).to_be_bytes()).collect();
+        for algo in [CompressionAlgo::Zlib, CompressionAlgo::Brotli] {
+            for level in [0, algo.default_level(), algo.max_level()] {
+                let compression = Compression::new(algo, level).unwrap();
+                let compressed = compression.compress(&data).unwrap();
+                let decompressed =
+                    decompress_channel(&compressed, MAX_RLP_BYTES_PER_CHANNEL_FJORD, true);
+                assert_eq!(decompressed.unwrap(), data, "{compression}");
+            }
+        }
+        let brotli = Compression::new(CompressionAlgo::Brotli, 1).unwrap();
+        assert_eq!(brotli.compress(b"batch").unwrap()[0], CHANNEL_VERSION_BROTLI);
+    }
+}
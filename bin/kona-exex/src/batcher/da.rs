@@ -0,0 +1,105 @@
+//! Switching batcher transactions between calldata and blobs, whichever is cheaper.
+
+use alloy_consensus::Header;
+use alloy_eips::{eip1559::BaseFeeParams, eip4844::DATA_GAS_PER_BLOB};
+use metrics::{counter, gauge};
+use tracing::info;
+
+use crate::{batcher::DataAvailability, blobs::MAX_BLOB_DATA_SIZE};
+
+/// Gas charged per calldata byte of a batcher transaction.
+///
+/// Batcher transactions are data-heavy, so they pay the EIP-7623 floor of 10 gas per token, with
+/// 4 tokens per non-zero byte. Compressed data is assumed to be entirely non-zero.
+pub const CALLDATA_GAS_PER_BYTE: u128 = 40;
+
+/// Default hysteresis of the switcher, in percent.
+pub const DEFAULT_DA_SWITCH_THRESHOLD: u64 = 10;
+
+/// The L1 fees deciding the data availability route.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct L1Fees {
+    /// The base fee per gas, in wei.
+    pub base_fee: u128,
+    /// The blob base fee per blob gas, in wei.
+    pub blob_base_fee: u128,
+}
+
+impl L1Fees {
+    /// Returns the fees of the block following the L1 block `header`, or `None` if the header
+    /// predates EIP-1559 or EIP-4844.
+    pub fn next_block(header: &Header) -> Option<Self> {
+        Some(Self {
+            base_fee: header.next_block_base_fee(BaseFeeParams::ethereum())?,
+            blob_base_fee: header.next_block_blob_fee()?,
+        })
+    }
+
+    /// Returns the cost of posting one byte of frame data through `da`, in wei.
+    pub const fn cost_per_byte(&self, da: DataAvailability) -> u128 {
+        match da {
+            DataAvailability::Calldata => self.base_fee * CALLDATA_GAS_PER_BYTE,
+            DataAvailability::Blobs => {
+                self.blob_base_fee * DATA_GAS_PER_BLOB as u128 / MAX_BLOB_DATA_SIZE as u128
+            }
+        }
+    }
+}
+
+/// Chooses the data availability route of each new channel from the L1 fees.
+///
+/// The route only changes once the other route is cheaper by more than the threshold, so that
+/// fees hovering around the break-even point do not make the batcher flap between routes.
+#[derive(Debug, Clone)]
+pub struct DaSwitcher {
+    current: DataAvailability,
+    threshold: u64,
+}
+
+impl DaSwitcher {
+    /// Creates a switcher starting on the `initial` route, switching once the other route is
+    /// `threshold` percent cheaper.
+    pub const fn new(initial: DataAvailability, threshold: u64) -> Self {
+        Self { current: initial, threshold }
+    }
+
+    /// Returns the current route.
+    pub const fn current(&self) -> DataAvailability {
+        self.current
+    }
+
+    /// Updates the route from the latest L1 fees, returning the route to use.
+    pub fn observe(&mut self, fees: L1Fees) -> DataAvailability {
+        let other = match self.current {
+            DataAvailability::Calldata => DataAvailability::Blobs,
+            DataAvailability::Blobs => DataAvailability::Calldata,
+        };
+        let current_cost = fees.cost_per_byte(self.current);
+        let other_cost = fees.cost_per_byte(other);
+        gauge!("hera_batcher_calldata_cost_per_byte_wei")
+            .set(fees.cost_per_byte(DataAvailability::Calldata) as f64);
+        gauge!("hera_batcher_blob_cost_per_byte_wei")
+            .set(fees.cost_per_byte(DataAvailability::Blobs) as f64);
+
+        if other_cost.saturating_mul(100 + self.threshold as u128) <
+            current_cost.saturating_mul(100)
+        {
+            info!(
+                target: "hera::batcher",
+                from = %self.current,
+                to = %other,
+                current_cost,
+                other_cost,
+                "Switching data availability route"
+            );
+            counter!("hera_batcher_da_switches_total").increment(1);
+            self.current = other;
+        }
+        gauge!("hera_batcher_da_blobs").set(if self.current == DataAvailability::Blobs {
+            1.0
+        } else {
+            0.0
+        });
+        self.current
+    }
+}
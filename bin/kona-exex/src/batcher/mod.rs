@@ -0,0 +1,403 @@
+//! The batcher: packing L2 batches into compressed channels and batcher transaction data.
+//!
+//! Batches are added to a [`ChannelManager`], which fills channels up to the capacity of the
+//! data availability route they will be posted with. Calldata channels fit a single frame of the
+//! target frame size. Blob channels take one frame per blob, and are split into sub-channels once
+//! they would need more blobs than fit in a single transaction, so that every channel can be
+//! posted atomically.
+//!
+//! With automatic switching enabled, the route of each new channel follows the L1 fees through a
+//! [`DaSwitcher`]. The resulting transactions are sent and kept moving by a [`TxManager`].
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alloy_eips::eip4844::Blob;
+use alloy_primitives::{keccak256, Bytes};
+use eyre::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{
+    blobs::{encode_blob_data, MAX_BLOB_DATA_SIZE},
+    protocol::{
+        frame::{DERIVATION_VERSION_0, FRAME_OVERHEAD},
+        Batch, ChannelId, Frame,
+    },
+};
+
+mod channel;
+pub use channel::ChannelOut;
+
+pub mod da;
+pub use da::{DaSwitcher, L1Fees};
+
+pub mod compression;
+pub use compression::{Compression, CompressionAlgo};
+
+pub mod txmgr;
+pub use txmgr::{InFlightTx, Inclusion, L1TxSender, TxFees, TxManager, TxManagerConfig, TxOutcome};
+
+/// Default target size of calldata frames, in bytes.
+pub const DEFAULT_TARGET_FRAME_SIZE: usize = 120_000;
+
+/// Default maximum number of blobs of a batcher transaction.
+pub const DEFAULT_MAX_BLOBS_PER_TX: usize = 6;
+
+/// The L1 data availability route of batcher transactions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataAvailability {
+    /// Frames are posted as transaction calldata.
+    #[default]
+    Calldata,
+    /// Frames are posted as EIP-4844 blobs, from Ecotone onwards.
+    Blobs,
+}
+
+impl fmt::Display for DataAvailability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Calldata => "calldata",
+            Self::Blobs => "blobs",
+        })
+    }
+}
+
+impl FromStr for DataAvailability {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "calldata" => Ok(Self::Calldata),
+            "blobs" => Ok(Self::Blobs),
+            _ => bail!("unknown data availability route {s}, expected calldata or blobs"),
+        }
+    }
+}
+
+/// Channel and frame settings of the batcher.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatcherConfig {
+    /// The compression of channels.
+    pub compression: Compression,
+    /// Target encoded size of calldata frames. Blob frames always fill a blob.
+    pub target_frame_size: usize,
+    /// Number of L1 blocks after which an open channel is closed, or 0 to only close channels
+    /// once full.
+    pub max_channel_duration: u64,
+    /// The data availability route, or the initial route when switching automatically.
+    pub data_availability: DataAvailability,
+    /// Whether to switch between calldata and blobs depending on the L1 fees.
+    pub auto_data_availability: bool,
+    /// How much cheaper, in percent, the other route must be before switching to it.
+    pub da_switch_threshold: u64,
+    /// Maximum number of blobs of a batcher transaction, bounding the size of blob channels.
+    pub max_blobs_per_tx: usize,
+}
+
+impl Default for BatcherConfig {
+    fn default() -> Self {
+        Self {
+            compression: Compression::default(),
+            target_frame_size: DEFAULT_TARGET_FRAME_SIZE,
+            max_channel_duration: 0,
+            data_availability: DataAvailability::default(),
+            auto_data_availability: false,
+            da_switch_threshold: da::DEFAULT_DA_SWITCH_THRESHOLD,
+            max_blobs_per_tx: DEFAULT_MAX_BLOBS_PER_TX,
+        }
+    }
+}
+
+impl BatcherConfig {
+    /// Returns the encoded size of frames posted through `da`.
+    pub const fn frame_size(&self, da: DataAvailability) -> usize {
+        match da {
+            DataAvailability::Calldata => self.target_frame_size,
+            // The blob data also carries the derivation version byte.
+            DataAvailability::Blobs => MAX_BLOB_DATA_SIZE - 1,
+        }
+    }
+
+    /// Returns the maximum compressed size of channels posted through `da`: the frame data of a
+    /// single batcher transaction.
+    pub const fn max_channel_size(&self, da: DataAvailability) -> usize {
+        let frame_data = self.frame_size(da) - FRAME_OVERHEAD;
+        match da {
+            DataAvailability::Calldata => frame_data,
+            DataAvailability::Blobs => frame_data * self.max_blobs_per_tx,
+        }
+    }
+}
+
+/// The frames of a single batcher transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxData {
+    /// The frames, all of the same channel.
+    pub frames: Vec<Frame>,
+    /// The route the frames are posted through.
+    pub data_availability: DataAvailability,
+}
+
+impl TxData {
+    /// Returns the calldata of the transaction: the derivation version byte followed by the
+    /// frames.
+    pub fn calldata(&self) -> Bytes {
+        let mut data = vec![DERIVATION_VERSION_0];
+        for frame in &self.frames {
+            data.extend_from_slice(&frame.encode());
+        }
+        data.into()
+    }
+
+    /// Returns the blobs of the transaction, one per frame.
+    pub fn blobs(&self) -> Result<Vec<Box<Blob>>> {
+        self.frames
+            .iter()
+            .map(|frame| {
+                let mut data = vec![DERIVATION_VERSION_0];
+                data.extend_from_slice(&frame.encode());
+                encode_blob_data(&data)
+            })
+            .collect()
+    }
+}
+
+/// Fills channels with batches and turns them into batcher transaction data.
+#[derive(Debug)]
+pub struct ChannelManager {
+    config: BatcherConfig,
+    current: Option<(ChannelOut, DataAvailability)>,
+    pending: VecDeque<TxData>,
+    channels_opened: u64,
+    switcher: Option<DaSwitcher>,
+}
+
+impl ChannelManager {
+    /// Creates a channel manager with no open channel.
+    pub fn new(config: BatcherConfig) -> Self {
+        let switcher = config
+            .auto_data_availability
+            .then(|| DaSwitcher::new(config.data_availability, config.da_switch_threshold));
+        Self { config, current: None, pending: VecDeque::new(), channels_opened: 0, switcher }
+    }
+
+    /// Returns the batcher config.
+    pub const fn config(&self) -> &BatcherConfig {
+        &self.config
+    }
+
+    /// Adds a batch to the open channel, or to a new channel opened in the L1 block `l1_head`.
+    /// Full channels are closed and split into sub-channels.
+    pub fn add_batch(&mut self, batch: &Batch, l1_head: u64) -> Result<()> {
+        if self.current.is_none() {
+            self.open_channel(l1_head);
+        }
+        let (channel, _) = self.current.as_mut().expect("channel just opened");
+        if channel.add_batch(batch)? {
+            return Ok(());
+        }
+
+        debug!(target: "hera::batcher", channel = %channel.id(), "Channel full, splitting");
+        self.close_channel()?;
+        self.open_channel(l1_head);
+        let (channel, _) = self.current.as_mut().expect("channel just opened");
+        channel.add_batch(batch)?;
+        Ok(())
+    }
+
+    /// Closes the open channel if it has been open for the maximum channel duration at the L1
+    /// block `l1_head`.
+    pub fn on_l1_head(&mut self, l1_head: u64) -> Result<()> {
+        let duration = self.config.max_channel_duration;
+        let timed_out = self
+            .current
+            .as_ref()
+            .is_some_and(|(channel, _)| duration > 0 && l1_head >= channel.opened_at() + duration);
+        if timed_out {
+            self.close_channel()?;
+        }
+        Ok(())
+    }
+
+    /// Updates the route of the channels opened from now on from the latest L1 fees, when
+    /// switching automatically. The open channel keeps its route.
+    pub fn update_fees(&mut self, fees: L1Fees) {
+        if let Some(switcher) = &mut self.switcher {
+            self.config.data_availability = switcher.observe(fees);
+        }
+    }
+
+    /// Closes the open channel, if any, so that its data can be posted.
+    pub fn flush(&mut self) -> Result<()> {
+        self.close_channel()
+    }
+
+    /// Returns the data of the next batcher transaction to post, if any.
+    pub fn next_tx(&mut self) -> Option<TxData> {
+        self.pending.pop_front()
+    }
+
+    /// Returns the number of batcher transactions ready to be posted.
+    pub fn pending_txs(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn open_channel(&mut self, l1_head: u64) {
+        let da = self.config.data_availability;
+        let id = self.next_channel_id();
+        let channel =
+            ChannelOut::new(id, self.config.compression, self.config.max_channel_size(da), l1_head);
+        self.current = Some((channel, da));
+    }
+
+    fn close_channel(&mut self) -> Result<()> {
+        let Some((channel, da)) = self.current.take() else { return Ok(()) };
+        if channel.is_empty() {
+            return Ok(());
+        }
+        let frames = channel.into_frames(self.config.frame_size(da))?;
+        let frames_per_tx = match da {
+            DataAvailability::Calldata => 1,
+            DataAvailability::Blobs => self.config.max_blobs_per_tx,
+        };
+        for frames in frames.chunks(frames_per_tx) {
+            self.pending.push_back(TxData { frames: frames.to_vec(), data_availability: da });
+        }
+        Ok(())
+    }
+
+    /// Derives a fresh channel ID from the current time and the number of channels opened.
+    fn next_channel_id(&mut self) -> ChannelId {
+        self.channels_opened += 1;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+        let hash =
+            keccak256([now.to_be_bytes(), u128::from(self.channels_opened).to_be_bytes()].concat());
+        ChannelId(hash[..16].try_into().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::B256;
+
+    use super::*;
+    use crate::{
+        blobs::decode_blob_data,
+        protocol::{
+            batch::SingleBatch,
+            channel::{decompress_channel, BatchReader, Channel, MAX_RLP_BYTES_PER_CHANNEL_FJORD},
+            BlockInfo,
+        },
+    };
+
+    /// A batch of `len` bytes of incompressible transaction data.
+    fn batch(number: u64, len: usize) -> Batch {
+        let mut data = Vec::with_capacity(len + 32);
+        let mut hash = B256::left_padding_from(&number.to_be_bytes());
+        while data.len() < len {
+            hash = keccak256(hash);
+            data.extend_from_slice(hash.as_slice());
+        }
+        data.truncate(len);
+        Batch::Single(SingleBatch {
+            epoch_num: number,
+            transactions: vec![data.into()],
+            ..Default::default()
+        })
+    }
+
+    /// Posts the pending transactions and derives their batches back, channel by channel.
+    fn derive(manager: &mut ChannelManager) -> Vec<Vec<Batch>> {
+        let mut channels: Vec<Channel> = Vec::new();
+        while let Some(tx) = manager.next_tx() {
+            let data = match tx.data_availability {
+                DataAvailability::Calldata => vec![tx.calldata()],
+                DataAvailability::Blobs => {
+                    tx.blobs().unwrap().iter().map(|blob| decode_blob_data(blob).unwrap()).collect()
+                }
+            };
+            for frame in data.iter().flat_map(|data| Frame::parse_frames(data).unwrap()) {
+                if channels.last().map_or(true, |channel| channel.id() != frame.id) {
+                    channels.push(Channel::new(frame.id, BlockInfo::default()));
+                }
+                channels.last_mut().unwrap().add_frame(frame, BlockInfo::default()).unwrap();
+            }
+        }
+        channels
+            .iter()
+            .map(|channel| {
+                let data = channel.frame_data().expect("channel posted entirely");
+                let data =
+                    decompress_channel(&data, MAX_RLP_BYTES_PER_CHANNEL_FJORD, true).unwrap();
+                BatchReader::new(&data).collect::<Result<_>>().unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn calldata_channels_fit_a_frame() {
+        let config = BatcherConfig { target_frame_size: 2_000, ..Default::default() };
+        let mut manager = ChannelManager::new(config);
+        let batches: Vec<_> = (0..5).map(|number| batch(number, 700)).collect();
+        for batch in &batches {
+            manager.add_batch(batch, 1).unwrap();
+        }
+        manager.flush().unwrap();
+
+        // Two batches fit under the frame size, so the five batches take three channels.
+        assert_eq!(manager.pending_txs(), 3);
+        let channels = derive(&mut manager);
+        assert_eq!(channels.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 1]);
+        assert_eq!(channels.concat(), batches);
+    }
+
+    #[test]
+    fn blob_channels_split_into_sub_channels() {
+        let config = BatcherConfig {
+            data_availability: DataAvailability::Blobs,
+            max_blobs_per_tx: 2,
+            ..Default::default()
+        };
+        let mut manager = ChannelManager::new(config);
+        let batches: Vec<_> = (0..6).map(|number| batch(number, 60_000)).collect();
+        for batch in &batches {
+            manager.add_batch(batch, 1).unwrap();
+        }
+        manager.flush().unwrap();
+
+        let mut txs = Vec::new();
+        while let Some(tx) = manager.next_tx() {
+            assert!(tx.frames.len() <= 2);
+            assert!(tx.frames.iter().all(|frame| frame.id == tx.frames[0].id));
+            txs.push(tx);
+        }
+        // Every sub-channel is posted by a single transaction, closing it.
+        assert!(txs.iter().all(|tx| tx.frames.last().unwrap().is_last));
+        assert!(txs.len() > 1);
+
+        manager.pending.extend(txs);
+        assert_eq!(derive(&mut manager).concat(), batches);
+    }
+
+    #[test]
+    fn closes_channels_after_the_maximum_duration() {
+        let config = BatcherConfig { max_channel_duration: 3, ..Default::default() };
+        let mut manager = ChannelManager::new(config);
+        manager.add_batch(&batch(0, 10), 10).unwrap();
+        manager.on_l1_head(12).unwrap();
+        assert_eq!(manager.pending_txs(), 0);
+        manager.on_l1_head(13).unwrap();
+        assert_eq!(manager.pending_txs(), 1);
+
+        // Nothing is posted for an empty channel.
+        manager.flush().unwrap();
+        assert_eq!(derive(&mut manager), [vec![batch(0, 10)]]);
+        assert_eq!(manager.pending_txs(), 0);
+    }
+}
@@ -0,0 +1,618 @@
+//! Management of the batcher's L1 transactions.
+//!
+//! Every batcher transaction takes the next nonce of the batcher account and is resubmitted
+//! with bumped fees when it is not included within the resubmission timeout. Replacements must
+//! raise every fee cap by the price bump of the L1 mempool: 10% for regular transactions, 100%
+//! for blob transactions. A transaction still not included after the maximum number of bumps is
+//! cancelled, replaced by an empty transaction, and its frames handed back to be posted again.
+//!
+//! Included transactions are followed until they are buried under the confirmation depth. One
+//! that an L1 reorg drops from the canonical chain is resubmitted with its nonce, so that its
+//! frames are not lost.
+//!
+//! In-flight transactions are persisted before being sent, so that a restarted batcher resumes
+//! tracking them instead of reusing their nonces.
+
+use std::{collections::BTreeMap, fmt, sync::Arc, time::Duration};
+
+use alloy_primitives::{Bytes, B256};
+use async_trait::async_trait;
+use eyre::{bail, ensure, Result, WrapErr};
+use metrics::{counter, gauge};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::{
+    batcher::{DataAvailability, TxData},
+    protocol::Frame,
+    storage::Storage,
+};
+
+/// The table of in-flight batcher transactions, keyed by big-endian nonce, holding the JSON
+/// encoded [`InFlightTx`].
+pub(crate) const BATCHER_TXS: &str = "batcher_txs";
+
+/// Default time after which a transaction that is not included is resubmitted with bumped fees.
+pub const DEFAULT_RESUBMISSION_TIMEOUT: Duration = Duration::from_secs(48);
+
+/// Default number of fee bumps after which a transaction is cancelled.
+pub const DEFAULT_MAX_FEE_BUMPS: u32 = 10;
+
+/// Default number of L1 blocks built on top of a transaction before it is considered final.
+pub const DEFAULT_CONFIRMATION_DEPTH: u64 = 10;
+
+/// The minimum fee increase, in percent, of a replacement transaction.
+pub const FEE_BUMP_PERCENT: u128 = 10;
+
+/// The minimum fee increase, in percent, of a replacement blob transaction.
+pub const BLOB_FEE_BUMP_PERCENT: u128 = 100;
+
+/// The fee caps of a batcher transaction, in wei.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxFees {
+    /// The EIP-1559 fee cap per gas.
+    pub max_fee_per_gas: u128,
+    /// The EIP-1559 priority fee per gas.
+    pub max_priority_fee_per_gas: u128,
+    /// The EIP-4844 fee cap per blob gas, set for blob transactions only.
+    pub max_fee_per_blob_gas: Option<u128>,
+}
+
+impl TxFees {
+    /// Returns the fees of a replacement: every cap raised by the price bump, and at least the
+    /// `market` fees.
+    pub fn bump(&self, market: &Self) -> Self {
+        let percent = if self.max_fee_per_blob_gas.is_some() {
+            BLOB_FEE_BUMP_PERCENT
+        } else {
+            FEE_BUMP_PERCENT
+        };
+        let bump = |fee: u128, market: u128| {
+            fee.saturating_mul(100 + percent).div_ceil(100).max(fee + 1).max(market)
+        };
+        Self {
+            max_fee_per_gas: bump(self.max_fee_per_gas, market.max_fee_per_gas),
+            max_priority_fee_per_gas: bump(
+                self.max_priority_fee_per_gas,
+                market.max_priority_fee_per_gas,
+            ),
+            max_fee_per_blob_gas: self
+                .max_fee_per_blob_gas
+                .map(|fee| bump(fee, market.max_fee_per_blob_gas.unwrap_or_default())),
+        }
+    }
+}
+
+/// A batcher transaction to sign and broadcast.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatcherTx {
+    /// The nonce of the transaction.
+    pub nonce: u64,
+    /// The fee caps of the transaction.
+    pub fees: TxFees,
+    /// The frames posted, or `None` for a cancellation.
+    pub data: Option<TxData>,
+}
+
+/// Signs batcher transactions and follows them on L1.
+#[async_trait]
+pub trait L1TxSender: fmt::Debug + Send + Sync {
+    /// Returns the number of transactions of the batcher account included on L1.
+    async fn confirmed_nonce(&self) -> Result<u64>;
+
+    /// Returns the fees a new transaction should pay, with a blob fee cap if `blobs` is set.
+    async fn suggested_fees(&self, blobs: bool) -> Result<TxFees>;
+
+    /// Signs and broadcasts `tx`, returning its hash. A cancellation is an empty transaction
+    /// from the batcher to itself, carrying a single empty blob if it replaces a blob
+    /// transaction, as the L1 mempool only replaces blob transactions with blob transactions.
+    async fn send(&self, tx: &BatcherTx) -> Result<B256>;
+
+    /// Returns the number of the L1 block the transaction `hash` is included in, if any.
+    async fn inclusion(&self, hash: B256) -> Result<Option<u64>>;
+
+    /// Returns the number of the L1 head.
+    async fn l1_head(&self) -> Result<u64>;
+}
+
+/// The inclusion of a batcher transaction on L1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inclusion {
+    /// The hash of the included submission.
+    pub hash: B256,
+    /// The L1 block the transaction is included in.
+    pub l1_block: u64,
+}
+
+/// A batcher transaction sent but not yet final.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InFlightTx {
+    /// The nonce of the transaction.
+    pub nonce: u64,
+    /// The route of the frames.
+    pub data_availability: DataAvailability,
+    /// The frames, as batcher transaction calldata.
+    pub data: Bytes,
+    /// The fee caps of the latest submission.
+    pub fees: TxFees,
+    /// The hashes of every submission posting the frames.
+    pub hashes: Vec<B256>,
+    /// The hashes of every cancellation.
+    pub cancellations: Vec<B256>,
+    /// The Unix timestamp of the latest submission, in seconds.
+    pub sent_at: u64,
+    /// The number of fee bumps so far.
+    pub bumps: u32,
+    /// Where the transaction is included, until it is final.
+    #[serde(default)]
+    pub inclusion: Option<Inclusion>,
+}
+
+impl InFlightTx {
+    /// Returns true if the transaction is being cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        !self.cancellations.is_empty()
+    }
+
+    /// Returns the frames posted by the transaction.
+    pub fn tx_data(&self) -> Result<TxData> {
+        Ok(TxData {
+            frames: Frame::parse_frames(&self.data)?,
+            data_availability: self.data_availability,
+        })
+    }
+}
+
+/// What happened to an in-flight transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxOutcome {
+    /// The frames were included on L1 and buried under the confirmation depth.
+    Confirmed {
+        /// The nonce of the transaction.
+        nonce: u64,
+        /// The hash of the included submission.
+        hash: B256,
+        /// The L1 block the transaction is included in.
+        l1_block: u64,
+    },
+    /// An L1 reorg dropped the transaction from the canonical chain. It was resubmitted with its
+    /// nonce.
+    Orphaned {
+        /// The nonce of the transaction.
+        nonce: u64,
+        /// The hash of the orphaned submission.
+        hash: B256,
+        /// The L1 block the transaction was included in.
+        l1_block: u64,
+    },
+    /// The nonce was taken by a cancellation or another transaction of the batcher account. The
+    /// frames must be posted again.
+    Cancelled {
+        /// The nonce of the transaction.
+        nonce: u64,
+        /// The frames that were not posted.
+        data: TxData,
+    },
+}
+
+/// Settings of the [`TxManager`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxManagerConfig {
+    /// Time after which a transaction that is not included is resubmitted with bumped fees.
+    pub resubmission_timeout: Duration,
+    /// Number of fee bumps after which a transaction is cancelled.
+    pub max_fee_bumps: u32,
+    /// Number of L1 blocks built on top of a transaction before it is considered final.
+    pub confirmation_depth: u64,
+}
+
+impl Default for TxManagerConfig {
+    fn default() -> Self {
+        Self {
+            resubmission_timeout: DEFAULT_RESUBMISSION_TIMEOUT,
+            max_fee_bumps: DEFAULT_MAX_FEE_BUMPS,
+            confirmation_depth: DEFAULT_CONFIRMATION_DEPTH,
+        }
+    }
+}
+
+/// Sends batcher transactions, tracking nonces and replacing stuck transactions.
+#[derive(Debug)]
+pub struct TxManager<S> {
+    sender: S,
+    storage: Arc<dyn Storage>,
+    config: TxManagerConfig,
+    in_flight: BTreeMap<u64, InFlightTx>,
+    next_nonce: Option<u64>,
+}
+
+impl<S: L1TxSender> TxManager<S> {
+    /// Creates a manager sending through `sender`, resuming the in-flight transactions persisted
+    /// in `storage`.
+    pub fn open(sender: S, storage: Arc<dyn Storage>, config: TxManagerConfig) -> Result<Self> {
+        let mut in_flight = BTreeMap::new();
+        let mut key = u64::MAX;
+        while let Some((found, value)) = storage.floor(BATCHER_TXS, &key.to_be_bytes())? {
+            let tx: InFlightTx = serde_json::from_slice(&value)
+                .wrap_err("failed to decode in-flight batcher transaction")?;
+            ensure!(
+                found == tx.nonce.to_be_bytes(),
+                "batcher transaction {} is malformed",
+                tx.nonce
+            );
+            let previous = tx.nonce.checked_sub(1);
+            in_flight.insert(tx.nonce, tx);
+            match previous {
+                Some(previous) => key = previous,
+                None => break,
+            }
+        }
+        if !in_flight.is_empty() {
+            info!(target: "hera::batcher", txs = in_flight.len(), "Resuming in-flight transactions");
+        }
+        let next_nonce = in_flight.keys().next_back().map(|nonce| nonce + 1);
+        gauge!("hera_batcher_txs_in_flight").set(in_flight.len() as f64);
+        Ok(Self { sender, storage, config, in_flight, next_nonce })
+    }
+
+    /// Returns the in-flight transactions, by nonce.
+    pub const fn in_flight(&self) -> &BTreeMap<u64, InFlightTx> {
+        &self.in_flight
+    }
+
+    /// Sends `data` with the next nonce at the Unix timestamp `now`, in seconds, returning the
+    /// hash of the transaction.
+    pub async fn submit(&mut self, data: TxData, now: u64) -> Result<B256> {
+        let confirmed = self.sender.confirmed_nonce().await?;
+        let nonce = self.next_nonce.map_or(confirmed, |next| next.max(confirmed));
+        let blobs = data.data_availability == DataAvailability::Blobs;
+        let fees = self.sender.suggested_fees(blobs).await?;
+        let mut tx = InFlightTx {
+            nonce,
+            data_availability: data.data_availability,
+            data: data.calldata(),
+            fees,
+            hashes: Vec::new(),
+            cancellations: Vec::new(),
+            sent_at: now,
+            bumps: 0,
+            inclusion: None,
+        };
+        // Persist first, so that a crash while sending does not lose track of the nonce.
+        self.persist(&tx)?;
+        let hash = self.sender.send(&BatcherTx { nonce, fees, data: Some(data) }).await;
+        let hash = match hash {
+            Ok(hash) => hash,
+            Err(err) => {
+                self.storage.remove(BATCHER_TXS, &[&nonce.to_be_bytes()])?;
+                return Err(err.wrap_err(format!("failed to send batcher transaction {nonce}")));
+            }
+        };
+        tx.hashes.push(hash);
+        self.persist(&tx)?;
+        self.in_flight.insert(nonce, tx);
+        self.next_nonce = Some(nonce + 1);
+        gauge!("hera_batcher_txs_in_flight").set(self.in_flight.len() as f64);
+        gauge!("hera_batcher_nonce").set(nonce as f64);
+        Ok(hash)
+    }
+
+    /// Replaces the in-flight transaction `nonce` with an empty one at the Unix timestamp
+    /// `now`, in seconds. Its frames are handed back by [`Self::poll`] once the cancellation is
+    /// included.
+    pub async fn cancel(&mut self, nonce: u64, now: u64) -> Result<B256> {
+        let Some(mut tx) = self.in_flight.get(&nonce).cloned() else {
+            bail!("no in-flight batcher transaction with nonce {nonce}")
+        };
+        let market = self.sender.suggested_fees(tx.fees.max_fee_per_blob_gas.is_some()).await?;
+        tx.fees = tx.fees.bump(&market);
+        let hash = self
+            .sender
+            .send(&BatcherTx { nonce, fees: tx.fees, data: None })
+            .await
+            .wrap_err_with(|| format!("failed to cancel batcher transaction {nonce}"))?;
+        warn!(target: "hera::batcher", nonce, %hash, "Cancelling batcher transaction");
+        counter!("hera_batcher_cancellations_total").increment(1);
+        tx.cancellations.push(hash);
+        tx.sent_at = now;
+        self.persist(&tx)?;
+        self.in_flight.insert(nonce, tx);
+        Ok(hash)
+    }
+
+    /// Follows the transactions whose nonce was used on L1 until they are final, resubmits the
+    /// ones orphaned by L1 reorgs, and the ones not included within the resubmission timeout as
+    /// of the Unix timestamp `now`, in seconds.
+    pub async fn poll(&mut self, now: u64) -> Result<Vec<TxOutcome>> {
+        let confirmed = self.sender.confirmed_nonce().await?;
+        let head = self.sender.l1_head().await?;
+        let mut outcomes = Vec::new();
+        let settled: Vec<_> = self
+            .in_flight
+            .values()
+            .filter(|tx| tx.nonce < confirmed || tx.inclusion.is_some())
+            .map(|tx| tx.nonce)
+            .collect();
+        for nonce in settled {
+            if let Some(outcome) = self.settle(nonce, confirmed, head, now).await? {
+                outcomes.push(outcome);
+            }
+        }
+
+        let timeout = self.config.resubmission_timeout.as_secs();
+        let stuck: Vec<_> = self
+            .in_flight
+            .values()
+            .filter(|tx| tx.inclusion.is_none() && now.saturating_sub(tx.sent_at) >= timeout)
+            .map(|tx| tx.nonce)
+            .collect();
+        for nonce in stuck {
+            let tx = &self.in_flight[&nonce];
+            if tx.is_cancelled() || tx.bumps < self.config.max_fee_bumps {
+                self.resubmit(nonce, now).await?;
+            } else {
+                self.cancel(nonce, now).await?;
+            }
+        }
+        gauge!("hera_batcher_txs_in_flight").set(self.in_flight.len() as f64);
+        Ok(outcomes)
+    }
+
+    /// Resends the transaction `nonce`, or its cancellation, with bumped fees.
+    async fn resubmit(&mut self, nonce: u64, now: u64) -> Result<()> {
+        let mut tx = self.in_flight[&nonce].clone();
+        let market = self.sender.suggested_fees(tx.fees.max_fee_per_blob_gas.is_some()).await?;
+        tx.fees = tx.fees.bump(&market);
+        let data = if tx.is_cancelled() { None } else { Some(tx.tx_data()?) };
+        let cancellation = data.is_none();
+        let hash = match self.sender.send(&BatcherTx { nonce, fees: tx.fees, data }).await {
+            Ok(hash) => hash,
+            Err(err) => {
+                // The transaction may have been included meanwhile, settled on the next poll.
+                warn!(target: "hera::batcher", nonce, %err, "Failed to resubmit batcher transaction");
+                return Ok(());
+            }
+        };
+        info!(
+            target: "hera::batcher",
+            nonce,
+            %hash,
+            max_fee_per_gas = tx.fees.max_fee_per_gas,
+            "Resubmitted batcher transaction with bumped fees"
+        );
+        counter!("hera_batcher_fee_bumps_total").increment(1);
+        if cancellation {
+            tx.cancellations.push(hash);
+        } else {
+            tx.hashes.push(hash);
+        }
+        tx.bumps += 1;
+        tx.sent_at = now;
+        self.persist(&tx)?;
+        self.in_flight.insert(nonce, tx);
+        Ok(())
+    }
+
+    /// Follows the transaction `nonce`, included or with its nonce used on L1, given the
+    /// `confirmed` nonce of the batcher account and the L1 `head`.
+    async fn settle(
+        &mut self,
+        nonce: u64,
+        confirmed: u64,
+        head: u64,
+        now: u64,
+    ) -> Result<Option<TxOutcome>> {
+        let mut tx = self.in_flight[&nonce].clone();
+        let inclusion = self.included(&tx).await?;
+        match (inclusion, tx.inclusion) {
+            (Some(inclusion), _) if head >= inclusion.l1_block + self.config.confirmation_depth => {
+                self.remove(nonce)?;
+                counter!("hera_batcher_txs_total", "outcome" => "confirmed").increment(1);
+                let Inclusion { hash, l1_block } = inclusion;
+                Ok(Some(TxOutcome::Confirmed { nonce, hash, l1_block }))
+            }
+            (Some(inclusion), previous) => {
+                if previous != Some(inclusion) {
+                    debug!(
+                        target: "hera::batcher",
+                        nonce,
+                        l1_block = inclusion.l1_block,
+                        "Batcher transaction included"
+                    );
+                    tx.inclusion = Some(inclusion);
+                    self.persist(&tx)?;
+                    self.in_flight.insert(nonce, tx);
+                }
+                Ok(None)
+            }
+            (None, _) if nonce < confirmed => {
+                self.remove(nonce)?;
+                warn!(target: "hera::batcher", nonce, "Batcher transaction was not included");
+                counter!("hera_batcher_txs_total", "outcome" => "cancelled").increment(1);
+                Ok(Some(TxOutcome::Cancelled { nonce, data: tx.tx_data()? }))
+            }
+            (None, Some(Inclusion { hash, l1_block })) => {
+                warn!(
+                    target: "hera::batcher",
+                    nonce,
+                    %hash,
+                    l1_block,
+                    "Batcher transaction orphaned by an L1 reorg, resubmitting"
+                );
+                counter!("hera_batcher_txs_total", "outcome" => "orphaned").increment(1);
+                tx.inclusion = None;
+                self.persist(&tx)?;
+                self.in_flight.insert(nonce, tx);
+                self.resubmit(nonce, now).await?;
+                Ok(Some(TxOutcome::Orphaned { nonce, hash, l1_block }))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Returns where a submission of the frames of `tx` is included, if any.
+    async fn included(&self, tx: &InFlightTx) -> Result<Option<Inclusion>> {
+        for &hash in tx.hashes.iter().rev() {
+            if let Some(l1_block) = self.sender.inclusion(hash).await? {
+                return Ok(Some(Inclusion { hash, l1_block }));
+            }
+        }
+        Ok(None)
+    }
+
+    fn remove(&mut self, nonce: u64) -> Result<()> {
+        self.in_flight.remove(&nonce);
+        self.storage.remove(BATCHER_TXS, &[&nonce.to_be_bytes()])
+    }
+
+    fn persist(&self, tx: &InFlightTx) -> Result<()> {
+        let value = serde_json::to_vec(tx)?;
+        self.storage
+            .put(BATCHER_TXS, &[(&tx.nonce.to_be_bytes(), &value)])
+            .wrap_err_with(|| format!("failed to persist batcher transaction {}", tx.nonce))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{protocol::ChannelId, storage::MemoryStorage};
+
+    /// An L1 including the transactions marked so, with a constant fee market.
+    #[derive(Debug, Default)]
+    struct MockSender {
+        sent: Mutex<Vec<BatcherTx>>,
+        confirmed: Mutex<u64>,
+        included: Mutex<BTreeMap<B256, u64>>,
+        head: Mutex<u64>,
+    }
+
+    #[async_trait]
+    impl L1TxSender for Arc<MockSender> {
+        async fn confirmed_nonce(&self) -> Result<u64> {
+            Ok(*self.confirmed.lock().unwrap())
+        }
+
+        async fn suggested_fees(&self, blobs: bool) -> Result<TxFees> {
+            Ok(TxFees {
+                max_fee_per_gas: 100,
+                max_priority_fee_per_gas: 10,
+                max_fee_per_blob_gas: blobs.then_some(50),
+            })
+        }
+
+        async fn send(&self, tx: &BatcherTx) -> Result<B256> {
+            let mut sent = self.sent.lock().unwrap();
+            sent.push(tx.clone());
+            Ok(B256::with_last_byte(sent.len() as u8))
+        }
+
+        async fn inclusion(&self, hash: B256) -> Result<Option<u64>> {
+            Ok(self.included.lock().unwrap().get(&hash).copied())
+        }
+
+        async fn l1_head(&self) -> Result<u64> {
+            Ok(*self.head.lock().unwrap())
+        }
+    }
+
+    fn tx_data(byte: u8) -> TxData {
+        let frame = Frame {
+            id: ChannelId([byte; 16]),
+            number: 0,
+            data: vec![byte; 10].into(),
+            is_last: true,
+        };
+        TxData { frames: vec![frame], data_availability: DataAvailability::Calldata }
+    }
+
+    #[test]
+    fn bumps_every_fee_cap() {
+        let fees =
+            TxFees { max_fee_per_gas: 100, max_priority_fee_per_gas: 1, ..Default::default() };
+        let market = TxFees { max_fee_per_gas: 200, ..Default::default() };
+        let bumped = fees.bump(&market);
+        assert_eq!((bumped.max_fee_per_gas, bumped.max_priority_fee_per_gas), (200, 2));
+
+        let blob = TxFees { max_fee_per_blob_gas: Some(7), ..fees };
+        assert_eq!(blob.bump(&TxFees::default()).max_fee_per_blob_gas, Some(14));
+        assert_eq!(blob.bump(&TxFees::default()).max_fee_per_gas, 200);
+    }
+
+    #[tokio::test]
+    async fn replaces_stuck_transactions_and_resumes_after_restart() {
+        let sender = Arc::new(MockSender::default());
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let config = TxManagerConfig {
+            resubmission_timeout: Duration::from_secs(10),
+            max_fee_bumps: 1,
+            confirmation_depth: 0,
+        };
+        let mut manager = TxManager::open(sender.clone(), storage.clone(), config).unwrap();
+        manager.submit(tx_data(1), 0).await.unwrap();
+        manager.submit(tx_data(2), 0).await.unwrap();
+        assert_eq!(manager.in_flight().keys().copied().collect::<Vec<_>>(), [0, 1]);
+
+        // Both are bumped once, then cancelled.
+        assert!(manager.poll(10).await.unwrap().is_empty());
+        assert_eq!(sender.sent.lock().unwrap()[2].fees.max_fee_per_gas, 110);
+        manager.poll(20).await.unwrap();
+        let sent = sender.sent.lock().unwrap().clone();
+        assert_eq!(sent.len(), 6);
+        assert!(sent[4..].iter().all(|tx| tx.data.is_none()));
+
+        // A restarted manager resumes the in-flight transactions and their nonces.
+        let mut manager = TxManager::open(sender.clone(), storage, config).unwrap();
+        assert!(manager.in_flight().values().all(InFlightTx::is_cancelled));
+        assert_eq!(manager.submit(tx_data(3), 20).await.unwrap(), B256::with_last_byte(7));
+        assert_eq!(sender.sent.lock().unwrap()[6].nonce, 2);
+
+        // The first transaction got in before its cancellation, the second did not.
+        sender.included.lock().unwrap().insert(B256::with_last_byte(3), 5);
+        *sender.confirmed.lock().unwrap() = 2;
+        *sender.head.lock().unwrap() = 5;
+        let outcomes = manager.poll(21).await.unwrap();
+        assert_eq!(
+            outcomes,
+            [
+                TxOutcome::Confirmed { nonce: 0, hash: B256::with_last_byte(3), l1_block: 5 },
+                TxOutcome::Cancelled { nonce: 1, data: tx_data(2) },
+            ]
+        );
+        assert_eq!(manager.in_flight().keys().copied().collect::<Vec<_>>(), [2]);
+    }
+
+    #[tokio::test]
+    async fn resubmits_transactions_orphaned_by_reorgs() {
+        let sender = Arc::new(MockSender::default());
+        let config = TxManagerConfig { confirmation_depth: 3, ..Default::default() };
+        let storage = Arc::new(MemoryStorage::new());
+        let mut manager = TxManager::open(sender.clone(), storage, config).unwrap();
+        let hash = manager.submit(tx_data(1), 0).await.unwrap();
+
+        // Included, but not final yet.
+        sender.included.lock().unwrap().insert(hash, 5);
+        *sender.confirmed.lock().unwrap() = 1;
+        *sender.head.lock().unwrap() = 6;
+        assert!(manager.poll(1).await.unwrap().is_empty());
+        assert_eq!(manager.in_flight()[&0].inclusion, Some(Inclusion { hash, l1_block: 5 }));
+
+        // A reorg drops the block, the frames are posted again with the same nonce.
+        sender.included.lock().unwrap().clear();
+        *sender.confirmed.lock().unwrap() = 0;
+        let outcomes = manager.poll(2).await.unwrap();
+        assert_eq!(outcomes, [TxOutcome::Orphaned { nonce: 0, hash, l1_block: 5 }]);
+        let resubmitted = sender.sent.lock().unwrap()[1].clone();
+        assert_eq!((resubmitted.nonce, resubmitted.data), (0, Some(tx_data(1))));
+
+        let hash = B256::with_last_byte(2);
+        sender.included.lock().unwrap().insert(hash, 7);
+        *sender.confirmed.lock().unwrap() = 1;
+        *sender.head.lock().unwrap() = 10;
+        let outcomes = manager.poll(3).await.unwrap();
+        assert_eq!(outcomes, [TxOutcome::Confirmed { nonce: 0, hash, l1_block: 7 }]);
+        assert!(manager.in_flight().is_empty());
+    }
+}
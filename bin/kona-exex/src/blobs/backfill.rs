@@ -0,0 +1,122 @@
+//! Backfill of the blob cache from a blob archiver.
+
+use std::sync::Arc;
+
+use alloy_primitives::Address;
+use eyre::{bail, Result, WrapErr};
+use metrics::{counter, gauge};
+use tracing::{debug, info};
+
+use crate::{
+    blobs::{BlobCache, BlobProvider, IndexedBlobHash},
+    l1::ChainProvider,
+};
+
+/// The name of the backfill progress checkpoint in the [`BlobCache`].
+const CHECKPOINT: &str = "blob_backfill";
+
+/// Fills the [`BlobCache`] with the blobs of batcher transactions in a range of older L1 blocks,
+/// fetched from a blob archiver.
+///
+/// Beacon nodes prune blobs after about 18 days, so replaying derivation from further back needs
+/// an archiver. Backfilling ahead of the replay keeps the archiver out of the derivation hot path.
+/// Progress is checkpointed in the cache after every block, and a restarted backfill resumes
+/// where the previous one stopped.
+///
+/// Every blob transaction sent to the batch inbox is backfilled, regardless of its sender, as the
+/// batcher may have changed over the range. Sidecars are checked against the versioned hashes of
+/// their transaction before being stored.
+#[derive(Debug)]
+pub struct BlobBackfill {
+    l1: Arc<dyn ChainProvider>,
+    archiver: Arc<dyn BlobProvider>,
+    cache: Arc<BlobCache>,
+    batch_inbox: Address,
+}
+
+impl BlobBackfill {
+    /// Creates a backfill of the blobs sent to `batch_inbox`, reading L1 blocks from `l1` and
+    /// blobs from `archiver` into `cache`.
+    pub fn new(
+        l1: Arc<dyn ChainProvider>,
+        archiver: Arc<dyn BlobProvider>,
+        cache: Arc<BlobCache>,
+        batch_inbox: Address,
+    ) -> Self {
+        Self { l1, archiver, cache, batch_inbox }
+    }
+
+    /// Backfills the L1 blocks from `from` to `to` inclusive, resuming after the last block
+    /// backfilled if the range was started before. Returns the number of blobs stored.
+    pub async fn run(&self, from: u64, to: u64) -> Result<u64> {
+        let start = self.cache.checkpoint(CHECKPOINT)?.map_or(from, |next| next.max(from));
+        if start > from {
+            info!(target: "hera::blobs", from = start, to, "Resuming blob backfill");
+        } else {
+            info!(target: "hera::blobs", from, to, "Starting blob backfill");
+        }
+
+        let mut stored = 0;
+        for number in start..=to {
+            stored += self
+                .backfill_block(number)
+                .await
+                .wrap_err_with(|| format!("failed to backfill blobs of L1 block {number}"))?;
+            self.cache.set_checkpoint(CHECKPOINT, number + 1)?;
+            gauge!("hera_blob_backfill_l1_block").set(number as f64);
+        }
+        info!(target: "hera::blobs", to, stored, "Finished blob backfill");
+        Ok(stored)
+    }
+
+    /// Backfills the batcher blobs of a single L1 block missing from the cache.
+    async fn backfill_block(&self, number: u64) -> Result<u64> {
+        let block = self.l1.block_info_by_number(number).await?;
+        let transactions = self.l1.transactions_by_hash(block.hash).await?;
+
+        let mut hashes = Vec::new();
+        let mut blob_index = 0;
+        for tx in transactions {
+            let first_blob = blob_index;
+            blob_index += tx.blob_versioned_hashes.len() as u64;
+            if tx.to != Some(self.batch_inbox) {
+                continue;
+            }
+            hashes.extend(
+                tx.blob_versioned_hashes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, hash)| IndexedBlobHash { index: first_blob + i as u64, hash: *hash }),
+            );
+        }
+        if hashes.is_empty() {
+            return Ok(0);
+        }
+
+        let indices = hashes.iter().map(|hash| hash.index).collect::<Vec<_>>();
+        let missing = self.cache.missing(block.hash, &indices)?;
+        hashes.retain(|hash| missing.contains(&hash.index));
+        if hashes.is_empty() {
+            return Ok(0);
+        }
+
+        let mut sidecars = self.archiver.blob_sidecars(&block, &missing).await?;
+        let mut verified = Vec::with_capacity(hashes.len());
+        for expected in &hashes {
+            let Some(position) = sidecars.iter().position(|s| s.index == expected.index) else {
+                bail!("blob {} is missing from the archiver", expected.index);
+            };
+            let sidecar = sidecars.swap_remove(position);
+            if sidecar.versioned_hash() != expected.hash {
+                bail!("archived blob {} has a mismatching commitment", expected.index);
+            }
+            verified.push(sidecar);
+        }
+        self.cache.insert(block.hash, &verified)?;
+
+        let stored = hashes.len() as u64;
+        counter!("hera_blob_backfill_blobs_total").increment(stored);
+        debug!(target: "hera::blobs", block = %block, blobs = stored, "Backfilled blobs");
+        Ok(stored)
+    }
+}
@@ -0,0 +1,192 @@
+//! A [`BlobProvider`] backed by the beacon node HTTP API.
+
+use async_trait::async_trait;
+use eyre::{Result, WrapErr};
+use reqwest::Client;
+use serde::{de::DeserializeOwned, Deserialize};
+use tokio::sync::OnceCell;
+use tracing::info;
+use url::Url;
+
+use crate::{
+    blobs::{deserialize_u64_string, BlobProvider, BlobSidecar, SlotClock},
+    protocol::BlockInfo,
+};
+
+/// Fetches blob sidecars from a beacon node.
+///
+/// The beacon genesis time and slot duration are fetched once, on first use, and slots are then
+/// computed locally from L1 block timestamps.
+#[derive(Debug)]
+pub struct BeaconClient {
+    client: Client,
+    url: Url,
+    clock: OnceCell<SlotClock>,
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct Genesis {
+    #[serde(deserialize_with = "deserialize_u64_string")]
+    genesis_time: u64,
+}
+
+#[derive(Deserialize)]
+struct Spec {
+    #[serde(rename = "SECONDS_PER_SLOT", deserialize_with = "deserialize_u64_string")]
+    seconds_per_slot: u64,
+}
+
+impl BeaconClient {
+    /// Creates a client of the beacon node at `url`.
+    ///
+    /// The API paths are resolved under the path of `url`, with or without a trailing slash.
+    pub fn new(mut url: Url) -> Self {
+        if !url.path().ends_with('/') {
+            url.set_path(&format!("{}/", url.path()));
+        }
+        Self { client: Client::new(), url, clock: OnceCell::new() }
+    }
+
+    /// Sends requests through `client`, e.g. shared from
+    /// [`HttpClients`](crate::endpoint::HttpClients), instead of a client of its own.
+    pub fn with_http_client(self, client: Client) -> Self {
+        Self { client, ..self }
+    }
+
+    /// Uses `clock` instead of fetching the genesis and spec of the beacon node.
+    pub fn with_slot_clock(self, clock: SlotClock) -> Self {
+        Self { clock: OnceCell::new_with(Some(clock)), ..self }
+    }
+
+    /// Returns the slot clock of the beacon chain, fetching it on first use.
+    pub async fn slot_clock(&self) -> Result<SlotClock> {
+        self.clock
+            .get_or_try_init(|| async {
+                let genesis: Genesis = self.get("eth/v1/beacon/genesis").await?;
+                let spec: Spec = self.get("eth/v1/config/spec").await?;
+                let clock = SlotClock::new(genesis.genesis_time, spec.seconds_per_slot)?;
+                info!(
+                    target: "hera::blobs",
+                    genesis_time = clock.genesis_time(),
+                    seconds_per_slot = clock.seconds_per_slot(),
+                    "Fetched beacon slot clock"
+                );
+                Ok::<_, eyre::Report>(clock)
+            })
+            .await
+            .copied()
+    }
+
+    /// Returns the beacon slot of an L1 block timestamp.
+    pub async fn slot(&self, timestamp: u64) -> Result<u64> {
+        self.slot_clock().await?.slot(timestamp)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let url = self.url.join(path)?;
+        let response = self
+            .client
+            .get(url.clone())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .wrap_err_with(|| format!("beacon request {url} failed"))?;
+        let body: Response<T> = response
+            .json()
+            .await
+            .wrap_err_with(|| format!("invalid beacon response from {url}"))?;
+        Ok(body.data)
+    }
+}
+
+#[async_trait]
+impl BlobProvider for BeaconClient {
+    async fn blob_sidecars(&self, block: &BlockInfo, indices: &[u64]) -> Result<Vec<BlobSidecar>> {
+        let slot = self.slot(block.timestamp).await?;
+        let indices = indices.iter().map(u64::to_string).collect::<Vec<_>>().join(",");
+        let path = format!("eth/v1/beacon/blob_sidecars/{slot}?indices={indices}");
+        self.get(&path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Serves the genesis and spec of Ethereum mainnet, counting the requests.
+    async fn serve_beacon() -> (Url, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap()).parse().unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut request = vec![0; 4096];
+                let n = stream.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..n]);
+                let body = if request.starts_with("GET /eth/v1/beacon/genesis ") {
+                    r#"{"data":{"genesis_time":"1606824023"}}"#
+                } else {
+                    r#"{"data":{"SECONDS_PER_SLOT":"12"}}"#
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                     content-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn fetches_the_slot_clock_once() {
+        let (url, requests) = serve_beacon().await;
+        let client = BeaconClient::new(url);
+
+        let (a, b) = tokio::join!(client.slot(1_718_000_015), client.slot(1_718_000_027));
+        assert_eq!((a.unwrap(), b.unwrap()), (9_264_666, 9_264_667));
+        assert_eq!(client.slot(1_718_000_039).await.unwrap(), 9_264_668);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn uses_a_given_slot_clock() {
+        let client = BeaconClient::new("http://127.0.0.1:1".parse().unwrap())
+            .with_slot_clock(SlotClock::new(1_000, 2).unwrap());
+        assert_eq!(client.slot(1_010).await.unwrap(), 5);
+    }
+
+    #[test]
+    fn resolves_paths_under_the_base_path() {
+        for base in [
+            "http://beacon:5052",
+            "http://beacon:5052/",
+            "http://beacon/node/",
+            "http://beacon/node",
+        ] {
+            let client = BeaconClient::new(base.parse().unwrap());
+            let url = client.url.join("eth/v1/beacon/genesis").unwrap();
+            let expected = format!("{}/eth/v1/beacon/genesis", base.trim_end_matches('/'));
+            assert_eq!(url.as_str(), expected);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! A local cache of blob sidecars, filled ahead of replays by the
+//! [`BlobBackfill`](crate::blobs::BlobBackfill) job.
+
+use std::{path::Path, sync::Arc};
+
+use alloy_eips::eip4844::{Blob, Bytes48, BYTES_PER_BLOB, BYTES_PER_COMMITMENT};
+use alloy_primitives::B256;
+use async_trait::async_trait;
+use eyre::{ensure, eyre, Result, WrapErr};
+use metrics::counter;
+
+use crate::{
+    blobs::{BlobProvider, BlobSidecar},
+    protocol::BlockInfo,
+    storage::{SqliteStorage, Storage},
+};
+
+/// The table of sidecars, keyed by block hash and big-endian blob index, holding the KZG
+/// commitment followed by the blob.
+pub(crate) const SIDECARS: &str = "blob_sidecars";
+
+/// The table of checkpoints, holding big-endian L1 block numbers.
+pub(crate) const CHECKPOINTS: &str = "blob_checkpoints";
+
+/// Blob sidecars stored locally, keyed by L1 block hash and blob index.
+///
+/// Only sidecars checked against the versioned hashes of their transaction are stored, so the
+/// cache can be served without further checks beyond those of the
+/// [`BlobFetcher`](crate::blobs::BlobFetcher).
+#[derive(Debug)]
+pub struct BlobCache {
+    storage: Arc<dyn Storage>,
+}
+
+impl BlobCache {
+    /// Creates a cache in `storage`.
+    pub fn new(storage: Arc<dyn Storage>) -> Self {
+        Self { storage }
+    }
+
+    /// Opens the cache in the SQLite database at `path`, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let storage = SqliteStorage::open(path)
+            .wrap_err_with(|| format!("failed to open blob cache {}", path.display()))?;
+        Ok(Self::new(Arc::new(storage)))
+    }
+
+    fn key(block_hash: B256, index: u64) -> [u8; 40] {
+        let mut key = [0; 40];
+        key[..32].copy_from_slice(block_hash.as_slice());
+        key[32..].copy_from_slice(&index.to_be_bytes());
+        key
+    }
+
+    /// Returns the cached sidecars of the blobs at `indices` in the block with the given hash,
+    /// skipping the ones not cached.
+    pub fn get(&self, block_hash: B256, indices: &[u64]) -> Result<Vec<BlobSidecar>> {
+        let mut sidecars = Vec::with_capacity(indices.len());
+        for &index in indices {
+            let value = self
+                .storage
+                .get(SIDECARS, &Self::key(block_hash, index))
+                .wrap_err_with(|| format!("failed to read blob {index} of {block_hash}"))?;
+            let Some(value) = value else { continue };
+            ensure!(
+                value.len() == BYTES_PER_COMMITMENT + BYTES_PER_BLOB,
+                "cached blob {index} of {block_hash} is malformed"
+            );
+            let (commitment, data) = value.split_at(BYTES_PER_COMMITMENT);
+            let mut blob = Box::new(Blob::ZERO);
+            blob.copy_from_slice(data);
+            sidecars.push(BlobSidecar {
+                index,
+                blob,
+                kzg_commitment: Bytes48::try_from(commitment)
+                    .map_err(|_| eyre!("cached commitment {index} of {block_hash} is malformed"))?,
+            });
+        }
+        Ok(sidecars)
+    }
+
+    /// Returns the indices out of `indices` whose blobs are not cached for the block with the
+    /// given hash.
+    pub fn missing(&self, block_hash: B256, indices: &[u64]) -> Result<Vec<u64>> {
+        let mut missing = Vec::new();
+        for &index in indices {
+            if !self.storage.contains(SIDECARS, &Self::key(block_hash, index))? {
+                missing.push(index);
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Stores the sidecars of the block with the given hash.
+    pub fn insert(&self, block_hash: B256, sidecars: &[BlobSidecar]) -> Result<()> {
+        let entries = sidecars
+            .iter()
+            .map(|sidecar| {
+                let mut value = Vec::with_capacity(BYTES_PER_COMMITMENT + BYTES_PER_BLOB);
+                value.extend_from_slice(sidecar.kzg_commitment.as_slice());
+                value.extend_from_slice(sidecar.blob.as_slice());
+                (Self::key(block_hash, sidecar.index), value)
+            })
+            .collect::<Vec<_>>();
+        let entries = entries
+            .iter()
+            .map(|(key, value)| (key.as_slice(), value.as_slice()))
+            .collect::<Vec<_>>();
+        self.storage
+            .put(SIDECARS, &entries)
+            .wrap_err_with(|| format!("failed to store the blobs of {block_hash}"))
+    }
+
+    /// Returns the checkpoint stored under `name`.
+    pub fn checkpoint(&self, name: &str) -> Result<Option<u64>> {
+        let Some(value) = self
+            .storage
+            .get(CHECKPOINTS, name.as_bytes())
+            .wrap_err_with(|| format!("failed to read checkpoint {name}"))?
+        else {
+            return Ok(None);
+        };
+        let number = <[u8; 8]>::try_from(value.as_slice())
+            .map_err(|_| eyre!("checkpoint {name} is malformed"))?;
+        Ok(Some(u64::from_be_bytes(number)))
+    }
+
+    /// Stores an L1 block number as the checkpoint `name`.
+    pub fn set_checkpoint(&self, name: &str, l1_number: u64) -> Result<()> {
+        self.storage
+            .put(CHECKPOINTS, &[(name.as_bytes(), &l1_number.to_be_bytes())])
+            .wrap_err_with(|| format!("failed to store checkpoint {name}"))
+    }
+}
+
+/// Serves blob sidecars from a [`BlobCache`], fetching the ones it misses from another provider.
+#[derive(Debug)]
+pub struct CachedBlobProvider {
+    cache: Arc<BlobCache>,
+    inner: Arc<dyn BlobProvider>,
+}
+
+impl CachedBlobProvider {
+    /// Creates a provider serving from `cache`, falling back to `inner`.
+    pub fn new(cache: Arc<BlobCache>, inner: Arc<dyn BlobProvider>) -> Self {
+        Self { cache, inner }
+    }
+}
+
+#[async_trait]
+impl BlobProvider for CachedBlobProvider {
+    async fn blob_sidecars(&self, block: &BlockInfo, indices: &[u64]) -> Result<Vec<BlobSidecar>> {
+        let mut sidecars = self.cache.get(block.hash, indices)?;
+        counter!("hera_blob_cache_hits_total").increment(sidecars.len() as u64);
+        if sidecars.len() == indices.len() {
+            return Ok(sidecars);
+        }
+
+        let missing = indices
+            .iter()
+            .filter(|index| !sidecars.iter().any(|sidecar| sidecar.index == **index))
+            .copied()
+            .collect::<Vec<_>>();
+        counter!("hera_blob_cache_misses_total").increment(missing.len() as u64);
+        sidecars.extend(self.inner.blob_sidecars(block, &missing).await?);
+        Ok(sidecars)
+    }
+}
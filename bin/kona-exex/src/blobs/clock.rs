@@ -0,0 +1,62 @@
+//! Beacon chain slot math.
+
+use eyre::{ensure, Result};
+
+/// Converts L1 execution block timestamps to beacon slots, from the beacon genesis time and the
+/// slot duration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotClock {
+    genesis_time: u64,
+    seconds_per_slot: u64,
+}
+
+impl SlotClock {
+    /// Creates the clock of a beacon chain started at `genesis_time`.
+    pub fn new(genesis_time: u64, seconds_per_slot: u64) -> Result<Self> {
+        ensure!(seconds_per_slot > 0, "beacon chain has zero seconds per slot");
+        Ok(Self { genesis_time, seconds_per_slot })
+    }
+
+    /// Returns the genesis time of the beacon chain.
+    pub const fn genesis_time(&self) -> u64 {
+        self.genesis_time
+    }
+
+    /// Returns the duration of a slot, in seconds.
+    pub const fn seconds_per_slot(&self) -> u64 {
+        self.seconds_per_slot
+    }
+
+    /// Returns the slot of the block with the given timestamp.
+    pub fn slot(&self, timestamp: u64) -> Result<u64> {
+        ensure!(
+            timestamp >= self.genesis_time,
+            "timestamp {timestamp} is before the beacon genesis {}",
+            self.genesis_time
+        );
+        Ok((timestamp - self.genesis_time) / self.seconds_per_slot)
+    }
+
+    /// Returns the timestamp of the start of `slot`, `None` if past the range of timestamps.
+    pub fn slot_start(&self, slot: u64) -> Option<u64> {
+        slot.checked_mul(self.seconds_per_slot)?.checked_add(self.genesis_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_timestamps_to_slots() {
+        // Ethereum mainnet.
+        let clock = SlotClock::new(1_606_824_023, 12).unwrap();
+        assert_eq!(clock.slot(1_606_824_023).unwrap(), 0);
+        assert_eq!(clock.slot(1_718_000_015).unwrap(), 9_264_666);
+        assert_eq!(clock.slot_start(9_264_666), Some(1_718_000_015));
+        assert_eq!(clock.slot_start(u64::MAX / 12), None);
+        assert_eq!(clock.slot(1_718_000_026).unwrap(), 9_264_666);
+        assert!(clock.slot(1_606_824_022).is_err());
+        assert!(SlotClock::new(0, 0).is_err());
+    }
+}
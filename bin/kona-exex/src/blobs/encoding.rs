@@ -0,0 +1,273 @@
+//! The OP Stack blob encoding, packing up to [`MAX_BLOB_DATA_SIZE`] bytes into the field elements
+//! of a blob.
+//!
+//! Each round packs 127 bytes into 4 field elements: 31 bytes go into the low bytes of each
+//! element, and the remaining 3 bytes are split into the low 6 bits of the element's first byte,
+//! keeping every element below the BLS modulus. The first element also carries the encoding
+//! version and the 3-byte data length.
+//!
+//! Decoding follows op-node: the two high-order bits of every element but the first must be
+//! zero, and everything past the data length must be zero as well, so that a blob decodes to
+//! some data only if it is the encoding of that data.
+
+use std::fmt;
+
+use alloy_eips::eip4844::Blob;
+use alloy_primitives::Bytes;
+use eyre::{ensure, Result};
+use metrics::counter;
+
+/// The maximum number of bytes one blob can carry.
+pub const MAX_BLOB_DATA_SIZE: usize = (4 * 31 + 3) * 1024 - 4;
+
+/// The version of the blob encoding.
+pub const BLOB_ENCODING_VERSION: u8 = 0;
+
+const ENCODING_ROUNDS: usize = 1024;
+
+/// Why a blob could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlobDecodingError {
+    /// The blob uses an unknown encoding version.
+    InvalidVersion(u8),
+    /// The data length exceeds [`MAX_BLOB_DATA_SIZE`].
+    InvalidLength(usize),
+    /// The high-order bits of the first byte of a field element are set.
+    InvalidFieldElement(usize),
+    /// The blob carries non-zero bytes past its data length.
+    ExtraneousData(usize),
+}
+
+impl BlobDecodingError {
+    /// Returns the kind of the error.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::InvalidVersion(_) => "invalid_version",
+            Self::InvalidLength(_) => "invalid_length",
+            Self::InvalidFieldElement(_) => "invalid_field_element",
+            Self::ExtraneousData(_) => "extraneous_data",
+        }
+    }
+}
+
+impl fmt::Display for BlobDecodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidVersion(version) => {
+                write!(f, "unsupported blob encoding version {version}")
+            }
+            Self::InvalidLength(len) => write!(f, "blob data length {len} too large"),
+            Self::InvalidFieldElement(index) => write!(f, "invalid field element {index}"),
+            Self::ExtraneousData(len) => write!(f, "extraneous data in blob past its length {len}"),
+        }
+    }
+}
+
+impl std::error::Error for BlobDecodingError {}
+
+/// Decodes the data packed into a blob.
+pub fn decode_blob_data(blob: &Blob) -> Result<Bytes, BlobDecodingError> {
+    decode(blob.as_slice()).inspect_err(|err| {
+        counter!("hera_blob_decoding_failures_total", "reason" => err.kind()).increment(1);
+    })
+}
+
+fn decode(blob: &[u8]) -> Result<Bytes, BlobDecodingError> {
+    if blob[1] != BLOB_ENCODING_VERSION {
+        return Err(BlobDecodingError::InvalidVersion(blob[1]));
+    }
+    let len = (blob[2] as usize) << 16 | (blob[3] as usize) << 8 | blob[4] as usize;
+    if len > MAX_BLOB_DATA_SIZE {
+        return Err(BlobDecodingError::InvalidLength(len));
+    }
+
+    let mut output = vec![0u8; MAX_BLOB_DATA_SIZE];
+    // The first element holds 27 data bytes after the version and length.
+    output[..27].copy_from_slice(&blob[5..32]);
+    let mut encoded = [blob[0], 0, 0, 0];
+    let mut opos = 28;
+    let mut ipos = 32;
+    for byte in &mut encoded[1..] {
+        *byte = decode_field_element(blob, &mut opos, &mut ipos, &mut output)?;
+    }
+    opos = reassemble_bytes(opos, encoded, &mut output);
+
+    for _ in 1..ENCODING_ROUNDS {
+        if opos >= len {
+            break;
+        }
+        for byte in &mut encoded {
+            *byte = decode_field_element(blob, &mut opos, &mut ipos, &mut output)?;
+        }
+        opos = reassemble_bytes(opos, encoded, &mut output);
+    }
+
+    if output[len..].iter().any(|b| *b != 0) || blob[ipos..].iter().any(|b| *b != 0) {
+        return Err(BlobDecodingError::ExtraneousData(len));
+    }
+    output.truncate(len);
+    Ok(output.into())
+}
+
+/// Encodes data into a blob.
+pub fn encode_blob_data(data: &[u8]) -> Result<Box<Blob>> {
+    ensure!(data.len() <= MAX_BLOB_DATA_SIZE, "blob data length {} too large", data.len());
+    let mut blob = Box::new(Blob::ZERO);
+    let out = blob.as_mut_slice();
+
+    let mut read = 0;
+    let mut write = 0;
+    let mut buf = [0u8; 31];
+    let read1 = |read: &mut usize| {
+        let byte = data.get(*read).copied().unwrap_or_default();
+        *read = (*read + 1).min(data.len());
+        byte
+    };
+    let read31 = |read: &mut usize, buf: &mut [u8; 31]| {
+        let n = (data.len() - *read).min(31);
+        buf[..n].copy_from_slice(&data[*read..*read + n]);
+        buf[n..].fill(0);
+        *read += n;
+    };
+    let mut write_element = |chunk: u8, buf: &[u8; 31]| {
+        out[write] = chunk;
+        out[write + 1..write + 32].copy_from_slice(buf);
+        write += 32;
+    };
+
+    for round in 0..ENCODING_ROUNDS {
+        if read >= data.len() {
+            break;
+        }
+        if round == 0 {
+            buf[0] = BLOB_ENCODING_VERSION;
+            buf[1..4].copy_from_slice(&(data.len() as u32).to_be_bytes()[1..]);
+            let n = data.len().min(27);
+            buf[4..4 + n].copy_from_slice(&data[..n]);
+            buf[4 + n..].fill(0);
+            read += n;
+        } else {
+            read31(&mut read, &mut buf);
+        }
+
+        let x = read1(&mut read);
+        write_element(x & 0b0011_1111, &buf);
+        read31(&mut read, &mut buf);
+        let y = read1(&mut read);
+        write_element((y & 0b0000_1111) | ((x & 0b1100_0000) >> 2), &buf);
+        read31(&mut read, &mut buf);
+        let z = read1(&mut read);
+        write_element(z & 0b0011_1111, &buf);
+        read31(&mut read, &mut buf);
+        write_element(((z & 0b1100_0000) >> 2) | ((y & 0b1111_0000) >> 4), &buf);
+    }
+    Ok(blob)
+}
+
+fn decode_field_element(
+    blob: &[u8],
+    opos: &mut usize,
+    ipos: &mut usize,
+    output: &mut [u8],
+) -> Result<u8, BlobDecodingError> {
+    let first = blob[*ipos];
+    if first & 0b1100_0000 != 0 {
+        return Err(BlobDecodingError::InvalidFieldElement(*ipos / 32));
+    }
+    output[*opos..*opos + 31].copy_from_slice(&blob[*ipos + 1..*ipos + 32]);
+    *opos += 32;
+    *ipos += 32;
+    Ok(first)
+}
+
+/// Reassembles the 3 bytes spread over the 6-bit chunks of a round into the gaps left in the
+/// output, returning the output position of the next round.
+fn reassemble_bytes(opos: usize, encoded: [u8; 4], output: &mut [u8]) -> usize {
+    let opos = opos - 1;
+    let x = (encoded[0] & 0b0011_1111) | ((encoded[1] & 0b0011_0000) << 2);
+    let y = (encoded[1] & 0b0000_1111) | ((encoded[3] & 0b0000_1111) << 4);
+    let z = (encoded[2] & 0b0011_1111) | ((encoded[3] & 0b0011_0000) << 2);
+    output[opos - 32] = z;
+    output[opos - 64] = y;
+    output[opos - 96] = x;
+    opos
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn roundtrips_every_length_class() {
+        for len in [0, 1, 27, 28, 31, 127, 128, 1_000, 130_044, MAX_BLOB_DATA_SIZE] {
+            let data = (0..len).map(|i| (i * 7 + 3) as u8).collect::<Vec<_>>();
+            let blob = encode_blob_data(&data).unwrap();
+            assert_eq!(decode_blob_data(&blob).unwrap(), data, "length {len}");
+        }
+    }
+
+    #[test]
+    fn keeps_field_elements_below_the_modulus() {
+        let blob = encode_blob_data(&[0xff; MAX_BLOB_DATA_SIZE]).unwrap();
+        assert!(blob.chunks(32).all(|element| element[0] & 0b1100_0000 == 0));
+    }
+
+    #[test]
+    fn rejects_too_much_data() {
+        assert!(encode_blob_data(&[0; MAX_BLOB_DATA_SIZE + 1]).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_blobs() {
+        let blob = encode_blob_data(b"hello").unwrap();
+
+        let mut version = blob.clone();
+        version[1] = 1;
+        assert_eq!(decode_blob_data(&version), Err(BlobDecodingError::InvalidVersion(1)));
+
+        let mut length = blob.clone();
+        length[2] = 0xff;
+        assert_eq!(decode_blob_data(&length), Err(BlobDecodingError::InvalidLength(0xff0005)));
+
+        let mut element = blob.clone();
+        element[32] = 0b1000_0000;
+        assert_eq!(decode_blob_data(&element), Err(BlobDecodingError::InvalidFieldElement(1)));
+
+        let mut padding = blob.clone();
+        padding[10] = 1;
+        assert_eq!(decode_blob_data(&padding), Err(BlobDecodingError::ExtraneousData(5)));
+
+        let mut trailing = blob;
+        trailing[4 * 32 + 1] = 1;
+        assert_eq!(decode_blob_data(&trailing), Err(BlobDecodingError::ExtraneousData(5)));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn roundtrips_random_data(data in prop::collection::vec(any::<u8>(), 0..=MAX_BLOB_DATA_SIZE)) {
+            let blob = encode_blob_data(&data).unwrap();
+            prop_assert_eq!(decode_blob_data(&blob).unwrap(), data);
+        }
+
+        /// A blob decodes only if it is the encoding of the decoded data, up to the two ignored
+        /// high-order bits of its first byte.
+        #[test]
+        fn decodes_only_canonical_blobs(
+            data in prop::collection::vec(any::<u8>(), 0..4_096),
+            corruptions in prop::collection::vec((0..4_096 * 32usize, any::<u8>()), 1..4),
+        ) {
+            let mut blob = encode_blob_data(&data).unwrap();
+            for (index, byte) in corruptions {
+                blob[index] = byte;
+            }
+            blob[0] &= 0b0011_1111;
+            if let Ok(decoded) = decode_blob_data(&blob) {
+                prop_assert_eq!(encode_blob_data(&decoded).unwrap(), blob);
+            }
+        }
+    }
+}
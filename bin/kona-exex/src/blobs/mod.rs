@@ -0,0 +1,216 @@
+//! Blobs carrying batcher data from Ecotone onwards, fetched from the L1 consensus layer.
+//!
+//! Sidecars served by a beacon endpoint are only used once their KZG commitments are confirmed
+//! to match the versioned hashes of the batcher transaction, which catches sidecars of the wrong
+//! block or index. The KZG proofs binding the blobs to their commitments are not verified, so the
+//! beacon endpoint is trusted for the content of the blobs themselves.
+
+use std::sync::Arc;
+
+use alloy_eips::eip4844::{kzg_to_versioned_hash, Blob, Bytes48};
+use alloy_primitives::B256;
+use async_trait::async_trait;
+use eyre::{bail, Result};
+use metrics::counter;
+use serde::{Deserialize, Deserializer};
+use tracing::error;
+
+use crate::protocol::BlockInfo;
+
+mod backfill;
+pub use backfill::BlobBackfill;
+
+mod beacon;
+pub use beacon::BeaconClient;
+
+pub(crate) mod cache;
+pub use cache::{BlobCache, CachedBlobProvider};
+
+mod clock;
+pub use clock::SlotClock;
+
+mod encoding;
+pub use encoding::{
+    decode_blob_data, encode_blob_data, BlobDecodingError, BLOB_ENCODING_VERSION,
+    MAX_BLOB_DATA_SIZE,
+};
+
+/// A blob versioned hash, with the index of the blob within its L1 block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexedBlobHash {
+    /// Index of the blob in the block.
+    pub index: u64,
+    /// The versioned hash of the blob.
+    pub hash: B256,
+}
+
+/// A blob sidecar, as served by the beacon API.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BlobSidecar {
+    /// Index of the blob in the block.
+    #[serde(deserialize_with = "deserialize_u64_string")]
+    pub index: u64,
+    /// The blob.
+    pub blob: Box<Blob>,
+    /// The KZG commitment to the blob.
+    pub kzg_commitment: Bytes48,
+}
+
+impl BlobSidecar {
+    /// Returns the versioned hash of the sidecar's commitment.
+    pub fn versioned_hash(&self) -> B256 {
+        kzg_to_versioned_hash(self.kzg_commitment.as_slice())
+    }
+}
+
+/// A source of blob sidecars.
+#[async_trait]
+pub trait BlobProvider: std::fmt::Debug + Send + Sync {
+    /// Returns the sidecars of the blobs at `indices` in the given L1 block.
+    async fn blob_sidecars(&self, block: &BlockInfo, indices: &[u64]) -> Result<Vec<BlobSidecar>>;
+}
+
+/// Fetches blobs and checks them against the versioned hashes of the transactions that carry
+/// them.
+#[derive(Debug, Clone)]
+pub struct BlobFetcher {
+    provider: Arc<dyn BlobProvider>,
+}
+
+impl BlobFetcher {
+    /// Creates a fetcher over `provider`.
+    pub fn new(provider: Arc<dyn BlobProvider>) -> Self {
+        Self { provider }
+    }
+
+    /// Returns the blobs with the given hashes in `block`, in order.
+    ///
+    /// A sidecar missing from the response, or whose commitment does not match the expected
+    /// versioned hash, is counted and fails the fetch, so derivation retries instead of running on
+    /// partial data. No request is made when `hashes` is empty.
+    pub async fn blobs(
+        &self,
+        block: &BlockInfo,
+        hashes: &[IndexedBlobHash],
+    ) -> Result<Vec<Box<Blob>>> {
+        if hashes.is_empty() {
+            return Ok(Vec::new());
+        }
+        let indices = hashes.iter().map(|h| h.index).collect::<Vec<_>>();
+        let mut sidecars = self.provider.blob_sidecars(block, &indices).await?;
+
+        let mut blobs = Vec::with_capacity(hashes.len());
+        for expected in hashes {
+            let Some(position) = sidecars.iter().position(|s| s.index == expected.index) else {
+                counter!("hera_blob_sidecars_missing_total").increment(1);
+                bail!("blob {} of L1 block {block} is missing from the sidecars", expected.index);
+            };
+            let sidecar = sidecars.swap_remove(position);
+            let actual = sidecar.versioned_hash();
+            if actual != expected.hash {
+                counter!("hera_blob_versioned_hash_mismatches_total").increment(1);
+                error!(
+                    target: "hera::blobs",
+                    block = %block,
+                    index = expected.index,
+                    expected = %expected.hash,
+                    %actual,
+                    "Blob sidecar commitment does not match the transaction versioned hash"
+                );
+                bail!("blob {} of L1 block {block} has a mismatching commitment", expected.index);
+            }
+            blobs.push(sidecar.blob);
+        }
+        Ok(blobs)
+    }
+}
+
+/// Deserializes a `u64` encoded as a decimal string, as the beacon API does.
+fn deserialize_u64_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use alloy_primitives::Bytes;
+
+    use super::*;
+
+    /// Serves the sidecars it holds, counting the requests it receives.
+    #[derive(Debug, Default)]
+    struct Sidecars {
+        sidecars: Vec<BlobSidecar>,
+        requests: AtomicUsize,
+    }
+
+    impl Sidecars {
+        fn new(data: &[&[u8]]) -> Self {
+            let sidecars = data
+                .iter()
+                .enumerate()
+                .map(|(index, data)| BlobSidecar {
+                    index: index as u64,
+                    blob: encode_blob_data(data).unwrap(),
+                    kzg_commitment: Bytes48::repeat_byte(index as u8 + 1),
+                })
+                .collect();
+            Self { sidecars, requests: AtomicUsize::new(0) }
+        }
+
+        fn hash(&self, index: u64) -> IndexedBlobHash {
+            IndexedBlobHash { index, hash: self.sidecars[index as usize].versioned_hash() }
+        }
+    }
+
+    #[async_trait]
+    impl BlobProvider for Sidecars {
+        async fn blob_sidecars(&self, _: &BlockInfo, indices: &[u64]) -> Result<Vec<BlobSidecar>> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            // Answer in reverse order, the fetcher must restore the requested one.
+            Ok(self.sidecars.iter().rev().filter(|s| indices.contains(&s.index)).cloned().collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn returns_blobs_in_requested_order() {
+        let provider = Arc::new(Sidecars::new(&[b"first", b"second", b"third"]));
+        let fetcher = BlobFetcher::new(provider.clone());
+        let hashes = [provider.hash(2), provider.hash(0)];
+
+        let blobs = fetcher.blobs(&BlockInfo::default(), &hashes).await.unwrap();
+        let data = blobs.iter().map(|blob| decode_blob_data(blob).unwrap()).collect::<Vec<_>>();
+        assert_eq!(data, [Bytes::from_static(b"third"), Bytes::from_static(b"first")]);
+    }
+
+    #[tokio::test]
+    async fn rejects_mismatching_commitment() {
+        let provider = Arc::new(Sidecars::new(&[b"first", b"second"]));
+        let fetcher = BlobFetcher::new(provider.clone());
+        let hashes = [provider.hash(0), IndexedBlobHash { index: 1, hash: provider.hash(0).hash }];
+
+        let err = fetcher.blobs(&BlockInfo::default(), &hashes).await.unwrap_err();
+        assert!(err.to_string().contains("blob 1 of L1 block"), "{err}");
+        assert!(err.to_string().contains("mismatching commitment"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn fails_on_missing_sidecar() {
+        let provider = Arc::new(Sidecars::new(&[b"first"]));
+        let fetcher = BlobFetcher::new(provider.clone());
+        let hashes = [provider.hash(0), IndexedBlobHash { index: 1, hash: B256::ZERO }];
+
+        let err = fetcher.blobs(&BlockInfo::default(), &hashes).await.unwrap_err();
+        assert!(err.to_string().contains("missing from the sidecars"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn skips_the_provider_without_blobs() {
+        let provider = Arc::new(Sidecars::new(&[b"first"]));
+        let fetcher = BlobFetcher::new(provider.clone());
+
+        assert!(fetcher.blobs(&BlockInfo::default(), &[]).await.unwrap().is_empty());
+        assert_eq!(provider.requests.load(Ordering::Relaxed), 0);
+    }
+}
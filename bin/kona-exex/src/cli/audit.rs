@@ -0,0 +1,75 @@
+//! The `hera audit` command, reading back the audit log.
+
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::Result;
+
+use crate::audit::{AuditLog, AuditQuery};
+
+/// Prints the events of the audit log, oldest first.
+#[derive(Debug, Clone, Args)]
+pub struct AuditCommand {
+    /// Path to the audit log database, as passed to `--hera.audit-log`.
+    #[arg(long)]
+    pub db: PathBuf,
+
+    /// Only print events of this kind: `derived_block`, `head_advanced`, `validation_failure`
+    /// or `reorg`.
+    #[arg(long)]
+    pub kind: Option<String>,
+
+    /// Only print events at or above this L2 block number.
+    #[arg(long)]
+    pub from_block: Option<u64>,
+
+    /// Only print events at or below this L2 block number.
+    #[arg(long)]
+    pub to_block: Option<u64>,
+
+    /// Only print events recorded at or after this Unix timestamp, in seconds.
+    #[arg(long)]
+    pub since: Option<i64>,
+
+    /// Maximum number of events printed, the most recent ones.
+    #[arg(long, default_value_t = 100)]
+    pub limit: u64,
+
+    /// Print events as JSON lines.
+    #[arg(long)]
+    pub json: bool,
+}
+
+impl AuditCommand {
+    /// Runs the command.
+    pub fn run(&self) -> Result<()> {
+        let log = AuditLog::open(&self.db)?;
+        let records = log.query(&AuditQuery {
+            kind: self.kind.clone(),
+            from_block: self.from_block,
+            to_block: self.to_block,
+            since: self.since.map(|since| since * 1000),
+            limit: Some(self.limit),
+        })?;
+
+        for record in records {
+            if self.json {
+                println!("{}", serde_json::to_string(&record)?);
+                continue;
+            }
+            let block = match (record.l2_number, &record.l2_hash) {
+                (Some(number), Some(hash)) => format!("{number} ({hash})"),
+                _ => "-".to_string(),
+            };
+            println!(
+                "{:>8} {} {:<18} {} {}",
+                record.id,
+                record.recorded_at,
+                record.kind,
+                block,
+                record.detail.as_deref().unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+//! Batcher arguments.
+
+use std::time::Duration;
+
+use clap::Args;
+use eyre::{bail, Result};
+
+use crate::{
+    batcher::{
+        da::DEFAULT_DA_SWITCH_THRESHOLD,
+        txmgr::{DEFAULT_CONFIRMATION_DEPTH, DEFAULT_MAX_FEE_BUMPS, DEFAULT_RESUBMISSION_TIMEOUT},
+        BatcherConfig, Compression, CompressionAlgo, DataAvailability, TxManagerConfig,
+        DEFAULT_MAX_BLOBS_PER_TX, DEFAULT_TARGET_FRAME_SIZE,
+    },
+    config::RollupConfig,
+};
+
+/// Channel and frame settings of the batcher, namespaced under `--hera.batcher.*`.
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+#[command(next_help_heading = "Hera batcher")]
+pub struct BatcherArgs {
+    /// Compression algorithm of channels: `zlib`, or `brotli` once Fjord is active.
+    #[arg(long = "hera.batcher.compression", default_value = "zlib")]
+    pub compression: CompressionAlgo,
+
+    /// Compression level, from 0 to 10 for zlib and 0 to 11 for brotli. Defaults to 10.
+    #[arg(long = "hera.batcher.compression-level")]
+    pub compression_level: Option<u32>,
+
+    /// Target encoded size of calldata frames, in bytes.
+    #[arg(long = "hera.batcher.target-frame-size", default_value_t = DEFAULT_TARGET_FRAME_SIZE)]
+    pub target_frame_size: usize,
+
+    /// Number of L1 blocks after which an open channel is closed, 0 to only close full channels.
+    #[arg(long = "hera.batcher.max-channel-duration", default_value_t = 0)]
+    pub max_channel_duration: u64,
+
+    /// Data availability route of batcher transactions: `calldata` or `blobs`. The initial
+    /// route when switching automatically.
+    #[arg(long = "hera.batcher.data-availability", default_value = "calldata")]
+    pub data_availability: DataAvailability,
+
+    /// Switch each new channel between calldata and blobs, whichever is cheaper at the current
+    /// L1 fees.
+    #[arg(long = "hera.batcher.auto-data-availability")]
+    pub auto_data_availability: bool,
+
+    /// How much cheaper, in percent, the other route must be before switching to it.
+    #[arg(long = "hera.batcher.da-switch-threshold", default_value_t = DEFAULT_DA_SWITCH_THRESHOLD)]
+    pub da_switch_threshold: u64,
+
+    /// Maximum number of blobs per batcher transaction. Blob channels larger than this are
+    /// split into sub-channels.
+    #[arg(long = "hera.batcher.max-blobs-per-tx", default_value_t = DEFAULT_MAX_BLOBS_PER_TX)]
+    pub max_blobs_per_tx: usize,
+
+    /// Seconds after which a batcher transaction that is not included is resubmitted with
+    /// bumped fees.
+    #[arg(
+        long = "hera.batcher.resubmission-timeout",
+        default_value_t = DEFAULT_RESUBMISSION_TIMEOUT.as_secs()
+    )]
+    pub resubmission_timeout: u64,
+
+    /// Number of fee bumps after which a stuck batcher transaction is cancelled and its frames
+    /// posted again.
+    #[arg(long = "hera.batcher.max-fee-bumps", default_value_t = DEFAULT_MAX_FEE_BUMPS)]
+    pub max_fee_bumps: u32,
+
+    /// Number of L1 blocks built on top of a batcher transaction before it is considered final.
+    /// Transactions orphaned by a shallower L1 reorg are resubmitted.
+    #[arg(
+        long = "hera.batcher.confirmation-depth",
+        default_value_t = DEFAULT_CONFIRMATION_DEPTH
+    )]
+    pub confirmation_depth: u64,
+}
+
+impl BatcherArgs {
+    /// Returns the batcher config, checking it is usable on the rollup.
+    pub fn batcher_config(&self, rollup: &RollupConfig) -> Result<BatcherConfig> {
+        if self.compression == CompressionAlgo::Brotli && rollup.fjord_time.is_none() {
+            bail!("brotli channel compression requires Fjord");
+        }
+        let blobs =
+            self.data_availability == DataAvailability::Blobs || self.auto_data_availability;
+        if blobs && rollup.ecotone_time.is_none() {
+            bail!("blob data availability requires Ecotone");
+        }
+        if !(1..=DEFAULT_MAX_BLOBS_PER_TX).contains(&self.max_blobs_per_tx) {
+            bail!("max blobs per tx must be between 1 and {DEFAULT_MAX_BLOBS_PER_TX}");
+        }
+        // Channels must close well before they time out, also under Granite's shorter timeout.
+        let timeout = rollup.channel_timeout.min(rollup.granite_channel_timeout);
+        if self.max_channel_duration >= timeout {
+            bail!(
+                "max channel duration {} must be below the channel timeout {timeout}",
+                self.max_channel_duration
+            );
+        }
+        let level = self.compression_level.unwrap_or_else(|| self.compression.default_level());
+        Ok(BatcherConfig {
+            compression: Compression::new(self.compression, level)?,
+            target_frame_size: self.target_frame_size,
+            max_channel_duration: self.max_channel_duration,
+            data_availability: self.data_availability,
+            auto_data_availability: self.auto_data_availability,
+            da_switch_threshold: self.da_switch_threshold,
+            max_blobs_per_tx: self.max_blobs_per_tx,
+        })
+    }
+
+    /// Returns the settings of the batcher's transaction manager.
+    pub const fn tx_manager_config(&self) -> TxManagerConfig {
+        TxManagerConfig {
+            resubmission_timeout: Duration::from_secs(self.resubmission_timeout),
+            max_fee_bumps: self.max_fee_bumps,
+            confirmation_depth: self.confirmation_depth,
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! The `hera bench` command, benchmarking derivation over synthetic L1 data.
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use alloy_consensus::Header;
+use alloy_eips::eip4844::{kzg_to_versioned_hash, Blob, Bytes48};
+use alloy_primitives::{keccak256, Bytes, B256};
+use async_trait::async_trait;
+use clap::Args;
+use eyre::{bail, ensure, Result};
+
+use crate::{
+    batcher::{BatcherConfig, ChannelManager, DataAvailability},
+    blobs::{BlobFetcher, BlobProvider, BlobSidecar},
+    config::{ChainGenesis, RollupConfig},
+    derive::{Pipeline, PipelineBuilder, StepResult},
+    l1::{
+        verify::receipts_root, ChainProvider, L1Receipt, L1Transaction, ProviderError,
+        ProviderResult,
+    },
+    protocol::{deposit::DEPOSIT_TX_TYPE, Batch, BlockId, BlockInfo, L2BlockInfo, SingleBatch},
+};
+
+/// The block time of the synthetic L1 chain.
+const L1_BLOCK_TIME: u64 = 12;
+
+/// How long derivation may go without progress before the benchmark fails.
+const STALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Arguments of `hera bench`.
+///
+/// Channels are packed with the `--hera.batcher.*` settings: their compression, frame size and
+/// data availability route. Channels are posted every L1 block, or every
+/// `--hera.batcher.max-channel-duration` L1 blocks if set.
+#[derive(Debug, Clone, Args)]
+pub struct BenchCommand {
+    /// Number of L1 blocks of batcher data to generate.
+    #[arg(long, default_value_t = 100)]
+    pub l1_blocks: u64,
+
+    /// Number of transactions of each L2 block.
+    #[arg(long, default_value_t = 20)]
+    pub txs_per_block: usize,
+
+    /// Size of each transaction, in bytes.
+    #[arg(long, default_value_t = 200, value_parser = clap::value_parser!(u64).range(1..))]
+    pub tx_size: u64,
+
+    /// Share of the bytes of each transaction left zero rather than random, in percent, to
+    /// control how well batches compress.
+    #[arg(long, default_value_t = 50, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub zero_bytes_percent: u8,
+}
+
+impl BenchCommand {
+    /// Generates the synthetic chain, derives it with a default pipeline and prints the
+    /// throughput to stdout.
+    pub async fn run(&self, config: &RollupConfig, batcher: BatcherConfig) -> Result<()> {
+        let started = Instant::now();
+        let chain = self.generate(config, batcher)?;
+        println!(
+            "Generated {} L1 blocks with {} batcher transactions ({} bytes of batcher data) for \
+             {} L2 blocks with {} transactions in {:.2?}",
+            chain.l1.blocks.len() - 1,
+            chain.batcher_txs,
+            chain.batcher_bytes,
+            chain.l2.len() - 1,
+            chain.txs,
+            started.elapsed()
+        );
+
+        let (derived, elapsed) = chain.derive().await?;
+        ensure!(
+            derived == chain.txs,
+            "derived {derived} transactions out of the {} generated",
+            chain.txs
+        );
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        println!("Derived {} L2 blocks in {elapsed:.2?}", chain.l2.len() - 1);
+        println!("  {:.0} L2 blocks/s", (chain.l2.len() - 1) as f64 / secs);
+        println!("  {:.0} transactions/s", chain.txs as f64 / secs);
+        println!("  {:.2} MB/s of batcher data", chain.batcher_bytes as f64 / secs / 1e6);
+        Ok(())
+    }
+
+    /// Generates the L1 blocks and the L2 chain whose batches they carry.
+    fn generate(&self, config: &RollupConfig, batcher: BatcherConfig) -> Result<SyntheticChain> {
+        ensure!(self.l1_blocks > 0, "at least one L1 block is needed");
+        let mut config = config.clone();
+        let system_config = config.genesis.system_config.clone().unwrap_or_default();
+        let (batcher_address, inbox) = (system_config.batcher_address, config.batch_inbox_address);
+
+        // The chain starts now, so that the hardforks active on the rollup are.
+        let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let mut l1 = SyntheticL1::new(start, self.l1_blocks + 1);
+        let l2_hash = |number: u64| keccak256([b"l2".as_slice(), &number.to_be_bytes()].concat());
+        config.genesis = ChainGenesis {
+            l1: l1.blocks[0].id(),
+            l2: BlockId::new(l2_hash(0), 0),
+            l2_time: start,
+            system_config: Some(system_config),
+        };
+
+        // The L2 blocks of every epoch but the last L1 block, whose batches are posted in the
+        // next L1 block.
+        let mut l2 = vec![L2BlockInfo {
+            block_info: BlockInfo::new(l2_hash(0), 0, B256::ZERO, start),
+            l1_origin: l1.blocks[0].id(),
+            seq_num: 0,
+        }];
+        let end = start + self.l1_blocks * L1_BLOCK_TIME;
+        loop {
+            let parent = l2[l2.len() - 1];
+            let number = parent.block_info.number + 1;
+            let timestamp = parent.block_info.timestamp + config.block_time;
+            if timestamp >= end {
+                break;
+            }
+            let epoch = l1.blocks[((timestamp - start) / L1_BLOCK_TIME) as usize].id();
+            let seq_num = if epoch == parent.l1_origin { parent.seq_num + 1 } else { 0 };
+            l2.push(L2BlockInfo {
+                block_info: BlockInfo::new(
+                    l2_hash(number),
+                    number,
+                    parent.block_info.hash,
+                    timestamp,
+                ),
+                l1_origin: epoch,
+                seq_num,
+            });
+        }
+
+        let mut rng = SplitMix64(0);
+        let mut chain = SyntheticChain {
+            config: Arc::new(config),
+            l1: Arc::default(),
+            l2,
+            txs: 0,
+            batcher_txs: 0,
+            batcher_bytes: 0,
+        };
+        let flush_every_block = batcher.max_channel_duration == 0;
+        let mut channels = ChannelManager::new(batcher);
+        let mut next = 1;
+        for number in 1..=self.l1_blocks {
+            while next < chain.l2.len() && chain.l2[next].l1_origin.number < number {
+                let block = chain.l2[next];
+                let transactions = (0..self.txs_per_block).map(|_| self.tx(&mut rng)).collect();
+                let batch = SingleBatch {
+                    parent_hash: block.block_info.parent_hash,
+                    epoch_num: block.l1_origin.number,
+                    epoch_hash: block.l1_origin.hash,
+                    timestamp: block.block_info.timestamp,
+                    transactions,
+                };
+                channels.add_batch(&Batch::Single(batch), number)?;
+                chain.txs += self.txs_per_block;
+                next += 1;
+            }
+            channels.on_l1_head(number)?;
+            if flush_every_block || number == self.l1_blocks {
+                channels.flush()?;
+            }
+            while let Some(tx) = channels.next_tx() {
+                let hash =
+                    keccak256([number.to_be_bytes(), chain.batcher_txs.to_be_bytes()].concat());
+                let mut tx = match tx.data_availability {
+                    DataAvailability::Calldata => {
+                        let input = tx.calldata();
+                        chain.batcher_bytes += input.len();
+                        L1Transaction { input, ..Default::default() }
+                    }
+                    DataAvailability::Blobs => {
+                        chain.batcher_bytes +=
+                            tx.frames.iter().map(|f| f.encode().len() + 1).sum::<usize>();
+                        let hashes =
+                            tx.blobs()?.into_iter().map(|blob| l1.add_blob(number, blob)).collect();
+                        L1Transaction { blob_versioned_hashes: hashes, ..Default::default() }
+                    }
+                };
+                tx.hash = hash;
+                tx.from = batcher_address;
+                tx.to = Some(inbox);
+                l1.transactions[number as usize].push(tx);
+                chain.batcher_txs += 1;
+            }
+        }
+        chain.l1 = Arc::new(l1);
+        Ok(chain)
+    }
+
+    /// Returns a transaction of the configured size: a type byte, then random bytes followed by
+    /// zeros.
+    fn tx(&self, rng: &mut SplitMix64) -> Bytes {
+        let mut tx = vec![0; self.tx_size as usize];
+        tx[0] = 2;
+        let random = (tx.len() - 1) * (100 - self.zero_bytes_percent as usize) / 100;
+        for chunk in tx[1..=random].chunks_mut(8) {
+            chunk.copy_from_slice(&rng.next().to_le_bytes()[..chunk.len()]);
+        }
+        tx.into()
+    }
+}
+
+/// A synthetic L1 chain and the L2 chain derived from it.
+#[derive(Debug)]
+struct SyntheticChain {
+    config: Arc<RollupConfig>,
+    l1: Arc<SyntheticL1>,
+    l2: Vec<L2BlockInfo>,
+    txs: usize,
+    batcher_txs: u64,
+    batcher_bytes: usize,
+}
+
+impl SyntheticChain {
+    /// Derives the L2 chain, returning the number of transactions derived and the time taken.
+    async fn derive(&self) -> Result<(usize, Duration)> {
+        let mut pipeline = PipelineBuilder::new(self.config.clone(), self.l1.clone())
+            .blob_fetcher(BlobFetcher::new(self.l1.clone()))
+            .build()?;
+        pipeline.reset(self.l2[0], self.l1.blocks[0]).await?;
+
+        let started = Instant::now();
+        let mut cursor = self.l2[0];
+        let mut txs = 0;
+        let mut progress = started;
+        while let Some(&next) = self.l2.get(cursor.block_info.number as usize + 1) {
+            match pipeline.step(cursor).await {
+                StepResult::PreparedAttributes => {
+                    let Some(derived) = pipeline.next() else { continue };
+                    let timestamp = derived.attributes.payload_attributes.timestamp;
+                    ensure!(
+                        timestamp == next.block_info.timestamp,
+                        "derived a block at {timestamp} instead of {next}"
+                    );
+                    let derived_txs = derived.attributes.transactions.unwrap_or_default();
+                    txs += derived_txs
+                        .iter()
+                        .filter(|tx| tx.first() != Some(&DEPOSIT_TX_TYPE))
+                        .count();
+                    cursor = next;
+                    progress = Instant::now();
+                }
+                StepResult::AdvancedOrigin => progress = Instant::now(),
+                // Past the last L1 block, the pipeline waits for the channels still being
+                // decompressed.
+                StepResult::OriginAdvanceErr(err) => {
+                    ensure!(
+                        progress.elapsed() < STALL_TIMEOUT,
+                        "derivation stalled at {cursor}: {err}"
+                    );
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+                StepResult::StepFailed(err) => bail!("derivation failed at {cursor}: {err}"),
+            }
+        }
+        Ok((txs, started.elapsed()))
+    }
+}
+
+/// An L1 chain of empty blocks but for the batcher transactions, served from memory.
+#[derive(Debug, Default)]
+struct SyntheticL1 {
+    headers: Vec<Header>,
+    blocks: Vec<BlockInfo>,
+    numbers: HashMap<B256, usize>,
+    transactions: Vec<Vec<L1Transaction>>,
+    sidecars: Vec<Vec<BlobSidecar>>,
+}
+
+impl SyntheticL1 {
+    /// Creates `count` L1 blocks, the first one at `start`.
+    fn new(start: u64, count: u64) -> Self {
+        let mut l1 = Self::default();
+        let mut parent_hash = B256::ZERO;
+        for number in 0..count {
+            let header = Header {
+                parent_hash,
+                number,
+                timestamp: start + number * L1_BLOCK_TIME,
+                receipts_root: receipts_root(&[]),
+                gas_limit: 30_000_000,
+                base_fee_per_gas: Some(1_000_000_000),
+                blob_gas_used: Some(0),
+                excess_blob_gas: Some(0),
+                parent_beacon_block_root: Some(B256::ZERO),
+                ..Default::default()
+            };
+            let hash = header.hash_slow();
+            l1.blocks.push(BlockInfo::new(hash, number, parent_hash, header.timestamp));
+            l1.numbers.insert(hash, number as usize);
+            l1.headers.push(header);
+            l1.transactions.push(Vec::new());
+            l1.sidecars.push(Vec::new());
+            parent_hash = hash;
+        }
+        l1
+    }
+
+    /// Adds a blob to the L1 block `number`, returning its versioned hash.
+    fn add_blob(&mut self, number: u64, blob: Box<Blob>) -> B256 {
+        let sidecars = &mut self.sidecars[number as usize];
+        let index = sidecars.len() as u64;
+        // Blobs are not checked against their commitment, any unique commitment will do.
+        let mut commitment = [0; 48];
+        commitment[..8].copy_from_slice(&number.to_be_bytes());
+        commitment[8..16].copy_from_slice(&index.to_be_bytes());
+        let kzg_commitment = Bytes48::from(commitment);
+        sidecars.push(BlobSidecar { index, blob, kzg_commitment });
+        kzg_to_versioned_hash(kzg_commitment.as_slice())
+    }
+
+    fn number(&self, hash: B256) -> ProviderResult<usize> {
+        self.numbers
+            .get(&hash)
+            .copied()
+            .ok_or_else(|| ProviderError::NotFound(format!("L1 block {hash}")))
+    }
+}
+
+#[async_trait]
+impl ChainProvider for SyntheticL1 {
+    async fn header_by_hash(&self, hash: B256) -> ProviderResult<Header> {
+        Ok(self.headers[self.number(hash)?].clone())
+    }
+
+    async fn block_info_by_number(&self, number: u64) -> ProviderResult<BlockInfo> {
+        self.blocks
+            .get(number as usize)
+            .copied()
+            .ok_or_else(|| ProviderError::NotFound(format!("L1 block {number}")))
+    }
+
+    async fn receipts_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Receipt>> {
+        self.number(hash)?;
+        Ok(Vec::new())
+    }
+
+    async fn transactions_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Transaction>> {
+        Ok(self.transactions[self.number(hash)?].clone())
+    }
+}
+
+#[async_trait]
+impl BlobProvider for SyntheticL1 {
+    async fn blob_sidecars(&self, block: &BlockInfo, indices: &[u64]) -> Result<Vec<BlobSidecar>> {
+        let sidecars = &self.sidecars[self.number(block.hash)?];
+        Ok(indices.iter().filter_map(|&index| sidecars.get(index as usize).cloned()).collect())
+    }
+}
+
+/// A splitmix64 generator, making the synthetic data reproducible.
+#[derive(Debug)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
@@ -0,0 +1,403 @@
+//! The `hera blob` debugging commands.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use alloy_eips::eip4844::Blob;
+use alloy_primitives::{Address, Bytes, B256, U64};
+use clap::{Args, Subcommand};
+use eyre::{bail, eyre, Result, WrapErr};
+use jsonrpsee::{
+    core::client::ClientT,
+    http_client::{HttpClient, HttpClientBuilder},
+    rpc_params,
+};
+use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    blobs::{decode_blob_data, BeaconClient, BlobFetcher, IndexedBlobHash},
+    config::RollupConfig,
+    protocol::{
+        channel::{
+            decompress_channel, MAX_RLP_BYTES_PER_CHANNEL_BEDROCK, MAX_RLP_BYTES_PER_CHANNEL_FJORD,
+        },
+        Batch, BatchReader, BlockInfo, Channel, ChannelId, Frame,
+    },
+};
+
+/// Debugging commands for batcher blobs.
+#[derive(Debug, Clone, Args)]
+pub struct BlobCommand {
+    /// The blob command to run.
+    #[command(subcommand)]
+    pub command: BlobSubcommand,
+}
+
+/// The `hera blob` subcommands.
+#[derive(Debug, Clone, Subcommand)]
+pub enum BlobSubcommand {
+    /// Fetch and decode the batcher data of an L1 block or transaction, printing its frames,
+    /// channels and batches.
+    Fetch(BlobFetchArgs),
+}
+
+/// Arguments of `hera blob fetch`.
+#[derive(Debug, Clone, Args)]
+pub struct BlobFetchArgs {
+    /// Number of the L1 block to decode the batcher transactions of.
+    #[arg(long, required_unless_present = "tx", conflicts_with = "tx")]
+    pub block: Option<u64>,
+
+    /// Hash of a single batcher transaction to decode.
+    #[arg(long)]
+    pub tx: Option<B256>,
+
+    /// URL of the L1 execution RPC.
+    #[arg(long, env = "L1_RPC_URL")]
+    pub l1_rpc_url: Url,
+
+    /// URL of the L1 beacon API.
+    #[arg(long, env = "L1_BEACON_URL")]
+    pub l1_beacon_url: Url,
+
+    /// Batcher address to filter on, instead of the one of the genesis system config.
+    #[arg(long)]
+    pub batcher: Option<Address>,
+}
+
+impl BlobCommand {
+    /// Runs the command.
+    pub async fn run(&self, config: &RollupConfig) -> Result<()> {
+        match &self.command {
+            BlobSubcommand::Fetch(args) => args.run(config).await,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RpcBlock {
+    pub(crate) hash: B256,
+    pub(crate) number: U64,
+    pub(crate) parent_hash: B256,
+    pub(crate) timestamp: U64,
+    pub(crate) transactions: Vec<RpcTransaction>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RpcTransaction {
+    pub(crate) hash: B256,
+    pub(crate) from: Address,
+    pub(crate) to: Option<Address>,
+    pub(crate) input: Bytes,
+    #[serde(default)]
+    pub(crate) blob_versioned_hashes: Vec<B256>,
+    pub(crate) block_number: Option<U64>,
+}
+
+impl RpcBlock {
+    /// Returns the block info of the block.
+    pub(crate) fn info(&self) -> BlockInfo {
+        BlockInfo::new(self.hash, self.number.to(), self.parent_hash, self.timestamp.to())
+    }
+}
+
+/// Fetches the L1 block `number` with its transactions.
+pub(crate) async fn fetch_block(client: &HttpClient, number: u64) -> Result<RpcBlock> {
+    let block: Option<RpcBlock> = client
+        .request("eth_getBlockByNumber", rpc_params![U64::from(number), true])
+        .await
+        .wrap_err("failed to fetch L1 block")?;
+    block.ok_or_else(|| eyre!("L1 block {number} not found"))
+}
+
+/// Fetches the L1 block including the transaction `hash`, with its transactions.
+pub(crate) async fn fetch_block_of_tx(client: &HttpClient, hash: B256) -> Result<RpcBlock> {
+    let tx: Option<RpcTransaction> = client
+        .request("eth_getTransactionByHash", rpc_params![hash])
+        .await
+        .wrap_err("failed to fetch transaction")?;
+    let tx = tx.ok_or_else(|| eyre!("transaction {hash} not found"))?;
+    let number = tx.block_number.ok_or_else(|| eyre!("transaction {hash} is pending"))?;
+    fetch_block(client, number.to()).await
+}
+
+impl BlobFetchArgs {
+    /// Fetches and decodes the batcher data, printing its structure to stdout.
+    pub async fn run(&self, config: &RollupConfig) -> Result<()> {
+        let client = HttpClientBuilder::default().build(self.l1_rpc_url.as_str())?;
+        let block = match (self.block, self.tx) {
+            (Some(number), _) => fetch_block(&client, number).await?,
+            (None, Some(hash)) => fetch_block_of_tx(&client, hash).await?,
+            (None, None) => bail!("either --block or --tx is required"),
+        };
+        let info = block.info();
+
+        let batcher = self
+            .batcher
+            .or_else(|| config.genesis.system_config.as_ref().map(|s| s.batcher_address))
+            .ok_or_else(|| eyre!("no batcher address in the config, pass --batcher"))?;
+        println!("L1 block {info}, batch inbox {}, batcher {batcher}", config.batch_inbox_address);
+
+        let fetcher =
+            BlobFetcher::new(std::sync::Arc::new(BeaconClient::new(self.l1_beacon_url.clone())));
+        let mut channels = BTreeMap::<ChannelId, Channel>::new();
+        let mut blob_index = 0;
+        for tx in &block.transactions {
+            let hashes = tx
+                .blob_versioned_hashes
+                .iter()
+                .map(|hash| {
+                    blob_index += 1;
+                    IndexedBlobHash { index: blob_index - 1, hash: *hash }
+                })
+                .collect::<Vec<_>>();
+            let is_batcher_tx = tx.to == Some(config.batch_inbox_address) && tx.from == batcher;
+            if !is_batcher_tx || self.tx.is_some_and(|hash| hash != tx.hash) {
+                continue;
+            }
+
+            let mut out = String::new();
+            let data = if hashes.is_empty() {
+                _ = writeln!(out, "\ntx {} (calldata, {} bytes)", tx.hash, tx.input.len());
+                vec![tx.input.clone()]
+            } else {
+                _ = writeln!(out, "\ntx {} ({} blobs)", tx.hash, hashes.len());
+                let blobs = fetcher.blobs(&info, &hashes).await?;
+                hashes
+                    .iter()
+                    .zip(blobs)
+                    .filter_map(|(hash, blob)| write_blob(&mut out, hash, &blob))
+                    .collect()
+            };
+            for data in data {
+                write_frames(&mut out, &data, info, &mut channels);
+            }
+            print!("{out}");
+        }
+
+        let mut out = String::new();
+        write_channels(&mut out, config, &channels, info);
+        print!("{out}");
+        Ok(())
+    }
+}
+
+/// Describes the frames of the batcher data read from the L1 block `info`, then the channels
+/// they form with the batches of the complete ones.
+pub(crate) fn describe_batcher_data(
+    config: &RollupConfig,
+    info: BlockInfo,
+    data: &[Bytes],
+) -> String {
+    let mut out = String::new();
+    let mut channels = BTreeMap::<ChannelId, Channel>::new();
+    for data in data {
+        write_frames(&mut out, data, info, &mut channels);
+    }
+    write_channels(&mut out, config, &channels, info);
+    out
+}
+
+/// Parses the frames of a batcher transaction's data, describing them in `out` and adding them
+/// to their channels.
+pub(crate) fn write_frames(
+    out: &mut String,
+    data: &Bytes,
+    info: BlockInfo,
+    channels: &mut BTreeMap<ChannelId, Channel>,
+) {
+    let frames = match Frame::parse_frames(data) {
+        Ok(frames) => frames,
+        Err(err) => {
+            _ = writeln!(out, "  invalid frames: {err}");
+            return;
+        }
+    };
+    for frame in frames {
+        _ = writeln!(
+            out,
+            "  frame channel={} number={} len={} last={}",
+            frame.id,
+            frame.number,
+            frame.data.len(),
+            frame.is_last
+        );
+        let channel = channels.entry(frame.id).or_insert_with(|| Channel::new(frame.id, info));
+        if let Err(err) = channel.add_frame(frame, info) {
+            _ = writeln!(out, "    dropped: {err}");
+        }
+    }
+}
+
+/// Describes in `out` the channels read from the L1 block `info`, with the batches of the
+/// complete ones.
+pub(crate) fn write_channels(
+    out: &mut String,
+    config: &RollupConfig,
+    channels: &BTreeMap<ChannelId, Channel>,
+    info: BlockInfo,
+) {
+    let fjord = config.is_fjord_active(info.timestamp);
+    for (id, channel) in channels {
+        let Some(data) = channel.frame_data() else {
+            _ = writeln!(
+                out,
+                "\nchannel {id}: incomplete, {} frames, closed={}",
+                channel.frame_count(),
+                channel.is_closed()
+            );
+            continue;
+        };
+        _ = writeln!(out, "\nchannel {id}: {} frames, {} bytes", channel.frame_count(), data.len());
+        write_batches(out, config, &data, fjord);
+    }
+}
+
+/// Decodes the data of a blob, describing it in `out`. Undecodable blobs are reported and
+/// skipped.
+pub(crate) fn write_blob(out: &mut String, hash: &IndexedBlobHash, blob: &Blob) -> Option<Bytes> {
+    match decode_blob_data(blob) {
+        Ok(data) => {
+            _ = writeln!(out, "  blob {} {}: {} bytes", hash.index, hash.hash, data.len());
+            Some(data)
+        }
+        Err(err) => {
+            _ = writeln!(out, "  blob {} {}: undecodable: {err}", hash.index, hash.hash);
+            None
+        }
+    }
+}
+
+fn write_batches(out: &mut String, config: &RollupConfig, data: &[u8], fjord: bool) {
+    let max_size =
+        if fjord { MAX_RLP_BYTES_PER_CHANNEL_FJORD } else { MAX_RLP_BYTES_PER_CHANNEL_BEDROCK };
+    let decompressed = match decompress_channel(data, max_size, fjord) {
+        Ok(decompressed) => decompressed,
+        Err(err) => {
+            _ = writeln!(out, "  decompression failed: {err}");
+            return;
+        }
+    };
+    _ = writeln!(out, "  decompressed to {} bytes", decompressed.len());
+    for batch in BatchReader::new(&decompressed) {
+        _ = match batch {
+            Ok(Batch::Single(batch)) => writeln!(
+                out,
+                "  single batch: timestamp={} epoch={} parent={} txs={}",
+                batch.timestamp,
+                batch.epoch(),
+                batch.parent_hash,
+                batch.transactions.len()
+            ),
+            Ok(Batch::Span(raw)) => {
+                match raw.derive(config.block_time, config.genesis.l2_time, config.l2_chain_id) {
+                    Ok(span) => writeln!(
+                        out,
+                        "  span batch: blocks={} timestamps={}..={} epochs={}..={} txs={}",
+                        span.elements.len(),
+                        span.start_timestamp(),
+                        span.final_timestamp(),
+                        span.elements.first().map(|e| e.epoch_num).unwrap_or_default(),
+                        span.elements.last().map(|e| e.epoch_num).unwrap_or_default(),
+                        raw.txs.len()
+                    ),
+                    Err(err) => writeln!(out, "  invalid span batch: {err}"),
+                }
+            }
+            Err(err) => writeln!(out, "  invalid batch: {err}"),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        batcher::{ChannelOut, Compression, DataAvailability, TxData},
+        protocol::{BlockId, SingleBatch},
+    };
+
+    /// Returns the calldata of a batcher transaction carrying a channel of one single batch,
+    /// split into frames of `frame_size` bytes, with the frames at `skip` left out.
+    fn calldata(id: ChannelId, frame_size: usize, skip: &[u16]) -> Bytes {
+        let mut channel = ChannelOut::new(id, Compression::default(), 100_000, 0);
+        let batch = SingleBatch {
+            parent_hash: B256::repeat_byte(1),
+            epoch_num: 7,
+            epoch_hash: B256::repeat_byte(2),
+            timestamp: 1_700_000_000,
+            transactions: vec![Bytes::from(vec![0x02; 300]), Bytes::from(vec![0x03; 300])],
+        };
+        assert!(channel.add_batch(&Batch::Single(batch)).unwrap());
+        let frames = channel
+            .into_frames(frame_size)
+            .unwrap()
+            .into_iter()
+            .filter(|frame| !skip.contains(&frame.number))
+            .collect();
+        TxData { frames, data_availability: DataAvailability::Calldata }.calldata()
+    }
+
+    fn info() -> BlockInfo {
+        let config = RollupConfig::from_registry(10).unwrap();
+        BlockInfo::new(B256::repeat_byte(9), 100, B256::ZERO, config.genesis.l2_time)
+    }
+
+    #[test]
+    fn describes_a_calldata_channel() {
+        let config = RollupConfig::from_registry(10).unwrap();
+        let id = ChannelId([5; 16]);
+        let out = describe_batcher_data(&config, info(), &[calldata(id, 1_000, &[])]);
+
+        let lines = out.lines().collect::<Vec<_>>();
+        assert!(lines[0].starts_with(&format!("  frame channel={id} number=0 len=")), "{out}");
+        assert!(lines[0].ends_with("last=true"), "{out}");
+        assert!(lines[2].starts_with(&format!("channel {id}: 1 frames, ")), "{out}");
+        assert!(lines[3].starts_with("  decompressed to "), "{out}");
+        assert_eq!(
+            lines[4],
+            format!(
+                "  single batch: timestamp=1700000000 epoch={} parent={} txs=2",
+                BlockId::new(B256::repeat_byte(2), 7),
+                B256::repeat_byte(1)
+            )
+        );
+        assert_eq!(lines.len(), 5, "{out}");
+    }
+
+    #[test]
+    fn reports_incomplete_channels_and_invalid_data() {
+        let config = RollupConfig::from_registry(10).unwrap();
+        let id = ChannelId([6; 16]);
+        let partial = calldata(id, 40, &[1]);
+        let out = describe_batcher_data(&config, info(), &[partial, Bytes::from_static(&[1, 2])]);
+
+        assert!(out.contains(&format!("  frame channel={id} number=0 ")), "{out}");
+        assert!(!out.contains(&format!("  frame channel={id} number=1 ")), "{out}");
+        assert!(out.contains("  invalid frames: unsupported derivation version 1"), "{out}");
+        assert!(out.contains(&format!("channel {id}: incomplete, ")), "{out}");
+        assert!(out.contains("closed=true"), "{out}");
+        assert!(!out.contains("batch:"), "{out}");
+    }
+
+    #[test]
+    fn reports_undecodable_blobs() {
+        let hash = IndexedBlobHash { index: 3, hash: B256::repeat_byte(4) };
+        let mut out = String::new();
+        let blob = crate::blobs::encode_blob_data(b"frames").unwrap();
+        assert_eq!(write_blob(&mut out, &hash, &blob).unwrap(), Bytes::from_static(b"frames"));
+
+        let mut bad = blob;
+        bad[1] = 9;
+        assert_eq!(write_blob(&mut out, &hash, &bad), None);
+        assert_eq!(
+            out,
+            format!(
+                "  blob 3 {0}: 6 bytes\n  blob 3 {0}: undecodable: unsupported blob encoding version 9\n",
+                B256::repeat_byte(4)
+            )
+        );
+    }
+}
@@ -0,0 +1,56 @@
+//! The `hera config` commands, inspecting rollup config files.
+
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+use eyre::Result;
+
+use crate::{cli::RollupConfigOverrides, config::RollupConfig};
+
+/// Commands inspecting rollup config files.
+#[derive(Debug, Clone, Args)]
+pub struct ConfigCommand {
+    /// The config command to run.
+    #[command(subcommand)]
+    pub command: ConfigSubcommand,
+}
+
+/// The `hera config` subcommands.
+#[derive(Debug, Clone, Subcommand)]
+pub enum ConfigSubcommand {
+    /// Parse and check a `rollup.json` file, printing the effective config with the
+    /// `--hera.override.*` flags applied.
+    Validate(ConfigValidateArgs),
+}
+
+/// Arguments of `hera config validate`.
+#[derive(Debug, Clone, Args)]
+pub struct ConfigValidateArgs {
+    /// Path to the rollup config file.
+    pub path: PathBuf,
+}
+
+impl ConfigCommand {
+    /// Runs the command.
+    pub fn run(&self, overrides: &RollupConfigOverrides) -> Result<()> {
+        match &self.command {
+            ConfigSubcommand::Validate(args) => args.run(overrides),
+        }
+    }
+}
+
+impl ConfigValidateArgs {
+    /// Parses and checks the config, printing it to stdout as JSON if it is valid.
+    pub fn run(&self, overrides: &RollupConfigOverrides) -> Result<()> {
+        let mut config = RollupConfig::from_file(&self.path)?;
+        let applied = overrides.apply(&mut config);
+        config.check()?;
+
+        if !applied.is_empty() {
+            eprintln!("applied overrides: {}", applied.join(", "));
+        }
+        println!("{}", serde_json::to_string_pretty(&config)?);
+        eprintln!("{} is valid", self.path.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+//! The `hera decode-batch` debugging command.
+
+use std::{
+    str::FromStr,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alloy_consensus::{Transaction, TxEip4844Variant, TxEnvelope};
+use alloy_eips::eip2718::Decodable2718;
+use alloy_primitives::{Bytes, TxKind, B256};
+use clap::Args;
+use eyre::{bail, eyre, Result, WrapErr};
+use jsonrpsee::http_client::HttpClientBuilder;
+use url::Url;
+
+use crate::{
+    blobs::{BeaconClient, BlobFetcher, IndexedBlobHash},
+    cli::blob::{describe_batcher_data, fetch_block_of_tx, write_blob},
+    config::RollupConfig,
+    protocol::BlockInfo,
+};
+
+/// Arguments of `hera decode-batch`.
+#[derive(Debug, Clone, Args)]
+pub struct DecodeBatchCommand {
+    /// The batcher transaction: either its hash, fetched from L1, or its raw EIP-2718 encoding
+    /// in hex, with the sidecar for blob transactions.
+    pub tx: String,
+
+    /// URL of the L1 execution RPC, to fetch transactions by hash.
+    #[arg(long, env = "L1_RPC_URL")]
+    pub l1_rpc_url: Option<Url>,
+
+    /// URL of the L1 beacon API, to fetch the blobs of blob transactions by hash.
+    #[arg(long, env = "L1_BEACON_URL")]
+    pub l1_beacon_url: Option<Url>,
+}
+
+impl DecodeBatchCommand {
+    /// Decodes the batcher transaction into its frames, channels and batches, printing them to
+    /// stdout.
+    ///
+    /// Channels spanning several transactions are only partially read, and reported incomplete.
+    pub async fn run(&self, config: &RollupConfig) -> Result<()> {
+        let input = Bytes::from_str(self.tx.trim())
+            .wrap_err("expected a transaction hash or a raw transaction in hex")?;
+        let (info, data) = if input.len() == B256::len_bytes() {
+            self.fetch(config, B256::from_slice(&input)).await?
+        } else {
+            decode_raw(config, &input)?
+        };
+
+        print!("{}", describe_batcher_data(config, info, &data));
+        Ok(())
+    }
+
+    /// Fetches the data of the transaction `hash`, and the L1 block including it.
+    async fn fetch(&self, config: &RollupConfig, hash: B256) -> Result<(BlockInfo, Vec<Bytes>)> {
+        let url = self
+            .l1_rpc_url
+            .as_ref()
+            .ok_or_else(|| eyre!("--l1-rpc-url is required to fetch a transaction by hash"))?;
+        let client = HttpClientBuilder::default().build(url.as_str())?;
+        let block = fetch_block_of_tx(&client, hash).await?;
+        let info = block.info();
+        println!("L1 block {info}, batch inbox {}", config.batch_inbox_address);
+
+        // Blobs are indexed by their position among all the blobs of the block.
+        let mut blob_index = 0;
+        let mut hashes = Vec::new();
+        let mut found = None;
+        for tx in &block.transactions {
+            for blob_hash in &tx.blob_versioned_hashes {
+                if tx.hash == hash {
+                    hashes.push(IndexedBlobHash { index: blob_index, hash: *blob_hash });
+                }
+                blob_index += 1;
+            }
+            if tx.hash == hash {
+                found = Some(tx);
+            }
+        }
+        let tx = found.ok_or_else(|| eyre!("transaction {hash} not found in L1 block {info}"))?;
+        if tx.to != Some(config.batch_inbox_address) {
+            println!("warning: transaction is not sent to the batch inbox");
+        }
+
+        if hashes.is_empty() {
+            println!("\ntx {} from {} (calldata, {} bytes)", tx.hash, tx.from, tx.input.len());
+            return Ok((info, vec![tx.input.clone()]));
+        }
+        println!("\ntx {} from {} ({} blobs)", tx.hash, tx.from, hashes.len());
+        let url = self.l1_beacon_url.as_ref().ok_or_else(|| {
+            eyre!("--l1-beacon-url is required to fetch the blobs of a blob transaction")
+        })?;
+        let fetcher = BlobFetcher::new(Arc::new(BeaconClient::new(url.clone())));
+        let blobs = fetcher.blobs(&info, &hashes).await?;
+        let mut out = String::new();
+        let data = hashes
+            .iter()
+            .zip(blobs)
+            .filter_map(|(hash, blob)| write_blob(&mut out, hash, &blob))
+            .collect();
+        print!("{out}");
+        Ok((info, data))
+    }
+}
+
+/// Decodes the data of a raw transaction. Its L1 block is unknown, so channels are read as if
+/// included now.
+fn decode_raw(config: &RollupConfig, raw: &[u8]) -> Result<(BlockInfo, Vec<Bytes>)> {
+    let tx = TxEnvelope::decode_2718(&mut &raw[..]).wrap_err("invalid raw transaction")?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let info = BlockInfo { timestamp: now, ..Default::default() };
+    if tx.to() != TxKind::Call(config.batch_inbox_address) {
+        println!("warning: transaction is not sent to the batch inbox");
+    }
+
+    let TxEnvelope::Eip4844(signed) = &tx else {
+        println!("tx {} (calldata, {} bytes)", tx.tx_hash(), tx.input().len());
+        return Ok((info, vec![Bytes::copy_from_slice(tx.input())]));
+    };
+    let TxEip4844Variant::TxEip4844WithSidecar(with_sidecar) = signed.tx() else {
+        bail!("raw blob transaction has no sidecar, pass its hash to fetch the blobs instead");
+    };
+    let hashes = &with_sidecar.tx.blob_versioned_hashes;
+    let blobs = &with_sidecar.sidecar.blobs;
+    if hashes.len() != blobs.len() {
+        bail!("blob transaction has {} versioned hashes for {} blobs", hashes.len(), blobs.len());
+    }
+    println!("tx {} ({} blobs)", tx.tx_hash(), blobs.len());
+    let mut out = String::new();
+    let data = hashes
+        .iter()
+        .zip(blobs)
+        .enumerate()
+        .filter_map(|(index, (hash, blob))| {
+            write_blob(&mut out, &IndexedBlobHash { index: index as u64, hash: *hash }, blob)
+        })
+        .collect();
+    print!("{out}");
+    Ok((info, data))
+}
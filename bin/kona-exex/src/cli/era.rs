@@ -0,0 +1,70 @@
+//! The `hera export-era` command, archiving the derived chain.
+
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::{eyre, Result};
+
+use crate::{
+    cli::HeraArgs,
+    config::RollupConfig,
+    era::{EraExporter, DEFAULT_BLOCKS_PER_FILE},
+    storage::DataDir,
+};
+
+/// Arguments of `hera export-era`.
+///
+/// Blocks are read from the L2 execution layer at `--hera.l2-rpc-url` with `debug_getRawBlock`,
+/// and anchored to the L1 blocks they were first derived from as recorded in the safe head
+/// database, when one is configured.
+#[derive(Debug, Clone, Args)]
+pub struct ExportEraCommand {
+    /// Directory the archive files are written to.
+    #[arg(long)]
+    pub out: PathBuf,
+
+    /// The first L2 block to export.
+    #[arg(long, default_value_t = 0)]
+    pub from_block: u64,
+
+    /// The last L2 block to export, by default the latest safe head of the safe head database.
+    #[arg(long)]
+    pub to_block: Option<u64>,
+
+    /// Number of L2 blocks per archive file.
+    #[arg(long, default_value_t = DEFAULT_BLOCKS_PER_FILE)]
+    pub blocks_per_file: u64,
+}
+
+impl ExportEraCommand {
+    /// Exports the blocks of the chain of `config`, printing the paths of the files written.
+    pub async fn run(
+        &self,
+        args: &HeraArgs,
+        config: &RollupConfig,
+        datadir: Option<&DataDir>,
+    ) -> Result<()> {
+        let endpoint = args
+            .l2_rpc_endpoint()
+            .ok_or_else(|| eyre!("--hera.l2-rpc-url is required to export blocks"))?;
+        let safe_db = args.safe_db(datadir)?;
+        let latest = match &safe_db {
+            Some(safe_db) => safe_db.safe_head_at(u64::MAX)?.map(|at| at.safe_head.number),
+            None => None,
+        };
+        let to = self.to_block.or(latest).ok_or_else(|| {
+            eyre!("--to-block is required without a safe head database to export up to")
+        })?;
+
+        let network = format!("hera-{}", config.l2_chain_id);
+        let mut exporter = EraExporter::new(endpoint.rpc_client()?, network)
+            .with_blocks_per_file(self.blocks_per_file);
+        if let Some(safe_db) = safe_db {
+            exporter = exporter.with_safe_db(safe_db);
+        }
+        for path in exporter.export(self.from_block, to, &self.out).await? {
+            println!("{}", path.display());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,664 @@
+//! Command line arguments of the Hera ExEx.
+
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+
+use clap::{ArgGroup, Args};
+use eyre::{Result, WrapErr};
+use tracing::info;
+use url::Url;
+
+use crate::{
+    alert::{WebhookAlerter, DEFAULT_ALERT_REORG_DEPTH},
+    blobs::BlobCache,
+    clock::{ClockMonitor, DEFAULT_CLOCK_SKEW_WARNING, DEFAULT_MAX_CLOCK_SKEW},
+    config::RollupConfig,
+    derive::{DEFAULT_DECOMPRESSION_QUEUE_SIZE, DEFAULT_DECOMPRESSION_WORKERS},
+    driver::{DerivationStart, DEFAULT_UNSAFE_SYNC_INTERVAL},
+    endpoint::{Endpoint, HttpHeader, DEFAULT_HTTP_TIMEOUT, DEFAULT_MAX_CONNECTIONS},
+    engine::{EngineClient, JwtSecret},
+    era::TrustedOutput,
+    exex::{
+        NotificationMode, NotificationWal, DEFAULT_FAILOVER_STALL_TIMEOUT,
+        DEFAULT_L1_POLL_INTERVAL, DEFAULT_MAX_REORG_DEPTH,
+    },
+    l1::DEFAULT_MAX_LOG_SCAN_RANGE,
+    safedb::SafeDb,
+    sequencer::{
+        BuilderFallback, DaThrottle, ExternalBuilder, MinerClient, ThrottleConfig,
+        DEFAULT_BUILDER_TIMEOUT, DEFAULT_THROTTLE_BLOCK_SIZE, DEFAULT_THROTTLE_THRESHOLD,
+        DEFAULT_THROTTLE_TX_SIZE,
+    },
+    stats::ThroughputStats,
+    storage::{DataDir, ReadOnlyStorage, SqliteStorage, Storage},
+    supervisor::{RestartConfig, RestartPolicy, Supervisor, WorkerRuntime, DEFAULT_MAX_RESTARTS},
+    validation::ValidationFailurePolicy,
+};
+
+mod audit;
+pub use audit::AuditCommand;
+
+mod batcher;
+pub use batcher::BatcherArgs;
+
+mod bench;
+pub use bench::BenchCommand;
+
+mod blob;
+pub use blob::{BlobCommand, BlobFetchArgs, BlobSubcommand};
+
+mod config;
+pub use config::{ConfigCommand, ConfigSubcommand, ConfigValidateArgs};
+
+mod era;
+pub use era::ExportEraCommand;
+
+mod decode;
+pub use decode::DecodeBatchCommand;
+
+mod monitor;
+pub use monitor::MonitorCommand;
+
+mod overrides;
+pub use overrides::RollupConfigOverrides;
+
+mod p2p;
+pub use p2p::{P2pArgs, DEFAULT_P2P_PORT};
+
+mod stats;
+pub use stats::StatsCommand;
+
+mod summary;
+
+mod validate;
+pub use validate::ValidateAttributesCommand;
+
+/// The default L2 chain ID, OP Mainnet.
+pub const DEFAULT_L2_CHAIN_ID: u64 = 10;
+
+/// Arguments of the Hera rollup node, namespaced under `--hera.*`.
+#[derive(Debug, Clone, Args)]
+#[command(
+    next_help_heading = "Hera",
+    group(ArgGroup::new("blob_cache").args(["datadir", "blob_cache_path"]).multiple(true))
+)]
+pub struct HeraArgs {
+    /// Chain ID of the L2 network.
+    #[arg(long = "hera.l2-chain-id", default_value_t = DEFAULT_L2_CHAIN_ID)]
+    pub l2_chain_id: u64,
+
+    /// Path to a custom L2 rollup configuration file, used instead of the superchain registry.
+    #[arg(long = "hera.l2-config-file")]
+    pub l2_config_file: Option<PathBuf>,
+
+    /// Directory to store the node's data in, in a subdirectory per L2 chain ID. Enables the
+    /// blob cache and audit log, unless their paths are given.
+    #[arg(long = "hera.datadir")]
+    pub datadir: Option<PathBuf>,
+
+    /// URL of the L1 execution layer RPC that derivation reads L1 blocks, receipts and batcher
+    /// transactions from, and that new L1 blocks are polled on.
+    #[arg(long = "hera.l1-rpc-url")]
+    pub l1_rpc_url: Option<Url>,
+
+    /// Header sent with every request to the L1 RPC, as `Name: value`, e.g. to authenticate
+    /// with a hosted provider. Can be repeated.
+    #[arg(long = "hera.l1-rpc-header", requires = "l1_rpc_url")]
+    pub l1_rpc_headers: Vec<HttpHeader>,
+
+    /// URL of the L1 beacon node serving the blob sidecars of batcher transactions.
+    #[arg(long = "hera.l1-beacon-url")]
+    pub l1_beacon_url: Option<Url>,
+
+    /// Header sent with every request to the L1 beacon node, as `Name: value`. Can be repeated.
+    #[arg(long = "hera.l1-beacon-header", requires = "l1_beacon_url")]
+    pub l1_beacon_headers: Vec<HttpHeader>,
+
+    /// Interval between two polls of the L1 head, in seconds.
+    #[arg(long = "hera.l1-poll-interval", default_value_t = DEFAULT_L1_POLL_INTERVAL.as_secs())]
+    pub l1_poll_interval: u64,
+
+    /// URL of a secondary L1 RPC that new L1 blocks are followed on and L1 data is read from
+    /// while the primary notifications are stalled, e.g. when the host node is wedged.
+    #[arg(long = "hera.l1-failover.rpc-url")]
+    pub l1_failover_rpc_url: Option<Url>,
+
+    /// Time without new L1 notifications after which the secondary L1 RPC is followed, in
+    /// seconds.
+    #[arg(
+        long = "hera.l1-failover.stall-timeout",
+        default_value_t = DEFAULT_FAILOVER_STALL_TIMEOUT.as_secs(),
+        requires = "l1_failover_rpc_url"
+    )]
+    pub l1_failover_stall_timeout: u64,
+
+    /// Find the L1 blocks with deposits, system config updates or Alt-DA challenges with ranged
+    /// `eth_getLogs` queries, and skip downloading the receipts of the other finalized blocks,
+    /// speeding up derivation far behind the L1 head.
+    #[arg(long = "hera.l1-log-scan.enabled")]
+    pub l1_log_scan_enabled: bool,
+
+    /// Maximum number of L1 blocks scanned by one `eth_getLogs` query, lowered while the L1 RPC
+    /// refuses ranges as large.
+    #[arg(
+        long = "hera.l1-log-scan.max-range",
+        default_value_t = DEFAULT_MAX_LOG_SCAN_RANGE,
+        requires = "l1_log_scan_enabled"
+    )]
+    pub l1_log_scan_max_range: u64,
+
+    /// URL of the L2 execution layer RPC that derived payloads are validated against and output
+    /// roots are computed from.
+    #[arg(long = "hera.l2-rpc-url")]
+    pub l2_rpc_url: Option<Url>,
+
+    /// Header sent with every request to the L2 RPC, as `Name: value`. Can be repeated.
+    #[arg(long = "hera.l2-rpc-header", requires = "l2_rpc_url")]
+    pub l2_rpc_headers: Vec<HttpHeader>,
+
+    /// URL of a trusted L2 RPC polled for new unsafe blocks, which are inserted into the L2
+    /// execution layer between safe head updates, e.g. without gossip. The node must serve
+    /// `debug_getRawBlock`.
+    #[arg(long = "hera.unsafe-sync.rpc-url")]
+    pub unsafe_sync_rpc_url: Option<Url>,
+
+    /// Interval between two polls of the trusted L2 RPC for new unsafe blocks, in seconds.
+    #[arg(
+        long = "hera.unsafe-sync.interval",
+        default_value_t = DEFAULT_UNSAFE_SYNC_INTERVAL.as_secs(),
+        requires = "unsafe_sync_rpc_url"
+    )]
+    pub unsafe_sync_interval: u64,
+
+    /// URL of the authenticated Engine API of the L2 execution layer.
+    #[arg(long = "hera.l2-engine-url")]
+    pub l2_engine_url: Option<Url>,
+
+    /// Path to the hex-encoded JWT secret shared with the L2 execution layer's Engine API.
+    #[arg(long = "hera.l2-engine-jwt-secret")]
+    pub l2_engine_jwt_secret: Option<PathBuf>,
+
+    /// Data directory of the L2 execution layer, searched for its JWT secret when
+    /// `--hera.l2-engine-jwt-secret` is not set, before the default data directories of reth and
+    /// geth.
+    #[arg(long = "hera.l2-engine-datadir", conflicts_with = "l2_engine_jwt_secret")]
+    pub l2_engine_datadir: Option<PathBuf>,
+
+    /// Timeout of a request to the L1 and L2 RPCs, the Engine API and the beacon node, in
+    /// seconds.
+    #[arg(long = "hera.http.timeout", default_value_t = DEFAULT_HTTP_TIMEOUT.as_secs())]
+    pub http_timeout: u64,
+
+    /// Maximum number of connections to each of the L1 and L2 RPCs, the Engine API and the
+    /// beacon node, shared by every subsystem talking to it.
+    #[arg(long = "hera.http.max-connections", default_value_t = DEFAULT_MAX_CONNECTIONS)]
+    pub http_max_connections: usize,
+
+    /// How L1 chain notifications are handled: `full` to derive from every L1 block, or
+    /// `head-only` to skip history and only follow the chain head, e.g. to run Hera as a
+    /// monitoring or relay component.
+    #[arg(long = "hera.exex.mode", default_value = "full")]
+    pub exex_mode: NotificationMode,
+
+    /// Recompute the receipts root and logs bloom of every committed L1 block from the execution
+    /// outcome notified by reth, and stop on a mismatch with the block header, catching
+    /// execution bugs before they corrupt derivation.
+    #[arg(long = "hera.exex.paranoid")]
+    pub exex_paranoid: bool,
+
+    /// Record the chain notifications received in a write-ahead log, `notifications.db` in the
+    /// chain data directory, and backfill the L1 blocks missed since the last one from the L1
+    /// RPC, e.g. after a crash.
+    #[arg(long = "hera.exex.wal", requires = "datadir")]
+    pub exex_wal: bool,
+
+    /// Derive this many L1 blocks behind the tip notified by the host, delaying the unsafe head
+    /// in exchange for not deriving from blocks that shallow L1 reorgs revert soon after.
+    #[arg(long = "hera.exex-lag", default_value_t = 0)]
+    pub exex_lag: u64,
+
+    /// The maximum depth of an L1 reorg handled without operator confirmation. Derivation halts
+    /// on deeper reorgs until they are confirmed with `admin_confirmReorg`, protecting against a
+    /// catastrophic rollback caused by a misbehaving L1 source.
+    #[arg(long = "hera.max-reorg-depth", default_value_t = DEFAULT_MAX_REORG_DEPTH)]
+    pub max_reorg_depth: u64,
+
+    /// Run the pipeline and validation without ever updating the execution layer's forkchoice,
+    /// logging a summary of what would have been done instead.
+    #[arg(long = "hera.dry-run")]
+    pub dry_run: bool,
+
+    /// URL of an op-node RPC to continuously compare Hera's safe and finalized heads against.
+    #[arg(long = "hera.shadow.op-node-url")]
+    pub shadow_op_node_url: Option<Url>,
+
+    /// Interval between two shadow comparisons, in seconds.
+    #[arg(long = "hera.shadow.interval", default_value_t = 12)]
+    pub shadow_interval: u64,
+
+    /// URL of an L1 RPC whose pending block is watched for batcher transactions, reporting the
+    /// L2 blocks they cover as a speculative safe head in the sync status.
+    #[arg(long = "hera.mempool-preview.l1-rpc-url")]
+    pub mempool_preview_l1_rpc_url: Option<Url>,
+
+    /// Interval between two mempool preview polls, in seconds.
+    #[arg(long = "hera.mempool-preview.interval", default_value_t = 4)]
+    pub mempool_preview_interval: u64,
+
+    /// Run as a sequencer, building new unsafe blocks on top of the unsafe head.
+    #[arg(long = "hera.sequencer.enabled", conflicts_with = "dry_run")]
+    pub sequencer_enabled: bool,
+
+    /// Start the sequencer stopped, waiting for `admin_startSequencer`.
+    #[arg(long = "hera.sequencer.stopped")]
+    pub sequencer_stopped: bool,
+
+    /// Skew of the local clock from the timestamps of arriving blocks past which the sequencer
+    /// refuses to build blocks, in seconds.
+    #[arg(long = "hera.clock.max-skew", default_value_t = DEFAULT_MAX_CLOCK_SKEW)]
+    pub clock_max_skew: u64,
+
+    /// Skew of the local clock past which a warning is logged, in seconds.
+    #[arg(long = "hera.clock.skew-warning", default_value_t = DEFAULT_CLOCK_SKEW_WARNING)]
+    pub clock_skew_warning: u64,
+
+    /// URL of the sequencer execution client's RPC, on which `miner_setMaxDASize` throttles the
+    /// transactions of sequenced blocks while the batcher backlog is over the threshold.
+    #[arg(long = "hera.sequencer.throttle.rpc-url", requires = "sequencer_enabled")]
+    pub sequencer_throttle_rpc_url: Option<Url>,
+
+    /// Batcher backlog past which sequenced blocks are throttled, in bytes of transactions of
+    /// unsafe blocks.
+    #[arg(
+        long = "hera.sequencer.throttle.threshold",
+        default_value_t = DEFAULT_THROTTLE_THRESHOLD
+    )]
+    pub sequencer_throttle_threshold: u64,
+
+    /// Maximum size of a transaction's data while throttled.
+    #[arg(long = "hera.sequencer.throttle.tx-size", default_value_t = DEFAULT_THROTTLE_TX_SIZE)]
+    pub sequencer_throttle_tx_size: u64,
+
+    /// Maximum size of a block's transaction data while throttled.
+    #[arg(
+        long = "hera.sequencer.throttle.block-size",
+        default_value_t = DEFAULT_THROTTLE_BLOCK_SIZE
+    )]
+    pub sequencer_throttle_block_size: u64,
+
+    /// Engine API URL of an external block builder, e.g. rollup-boost, that block building is
+    /// delegated to. Its payloads are sealed when they arrive in time and the local engine
+    /// accepts them.
+    #[arg(long = "hera.sequencer.builder-url", requires = "sequencer_enabled")]
+    pub sequencer_builder_url: Option<Url>,
+
+    /// Path to the hex-encoded JWT secret of the builder's Engine API. Defaults to the secret of
+    /// the L2 execution layer.
+    #[arg(long = "hera.sequencer.builder-jwt-secret", requires = "sequencer_builder_url")]
+    pub sequencer_builder_jwt_secret: Option<PathBuf>,
+
+    /// Time the builder has to answer a request, in milliseconds.
+    #[arg(
+        long = "hera.sequencer.builder-timeout",
+        default_value_t = DEFAULT_BUILDER_TIMEOUT.as_millis() as u64
+    )]
+    pub sequencer_builder_timeout: u64,
+
+    /// What to do when the builder fails, times out or builds a payload the local engine
+    /// rejects: `local` to seal the local payload instead, or `none` to fail the block.
+    #[arg(long = "hera.sequencer.builder-fallback", default_value = "local")]
+    pub sequencer_builder_fallback: BuilderFallback,
+
+    /// URL of the op-conductor RPC managing this sequencer in a high-availability cluster.
+    #[arg(long = "hera.conductor.rpc-url", requires = "sequencer_enabled")]
+    pub conductor_rpc_url: Option<Url>,
+
+    /// Address of Hera's own RPC server.
+    #[arg(long = "hera.rpc.addr", default_value = "127.0.0.1:9545")]
+    pub rpc_addr: SocketAddr,
+
+    /// Register Hera's RPC namespaces on the RPC server of the host reth node instead of serving
+    /// them on `--hera.rpc.addr`.
+    #[arg(long = "hera.rpc.attach-to-reth")]
+    pub rpc_attach_to_reth: bool,
+
+    /// Prefix Hera's RPC methods with the L2 chain id, e.g. `chain10_optimism_syncStatus`, so
+    /// that the nodes of several chains can share the RPC server of the host reth node.
+    #[arg(long = "hera.rpc.chain-prefix")]
+    pub rpc_chain_prefix: bool,
+
+    /// URL of the sequencer's execution client RPC that `eth_sendRawTransaction` calls on Hera's
+    /// RPC are forwarded to. Not available with `--hera.rpc.attach-to-reth`, where reth serves the
+    /// `eth_*` namespace.
+    #[arg(long = "hera.tx-forward.sequencer-url", conflicts_with = "rpc_attach_to_reth")]
+    pub tx_forward_sequencer_url: Option<Url>,
+
+    /// Comma-separated execution client RPCs of backup sequencers, also receiving every
+    /// forwarded transaction.
+    #[arg(
+        long = "hera.tx-forward.backup-urls",
+        value_delimiter = ',',
+        requires = "tx_forward_sequencer_url"
+    )]
+    pub tx_forward_backup_urls: Vec<Url>,
+
+    /// Path to an SQLite database recording derived blocks, head advancements, validation
+    /// failures and reorgs, for inspection with `hera audit`. Defaults to `audit.db` in the
+    /// chain data directory.
+    #[arg(long = "hera.audit-log")]
+    pub audit_log: Option<PathBuf>,
+
+    /// Path to an SQLite database recording the L2 safe head at each L1 block, served through
+    /// `optimism_safeHeadAtL1Block`. Defaults to `safedb.db` in the chain data directory.
+    #[arg(long = "hera.safe-db")]
+    pub safe_db: Option<PathBuf>,
+
+    /// Path to an SQLite database recording the hourly derivation throughput, for inspection
+    /// with `hera stats`. Defaults to `stats.db` in the chain data directory.
+    #[arg(long = "hera.stats-db")]
+    pub stats_db: Option<PathBuf>,
+
+    /// What to do when a derived payload fails validation: `panic` to stop the node, `halt` to
+    /// halt derivation awaiting operator action, `log` to only log it, or `resync` to reset the
+    /// pipeline to the safe head and derive again.
+    #[arg(long = "hera.validation.on-failure", default_value = "halt")]
+    pub validation_on_failure: ValidationFailurePolicy,
+
+    /// Number of a known-good L2 block to force derivation to start on top of, instead of the
+    /// genesis. For expert operators only, e.g. after a chain migration: the block is trusted as
+    /// the safe head without any check.
+    #[arg(long = "hera.derivation-start-l2-block")]
+    pub derivation_start_l2_block: Option<u64>,
+
+    /// Number of the L1 block to start reading batcher data from with
+    /// `--hera.derivation-start-l2-block`, instead of the L1 origin of that block.
+    #[arg(long = "hera.derivation-start-l1-origin", requires = "derivation_start_l2_block")]
+    pub derivation_start_l1_origin: Option<u64>,
+
+    /// Directory of archive files written by `hera export-era` to import into the L2 execution
+    /// layer on startup, deriving on top of the last imported block covered by a
+    /// `--hera.import.output-root` instead of syncing it from L1.
+    #[arg(
+        long = "hera.import.era-dir",
+        conflicts_with_all = ["derivation_start_l2_block", "dry_run"]
+    )]
+    pub import_era_dir: Option<PathBuf>,
+
+    /// Output root to check the imported blocks against, as `<l2 block number>:<output root>`,
+    /// e.g. as proposed to the `L2OutputOracle`. The safe head only moves to imported blocks
+    /// covered by one. May be repeated.
+    #[arg(long = "hera.import.output-root", requires = "import_era_dir")]
+    pub import_output_roots: Vec<TrustedOutput>,
+
+    /// Number of threads decompressing channels, off the derivation task.
+    #[arg(
+        long = "hera.derive.decompression-workers",
+        default_value_t = DEFAULT_DECOMPRESSION_WORKERS
+    )]
+    pub decompression_workers: usize,
+
+    /// Maximum number of channels queued for decompression at once.
+    #[arg(
+        long = "hera.derive.decompression-queue-size",
+        default_value_t = DEFAULT_DECOMPRESSION_QUEUE_SIZE
+    )]
+    pub decompression_queue_size: usize,
+
+    /// Webhook URL that critical events are posted to: divergences from the shadowed op-node,
+    /// validation failures, safe head stalls and deep L2 reorgs. Works with Slack and Discord
+    /// webhooks and the Telegram `sendMessage` method.
+    #[arg(long = "hera.alert.webhook-url")]
+    pub alert_webhook_url: Option<Url>,
+
+    /// Time after which a safe head that has not advanced is alerted, in seconds.
+    #[arg(long = "hera.alert.stall-timeout", default_value_t = 600)]
+    pub alert_stall_timeout: u64,
+
+    /// Depth, in L2 blocks, above which reorgs are alerted.
+    #[arg(long = "hera.alert.reorg-depth", default_value_t = DEFAULT_ALERT_REORG_DEPTH)]
+    pub alert_reorg_depth: u64,
+
+    /// When crashed node tasks are restarted: `never`, `on-failure` or `always`.
+    #[arg(long = "hera.supervisor.restart-policy", default_value = "on-failure")]
+    pub restart_policy: RestartPolicy,
+
+    /// Number of restarts after which a crashing task stops the node.
+    #[arg(long = "hera.supervisor.max-restarts", default_value_t = DEFAULT_MAX_RESTARTS)]
+    pub max_restarts: u32,
+
+    /// Delay before the first restart of a crashed task in seconds, doubled on every further
+    /// restart.
+    #[arg(long = "hera.supervisor.restart-backoff", default_value_t = 1)]
+    pub restart_backoff: u64,
+
+    /// Number of threads of a runtime dedicated to the L1 watcher, derivation and blob
+    /// backfill. By default they share the runtime of the host node, which they can starve
+    /// while catching up.
+    #[arg(long = "hera.worker-threads")]
+    pub worker_threads: Option<usize>,
+
+    /// Address to serve the metrics of a standalone node on, for Prometheus to scrape. Hosts
+    /// running Hera as an ExEx export them with their own recorder.
+    #[arg(long = "hera.metrics.addr")]
+    pub metrics_addr: Option<SocketAddr>,
+
+    /// Export the metrics of the tokio runtimes, the poll times of the node's tasks and the
+    /// depths of the channels between stages, to spot tasks starving the executor.
+    /// A standalone node serves them on `--hera.metrics.addr`.
+    #[arg(long = "hera.metrics.runtime")]
+    pub metrics_runtime: bool,
+
+    /// Path to an SQLite cache of blob sidecars, served before the beacon node. Defaults to
+    /// `blobs.db` in the chain data directory.
+    #[arg(long = "hera.blob-cache.path")]
+    pub blob_cache_path: Option<PathBuf>,
+
+    /// Serves the blob cache without writing to it, e.g. when it is shared with another node.
+    #[arg(
+        long = "hera.blob-cache.read-only",
+        requires = "blob_cache",
+        conflicts_with = "blob_archiver_url"
+    )]
+    pub blob_cache_read_only: bool,
+
+    /// URL of a blob archiver serving the beacon blob sidecars API, to backfill the blob cache
+    /// from.
+    #[arg(long = "hera.blob-archiver.url", requires = "blob_cache")]
+    pub blob_archiver_url: Option<Url>,
+
+    /// Number of the first L1 block to backfill the blob cache from the archiver from, up to the
+    /// L1 head. An interrupted backfill resumes where it stopped.
+    #[arg(long = "hera.blob-backfill.from-block", requires = "blob_archiver_url")]
+    pub blob_backfill_from_block: Option<u64>,
+
+    /// Run as a system service: report readiness and shutdowns to systemd through
+    /// `NOTIFY_SOCKET`, and upgrade in place on `SIGUSR2` by executing the binary again.
+    #[arg(long = "hera.daemon")]
+    pub daemon: bool,
+
+    /// Path to a file to write the process ID of the node to while it runs.
+    #[arg(long = "hera.daemon.pid-file", requires = "daemon")]
+    pub daemon_pid_file: Option<PathBuf>,
+
+    /// Channel and frame settings of the batcher.
+    #[command(flatten)]
+    pub batcher: BatcherArgs,
+
+    /// Identity, listening and NAT traversal settings of the p2p stack.
+    #[command(flatten)]
+    pub p2p: P2pArgs,
+
+    /// Per-field overrides applied on top of the registry or file config.
+    #[command(flatten)]
+    pub overrides: RollupConfigOverrides,
+}
+
+impl HeraArgs {
+    /// Returns the effective rollup config: the registry or file config with overrides applied.
+    pub fn rollup_config(&self) -> Result<RollupConfig> {
+        let mut config = RollupConfig::load(self.l2_chain_id, self.l2_config_file.as_deref())?;
+        let applied = self.overrides.apply(&mut config);
+        if !applied.is_empty() {
+            info!(target: "hera::cli", overrides = ?applied, "Applied rollup config overrides");
+        }
+        Ok(config)
+    }
+
+    /// Returns the endpoint of the L1 RPC, if set.
+    pub fn l1_rpc_endpoint(&self) -> Option<Endpoint> {
+        self.l1_rpc_url.as_ref().map(|url| self.endpoint(url, &self.l1_rpc_headers))
+    }
+
+    /// Returns the endpoint of the L1 beacon node, if set.
+    pub fn l1_beacon_endpoint(&self) -> Option<Endpoint> {
+        self.l1_beacon_url.as_ref().map(|url| self.endpoint(url, &self.l1_beacon_headers))
+    }
+
+    /// Returns the endpoint of the L2 RPC, if set.
+    pub fn l2_rpc_endpoint(&self) -> Option<Endpoint> {
+        self.l2_rpc_url.as_ref().map(|url| self.endpoint(url, &self.l2_rpc_headers))
+    }
+
+    /// Returns the endpoint of the L2 Engine API, if set. Requests are authenticated with the
+    /// JWT secret instead of headers.
+    pub fn l2_engine_endpoint(&self) -> Option<Endpoint> {
+        self.l2_engine_url.as_ref().map(|url| self.endpoint(url, &[]))
+    }
+
+    /// Loads the JWT secret of the L2 Engine API: the one at `--hera.l2-engine-jwt-secret` if
+    /// set, otherwise the first found in [`Self::l2_engine_datadirs`], returned with its path.
+    pub fn l2_engine_jwt_secret(&self) -> Result<(JwtSecret, PathBuf)> {
+        match &self.l2_engine_jwt_secret {
+            Some(path) => Ok((JwtSecret::from_file(path)?, path.clone())),
+            None => JwtSecret::discover(&self.l2_engine_datadirs()).wrap_err(
+                "--hera.l2-engine-jwt-secret is not set and no JWT secret was found in the data \
+                 directories of the L2 execution layer",
+            ),
+        }
+    }
+
+    /// Returns the data directories searched for the JWT secret of the L2 Engine API:
+    /// `--hera.l2-engine-datadir`, then the default data directories of reth for the chain and
+    /// of geth.
+    pub fn l2_engine_datadirs(&self) -> Vec<PathBuf> {
+        let mut datadirs: Vec<_> = self.l2_engine_datadir.iter().cloned().collect();
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".local/share")));
+        datadirs.extend(data_home.map(|dir| dir.join("reth").join(self.l2_chain_id.to_string())));
+        datadirs.extend(home.map(|home| home.join(".ethereum")));
+        datadirs
+    }
+
+    /// Returns the endpoint at `url` with the HTTP settings of the node.
+    pub fn endpoint(&self, url: &Url, headers: &[HttpHeader]) -> Endpoint {
+        Endpoint::new(url.clone())
+            .with_timeout(Duration::from_secs(self.http_timeout))
+            .with_max_connections(self.http_max_connections)
+            .with_headers(headers.iter().cloned())
+    }
+
+    /// Returns the webhook alerter, if alerting is enabled.
+    pub fn alerter(&self) -> Option<WebhookAlerter> {
+        self.alert_webhook_url
+            .clone()
+            .map(|url| WebhookAlerter::new(url).with_reorg_depth(self.alert_reorg_depth))
+    }
+
+    /// Returns the monitor of the local clock's skew.
+    pub fn clock_monitor(&self) -> ClockMonitor {
+        ClockMonitor::new(self.clock_max_skew).with_warning(self.clock_skew_warning)
+    }
+
+    /// Returns the DA throttle of the sequencer, if enabled.
+    pub fn sequencer_throttle(&self) -> Result<Option<DaThrottle>> {
+        let Some(url) = &self.sequencer_throttle_rpc_url else { return Ok(None) };
+        let config = ThrottleConfig {
+            threshold: self.sequencer_throttle_threshold,
+            max_tx_size: self.sequencer_throttle_tx_size,
+            max_block_size: self.sequencer_throttle_block_size,
+        };
+        Ok(Some(DaThrottle::new(config, Box::new(MinerClient::new(url.as_str())?))))
+    }
+
+    /// Returns the external block builder of the sequencer, if enabled, authenticated with its
+    /// own secret or else `engine_secret`.
+    pub fn sequencer_builder(&self, engine_secret: &JwtSecret) -> Result<Option<ExternalBuilder>> {
+        let Some(url) = &self.sequencer_builder_url else { return Ok(None) };
+        let secret = match &self.sequencer_builder_jwt_secret {
+            Some(path) => JwtSecret::from_file(path)?,
+            None => engine_secret.clone(),
+        };
+        let engine = Arc::new(EngineClient::new(url.as_str(), secret)?);
+        Ok(Some(
+            ExternalBuilder::new(engine)
+                .with_timeout(Duration::from_millis(self.sequencer_builder_timeout))
+                .with_fallback(self.sequencer_builder_fallback),
+        ))
+    }
+
+    /// Returns the supervisor to spawn the node's tasks with, starting its worker runtime if
+    /// enabled.
+    pub fn supervisor(&self) -> Result<Supervisor> {
+        let supervisor = Supervisor::new(RestartConfig {
+            policy: self.restart_policy,
+            max_restarts: self.max_restarts,
+            backoff: Duration::from_secs(self.restart_backoff),
+        });
+        let supervisor =
+            if self.metrics_runtime { supervisor.with_poll_timing() } else { supervisor };
+        Ok(match self.worker_threads {
+            Some(threads) => supervisor.with_worker_runtime(WorkerRuntime::new(threads)?),
+            None => supervisor,
+        })
+    }
+
+    /// Opens the data directory of the chain, migrating it to the current layout, if set.
+    pub fn datadir(&self) -> Result<Option<DataDir>> {
+        self.datadir.as_ref().map(|datadir| DataDir::open(datadir, self.l2_chain_id)).transpose()
+    }
+
+    /// Returns the path of the audit log, if enabled.
+    pub fn audit_log(&self, datadir: Option<&DataDir>) -> Option<PathBuf> {
+        self.audit_log.clone().or_else(|| datadir.map(DataDir::audit_log))
+    }
+
+    /// Opens the write-ahead log of chain notifications, if enabled.
+    pub fn notification_wal(&self, datadir: Option<&DataDir>) -> Result<Option<NotificationWal>> {
+        let path = datadir.filter(|_| self.exex_wal).map(DataDir::notification_wal);
+        path.map(NotificationWal::open).transpose()
+    }
+
+    /// Opens the safe head database, if enabled.
+    pub fn safe_db(&self, datadir: Option<&DataDir>) -> Result<Option<SafeDb>> {
+        let path = self.safe_db.clone().or_else(|| datadir.map(DataDir::safe_db));
+        path.map(SafeDb::open).transpose()
+    }
+
+    /// Opens the derivation throughput database, if enabled.
+    pub fn throughput_stats(&self, datadir: Option<&DataDir>) -> Result<Option<ThroughputStats>> {
+        let path = self.stats_db.clone().or_else(|| datadir.map(DataDir::stats));
+        path.map(ThroughputStats::open).transpose()
+    }
+
+    /// Opens the blob cache, if enabled.
+    pub fn blob_cache(&self, datadir: Option<&DataDir>) -> Result<Option<BlobCache>> {
+        let path = self.blob_cache_path.clone().or_else(|| datadir.map(DataDir::blob_cache));
+        let Some(path) = path else { return Ok(None) };
+        let storage: Arc<dyn Storage> = Arc::new(SqliteStorage::open(path)?);
+        let storage = if self.blob_cache_read_only {
+            Arc::new(ReadOnlyStorage::new(storage))
+        } else {
+            storage
+        };
+        Ok(Some(BlobCache::new(storage)))
+    }
+
+    /// Returns the derivation start point forced by the operator, if any.
+    pub fn derivation_start(&self) -> Option<DerivationStart> {
+        self.derivation_start_l2_block.map(|l2_block| DerivationStart {
+            l2_block,
+            l1_origin: self.derivation_start_l1_origin,
+        })
+    }
+}
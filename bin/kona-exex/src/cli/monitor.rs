@@ -0,0 +1,78 @@
+//! The `hera monitor` command, checking the output roots proposed on L1.
+
+use std::{sync::Arc, time::Duration};
+
+use alloy_primitives::Address;
+use clap::Args;
+use eyre::{eyre, Result};
+
+use crate::{
+    cli::HeraArgs,
+    config::RollupConfig,
+    l1::{ChainProvider, RpcChainProvider},
+    monitor::{OutputMonitor, DEFAULT_MONITOR_CONFIRMATIONS},
+    output::{OutputRootCache, RpcL2StateProvider, DEFAULT_OUTPUT_CACHE_SIZE},
+};
+
+/// Arguments of `hera monitor`.
+///
+/// Proposals are read from the L1 RPC at `--hera.l1-rpc-url` and checked against outputs
+/// computed from the L2 execution layer at `--hera.l2-rpc-url`, which must have synced the
+/// proposed blocks. Mismatches are alerted to `--hera.alert.webhook-url`.
+#[derive(Debug, Clone, Args)]
+pub struct MonitorCommand {
+    /// The `L2OutputOracle` proposals are made to, by default the one of the chain in the
+    /// superchain registry.
+    #[arg(long)]
+    pub oracle_address: Option<Address>,
+
+    /// The first L1 block to check the proposals of.
+    #[arg(long)]
+    pub from_block: u64,
+
+    /// Number of L1 blocks built on top of a block before its proposals are checked.
+    #[arg(long, default_value_t = DEFAULT_MONITOR_CONFIRMATIONS)]
+    pub confirmations: u64,
+}
+
+impl MonitorCommand {
+    /// Checks the proposals of the chain of `config` until interrupted.
+    pub async fn run(&self, args: &HeraArgs, config: &RollupConfig) -> Result<()> {
+        let oracle = self
+            .oracle_address
+            .or_else(|| {
+                let chain = superchain_registry::OPCHAINS.get(&config.l2_chain_id)?;
+                chain.addresses.as_ref()?.l2_output_oracle_proxy
+            })
+            .ok_or_else(|| {
+                eyre!(
+                    "chain {} has no known L2OutputOracle, set --oracle-address",
+                    config.l2_chain_id
+                )
+            })?;
+        let l1_url = args
+            .l1_rpc_url
+            .as_ref()
+            .ok_or_else(|| eyre!("--hera.l1-rpc-url is required to monitor proposals"))?;
+        let l2_url = args
+            .l2_rpc_url
+            .as_ref()
+            .ok_or_else(|| eyre!("--hera.l2-rpc-url is required to monitor proposals"))?;
+
+        let l1: Arc<dyn ChainProvider> = Arc::new(RpcChainProvider::new(l1_url.as_str())?);
+        let l2 = RpcL2StateProvider::new(l2_url.as_str(), config.genesis.clone())?
+            .with_isthmus_time(config.isthmus_time);
+        let outputs = Arc::new(OutputRootCache::new(Arc::new(l2), DEFAULT_OUTPUT_CACHE_SIZE));
+        let mut monitor = OutputMonitor::new(l1, outputs, oracle, self.from_block)
+            .with_confirmations(self.confirmations)
+            .with_poll_interval(Duration::from_secs(args.l1_poll_interval));
+        if let Some(alerter) = args.alerter() {
+            monitor = monitor.with_alerter(Arc::new(alerter));
+        }
+
+        tokio::select! {
+            () = monitor.run() => Ok(()),
+            result = tokio::signal::ctrl_c() => Ok(result?),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Per-field overrides of the rollup config.
+
+use alloy_primitives::Address;
+use clap::Args;
+
+use crate::config::{GasLimitSource, RollupConfig};
+
+/// Overrides of individual [`RollupConfig`] fields, namespaced under `--hera.override.*`.
+///
+/// Each override replaces the corresponding field of the registry or file config, so operators
+/// can test parameter changes without maintaining a full custom config file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Args)]
+#[command(next_help_heading = "Hera rollup config overrides")]
+pub struct RollupConfigOverrides {
+    /// Override the L2 block time, in seconds.
+    #[arg(long = "hera.override.block-time")]
+    pub block_time: Option<u64>,
+    /// Override the max sequencer drift, in seconds.
+    #[arg(long = "hera.override.max-sequencer-drift")]
+    pub max_sequencer_drift: Option<u64>,
+    /// Override the sequencing window size, in L1 blocks.
+    #[arg(long = "hera.override.seq-window-size")]
+    pub seq_window_size: Option<u64>,
+    /// Override the pre-Granite channel timeout, in L1 blocks.
+    #[arg(long = "hera.override.channel-timeout")]
+    pub channel_timeout: Option<u64>,
+    /// Override the Granite channel timeout, in L1 blocks.
+    #[arg(long = "hera.override.granite-channel-timeout")]
+    pub granite_channel_timeout: Option<u64>,
+    /// Override the batch inbox address.
+    #[arg(long = "hera.override.batch-inbox-address")]
+    pub batch_inbox_address: Option<Address>,
+    /// Override the deposit contract address.
+    #[arg(long = "hera.override.deposit-contract-address")]
+    pub deposit_contract_address: Option<Address>,
+    /// Override the L1 system config contract address.
+    #[arg(long = "hera.override.l1-system-config-address")]
+    pub l1_system_config_address: Option<Address>,
+    /// Override the protocol versions contract address.
+    #[arg(long = "hera.override.protocol-versions-address")]
+    pub protocol_versions_address: Option<Address>,
+    /// Override the Alt-DA challenge contract address.
+    #[arg(long = "hera.override.da-challenge-address")]
+    pub da_challenge_address: Option<Address>,
+    /// Override the Alt-DA challenge window, in L1 blocks.
+    #[arg(long = "hera.override.da-challenge-window")]
+    pub da_challenge_window: Option<u64>,
+    /// Override the Alt-DA resolve window, in L1 blocks.
+    #[arg(long = "hera.override.da-resolve-window")]
+    pub da_resolve_window: Option<u64>,
+    /// Use a static gas limit for derived blocks instead of the one of the system config, for
+    /// devnets whose `SystemConfig` contract is not deployed yet.
+    #[arg(long = "hera.override.gas-limit")]
+    pub gas_limit: Option<u64>,
+    /// Override the fee recipient of derived blocks.
+    #[arg(long = "hera.override.fee-recipient")]
+    pub fee_recipient: Option<Address>,
+    /// Override the Regolith activation timestamp.
+    #[arg(long = "hera.override.regolith-time")]
+    pub regolith_time: Option<u64>,
+    /// Override the Canyon activation timestamp.
+    #[arg(long = "hera.override.canyon-time")]
+    pub canyon_time: Option<u64>,
+    /// Override the Delta activation timestamp.
+    #[arg(long = "hera.override.delta-time")]
+    pub delta_time: Option<u64>,
+    /// Override the Ecotone activation timestamp.
+    #[arg(long = "hera.override.ecotone-time")]
+    pub ecotone_time: Option<u64>,
+    /// Override the Fjord activation timestamp.
+    #[arg(long = "hera.override.fjord-time")]
+    pub fjord_time: Option<u64>,
+    /// Override the Granite activation timestamp.
+    #[arg(long = "hera.override.granite-time")]
+    pub granite_time: Option<u64>,
+    /// Override the Holocene activation timestamp.
+    #[arg(long = "hera.override.holocene-time")]
+    pub holocene_time: Option<u64>,
+    /// Override the Isthmus activation timestamp.
+    #[arg(long = "hera.override.isthmus-time")]
+    pub isthmus_time: Option<u64>,
+    /// Override the Interop activation timestamp.
+    #[arg(long = "hera.override.interop-time")]
+    pub interop_time: Option<u64>,
+}
+
+impl RollupConfigOverrides {
+    /// Applies the overrides to the given config, returning the names of the overridden fields.
+    pub fn apply(&self, config: &mut RollupConfig) -> Vec<&'static str> {
+        let mut applied = Vec::new();
+
+        macro_rules! apply {
+            ($($field:ident),* $(,)?) => {$(
+                if let Some(value) = self.$field {
+                    config.$field = value;
+                    applied.push(stringify!($field));
+                }
+            )*};
+            (optional: $($field:ident),* $(,)?) => {$(
+                if let Some(value) = self.$field {
+                    config.$field = Some(value);
+                    applied.push(stringify!($field));
+                }
+            )*};
+        }
+
+        apply!(
+            block_time,
+            max_sequencer_drift,
+            seq_window_size,
+            channel_timeout,
+            granite_channel_timeout,
+            batch_inbox_address,
+            deposit_contract_address,
+            l1_system_config_address,
+            protocol_versions_address,
+        );
+        apply!(
+            optional: da_challenge_address,
+            da_challenge_window,
+            da_resolve_window,
+            regolith_time,
+            canyon_time,
+            delta_time,
+            ecotone_time,
+            fjord_time,
+            granite_time,
+            holocene_time,
+            isthmus_time,
+            interop_time,
+        );
+        if let Some(gas_limit) = self.gas_limit {
+            config.attributes.gas_limit = GasLimitSource::Static(gas_limit);
+            applied.push("gas_limit");
+        }
+        if let Some(fee_recipient) = self.fee_recipient {
+            config.attributes.fee_recipient = Some(fee_recipient);
+            applied.push("fee_recipient");
+        }
+
+        applied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::address;
+
+    use super::*;
+
+    #[test]
+    fn applies_set_fields_only() {
+        let mut config = RollupConfig::load(10, None).unwrap();
+        let original = config.clone();
+        assert!(RollupConfigOverrides::default().apply(&mut config).is_empty());
+        assert_eq!(config, original);
+
+        let inbox = address!("ff00000000000000000000000000000000000010");
+        let overrides = RollupConfigOverrides {
+            block_time: Some(1),
+            batch_inbox_address: Some(inbox),
+            da_challenge_address: Some(Address::repeat_byte(1)),
+            isthmus_time: Some(300),
+            gas_limit: Some(60_000_000),
+            ..Default::default()
+        };
+        let applied = overrides.apply(&mut config);
+        assert_eq!(
+            applied,
+            [
+                "block_time",
+                "batch_inbox_address",
+                "da_challenge_address",
+                "isthmus_time",
+                "gas_limit"
+            ]
+        );
+        assert_eq!(config.attributes.gas_limit, GasLimitSource::Static(60_000_000));
+        assert_eq!(config.block_time, 1);
+        assert_eq!(config.batch_inbox_address, inbox);
+        assert_eq!(config.da_challenge_address, Some(Address::repeat_byte(1)));
+        assert_eq!(config.isthmus_time, Some(300));
+        assert_eq!(config.ecotone_time, original.ecotone_time);
+    }
+
+    #[test]
+    fn applies_on_top_of_registry_config() {
+        let registry = RollupConfig::load(10, None).unwrap();
+        let mut config = registry.clone();
+        let overrides = RollupConfigOverrides {
+            seq_window_size: Some(7200),
+            holocene_time: Some(1_800_000_000),
+            ..Default::default()
+        };
+        overrides.apply(&mut config);
+
+        assert_eq!(config.seq_window_size, 7200);
+        assert_eq!(config.holocene_time, Some(1_800_000_000));
+        assert_eq!(
+            RollupConfig { seq_window_size: 3600, holocene_time: registry.holocene_time, ..config },
+            registry
+        );
+    }
+}
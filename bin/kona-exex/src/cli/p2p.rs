@@ -0,0 +1,117 @@
+//! P2P arguments.
+
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
+};
+
+use clap::Args;
+use eyre::Result;
+use tracing::warn;
+
+use crate::{
+    p2p::{EnrBuilder, P2pIdentity, P2pListeners, PortMapping, PortProtocol, DEFAULT_UPNP_TIMEOUT},
+    storage::DataDir,
+};
+
+/// The default libp2p and discv5 port.
+pub const DEFAULT_P2P_PORT: u16 = 9222;
+
+/// Identity, listening and NAT traversal settings of the p2p stack, namespaced under
+/// `--hera.p2p.*`.
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+#[command(next_help_heading = "Hera p2p")]
+pub struct P2pArgs {
+    /// Enable the p2p stack: bind the TCP and UDP listening ports, load the node identity and
+    /// print its record. No port is bound without it.
+    #[arg(long = "hera.p2p.enabled")]
+    pub enabled: bool,
+
+    /// Path to the hex encoded secp256k1 key identifying the node, generated on first start.
+    /// Defaults to `p2p_priv.txt` in the chain data directory, or an ephemeral key without one.
+    #[arg(long = "hera.p2p.priv-key-path")]
+    pub priv_key_path: Option<PathBuf>,
+
+    /// IP address to listen on.
+    #[arg(long = "hera.p2p.listen.ip", default_value = "0.0.0.0")]
+    pub listen_ip: IpAddr,
+
+    /// TCP port of libp2p, 0 to let the system pick one.
+    #[arg(long = "hera.p2p.listen.tcp", default_value_t = DEFAULT_P2P_PORT)]
+    pub listen_tcp: u16,
+
+    /// UDP port of discv5, 0 to let the system pick one.
+    #[arg(long = "hera.p2p.listen.udp", default_value_t = DEFAULT_P2P_PORT)]
+    pub listen_udp: u16,
+
+    /// Public IPv4 address advertised to peers, e.g. of the NAT gateway in front of the node.
+    /// Defaults to the external address found through UPnP, or to the listening address.
+    #[arg(long = "hera.p2p.advertise-ip", requires = "enabled")]
+    pub advertise_ip: Option<Ipv4Addr>,
+
+    /// TCP and UDP port advertised to peers instead of the listening ones, e.g. when a gateway
+    /// forwards another port to the node.
+    #[arg(long = "hera.p2p.advertise-port", requires = "enabled")]
+    pub advertise_port: Option<u16>,
+
+    /// Forward the listening ports through the UPnP gateway of the local network, and advertise
+    /// its external address.
+    #[arg(long = "hera.p2p.upnp", requires = "enabled")]
+    pub upnp: bool,
+}
+
+impl P2pArgs {
+    /// Returns the path of the identity key, if it is persisted.
+    pub fn priv_key_path(&self, datadir: Option<&DataDir>) -> Option<PathBuf> {
+        self.priv_key_path.clone().or_else(|| datadir.map(DataDir::p2p_key))
+    }
+
+    /// Binds the listening ports.
+    pub async fn listen(&self) -> Result<P2pListeners> {
+        P2pListeners::bind(self.listen_ip, self.listen_tcp, self.listen_udp).await
+    }
+
+    /// Forwards the ports of `listeners` through UPnP, if enabled. Nodes behind a NAT still
+    /// reach out to peers without it, so a failure is only logged.
+    pub async fn port_mapping(&self, listeners: &P2pListeners) -> Result<Option<PortMapping>> {
+        if !self.upnp {
+            return Ok(None);
+        }
+        let ports = vec![
+            (PortProtocol::Tcp, listeners.tcp_port()?),
+            (PortProtocol::Udp, listeners.udp_port()?),
+        ];
+        Ok(match PortMapping::map(ports, DEFAULT_UPNP_TIMEOUT).await {
+            Ok(mapping) => Some(mapping),
+            Err(err) => {
+                warn!(target: "hera::p2p", %err, "Failed to map p2p ports through UPnP");
+                None
+            }
+        })
+    }
+
+    /// Loads the node's identity on the p2p network of the chain `l2_chain_id`, generating its
+    /// key on first start, and signs its record advertising the ports of `listeners` under the
+    /// address of `mapping`, if any.
+    pub fn identity(
+        &self,
+        datadir: Option<&DataDir>,
+        l2_chain_id: u64,
+        listeners: &P2pListeners,
+        mapping: Option<&PortMapping>,
+    ) -> Result<P2pIdentity> {
+        let port = |listen| self.advertise_port.unwrap_or(listen);
+        let mut builder = EnrBuilder::new(1)
+            .with_tcp4(port(listeners.tcp_port()?))
+            .with_udp4(port(listeners.udp_port()?))
+            .with_l2_chain_id(l2_chain_id);
+        // Records only carry the address when it is a specific one.
+        let mapped = mapping.map(|mapping| mapping.external_ip);
+        match self.advertise_ip.map(IpAddr::V4).or(mapped).unwrap_or(self.listen_ip) {
+            IpAddr::V4(ip) if !ip.is_unspecified() => builder = builder.with_ip4(ip),
+            _ => {}
+        }
+        let enr_path = datadir.map(DataDir::p2p_enr);
+        P2pIdentity::load(self.priv_key_path(datadir).as_deref(), enr_path.as_deref(), builder)
+    }
+}
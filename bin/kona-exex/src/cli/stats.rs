@@ -0,0 +1,71 @@
+//! The `hera stats` command, summarizing the recorded derivation throughput.
+
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use clap::Args;
+use eyre::Result;
+
+use crate::stats::{HourlyThroughput, ThroughputStats};
+
+/// Prints the hourly derivation throughput, oldest first, followed by its totals.
+#[derive(Debug, Clone, Args)]
+pub struct StatsCommand {
+    /// Path to the throughput database, as passed to `--hera.stats-db`.
+    #[arg(long)]
+    pub db: PathBuf,
+
+    /// Only summarize this many past hours.
+    #[arg(long, default_value_t = 24)]
+    pub hours: u64,
+
+    /// Print hours as JSON lines.
+    #[arg(long)]
+    pub json: bool,
+}
+
+impl StatsCommand {
+    /// Runs the command.
+    pub fn run(&self) -> Result<()> {
+        let stats = ThroughputStats::open(&self.db)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let since = (now / 3600).saturating_sub(self.hours.saturating_sub(1)) * 3600;
+        let hours = stats.hours(since)?;
+
+        if self.json {
+            for hour in &hours {
+                println!("{}", serde_json::to_string(hour)?);
+            }
+            return Ok(());
+        }
+        println!(
+            "{:>12} {:>10} {:>12} {:>10} {:>14}",
+            "hour", "l2 blocks", "l2 txs", "l1 blocks", "batch bytes"
+        );
+        let mut total = HourlyThroughput::default();
+        for hour in &hours {
+            total.add(hour);
+            print_row(&hour.hour.to_string(), hour);
+        }
+        print_row("total", &total);
+        if !hours.is_empty() {
+            let count = hours.len() as u64;
+            println!(
+                "{} hours with derivation, {} L2 blocks and {} batch bytes per hour on average",
+                count,
+                total.l2_blocks / count,
+                total.batch_bytes / count
+            );
+        }
+        Ok(())
+    }
+}
+
+fn print_row(label: &str, hour: &HourlyThroughput) {
+    println!(
+        "{:>12} {:>10} {:>12} {:>10} {:>14}",
+        label, hour.l2_blocks, hour.l2_transactions, hour.l1_blocks, hour.batch_bytes
+    );
+}
@@ -0,0 +1,108 @@
+//! The summary of the effective configuration, logged at startup.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::info;
+use url::Url;
+
+use crate::{cli::HeraArgs, config::RollupConfig, storage::DataDir};
+
+impl HeraArgs {
+    /// Logs the effective configuration: the chain and its hardfork schedule, the derivation and
+    /// validation modes, the endpoints and the enabled subsystems. Logged at startup, so that a
+    /// misconfiguration shows in the operator logs right away.
+    pub fn log_summary(&self, config: &RollupConfig, datadir: Option<&DataDir>) {
+        let source = self
+            .l2_config_file
+            .as_ref()
+            .map_or_else(|| "superchain registry".to_string(), |path| path.display().to_string());
+        info!(
+            target: "hera",
+            l2_chain_id = config.l2_chain_id,
+            l1_chain_id = config.l1_chain_id,
+            block_time = config.block_time,
+            %source,
+            "Chain"
+        );
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        for (fork, time) in config.hardforks() {
+            let status = match time {
+                Some(time) if time <= now => "active",
+                Some(_) => "scheduled",
+                None => "not scheduled",
+            };
+            info!(target: "hera", %fork, time, %status, "Hardfork");
+        }
+
+        info!(
+            target: "hera",
+            exex_mode = %self.exex_mode,
+            exex_paranoid = self.exex_paranoid,
+            exex_lag = self.exex_lag,
+            l1_log_scan = self.l1_log_scan_enabled,
+            dry_run = self.dry_run,
+            max_reorg_depth = self.max_reorg_depth,
+            validation_on_failure = %self.validation_on_failure,
+            derivation_start = ?self.derivation_start(),
+            import_era_dir = ?self.import_era_dir,
+            import_output_roots = self.import_output_roots.len(),
+            "Derivation"
+        );
+
+        let rpc = if self.rpc_attach_to_reth {
+            "attached to reth".to_string()
+        } else {
+            self.rpc_addr.to_string()
+        };
+        info!(
+            target: "hera",
+            %rpc,
+            l1_rpc = %endpoint(self.l1_rpc_url.as_ref()),
+            l1_failover_rpc = %endpoint(self.l1_failover_rpc_url.as_ref()),
+            l1_beacon = %endpoint(self.l1_beacon_url.as_ref()),
+            l2_rpc = %endpoint(self.l2_rpc_url.as_ref()),
+            unsafe_sync_rpc = %endpoint(self.unsafe_sync_rpc_url.as_ref()),
+            l2_engine = %endpoint(self.l2_engine_url.as_ref()),
+            shadow_op_node = %endpoint(self.shadow_op_node_url.as_ref()),
+            mempool_preview_l1_rpc = %endpoint(self.mempool_preview_l1_rpc_url.as_ref()),
+            conductor = %endpoint(self.conductor_rpc_url.as_ref()),
+            sequencer_throttle = %endpoint(self.sequencer_throttle_rpc_url.as_ref()),
+            tx_forward_sequencer = %endpoint(self.tx_forward_sequencer_url.as_ref()),
+            tx_forward_backups = self.tx_forward_backup_urls.len(),
+            blob_archiver = %endpoint(self.blob_archiver_url.as_ref()),
+            "Endpoints"
+        );
+
+        let path = |path: Option<std::path::PathBuf>| {
+            path.map_or_else(|| "disabled".to_string(), |path| path.display().to_string())
+        };
+        let metrics =
+            self.metrics_addr.map_or_else(|| "disabled".to_string(), |addr| addr.to_string());
+        let blob_cache = self.blob_cache_path.clone().or_else(|| datadir.map(DataDir::blob_cache));
+        info!(
+            target: "hera",
+            datadir = %path(datadir.map(|datadir| datadir.path().to_path_buf())),
+            sequencer = self.sequencer_enabled,
+            shadow = self.shadow_op_node_url.is_some(),
+            mempool_preview = self.mempool_preview_l1_rpc_url.is_some(),
+            alerts = self.alert_webhook_url.is_some(),
+            metrics = %metrics,
+            runtime_metrics = self.metrics_runtime,
+            audit_log = %path(self.audit_log(datadir)),
+            safe_db = %path(self.safe_db.clone().or_else(|| datadir.map(DataDir::safe_db))),
+            notification_wal =
+                %path(datadir.filter(|_| self.exex_wal).map(DataDir::notification_wal)),
+            stats_db = %path(self.stats_db.clone().or_else(|| datadir.map(DataDir::stats))),
+            blob_cache = %path(blob_cache),
+            blob_cache_read_only = self.blob_cache_read_only,
+            p2p_key = %path(self.p2p.priv_key_path(datadir).filter(|_| self.p2p.enabled)),
+            "Subsystems"
+        );
+    }
+}
+
+/// Returns the origin of an endpoint, leaving out credentials and paths that may carry API keys.
+fn endpoint(url: Option<&Url>) -> String {
+    url.map_or_else(|| "none".to_string(), |url| url.origin().ascii_serialization())
+}
@@ -0,0 +1,83 @@
+//! The `hera validate-attributes` command, validating attributes derived by another node.
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use clap::{ArgGroup, Args};
+use eyre::{ensure, eyre, Result, WrapErr};
+use tokio::io::BufReader;
+use tracing::info;
+
+use crate::{
+    cli::HeraArgs,
+    rpc::{HeraRpcModules, HeraValidationRpc},
+    validation::{ExternalValidation, RpcAttributesValidator, DEFAULT_SYNC_TIMEOUT},
+};
+
+/// Arguments of `hera validate-attributes`.
+///
+/// Derivation is skipped entirely: attributes derived by another node are checked against the
+/// canonical chain of the L2 execution layer at `--hera.l2-rpc-url`, e.g. to diff rollup node
+/// implementations.
+#[derive(Debug, Clone, Args)]
+#[command(group(ArgGroup::new("source").args(["input", "serve"]).required(true)))]
+pub struct ValidateAttributesCommand {
+    /// File of JSON attributes with their parent, one per line, or `-` for stdin.
+    pub input: Option<PathBuf>,
+
+    /// Keep reading the file as it grows, like `tail -f`.
+    #[arg(long, requires = "input")]
+    pub follow: bool,
+
+    /// Serve `hera_validateAttributes` on `--hera.rpc.addr` until interrupted, instead of
+    /// reading a file.
+    #[arg(long)]
+    pub serve: bool,
+
+    /// Time to wait for the block of the attributes to be synced by the L2 execution layer, in
+    /// seconds.
+    #[arg(long, default_value_t = DEFAULT_SYNC_TIMEOUT.as_secs())]
+    pub sync_timeout: u64,
+}
+
+impl ValidateAttributesCommand {
+    /// Validates the attributes, failing if any of the ones read from the input is not valid.
+    pub async fn run(&self, args: &HeraArgs) -> Result<()> {
+        let url = args
+            .l2_rpc_url
+            .as_ref()
+            .ok_or_else(|| eyre!("--hera.l2-rpc-url is required to validate attributes"))?;
+        let validation = Arc::new(
+            ExternalValidation::new(RpcAttributesValidator::new(url.as_str())?)
+                .with_sync_timeout(Duration::from_secs(self.sync_timeout)),
+        );
+
+        let Some(input) = &self.input else {
+            let modules = HeraRpcModules::new()
+                .with_build_info()?
+                .with_validation(HeraValidationRpc::new(validation.clone()))?;
+            let server = modules.serve(args.rpc_addr).await?;
+            tokio::signal::ctrl_c().await?;
+            info!(target: "hera", summary = ?validation.summary(), "Shutting down");
+            server.stop()?;
+            return Ok(());
+        };
+
+        let summary = if input.as_os_str() == "-" {
+            validation.run(BufReader::new(tokio::io::stdin()), self.follow).await?
+        } else {
+            let file = tokio::fs::File::open(input)
+                .await
+                .wrap_err_with(|| format!("failed to open {}", input.display()))?;
+            validation.run(BufReader::new(file), self.follow).await?
+        };
+        println!(
+            "{} valid, {} invalid, {} failed out of {} attributes",
+            summary.valid,
+            summary.invalid,
+            summary.failed,
+            summary.total()
+        );
+        ensure!(summary.valid == summary.total(), "not all attributes are valid");
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+//! Sanity checks of the local clock against the timestamps of the blocks Hera receives.
+//!
+//! Blocks reach the node shortly after their timestamp: L1 heads within seconds, and unsafe L2
+//! blocks gossiped by the sequencer even sooner. Propagation only ever adds to the delay between
+//! the timestamp of a block and its arrival, so the smallest recent delay estimates how far the
+//! local clock is ahead of the network, and a negative one means blocks arrive from the future.
+//!
+//! The sequencer paces blocks by the local clock, so a skewed clock makes it build blocks peers
+//! reject as too new, or fall behind the network. It refuses to sequence while the skew exceeds
+//! the maximum. While the L1 node catches up, its blocks arrive late and the clock appears ahead,
+//! but sequencing needs recent L1 origins and would not progress anyway.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{ensure, Result};
+use metrics::gauge;
+use tracing::{info, warn};
+
+/// Default skew of the local clock past which a warning is logged, in seconds.
+pub const DEFAULT_CLOCK_SKEW_WARNING: u64 = 2;
+
+/// Default skew of the local clock past which the sequencer refuses to build blocks, in seconds.
+/// Peers reject gossiped blocks more than 5 seconds ahead of their clock.
+pub const DEFAULT_MAX_CLOCK_SKEW: u64 = 5;
+
+/// Number of recent blocks of each source the skew is estimated from.
+const MAX_SAMPLES: usize = 16;
+
+/// Where the timestamp of a block came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClockSource {
+    /// A new L1 head.
+    L1,
+    /// An unsafe L2 block gossiped by the sequencer.
+    Gossip,
+}
+
+impl fmt::Display for ClockSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::L1 => "l1",
+            Self::Gossip => "gossip",
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct ClockState {
+    delays: HashMap<ClockSource, VecDeque<i64>>,
+    skewed: bool,
+}
+
+/// Estimates the skew of the local clock from the arrival of recent blocks.
+#[derive(Debug)]
+pub struct ClockMonitor {
+    max_skew: u64,
+    warning: u64,
+    state: Mutex<ClockState>,
+}
+
+impl ClockMonitor {
+    /// Creates a monitor failing [checks](Self::check) past `max_skew` seconds of skew.
+    pub fn new(max_skew: u64) -> Self {
+        Self {
+            max_skew,
+            warning: DEFAULT_CLOCK_SKEW_WARNING.min(max_skew),
+            state: Mutex::new(ClockState::default()),
+        }
+    }
+
+    /// Warns past `warning` seconds of skew.
+    pub const fn with_warning(mut self, warning: u64) -> Self {
+        self.warning = warning;
+        self
+    }
+
+    /// Records the arrival at the local time `now` of a block from `source` with `timestamp`.
+    pub fn record(&self, source: ClockSource, timestamp: u64, now: u64) {
+        let delay = now as i64 - timestamp as i64;
+        let mut state = self.state.lock().unwrap();
+        let delays = state.delays.entry(source).or_default();
+        delays.push_back(delay);
+        if delays.len() > MAX_SAMPLES {
+            delays.pop_front();
+        }
+
+        let Some(skew) = estimate(&state) else { return };
+        gauge!("hera_clock_skew_seconds").set(skew as f64);
+        let skewed = skew.unsigned_abs() > self.warning;
+        if skewed != state.skewed {
+            if skewed {
+                warn!(target: "hera::clock", skew, %source, "Local clock skewed from the network");
+            } else {
+                info!(target: "hera::clock", skew, "Local clock back in sync with the network");
+            }
+            state.skewed = skewed;
+        }
+    }
+
+    /// Returns how many seconds the local clock is estimated to be ahead of the network, negative
+    /// when behind, or `None` before any block arrived.
+    pub fn skew(&self) -> Option<i64> {
+        estimate(&self.state.lock().unwrap())
+    }
+
+    /// Fails if the local clock is skewed by more than the maximum.
+    pub fn check(&self) -> Result<()> {
+        let Some(skew) = self.skew() else { return Ok(()) };
+        ensure!(
+            skew.unsigned_abs() <= self.max_skew,
+            "local clock is {} by {}s, over the maximum skew of {}s",
+            if skew > 0 { "ahead" } else { "behind" },
+            skew.unsigned_abs(),
+            self.max_skew
+        );
+        Ok(())
+    }
+}
+
+/// Returns the smallest recent delay of all sources.
+fn estimate(state: &ClockState) -> Option<i64> {
+    state.delays.values().flatten().min().copied()
+}
+
+/// Returns the local time in seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_the_skew_from_the_fastest_blocks() {
+        let clock = ClockMonitor::new(DEFAULT_MAX_CLOCK_SKEW);
+        assert_eq!(clock.skew(), None);
+        clock.check().unwrap();
+
+        // A lagging L1 node does not hide the prompt arrival of gossiped blocks.
+        clock.record(ClockSource::L1, 1_000, 1_030);
+        assert_eq!(clock.skew(), Some(30));
+        assert!(clock.check().unwrap_err().to_string().contains("ahead by 30s"));
+        clock.record(ClockSource::Gossip, 1_030, 1_031);
+        assert_eq!(clock.skew(), Some(1));
+        clock.check().unwrap();
+
+        // Blocks from the future reveal a clock behind the network.
+        clock.record(ClockSource::Gossip, 1_040, 1_032);
+        assert!(clock.check().unwrap_err().to_string().contains("behind by 8s"));
+
+        // Old samples are forgotten.
+        for timestamp in 0..MAX_SAMPLES as u64 {
+            clock.record(ClockSource::Gossip, 1_100 + timestamp, 1_100 + timestamp);
+        }
+        assert_eq!(clock.skew(), Some(0));
+    }
+}
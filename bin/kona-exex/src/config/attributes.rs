@@ -0,0 +1,66 @@
+//! Defaults of the payload attributes of derived blocks, for custom chains.
+
+use alloy_primitives::Address;
+use serde::{Deserialize, Serialize};
+
+use crate::config::SystemConfig;
+
+/// Where the gas limit of derived blocks comes from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GasLimitSource {
+    /// The gas limit of the system config, updated by the `SystemConfig` contract on L1.
+    #[default]
+    SystemConfig,
+    /// A fixed gas limit, for devnets whose `SystemConfig` contract is not deployed yet.
+    Static(u64),
+}
+
+/// Hera-specific defaults of the payload attributes of derived blocks, under `hera_attributes`
+/// in the rollup config. Chains following the protocol leave them unset: attributes built
+/// otherwise produce blocks other nodes do not derive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttributesConfig {
+    /// Where the gas limit comes from.
+    #[serde(default)]
+    pub gas_limit: GasLimitSource,
+    /// The fee recipient, instead of the `SequencerFeeVault` predeploy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_recipient: Option<Address>,
+}
+
+impl AttributesConfig {
+    /// Returns true if no default is changed.
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns the gas limit of blocks derived with `system_config`.
+    pub const fn gas_limit(&self, system_config: &SystemConfig) -> u64 {
+        match self.gas_limit {
+            GasLimitSource::SystemConfig => system_config.gas_limit,
+            GasLimitSource::Static(gas_limit) => gas_limit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_attributes_defaults() {
+        let config: AttributesConfig = serde_json::from_str("{}").unwrap();
+        assert!(config.is_default());
+        let system_config = SystemConfig { gas_limit: 30_000_000, ..Default::default() };
+        assert_eq!(config.gas_limit(&system_config), 30_000_000);
+
+        let recipient = Address::repeat_byte(1);
+        let json =
+            format!(r#"{{"gas_limit":{{"static":60000000}},"fee_recipient":"{recipient}"}}"#);
+        let config: AttributesConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(config.gas_limit(&system_config), 60_000_000);
+        assert_eq!(config.fee_recipient, Some(recipient));
+        assert_eq!(serde_json::to_string(&config).unwrap(), json);
+    }
+}
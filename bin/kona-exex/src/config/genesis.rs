@@ -0,0 +1,189 @@
+//! Genesis state of a rollup.
+
+use alloy_primitives::{b256, Address, Log, B256, U256};
+use eyre::{bail, ensure, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+
+use crate::{l1::L1Receipt, protocol::BlockId};
+
+/// The genesis anchors of the rollup on L1 and L2.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainGenesis {
+    /// The L1 block the rollup starts *after*.
+    pub l1: BlockId,
+    /// The first L2 block of the rollup.
+    pub l2: BlockId,
+    /// Timestamp of the first L2 block.
+    pub l2_time: u64,
+    /// The initial system configuration.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_config: Option<SystemConfig>,
+}
+
+impl From<&superchain_registry::ChainGenesis> for ChainGenesis {
+    fn from(genesis: &superchain_registry::ChainGenesis) -> Self {
+        Self {
+            l1: BlockId::new(genesis.l1.hash, genesis.l1.number),
+            l2: BlockId::new(genesis.l2.hash, genesis.l2.number),
+            l2_time: genesis.l2_time,
+            system_config: genesis.system_config.as_ref().map(Into::into),
+        }
+    }
+}
+
+/// Topic of the `ConfigUpdate(uint256,uint8,bytes)` event of the L1 `SystemConfig` contract.
+pub const CONFIG_UPDATE_TOPIC: B256 =
+    b256!("1d2b0bda21d56b8bd12d4f94ebacffdfb35f5e226f84b461103bb8beab6353be");
+
+/// The only supported version of the config update event encoding.
+pub const CONFIG_UPDATE_EVENT_VERSION_0: B256 = B256::ZERO;
+
+/// The L1 `SystemConfig` values that derivation depends on.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemConfig {
+    /// The address allowed to submit batches to the batch inbox.
+    #[serde(rename = "batcherAddr")]
+    pub batcher_address: Address,
+    /// The L1 fee overhead, pre-Ecotone.
+    pub overhead: U256,
+    /// The L1 fee scalar. Post-Ecotone this encodes the base fee and blob base fee scalars.
+    pub scalar: U256,
+    /// The L2 block gas limit.
+    pub gas_limit: u64,
+    /// The L1 base fee scalar, post-Ecotone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_fee_scalar: Option<u64>,
+    /// The L1 blob base fee scalar, post-Ecotone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob_base_fee_scalar: Option<u64>,
+    /// The operator fee scalar, post-Isthmus.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator_fee_scalar: Option<u32>,
+    /// The operator fee constant, post-Isthmus.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator_fee_constant: Option<u64>,
+    /// The key signing unsafe blocks gossiped by the sequencer, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unsafe_block_signer: Option<Address>,
+}
+
+impl SystemConfig {
+    /// Returns the `(base_fee_scalar, blob_base_fee_scalar)` pair used from Ecotone onwards.
+    ///
+    /// Explicit scalars take precedence. Otherwise they are decoded from the versioned `scalar`
+    /// word: version 0 only carries the base fee scalar in its last four bytes, version 1 also
+    /// carries the blob base fee scalar in the four bytes before it.
+    pub fn ecotone_scalars(&self) -> Result<(u32, u32)> {
+        if let (Some(base), Some(blob)) = (self.base_fee_scalar, self.blob_base_fee_scalar) {
+            return Ok((base as u32, blob as u32));
+        }
+
+        let scalar = B256::from(self.scalar.to_be_bytes::<32>());
+        let base_fee_scalar = u32::from_be_bytes(scalar[28..].try_into().unwrap());
+        match scalar[0] {
+            0 if scalar[1..28].iter().any(|b| *b != 0) => {
+                // A misconfigured pre-Ecotone scalar with a non-empty padding, handled like
+                // op-node by charging the maximum base fee scalar.
+                Ok((u32::MAX, 0))
+            }
+            0 => Ok((base_fee_scalar, 0)),
+            1 if scalar[1..24].iter().any(|b| *b != 0) => {
+                bail!("invalid version 1 system config scalar {scalar}")
+            }
+            1 => Ok((base_fee_scalar, u32::from_be_bytes(scalar[24..28].try_into().unwrap()))),
+            version => bail!("unknown system config scalar version {version}"),
+        }
+    }
+}
+
+impl SystemConfig {
+    /// Applies the `ConfigUpdate` events emitted by the L1 `SystemConfig` contract at `address`
+    /// in the receipts of an L1 block.
+    ///
+    /// `ecotone` tells whether Ecotone is active at the L1 block's timestamp, from which the
+    /// overhead is no longer used.
+    pub fn update_with_receipts(
+        &mut self,
+        receipts: &[L1Receipt],
+        address: Address,
+        ecotone: bool,
+    ) -> Result<()> {
+        for receipt in receipts.iter().filter(|receipt| receipt.status()) {
+            for log in receipt.logs() {
+                if log.address == address && log.topics().first() == Some(&CONFIG_UPDATE_TOPIC) {
+                    self.apply_update_log(log, ecotone)
+                        .wrap_err("invalid system config update log")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_update_log(&mut self, log: &Log, ecotone: bool) -> Result<()> {
+        let topics = log.topics();
+        ensure!(topics.len() == 3, "expected 3 config update topics, got {}", topics.len());
+        ensure!(
+            topics[1] == CONFIG_UPDATE_EVENT_VERSION_0,
+            "unsupported config update version {}",
+            topics[1]
+        );
+
+        let data = log.data.data.as_ref();
+        ensure!(data.len() >= 64, "config update data too short");
+        let len = U256::from_be_slice(&data[32..64]);
+        let payload = &data[64..];
+        let word = |i: usize| -> Result<U256> {
+            ensure!(payload.len() >= (i + 1) * 32, "config update payload too short");
+            Ok(U256::from_be_slice(&payload[i * 32..(i + 1) * 32]))
+        };
+
+        match U256::from_be_bytes(topics[2].0) {
+            t if t == U256::from(0) => {
+                ensure!(len == U256::from(32), "invalid batcher update length {len}");
+                self.batcher_address = Address::from_word(B256::from(word(0)?));
+            }
+            t if t == U256::from(1) => {
+                ensure!(len == U256::from(64), "invalid gas config update length {len}");
+                self.overhead = if ecotone { U256::ZERO } else { word(0)? };
+                self.scalar = word(1)?;
+                // The versioned scalar word is authoritative from now on.
+                self.base_fee_scalar = None;
+                self.blob_base_fee_scalar = None;
+            }
+            t if t == U256::from(2) => {
+                ensure!(len == U256::from(32), "invalid gas limit update length {len}");
+                self.gas_limit = u64::try_from(word(0)?).wrap_err("gas limit overflows u64")?;
+            }
+            t if t == U256::from(3) => {
+                ensure!(len == U256::from(32), "invalid unsafe block signer update length {len}");
+                self.unsafe_block_signer = Some(Address::from_word(B256::from(word(0)?)));
+            }
+            t if t == U256::from(5) => {
+                ensure!(len == U256::from(32), "invalid operator fee update length {len}");
+                let value = word(0)?;
+                self.operator_fee_scalar = Some(value.as_limbs()[1] as u32);
+                self.operator_fee_constant = Some(value.as_limbs()[0]);
+            }
+            // Later update types do not affect derivation here.
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl From<&superchain_registry::SystemConfig> for SystemConfig {
+    fn from(config: &superchain_registry::SystemConfig) -> Self {
+        Self {
+            batcher_address: config.batcher_address,
+            overhead: config.overhead,
+            scalar: config.scalar,
+            gas_limit: config.gas_limit,
+            base_fee_scalar: config.base_fee_scalar,
+            blob_base_fee_scalar: config.blob_base_fee_scalar,
+            operator_fee_scalar: None,
+            operator_fee_constant: None,
+            unsafe_block_signer: None,
+        }
+    }
+}
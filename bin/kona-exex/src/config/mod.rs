@@ -0,0 +1,386 @@
+//! Rollup configuration for the L2 chain followed by Hera.
+//!
+//! The [`RollupConfig`] mirrors the `rollup.json` format consumed by op-node, so that existing
+//! configuration files can be used as-is. Configs for chains in the superchain registry can be
+//! loaded by chain ID through [`RollupConfig::from_registry`].
+
+use std::path::Path;
+
+use alloy_eips::eip1559::BaseFeeParams;
+use alloy_primitives::Address;
+use eyre::{bail, eyre, Context, Result};
+use serde::{Deserialize, Serialize};
+
+mod attributes;
+pub use attributes::{AttributesConfig, GasLimitSource};
+
+mod genesis;
+pub use genesis::{ChainGenesis, SystemConfig, CONFIG_UPDATE_EVENT_VERSION_0, CONFIG_UPDATE_TOPIC};
+
+/// The channel timeout once the Granite hardfork is active.
+pub const GRANITE_CHANNEL_TIMEOUT: u64 = 50;
+
+/// The maximum input size of the `bn256Pairing` precompile once the Granite hardfork is active,
+/// allowing at most 589 pairings.
+pub const GRANITE_BN256_PAIRING_MAX_INPUT_SIZE: usize = 112_687;
+
+/// The max sequencer drift once the Fjord hardfork is active.
+pub const FJORD_MAX_SEQUENCER_DRIFT: u64 = 1800;
+
+/// The default EIP-1559 parameters of OP Stack chains.
+pub const OP_BASE_FEE_PARAMS: BaseFeeParams =
+    BaseFeeParams { max_change_denominator: 50, elasticity_multiplier: 6 };
+
+const fn default_granite_channel_timeout() -> u64 {
+    GRANITE_CHANNEL_TIMEOUT
+}
+
+const fn default_base_fee_params() -> BaseFeeParams {
+    OP_BASE_FEE_PARAMS
+}
+
+/// The rollup configuration of an OP Stack chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RollupConfig {
+    /// The genesis state of the rollup.
+    pub genesis: ChainGenesis,
+    /// The block time of the L2, in seconds.
+    pub block_time: u64,
+    /// Sequencer batches may not be more than this many seconds after the L1 timestamp of the
+    /// sequencing window end.
+    pub max_sequencer_drift: u64,
+    /// Number of L1 blocks in a sequencing window.
+    pub seq_window_size: u64,
+    /// Number of L1 blocks after which a channel times out, before Granite.
+    pub channel_timeout: u64,
+    /// Number of L1 blocks after which a channel times out, once Granite is active.
+    #[serde(default = "default_granite_channel_timeout")]
+    pub granite_channel_timeout: u64,
+    /// The L1 chain ID.
+    pub l1_chain_id: u64,
+    /// The L2 chain ID.
+    pub l2_chain_id: u64,
+    /// EIP-1559 parameters of the L2 before Canyon.
+    #[serde(default = "default_base_fee_params")]
+    pub base_fee_params: BaseFeeParams,
+    /// EIP-1559 parameters of the L2 once Canyon is active.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canyon_base_fee_params: Option<BaseFeeParams>,
+    /// Activation timestamp of the Regolith hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regolith_time: Option<u64>,
+    /// Activation timestamp of the Canyon hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canyon_time: Option<u64>,
+    /// Activation timestamp of the Delta hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta_time: Option<u64>,
+    /// Activation timestamp of the Ecotone hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ecotone_time: Option<u64>,
+    /// Activation timestamp of the Fjord hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fjord_time: Option<u64>,
+    /// Activation timestamp of the Granite hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub granite_time: Option<u64>,
+    /// Activation timestamp of the Holocene hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub holocene_time: Option<u64>,
+    /// Activation timestamp of the Isthmus hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isthmus_time: Option<u64>,
+    /// Activation timestamp of the Interop hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interop_time: Option<u64>,
+    /// The L1 address that batches are sent to.
+    pub batch_inbox_address: Address,
+    /// The L1 address of the `OptimismPortal` deposit contract.
+    pub deposit_contract_address: Address,
+    /// The L1 address of the `SystemConfig` contract.
+    pub l1_system_config_address: Address,
+    /// The L1 address of the `ProtocolVersions` contract.
+    #[serde(default)]
+    pub protocol_versions_address: Address,
+    /// The L1 address of the data availability challenge contract, for Alt-DA chains.
+    #[serde(
+        default,
+        alias = "da_challenge_contract_address",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub da_challenge_address: Option<Address>,
+    /// The number of L1 blocks after its inclusion during which an Alt-DA commitment can be
+    /// challenged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub da_challenge_window: Option<u64>,
+    /// The number of L1 blocks after a challenge during which it can be resolved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub da_resolve_window: Option<u64>,
+    /// Defaults of the payload attributes of derived blocks, for custom chains.
+    #[serde(
+        default,
+        rename = "hera_attributes",
+        skip_serializing_if = "AttributesConfig::is_default"
+    )]
+    pub attributes: AttributesConfig,
+}
+
+impl RollupConfig {
+    /// Returns the rollup config of the given L2 chain from the superchain registry, if present.
+    pub fn from_registry(l2_chain_id: u64) -> Option<Self> {
+        superchain_registry::ROLLUP_CONFIGS.get(&l2_chain_id).map(Into::into)
+    }
+
+    /// Reads a rollup config from a `rollup.json` file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read rollup config {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .wrap_err_with(|| format!("failed to parse rollup config {}", path.display()))
+    }
+
+    /// Loads the rollup config from `path` if given, or from the superchain registry otherwise.
+    pub fn load(l2_chain_id: u64, path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => Self::from_file(path),
+            None => Self::from_registry(l2_chain_id).ok_or_else(|| {
+                eyre!(
+                    "chain {l2_chain_id} is not in the superchain registry, provide a config file"
+                )
+            }),
+        }
+    }
+
+    /// Checks the config for values op-node would reject or that cannot be intended, reporting
+    /// every problem found at once.
+    pub fn check(&self) -> Result<()> {
+        let mut problems = Vec::new();
+        let mut nonzero = |name: &str, value: u64| {
+            if value == 0 {
+                problems.push(format!("{name} must not be zero"));
+            }
+        };
+        nonzero("block_time", self.block_time);
+        nonzero("max_sequencer_drift", self.max_sequencer_drift);
+        nonzero("seq_window_size", self.seq_window_size);
+        nonzero("channel_timeout", self.channel_timeout);
+        nonzero("granite_channel_timeout", self.granite_channel_timeout);
+        nonzero("l1_chain_id", self.l1_chain_id);
+        nonzero("l2_chain_id", self.l2_chain_id);
+        nonzero("genesis.l2_time", self.genesis.l2_time);
+
+        if self.genesis.l1.hash.is_zero() {
+            problems.push("genesis.l1.hash must not be zero".to_string());
+        }
+        if self.genesis.l2.hash.is_zero() {
+            problems.push("genesis.l2.hash must not be zero".to_string());
+        }
+        for (name, address) in [
+            ("batch_inbox_address", self.batch_inbox_address),
+            ("deposit_contract_address", self.deposit_contract_address),
+        ] {
+            if address.is_zero() {
+                problems.push(format!("{name} must not be zero"));
+            }
+        }
+        // Devnets without a `SystemConfig` contract take their gas limit from the config.
+        if self.l1_system_config_address.is_zero() &&
+            self.attributes.gas_limit == GasLimitSource::SystemConfig
+        {
+            problems.push(
+                "l1_system_config_address must not be zero without a static gas limit".to_string(),
+            );
+        }
+        if self.genesis.system_config.is_none() {
+            problems.push("genesis.system_config is missing".to_string());
+        }
+        if self.base_fee_params.max_change_denominator == 0 ||
+            self.canyon_base_fee_params.is_some_and(|params| params.max_change_denominator == 0)
+        {
+            problems.push("base fee max change denominator must not be zero".to_string());
+        }
+
+        // Hardforks activate in order: each must be scheduled no earlier than the one before.
+        let forks = self.hardforks();
+        for pair in forks.windows(2) {
+            let [(prev_name, prev), (name, time)] = pair else { unreachable!() };
+            match (prev, time) {
+                (None, Some(_)) => {
+                    problems.push(format!("{name}_time is set but {prev_name}_time is not"))
+                }
+                (Some(prev), Some(time)) if time < prev => {
+                    problems.push(format!("{name}_time {time} is before {prev_name}_time {prev}"))
+                }
+                _ => {}
+            }
+        }
+
+        if !problems.is_empty() {
+            bail!("invalid rollup config:\n  - {}", problems.join("\n  - "));
+        }
+        Ok(())
+    }
+
+    /// Returns the hardforks after Bedrock with their activation timestamps, in activation order.
+    pub const fn hardforks(&self) -> [(&'static str, Option<u64>); 9] {
+        [
+            ("regolith", self.regolith_time),
+            ("canyon", self.canyon_time),
+            ("delta", self.delta_time),
+            ("ecotone", self.ecotone_time),
+            ("fjord", self.fjord_time),
+            ("granite", self.granite_time),
+            ("holocene", self.holocene_time),
+            ("isthmus", self.isthmus_time),
+            ("interop", self.interop_time),
+        ]
+    }
+
+    /// Returns the timestamp of the L2 block `number`, at or after genesis.
+    pub const fn l2_block_timestamp(&self, number: u64) -> u64 {
+        self.genesis.l2_time + number.saturating_sub(self.genesis.l2.number) * self.block_time
+    }
+
+    /// Returns true if Regolith is active at the given timestamp.
+    pub fn is_regolith_active(&self, timestamp: u64) -> bool {
+        self.regolith_time.is_some_and(|t| timestamp >= t)
+    }
+
+    /// Returns true if Canyon is active at the given timestamp.
+    pub fn is_canyon_active(&self, timestamp: u64) -> bool {
+        self.canyon_time.is_some_and(|t| timestamp >= t)
+    }
+
+    /// Returns true if Delta is active at the given timestamp.
+    pub fn is_delta_active(&self, timestamp: u64) -> bool {
+        self.delta_time.is_some_and(|t| timestamp >= t)
+    }
+
+    /// Returns true if Ecotone is active at the given timestamp.
+    pub fn is_ecotone_active(&self, timestamp: u64) -> bool {
+        self.ecotone_time.is_some_and(|t| timestamp >= t)
+    }
+
+    /// Returns true if Fjord is active at the given timestamp.
+    pub fn is_fjord_active(&self, timestamp: u64) -> bool {
+        self.fjord_time.is_some_and(|t| timestamp >= t)
+    }
+
+    /// Returns true if Granite is active at the given timestamp.
+    pub fn is_granite_active(&self, timestamp: u64) -> bool {
+        self.granite_time.is_some_and(|t| timestamp >= t)
+    }
+
+    /// Returns true if Holocene is active at the given timestamp.
+    pub fn is_holocene_active(&self, timestamp: u64) -> bool {
+        self.holocene_time.is_some_and(|t| timestamp >= t)
+    }
+
+    /// Returns true if Isthmus is active at the given timestamp.
+    pub fn is_isthmus_active(&self, timestamp: u64) -> bool {
+        self.isthmus_time.is_some_and(|t| timestamp >= t)
+    }
+
+    /// Returns true if Interop is active at the given timestamp.
+    pub fn is_interop_active(&self, timestamp: u64) -> bool {
+        self.interop_time.is_some_and(|t| timestamp >= t)
+    }
+
+    /// Returns true if `timestamp` is the first L2 block with Ecotone active.
+    pub fn is_ecotone_activation_block(&self, timestamp: u64) -> bool {
+        self.is_activation_block(self.ecotone_time, timestamp)
+    }
+
+    /// Returns true if `timestamp` is the first L2 block with Granite active.
+    pub fn is_granite_activation_block(&self, timestamp: u64) -> bool {
+        self.is_activation_block(self.granite_time, timestamp)
+    }
+
+    /// Returns true if `timestamp` is the first L2 block with Isthmus active.
+    pub fn is_isthmus_activation_block(&self, timestamp: u64) -> bool {
+        self.is_activation_block(self.isthmus_time, timestamp)
+    }
+
+    /// Returns true if `timestamp` is the first L2 block with Interop active.
+    pub fn is_interop_activation_block(&self, timestamp: u64) -> bool {
+        self.is_activation_block(self.interop_time, timestamp)
+    }
+
+    /// Returns true if the hardfork activating at `activation` is active at `timestamp` but not
+    /// at the previous L2 block.
+    fn is_activation_block(&self, activation: Option<u64>, timestamp: u64) -> bool {
+        let active = |t: u64| activation.is_some_and(|a| t >= a);
+        active(timestamp) && timestamp >= self.block_time && !active(timestamp - self.block_time)
+    }
+
+    /// Returns true if the chain uses Alt-DA.
+    pub const fn is_alt_da_enabled(&self) -> bool {
+        self.da_challenge_address.is_some()
+    }
+
+    /// Returns the max sequencer drift at the given timestamp.
+    pub fn max_sequencer_drift(&self, timestamp: u64) -> u64 {
+        if self.is_fjord_active(timestamp) {
+            FJORD_MAX_SEQUENCER_DRIFT
+        } else {
+            self.max_sequencer_drift
+        }
+    }
+
+    /// Returns the maximum input size of the `bn256Pairing` precompile at the given timestamp, or
+    /// `None` if it is unbounded.
+    pub fn bn256_pairing_max_input_size(&self, timestamp: u64) -> Option<usize> {
+        self.is_granite_active(timestamp).then_some(GRANITE_BN256_PAIRING_MAX_INPUT_SIZE)
+    }
+
+    /// Returns the channel timeout at the given timestamp.
+    ///
+    /// Granite shortens the timeout, applying it to the channels already open in the channel bank
+    /// as soon as the L1 origin passes the activation timestamp.
+    pub fn channel_timeout(&self, timestamp: u64) -> u64 {
+        if self.is_granite_active(timestamp) {
+            self.granite_channel_timeout
+        } else {
+            self.channel_timeout
+        }
+    }
+}
+
+impl From<&superchain_registry::RollupConfig> for RollupConfig {
+    fn from(config: &superchain_registry::RollupConfig) -> Self {
+        Self {
+            genesis: (&config.genesis).into(),
+            block_time: config.block_time,
+            max_sequencer_drift: config.max_sequencer_drift,
+            seq_window_size: config.seq_window_size,
+            channel_timeout: config.channel_timeout,
+            granite_channel_timeout: config.granite_channel_timeout,
+            l1_chain_id: config.l1_chain_id,
+            l2_chain_id: config.l2_chain_id,
+            base_fee_params: config.base_fee_params,
+            canyon_base_fee_params: config.canyon_base_fee_params,
+            regolith_time: config.regolith_time,
+            canyon_time: config.canyon_time,
+            delta_time: config.delta_time,
+            ecotone_time: config.ecotone_time,
+            fjord_time: config.fjord_time,
+            granite_time: config.granite_time,
+            holocene_time: config.holocene_time,
+            // The registry does not schedule Isthmus or Interop yet, they can only be set in a
+            // config file or with overrides.
+            isthmus_time: None,
+            interop_time: None,
+            batch_inbox_address: config.batch_inbox_address,
+            deposit_contract_address: config.deposit_contract_address,
+            l1_system_config_address: config.l1_system_config_address,
+            protocol_versions_address: config.protocol_versions_address,
+            da_challenge_address: config.da_challenge_address,
+            // The registry does not carry the Alt-DA windows, they can only be set in a config
+            // file or with overrides.
+            da_challenge_window: None,
+            da_resolve_window: None,
+            attributes: AttributesConfig::default(),
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Service integration of the standalone binary: readiness notifications, PID files and upgrades
+//! in place.
+//!
+//! Under systemd with `Type=notify`, the node reports `READY=1` once every service is ready, and
+//! `STOPPING=1` when shutting down, over the socket systemd passes in `NOTIFY_SOCKET`. Sending
+//! `SIGUSR2` upgrades the node in place: it shuts down cleanly, reports `RELOADING=1`, and
+//! executes the binary at its original path again with the same arguments, keeping its process ID
+//! so that neither systemd nor the PID file lose track of it. Outside systemd, notifications are
+//! skipped, and on platforms without Unix signals only the shutdown signal is handled.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{bail, eyre, Result, WrapErr};
+use tracing::{debug, info, warn};
+
+/// The environment variable systemd passes the notification socket in.
+pub const NOTIFY_SOCKET: &str = "NOTIFY_SOCKET";
+
+/// A state reported to the service manager.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotifyState {
+    /// The service finished starting up.
+    Ready,
+    /// The service is reloading, here being upgraded in place.
+    Reloading,
+    /// The service is shutting down.
+    Stopping,
+    /// A free-form status line.
+    Status(String),
+}
+
+impl fmt::Display for NotifyState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ready => f.write_str("READY=1"),
+            Self::Reloading => {
+                // systemd expects the monotonic time of the reload request along with it, which
+                // the wall clock only approximates but is enough to tell reloads apart.
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                write!(f, "RELOADING=1\nMONOTONIC_USEC={}", now.as_micros())
+            }
+            Self::Stopping => f.write_str("STOPPING=1"),
+            Self::Status(status) => write!(f, "STATUS={status}"),
+        }
+    }
+}
+
+/// Reports `states` to the service manager, returning false without doing anything when not
+/// running under one.
+pub fn notify(states: &[NotifyState]) -> Result<bool> {
+    let Some(socket) = std::env::var_os(NOTIFY_SOCKET) else { return Ok(false) };
+    let message = message(states);
+    send_notification(Path::new(&socket), &message)
+        .wrap_err_with(|| format!("failed to notify the service manager at {socket:?}"))?;
+    debug!(target: "hera::daemon", %message, "Notified the service manager");
+    Ok(true)
+}
+
+/// Returns the notification message reporting `states`.
+fn message(states: &[NotifyState]) -> String {
+    states.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(unix)]
+fn send_notification(socket: &Path, message: &str) -> io::Result<()> {
+    use std::os::unix::{ffi::OsStrExt, net::UnixDatagram};
+
+    let datagram = UnixDatagram::unbound()?;
+    match socket.as_os_str().as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+            datagram.send_to_addr(message.as_bytes(), &SocketAddr::from_abstract_name(name)?)?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "abstract notify socket"));
+        }
+        None => {
+            datagram.send_to(message.as_bytes(), socket)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send_notification(_: &Path, _: &str) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "notify sockets require Unix"))
+}
+
+/// A file holding the process ID of the running node, removed when dropped.
+///
+/// Creating it fails while it names another running process, so that two nodes never share a
+/// data directory by accident. A file naming the current process is taken over, as after an
+/// upgrade in place.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Writes the ID of the current process to `path`.
+    pub fn create(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let pid = std::process::id();
+        match fs::read_to_string(&path) {
+            Ok(contents) => match contents.trim().parse::<u32>() {
+                Ok(other) if other != pid && is_running(other) => {
+                    bail!("PID file {} is held by running process {other}", path.display())
+                }
+                _ => warn!(target: "hera::daemon", path = %path.display(), "Replacing PID file"),
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).wrap_err_with(|| format!("failed to read {}", path.display()))
+            }
+        }
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, format!("{pid}\n"))
+            .wrap_err_with(|| format!("failed to write PID file {}", path.display()))?;
+        info!(target: "hera::daemon", path = %path.display(), pid, "Wrote PID file");
+        Ok(Self { path })
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            let path = self.path.display();
+            warn!(target: "hera::daemon", %err, %path, "Failed to remove PID file");
+        }
+    }
+}
+
+/// Returns whether the process `pid` is running, assuming it is where this cannot be told.
+fn is_running(pid: u32) -> bool {
+    let proc = Path::new("/proc");
+    !proc.is_dir() || proc.join(pid.to_string()).exists()
+}
+
+/// Why the node was asked to stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopSignal {
+    /// Shut down, on `SIGINT` or `SIGTERM`.
+    Shutdown,
+    /// Shut down and start the binary again, on `SIGUSR2`.
+    Upgrade,
+}
+
+/// Waits for a signal asking the node to stop.
+#[cfg(unix)]
+pub async fn stop_signal() -> Result<StopSignal> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut upgrade = signal(SignalKind::user_defined2())?;
+    Ok(tokio::select! {
+        _ = interrupt.recv() => StopSignal::Shutdown,
+        _ = terminate.recv() => StopSignal::Shutdown,
+        _ = upgrade.recv() => StopSignal::Upgrade,
+    })
+}
+
+/// Waits for a signal asking the node to stop.
+#[cfg(not(unix))]
+pub async fn stop_signal() -> Result<StopSignal> {
+    tokio::signal::ctrl_c().await?;
+    Ok(StopSignal::Shutdown)
+}
+
+/// Returns the path of the running binary, to be resolved at startup: once an upgrade replaced
+/// the file, the path the system reports for the running binary no longer leads to it.
+pub fn current_binary() -> Result<PathBuf> {
+    std::env::current_exe().wrap_err("failed to locate the current binary")
+}
+
+/// Replaces the current process with the binary at `exe`, with the same arguments. Only returns
+/// on failure.
+#[cfg(unix)]
+pub fn reexec(exe: &Path) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    info!(target: "hera::daemon", exe = %exe.display(), "Executing upgraded binary");
+    let err = std::process::Command::new(exe).args(std::env::args_os().skip(1)).exec();
+    Err(eyre!(err).wrap_err(format!("failed to execute {}", exe.display())))
+}
+
+/// Replaces the current process with the binary at `exe`, with the same arguments. Only returns
+/// on failure.
+#[cfg(not(unix))]
+pub fn reexec(_: &Path) -> Result<()> {
+    bail!("upgrades in place require Unix")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns an empty temporary directory for the test `name`.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hera-daemon-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn pid_file_guards_against_running_processes() {
+        let path = temp_dir("pid").join("run/hera.pid");
+
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("{}\n", std::process::id()));
+        // The current process takes its own file over, as after an upgrade in place.
+        std::mem::forget(PidFile::create(&path).unwrap());
+        drop(pid_file);
+        assert!(!path.exists());
+
+        // Stale files are replaced.
+        fs::write(&path, "not a pid").unwrap();
+        drop(PidFile::create(&path).unwrap());
+
+        if Path::new("/proc/1").exists() {
+            fs::write(&path, "1\n").unwrap();
+            let err = PidFile::create(&path).unwrap_err();
+            assert!(err.to_string().contains("held by running process 1"), "{err}");
+        }
+        fs::remove_dir_all(path.parent().unwrap().parent().unwrap()).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn notifies_the_service_manager() {
+        use std::os::unix::net::UnixDatagram;
+
+        let dir = temp_dir("notify");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notify.sock");
+        let socket = UnixDatagram::bind(&path).unwrap();
+
+        let states = [NotifyState::Ready, NotifyState::Status("synced".to_string())];
+        send_notification(&path, &message(&states)).unwrap();
+        let mut buf = [0; 64];
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1\nSTATUS=synced");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
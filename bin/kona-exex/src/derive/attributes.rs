@@ -0,0 +1,401 @@
+//! The default [`AttributesBuilder`].
+
+use std::sync::Arc;
+
+use alloy_primitives::{address, Address, B256};
+use alloy_rpc_types_engine::{OptimismPayloadAttributes, PayloadAttributes};
+use async_trait::async_trait;
+use eyre::{ensure, Result, WrapErr};
+
+use crate::{
+    config::{RollupConfig, SystemConfig},
+    derive::AttributesBuilder,
+    l1::ChainProvider,
+    protocol::{decode_deposits, BlockId, L1BlockInfoTx, L2BlockInfo},
+};
+
+/// The `SequencerFeeVault` predeploy, the fee recipient of every derived L2 block.
+pub const SEQUENCER_FEE_VAULT_ADDRESS: Address =
+    address!("4200000000000000000000000000000000000011");
+
+/// Builds payload attributes from the L1 origin: the L1 info deposit, followed by the user
+/// deposits of the epoch in its first block.
+///
+/// The system config is tracked across epochs by applying the config updates of each new L1
+/// origin. Network upgrade deposits at hardfork activation blocks are not included. The gas
+/// limit and fee recipient follow the [attributes defaults](RollupConfig::attributes) of the
+/// chain.
+#[derive(Debug)]
+pub struct StatefulAttributesBuilder {
+    config: Arc<RollupConfig>,
+    provider: Arc<dyn ChainProvider>,
+    system_config: SystemConfig,
+}
+
+impl StatefulAttributesBuilder {
+    /// Creates a builder reading L1 data from `provider`, starting from the genesis system
+    /// config.
+    pub fn new(config: Arc<RollupConfig>, provider: Arc<dyn ChainProvider>) -> Self {
+        let system_config = config.genesis.system_config.clone().unwrap_or_default();
+        Self { config, provider, system_config }
+    }
+}
+
+#[async_trait]
+impl AttributesBuilder for StatefulAttributesBuilder {
+    async fn prepare_payload_attributes(
+        &mut self,
+        parent: L2BlockInfo,
+        epoch: BlockId,
+    ) -> Result<OptimismPayloadAttributes> {
+        let header = self.provider.header_by_hash(epoch.hash).await?;
+        ensure!(header.number == epoch.number, "L1 block {epoch} has number {}", header.number);
+
+        let mut transactions = Vec::new();
+        let sequence_number = if parent.l1_origin.number == epoch.number {
+            ensure!(
+                parent.l1_origin.hash == epoch.hash,
+                "epoch {epoch} reorged from under {parent}"
+            );
+            parent.seq_num + 1
+        } else {
+            ensure!(
+                header.parent_hash == parent.l1_origin.hash,
+                "epoch {epoch} does not follow the L1 origin {} of {parent}",
+                parent.l1_origin
+            );
+            let receipts = self.provider.receipts_by_hash(epoch.hash).await?;
+            self.system_config
+                .update_with_receipts(
+                    &receipts,
+                    self.config.l1_system_config_address,
+                    self.config.is_ecotone_active(header.timestamp),
+                )
+                .wrap_err_with(|| format!("failed to apply system config updates of {epoch}"))?;
+            transactions =
+                decode_deposits(&receipts, self.config.deposit_contract_address, epoch.hash)?;
+            0
+        };
+
+        let timestamp = parent.block_info.timestamp + self.config.block_time;
+        ensure!(
+            timestamp >= header.timestamp,
+            "L2 block time {timestamp} is older than its L1 origin {epoch} at {}",
+            header.timestamp
+        );
+
+        let l1_info = L1BlockInfoTx::try_new(
+            &self.config,
+            &self.system_config,
+            sequence_number,
+            &header,
+            timestamp,
+        )?;
+        transactions.insert(0, l1_info.to_deposit_tx(&self.config, timestamp).encoded_2718());
+
+        Ok(OptimismPayloadAttributes {
+            payload_attributes: PayloadAttributes {
+                timestamp,
+                prev_randao: header.mix_hash,
+                suggested_fee_recipient: self
+                    .config
+                    .attributes
+                    .fee_recipient
+                    .unwrap_or(SEQUENCER_FEE_VAULT_ADDRESS),
+                withdrawals: self.config.is_canyon_active(timestamp).then(Vec::new),
+                parent_beacon_block_root: self
+                    .config
+                    .is_ecotone_active(timestamp)
+                    .then(|| header.parent_beacon_block_root.unwrap_or(B256::ZERO)),
+            },
+            transactions: Some(transactions),
+            no_tx_pool: Some(true),
+            gas_limit: Some(self.config.attributes.gas_limit(&self.system_config)),
+        })
+    }
+
+    fn reset(&mut self, system_config: SystemConfig) {
+        self.system_config = system_config;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use alloy_primitives::{Bytes, Log, LogData, U256};
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{
+        config::{AttributesConfig, GasLimitSource},
+        l1::{mock::MockL1, VerifyingChainProvider},
+        protocol::{deposit::DEPOSIT_EVENT_TOPIC, BlockInfo, SingleBatch, TxDeposit},
+    };
+
+    const GENESIS_TIME: u64 = 1_700_000_000;
+
+    fn builder() -> (StatefulAttributesBuilder, Arc<MockL1>) {
+        let l1 = Arc::new(MockL1::new(GENESIS_TIME, 3));
+        let config = Arc::new(l1.rollup_config());
+        let provider = Arc::new(VerifyingChainProvider::new(l1.clone()));
+        (StatefulAttributesBuilder::new(config, provider), l1)
+    }
+
+    fn parent(l1: &MockL1, timestamp: u64, seq_num: u64) -> L2BlockInfo {
+        L2BlockInfo::new(
+            BlockInfo::new(B256::repeat_byte(0xaa), 10, B256::ZERO, timestamp),
+            l1.block(0).id(),
+            seq_num,
+        )
+    }
+
+    fn l1_info(attributes: &OptimismPayloadAttributes) -> L1BlockInfoTx {
+        let tx = &attributes.transactions.as_ref().unwrap()[0];
+        let deposit = TxDeposit::decode_2718(&mut &tx[..]).unwrap();
+        L1BlockInfoTx::decode_calldata(&deposit.input).unwrap()
+    }
+
+    #[tokio::test]
+    async fn starts_a_new_epoch() {
+        let (mut builder, l1) = builder();
+        let epoch = l1.block(1);
+        let attributes = builder
+            .prepare_payload_attributes(parent(&l1, epoch.timestamp - 2, 5), epoch.id())
+            .await
+            .unwrap();
+
+        let payload = &attributes.payload_attributes;
+        assert_eq!(payload.timestamp, epoch.timestamp);
+        assert_eq!(payload.prev_randao, B256::with_last_byte(1));
+        assert_eq!(payload.suggested_fee_recipient, SEQUENCER_FEE_VAULT_ADDRESS);
+        assert_eq!(payload.withdrawals, None);
+        assert_eq!(payload.parent_beacon_block_root, None);
+        assert_eq!(attributes.no_tx_pool, Some(true));
+        assert_eq!(attributes.gas_limit, Some(builder.system_config.gas_limit));
+        assert_eq!(attributes.transactions.as_ref().unwrap().len(), 1);
+
+        let info = l1_info(&attributes);
+        assert_eq!(info.id(), epoch.id());
+        assert_eq!(info.sequence_number(), 0);
+    }
+
+    #[tokio::test]
+    async fn applies_the_attributes_defaults_of_the_chain() {
+        let l1 = Arc::new(MockL1::new(GENESIS_TIME, 3));
+        let mut config = l1.rollup_config();
+        config.attributes = AttributesConfig {
+            gas_limit: GasLimitSource::Static(60_000_000),
+            fee_recipient: Some(Address::repeat_byte(1)),
+        };
+        let provider = Arc::new(VerifyingChainProvider::new(l1.clone()));
+        let mut builder = StatefulAttributesBuilder::new(Arc::new(config), provider);
+        let parent = parent(&l1, GENESIS_TIME + 4, 2);
+        let attributes =
+            builder.prepare_payload_attributes(parent, l1.block(0).id()).await.unwrap();
+
+        assert_eq!(attributes.gas_limit, Some(60_000_000));
+        assert_eq!(attributes.payload_attributes.suggested_fee_recipient, Address::repeat_byte(1));
+    }
+
+    #[tokio::test]
+    async fn continues_the_epoch_of_the_parent() {
+        let (mut builder, l1) = builder();
+        let parent = parent(&l1, GENESIS_TIME + 4, 2);
+        let attributes =
+            builder.prepare_payload_attributes(parent, l1.block(0).id()).await.unwrap();
+
+        assert_eq!(attributes.payload_attributes.timestamp, GENESIS_TIME + 6);
+        let info = l1_info(&attributes);
+        assert_eq!(info.id(), l1.block(0).id());
+        assert_eq!(info.sequence_number(), 3);
+    }
+
+    #[tokio::test]
+    async fn rejects_inconsistent_epochs() {
+        let (mut builder, l1) = builder();
+
+        let mut reorged = parent(&l1, GENESIS_TIME, 0);
+        reorged.l1_origin.hash = B256::repeat_byte(1);
+        let err = builder.prepare_payload_attributes(reorged, l1.block(0).id()).await.unwrap_err();
+        assert!(err.to_string().contains("reorged from under"), "{err}");
+
+        let err = builder
+            .prepare_payload_attributes(parent(&l1, GENESIS_TIME + 30, 0), l1.block(2).id())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("does not follow the L1 origin"), "{err}");
+
+        let err = builder
+            .prepare_payload_attributes(parent(&l1, GENESIS_TIME, 0), l1.block(1).id())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("is older than its L1 origin"), "{err}");
+
+        let mut unknown = l1.block(1).id();
+        unknown.number = 2;
+        let err = builder
+            .prepare_payload_attributes(parent(&l1, GENESIS_TIME + 30, 0), unknown)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("has number 1"), "{err}");
+    }
+
+    /// The hardforks changing payload attributes or their L1 info deposit, in activation order.
+    const HARDFORKS: [&str; 9] = [
+        "regolith", "canyon", "delta", "ecotone", "fjord", "granite", "holocene", "isthmus",
+        "interop",
+    ];
+
+    /// A golden vector: the attributes built for a batch on top of an L1 origin, with the
+    /// system config in effect.
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct GoldenVector {
+        hardfork: String,
+        activation_block: bool,
+        system_config: SystemConfig,
+        l1_origin: BlockInfo,
+        batch_transactions: Vec<Bytes>,
+        attributes: OptimismPayloadAttributes,
+    }
+
+    /// Returns a `TransactionDeposited` event of `deposit_contract`, calling `to` with `input`.
+    fn deposit_log(deposit_contract: Address, to: Address, input: &[u8]) -> Log {
+        let mut opaque = U256::from(1_000).to_be_bytes::<32>().to_vec();
+        opaque.extend_from_slice(&U256::from(500).to_be_bytes::<32>());
+        opaque.extend_from_slice(&100_000u64.to_be_bytes());
+        opaque.push(0);
+        opaque.extend_from_slice(input);
+        let mut data = U256::from(32).to_be_bytes::<32>().to_vec();
+        data.extend_from_slice(&U256::from(opaque.len()).to_be_bytes::<32>());
+        data.extend_from_slice(&opaque);
+        data.resize(data.len().div_ceil(32) * 32, 0);
+        let topics = vec![
+            DEPOSIT_EVENT_TOPIC,
+            Address::repeat_byte(0xd0).into_word(),
+            to.into_word(),
+            B256::ZERO,
+        ];
+        Log { address: deposit_contract, data: LogData::new_unchecked(topics, data.into()) }
+    }
+
+    /// Builds the golden vector of `hardfork`, active since genesis or activating at the built
+    /// block.
+    async fn golden_vector(hardfork: &str, activation_block: bool) -> GoldenVector {
+        let mut l1 = MockL1::new(GENESIS_TIME, 2);
+        let mut config = l1.rollup_config();
+        l1.push_tx_logs(
+            Default::default(),
+            vec![deposit_log(config.deposit_contract_address, Address::repeat_byte(0xd1), &[1, 2])],
+        );
+        let epoch = l1.block(2);
+
+        let fork = HARDFORKS.iter().position(|name| *name == hardfork).unwrap();
+        let times = [
+            &mut config.regolith_time,
+            &mut config.canyon_time,
+            &mut config.delta_time,
+            &mut config.ecotone_time,
+            &mut config.fjord_time,
+            &mut config.granite_time,
+            &mut config.holocene_time,
+            &mut config.isthmus_time,
+            &mut config.interop_time,
+        ];
+        for (i, time) in times.into_iter().enumerate() {
+            *time = if i == fork && activation_block {
+                Some(epoch.timestamp)
+            } else {
+                (i <= fork).then_some(0)
+            };
+        }
+
+        let system_config = SystemConfig {
+            batcher_address: Address::repeat_byte(0xba),
+            overhead: U256::from(188),
+            scalar: U256::from(684_000),
+            gas_limit: 30_000_000,
+            base_fee_scalar: Some(1_368),
+            blob_base_fee_scalar: Some(810_949),
+            operator_fee_scalar: Some(7),
+            operator_fee_constant: Some(100),
+            unsafe_block_signer: None,
+        };
+        let l1 = Arc::new(l1);
+        let provider = Arc::new(VerifyingChainProvider::new(l1.clone()));
+        let mut builder = StatefulAttributesBuilder::new(Arc::new(config), provider);
+        builder.reset(system_config.clone());
+
+        let parent = L2BlockInfo::new(
+            BlockInfo::new(
+                B256::repeat_byte(0xaa),
+                20,
+                B256::repeat_byte(0xab),
+                epoch.timestamp - 2,
+            ),
+            l1.block(1).id(),
+            5,
+        );
+        let batch = SingleBatch {
+            parent_hash: parent.block_info.hash,
+            epoch_num: epoch.number,
+            epoch_hash: epoch.hash,
+            timestamp: epoch.timestamp,
+            transactions: vec![
+                Bytes::from_static(&[0x02, 0xc0]),
+                Bytes::from_static(&[0x01, 0xc0]),
+            ],
+        };
+        // The pipeline appends the batch transactions to the built attributes.
+        let mut attributes = builder.prepare_payload_attributes(parent, epoch.id()).await.unwrap();
+        attributes.transactions.get_or_insert_with(Vec::new).extend(batch.transactions.clone());
+
+        GoldenVector {
+            hardfork: hardfork.to_string(),
+            activation_block,
+            system_config,
+            l1_origin: epoch,
+            batch_transactions: batch.transactions,
+            attributes,
+        }
+    }
+
+    /// Checks the attributes built at every hardfork, and at the activation blocks of the ones
+    /// changing the L1 info deposit, against the vectors in `tests/fixtures/attributes`.
+    ///
+    /// Rewrite the vectors after an intended change with `HERA_UPDATE_GOLDEN=1 cargo test
+    /// -p kona-exex matches_golden_vectors`, and review their diff.
+    #[tokio::test]
+    async fn matches_golden_vectors() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/attributes");
+        let update = std::env::var_os("HERA_UPDATE_GOLDEN").is_some();
+        let mut cases: Vec<_> = HARDFORKS.iter().map(|fork| (*fork, false)).collect();
+        cases.extend(["ecotone", "isthmus", "interop"].map(|fork| (fork, true)));
+
+        for (hardfork, activation_block) in cases {
+            let name = if activation_block {
+                format!("{hardfork}-activation.json")
+            } else {
+                format!("{hardfork}.json")
+            };
+            let path = dir.join(&name);
+            let vector =
+                serde_json::to_value(golden_vector(hardfork, activation_block).await).unwrap();
+            if update {
+                std::fs::create_dir_all(&dir).unwrap();
+                let json = serde_json::to_string_pretty(&vector).unwrap();
+                std::fs::write(&path, json + "\n").unwrap();
+                continue;
+            }
+            let golden = std::fs::read(&path).unwrap_or_else(|_| {
+                panic!(
+                    "missing golden vector {}, write it with HERA_UPDATE_GOLDEN=1",
+                    path.display()
+                )
+            });
+            let golden: serde_json::Value = serde_json::from_slice(&golden).unwrap();
+            assert_eq!(vector, golden, "attributes differ from the golden vector {name}");
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! The default [`BatchFilter`], enforcing the batch validity rules of the protocol.
+
+use crate::{
+    derive::{BatchContext, BatchFilter, BatchValidity},
+    protocol::{deposit::DEPOSIT_TX_TYPE, SingleBatch},
+};
+
+/// Checks a batch's timestamp, parent, L1 origin, sequencer drift and transactions against
+/// the safe head.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProtocolBatchFilter;
+
+impl BatchFilter for ProtocolBatchFilter {
+    fn check(&mut self, batch: &SingleBatch, ctx: &BatchContext<'_>) -> BatchValidity {
+        let config = ctx.config;
+        let safe_head = ctx.safe_head;
+        let next_timestamp = safe_head.block_info.timestamp + config.block_time;
+        if batch.timestamp > next_timestamp {
+            return BatchValidity::Future;
+        }
+        if batch.timestamp < next_timestamp {
+            return BatchValidity::Drop("batch is older than the next block".to_string());
+        }
+        if batch.parent_hash != safe_head.block_info.hash {
+            return BatchValidity::Drop(format!("batch does not build on {safe_head}"));
+        }
+        if batch.epoch_num + config.seq_window_size < ctx.inclusion.number {
+            return BatchValidity::Drop(format!(
+                "batch was included after its sequencing window, in {}",
+                ctx.inclusion
+            ));
+        }
+
+        let current_epoch = safe_head.l1_origin.number;
+        if batch.epoch_num < current_epoch || batch.epoch_num > current_epoch + 1 {
+            return BatchValidity::Drop(format!(
+                "batch epoch {} is not {current_epoch} or the one after",
+                batch.epoch_num
+            ));
+        }
+        let Some(epoch) = ctx.l1_block(batch.epoch_num) else {
+            return BatchValidity::Undecided;
+        };
+        if epoch.hash != batch.epoch_hash {
+            return BatchValidity::Drop(format!("batch epoch hash does not match {epoch}"));
+        }
+        if batch.timestamp < epoch.timestamp {
+            return BatchValidity::Drop(format!("batch is older than its L1 origin {epoch}"));
+        }
+
+        if batch.timestamp > epoch.timestamp + config.max_sequencer_drift(epoch.timestamp) {
+            if !batch.transactions.is_empty() {
+                return BatchValidity::Drop("batch exceeds the sequencer time drift".to_string());
+            }
+            // An empty batch past the drift is only valid if the next L1 origin cannot be
+            // adopted yet.
+            match ctx.l1_block(epoch.number + 1) {
+                Some(next) if batch.timestamp >= next.timestamp => {
+                    return BatchValidity::Drop(
+                        "empty batch exceeds the sequencer drift but could adopt the next origin"
+                            .to_string(),
+                    );
+                }
+                Some(_) => {}
+                None => return BatchValidity::Undecided,
+            }
+        }
+
+        for (i, tx) in batch.transactions.iter().enumerate() {
+            match tx.first() {
+                None => return BatchValidity::Drop(format!("transaction {i} is empty")),
+                Some(&DEPOSIT_TX_TYPE) => {
+                    return BatchValidity::Drop(format!("transaction {i} is a deposit"))
+                }
+                Some(_) => {}
+            }
+        }
+        BatchValidity::Accept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{Bytes, B256};
+
+    use super::*;
+    use crate::{
+        config::RollupConfig,
+        protocol::{BlockInfo, L2BlockInfo},
+    };
+
+    type Mutation = fn(&mut Case);
+
+    /// A batch for the block after `safe_head`, checked with the L1 blocks 10 and 11 known.
+    struct Case {
+        batch: SingleBatch,
+        safe_head: L2BlockInfo,
+        inclusion: BlockInfo,
+        l1_blocks: Vec<BlockInfo>,
+    }
+
+    fn l1_block(number: u64, timestamp: u64) -> BlockInfo {
+        BlockInfo::new(B256::with_last_byte(number as u8), number, B256::ZERO, timestamp)
+    }
+
+    fn case() -> Case {
+        let l1_blocks = vec![l1_block(10, 1_000), l1_block(11, 1_012)];
+        let safe_head = L2BlockInfo::new(
+            BlockInfo::new(B256::repeat_byte(0xaa), 100, B256::ZERO, 1_000),
+            l1_blocks[0].id(),
+            0,
+        );
+        Case {
+            batch: SingleBatch {
+                parent_hash: safe_head.block_info.hash,
+                epoch_num: 10,
+                epoch_hash: l1_blocks[0].hash,
+                timestamp: 1_002,
+                transactions: vec![Bytes::from_static(&[0x02, 0xc0])],
+            },
+            safe_head,
+            inclusion: l1_blocks[1],
+            l1_blocks,
+        }
+    }
+
+    /// Moves the safe head to the sequencer drift limit of epoch 10, with the batch on top.
+    fn at_drift_limit(case: &mut Case) {
+        case.safe_head.block_info.timestamp = 1_600;
+        case.batch.timestamp = 1_602;
+    }
+
+    fn check(mutate: Mutation) -> BatchValidity {
+        let config = RollupConfig::from_registry(10).unwrap();
+        let mut case = case();
+        mutate(&mut case);
+        let ctx = BatchContext {
+            config: &config,
+            safe_head: case.safe_head,
+            inclusion: case.inclusion,
+            l1_blocks: &case.l1_blocks,
+        };
+        ProtocolBatchFilter.check(&case.batch, &ctx)
+    }
+
+    #[test]
+    fn accepts_valid_batches() {
+        let valid: [Mutation; 3] = [
+            |_| {},
+            // The next epoch, once its L1 block is known.
+            |case| {
+                case.batch.epoch_num = 11;
+                case.batch.epoch_hash = case.l1_blocks[1].hash;
+                case.batch.timestamp = 1_012;
+                case.safe_head.block_info.timestamp = 1_010;
+            },
+            // An empty batch past the drift, as the next epoch cannot be adopted yet.
+            |case| {
+                at_drift_limit(case);
+                case.batch.transactions.clear();
+                case.l1_blocks[1].timestamp = 2_000;
+            },
+        ];
+        for mutate in valid {
+            assert_eq!(check(mutate), BatchValidity::Accept);
+        }
+    }
+
+    #[test]
+    fn keeps_future_and_undecided_batches() {
+        assert_eq!(check(|case| case.batch.timestamp = 1_004), BatchValidity::Future);
+
+        let undecided: [Mutation; 2] = [
+            |case| {
+                case.batch.epoch_num = 11;
+                case.l1_blocks.truncate(1);
+            },
+            |case| {
+                at_drift_limit(case);
+                case.batch.transactions.clear();
+                case.l1_blocks.truncate(1);
+            },
+        ];
+        for mutate in undecided {
+            assert_eq!(check(mutate), BatchValidity::Undecided);
+        }
+    }
+
+    #[test]
+    fn drops_invalid_batches() {
+        let invalid: [(Mutation, &str); 11] = [
+            (|case| case.batch.timestamp = 1_000, "older than the next block"),
+            (|case| case.batch.parent_hash = B256::ZERO, "does not build on"),
+            (|case| case.inclusion.number = 3_611, "after its sequencing window"),
+            (|case| case.batch.epoch_num = 9, "is not 10 or the one after"),
+            (|case| case.batch.epoch_num = 12, "is not 10 or the one after"),
+            (|case| case.batch.epoch_hash = B256::ZERO, "epoch hash does not match"),
+            (
+                |case| {
+                    case.batch.epoch_num = 11;
+                    case.batch.epoch_hash = case.l1_blocks[1].hash;
+                },
+                "older than its L1 origin",
+            ),
+            (at_drift_limit, "exceeds the sequencer time drift"),
+            (
+                |case| {
+                    at_drift_limit(case);
+                    case.batch.transactions.clear();
+                },
+                "could adopt the next origin",
+            ),
+            (|case| case.batch.transactions.push(Bytes::new()), "transaction 1 is empty"),
+            (
+                |case| case.batch.transactions[0] = Bytes::from_static(&[DEPOSIT_TX_TYPE]),
+                "transaction 0 is a deposit",
+            ),
+        ];
+        for (mutate, expected) in invalid {
+            match check(mutate) {
+                BatchValidity::Drop(reason) => assert!(reason.contains(expected), "{reason}"),
+                verdict => panic!("expected {expected}, got {verdict:?}"),
+            }
+        }
+    }
+}
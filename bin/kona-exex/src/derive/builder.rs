@@ -0,0 +1,270 @@
+//! The [`PipelineBuilder`], composing a [`DerivationPipeline`] from its stages.
+
+use std::sync::Arc;
+
+use eyre::{eyre, Result};
+
+use crate::{
+    blobs::BlobFetcher,
+    config::RollupConfig,
+    derive::{
+        AttributesBuilder, BatchFilter, BatcherTxFilter, DataSource, DecompressionPool,
+        DerivationPipeline, EthereumDataSource, L2ChainProvider, ProtocolBatchFilter,
+        StatefulAttributesBuilder, DEFAULT_DECOMPRESSION_QUEUE_SIZE, DEFAULT_DECOMPRESSION_WORKERS,
+    },
+    l1::{ChainProvider, VerifyingChainProvider},
+};
+
+type Wrapper<T> = Box<dyn FnOnce(Box<T>) -> Box<T> + Send>;
+
+/// Builds a [`DerivationPipeline`], with any of its stages replaced or wrapped.
+///
+/// By default the pipeline reads batcher data with an [`EthereumDataSource`], checks batches with
+/// the [`ProtocolBatchFilter`] and builds attributes with a [`StatefulAttributesBuilder`]. Wrappers
+/// are applied in the order they were added, around the default or replaced stage.
+///
+/// The L1 provider is wrapped in a [`VerifyingChainProvider`], so receipts from any source are
+/// checked against their block header before use.
+#[derive(Default)]
+pub struct PipelineBuilder {
+    config: Option<Arc<RollupConfig>>,
+    provider: Option<Arc<dyn ChainProvider>>,
+    l2_provider: Option<Arc<dyn L2ChainProvider>>,
+    blobs: Option<BlobFetcher>,
+    tx_filter: Option<Box<dyn BatcherTxFilter>>,
+    data_source: Option<Box<dyn DataSource>>,
+    data_source_wrappers: Vec<Wrapper<dyn DataSource>>,
+    protocol_filter: bool,
+    filters: Vec<Box<dyn BatchFilter>>,
+    attributes: Option<Box<dyn AttributesBuilder>>,
+    attributes_wrappers: Vec<Wrapper<dyn AttributesBuilder>>,
+    decompression_workers: usize,
+    decompression_queue_size: usize,
+}
+
+impl std::fmt::Debug for PipelineBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PipelineBuilder")
+            .field("config", &self.config.as_ref().map(|c| c.l2_chain_id))
+            .field("tx_filter", &self.tx_filter)
+            .field("data_source", &self.data_source)
+            .field("data_source_wrappers", &self.data_source_wrappers.len())
+            .field("protocol_filter", &self.protocol_filter)
+            .field("filters", &self.filters)
+            .field("attributes", &self.attributes)
+            .field("attributes_wrappers", &self.attributes_wrappers.len())
+            .field("decompression_workers", &self.decompression_workers)
+            .field("decompression_queue_size", &self.decompression_queue_size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PipelineBuilder {
+    /// Creates a builder for the given rollup config and L1 chain.
+    pub fn new(config: Arc<RollupConfig>, provider: Arc<dyn ChainProvider>) -> Self {
+        Self {
+            config: Some(config),
+            provider: Some(Arc::new(VerifyingChainProvider::new(provider))),
+            protocol_filter: true,
+            decompression_workers: DEFAULT_DECOMPRESSION_WORKERS,
+            decompression_queue_size: DEFAULT_DECOMPRESSION_QUEUE_SIZE,
+            ..Default::default()
+        }
+    }
+
+    /// Reads the system config from the L2 chain on reset, instead of the genesis one.
+    pub fn l2_chain_provider(mut self, provider: Arc<dyn L2ChainProvider>) -> Self {
+        self.l2_provider = Some(provider);
+        self
+    }
+
+    /// Fetches blobs with `blobs` in the default data source.
+    pub fn blob_fetcher(mut self, blobs: BlobFetcher) -> Self {
+        self.blobs = Some(blobs);
+        self
+    }
+
+    /// Selects batcher transactions with `filter` in the default data source, e.g. a
+    /// [`ContractInbox`](crate::derive::ContractInbox) for chains whose batch inbox is a contract.
+    pub fn batcher_tx_filter(mut self, filter: impl BatcherTxFilter + 'static) -> Self {
+        self.tx_filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Replaces the data source.
+    pub fn data_source(mut self, data_source: impl DataSource + 'static) -> Self {
+        self.data_source = Some(Box::new(data_source));
+        self
+    }
+
+    /// Wraps the data source, e.g. to record or alter the batcher data it returns.
+    pub fn wrap_data_source(
+        mut self,
+        wrap: impl FnOnce(Box<dyn DataSource>) -> Box<dyn DataSource> + Send + 'static,
+    ) -> Self {
+        self.data_source_wrappers.push(Box::new(wrap));
+        self
+    }
+
+    /// Adds a batch filter, run after the protocol rules and the filters added before it.
+    pub fn batch_filter(mut self, filter: impl BatchFilter + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Removes the [`ProtocolBatchFilter`], leaving only the filters added with
+    /// [`Self::batch_filter`]. Chains with modified batch validity rules start from here.
+    pub const fn without_protocol_batch_filter(mut self) -> Self {
+        self.protocol_filter = false;
+        self
+    }
+
+    /// Replaces the attributes builder.
+    pub fn attributes_builder(mut self, attributes: impl AttributesBuilder + 'static) -> Self {
+        self.attributes = Some(Box::new(attributes));
+        self
+    }
+
+    /// Wraps the attributes builder, e.g. to record or alter the attributes it prepares.
+    pub fn wrap_attributes_builder(
+        mut self,
+        wrap: impl FnOnce(Box<dyn AttributesBuilder>) -> Box<dyn AttributesBuilder> + Send + 'static,
+    ) -> Self {
+        self.attributes_wrappers.push(Box::new(wrap));
+        self
+    }
+
+    /// Decompresses channels on `workers` threads, with at most `queue_size` channels in flight.
+    pub const fn decompression(mut self, workers: usize, queue_size: usize) -> Self {
+        self.decompression_workers = workers;
+        self.decompression_queue_size = queue_size;
+        self
+    }
+
+    /// Builds the pipeline. It must be reset before its first step.
+    pub fn build(self) -> Result<DerivationPipeline> {
+        let config = self.config.ok_or_else(|| eyre!("pipeline builder has no rollup config"))?;
+        let provider = self.provider.ok_or_else(|| eyre!("pipeline builder has no L1 provider"))?;
+
+        let data_source = self.data_source.unwrap_or_else(|| {
+            let source = EthereumDataSource::new(config.clone(), provider.clone(), self.blobs);
+            match self.tx_filter {
+                Some(filter) => Box::new(source.with_tx_filter(filter)),
+                None => Box::new(source),
+            }
+        });
+        let data_source = self.data_source_wrappers.into_iter().fold(data_source, |s, w| w(s));
+
+        let attributes = self.attributes.unwrap_or_else(|| {
+            Box::new(StatefulAttributesBuilder::new(config.clone(), provider.clone()))
+        });
+        let attributes = self.attributes_wrappers.into_iter().fold(attributes, |a, w| w(a));
+
+        let mut filters: Vec<Box<dyn BatchFilter>> = Vec::new();
+        if self.protocol_filter {
+            filters.push(Box::new(ProtocolBatchFilter));
+        }
+        filters.extend(self.filters);
+
+        Ok(DerivationPipeline::new(
+            config,
+            provider,
+            self.l2_provider,
+            data_source,
+            filters,
+            attributes,
+            DecompressionPool::new(self.decompression_workers, self.decompression_queue_size),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use alloy_rpc_types_engine::OptimismPayloadAttributes;
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::{
+        config::SystemConfig,
+        derive::{BatchContext, BatchValidity, Pipeline},
+        l1::mock::MockL1,
+        protocol::{BlockId, L2BlockInfo, SingleBatch},
+    };
+
+    /// Wraps an attributes builder, recording its tag in `resets` when reset.
+    #[derive(Debug)]
+    struct Tagged {
+        tag: &'static str,
+        resets: Arc<Mutex<Vec<&'static str>>>,
+        inner: Box<dyn AttributesBuilder>,
+    }
+
+    #[async_trait]
+    impl AttributesBuilder for Tagged {
+        async fn prepare_payload_attributes(
+            &mut self,
+            parent: L2BlockInfo,
+            epoch: BlockId,
+        ) -> Result<OptimismPayloadAttributes> {
+            self.inner.prepare_payload_attributes(parent, epoch).await
+        }
+
+        fn reset(&mut self, system_config: SystemConfig) {
+            self.resets.lock().unwrap().push(self.tag);
+            self.inner.reset(system_config);
+        }
+    }
+
+    #[derive(Debug)]
+    struct RejectAll;
+
+    impl BatchFilter for RejectAll {
+        fn check(&mut self, _: &SingleBatch, _: &BatchContext<'_>) -> BatchValidity {
+            BatchValidity::Drop("rejected".to_string())
+        }
+    }
+
+    fn builder() -> PipelineBuilder {
+        let l1 = MockL1::new(0, 1);
+        PipelineBuilder::new(Arc::new(l1.rollup_config()), Arc::new(l1))
+    }
+
+    #[tokio::test]
+    async fn applies_wrappers_in_order() {
+        let resets = Arc::new(Mutex::new(Vec::new()));
+        let wrap = |tag| {
+            let resets = resets.clone();
+            move |inner| Box::new(Tagged { tag, resets, inner }) as Box<dyn AttributesBuilder>
+        };
+        let l1 = MockL1::new(0, 1);
+        let origin = l1.block(0);
+        let mut pipeline = PipelineBuilder::new(Arc::new(l1.rollup_config()), Arc::new(l1))
+            .wrap_attributes_builder(wrap("first"))
+            .wrap_attributes_builder(wrap("second"))
+            .build()
+            .unwrap();
+
+        // The last wrapper added is the outermost one.
+        pipeline.reset(L2BlockInfo::default(), origin).await.unwrap();
+        assert_eq!(*resets.lock().unwrap(), ["second", "first"]);
+    }
+
+    #[test]
+    fn replaces_the_protocol_batch_filter() {
+        let debug = format!("{:?}", builder().batch_filter(RejectAll).build().unwrap());
+        assert!(debug.contains("filters: [ProtocolBatchFilter, RejectAll]"), "{debug}");
+
+        let pipeline =
+            builder().without_protocol_batch_filter().batch_filter(RejectAll).build().unwrap();
+        let debug = format!("{pipeline:?}");
+        assert!(debug.contains("filters: [RejectAll]"), "{debug}");
+    }
+
+    #[test]
+    fn requires_a_config_and_provider() {
+        let err = PipelineBuilder::default().build().unwrap_err();
+        assert!(err.to_string().contains("no rollup config"), "{err}");
+    }
+}
@@ -0,0 +1,459 @@
+//! The channel bank, buffering frames until their channel is complete.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+use alloy_primitives::Bytes;
+use metrics::{counter, gauge};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tracing::{debug, trace, warn};
+
+use crate::{
+    config::RollupConfig,
+    protocol::{BlockInfo, Channel, ChannelId, Frame, FrameError},
+};
+
+/// Maximum summed size of the channels held by the bank before the oldest are pruned.
+pub const MAX_CHANNEL_BANK_SIZE: usize = 100_000_000;
+
+/// Number of ids of channels that left the bank remembered to detect reused channel ids.
+const REMEMBERED_CHANNEL_IDS: usize = 1_000;
+
+/// Counts of batcher misbehavior seen by the channel bank since the node started, as reported
+/// by the `hera_channelBank` debug RPC.
+///
+/// None of them affects derivation, which ignores the offending frames, but an honest batcher
+/// produces none of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameAnomalies {
+    /// Frames dropped as duplicates of a frame of their channel.
+    pub duplicate_frames: u64,
+    /// Frames dropped for following the last frame of their channel or closing it twice.
+    pub invalid_frames: u64,
+    /// Frames dropped because their channel timed out.
+    pub timed_out_frames: u64,
+    /// Frames received after a frame of their channel with a higher number.
+    pub out_of_order_frames: u64,
+    /// Channels opened with the id of a channel that already left the bank.
+    pub reused_channel_ids: u64,
+}
+
+/// A frame held by the channel bank, as reported by the `hera_frames` debug RPC.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameSummary {
+    /// The channel of the frame.
+    pub channel: ChannelId,
+    /// The frame number.
+    pub number: u16,
+    /// Length of the frame data.
+    pub len: usize,
+    /// Whether this is the last frame of the channel.
+    pub is_last: bool,
+}
+
+/// A channel held by the channel bank, as reported by the `hera_channelBank` debug RPC.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelSummary {
+    /// The channel id.
+    pub id: ChannelId,
+    /// The L1 block the channel was opened in.
+    pub open_block: BlockInfo,
+    /// The highest L1 block any frame of the channel was included in.
+    pub highest_l1_inclusion: BlockInfo,
+    /// Number of L1 blocks since the channel was opened.
+    pub age: u64,
+    /// Number of frames received.
+    pub frame_count: usize,
+    /// Summed encoded size of the frames.
+    pub size: usize,
+    /// Whether the last frame was received.
+    pub closed: bool,
+    /// Whether every frame was received.
+    pub ready: bool,
+    /// The frames received.
+    #[serde(skip)]
+    pub frames: Vec<FrameSummary>,
+}
+
+/// The contents of the channel bank, in queue order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelBankSnapshot {
+    /// The L1 origin of the bank.
+    pub origin: Option<BlockInfo>,
+    /// Summed size of all channels.
+    pub size: usize,
+    /// The channels, oldest first.
+    pub channels: Vec<ChannelSummary>,
+    /// The batcher misbehavior seen so far.
+    pub anomalies: FrameAnomalies,
+}
+
+/// Buffers frames by channel and releases the data of complete channels, oldest first.
+///
+/// Channels that are not complete within the channel timeout are dropped, as are the oldest
+/// channels once the bank exceeds [`MAX_CHANNEL_BANK_SIZE`]. The channel at the front of the
+/// queue, read next, is never pruned.
+#[derive(Debug)]
+pub struct ChannelBank {
+    config: Arc<RollupConfig>,
+    channels: HashMap<ChannelId, Channel>,
+    queue: VecDeque<ChannelId>,
+    origin: Option<BlockInfo>,
+    max_size: usize,
+    /// The ids of the channels that left the bank, most recent last.
+    departed: VecDeque<ChannelId>,
+    anomalies: FrameAnomalies,
+    snapshot: watch::Sender<ChannelBankSnapshot>,
+}
+
+impl ChannelBank {
+    /// Creates an empty channel bank.
+    pub fn new(config: Arc<RollupConfig>) -> Self {
+        Self {
+            config,
+            channels: HashMap::new(),
+            queue: VecDeque::new(),
+            origin: None,
+            max_size: MAX_CHANNEL_BANK_SIZE,
+            departed: VecDeque::new(),
+            anomalies: FrameAnomalies::default(),
+            snapshot: watch::Sender::new(ChannelBankSnapshot::default()),
+        }
+    }
+
+    /// Sets the summed size of the channels above which the oldest are pruned.
+    pub const fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Subscribes to the contents of the bank, updated whenever it changes.
+    pub fn subscribe(&self) -> watch::Receiver<ChannelBankSnapshot> {
+        self.snapshot.subscribe()
+    }
+
+    /// Returns the L1 origin of the bank.
+    pub const fn origin(&self) -> Option<BlockInfo> {
+        self.origin
+    }
+
+    /// Returns the number of channels.
+    pub fn channel_count(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns the summed size of all channels.
+    pub fn size(&self) -> usize {
+        self.channels.values().map(Channel::size).sum()
+    }
+
+    /// Adds a frame read from `origin` to its channel, opening the channel if needed.
+    pub fn ingest_frame(&mut self, frame: Frame, origin: BlockInfo) {
+        self.ingest_frames([frame], origin);
+    }
+
+    /// Adds the frames read from `origin` to their channels, publishing the contents of the bank
+    /// once they are all in.
+    pub fn ingest_frames(&mut self, frames: impl IntoIterator<Item = Frame>, origin: BlockInfo) {
+        for frame in frames {
+            self.add_frame(frame, origin);
+            self.prune();
+        }
+        self.publish();
+    }
+
+    fn add_frame(&mut self, frame: Frame, origin: BlockInfo) {
+        self.origin = Some(origin);
+        trace!(
+            target: "hera::channel_bank",
+            channel = %frame.id,
+            number = frame.number,
+            len = frame.data.len(),
+            is_last = frame.is_last,
+            origin = %origin,
+            "Ingesting frame"
+        );
+
+        let id = frame.id;
+        if !self.channels.contains_key(&id) && self.departed.contains(&id) {
+            warn!(target: "hera::channel_bank", channel = %id, "Channel id reused");
+            counter!("hera_channel_bank_reused_channel_ids_total").increment(1);
+            self.anomalies.reused_channel_ids += 1;
+        }
+        let channel = self.channels.entry(id).or_insert_with(|| {
+            debug!(target: "hera::channel_bank", channel = %id, origin = %origin, "Opened channel");
+            self.queue.push_back(id);
+            Channel::new(id, origin)
+        });
+        if is_timed_out(&self.config, channel, origin) {
+            warn!(target: "hera::channel_bank", channel = %id, "Ignoring frame of timed out channel");
+            counter!("hera_channel_bank_dropped_frames_total", "reason" => "timed_out")
+                .increment(1);
+            self.anomalies.timed_out_frames += 1;
+            return;
+        }
+        let number = frame.number;
+        let highest = channel.highest_frame_number();
+        match channel.add_frame(frame, origin) {
+            Ok(()) if highest.is_some_and(|highest| number < highest) => {
+                debug!(target: "hera::channel_bank", channel = %id, number, "Frame out of order");
+                counter!("hera_channel_bank_out_of_order_frames_total").increment(1);
+                self.anomalies.out_of_order_frames += 1;
+            }
+            Ok(()) => {}
+            Err(err) => {
+                warn!(target: "hera::channel_bank", channel = %id, %err, "Dropping invalid frame");
+                counter!("hera_channel_bank_dropped_frames_total", "reason" => err.kind())
+                    .increment(1);
+                match err {
+                    FrameError::Duplicate(_) => self.anomalies.duplicate_frames += 1,
+                    _ => self.anomalies.invalid_frames += 1,
+                }
+            }
+        }
+    }
+
+    /// Returns the data of the oldest channel, once complete. Timed out channels at the front of
+    /// the queue are dropped.
+    pub fn read(&mut self, origin: BlockInfo) -> Option<Bytes> {
+        let mut changed = self.origin.replace(origin) != Some(origin);
+        let mut data = None;
+        while let Some(id) = self.queue.front().copied() {
+            let channel = &self.channels[&id];
+            if is_timed_out(&self.config, channel, origin) {
+                debug!(target: "hera::channel_bank", channel = %id, "Channel timed out");
+                self.remove_front();
+                changed = true;
+                continue;
+            }
+            if channel.is_ready() {
+                data = channel.frame_data();
+                debug!(target: "hera::channel_bank", channel = %id, "Read complete channel");
+                self.remove_front();
+                changed = true;
+            }
+            break;
+        }
+        // Reads find nothing new most of the time, only publish actual changes.
+        if changed {
+            self.publish();
+        }
+        data
+    }
+
+    /// Returns the batcher misbehavior seen so far.
+    pub const fn anomalies(&self) -> FrameAnomalies {
+        self.anomalies
+    }
+
+    /// Drops every channel.
+    pub fn reset(&mut self) {
+        self.channels.clear();
+        self.queue.clear();
+        // Batcher data is read again after a reset, with the same channel ids.
+        self.departed.clear();
+        self.origin = None;
+        self.publish();
+    }
+
+    /// Returns the contents of the bank.
+    pub fn snapshot(&self) -> ChannelBankSnapshot {
+        let origin_number = self.origin.map(|o| o.number).unwrap_or_default();
+        let channels = self
+            .queue
+            .iter()
+            .map(|id| {
+                let channel = &self.channels[id];
+                ChannelSummary {
+                    id: *id,
+                    open_block: channel.open_block(),
+                    highest_l1_inclusion: channel.highest_l1_inclusion(),
+                    age: origin_number.saturating_sub(channel.open_block().number),
+                    frame_count: channel.frame_count(),
+                    size: channel.size(),
+                    closed: channel.is_closed(),
+                    ready: channel.is_ready(),
+                    frames: channel
+                        .frames()
+                        .map(|frame| FrameSummary {
+                            channel: *id,
+                            number: frame.number,
+                            len: frame.data.len(),
+                            is_last: frame.is_last,
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        ChannelBankSnapshot {
+            origin: self.origin,
+            size: self.size(),
+            channels,
+            anomalies: self.anomalies,
+        }
+    }
+
+    /// Drops the oldest channels past the front one until the bank fits its maximum size.
+    fn prune(&mut self) {
+        let mut size = self.size();
+        while size > self.max_size && self.queue.len() > 1 {
+            let Some(channel) = self.queue.remove(1).and_then(|id| self.channels.remove(&id))
+            else {
+                break;
+            };
+            warn!(target: "hera::channel_bank", channel = %channel.id(), "Pruned channel");
+            size -= channel.size();
+        }
+    }
+
+    /// Removes the channel at the front of the queue, once read or timed out. Its id is
+    /// remembered: honest batchers never reuse it. Pruned channels are not remembered, as their
+    /// frames may still be on their way.
+    fn remove_front(&mut self) -> Option<Channel> {
+        let id = self.queue.pop_front()?;
+        if self.departed.len() == REMEMBERED_CHANNEL_IDS {
+            self.departed.pop_front();
+        }
+        self.departed.push_back(id);
+        self.channels.remove(&id)
+    }
+
+    fn publish(&self) {
+        gauge!("hera_channel_bank_channels").set(self.queue.len() as f64);
+        gauge!("hera_channel_bank_size_bytes").set(self.size() as f64);
+        if self.snapshot.receiver_count() > 0 {
+            self.snapshot.send_replace(self.snapshot());
+        }
+    }
+}
+
+/// Returns true if `channel` can no longer be completed at `origin`.
+fn is_timed_out(config: &RollupConfig, channel: &Channel, origin: BlockInfo) -> bool {
+    channel.open_block().number + config.channel_timeout(origin.timestamp) < origin.number
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::B256;
+
+    use super::*;
+    use crate::protocol::frame::FRAME_OVERHEAD;
+
+    fn bank() -> ChannelBank {
+        ChannelBank::new(Arc::new(RollupConfig::from_registry(10).unwrap()))
+    }
+
+    fn origin(number: u64) -> BlockInfo {
+        BlockInfo::new(B256::with_last_byte(number as u8), number, B256::ZERO, number * 12)
+    }
+
+    fn frame(id: u8, number: u16, data: &[u8], is_last: bool) -> Frame {
+        Frame { id: ChannelId([id; 16]), number, data: Bytes::copy_from_slice(data), is_last }
+    }
+
+    fn ids(bank: &ChannelBank) -> Vec<u8> {
+        bank.snapshot().channels.iter().map(|channel| channel.id.0[0]).collect()
+    }
+
+    #[test]
+    fn reads_channels_oldest_first() {
+        let mut bank = bank();
+        bank.ingest_frames([frame(1, 0, b"a", false), frame(2, 0, b"b", true)], origin(1));
+        // The second channel is complete, but waits for the first one.
+        assert_eq!(bank.read(origin(1)), None);
+
+        bank.ingest_frame(frame(1, 1, b"c", true), origin(2));
+        assert_eq!(bank.read(origin(2)), Some(Bytes::from_static(b"ac")));
+        assert_eq!(bank.read(origin(2)), Some(Bytes::from_static(b"b")));
+        assert_eq!(bank.read(origin(2)), None);
+        assert_eq!(bank.size(), 0);
+    }
+
+    #[test]
+    fn drops_timed_out_channels() {
+        let mut bank = bank();
+        let timeout = bank.config.channel_timeout(0);
+        bank.ingest_frames([frame(1, 0, b"a", false), frame(2, 0, b"b", true)], origin(1));
+
+        let late = origin(2 + timeout);
+        bank.ingest_frame(frame(1, 1, b"c", true), late);
+        assert_eq!(bank.read(late), None);
+        assert!(bank.snapshot().channels.is_empty());
+    }
+
+    #[test]
+    fn prunes_the_oldest_channels_past_the_front() {
+        let mut bank = bank().with_max_size(3 * (FRAME_OVERHEAD + 10));
+        for id in 1..=3 {
+            bank.ingest_frame(frame(id, 0, &[id; 10], false), origin(1));
+        }
+        assert_eq!(ids(&bank), [1, 2, 3]);
+
+        bank.ingest_frame(frame(4, 0, &[4; 10], false), origin(1));
+        assert_eq!(ids(&bank), [1, 3, 4]);
+
+        // A front channel larger than the bank is kept on its own.
+        bank.ingest_frame(frame(1, 1, &[1; 100], true), origin(1));
+        assert_eq!(ids(&bank), [1]);
+        assert_eq!(bank.read(origin(1)).map(|data| data.len()), Some(110));
+    }
+
+    #[test]
+    fn counts_batcher_misbehavior() {
+        let mut bank = bank();
+        let timeout = bank.config.channel_timeout(0);
+        bank.ingest_frames(
+            [
+                frame(1, 2, b"c", true),
+                frame(1, 0, b"a", false),
+                frame(1, 0, b"a", false),
+                frame(1, 3, b"d", false),
+                frame(2, 0, b"b", false),
+            ],
+            origin(1),
+        );
+        bank.ingest_frame(frame(1, 1, b"b", false), origin(2));
+        assert_eq!(bank.read(origin(2)), Some(Bytes::from_static(b"abc")));
+
+        // Channel 2 times out, and channel 1 is opened again.
+        let late = origin(2 + timeout);
+        bank.ingest_frames([frame(2, 1, b"c", true), frame(1, 0, b"e", true)], late);
+        assert_eq!(
+            bank.snapshot().anomalies,
+            FrameAnomalies {
+                duplicate_frames: 1,
+                invalid_frames: 1,
+                timed_out_frames: 1,
+                out_of_order_frames: 2,
+                reused_channel_ids: 1,
+            }
+        );
+
+        // Channels read again after a reset are not reused.
+        bank.reset();
+        bank.ingest_frame(frame(1, 0, b"a", true), origin(1));
+        assert_eq!(bank.anomalies().reused_channel_ids, 1);
+    }
+
+    #[test]
+    fn publishes_changes_only() {
+        let mut bank = bank();
+        let mut snapshots = bank.subscribe();
+        bank.ingest_frames([frame(1, 0, b"a", false), frame(1, 1, b"b", false)], origin(1));
+        assert!(snapshots.has_changed().unwrap());
+        assert_eq!(snapshots.borrow_and_update().channels[0].frame_count, 2);
+
+        assert_eq!(bank.read(origin(1)), None);
+        assert!(!snapshots.has_changed().unwrap());
+
+        bank.read(origin(2));
+        assert_eq!(snapshots.borrow_and_update().origin, Some(origin(2)));
+    }
+}
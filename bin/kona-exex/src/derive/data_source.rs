@@ -0,0 +1,308 @@
+//! The default [`DataSource`], reading batcher calldata and blobs.
+
+use std::sync::Arc;
+
+use alloy_primitives::{Address, Bytes};
+use async_trait::async_trait;
+use eyre::{bail, Result};
+use tracing::warn;
+
+use crate::{
+    blobs::{decode_blob_data, BlobFetcher, IndexedBlobHash},
+    config::RollupConfig,
+    derive::{AddressInbox, BatcherTxFilter, DataSource},
+    l1::ChainProvider,
+    protocol::BlockInfo,
+};
+
+/// Reads batcher data from the calldata of batcher transactions, and from their blobs once
+/// Ecotone is active.
+///
+/// Without a [`BlobFetcher`], L1 blocks with calldata batches only are read as usual, and the
+/// first block with batcher blobs fails with an error asking for a beacon endpoint.
+///
+/// Batcher transactions are selected with a [`BatcherTxFilter`], by default the [`AddressInbox`]
+/// of the batch inbox address of the rollup config.
+#[derive(Debug)]
+pub struct EthereumDataSource {
+    config: Arc<RollupConfig>,
+    provider: Arc<dyn ChainProvider>,
+    blobs: Option<BlobFetcher>,
+    filter: Box<dyn BatcherTxFilter>,
+}
+
+impl EthereumDataSource {
+    /// Creates a data source reading L1 transactions from `provider`, and blobs from `blobs` if
+    /// set.
+    pub fn new(
+        config: Arc<RollupConfig>,
+        provider: Arc<dyn ChainProvider>,
+        blobs: Option<BlobFetcher>,
+    ) -> Self {
+        let filter = Box::new(AddressInbox::new(config.batch_inbox_address));
+        Self { config, provider, blobs, filter }
+    }
+
+    /// Selects batcher transactions with `filter`, instead of the [`AddressInbox`].
+    pub fn with_tx_filter(mut self, filter: Box<dyn BatcherTxFilter>) -> Self {
+        self.filter = filter;
+        self
+    }
+}
+
+/// Where the data of a batcher transaction is.
+enum Entry {
+    Calldata(Bytes),
+    Blob(IndexedBlobHash),
+}
+
+#[async_trait]
+impl DataSource for EthereumDataSource {
+    async fn open_data(&mut self, block: &BlockInfo, batcher: Address) -> Result<Vec<Bytes>> {
+        let ecotone = self.config.is_ecotone_active(block.timestamp);
+        let transactions = self.provider.transactions_by_hash(block.hash).await?;
+        let receipts = if self.filter.needs_receipts() {
+            let receipts = self.provider.receipts_by_hash(block.hash).await?;
+            if receipts.len() != transactions.len() {
+                bail!(
+                    "L1 block {block} has {} receipts for {} transactions",
+                    receipts.len(),
+                    transactions.len()
+                );
+            }
+            receipts
+        } else {
+            Vec::new()
+        };
+
+        let mut entries = Vec::new();
+        let mut blob_index = 0;
+        for (index, tx) in transactions.into_iter().enumerate() {
+            let first_blob = blob_index;
+            blob_index += tx.blob_versioned_hashes.len() as u64;
+            let succeeded = receipts.get(index).map_or(true, |receipt| receipt.status());
+            if !self.filter.is_batcher_tx(&tx, batcher, succeeded) {
+                continue;
+            }
+            if tx.blob_versioned_hashes.is_empty() {
+                entries.push(Entry::Calldata(tx.input));
+            } else if ecotone {
+                // The calldata of blob transactions is ignored.
+                entries.extend(tx.blob_versioned_hashes.iter().enumerate().map(|(i, hash)| {
+                    Entry::Blob(IndexedBlobHash { index: first_blob + i as u64, hash: *hash })
+                }));
+            }
+        }
+
+        let hashes = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Blob(hash) => Some(*hash),
+                Entry::Calldata(_) => None,
+            })
+            .collect::<Vec<_>>();
+        let mut blobs = match (&self.blobs, hashes.is_empty()) {
+            (_, true) => Vec::new(),
+            (Some(fetcher), false) => fetcher.blobs(block, &hashes).await?,
+            (None, false) => bail!(
+                "L1 block {block} has {} batcher blobs but no beacon endpoint is configured to \
+                 fetch them, restart with --hera.l1-beacon-url",
+                hashes.len()
+            ),
+        }
+        .into_iter();
+
+        let mut data = Vec::with_capacity(entries.len());
+        for entry in entries {
+            match entry {
+                Entry::Calldata(calldata) => data.push(calldata),
+                Entry::Blob(hash) => {
+                    let blob = blobs.next().expect("one blob per hash");
+                    match decode_blob_data(&blob) {
+                        Ok(blob_data) => data.push(blob_data),
+                        // Like op-node, undecodable blobs are skipped rather than retried.
+                        Err(err) => warn!(
+                            target: "hera::derive",
+                            block = %block,
+                            index = hash.index,
+                            %err,
+                            "Skipping undecodable blob"
+                        ),
+                    }
+                }
+            }
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_consensus::{Eip658Value, Header};
+    use alloy_eips::eip4844::{kzg_to_versioned_hash, Bytes48};
+    use alloy_primitives::{address, B256};
+
+    use super::*;
+    use crate::{
+        blobs::{encode_blob_data, BlobProvider, BlobSidecar},
+        derive::ContractInbox,
+        l1::{L1Receipt, L1Transaction, ProviderError, ProviderResult},
+    };
+
+    const BATCHER: Address = address!("6887246668a3b87f54deb3b94ba47a6f63f32985");
+
+    /// Serves the transactions of a single L1 block, and receipts with the given statuses.
+    #[derive(Debug)]
+    struct Transactions(Vec<L1Transaction>, Vec<bool>);
+
+    #[async_trait]
+    impl ChainProvider for Transactions {
+        async fn header_by_hash(&self, hash: B256) -> ProviderResult<Header> {
+            Err(ProviderError::NotFound(format!("L1 header {hash}")))
+        }
+
+        async fn block_info_by_number(&self, number: u64) -> ProviderResult<BlockInfo> {
+            Err(ProviderError::NotFound(format!("L1 block {number}")))
+        }
+
+        async fn receipts_by_hash(&self, _: B256) -> ProviderResult<Vec<L1Receipt>> {
+            Ok(self
+                .1
+                .iter()
+                .map(|&status| {
+                    let mut receipt = L1Receipt::default();
+                    receipt.receipt.receipt.status = Eip658Value::Eip658(status);
+                    receipt
+                })
+                .collect())
+        }
+
+        async fn transactions_by_hash(&self, _: B256) -> ProviderResult<Vec<L1Transaction>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    /// Serves sidecars of blobs carrying their own index as data, with the commitment set to the
+    /// index as well.
+    #[derive(Debug)]
+    struct Sidecars;
+
+    impl Sidecars {
+        fn commitment(index: u64) -> Bytes48 {
+            Bytes48::from([index as u8; 48])
+        }
+
+        fn versioned_hash(index: u64) -> B256 {
+            kzg_to_versioned_hash(Self::commitment(index).as_slice())
+        }
+    }
+
+    #[async_trait]
+    impl BlobProvider for Sidecars {
+        async fn blob_sidecars(&self, _: &BlockInfo, indices: &[u64]) -> Result<Vec<BlobSidecar>> {
+            indices
+                .iter()
+                .map(|&index| {
+                    Ok(BlobSidecar {
+                        index,
+                        blob: encode_blob_data(&[index as u8])?,
+                        kzg_commitment: Self::commitment(index),
+                    })
+                })
+                .collect()
+        }
+    }
+
+    fn blob_tx(from: Address, to: Address, indices: std::ops::Range<u64>) -> L1Transaction {
+        L1Transaction {
+            from,
+            to: Some(to),
+            blob_versioned_hashes: indices.map(Sidecars::versioned_hash).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_blobs_past_the_cancun_block_limit() {
+        let mut config = RollupConfig::from_registry(10).unwrap();
+        config.ecotone_time = Some(0);
+        let inbox = config.batch_inbox_address;
+        let other = address!("95222290dd7278aa3ddd389cc1e1d165cc4bafe5");
+
+        // A Pectra L1 block with 9 blobs: 2 of another rollup, then 7 of the batcher, in two
+        // transactions around an EIP-7702 transaction.
+        let provider = Transactions(
+            vec![
+                blob_tx(other, other, 0..2),
+                blob_tx(BATCHER, inbox, 2..8),
+                L1Transaction { from: other, to: Some(other), ..Default::default() },
+                blob_tx(BATCHER, inbox, 8..9),
+            ],
+            Vec::new(),
+        );
+        let mut source = EthereumDataSource::new(
+            Arc::new(config),
+            Arc::new(provider),
+            Some(BlobFetcher::new(Arc::new(Sidecars))),
+        );
+
+        let data = source.open_data(&BlockInfo::default(), BATCHER).await.unwrap();
+        let expected: Vec<Bytes> = (2..9u8).map(|index| Bytes::from(vec![index])).collect();
+        assert_eq!(data, expected);
+    }
+
+    #[tokio::test]
+    async fn reads_calldata_without_a_blob_provider() {
+        let mut config = RollupConfig::from_registry(10).unwrap();
+        config.ecotone_time = Some(0);
+        let inbox = config.batch_inbox_address;
+        let other = address!("95222290dd7278aa3ddd389cc1e1d165cc4bafe5");
+        let calldata = L1Transaction {
+            from: BATCHER,
+            to: Some(inbox),
+            input: Bytes::from(vec![1]),
+            ..Default::default()
+        };
+        let source = |transactions| {
+            EthereumDataSource::new(
+                Arc::new(config.clone()),
+                Arc::new(Transactions(transactions, Vec::new())),
+                None,
+            )
+        };
+
+        // Blobs of other rollups are not needed.
+        let mut calldata_only = source(vec![blob_tx(other, other, 0..2), calldata.clone()]);
+        let data = calldata_only.open_data(&BlockInfo::default(), BATCHER).await.unwrap();
+        assert_eq!(data, vec![Bytes::from(vec![1])]);
+
+        let mut with_blobs = source(vec![calldata, blob_tx(BATCHER, inbox, 0..3)]);
+        let err = with_blobs.open_data(&BlockInfo::default(), BATCHER).await.unwrap_err();
+        let err = err.to_string();
+        assert!(err.contains("has 3 batcher blobs"), "{err}");
+        assert!(err.contains("--hera.l1-beacon-url"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn contract_inbox_reads_successful_transactions_of_any_sender() {
+        let config = RollupConfig::from_registry(10).unwrap();
+        let inbox = config.batch_inbox_address;
+        let other = address!("95222290dd7278aa3ddd389cc1e1d165cc4bafe5");
+        let calldata = |from, data: u8| L1Transaction {
+            from,
+            to: Some(inbox),
+            input: Bytes::from(vec![data]),
+            ..Default::default()
+        };
+
+        let provider = Transactions(
+            vec![calldata(BATCHER, 0), calldata(other, 1), calldata(other, 2)],
+            vec![true, true, false],
+        );
+        let mut source = EthereumDataSource::new(Arc::new(config), Arc::new(provider), None)
+            .with_tx_filter(Box::new(ContractInbox::new(inbox)));
+
+        let data = source.open_data(&BlockInfo::default(), BATCHER).await.unwrap();
+        assert_eq!(data, vec![Bytes::from(vec![0]), Bytes::from(vec![1])]);
+    }
+}
@@ -0,0 +1,142 @@
+//! A pool of worker threads decompressing channels off the pipeline task.
+
+use std::{
+    collections::VecDeque,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use alloy_primitives::Bytes;
+use eyre::{eyre, Result};
+use metrics::gauge;
+use tokio::sync::oneshot;
+use tracing::{trace, warn};
+
+use crate::protocol::{channel::decompress_channel, BlockInfo};
+
+/// The default number of decompression worker threads.
+pub const DEFAULT_DECOMPRESSION_WORKERS: usize = 4;
+
+/// The default number of channels queued for decompression at once.
+pub const DEFAULT_DECOMPRESSION_QUEUE_SIZE: usize = 16;
+
+/// A channel to decompress, with the slot its output is returned in.
+struct Job {
+    data: Bytes,
+    max_size: u64,
+    fjord: bool,
+    output: oneshot::Sender<Result<Vec<u8>>>,
+}
+
+/// A decompressed channel, with the L1 block its data was read at.
+type Decompressed = (BlockInfo, Result<Vec<u8>>);
+
+/// Decompresses channels on a pool of worker threads, handing the output back in submission
+/// order.
+///
+/// Brotli channels of several megabytes take long enough to decompress that doing it inline
+/// holds up the pipeline task. The pool lets the pipeline keep fetching L1 data and reading frames
+/// meanwhile. At most `queue_size` channels are in flight; the pipeline waits for the oldest before
+/// submitting more, which bounds the memory held by decompressed channels.
+#[derive(Debug)]
+pub struct DecompressionPool {
+    jobs: mpsc::SyncSender<Job>,
+    pending: VecDeque<(BlockInfo, oneshot::Receiver<Result<Vec<u8>>>)>,
+    queue_size: usize,
+}
+
+impl DecompressionPool {
+    /// Spawns `workers` threads, with at most `queue_size` channels in flight.
+    pub fn new(workers: usize, queue_size: usize) -> Self {
+        let queue_size = queue_size.max(1);
+        let (jobs, queue) = mpsc::sync_channel::<Job>(queue_size);
+        let queue = Arc::new(Mutex::new(queue));
+        for i in 0..workers.max(1) {
+            let queue = queue.clone();
+            let spawned = thread::Builder::new()
+                .name(format!("hera-decompress-{i}"))
+                .spawn(move || work(&queue));
+            if let Err(err) = spawned {
+                warn!(target: "hera::derive", %err, "Failed to spawn decompression worker");
+            }
+        }
+        Self { jobs, pending: VecDeque::new(), queue_size }
+    }
+
+    /// Returns the number of channels in flight.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns the L1 block the oldest channel in flight was read at.
+    pub fn oldest_origin(&self) -> Option<BlockInfo> {
+        self.pending.front().map(|(origin, _)| *origin)
+    }
+
+    /// Returns true if no channels are in flight.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Returns true if no more channels can be submitted before the oldest is taken.
+    pub fn is_full(&self) -> bool {
+        self.pending.len() >= self.queue_size
+    }
+
+    /// Queues channel data read at `origin` for decompression, with at most `max_size` bytes of
+    /// output.
+    pub fn submit(&mut self, data: Bytes, origin: BlockInfo, max_size: u64, fjord: bool) {
+        let (output, rx) = oneshot::channel();
+        trace!(target: "hera::derive", len = data.len(), %origin, "Queueing channel for decompression");
+        // The queue holds at most as many jobs as are pending, so sending never blocks.
+        if let Err(mpsc::SendError(job)) = self.jobs.send(Job { data, max_size, fjord, output }) {
+            let _ = job.output.send(Err(eyre!("no decompression workers running")));
+        }
+        self.pending.push_back((origin, rx));
+        self.publish();
+    }
+
+    /// Takes the oldest channel if it is decompressed, without waiting.
+    pub fn try_next(&mut self) -> Option<Decompressed> {
+        let (_, rx) = self.pending.front_mut()?;
+        let output = match rx.try_recv() {
+            Ok(output) => output,
+            Err(oneshot::error::TryRecvError::Empty) => return None,
+            Err(oneshot::error::TryRecvError::Closed) => Err(eyre!("decompression worker died")),
+        };
+        let (origin, _) = self.pending.pop_front()?;
+        self.publish();
+        Some((origin, output))
+    }
+
+    /// Waits for the oldest channel to be decompressed and takes it.
+    pub async fn next(&mut self) -> Option<Decompressed> {
+        let (origin, rx) = self.pending.pop_front()?;
+        self.publish();
+        let output = rx.await.unwrap_or_else(|_| Err(eyre!("decompression worker died")));
+        Some((origin, output))
+    }
+
+    /// Discards the channels in flight.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.publish();
+    }
+
+    fn publish(&self) {
+        gauge!("hera_decompression_pending_channels").set(self.pending.len() as f64);
+    }
+}
+
+/// Runs jobs from the queue until the pool is dropped.
+fn work(queue: &Mutex<mpsc::Receiver<Job>>) {
+    loop {
+        let job = match queue.lock() {
+            Ok(queue) => queue.recv(),
+            Err(_) => return,
+        };
+        let Ok(job) = job else { return };
+        // The receiver is gone if the channel was discarded by a reset.
+        let _ = job.output.send(decompress_channel(&job.data, job.max_size, job.fjord));
+    }
+}
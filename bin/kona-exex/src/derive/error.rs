@@ -0,0 +1,88 @@
+//! Errors of the derivation pipeline.
+
+use std::fmt;
+
+use eyre::Report;
+
+use crate::{derive::L1Reorg, l1::ProviderError};
+
+/// The result of a pipeline operation.
+pub type PipelineResult<T> = Result<T, PipelineError>;
+
+/// An error of the derivation pipeline, reported through
+/// [`StepResult`](crate::derive::StepResult) and [`Pipeline::reset`](crate::derive::Pipeline).
+#[derive(Debug)]
+pub enum PipelineError {
+    /// L1 data could not be read.
+    Provider(ProviderError),
+    /// The next L1 block does not build on the origin. The pipeline must be reset.
+    Reorg(L1Reorg),
+    /// The pipeline was stepped before its first reset.
+    NotReset,
+    /// The system config could not be updated from the receipts of an L1 block.
+    SystemConfig(Report),
+    /// The data source failed to read batcher data.
+    DataSource(Report),
+    /// The attributes builder failed to prepare attributes.
+    Attributes(Report),
+    /// The L2 chain could not be read.
+    L2Provider(Report),
+}
+
+impl PipelineError {
+    /// Returns true if the operation may succeed when retried, possibly after a reset.
+    ///
+    /// Errors of replaceable stages are recoverable if they stem from a recoverable
+    /// [`ProviderError`].
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Self::Provider(err) => err.is_recoverable(),
+            Self::Reorg(_) => true,
+            Self::NotReset | Self::SystemConfig(_) => false,
+            Self::DataSource(err) | Self::Attributes(err) | Self::L2Provider(err) => {
+                err.downcast_ref::<ProviderError>().is_some_and(ProviderError::is_recoverable)
+            }
+        }
+    }
+
+    /// Returns true if the error stems from L1 data pruned by the L1 node, which derivation
+    /// cannot proceed without.
+    pub fn is_pruned(&self) -> bool {
+        let provider = match self {
+            Self::Provider(err) => Some(err),
+            Self::SystemConfig(err) | Self::DataSource(err) | Self::Attributes(err) => {
+                err.downcast_ref::<ProviderError>()
+            }
+            Self::Reorg(_) | Self::NotReset | Self::L2Provider(_) => None,
+        };
+        matches!(provider, Some(ProviderError::Pruned(_)))
+    }
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Provider(err) => err.fmt(f),
+            Self::Reorg(reorg) => reorg.fmt(f),
+            Self::NotReset => f.write_str("pipeline has not been reset"),
+            Self::SystemConfig(err) => write!(f, "failed to update the system config: {err:#}"),
+            Self::DataSource(err) => write!(f, "failed to read batcher data: {err:#}"),
+            Self::Attributes(err) => write!(f, "failed to prepare attributes: {err:#}"),
+            Self::L2Provider(err) => write!(f, "failed to read the L2 chain: {err:#}"),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+impl From<ProviderError> for PipelineError {
+    fn from(err: ProviderError) -> Self {
+        Self::Provider(err)
+    }
+}
+
+impl From<L1Reorg> for PipelineError {
+    fn from(reorg: L1Reorg) -> Self {
+        Self::Reorg(reorg)
+    }
+}
@@ -0,0 +1,55 @@
+//! The [`BatcherTxFilter`]s of batch inboxes.
+
+use alloy_primitives::Address;
+
+use crate::{derive::BatcherTxFilter, l1::L1Transaction};
+
+/// A batch inbox at a plain address: batcher transactions are the ones the batcher of the system
+/// config sends to it, whether or not they succeed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressInbox {
+    /// The batch inbox address.
+    pub inbox: Address,
+}
+
+impl AddressInbox {
+    /// Creates the filter of the batch inbox at `inbox`.
+    pub const fn new(inbox: Address) -> Self {
+        Self { inbox }
+    }
+}
+
+impl BatcherTxFilter for AddressInbox {
+    fn is_batcher_tx(&self, tx: &L1Transaction, batcher: Address, _: bool) -> bool {
+        tx.to == Some(self.inbox) && tx.from == batcher
+    }
+}
+
+/// A batch inbox contract authorizing its senders: batcher transactions are the successful
+/// transactions to it, from any sender, since the contract reverts the ones it does not
+/// authorize.
+///
+/// This also covers inboxes that only check blob transactions point to their data, as long as
+/// they revert on anything they reject.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContractInbox {
+    /// The batch inbox contract address.
+    pub inbox: Address,
+}
+
+impl ContractInbox {
+    /// Creates the filter of the batch inbox contract at `inbox`.
+    pub const fn new(inbox: Address) -> Self {
+        Self { inbox }
+    }
+}
+
+impl BatcherTxFilter for ContractInbox {
+    fn needs_receipts(&self) -> bool {
+        true
+    }
+
+    fn is_batcher_tx(&self, tx: &L1Transaction, _: Address, succeeded: bool) -> bool {
+        tx.to == Some(self.inbox) && succeeded
+    }
+}
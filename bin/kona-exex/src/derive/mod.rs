@@ -0,0 +1,112 @@
+//! The derivation pipeline, turning L1 data into L2 payload attributes.
+
+use alloy_rpc_types_engine::OptimismPayloadAttributes;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::{BlockInfo, L2BlockInfo};
+
+mod attributes;
+pub use attributes::{StatefulAttributesBuilder, SEQUENCER_FEE_VAULT_ADDRESS};
+
+mod batch_filter;
+pub use batch_filter::ProtocolBatchFilter;
+
+mod builder;
+pub use builder::PipelineBuilder;
+
+pub mod channel_bank;
+pub use channel_bank::{
+    ChannelBank, ChannelBankSnapshot, ChannelSummary, FrameAnomalies, FrameSummary,
+};
+
+mod data_source;
+pub use data_source::EthereumDataSource;
+
+mod decompress;
+pub use decompress::{
+    DecompressionPool, DEFAULT_DECOMPRESSION_QUEUE_SIZE, DEFAULT_DECOMPRESSION_WORKERS,
+};
+
+mod error;
+pub use error::{PipelineError, PipelineResult};
+
+mod inbox;
+pub use inbox::{AddressInbox, ContractInbox};
+
+mod pipeline;
+pub use pipeline::DerivationPipeline;
+
+mod stages;
+pub use stages::{
+    AttributesBuilder, BatchContext, BatchFilter, BatchValidity, BatcherTxFilter, DataSource,
+    L2ChainProvider,
+};
+
+pub mod status;
+pub use status::{BufferedCounts, PipelineReset, PipelineStatus, StageOrigins};
+
+/// Payload attributes derived from L1, together with the L2 block they build on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct L2AttributesWithParent {
+    /// The derived payload attributes.
+    pub attributes: OptimismPayloadAttributes,
+    /// The L2 block the attributes build on.
+    pub parent: L2BlockInfo,
+    /// The L1 block whose data the attributes were derived from.
+    pub derived_from: BlockInfo,
+}
+
+/// An L1 reorg detected by the pipeline, reported through [`StepResult::StepFailed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct L1Reorg {
+    /// The current L1 origin of the pipeline.
+    pub origin: BlockInfo,
+    /// The canonical L1 block at the next height, which does not build on the origin.
+    pub next: BlockInfo,
+}
+
+impl std::fmt::Display for L1Reorg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "L1 reorg: {} does not build on {}", self.next, self.origin)
+    }
+}
+
+impl std::error::Error for L1Reorg {}
+
+/// The outcome of a [`Pipeline::step`].
+#[derive(Debug)]
+pub enum StepResult {
+    /// New attributes are ready to be taken with [`Pipeline::next`].
+    PreparedAttributes,
+    /// The pipeline advanced its L1 origin.
+    AdvancedOrigin,
+    /// The L1 origin could not be advanced, usually because the next L1 block is not known yet.
+    OriginAdvanceErr(PipelineError),
+    /// The step failed.
+    StepFailed(PipelineError),
+}
+
+/// A derivation pipeline.
+#[async_trait]
+pub trait Pipeline: Send {
+    /// Advances the pipeline by one step, deriving attributes on top of `cursor`.
+    async fn step(&mut self, cursor: L2BlockInfo) -> StepResult;
+
+    /// Takes the next prepared attributes, if any.
+    fn next(&mut self) -> Option<L2AttributesWithParent>;
+
+    /// Returns the current L1 origin of the pipeline.
+    fn origin(&self) -> Option<BlockInfo>;
+
+    /// Resets the pipeline to derive on top of `l2_safe_head`, starting from `l1_origin`.
+    async fn reset(
+        &mut self,
+        l2_safe_head: L2BlockInfo,
+        l1_origin: BlockInfo,
+    ) -> PipelineResult<()>;
+
+    /// Records why the pipeline was last reset, for introspection. Ignored by default.
+    fn record_reset_reason(&mut self, _reason: &str) {}
+}
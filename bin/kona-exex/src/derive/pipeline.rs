@@ -0,0 +1,701 @@
+//! The derivation pipeline, composed of replaceable stages.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use alloy_primitives::B256;
+use async_trait::async_trait;
+use eyre::Result;
+use metrics::counter;
+use tokio::sync::watch;
+use tracing::{debug, warn};
+
+use crate::{
+    clock::unix_now,
+    config::{RollupConfig, SystemConfig},
+    derive::{
+        AttributesBuilder, BatchContext, BatchFilter, BatchValidity, BufferedCounts, ChannelBank,
+        DataSource, DecompressionPool, L1Reorg, L2AttributesWithParent, L2ChainProvider, Pipeline,
+        PipelineError, PipelineReset, PipelineResult, PipelineStatus, StageOrigins, StepResult,
+    },
+    l1::ChainProvider,
+    protocol::{
+        channel::{MAX_RLP_BYTES_PER_CHANNEL_BEDROCK, MAX_RLP_BYTES_PER_CHANNEL_FJORD},
+        Batch, BatchReader, BlockId, BlockInfo, Frame, L2BlockInfo, SingleBatch,
+    },
+};
+
+/// How the parent of a queued batch is checked.
+#[derive(Debug, Clone, Copy)]
+enum ParentCheck {
+    /// A single batch, carrying the full parent hash.
+    Hash,
+    /// The first block of a span batch, carrying the first 20 bytes of the parent hash.
+    Prefix([u8; 20]),
+    /// A later block of a span batch, building on the block before it in the span.
+    Previous,
+}
+
+/// A batch waiting for its L2 block.
+#[derive(Debug, Clone)]
+struct QueuedBatch {
+    batch: SingleBatch,
+    inclusion: BlockInfo,
+    parent: ParentCheck,
+    /// The span batch the block belongs to, if any.
+    span: Option<u64>,
+    /// The first 20 bytes of the L1 origin hash, on the last block of a span batch.
+    origin_check: Option<[u8; 20]>,
+}
+
+/// Derives payload attributes from L1, built with the
+/// [`PipelineBuilder`](crate::derive::PipelineBuilder).
+#[derive(Debug)]
+pub struct DerivationPipeline {
+    config: Arc<RollupConfig>,
+    provider: Arc<dyn ChainProvider>,
+    l2_provider: Option<Arc<dyn L2ChainProvider>>,
+    data_source: Box<dyn DataSource>,
+    filters: Vec<Box<dyn BatchFilter>>,
+    attributes: Box<dyn AttributesBuilder>,
+    channel_bank: ChannelBank,
+    decompression: DecompressionPool,
+    /// The L1 blocks traversed, from the safe head's L1 origin up to the current origin.
+    l1_blocks: Vec<BlockInfo>,
+    /// The system config at the current origin, for the batcher address.
+    system_config: SystemConfig,
+    batches: VecDeque<QueuedBatch>,
+    next_span: u64,
+    prepared: VecDeque<L2AttributesWithParent>,
+    /// The L1 block the last prepared attributes were derived from.
+    last_derived_from: Option<BlockInfo>,
+    status: watch::Sender<PipelineStatus>,
+}
+
+impl DerivationPipeline {
+    pub(crate) fn new(
+        config: Arc<RollupConfig>,
+        provider: Arc<dyn ChainProvider>,
+        l2_provider: Option<Arc<dyn L2ChainProvider>>,
+        data_source: Box<dyn DataSource>,
+        filters: Vec<Box<dyn BatchFilter>>,
+        attributes: Box<dyn AttributesBuilder>,
+        decompression: DecompressionPool,
+    ) -> Self {
+        Self {
+            system_config: config.genesis.system_config.clone().unwrap_or_default(),
+            channel_bank: ChannelBank::new(config.clone()),
+            config,
+            provider,
+            l2_provider,
+            data_source,
+            filters,
+            attributes,
+            decompression,
+            l1_blocks: Vec::new(),
+            batches: VecDeque::new(),
+            next_span: 0,
+            prepared: VecDeque::new(),
+            last_derived_from: None,
+            status: watch::Sender::new(PipelineStatus::default()),
+        }
+    }
+
+    /// Returns the channel bank.
+    pub const fn channel_bank(&self) -> &ChannelBank {
+        &self.channel_bank
+    }
+
+    /// Subscribes to the state of the pipeline, updated after every step and reset.
+    pub fn subscribe_status(&self) -> watch::Receiver<PipelineStatus> {
+        self.status.subscribe()
+    }
+
+    /// Publishes the state of the stages, with the error of `result` if the step failed.
+    fn publish_status(&self, result: Option<&StepResult>) {
+        let origins = StageOrigins {
+            traversal: self.l1_blocks.last().copied(),
+            l1_window_start: self.l1_blocks.first().copied(),
+            channel_bank: self.channel_bank.origin(),
+            decompression: self.decompression.oldest_origin(),
+            batch_queue: self.batches.front().map(|queued| queued.inclusion),
+            attributes: self.last_derived_from,
+        };
+        let buffered = BufferedCounts {
+            channels: self.channel_bank.channel_count(),
+            channel_bank_size: self.channel_bank.size(),
+            decompressing_channels: self.decompression.len(),
+            batches: self.batches.len(),
+            attributes: self.prepared.len(),
+        };
+        self.status.send_modify(|status| {
+            status.origins = origins;
+            status.buffered = buffered;
+            match result {
+                Some(StepResult::OriginAdvanceErr(err) | StepResult::StepFailed(err)) => {
+                    status.last_error = Some(err.to_string());
+                }
+                Some(_) => status.last_error = None,
+                None => {}
+            }
+        });
+    }
+
+    /// Steps the pipeline, see [`Pipeline::step`].
+    async fn try_step(&mut self, cursor: L2BlockInfo) -> StepResult {
+        if !self.prepared.is_empty() {
+            return StepResult::PreparedAttributes;
+        }
+        let Some(origin) = self.l1_blocks.last().copied() else {
+            return StepResult::StepFailed(PipelineError::NotReset);
+        };
+
+        let (batch, epoch) = loop {
+            self.read_channels(origin);
+            let allow_empty = self.decompression.is_empty();
+            if let Some(next) = self.next_batch(cursor, origin, allow_empty) {
+                break next;
+            }
+            // Channels still being decompressed are waited for once no more can be queued, or
+            // when they decide between a batch and an empty block. Otherwise the next L1 block is
+            // fetched meanwhile, its channels queued behind the ones in flight.
+            let wait = self.decompression.is_full() ||
+                (!allow_empty && self.sequencing_window_passed(cursor, origin));
+            if !wait {
+                return self.advance_origin(origin).await;
+            }
+            if let Some((inclusion, decompressed)) = self.decompression.next().await {
+                self.queue_channel(inclusion, decompressed);
+            }
+        };
+
+        let attributes = match self.attributes.prepare_payload_attributes(cursor, epoch).await {
+            Ok(mut attributes) => {
+                attributes.transactions.get_or_insert_with(Vec::new).extend(batch.transactions);
+                attributes
+            }
+            Err(err) => return StepResult::StepFailed(PipelineError::Attributes(err)),
+        };
+        self.prepared.push_back(L2AttributesWithParent {
+            attributes,
+            parent: cursor,
+            derived_from: origin,
+        });
+        self.last_derived_from = Some(origin);
+        StepResult::PreparedAttributes
+    }
+
+    /// Submits complete channels for decompression and moves the batches of the decompressed
+    /// ones into the batch queue.
+    fn read_channels(&mut self, origin: BlockInfo) {
+        let fjord = self.config.is_fjord_active(origin.timestamp);
+        let max_size =
+            if fjord { MAX_RLP_BYTES_PER_CHANNEL_FJORD } else { MAX_RLP_BYTES_PER_CHANNEL_BEDROCK };
+        while !self.decompression.is_full() {
+            let Some(data) = self.channel_bank.read(origin) else { break };
+            self.decompression.submit(data, origin, max_size, fjord);
+        }
+        while let Some((inclusion, decompressed)) = self.decompression.try_next() {
+            self.queue_channel(inclusion, decompressed);
+        }
+    }
+
+    /// Moves the batches of a decompressed channel read at `origin` into the batch queue.
+    fn queue_channel(&mut self, origin: BlockInfo, decompressed: Result<Vec<u8>>) {
+        let decompressed = match decompressed {
+            Ok(decompressed) => decompressed,
+            Err(err) => {
+                warn!(target: "hera::derive", %err, "Dropping undecodable channel");
+                return;
+            }
+        };
+        for batch in BatchReader::new(&decompressed) {
+            match batch {
+                Ok(Batch::Single(batch)) => self.batches.push_back(QueuedBatch {
+                    batch,
+                    inclusion: origin,
+                    parent: ParentCheck::Hash,
+                    span: None,
+                    origin_check: None,
+                }),
+                Ok(Batch::Span(raw)) => self.queue_span_batch(&raw, origin),
+                Err(err) => warn!(target: "hera::derive", %err, "Dropping invalid batch"),
+            }
+        }
+    }
+
+    /// Returns true if the sequencing window of the epoch of `cursor` has passed at `origin`,
+    /// so an empty batch is derived when there is no batch for the next block.
+    fn sequencing_window_passed(&self, cursor: L2BlockInfo, origin: BlockInfo) -> bool {
+        cursor.l1_origin.number + self.config.seq_window_size < origin.number
+    }
+
+    fn queue_span_batch(&mut self, raw: &crate::protocol::RawSpanBatch, origin: BlockInfo) {
+        if !self.config.is_delta_active(origin.timestamp) {
+            warn!(target: "hera::derive", "Dropping span batch included before Delta");
+            return;
+        }
+        let span = match raw.derive(
+            self.config.block_time,
+            self.config.genesis.l2_time,
+            self.config.l2_chain_id,
+        ) {
+            Ok(span) => span,
+            Err(err) => {
+                warn!(target: "hera::derive", %err, "Dropping invalid span batch");
+                return;
+            }
+        };
+
+        let id = self.next_span;
+        self.next_span += 1;
+        let last = span.elements.len() - 1;
+        for (i, element) in span.elements.into_iter().enumerate() {
+            self.batches.push_back(QueuedBatch {
+                batch: SingleBatch {
+                    parent_hash: B256::ZERO,
+                    epoch_num: element.epoch_num,
+                    // Span batches only commit to the L1 origin of their last block, the other
+                    // epoch hashes are taken from the traversed L1 blocks.
+                    epoch_hash: B256::ZERO,
+                    timestamp: element.timestamp,
+                    transactions: element.transactions,
+                },
+                inclusion: origin,
+                parent: if i == 0 {
+                    ParentCheck::Prefix(span.parent_check)
+                } else {
+                    ParentCheck::Previous
+                },
+                span: Some(id),
+                origin_check: (i == last).then_some(span.l1_origin_check),
+            });
+        }
+    }
+
+    /// Returns the next valid batch for the block after `cursor`, with its L1 origin.
+    ///
+    /// Empty batches are only derived with `allow_empty`, as channels still being decompressed
+    /// may hold a batch for the block.
+    fn next_batch(
+        &mut self,
+        cursor: L2BlockInfo,
+        origin: BlockInfo,
+        allow_empty: bool,
+    ) -> Option<(SingleBatch, BlockId)> {
+        // Keep the traversed L1 blocks from the safe head's L1 origin onwards.
+        let keep_from = cursor.l1_origin.number;
+        let prune = self.l1_blocks.iter().take_while(|b| b.number < keep_from).count();
+        self.l1_blocks.drain(..prune.min(self.l1_blocks.len().saturating_sub(1)));
+
+        let next_timestamp = cursor.block_info.timestamp + self.config.block_time;
+        let mut i = 0;
+        while i < self.batches.len() {
+            let queued = &self.batches[i];
+            if queued.batch.timestamp < next_timestamp {
+                // Batches for blocks that are already safe, e.g. read again after a reset or
+                // resubmitted by the batcher.
+                debug!(
+                    target: "hera::derive",
+                    timestamp = queued.batch.timestamp,
+                    "Dropping stale batch"
+                );
+                counter!("hera_derive_stale_batches_total").increment(1);
+                self.batches.remove(i);
+                continue;
+            }
+            if queued.batch.timestamp > next_timestamp {
+                i += 1;
+                continue;
+            }
+            let ctx = BatchContext {
+                config: &self.config,
+                safe_head: cursor,
+                inclusion: queued.inclusion,
+                l1_blocks: &self.l1_blocks,
+            };
+            let mut batch = queued.batch.clone();
+            let verdict = match resolve(&mut batch, queued, &ctx) {
+                Some(verdict) => verdict,
+                None => self
+                    .filters
+                    .iter_mut()
+                    .map(|filter| filter.check(&batch, &ctx))
+                    .find(|verdict| *verdict != BatchValidity::Accept)
+                    .unwrap_or(BatchValidity::Accept),
+            };
+
+            match verdict {
+                BatchValidity::Accept => {
+                    self.batches.remove(i);
+                    let epoch = BlockId::new(batch.epoch_hash, batch.epoch_num);
+                    return Some((batch, epoch));
+                }
+                BatchValidity::Drop(reason) => {
+                    warn!(
+                        target: "hera::derive",
+                        timestamp = batch.timestamp,
+                        %reason,
+                        "Dropping batch"
+                    );
+                    // The rest of the span builds on the dropped block.
+                    if let Some(span) = self.batches.remove(i).and_then(|queued| queued.span) {
+                        self.batches.retain(|queued| queued.span != Some(span));
+                    }
+                }
+                BatchValidity::Future | BatchValidity::Undecided => i += 1,
+            }
+        }
+
+        // Without a batch, empty blocks are derived once the sequencing window of the current
+        // epoch has passed.
+        let epoch = cursor.l1_origin;
+        if allow_empty && self.sequencing_window_passed(cursor, origin) {
+            let next_epoch = self.l1_blocks.iter().find(|b| b.number == epoch.number + 1);
+            let epoch = match next_epoch {
+                Some(next) if next_timestamp >= next.timestamp => next.id(),
+                _ => epoch,
+            };
+            debug!(target: "hera::derive", timestamp = next_timestamp, "Deriving empty batch");
+            let batch = SingleBatch {
+                parent_hash: cursor.block_info.hash,
+                epoch_num: epoch.number,
+                epoch_hash: epoch.hash,
+                timestamp: next_timestamp,
+                transactions: Vec::new(),
+            };
+            return Some((batch, epoch));
+        }
+        None
+    }
+
+    /// Moves the origin to the next L1 block and ingests its batcher data.
+    async fn advance_origin(&mut self, origin: BlockInfo) -> StepResult {
+        let next = match self.provider.block_info_by_number(origin.number + 1).await {
+            Ok(next) => next,
+            Err(err) => return StepResult::OriginAdvanceErr(err.into()),
+        };
+        if next.parent_hash != origin.hash {
+            return StepResult::StepFailed(L1Reorg { origin, next }.into());
+        }
+
+        let receipts = match self.provider.receipts_by_hash(next.hash).await {
+            Ok(receipts) => receipts,
+            Err(err) => return StepResult::OriginAdvanceErr(err.into()),
+        };
+        let mut system_config = self.system_config.clone();
+        if let Err(err) = system_config.update_with_receipts(
+            &receipts,
+            self.config.l1_system_config_address,
+            self.config.is_ecotone_active(next.timestamp),
+        ) {
+            return StepResult::StepFailed(PipelineError::SystemConfig(err));
+        }
+        let data = match self.data_source.open_data(&next, system_config.batcher_address).await {
+            Ok(data) => data,
+            Err(err) => return StepResult::OriginAdvanceErr(PipelineError::DataSource(err)),
+        };
+
+        self.system_config = system_config;
+        self.l1_blocks.push(next);
+        for data in data {
+            match Frame::parse_frames(&data) {
+                Ok(frames) => self.channel_bank.ingest_frames(frames, next),
+                Err(err) => warn!(target: "hera::derive", %err, "Dropping invalid batcher data"),
+            }
+        }
+        debug!(target: "hera::derive", origin = %next, "Advanced L1 origin");
+        StepResult::AdvancedOrigin
+    }
+}
+
+/// Fills in the parent and epoch hashes of span batch blocks, returning a verdict if the batch
+/// cannot be passed on to the filters.
+fn resolve(
+    batch: &mut SingleBatch,
+    queued: &QueuedBatch,
+    ctx: &BatchContext<'_>,
+) -> Option<BatchValidity> {
+    let safe_head = ctx.safe_head.block_info.hash;
+    match queued.parent {
+        ParentCheck::Hash => return None,
+        ParentCheck::Prefix(prefix) if safe_head[..20] != prefix => {
+            return Some(BatchValidity::Drop(format!("span batch does not build on {safe_head}")))
+        }
+        ParentCheck::Prefix(_) | ParentCheck::Previous => batch.parent_hash = safe_head,
+    }
+
+    let Some(epoch) = ctx.l1_block(batch.epoch_num) else {
+        return Some(BatchValidity::Undecided);
+    };
+    if queued.origin_check.is_some_and(|check| epoch.hash[..20] != check) {
+        return Some(BatchValidity::Drop(format!("span batch L1 origin check fails for {epoch}")));
+    }
+    batch.epoch_hash = epoch.hash;
+    None
+}
+
+#[async_trait]
+impl Pipeline for DerivationPipeline {
+    async fn step(&mut self, cursor: L2BlockInfo) -> StepResult {
+        let result = self.try_step(cursor).await;
+        self.publish_status(Some(&result));
+        result
+    }
+
+    fn next(&mut self) -> Option<L2AttributesWithParent> {
+        self.prepared.pop_front()
+    }
+
+    fn origin(&self) -> Option<BlockInfo> {
+        self.l1_blocks.last().copied()
+    }
+
+    async fn reset(
+        &mut self,
+        l2_safe_head: L2BlockInfo,
+        l1_origin: BlockInfo,
+    ) -> PipelineResult<()> {
+        let system_config = match &self.l2_provider {
+            Some(provider) => provider
+                .system_config_by_number(l2_safe_head.block_info.number)
+                .await
+                .map_err(PipelineError::L2Provider)?,
+            None => self.config.genesis.system_config.clone().unwrap_or_default(),
+        };
+        self.attributes.reset(system_config.clone());
+        self.system_config = system_config;
+
+        // Start a channel timeout back, so channels still open at the safe head are read again.
+        let start = l1_origin
+            .number
+            .saturating_sub(self.config.channel_timeout(l1_origin.timestamp))
+            .max(self.config.genesis.l1.number);
+        let start = if start == l1_origin.number {
+            l1_origin
+        } else {
+            self.provider.block_info_by_number(start).await?
+        };
+
+        self.l1_blocks = vec![start];
+        self.channel_bank.reset();
+        self.decompression.clear();
+        self.batches.clear();
+        self.prepared.clear();
+        self.last_derived_from = None;
+        let reset = PipelineReset {
+            reason: "unspecified".to_string(),
+            safe_head: l2_safe_head,
+            origin: start,
+            timestamp: unix_now(),
+        };
+        self.status.send_modify(|status| {
+            status.last_reset = Some(reset);
+            status.last_error = None;
+        });
+        self.publish_status(None);
+        debug!(target: "hera::derive", safe_head = %l2_safe_head, origin = %start, "Reset pipeline");
+        Ok(())
+    }
+
+    fn record_reset_reason(&mut self, reason: &str) {
+        self.status.send_modify(|status| {
+            if let Some(reset) = &mut status.last_reset {
+                reset.reason = reason.to_string();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use alloy_primitives::Bytes;
+
+    use super::*;
+    use crate::{
+        batcher::{ChannelOut, Compression, DataAvailability, TxData},
+        derive::PipelineBuilder,
+        l1::{mock::MockL1, L1Transaction},
+        protocol::ChannelId,
+    };
+
+    const GENESIS_TIME: u64 = 1_700_000_000;
+
+    /// Returns the calldata of a batcher transaction carrying `batch` in a channel of its own.
+    fn calldata(batch: SingleBatch) -> Bytes {
+        let mut channel = ChannelOut::new(ChannelId([1; 16]), Compression::default(), 100_000, 0);
+        assert!(channel.add_batch(&Batch::Single(batch)).unwrap());
+        let frames = channel.into_frames(100_000).unwrap();
+        TxData { frames, data_availability: DataAvailability::Calldata }.calldata()
+    }
+
+    fn genesis(config: &RollupConfig) -> L2BlockInfo {
+        let block = BlockInfo::new(config.genesis.l2.hash, 0, B256::ZERO, config.genesis.l2_time);
+        L2BlockInfo::new(block, config.genesis.l1, 0)
+    }
+
+    async fn pipeline(l1: MockL1, config: RollupConfig) -> DerivationPipeline {
+        let origin = l1.block(0);
+        let safe_head = genesis(&config);
+        let mut pipeline = PipelineBuilder::new(Arc::new(config), Arc::new(l1))
+            .decompression(1, 4)
+            .build()
+            .unwrap();
+        pipeline.reset(safe_head, origin).await.unwrap();
+        pipeline
+    }
+
+    /// Steps the pipeline until it prepares the attributes of the block after `cursor`, waiting
+    /// for the decompression of channels once the L1 chain runs out.
+    async fn derive(
+        pipeline: &mut DerivationPipeline,
+        cursor: L2BlockInfo,
+    ) -> L2AttributesWithParent {
+        for _ in 0..100 {
+            match pipeline.step(cursor).await {
+                StepResult::PreparedAttributes => return pipeline.next().unwrap(),
+                StepResult::AdvancedOrigin => {}
+                StepResult::OriginAdvanceErr(_) => {
+                    tokio::time::sleep(Duration::from_millis(10)).await
+                }
+                StepResult::StepFailed(err) => panic!("step failed: {err}"),
+            }
+        }
+        panic!("no attributes derived on top of {cursor}");
+    }
+
+    #[tokio::test]
+    async fn derives_batches_from_batcher_calldata() {
+        let mut l1 = MockL1::new(GENESIS_TIME, 1);
+        let config = l1.rollup_config();
+        let batch = SingleBatch {
+            parent_hash: config.genesis.l2.hash,
+            epoch_num: 0,
+            epoch_hash: config.genesis.l1.hash,
+            timestamp: GENESIS_TIME + 2,
+            transactions: vec![Bytes::from_static(&[0x02, 0xc0])],
+        };
+        l1.push_block(vec![L1Transaction {
+            from: config.genesis.system_config.as_ref().unwrap().batcher_address,
+            to: Some(config.batch_inbox_address),
+            input: calldata(batch),
+            ..Default::default()
+        }]);
+        let derived_from = l1.block(1);
+        let mut pipeline = pipeline(l1, config.clone()).await;
+
+        let attributes = derive(&mut pipeline, genesis(&config)).await;
+        assert_eq!(attributes.parent, genesis(&config));
+        assert_eq!(attributes.derived_from, derived_from);
+        assert_eq!(attributes.attributes.payload_attributes.timestamp, GENESIS_TIME + 2);
+        let transactions = attributes.attributes.transactions.unwrap();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[1], Bytes::from_static(&[0x02, 0xc0]));
+    }
+
+    #[tokio::test]
+    async fn ignores_data_of_other_senders() {
+        let mut l1 = MockL1::new(GENESIS_TIME, 1);
+        let mut config = l1.rollup_config();
+        config.seq_window_size = 2;
+        let batch = SingleBatch {
+            parent_hash: config.genesis.l2.hash,
+            epoch_num: 0,
+            epoch_hash: config.genesis.l1.hash,
+            timestamp: GENESIS_TIME + 2,
+            transactions: vec![Bytes::from_static(&[0x02, 0xc0])],
+        };
+        l1.push_block(vec![L1Transaction {
+            to: Some(config.batch_inbox_address),
+            input: calldata(batch),
+            ..Default::default()
+        }]);
+        for _ in 0..3 {
+            l1.push_block(Vec::new());
+        }
+        let mut pipeline = pipeline(l1, config.clone()).await;
+
+        // Only the empty batch of the passed sequencing window is derived.
+        let attributes = derive(&mut pipeline, genesis(&config)).await;
+        assert_eq!(attributes.derived_from.number, 3);
+        assert_eq!(attributes.attributes.transactions.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn derives_empty_batches_past_the_sequencing_window() {
+        let l1 = MockL1::new(GENESIS_TIME, 5);
+        let mut config = l1.rollup_config();
+        config.seq_window_size = 2;
+        let mut pipeline = pipeline(l1, config.clone()).await;
+
+        let mut cursor = genesis(&config);
+        for number in 1..=3 {
+            let attributes = derive(&mut pipeline, cursor).await;
+            let timestamp = attributes.attributes.payload_attributes.timestamp;
+            assert_eq!(timestamp, GENESIS_TIME + 2 * number);
+            assert_eq!(attributes.attributes.transactions.as_ref().unwrap().len(), 1);
+            cursor = L2BlockInfo::new(
+                BlockInfo::new(
+                    B256::with_last_byte(number as u8),
+                    number,
+                    cursor.block_info.hash,
+                    timestamp,
+                ),
+                config.genesis.l1,
+                number,
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_the_origin_of_each_stage() {
+        let l1 = MockL1::new(GENESIS_TIME, 3);
+        let mut config = l1.rollup_config();
+        config.seq_window_size = 1;
+        let (start, tip) = (l1.block(0), l1.block(2));
+        let mut pipeline = pipeline(l1, config.clone()).await;
+        pipeline.record_reset_reason("startup");
+        let status = pipeline.subscribe_status();
+        let reset = status.borrow().last_reset.clone().unwrap();
+        assert_eq!((reset.reason.as_str(), reset.origin), ("startup", start));
+        assert_eq!(status.borrow().origins.traversal, Some(start));
+
+        derive(&mut pipeline, genesis(&config)).await;
+        let origins = status.borrow().origins;
+        assert_eq!(origins.traversal, Some(tip));
+        assert_eq!(origins.l1_window_start, Some(start));
+        assert_eq!(origins.attributes, Some(tip));
+        assert_eq!(status.borrow().buffered.attributes, 1);
+        assert_eq!(status.borrow().last_error, None);
+
+        // Failures are reported until the pipeline makes progress.
+        let stale = BlockInfo { hash: B256::repeat_byte(9), ..start };
+        pipeline.reset(genesis(&config), stale).await.unwrap();
+        assert!(matches!(pipeline.step(genesis(&config)).await, StepResult::StepFailed(_)));
+        let last_error = status.borrow().last_error.clone().unwrap();
+        assert!(last_error.contains("L1 reorg"), "{last_error}");
+        assert_eq!(status.borrow().last_reset.as_ref().unwrap().reason, "unspecified");
+        assert_eq!(status.borrow().origins.attributes, None);
+    }
+
+    #[tokio::test]
+    async fn detects_l1_reorgs() {
+        let l1 = MockL1::new(GENESIS_TIME, 2);
+        let config = l1.rollup_config();
+        let stale = BlockInfo { hash: B256::repeat_byte(9), ..l1.block(0) };
+        let mut pipeline =
+            PipelineBuilder::new(Arc::new(config.clone()), Arc::new(l1)).build().unwrap();
+
+        assert!(matches!(
+            pipeline.step(genesis(&config)).await,
+            StepResult::StepFailed(PipelineError::NotReset)
+        ));
+
+        pipeline.reset(genesis(&config), stale).await.unwrap();
+        match pipeline.step(genesis(&config)).await {
+            StepResult::StepFailed(PipelineError::Reorg(reorg)) => assert_eq!(reorg.origin, stale),
+            result => panic!("expected a reorg, got {result:?}"),
+        }
+    }
+}
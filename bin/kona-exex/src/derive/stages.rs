@@ -0,0 +1,138 @@
+//! The replaceable stages of the derivation pipeline.
+//!
+//! The [`DerivationPipeline`](crate::derive::DerivationPipeline) reads batcher data from a
+//! [`DataSource`], assembles it into batches through the channel bank, checks each batch against
+//! its [`BatchFilter`]s and turns accepted batches into payload attributes with an
+//! [`AttributesBuilder`]. Each of these can be replaced or wrapped through the
+//! [`PipelineBuilder`](crate::derive::PipelineBuilder).
+
+use alloy_primitives::{Address, Bytes};
+use alloy_rpc_types_engine::OptimismPayloadAttributes;
+use async_trait::async_trait;
+use eyre::Result;
+
+use crate::{
+    config::{RollupConfig, SystemConfig},
+    l1::L1Transaction,
+    protocol::{BlockId, BlockInfo, L2BlockInfo, SingleBatch},
+};
+
+/// Retrieves the batcher data posted in an L1 block.
+#[async_trait]
+pub trait DataSource: std::fmt::Debug + Send {
+    /// Returns the data of the batcher transactions sent by `batcher` in `block`, in block
+    /// order: one entry per calldata transaction or blob.
+    async fn open_data(&mut self, block: &BlockInfo, batcher: Address) -> Result<Vec<Bytes>>;
+}
+
+/// Selects the L1 transactions carrying batcher data, in the
+/// [`EthereumDataSource`](crate::derive::EthereumDataSource).
+///
+/// The default [`AddressInbox`](crate::derive::AddressInbox) takes the transactions the batcher
+/// sends to the batch inbox address. Chains whose batch inbox is a contract authorizing its
+/// senders use a [`ContractInbox`](crate::derive::ContractInbox) or a filter of their own.
+pub trait BatcherTxFilter: std::fmt::Debug + Send + Sync {
+    /// Returns whether the filter needs the receipts of L1 blocks, to know which transactions
+    /// succeeded. Receipts are only fetched for filters that do.
+    fn needs_receipts(&self) -> bool {
+        false
+    }
+
+    /// Returns true if `tx` carries batcher data, given the `batcher` of the system config and
+    /// whether the transaction succeeded, which is always true unless [`Self::needs_receipts`].
+    fn is_batcher_tx(&self, tx: &L1Transaction, batcher: Address, succeeded: bool) -> bool;
+}
+
+/// The verdict of a [`BatchFilter`] on a batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchValidity {
+    /// The batch can be used for the next L2 block.
+    Accept,
+    /// The batch is invalid and must be dropped.
+    Drop(String),
+    /// The batch is for a later L2 block and must be kept.
+    Future,
+    /// The batch cannot be checked until more L1 data is known.
+    Undecided,
+}
+
+/// What a [`BatchFilter`] checks a batch against.
+#[derive(Debug, Clone, Copy)]
+pub struct BatchContext<'a> {
+    /// The rollup config.
+    pub config: &'a RollupConfig,
+    /// The safe head the batch must build on.
+    pub safe_head: L2BlockInfo,
+    /// The L1 block the batch was included in.
+    pub inclusion: BlockInfo,
+    /// The L1 blocks known to the pipeline, from the safe head's L1 origin onwards.
+    pub l1_blocks: &'a [BlockInfo],
+}
+
+impl BatchContext<'_> {
+    /// Returns the known L1 block at `number`.
+    pub fn l1_block(&self, number: u64) -> Option<BlockInfo> {
+        let first = self.l1_blocks.first()?.number;
+        self.l1_blocks.get(number.checked_sub(first)? as usize).copied()
+    }
+}
+
+/// Checks batches before they are turned into payload attributes.
+///
+/// Filters run in order, and the first verdict other than [`BatchValidity::Accept`] wins.
+pub trait BatchFilter: std::fmt::Debug + Send {
+    /// Checks `batch` for the L2 block following `ctx.safe_head`.
+    fn check(&mut self, batch: &SingleBatch, ctx: &BatchContext<'_>) -> BatchValidity;
+}
+
+/// Prepares the payload attributes of L2 blocks, with their deposits but without the batch
+/// transactions.
+#[async_trait]
+pub trait AttributesBuilder: std::fmt::Debug + Send + Sync {
+    /// Prepares the attributes of the L2 block following `parent`, with L1 origin `epoch`.
+    async fn prepare_payload_attributes(
+        &mut self,
+        parent: L2BlockInfo,
+        epoch: BlockId,
+    ) -> Result<OptimismPayloadAttributes>;
+
+    /// Resets the builder to the system config in effect at the safe head.
+    fn reset(&mut self, system_config: SystemConfig);
+}
+
+/// Provides the L2 chain state the pipeline is reset from.
+#[async_trait]
+pub trait L2ChainProvider: std::fmt::Debug + Send + Sync {
+    /// Returns the system config in effect at the L2 block `number`.
+    async fn system_config_by_number(&self, number: u64) -> Result<SystemConfig>;
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::B256;
+
+    use super::*;
+
+    #[test]
+    fn looks_up_known_l1_blocks_by_number() {
+        let config = RollupConfig::from_registry(10).unwrap();
+        let l1_blocks = (5..8)
+            .map(|number| {
+                BlockInfo::new(B256::with_last_byte(number), number.into(), B256::ZERO, 0)
+            })
+            .collect::<Vec<_>>();
+        let ctx = BatchContext {
+            config: &config,
+            safe_head: L2BlockInfo::default(),
+            inclusion: BlockInfo::default(),
+            l1_blocks: &l1_blocks,
+        };
+        assert_eq!(ctx.l1_block(5), Some(l1_blocks[0]));
+        assert_eq!(ctx.l1_block(7), Some(l1_blocks[2]));
+        assert_eq!(ctx.l1_block(4), None);
+        assert_eq!(ctx.l1_block(8), None);
+
+        let ctx = BatchContext { l1_blocks: &[], ..ctx };
+        assert_eq!(ctx.l1_block(5), None);
+    }
+}
@@ -0,0 +1,72 @@
+//! The state of the derivation pipeline, as reported by the `hera_pipelineStatus` debug RPC.
+//!
+//! Every stage holds data read at some L1 block: traversal is at the origin of the pipeline,
+//! while the channels, batches and attributes waiting in the later stages were read earlier.
+//! The gap between the origins of two stages, with what each of them buffers, shows which stage
+//! derivation is stuck in.
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::{BlockInfo, L2BlockInfo};
+
+/// The L1 block each stage of the pipeline is at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageOrigins {
+    /// The last L1 block traversed, the origin of the pipeline.
+    pub traversal: Option<BlockInfo>,
+    /// The oldest L1 block kept to resolve the epochs of batches.
+    pub l1_window_start: Option<BlockInfo>,
+    /// The L1 origin of the channel bank.
+    pub channel_bank: Option<BlockInfo>,
+    /// The L1 block the oldest channel being decompressed was read at.
+    pub decompression: Option<BlockInfo>,
+    /// The L1 block the oldest queued batch was included in.
+    pub batch_queue: Option<BlockInfo>,
+    /// The L1 block the last prepared attributes were derived from.
+    pub attributes: Option<BlockInfo>,
+}
+
+/// The data buffered by each stage of the pipeline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BufferedCounts {
+    /// Channels in the channel bank.
+    pub channels: usize,
+    /// Summed size of the channels in the channel bank.
+    pub channel_bank_size: usize,
+    /// Channels being decompressed.
+    pub decompressing_channels: usize,
+    /// Batches waiting for their L2 block, span batches counting once per block.
+    pub batches: usize,
+    /// Attributes prepared but not taken by the driver yet.
+    pub attributes: usize,
+}
+
+/// The last reset of the pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineReset {
+    /// Why the pipeline was reset.
+    pub reason: String,
+    /// The safe head derivation restarted on top of.
+    pub safe_head: L2BlockInfo,
+    /// The L1 block traversal restarted from.
+    pub origin: BlockInfo,
+    /// When the reset happened, in seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+/// The state of the derivation pipeline.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineStatus {
+    /// The L1 block each stage is at.
+    pub origins: StageOrigins,
+    /// The data buffered by each stage.
+    pub buffered: BufferedCounts,
+    /// The last reset, `None` before the first.
+    pub last_reset: Option<PipelineReset>,
+    /// The error of the last failed step, cleared by the next step that makes progress.
+    pub last_error: Option<String>,
+}
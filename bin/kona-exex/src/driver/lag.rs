@@ -0,0 +1,97 @@
+//! Derivation lag gauges.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use metrics::gauge;
+use tokio::sync::watch;
+
+use crate::rpc::SyncStatus;
+
+/// Default interval between two updates of the lag gauges.
+pub const DEFAULT_LAG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The lag of derivation, as exported by the `hera_lag_*` gauges.
+///
+/// The gauges mirror the lag metrics op-node dashboards alert on, so their alert rules can be
+/// reused: the L1 blocks derivation is behind the L1 head, the L2 blocks the safe head is behind
+/// the unsafe head, and the wall-clock age of the safe head.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DerivationLag {
+    /// L1 blocks between the block derivation reads from and the L1 head.
+    pub l1_blocks_behind: u64,
+    /// L2 blocks between the safe head and the unsafe head.
+    pub unsafe_safe_gap: u64,
+    /// Seconds since the timestamp of the safe head.
+    pub safe_head_age: u64,
+}
+
+impl DerivationLag {
+    /// Computes the lag of `status` at the unix timestamp `now`.
+    pub const fn new(status: &SyncStatus, now: u64) -> Self {
+        Self {
+            l1_blocks_behind: status.head_l1.number.saturating_sub(status.current_l1.number),
+            unsafe_safe_gap: status
+                .unsafe_l2
+                .block_info
+                .number
+                .saturating_sub(status.safe_l2.block_info.number),
+            safe_head_age: now.saturating_sub(status.safe_l2.block_info.timestamp),
+        }
+    }
+
+    /// Sets the lag gauges.
+    pub fn export(&self) {
+        gauge!("hera_lag_l1_blocks_behind").set(self.l1_blocks_behind as f64);
+        gauge!("hera_lag_unsafe_safe_blocks").set(self.unsafe_safe_gap as f64);
+        gauge!("hera_lag_safe_head_age_seconds").set(self.safe_head_age as f64);
+    }
+}
+
+/// Follows the sync status, e.g. from [`Driver::subscribe_status`], updating the lag gauges on
+/// every change and every `interval`, so the safe head age keeps growing while derivation is
+/// stalled. Runs until the status sender is dropped.
+///
+/// [`Driver::subscribe_status`]: crate::driver::Driver::subscribe_status
+pub async fn export_lag(mut status: watch::Receiver<SyncStatus>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        DerivationLag::new(&status.borrow_and_update(), now).export();
+        tokio::select! {
+            _ = interval.tick() => {}
+            changed = status.changed() => if changed.is_err() { return },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{BlockInfo, L2BlockInfo};
+
+    fn l2(number: u64, timestamp: u64) -> L2BlockInfo {
+        L2BlockInfo {
+            block_info: BlockInfo { number, timestamp, ..Default::default() },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lag_saturates_on_heads_ahead() {
+        let status = SyncStatus {
+            current_l1: BlockInfo { number: 90, ..Default::default() },
+            head_l1: BlockInfo { number: 100, ..Default::default() },
+            unsafe_l2: l2(1_010, 2_020),
+            safe_l2: l2(1_000, 2_000),
+            ..Default::default()
+        };
+        assert_eq!(
+            DerivationLag::new(&status, 2_030),
+            DerivationLag { l1_blocks_behind: 10, unsafe_safe_gap: 10, safe_head_age: 30 }
+        );
+
+        // The L1 head may be reported after derivation already read past it.
+        let status = SyncStatus { head_l1: BlockInfo::default(), ..status };
+        assert_eq!(DerivationLag::new(&status, 0).l1_blocks_behind, 0);
+    }
+}
@@ -0,0 +1,641 @@
+//! The driver, stepping the derivation pipeline and advancing the L2 chain with its output.
+
+use std::{
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use alloy_rpc_types_engine::{ForkchoiceState, PayloadStatusEnum};
+use eyre::{bail, ensure, eyre, Result, WrapErr};
+use metrics::counter;
+use tokio::sync::watch;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    alert::{Alert, WebhookAlerter},
+    audit::{AuditEvent, AuditLog, HeadKind},
+    config::RollupConfig,
+    derive::{L2AttributesWithParent, Pipeline, PipelineError, StepResult},
+    engine::{
+        CatchUpConfig, CatchUpSubmitter, DerivationGovernor, DryRunEngine, DryRunSummary,
+        EngineApi, ForkchoiceUpdatedVersion, GovernedEngine, PayloadSource,
+    },
+    fees::FeeTracker,
+    l1::ChainProvider,
+    protocol::{BlockInfo, L2BlockInfo},
+    rpc::SyncStatus,
+    safedb::SafeDb,
+    stats::ThroughputStats,
+    validation::{AttributesValidator, ValidationFailurePolicy, ValidationOutcome},
+};
+
+mod lag;
+pub use lag::{export_lag, DerivationLag, DEFAULT_LAG_INTERVAL};
+
+mod start;
+pub use start::DerivationStart;
+
+/// The default interval between two polls of the unsafe sync source, an L2 block time.
+pub const DEFAULT_UNSAFE_SYNC_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Drives the derivation pipeline: derives attributes, validates them and moves the safe head of
+/// the execution layer to each validated block.
+#[derive(Debug)]
+pub struct Driver<P, V> {
+    config: Arc<RollupConfig>,
+    pipeline: P,
+    validator: V,
+    engine: Arc<dyn EngineApi>,
+    cursor: L2BlockInfo,
+    forkchoice: ForkchoiceState,
+    dry_run: Option<Arc<Mutex<DryRunSummary>>>,
+    status: watch::Sender<SyncStatus>,
+    audit: Option<Arc<AuditLog>>,
+    on_failure: ValidationFailurePolicy,
+    l1: Option<Arc<dyn ChainProvider>>,
+    halted: Option<String>,
+    alerter: Option<Arc<WebhookAlerter>>,
+    fees: Option<FeeTracker>,
+    safe_db: Option<Arc<SafeDb>>,
+    stats: Option<Arc<ThroughputStats>>,
+    catch_up: Option<(Arc<dyn PayloadSource>, CatchUpConfig)>,
+    unsafe_sync: Option<Arc<dyn PayloadSource>>,
+    governor: Option<Arc<DerivationGovernor>>,
+}
+
+impl<P: Pipeline, V: AttributesValidator> Driver<P, V> {
+    /// Creates a new driver deriving on top of the `cursor` safe head.
+    pub fn new(
+        config: Arc<RollupConfig>,
+        pipeline: P,
+        validator: V,
+        engine: Arc<dyn EngineApi>,
+        cursor: L2BlockInfo,
+    ) -> Self {
+        let hash = cursor.block_info.hash;
+        Self {
+            config,
+            pipeline,
+            validator,
+            engine,
+            cursor,
+            forkchoice: ForkchoiceState {
+                head_block_hash: hash,
+                safe_block_hash: hash,
+                finalized_block_hash: hash,
+            },
+            dry_run: None,
+            status: watch::Sender::new(SyncStatus {
+                unsafe_l2: cursor,
+                safe_l2: cursor,
+                finalized_l2: cursor,
+                ..Default::default()
+            }),
+            audit: None,
+            on_failure: ValidationFailurePolicy::default(),
+            l1: None,
+            halted: None,
+            alerter: None,
+            fees: None,
+            safe_db: None,
+            stats: None,
+            catch_up: None,
+            unsafe_sync: None,
+            governor: None,
+        }
+    }
+
+    /// Switches the driver to dry-run mode: the pipeline and validation still run, but the
+    /// execution layer's forkchoice is never updated.
+    pub fn with_dry_run(mut self, engine: impl EngineApi + 'static) -> Self {
+        let engine = DryRunEngine::new(engine);
+        self.dry_run = Some(engine.summary_handle());
+        self.engine = Arc::new(engine);
+        self
+    }
+
+    /// Records derived blocks, head advancements, validation failures and reorgs in `audit`.
+    pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Alerts validation failures and L2 reorgs through `alerter`.
+    pub fn with_alerter(mut self, alerter: Arc<WebhookAlerter>) -> Self {
+        self.alerter = Some(alerter);
+        self
+    }
+
+    /// Records the fee parameters of every derived block in `fees`.
+    pub fn with_fee_tracker(mut self, fees: FeeTracker) -> Self {
+        self.fees = Some(fees);
+        self
+    }
+
+    /// Records every new safe head in `safe_db`, with the L1 block it was derived from.
+    pub fn with_safe_db(mut self, safe_db: Arc<SafeDb>) -> Self {
+        self.safe_db = Some(safe_db);
+        self
+    }
+
+    /// Accounts every derived block in the hourly throughput `stats`.
+    pub fn with_throughput_stats(mut self, stats: Arc<ThroughputStats>) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Feeds every Engine API call into `governor`, and delays each safe head update and
+    /// catch-up payload by the governor's delay while the execution layer is syncing or slow.
+    ///
+    /// Must be set after [`Self::with_dry_run`], which replaces the engine.
+    pub fn with_governor(mut self, governor: Arc<DerivationGovernor>) -> Self {
+        self.engine = Arc::new(GovernedEngine::new(self.engine.clone(), governor.clone()));
+        self.governor = Some(governor);
+        self
+    }
+
+    /// Inserts the payloads of `source` ahead of derivation whenever its tip is far ahead of the
+    /// unsafe head, see [`CatchUpConfig::threshold`].
+    pub fn with_catch_up(
+        mut self,
+        source: Arc<dyn PayloadSource>,
+        settings: CatchUpConfig,
+    ) -> Self {
+        self.catch_up = Some((source, settings));
+        self
+    }
+
+    /// Follows the unsafe head of `source`, e.g. a trusted L2 node, inserting every block it is
+    /// ahead of the unsafe head with [`Self::sync_unsafe`].
+    pub fn with_unsafe_sync(mut self, source: Arc<dyn PayloadSource>) -> Self {
+        self.unsafe_sync = Some(source);
+        self
+    }
+
+    /// Sets what to do when derived attributes fail validation. Resyncing needs an L1 provider,
+    /// see [`Self::with_l1_provider`].
+    pub const fn with_validation_failure_policy(mut self, policy: ValidationFailurePolicy) -> Self {
+        self.on_failure = policy;
+        self
+    }
+
+    /// Looks up the L1 origin of the safe head with `l1` when resyncing the pipeline.
+    pub fn with_l1_provider(mut self, l1: Arc<dyn ChainProvider>) -> Self {
+        self.l1 = Some(l1);
+        self
+    }
+
+    /// Returns the validation failure derivation is halted on, if any.
+    pub fn halted(&self) -> Option<&str> {
+        self.halted.as_deref()
+    }
+
+    /// Resumes derivation halted on a validation failure.
+    pub fn resume(&mut self) {
+        if let Some(reason) = self.halted.take() {
+            info!(target: "hera::driver", %reason, "Resuming derivation");
+        }
+    }
+
+    /// Returns the rollup config.
+    pub fn config(&self) -> &RollupConfig {
+        &self.config
+    }
+
+    /// Returns the current safe head.
+    pub const fn cursor(&self) -> L2BlockInfo {
+        self.cursor
+    }
+
+    /// Subscribes to the sync status, updated whenever the safe head advances.
+    pub fn subscribe_status(&self) -> watch::Receiver<SyncStatus> {
+        self.status.subscribe()
+    }
+
+    /// Returns a handle to the sync status, for components reporting into it alongside the
+    /// driver.
+    pub fn status_sender(&self) -> watch::Sender<SyncStatus> {
+        self.status.clone()
+    }
+
+    /// Returns what the dry run would have done so far, if running in dry-run mode.
+    pub fn dry_run_summary(&self) -> Option<DryRunSummary> {
+        self.dry_run.as_ref().map(|summary| summary.lock().unwrap().clone())
+    }
+
+    /// Resets the pipeline to derive on top of `safe_head`, reading L1 from `l1_origin`, and
+    /// moves the unsafe and safe heads back to `safe_head`. The `reason` is reported with the
+    /// state of the pipeline.
+    pub async fn reset(
+        &mut self,
+        safe_head: L2BlockInfo,
+        l1_origin: BlockInfo,
+        reason: &str,
+    ) -> Result<()> {
+        self.pipeline.reset(safe_head, l1_origin).await?;
+        self.pipeline.record_reset_reason(reason);
+        if let Some(safe_db) = &self.safe_db {
+            safe_db.truncate(l1_origin.number + 1)?;
+        }
+        let unsafe_head = self.status.borrow().unsafe_l2;
+        let depth = unsafe_head.block_info.number.saturating_sub(safe_head.block_info.number);
+        if depth > 0 {
+            self.alert(Alert::Reorg {
+                depth,
+                detail: format!("reset from unsafe head {unsafe_head} to {safe_head}"),
+            });
+        }
+        self.cursor = safe_head;
+        self.forkchoice.head_block_hash = safe_head.block_info.hash;
+        self.forkchoice.safe_block_hash = safe_head.block_info.hash;
+        self.status.send_modify(|status| {
+            status.unsafe_l2 = safe_head;
+            status.safe_l2 = safe_head;
+            status.pending_safe_l2 = safe_head;
+            if status.finalized_l2.block_info.number > safe_head.block_info.number {
+                status.finalized_l2 = safe_head;
+                self.forkchoice.finalized_block_hash = safe_head.block_info.hash;
+            }
+            status.current_l1 = l1_origin;
+        });
+        info!(target: "hera::driver", %safe_head, %l1_origin, "Reset driver");
+        Ok(())
+    }
+
+    /// Steps the pipeline until it runs out of L1 data, returning the number of L2 blocks the
+    /// safe head advanced by.
+    ///
+    /// Catches up with the tip of the catch-up source first, if far behind it. Stops at the first
+    /// validation failure, and returns immediately while derivation is halted on one.
+    pub async fn advance(&mut self) -> Result<u64> {
+        if self.halted.is_some() {
+            return Ok(0);
+        }
+        self.catch_up().await?;
+        let mut advanced = 0;
+        'derive: loop {
+            match self.pipeline.step(self.cursor).await {
+                StepResult::PreparedAttributes => {
+                    while let Some(attributes) = self.pipeline.next() {
+                        if !self.process(attributes).await? {
+                            break 'derive;
+                        }
+                        advanced += 1;
+                    }
+                }
+                StepResult::AdvancedOrigin => {}
+                StepResult::OriginAdvanceErr(err) if err.is_recoverable() => {
+                    debug!(target: "hera::driver", %err, "Pipeline waiting for L1 data");
+                    break;
+                }
+                StepResult::OriginAdvanceErr(err) | StepResult::StepFailed(err) => {
+                    if err.is_pruned() {
+                        bail!("derivation cannot proceed: {err}");
+                    }
+                    if let PipelineError::Reorg(reorg) = &err {
+                        self.audit(AuditEvent::Reorg { block: None, detail: reorg.to_string() });
+                    }
+                    warn!(
+                        target: "hera::driver",
+                        %err,
+                        recoverable = err.is_recoverable(),
+                        "Pipeline step failed"
+                    );
+                    break;
+                }
+            }
+        }
+
+        if let Some(summary) = self.dry_run_summary() {
+            if advanced > 0 {
+                info!(target: "hera::driver", %summary, "Dry run progress");
+            }
+        }
+        Ok(advanced)
+    }
+
+    /// Moves the unsafe head to the tip of the unsafe sync source, returning the number of
+    /// inserted blocks.
+    ///
+    /// Fills the unsafe chain between the safe head updates of derivation when no gossip brings
+    /// it, so that the execution layer and the sync status stay at the tip. Blocks are inserted
+    /// a catch-up batch at a time, and only on top of the unsafe head: a source that reorged
+    /// fails the run until derivation resets the unsafe head.
+    pub async fn sync_unsafe(&mut self) -> Result<u64> {
+        let Some(source) = self.unsafe_sync.clone() else {
+            return Ok(0);
+        };
+        let unsafe_head = self.status.borrow().unsafe_l2;
+        let tip = source.tip().await.wrap_err("failed to fetch the unsafe sync tip")?;
+        let settings = CatchUpConfig { threshold: 0, ..Default::default() };
+        let Some(range) = settings.range(unsafe_head.block_info.number, tip) else {
+            return Ok(0);
+        };
+        debug!(target: "hera::driver", %unsafe_head, tip, "Filling the unsafe chain");
+        let inserted = self.insert_unsafe(source, settings, range).await?;
+        counter!("hera_unsafe_sync_blocks_total").increment(inserted);
+        Ok(inserted)
+    }
+
+    /// Moves the unsafe head to the tip of the catch-up source if it is far behind it, returning
+    /// the number of inserted blocks.
+    async fn catch_up(&mut self) -> Result<u64> {
+        let Some((source, settings)) = self.catch_up.clone() else {
+            return Ok(0);
+        };
+        let unsafe_head = self.status.borrow().unsafe_l2;
+        let tip = source.tip().await.wrap_err("failed to fetch the catch-up tip")?;
+        let Some(range) = settings.range(unsafe_head.block_info.number, tip) else {
+            return Ok(0);
+        };
+        info!(target: "hera::driver", %unsafe_head, tip, "Far behind the tip, catching up");
+        self.insert_unsafe(source, settings, range).await
+    }
+
+    /// Inserts the blocks in `range` of `source` on top of the unsafe head.
+    async fn insert_unsafe(
+        &mut self,
+        source: Arc<dyn PayloadSource>,
+        settings: CatchUpConfig,
+        range: RangeInclusive<u64>,
+    ) -> Result<u64> {
+        let unsafe_head = self.status.borrow().unsafe_l2;
+        let submitter = CatchUpSubmitter::new(self.engine.clone(), self.config.clone(), settings);
+        let state =
+            ForkchoiceState { head_block_hash: unsafe_head.block_info.hash, ..self.forkchoice };
+        let outcome = submitter.sync(source, state, range).await?;
+        if let Some(head) = outcome.head {
+            self.forkchoice.head_block_hash = head.block_info.hash;
+            self.status.send_modify(|status| status.unsafe_l2 = head);
+            self.audit(AuditEvent::HeadAdvanced { head: HeadKind::Unsafe, block: head });
+        }
+        Ok(outcome.inserted)
+    }
+
+    /// Validates the attributes and advances the safe head to their block. Returns `false` if
+    /// they failed validation, after applying the failure policy.
+    async fn process(&mut self, attributes: L2AttributesWithParent) -> Result<bool> {
+        let timestamp = attributes.attributes.payload_attributes.timestamp;
+        if self.config.is_granite_activation_block(timestamp) {
+            info!(
+                target: "hera::driver",
+                timestamp,
+                channel_timeout = self.config.channel_timeout(timestamp),
+                bn256_pairing_max_input_size = ?self.config.bn256_pairing_max_input_size(timestamp),
+                "Granite activated"
+            );
+        }
+        let block = match self.validator.validate(&attributes).await? {
+            ValidationOutcome::Valid(block) => block,
+            ValidationOutcome::Invalid(reason) => {
+                self.audit(AuditEvent::ValidationFailure {
+                    parent: attributes.parent,
+                    reason: reason.clone(),
+                });
+                self.on_validation_failure(attributes.parent, reason).await?;
+                return Ok(false);
+            }
+        };
+
+        self.audit(AuditEvent::DerivedBlock(block));
+
+        // A sequencer may have built unsafe blocks past the derived safe head, keep them.
+        let unsafe_head = self.status.borrow().unsafe_l2;
+        if unsafe_head.block_info.number == block.block_info.number && unsafe_head != block {
+            let detail = format!("derived block {block} replaced unsafe block {unsafe_head}");
+            self.alert(Alert::Reorg { depth: 1, detail: detail.clone() });
+            self.audit(AuditEvent::Reorg { block: Some(block), detail });
+        }
+        let head = if unsafe_head.block_info.number > block.block_info.number {
+            unsafe_head
+        } else {
+            block
+        };
+        self.forkchoice.head_block_hash = head.block_info.hash;
+        self.forkchoice.safe_block_hash = block.block_info.hash;
+        if let Some(governor) = &self.governor {
+            governor.throttle().await;
+        }
+        let updated = self
+            .engine
+            .forkchoice_updated(ForkchoiceUpdatedVersion::V3, self.forkchoice, None)
+            .await?;
+        if let PayloadStatusEnum::Invalid { validation_error } = updated.payload_status.status {
+            bail!("engine rejected safe head {block}: {validation_error}");
+        }
+
+        debug!(target: "hera::driver", safe_head = %block, "Advanced safe head");
+        self.audit(AuditEvent::HeadAdvanced { head: HeadKind::Safe, block });
+        if head == block {
+            self.audit(AuditEvent::HeadAdvanced { head: HeadKind::Unsafe, block });
+        }
+        if let Some(safe_db) = &self.safe_db {
+            if let Err(err) = safe_db.record(attributes.derived_from.id(), block.block_info.id()) {
+                warn!(target: "hera::driver", %err, "Failed to record safe head");
+            }
+        }
+        if let Some(stats) = &self.stats {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            let transactions = attributes.attributes.transactions.as_deref().unwrap_or_default();
+            if let Err(err) = stats.record(now, attributes.derived_from.number, transactions) {
+                warn!(target: "hera::driver", %err, "Failed to record derivation throughput");
+            }
+        }
+        if let Some(fees) = &self.fees {
+            let l1_info = attributes.attributes.transactions.as_ref().and_then(|txs| txs.first());
+            let number = block.block_info.number;
+            if let Err(err) = fees.record_l1_info_tx(number, l1_info.map(|tx| &tx[..])) {
+                warn!(target: "hera::driver", %err, "Failed to record fee parameters");
+            }
+        }
+        self.cursor = block;
+        let origin = self.pipeline.origin();
+        self.status.send_modify(|status| {
+            status.unsafe_l2 = head;
+            status.safe_l2 = block;
+            status.pending_safe_l2 = block;
+            if let Some(origin) = origin {
+                status.current_l1 = origin;
+            }
+        });
+        Ok(true)
+    }
+
+    /// Applies the validation failure policy to attributes derived on top of `parent` that
+    /// failed validation.
+    ///
+    /// Attributes already prepared after the failed ones build on the rejected block, and are
+    /// dropped under every policy.
+    async fn on_validation_failure(&mut self, parent: L2BlockInfo, reason: String) -> Result<()> {
+        counter!("hera_validation_failures_total", "policy" => self.on_failure.to_string())
+            .increment(1);
+        let message = format!("attributes derived on top of {parent} failed validation: {reason}");
+        if let Some(alerter) = &self.alerter {
+            let alert = Alert::ValidationFailure { parent, reason: reason.clone() };
+            if self.on_failure == ValidationFailurePolicy::Panic {
+                // Post the alert before the node goes down.
+                if let Err(err) = alerter.send(&alert).await {
+                    warn!(target: "hera::driver", %err, "Failed to post alert");
+                }
+            } else {
+                alerter.notify(alert);
+            }
+        }
+        while self.pipeline.next().is_some() {}
+
+        match self.on_failure {
+            ValidationFailurePolicy::Panic => panic!("{message}"),
+            ValidationFailurePolicy::Halt => {
+                error!(target: "hera::driver", %parent, %reason, "Validation failed, halting derivation");
+                self.halted = Some(message);
+            }
+            ValidationFailurePolicy::Log => {
+                warn!(target: "hera::driver", %parent, %reason, "Validation failed, skipping attributes");
+            }
+            ValidationFailurePolicy::Resync => {
+                warn!(target: "hera::driver", %parent, %reason, "Validation failed, resyncing pipeline");
+                let resynced = self.resync(&message).await;
+                resynced.wrap_err(message)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Resets the pipeline to derive again on top of the safe head, e.g. after attributes were
+    /// dropped by a failed step, for `reason`. Needs an L1 provider, see
+    /// [`Self::with_l1_provider`].
+    pub async fn resync(&mut self, reason: &str) -> Result<()> {
+        let l1 = self.l1.as_ref().ok_or_else(|| eyre!("no L1 provider to resync with"))?;
+        let origin = l1
+            .block_info_by_number(self.cursor.l1_origin.number)
+            .await
+            .wrap_err("failed to fetch the L1 origin of the safe head to resync")?;
+        ensure!(
+            origin.hash == self.cursor.l1_origin.hash,
+            "L1 origin {} of the safe head was reorged",
+            self.cursor.l1_origin
+        );
+        self.pipeline.reset(self.cursor, origin).await?;
+        self.pipeline.record_reset_reason(reason);
+        Ok(())
+    }
+
+    /// Posts an alert, if alerting is enabled.
+    fn alert(&self, alert: Alert) {
+        if let Some(alerter) = &self.alerter {
+            alerter.notify(alert);
+        }
+    }
+
+    /// Records an audit event, if the audit log is enabled. Failures are logged but never stop
+    /// the driver.
+    fn audit(&self, event: AuditEvent) {
+        if let Some(audit) = &self.audit {
+            if let Err(err) = audit.record(&event) {
+                warn!(target: "hera::driver", %err, "Failed to record audit event");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::{
+        derive::PipelineResult,
+        engine::mock::{block_hash, payload, EngineCall, MockEngine, MockSource},
+        validation::ValidationError,
+    };
+
+    /// A pipeline without L1 data.
+    #[derive(Debug)]
+    struct IdlePipeline;
+
+    #[async_trait]
+    impl Pipeline for IdlePipeline {
+        async fn step(&mut self, _cursor: L2BlockInfo) -> StepResult {
+            StepResult::StepFailed(PipelineError::NotReset)
+        }
+
+        fn next(&mut self) -> Option<L2AttributesWithParent> {
+            None
+        }
+
+        fn origin(&self) -> Option<BlockInfo> {
+            None
+        }
+
+        async fn reset(&mut self, _: L2BlockInfo, _: BlockInfo) -> PipelineResult<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Debug)]
+    struct NoValidator;
+
+    #[async_trait]
+    impl AttributesValidator for NoValidator {
+        async fn validate(
+            &self,
+            _attributes: &L2AttributesWithParent,
+        ) -> Result<ValidationOutcome, ValidationError> {
+            unreachable!("idle pipeline derives no attributes")
+        }
+    }
+
+    fn driver(engine: Arc<MockEngine>, tip: u64) -> Driver<IdlePipeline, NoValidator> {
+        let config = Arc::new(RollupConfig::from_registry(10).unwrap());
+        let genesis = payload(0).l2_block_info().unwrap();
+        let settings = CatchUpConfig { threshold: 10, max_batch: 50, ..Default::default() };
+        Driver::new(config, IdlePipeline, NoValidator, engine, genesis)
+            .with_catch_up(Arc::new(MockSource { tip }), settings)
+    }
+
+    #[tokio::test]
+    async fn catches_up_when_far_behind_the_tip() {
+        let engine = Arc::new(MockEngine::new());
+        let mut driver = driver(engine.clone(), 120);
+        let status = driver.subscribe_status();
+
+        driver.advance().await.unwrap();
+        assert_eq!(status.borrow().unsafe_l2.block_info.hash, block_hash(50));
+        assert_eq!(status.borrow().safe_l2.block_info.hash, block_hash(0));
+
+        driver.advance().await.unwrap();
+        assert_eq!(status.borrow().unsafe_l2.block_info.number, 100);
+        driver.advance().await.unwrap();
+        assert_eq!(status.borrow().unsafe_l2.block_info.number, 120);
+
+        // Within the threshold of the tip, blocks are left to derivation and gossip.
+        let calls = engine.calls().len();
+        driver.advance().await.unwrap();
+        assert_eq!(engine.calls().len(), calls);
+    }
+
+    #[tokio::test]
+    async fn fills_the_unsafe_chain_up_to_the_trusted_tip() {
+        let engine = Arc::new(MockEngine::new());
+        let mut driver =
+            driver(engine.clone(), 0).with_unsafe_sync(Arc::new(MockSource { tip: 3 }));
+        let status = driver.subscribe_status();
+
+        // Gaps below the catch-up threshold are filled too.
+        assert_eq!(driver.sync_unsafe().await.unwrap(), 3);
+        assert_eq!(status.borrow().unsafe_l2.block_info.hash, block_hash(3));
+        assert_eq!(status.borrow().safe_l2.block_info.hash, block_hash(0));
+        let last = engine.calls().into_iter().rev().find_map(|call| match call {
+            EngineCall::ForkchoiceUpdated(state) => Some(state),
+            _ => None,
+        });
+        let last = last.unwrap();
+        assert_eq!(last.head_block_hash, block_hash(3));
+        assert_eq!(last.safe_block_hash, block_hash(0));
+
+        let calls = engine.calls().len();
+        assert_eq!(driver.sync_unsafe().await.unwrap(), 0);
+        assert_eq!(engine.calls().len(), calls);
+    }
+}
@@ -0,0 +1,61 @@
+//! Operator-forced derivation start points.
+
+use eyre::{ensure, Result, WrapErr};
+use tracing::{info, warn};
+
+use crate::{
+    l1::ChainProvider,
+    output::L2StateProvider,
+    protocol::{BlockInfo, L2BlockInfo},
+};
+
+/// A known-good point to start derivation from, overriding the genesis.
+///
+/// The L2 block is taken as the safe head without any check against L1, so a wrong start point
+/// derives a wrong chain. It is meant for expert operators recovering from situations where the
+/// regular start point is unusable, such as a chain migration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DerivationStart {
+    /// The number of the L2 block to start deriving on top of.
+    pub l2_block: u64,
+    /// The number of the L1 block to start reading batcher data from, defaulting to the L1
+    /// origin of the L2 block.
+    pub l1_origin: Option<u64>,
+}
+
+impl DerivationStart {
+    /// Resolves the start point into the safe head and L1 origin to reset the pipeline to.
+    pub async fn resolve(
+        &self,
+        l2: &dyn L2StateProvider,
+        l1: &dyn ChainProvider,
+    ) -> Result<(L2BlockInfo, BlockInfo)> {
+        let (safe_head, _) = l2.block_by_number(self.l2_block).await.wrap_err_with(|| {
+            format!("failed to fetch derivation start L2 block {}", self.l2_block)
+        })?;
+
+        let number = self.l1_origin.unwrap_or(safe_head.l1_origin.number);
+        let l1_origin = l1
+            .block_info_by_number(number)
+            .await
+            .wrap_err_with(|| format!("failed to fetch derivation start L1 origin {number}"))?;
+        if self.l1_origin.is_none() {
+            ensure!(
+                l1_origin.hash == safe_head.l1_origin.hash,
+                "L1 origin {} of derivation start block {safe_head} is not canonical",
+                safe_head.l1_origin
+            );
+        } else if l1_origin.number > safe_head.l1_origin.number {
+            warn!(
+                target: "hera::driver",
+                %safe_head,
+                %l1_origin,
+                "Derivation start L1 origin is past the L1 origin of the start block, batches in \
+                 between are skipped"
+            );
+        }
+
+        info!(target: "hera::driver", %safe_head, %l1_origin, "Resolved derivation start point");
+        Ok((safe_head, l1_origin))
+    }
+}
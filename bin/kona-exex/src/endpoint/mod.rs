@@ -0,0 +1,211 @@
+//! Typed configuration of the HTTP endpoints Hera talks to, and the clients shared across them.
+//!
+//! Several subsystems read from the same endpoint, e.g. derivation and payload validation both
+//! read the L2 RPC. Building their clients through [`HttpClients`] makes them share one
+//! connection pool per endpoint instead of each opening its own connections.
+
+use std::{collections::HashMap, fmt, str::FromStr, sync::Mutex, time::Duration};
+
+use eyre::{eyre, Result, WrapErr};
+use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION},
+    Client,
+};
+use url::Url;
+
+/// The default timeout of a request.
+pub const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The default maximum number of connections to an endpoint.
+pub const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+/// A header sent with every request to an endpoint, parsed from `Name: value`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HttpHeader {
+    /// The header name.
+    pub name: HeaderName,
+    /// The header value.
+    pub value: HeaderValue,
+}
+
+impl FromStr for HttpHeader {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, value) =
+            s.split_once(':').ok_or_else(|| eyre!("invalid header {s}, expected Name: value"))?;
+        let name = name.trim().parse().wrap_err_with(|| format!("invalid header name in {s}"))?;
+        let mut value: HeaderValue =
+            value.trim().parse().wrap_err_with(|| format!("invalid header value in {s}"))?;
+        // Headers often carry credentials, keep them out of logs.
+        value.set_sensitive(true);
+        Ok(Self { name, value })
+    }
+}
+
+impl fmt::Display for HttpHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {:?}", self.name, self.value)
+    }
+}
+
+/// An HTTP endpoint, with the settings of the clients talking to it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Endpoint {
+    /// The URL of the endpoint.
+    pub url: Url,
+    /// The timeout of a request.
+    pub timeout: Duration,
+    /// The maximum number of connections, or of concurrent requests for JSON-RPC clients.
+    pub max_connections: usize,
+    /// The headers sent with every request, e.g. for authentication.
+    pub headers: Vec<HttpHeader>,
+}
+
+impl Endpoint {
+    /// Creates an endpoint at `url` with the default settings.
+    pub const fn new(url: Url) -> Self {
+        Self {
+            url,
+            timeout: DEFAULT_HTTP_TIMEOUT,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Parses an endpoint at `url` with the default settings.
+    pub fn parse(url: &str) -> Result<Self> {
+        Ok(Self::new(Url::parse(url).wrap_err_with(|| format!("invalid URL {url}"))?))
+    }
+
+    /// Sets the timeout of a request.
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum number of connections.
+    pub const fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    /// Sends `headers` with every request.
+    pub fn with_headers(mut self, headers: impl IntoIterator<Item = HttpHeader>) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
+    /// Authenticates every request with the bearer `token`.
+    pub fn with_bearer_auth(self, token: &str) -> Result<Self> {
+        let mut value = HeaderValue::from_str(&format!("Bearer {token}"))?;
+        value.set_sensitive(true);
+        Ok(self.with_headers([HttpHeader { name: AUTHORIZATION, value }]))
+    }
+
+    /// Returns the headers sent with every request.
+    pub fn header_map(&self) -> HeaderMap {
+        self.headers.iter().map(|header| (header.name.clone(), header.value.clone())).collect()
+    }
+
+    /// Builds a JSON-RPC client of the endpoint, not shared with other subsystems.
+    pub fn rpc_client(&self) -> Result<HttpClient> {
+        HttpClientBuilder::default()
+            .request_timeout(self.timeout)
+            .max_concurrent_requests(self.max_connections)
+            .set_headers(self.header_map())
+            .build(self.url.as_str())
+            .wrap_err_with(|| format!("invalid JSON-RPC URL {}", self.url))
+    }
+
+    /// Builds a REST client of the endpoint, not shared with other subsystems.
+    pub fn rest_client(&self) -> Result<Client> {
+        Client::builder()
+            .timeout(self.timeout)
+            .pool_max_idle_per_host(self.max_connections)
+            .default_headers(self.header_map())
+            .build()
+            .wrap_err_with(|| format!("failed to build the HTTP client of {}", self.url))
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.url.fmt(f)
+    }
+}
+
+/// The clients of every endpoint, shared by the subsystems talking to the same endpoint.
+///
+/// Endpoints are told apart by all of their settings, so that clients sending different headers
+/// to the same URL are never mixed up.
+#[derive(Debug, Default)]
+pub struct HttpClients {
+    rpc: Mutex<HashMap<Endpoint, HttpClient>>,
+    rest: Mutex<HashMap<Endpoint, Client>>,
+}
+
+impl HttpClients {
+    /// Returns the JSON-RPC client of `endpoint`, building it on first use.
+    pub fn rpc(&self, endpoint: &Endpoint) -> Result<HttpClient> {
+        let mut clients = self.rpc.lock().unwrap();
+        if let Some(client) = clients.get(endpoint) {
+            return Ok(client.clone());
+        }
+        let client = endpoint.rpc_client()?;
+        clients.insert(endpoint.clone(), client.clone());
+        Ok(client)
+    }
+
+    /// Returns the REST client of `endpoint`, building it on first use.
+    pub fn rest(&self, endpoint: &Endpoint) -> Result<Client> {
+        let mut clients = self.rest.lock().unwrap();
+        if let Some(client) = clients.get(endpoint) {
+            return Ok(client.clone());
+        }
+        let client = endpoint.rest_client()?;
+        clients.insert(endpoint.clone(), client.clone());
+        Ok(client)
+    }
+
+    /// Returns the number of endpoints with a client.
+    pub fn len(&self) -> usize {
+        self.rpc.lock().unwrap().len() + self.rest.lock().unwrap().len()
+    }
+
+    /// Returns true if no client was built.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_headers() {
+        let header: HttpHeader = "X-Api-Key:  secret ".parse().unwrap();
+        assert_eq!(header.name, "x-api-key");
+        assert_eq!(header.value, "secret");
+        assert_eq!(header.to_string(), "x-api-key: Sensitive");
+        assert!("X-Api-Key".parse::<HttpHeader>().is_err());
+        assert!("Bad Name: value".parse::<HttpHeader>().is_err());
+    }
+
+    #[test]
+    fn shares_clients_per_endpoint() {
+        let clients = HttpClients::default();
+        let endpoint = Endpoint::parse("http://localhost:8545").unwrap();
+        clients.rpc(&endpoint).unwrap();
+        clients.rpc(&endpoint).unwrap();
+        assert_eq!(clients.len(), 1);
+
+        let authenticated = endpoint.clone().with_bearer_auth("token").unwrap();
+        assert_eq!(authenticated.header_map()[AUTHORIZATION], "Bearer token");
+        clients.rpc(&authenticated).unwrap();
+        clients.rest(&endpoint.with_timeout(Duration::from_secs(1))).unwrap();
+        assert_eq!(clients.len(), 3);
+    }
+}
@@ -0,0 +1,137 @@
+//! The Engine API of the L2 execution layer.
+
+use std::sync::Arc;
+
+use alloy_primitives::B256;
+use alloy_rpc_types_engine::{
+    ExecutionPayload, ForkchoiceState, ForkchoiceUpdated, OptimismPayloadAttributes, PayloadId,
+    PayloadStatus,
+};
+use async_trait::async_trait;
+use eyre::{eyre, Result, WrapErr};
+
+use crate::{
+    engine::{EngineResult, ForkchoiceUpdatedVersion, GetPayloadVersion, NewPayloadVersion},
+    protocol::{BlockId, BlockInfo, L1BlockInfoTx, L2BlockInfo, TxDeposit},
+};
+
+/// An execution payload together with the fields needed to insert it into the execution layer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnginePayload {
+    /// The execution payload.
+    pub payload: ExecutionPayload,
+    /// The parent beacon block root, from Ecotone onwards.
+    pub parent_beacon_block_root: Option<B256>,
+    /// The withdrawals root of the block header, from Isthmus onwards, where it commits to the
+    /// storage root of the `L2ToL1MessagePasser` predeploy. Carried by V4 payloads only.
+    pub withdrawals_root: Option<B256>,
+}
+
+impl EnginePayload {
+    /// Returns the id of the block built by the payload.
+    pub const fn block_id(&self) -> BlockId {
+        let payload = self.payload.as_v1();
+        BlockId::new(payload.block_hash, payload.block_number)
+    }
+
+    /// Returns the timestamp of the block built by the payload.
+    pub const fn timestamp(&self) -> u64 {
+        self.payload.as_v1().timestamp
+    }
+
+    /// Returns the parent hash of the block built by the payload.
+    pub const fn parent_hash(&self) -> B256 {
+        self.payload.as_v1().parent_hash
+    }
+
+    /// Returns the state root of the block built by the payload.
+    pub const fn state_root(&self) -> B256 {
+        self.payload.as_v1().state_root
+    }
+
+    /// Returns the reference of the block built by the payload, with the L1 origin read from the
+    /// L1 info deposit opening every post-genesis block.
+    pub fn l2_block_info(&self) -> Result<L2BlockInfo> {
+        let payload = self.payload.as_v1();
+        let block = self.block_id();
+        let tx = payload
+            .transactions
+            .first()
+            .ok_or_else(|| eyre!("payload {block} has no L1 info deposit"))?;
+        let info = TxDeposit::decode_2718(&mut &tx[..])
+            .and_then(|deposit| L1BlockInfoTx::decode_calldata(&deposit.input))
+            .wrap_err_with(|| format!("invalid L1 info deposit in payload {block}"))?;
+        let block_info = BlockInfo::new(
+            payload.block_hash,
+            payload.block_number,
+            payload.parent_hash,
+            payload.timestamp,
+        );
+        Ok(L2BlockInfo::new(block_info, info.id(), info.sequence_number()))
+    }
+}
+
+/// The subset of the Engine API used by Hera.
+///
+/// Method versions are selected by the caller from the payload timestamp, see
+/// [`crate::engine::version`].
+#[async_trait]
+pub trait EngineApi: std::fmt::Debug + Send + Sync {
+    /// Inserts a payload through `engine_newPayload`.
+    async fn new_payload(
+        &self,
+        version: NewPayloadVersion,
+        payload: EnginePayload,
+    ) -> EngineResult<PayloadStatus>;
+
+    /// Updates the forkchoice through `engine_forkchoiceUpdated`, optionally starting a payload
+    /// build with the given attributes.
+    async fn forkchoice_updated(
+        &self,
+        version: ForkchoiceUpdatedVersion,
+        state: ForkchoiceState,
+        attributes: Option<OptimismPayloadAttributes>,
+    ) -> EngineResult<ForkchoiceUpdated>;
+
+    /// Retrieves a built payload through `engine_getPayload`.
+    async fn get_payload(
+        &self,
+        version: GetPayloadVersion,
+        payload_id: PayloadId,
+    ) -> EngineResult<EnginePayload>;
+
+    /// Exchanges the supported Engine API methods through `engine_exchangeCapabilities`.
+    async fn exchange_capabilities(&self, methods: Vec<String>) -> EngineResult<Vec<String>>;
+}
+
+#[async_trait]
+impl<E: EngineApi + ?Sized> EngineApi for Arc<E> {
+    async fn new_payload(
+        &self,
+        version: NewPayloadVersion,
+        payload: EnginePayload,
+    ) -> EngineResult<PayloadStatus> {
+        (**self).new_payload(version, payload).await
+    }
+
+    async fn forkchoice_updated(
+        &self,
+        version: ForkchoiceUpdatedVersion,
+        state: ForkchoiceState,
+        attributes: Option<OptimismPayloadAttributes>,
+    ) -> EngineResult<ForkchoiceUpdated> {
+        (**self).forkchoice_updated(version, state, attributes).await
+    }
+
+    async fn get_payload(
+        &self,
+        version: GetPayloadVersion,
+        payload_id: PayloadId,
+    ) -> EngineResult<EnginePayload> {
+        (**self).get_payload(version, payload_id).await
+    }
+
+    async fn exchange_capabilities(&self, methods: Vec<String>) -> EngineResult<Vec<String>> {
+        (**self).exchange_capabilities(methods).await
+    }
+}
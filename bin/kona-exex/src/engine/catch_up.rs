@@ -0,0 +1,326 @@
+//! Pipelined payload submission while catching up with the L2 tip.
+//!
+//! Far behind the tip, inserting payloads one at a time with a forkchoice update after each
+//! leaves the execution layer idle while the next payload is prepared. The [`CatchUpSubmitter`]
+//! prepares payloads ahead on a separate task while the previous one is inserted, and coalesces
+//! forkchoice updates so the head only moves every [`CatchUpConfig::forkchoice_interval`] blocks.
+//!
+//! The driver switches to catch-up when the [`PayloadSource`] tip is more than
+//! [`CatchUpConfig::threshold`] blocks ahead of its unsafe head, see
+//! [`Driver::with_catch_up`](crate::driver::Driver::with_catch_up).
+
+use std::{ops::RangeInclusive, sync::Arc};
+
+use alloy_rpc_types_engine::ForkchoiceState;
+use async_trait::async_trait;
+use eyre::{bail, ensure, Result};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::RollupConfig,
+    engine::{EngineApi, EnginePayload, ForkchoiceUpdatedVersion, NewPayloadVersion},
+    protocol::L2BlockInfo,
+    tenant,
+};
+
+/// Settings of pipelined payload submission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CatchUpConfig {
+    /// Number of payloads prepared ahead of the one being inserted.
+    pub lookahead: usize,
+    /// Number of inserted payloads between two forkchoice updates.
+    pub forkchoice_interval: u64,
+    /// Number of blocks the tip must be ahead of the unsafe head for catch-up to start.
+    pub threshold: u64,
+    /// Maximum number of payloads inserted by a single catch-up run.
+    pub max_batch: u64,
+}
+
+impl Default for CatchUpConfig {
+    fn default() -> Self {
+        Self { lookahead: 4, forkchoice_interval: 16, threshold: 64, max_batch: 1024 }
+    }
+}
+
+impl CatchUpConfig {
+    /// Returns the blocks to insert on top of `head` to catch up with `tip`, or `None` if `head`
+    /// is within [`Self::threshold`] blocks of it.
+    pub fn range(&self, head: u64, tip: u64) -> Option<RangeInclusive<u64>> {
+        (tip.saturating_sub(head) > self.threshold)
+            .then(|| head + 1..=tip.min(head.saturating_add(self.max_batch.max(1))))
+    }
+}
+
+/// A source of payloads to insert, looked up by block number.
+#[async_trait]
+pub trait PayloadSource: std::fmt::Debug + Send + Sync {
+    /// Returns the number of the latest block the source has a payload of.
+    async fn tip(&self) -> Result<u64>;
+
+    /// Returns the payload of the given L2 block, or `None` if it is not available yet.
+    async fn payload_by_number(&self, number: u64) -> Result<Option<EnginePayload>>;
+}
+
+/// The result of a catch-up run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CatchUpOutcome {
+    /// The head after the final forkchoice update, or `None` if nothing was inserted.
+    pub head: Option<L2BlockInfo>,
+    /// Number of inserted payloads.
+    pub inserted: u64,
+    /// Number of forkchoice updates sent.
+    pub forkchoice_updates: u64,
+}
+
+/// Inserts consecutive payloads into the execution layer with pipelining and coalesced
+/// forkchoice updates.
+#[derive(Debug)]
+pub struct CatchUpSubmitter<E: ?Sized> {
+    engine: Arc<E>,
+    config: Arc<RollupConfig>,
+    settings: CatchUpConfig,
+}
+
+impl<E: EngineApi + ?Sized> CatchUpSubmitter<E> {
+    /// Creates a new submitter.
+    pub const fn new(engine: Arc<E>, config: Arc<RollupConfig>, settings: CatchUpConfig) -> Self {
+        Self { engine, config, settings }
+    }
+
+    /// Inserts the payloads of the blocks in `range` on top of `state`, stopping early at the
+    /// first block the source does not have yet.
+    ///
+    /// Inserted payloads are not derived from L1 yet, so only the head of `state` moves; the
+    /// safe and finalized heads are left to derivation. A payload or forkchoice update counts as
+    /// applied only once the execution layer reports it `VALID`. A forkchoice update is always
+    /// sent after the last inserted payload, even if the run stopped on an error.
+    pub async fn sync<S: PayloadSource + ?Sized + 'static>(
+        &self,
+        source: Arc<S>,
+        mut state: ForkchoiceState,
+        range: RangeInclusive<u64>,
+    ) -> Result<CatchUpOutcome> {
+        let (tx, mut rx) = mpsc::channel(self.settings.lookahead.max(1));
+        let producer = tenant::spawn(async move {
+            for number in range {
+                let next = match source.payload_by_number(number).await {
+                    Ok(Some(payload)) => Ok(payload),
+                    Ok(None) => break,
+                    Err(err) => Err(err),
+                };
+                let failed = next.is_err();
+                if tx.send(next).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        let mut outcome = CatchUpOutcome { head: None, inserted: 0, forkchoice_updates: 0 };
+        let mut pending = 0;
+        let result = async {
+            while let Some(payload) = rx.recv().await {
+                let payload = payload?;
+                let block = payload.l2_block_info()?;
+                ensure!(
+                    payload.parent_hash() == state.head_block_hash,
+                    "payload {} does not extend head {}",
+                    block.block_info,
+                    state.head_block_hash
+                );
+
+                let version = NewPayloadVersion::from_timestamp(&self.config, payload.timestamp());
+                let status = self.engine.new_payload(version, payload).await?;
+                if !status.status.is_valid() {
+                    bail!("engine did not accept payload {}: {}", block.block_info, status.status);
+                }
+
+                state.head_block_hash = block.block_info.hash;
+                outcome.head = Some(block);
+                outcome.inserted += 1;
+                pending += 1;
+
+                if pending >= self.settings.forkchoice_interval {
+                    // The update is not retried at the end of the run if it fails.
+                    pending = 0;
+                    self.update_forkchoice(state).await?;
+                    outcome.forkchoice_updates += 1;
+                }
+            }
+            Ok(())
+        }
+        .await;
+        producer.abort();
+
+        // Commit whatever was inserted, even if the run stopped on an error, without hiding that
+        // error behind a failure of the update.
+        if pending > 0 {
+            match self.update_forkchoice(state).await {
+                Ok(()) => outcome.forkchoice_updates += 1,
+                Err(err) if result.is_err() => {
+                    warn!(target: "hera::engine", %err, "Failed to commit partial catch-up batch");
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        result?;
+
+        info!(
+            target: "hera::engine",
+            head = ?outcome.head.map(|head| head.block_info),
+            inserted = outcome.inserted,
+            forkchoice_updates = outcome.forkchoice_updates,
+            "Catch-up batch inserted"
+        );
+        Ok(outcome)
+    }
+
+    async fn update_forkchoice(&self, state: ForkchoiceState) -> Result<()> {
+        let updated =
+            self.engine.forkchoice_updated(ForkchoiceUpdatedVersion::V3, state, None).await?;
+        let status = updated.payload_status.status;
+        if !status.is_valid() {
+            bail!("engine did not accept forkchoice update to {}: {status}", state.head_block_hash);
+        }
+        debug!(target: "hera::engine", head = %state.head_block_hash, "Forkchoice updated");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::B256;
+    use alloy_rpc_types_engine::PayloadStatusEnum;
+
+    use super::*;
+    use crate::engine::{
+        mock::{block_hash, EngineCall, MockEngine, MockSource},
+        EngineError,
+    };
+
+    fn submitter(
+        engine: &Arc<MockEngine>,
+        forkchoice_interval: u64,
+    ) -> CatchUpSubmitter<MockEngine> {
+        let settings = CatchUpConfig { forkchoice_interval, ..Default::default() };
+        let config = Arc::new(RollupConfig::from_registry(10).unwrap());
+        CatchUpSubmitter::new(engine.clone(), config, settings)
+    }
+
+    /// The forkchoice state on top of test block `head`, with the safe head at genesis.
+    fn state(head: u64) -> ForkchoiceState {
+        ForkchoiceState {
+            head_block_hash: block_hash(head),
+            safe_block_hash: block_hash(0),
+            finalized_block_hash: block_hash(0),
+        }
+    }
+
+    fn forkchoice_updates(engine: &MockEngine) -> Vec<ForkchoiceState> {
+        engine
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                EngineCall::ForkchoiceUpdated(state) => Some(state),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn starts_far_behind_the_tip_only() {
+        let settings = CatchUpConfig { threshold: 10, max_batch: 100, ..Default::default() };
+        assert_eq!(settings.range(50, 60), None);
+        assert_eq!(settings.range(50, 61), Some(51..=61));
+        assert_eq!(settings.range(50, 1_000), Some(51..=150));
+        assert_eq!(settings.range(1_000, 50), None);
+    }
+
+    #[tokio::test]
+    async fn inserts_range_moving_the_head_only() {
+        let engine = Arc::new(MockEngine::new());
+        let source = Arc::new(MockSource { tip: 100 });
+        let outcome = submitter(&engine, 2).sync(source, state(0), 1..=5).await.unwrap();
+
+        assert_eq!(outcome.inserted, 5);
+        assert_eq!(outcome.forkchoice_updates, 3);
+        let head = outcome.head.unwrap();
+        assert_eq!(head.block_info.hash, block_hash(5));
+        assert_eq!(head.l1_origin.number, 0);
+        assert_eq!(head.seq_num, 5);
+
+        let updates = forkchoice_updates(&engine);
+        let heads: Vec<_> = updates.iter().map(|state| state.head_block_hash).collect();
+        assert_eq!(heads, [block_hash(2), block_hash(4), block_hash(5)]);
+        assert!(updates.iter().all(|update| update.safe_block_hash == block_hash(0)));
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_end_of_the_source() {
+        let engine = Arc::new(MockEngine::new());
+        let source = Arc::new(MockSource { tip: 3 });
+        let outcome = submitter(&engine, 16).sync(source, state(0), 1..=10).await.unwrap();
+        assert_eq!(outcome.inserted, 3);
+        assert_eq!(forkchoice_updates(&engine).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rejects_payload_not_extending_the_head() {
+        let engine = Arc::new(MockEngine::new());
+        let source = Arc::new(MockSource { tip: 10 });
+        let mut state = state(4);
+        state.head_block_hash = B256::repeat_byte(0xaa);
+        let err = submitter(&engine, 16).sync(source, state, 5..=10).await.unwrap_err();
+        assert!(err.to_string().contains("does not extend head"), "{err}");
+        assert!(engine.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn syncing_and_accepted_are_not_success() {
+        for status in [PayloadStatusEnum::Syncing, PayloadStatusEnum::Accepted] {
+            let engine = Arc::new(MockEngine::new());
+            engine.respond(Ok(PayloadStatusEnum::Valid));
+            engine.respond(Ok(status));
+            let source = Arc::new(MockSource { tip: 10 });
+            let err = submitter(&engine, 16).sync(source, state(0), 1..=5).await.unwrap_err();
+            assert!(err.to_string().contains("did not accept payload"), "{err}");
+
+            // Only the first payload is committed.
+            let updates = forkchoice_updates(&engine);
+            assert_eq!(updates.len(), 1);
+            assert_eq!(updates[0].head_block_hash, block_hash(1));
+        }
+
+        let engine = Arc::new(MockEngine::new());
+        engine.respond(Ok(PayloadStatusEnum::Valid));
+        engine.respond(Ok(PayloadStatusEnum::Syncing));
+        let source = Arc::new(MockSource { tip: 10 });
+        let err = submitter(&engine, 1).sync(source, state(0), 1..=5).await.unwrap_err();
+        assert!(err.to_string().contains("did not accept forkchoice update"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn failed_forkchoice_update_is_not_retried() {
+        let engine = Arc::new(MockEngine::new());
+        engine.respond(Ok(PayloadStatusEnum::Valid));
+        engine.respond(Ok(PayloadStatusEnum::Valid));
+        engine.respond(Err(EngineError::Transport(eyre::eyre!("connection reset"))));
+        let source = Arc::new(MockSource { tip: 10 });
+        let err = submitter(&engine, 2).sync(source, state(0), 1..=5).await.unwrap_err();
+        assert!(err.to_string().contains("connection reset"), "{err}");
+        assert_eq!(forkchoice_updates(&engine).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn final_forkchoice_failure_keeps_the_original_error() {
+        let engine = Arc::new(MockEngine::new());
+        engine.respond(Ok(PayloadStatusEnum::Valid));
+        engine
+            .respond(Ok(PayloadStatusEnum::Invalid { validation_error: "bad state root".into() }));
+        engine.respond(Err(EngineError::Transport(eyre::eyre!("connection reset"))));
+        let source = Arc::new(MockSource { tip: 10 });
+        let err = submitter(&engine, 16).sync(source, state(0), 1..=5).await.unwrap_err();
+        assert!(err.to_string().contains("bad state root"), "{err}");
+        assert_eq!(forkchoice_updates(&engine).len(), 1);
+    }
+}
@@ -0,0 +1,371 @@
+//! An [`EngineApi`] client of the L2 execution layer's authenticated RPC.
+//!
+//! The Engine API is served on a separate port, authenticated with JWTs signed with a secret
+//! shared between the rollup node and the execution layer. Tokens are only accepted within a
+//! minute of the time they were issued at, so the client issues a new one every
+//! [`JWT_REFRESH_INTERVAL`].
+//!
+//! Payload statuses are normalized into the spec, working around the
+//! [quirks](crate::engine::quirks) of the execution layer, identified with
+//! [`EngineClient::identify`].
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use alloy_primitives::{hex, B256};
+use alloy_rpc_types_engine::{
+    ExecutionPayload, ForkchoiceState, ForkchoiceUpdated, OptimismPayloadAttributes, PayloadId,
+    PayloadStatus,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use eyre::{eyre, Result, WrapErr};
+use hmac::{Hmac, Mac};
+use jsonrpsee::{
+    core::{client::ClientT, params::ArrayParams},
+    http_client::HttpClient,
+    rpc_params,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use sha2::Sha256;
+
+use crate::{
+    endpoint::Endpoint,
+    engine::{
+        quirks::{RawForkchoiceUpdated, RawPayloadStatus},
+        ClientVersion, EngineApi, EngineError, EnginePayload, EngineResult, ExecutionLayer,
+        ForkchoiceUpdatedVersion, GetPayloadVersion, NewPayloadVersion, Quirk, QuirkTracker,
+    },
+    version::{GIT_SHA, SHORT_VERSION},
+};
+
+/// The interval after which a new JWT is issued.
+pub const JWT_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The files execution layers write their JWT secret to on first start, relative to their data
+/// directory: reth's `jwt.hex` and geth's `geth/jwtsecret`.
+pub const JWT_SECRET_FILES: [&str; 2] = ["jwt.hex", "geth/jwtsecret"];
+
+/// The Engine API error code of a payload that was never built.
+const UNKNOWN_PAYLOAD_CODE: i32 = -38001;
+
+/// The 32-byte secret shared with the execution layer to authenticate Engine API requests.
+#[derive(Clone, PartialEq, Eq)]
+pub struct JwtSecret([u8; 32]);
+
+impl fmt::Debug for JwtSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("JwtSecret(..)")
+    }
+}
+
+impl JwtSecret {
+    /// Parses a hex encoded secret, with or without `0x` prefix.
+    pub fn from_hex(secret: &str) -> Result<Self> {
+        let bytes = hex::decode(secret.trim()).wrap_err("JWT secret is not hex encoded")?;
+        let len = bytes.len();
+        let secret = bytes.try_into().map_err(|_| eyre!("JWT secret has {len} bytes, not 32"))?;
+        Ok(Self(secret))
+    }
+
+    /// Reads the hex encoded secret at `path`, as written by execution layers on first start.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let secret = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read JWT secret {}", path.display()))?;
+        Self::from_hex(&secret).wrap_err_with(|| format!("invalid JWT secret {}", path.display()))
+    }
+
+    /// Reads the first secret found in the data directories `datadirs`, in order, returning it
+    /// with its path. Fails if none of them holds one of the [`JWT_SECRET_FILES`].
+    pub fn discover(datadirs: &[PathBuf]) -> Result<(Self, PathBuf)> {
+        let path = datadirs
+            .iter()
+            .flat_map(|datadir| JWT_SECRET_FILES.iter().map(move |file| datadir.join(file)))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                let searched: Vec<_> = datadirs.iter().map(|dir| dir.display()).collect();
+                eyre!("no JWT secret found in {searched:?}")
+            })?;
+        Ok((Self::from_file(&path)?, path))
+    }
+
+    /// Returns an HS256 token issued at `iat`, in seconds since the Unix epoch.
+    pub fn token(&self, iat: u64) -> String {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(format!(r#"{{"iat":{iat}}}"#));
+        let message = format!("{header}.{claims}");
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.0).expect("HMAC accepts any key size");
+        mac.update(message.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        format!("{message}.{signature}")
+    }
+}
+
+/// Calls the Engine API of the L2 execution layer over HTTP.
+#[derive(Debug)]
+pub struct EngineClient {
+    endpoint: Endpoint,
+    secret: JwtSecret,
+    /// The client carrying the current token, with the time it was issued at.
+    client: Mutex<(HttpClient, u64)>,
+    quirks: QuirkTracker,
+}
+
+impl EngineClient {
+    /// Creates a client of the Engine API at `url`, authenticated with `secret`.
+    pub fn new(url: &str, secret: JwtSecret) -> Result<Self> {
+        Self::from_endpoint(Endpoint::parse(url)?, secret)
+    }
+
+    /// Creates a client of the Engine API at `endpoint`, authenticated with `secret`.
+    pub fn from_endpoint(endpoint: Endpoint, secret: JwtSecret) -> Result<Self> {
+        let iat = unix_time();
+        let client = Self::build(&endpoint, &secret, iat)?;
+        Ok(Self { endpoint, secret, client: Mutex::new((client, iat)), quirks: Default::default() })
+    }
+
+    /// Identifies the execution layer through `engine_getClientVersionV1`, labeling the quirks
+    /// of its responses with it.
+    pub async fn identify(&self) -> EngineResult<ExecutionLayer> {
+        let hera = ClientVersion {
+            code: "HR".to_string(),
+            name: "hera".to_string(),
+            version: SHORT_VERSION.to_string(),
+            commit: format!("0x{}", GIT_SHA.get(..8).unwrap_or(GIT_SHA)),
+        };
+        let versions: Vec<ClientVersion> =
+            self.request("engine_getClientVersionV1", rpc_params![hera]).await?;
+        let execution_layer = versions
+            .first()
+            .map_or(ExecutionLayer::Unknown, |v| ExecutionLayer::from_code(&v.code));
+        self.quirks.set_execution_layer(execution_layer.clone());
+        Ok(execution_layer)
+    }
+
+    /// Returns the quirks of the execution layer seen so far.
+    pub const fn quirks(&self) -> &QuirkTracker {
+        &self.quirks
+    }
+
+    fn build(endpoint: &Endpoint, secret: &JwtSecret, iat: u64) -> Result<HttpClient> {
+        endpoint
+            .clone()
+            .with_bearer_auth(&secret.token(iat))?
+            .rpc_client()
+            .wrap_err_with(|| format!("invalid engine URL {endpoint}"))
+    }
+
+    /// Returns the client, with a new token if the current one is getting old.
+    fn client(&self) -> EngineResult<HttpClient> {
+        let mut client = self.client.lock().unwrap();
+        let now = unix_time();
+        if now.saturating_sub(client.1) >= JWT_REFRESH_INTERVAL.as_secs() {
+            *client = (
+                Self::build(&self.endpoint, &self.secret, now).map_err(EngineError::Transport)?,
+                now,
+            );
+        }
+        Ok(client.0.clone())
+    }
+
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: &'static str,
+        params: ArrayParams,
+    ) -> EngineResult<T> {
+        Ok(self.client()?.request(method, params).await?)
+    }
+
+    /// Records the quirks of a normalized response to `method`.
+    fn normalized<T>(
+        &self,
+        method: &'static str,
+        normalized: Result<(T, Vec<Quirk>), String>,
+    ) -> EngineResult<T> {
+        let (response, quirks) = normalized.map_err(|err| {
+            EngineError::Transport(eyre!(err).wrap_err(format!("invalid {method} response")))
+        })?;
+        self.quirks.record(method, &quirks);
+        Ok(response)
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs())
+}
+
+/// The envelope of a built payload, returned by every `engine_getPayload` version.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PayloadEnvelope {
+    execution_payload: serde_json::Value,
+    #[serde(default)]
+    parent_beacon_block_root: Option<B256>,
+}
+
+impl TryFrom<PayloadEnvelope> for EnginePayload {
+    type Error = serde_json::Error;
+
+    fn try_from(envelope: PayloadEnvelope) -> Result<Self, Self::Error> {
+        // Isthmus payloads carry the withdrawals root the upstream types do not know about.
+        let withdrawals_root =
+            envelope.execution_payload.get("withdrawalsRoot").map(B256::deserialize).transpose()?;
+        Ok(Self {
+            payload: ExecutionPayload::deserialize(envelope.execution_payload)?,
+            parent_beacon_block_root: envelope.parent_beacon_block_root,
+            withdrawals_root,
+        })
+    }
+}
+
+/// Serializes the payload as expected by `engine_newPayload` of the given version.
+fn payload_json(payload: &EnginePayload, version: NewPayloadVersion) -> serde_json::Value {
+    let mut json = serde_json::to_value(&payload.payload).expect("payloads serialize");
+    if let (NewPayloadVersion::V4, Some(root)) = (version, payload.withdrawals_root) {
+        json["withdrawalsRoot"] = serde_json::to_value(root).expect("hashes serialize");
+    }
+    json
+}
+
+#[async_trait]
+impl EngineApi for EngineClient {
+    async fn new_payload(
+        &self,
+        version: NewPayloadVersion,
+        payload: EnginePayload,
+    ) -> EngineResult<PayloadStatus> {
+        let block = payload.block_id().hash;
+        let json = payload_json(&payload, version);
+        // OP Stack blocks carry no blobs, and no execution layer requests.
+        let params = match version {
+            NewPayloadVersion::V2 => rpc_params![json],
+            NewPayloadVersion::V3 => {
+                rpc_params![json, Vec::<B256>::new(), payload.parent_beacon_block_root]
+            }
+            NewPayloadVersion::V4 => rpc_params![
+                json,
+                Vec::<B256>::new(),
+                payload.parent_beacon_block_root,
+                Vec::<String>::new()
+            ],
+        };
+        let status: RawPayloadStatus = self.request(version.method(), params).await?;
+        self.normalized(version.method(), status.normalize(block))
+    }
+
+    async fn forkchoice_updated(
+        &self,
+        version: ForkchoiceUpdatedVersion,
+        state: ForkchoiceState,
+        attributes: Option<OptimismPayloadAttributes>,
+    ) -> EngineResult<ForkchoiceUpdated> {
+        let updated: RawForkchoiceUpdated =
+            self.request(version.method(), rpc_params![state, attributes]).await?;
+        self.normalized(version.method(), updated.normalize(state.head_block_hash))
+    }
+
+    async fn get_payload(
+        &self,
+        version: GetPayloadVersion,
+        payload_id: PayloadId,
+    ) -> EngineResult<EnginePayload> {
+        let envelope: PayloadEnvelope =
+            match self.request(version.method(), rpc_params![payload_id]).await {
+                Err(EngineError::Rpc { code: UNKNOWN_PAYLOAD_CODE, .. }) => {
+                    return Err(EngineError::UnknownPayload(payload_id))
+                }
+                result => result?,
+            };
+        envelope.try_into().map_err(|err: serde_json::Error| {
+            EngineError::Transport(eyre!(err).wrap_err("invalid payload envelope"))
+        })
+    }
+
+    async fn exchange_capabilities(&self, methods: Vec<String>) -> EngineResult<Vec<String>> {
+        self.request("engine_exchangeCapabilities", rpc_params![methods]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_tokens() {
+        let secret = JwtSecret::from_hex(&format!("0x{}", "11".repeat(32))).unwrap();
+        assert_eq!(
+            secret.token(1_700_000_000),
+            "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.eyJpYXQiOjE3MDAwMDAwMDB9.\
+             Hb09lNHg9IiC1UttHzXRW1voUqjM97m4oEuKWkSux5g"
+        );
+        assert!(JwtSecret::from_hex(&"11".repeat(31)).is_err());
+        assert!(JwtSecret::from_hex("not hex").is_err());
+    }
+
+    #[test]
+    fn discovers_secrets_in_datadirs() {
+        let dir = std::env::temp_dir().join(format!("hera-jwt-{}", std::process::id()));
+        let (reth, geth) = (dir.join("reth"), dir.join("geth"));
+        std::fs::create_dir_all(reth.join("geth")).unwrap();
+        std::fs::create_dir_all(geth.join("geth")).unwrap();
+        std::fs::write(geth.join("geth/jwtsecret"), "22".repeat(32)).unwrap();
+
+        let datadirs = [dir.join("missing"), reth.clone(), geth.clone()];
+        let (secret, path) = JwtSecret::discover(&datadirs).unwrap();
+        assert_eq!(path, geth.join("geth/jwtsecret"));
+        assert_eq!(secret.0, [0x22; 32]);
+
+        // reth's secret takes precedence within a directory, and earlier directories first.
+        std::fs::write(reth.join("jwt.hex"), format!("0x{}", "11".repeat(32))).unwrap();
+        let (secret, path) = JwtSecret::discover(&datadirs).unwrap();
+        assert_eq!(path, reth.join("jwt.hex"));
+        assert_eq!(secret.0, [0x11; 32]);
+
+        assert!(JwtSecret::discover(&[dir.join("missing")]).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn decodes_isthmus_payload_envelopes() {
+        let envelope: PayloadEnvelope = serde_json::from_value(serde_json::json!({
+            "executionPayload": {
+                "parentHash": B256::repeat_byte(1),
+                "feeRecipient": "0x4200000000000000000000000000000000000011",
+                "stateRoot": B256::repeat_byte(2),
+                "receiptsRoot": B256::repeat_byte(3),
+                "logsBloom": format!("0x{}", "00".repeat(256)),
+                "prevRandao": B256::repeat_byte(4),
+                "blockNumber": "0x10",
+                "gasLimit": "0x1c9c380",
+                "gasUsed": "0x0",
+                "timestamp": "0x6553f100",
+                "extraData": "0x",
+                "baseFeePerGas": "0x7",
+                "blockHash": B256::repeat_byte(5),
+                "transactions": [],
+                "withdrawals": [],
+                "blobGasUsed": "0x0",
+                "excessBlobGas": "0x0",
+                "withdrawalsRoot": B256::repeat_byte(6),
+            },
+            "blockValue": "0x0",
+            "blobsBundle": { "commitments": [], "proofs": [], "blobs": [] },
+            "shouldOverrideBuilder": false,
+            "parentBeaconBlockRoot": B256::repeat_byte(7),
+            "executionRequests": [],
+        }))
+        .unwrap();
+        let payload = EnginePayload::try_from(envelope).unwrap();
+        assert_eq!(payload.block_id().hash, B256::repeat_byte(5));
+        assert_eq!(payload.withdrawals_root, Some(B256::repeat_byte(6)));
+        assert_eq!(payload.parent_beacon_block_root, Some(B256::repeat_byte(7)));
+
+        let json = payload_json(&payload, NewPayloadVersion::V4);
+        assert_eq!(json["withdrawalsRoot"], serde_json::json!(B256::repeat_byte(6)));
+        assert!(payload_json(&payload, NewPayloadVersion::V3).get("withdrawalsRoot").is_none());
+    }
+}
@@ -0,0 +1,172 @@
+//! An engine wrapper that never changes the execution layer's forkchoice.
+//!
+//! In dry-run mode the pipeline and validation run as usual, but payloads and forkchoice updates
+//! are recorded instead of sent, so Hera can shadow a production node without side effects.
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use alloy_rpc_types_engine::{
+    ForkchoiceState, ForkchoiceUpdated, OptimismPayloadAttributes, PayloadId, PayloadStatus,
+    PayloadStatusEnum,
+};
+use async_trait::async_trait;
+use tracing::debug;
+
+use crate::engine::{
+    EngineApi, EngineError, EnginePayload, EngineResult, ForkchoiceUpdatedVersion,
+    GetPayloadVersion, NewPayloadVersion,
+};
+
+/// What a dry run would have done.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DryRunSummary {
+    /// Number of payloads that were not inserted.
+    pub payloads_suppressed: u64,
+    /// Number of forkchoice updates that were not sent.
+    pub forkchoice_updates_suppressed: u64,
+    /// Number of payload builds that were not started.
+    pub payload_builds_suppressed: u64,
+    /// The last forkchoice state that would have been sent.
+    pub last_forkchoice: Option<ForkchoiceState>,
+}
+
+impl fmt::Display for DryRunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} payloads, {} forkchoice updates and {} payload builds suppressed",
+            self.payloads_suppressed,
+            self.forkchoice_updates_suppressed,
+            self.payload_builds_suppressed
+        )?;
+        if let Some(state) = &self.last_forkchoice {
+            write!(
+                f,
+                ", would have set head {} safe {} finalized {}",
+                state.head_block_hash, state.safe_block_hash, state.finalized_block_hash
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// An [`EngineApi`] wrapper that records payloads and forkchoice updates instead of sending them.
+///
+/// Only `engine_exchangeCapabilities`, which changes nothing, reaches the inner engine.
+#[derive(Debug)]
+pub struct DryRunEngine<E> {
+    inner: E,
+    summary: Arc<Mutex<DryRunSummary>>,
+}
+
+impl<E> DryRunEngine<E> {
+    /// Wraps the given engine.
+    pub fn new(inner: E) -> Self {
+        Self { inner, summary: Arc::default() }
+    }
+
+    /// Returns a snapshot of what the dry run would have done so far.
+    pub fn summary(&self) -> DryRunSummary {
+        self.summary.lock().unwrap().clone()
+    }
+
+    /// Returns a shared handle to the summary, which stays valid after the engine is moved.
+    pub fn summary_handle(&self) -> Arc<Mutex<DryRunSummary>> {
+        self.summary.clone()
+    }
+}
+
+#[async_trait]
+impl<E: EngineApi> EngineApi for DryRunEngine<E> {
+    async fn new_payload(
+        &self,
+        _version: NewPayloadVersion,
+        payload: EnginePayload,
+    ) -> EngineResult<PayloadStatus> {
+        let block = payload.block_id();
+        debug!(target: "hera::engine", %block, "Dry run: suppressed payload");
+        self.summary.lock().unwrap().payloads_suppressed += 1;
+        Ok(PayloadStatus::new(PayloadStatusEnum::Valid, Some(block.hash)))
+    }
+
+    async fn forkchoice_updated(
+        &self,
+        _version: ForkchoiceUpdatedVersion,
+        state: ForkchoiceState,
+        attributes: Option<OptimismPayloadAttributes>,
+    ) -> EngineResult<ForkchoiceUpdated> {
+        debug!(
+            target: "hera::engine",
+            head = %state.head_block_hash,
+            safe = %state.safe_block_hash,
+            build = attributes.is_some(),
+            "Dry run: suppressed forkchoice update"
+        );
+        let mut summary = self.summary.lock().unwrap();
+        summary.forkchoice_updates_suppressed += 1;
+        summary.payload_builds_suppressed += u64::from(attributes.is_some());
+        summary.last_forkchoice = Some(state);
+
+        Ok(ForkchoiceUpdated::from_status(PayloadStatusEnum::Valid)
+            .with_latest_valid_hash(state.head_block_hash))
+    }
+
+    async fn get_payload(
+        &self,
+        _version: GetPayloadVersion,
+        payload_id: PayloadId,
+    ) -> EngineResult<EnginePayload> {
+        Err(EngineError::UnknownPayload(payload_id))
+    }
+
+    async fn exchange_capabilities(&self, methods: Vec<String>) -> EngineResult<Vec<String>> {
+        self.inner.exchange_capabilities(methods).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_rpc_types_engine::PayloadAttributes;
+
+    use super::*;
+    use crate::engine::mock::{block_hash, payload, MockEngine};
+
+    #[tokio::test]
+    async fn never_reaches_the_inner_engine() {
+        let inner = Arc::new(MockEngine::new());
+        let engine = DryRunEngine::new(inner.clone());
+
+        let status = engine.new_payload(NewPayloadVersion::V2, payload(1)).await.unwrap();
+        assert!(status.status.is_valid());
+        assert_eq!(status.latest_valid_hash, Some(block_hash(1)));
+
+        let state = ForkchoiceState { head_block_hash: block_hash(1), ..Default::default() };
+        let attributes = OptimismPayloadAttributes {
+            payload_attributes: PayloadAttributes {
+                timestamp: 2,
+                prev_randao: Default::default(),
+                suggested_fee_recipient: Default::default(),
+                withdrawals: None,
+                parent_beacon_block_root: None,
+            },
+            transactions: None,
+            no_tx_pool: None,
+            gas_limit: None,
+        };
+        engine
+            .forkchoice_updated(ForkchoiceUpdatedVersion::V3, state, Some(attributes))
+            .await
+            .unwrap();
+        assert!(engine.get_payload(GetPayloadVersion::V3, PayloadId::new([1; 8])).await.is_err());
+
+        assert!(inner.calls().is_empty());
+        let summary = engine.summary();
+        assert_eq!(summary.payloads_suppressed, 1);
+        assert_eq!(summary.forkchoice_updates_suppressed, 1);
+        assert_eq!(summary.payload_builds_suppressed, 1);
+        assert_eq!(summary.last_forkchoice, Some(state));
+    }
+}
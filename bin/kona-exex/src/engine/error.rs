@@ -0,0 +1,65 @@
+//! Errors of [`EngineApi`](crate::engine::EngineApi) calls.
+
+use std::fmt;
+
+use alloy_rpc_types_engine::PayloadId;
+use eyre::Report;
+use jsonrpsee::core::ClientError;
+
+/// The result of an [`EngineApi`](crate::engine::EngineApi) call.
+pub type EngineResult<T> = Result<T, EngineError>;
+
+/// The JSON-RPC error code of an internal error of the execution layer.
+pub const INTERNAL_ERROR_CODE: i32 = -32603;
+
+/// An error calling the Engine API.
+#[derive(Debug)]
+pub enum EngineError {
+    /// The execution layer could not be reached, or failed to answer.
+    Transport(Report),
+    /// The execution layer answered with a JSON-RPC error, such as `-38002` for an invalid
+    /// forkchoice state or `-38005` for an unsupported fork.
+    Rpc {
+        /// The error code.
+        code: i32,
+        /// The error message.
+        message: String,
+    },
+    /// The payload was never built by the execution layer.
+    UnknownPayload(PayloadId),
+}
+
+impl EngineError {
+    /// Returns true if the call may succeed when retried: transport failures and internal
+    /// errors of the execution layer are usually transient, while rejected requests are not.
+    pub const fn is_recoverable(&self) -> bool {
+        match self {
+            Self::Transport(_) => true,
+            Self::Rpc { code, .. } => *code == INTERNAL_ERROR_CODE,
+            Self::UnknownPayload(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport(err) => write!(f, "engine request failed: {err:#}"),
+            Self::Rpc { code, message } => write!(f, "engine returned error {code}: {message}"),
+            Self::UnknownPayload(id) => write!(f, "payload {id} was never built"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<ClientError> for EngineError {
+    fn from(err: ClientError) -> Self {
+        match err {
+            ClientError::Call(err) => {
+                Self::Rpc { code: err.code(), message: err.message().to_string() }
+            }
+            err => Self::Transport(err.into()),
+        }
+    }
+}
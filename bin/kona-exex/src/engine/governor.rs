@@ -0,0 +1,251 @@
+//! Adaptive throttling of derivation based on the health of the execution layer.
+//!
+//! Derivation can produce payloads much faster than the execution layer imports them. When the
+//! engine reports `SYNCING` or its response latency spikes, the [`DerivationGovernor`] delays the
+//! next payload instead of letting a queue of payloads build up against an overloaded engine.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use alloy_rpc_types_engine::{
+    ForkchoiceState, ForkchoiceUpdated, OptimismPayloadAttributes, PayloadId, PayloadStatus,
+    PayloadStatusEnum,
+};
+use async_trait::async_trait;
+use metrics::{counter, gauge, histogram};
+use tokio::time::Instant;
+use tracing::debug;
+
+use crate::engine::{
+    EngineApi, EnginePayload, EngineResult, ForkchoiceUpdatedVersion, GetPayloadVersion,
+    NewPayloadVersion,
+};
+
+/// Weight of the latest sample in the latency moving average.
+const LATENCY_EWMA_ALPHA: f64 = 0.2;
+
+/// Settings of the [`DerivationGovernor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GovernorConfig {
+    /// Average Engine API latency above which derivation is slowed down.
+    pub latency_threshold: Duration,
+    /// Delay applied after the first `SYNCING` response, doubled on each consecutive one.
+    pub syncing_backoff: Duration,
+    /// Upper bound of the delay applied before a payload.
+    pub max_delay: Duration,
+}
+
+impl Default for GovernorConfig {
+    fn default() -> Self {
+        Self {
+            latency_threshold: Duration::from_millis(500),
+            syncing_backoff: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct GovernorState {
+    /// Exponentially weighted moving average of the Engine API latency, in seconds.
+    latency_ewma: f64,
+    /// Number of consecutive `SYNCING` responses.
+    syncing_streak: u32,
+}
+
+/// Computes the delay to apply before submitting the next payload from the observed engine
+/// latency and status.
+#[derive(Debug)]
+pub struct DerivationGovernor {
+    config: GovernorConfig,
+    state: Mutex<GovernorState>,
+}
+
+impl DerivationGovernor {
+    /// Creates a new governor.
+    pub fn new(config: GovernorConfig) -> Self {
+        Self { config, state: Mutex::default() }
+    }
+
+    /// Records the latency of an Engine API call and the payload status it returned, if any.
+    pub fn observe(&self, latency: Duration, status: Option<&PayloadStatusEnum>) {
+        histogram!("hera_engine_request_duration_seconds").record(latency.as_secs_f64());
+
+        let mut state = self.state.lock().unwrap();
+        state.latency_ewma = if state.latency_ewma == 0.0 {
+            latency.as_secs_f64()
+        } else {
+            LATENCY_EWMA_ALPHA
+                .mul_add(latency.as_secs_f64(), (1.0 - LATENCY_EWMA_ALPHA) * state.latency_ewma)
+        };
+        match status {
+            Some(PayloadStatusEnum::Syncing) => {
+                state.syncing_streak = state.syncing_streak.saturating_add(1);
+                counter!("hera_engine_syncing_responses_total").increment(1);
+            }
+            Some(_) => state.syncing_streak = 0,
+            None => {}
+        }
+    }
+
+    /// Returns the delay to apply before the next payload.
+    pub fn delay(&self) -> Duration {
+        let state = self.state.lock().unwrap();
+        let syncing = if state.syncing_streak == 0 {
+            Duration::ZERO
+        } else {
+            self.config.syncing_backoff.saturating_mul(1 << (state.syncing_streak - 1).min(16))
+        };
+
+        // Above the threshold, wait as long as the excess latency to let the engine drain.
+        let threshold = self.config.latency_threshold.as_secs_f64();
+        let latency = if state.latency_ewma > threshold {
+            Duration::from_secs_f64(state.latency_ewma - threshold)
+        } else {
+            Duration::ZERO
+        };
+
+        syncing.max(latency).min(self.config.max_delay)
+    }
+
+    /// Waits for the current delay, if any.
+    pub async fn throttle(&self) {
+        let delay = self.delay();
+        gauge!("hera_derivation_throttle_delay_seconds").set(delay.as_secs_f64());
+        if !delay.is_zero() {
+            debug!(target: "hera::engine", ?delay, "Throttling derivation");
+            counter!("hera_derivation_throttled_total").increment(1);
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// An [`EngineApi`] wrapper that feeds every call into a [`DerivationGovernor`] and throttles
+/// calls that submit new work to the execution layer.
+#[derive(Debug)]
+pub struct GovernedEngine<E> {
+    inner: E,
+    governor: Arc<DerivationGovernor>,
+}
+
+impl<E> GovernedEngine<E> {
+    /// Wraps the given engine.
+    pub const fn new(inner: E, governor: Arc<DerivationGovernor>) -> Self {
+        Self { inner, governor }
+    }
+
+    /// Returns the governor.
+    pub const fn governor(&self) -> &Arc<DerivationGovernor> {
+        &self.governor
+    }
+}
+
+#[async_trait]
+impl<E: EngineApi> EngineApi for GovernedEngine<E> {
+    async fn new_payload(
+        &self,
+        version: NewPayloadVersion,
+        payload: EnginePayload,
+    ) -> EngineResult<PayloadStatus> {
+        self.governor.throttle().await;
+        let start = Instant::now();
+        let status = self.inner.new_payload(version, payload).await?;
+        self.governor.observe(start.elapsed(), Some(&status.status));
+        Ok(status)
+    }
+
+    async fn forkchoice_updated(
+        &self,
+        version: ForkchoiceUpdatedVersion,
+        state: ForkchoiceState,
+        attributes: Option<OptimismPayloadAttributes>,
+    ) -> EngineResult<ForkchoiceUpdated> {
+        if attributes.is_some() {
+            self.governor.throttle().await;
+        }
+        let start = Instant::now();
+        let updated = self.inner.forkchoice_updated(version, state, attributes).await?;
+        self.governor.observe(start.elapsed(), Some(&updated.payload_status.status));
+        Ok(updated)
+    }
+
+    async fn get_payload(
+        &self,
+        version: GetPayloadVersion,
+        payload_id: PayloadId,
+    ) -> EngineResult<EnginePayload> {
+        let start = Instant::now();
+        let payload = self.inner.get_payload(version, payload_id).await?;
+        self.governor.observe(start.elapsed(), None);
+        Ok(payload)
+    }
+
+    async fn exchange_capabilities(&self, methods: Vec<String>) -> EngineResult<Vec<String>> {
+        self.inner.exchange_capabilities(methods).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::mock::{payload, MockEngine};
+
+    fn governed(engine: MockEngine) -> GovernedEngine<MockEngine> {
+        GovernedEngine::new(engine, Arc::new(DerivationGovernor::new(GovernorConfig::default())))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn syncing_responses_back_off_exponentially() {
+        let engine = MockEngine::new();
+        for _ in 0..3 {
+            engine.respond(Ok(PayloadStatusEnum::Syncing));
+        }
+        let engine = governed(engine);
+
+        let mut delays = Vec::new();
+        for number in 1..=4 {
+            engine.new_payload(NewPayloadVersion::V2, payload(number)).await.unwrap();
+            delays.push(engine.governor().delay());
+        }
+        assert_eq!(
+            delays,
+            [
+                Duration::from_millis(100),
+                Duration::from_millis(200),
+                Duration::from_millis(400),
+                Duration::ZERO
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_engine_delays_the_next_payload() {
+        let engine = governed(MockEngine::new().with_latency(Duration::from_millis(1_500)));
+        engine.new_payload(NewPayloadVersion::V2, payload(1)).await.unwrap();
+        assert_eq!(engine.governor().delay(), Duration::from_secs(1));
+
+        // The next payload waits for the excess latency before being sent.
+        let start = Instant::now();
+        engine.new_payload(NewPayloadVersion::V2, payload(2)).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(2_500));
+
+        // Forkchoice updates without attributes are not throttled.
+        let start = Instant::now();
+        engine
+            .forkchoice_updated(ForkchoiceUpdatedVersion::V3, ForkchoiceState::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(1_500));
+    }
+
+    #[test]
+    fn delay_is_capped() {
+        let governor = DerivationGovernor::new(GovernorConfig::default());
+        for _ in 0..40 {
+            governor.observe(Duration::from_secs(60), Some(&PayloadStatusEnum::Syncing));
+        }
+        assert_eq!(governor.delay(), GovernorConfig::default().max_delay);
+    }
+}
@@ -0,0 +1,176 @@
+//! A scripted [`EngineApi`] for tests.
+
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_rpc_types_engine::{
+    ExecutionPayload, ExecutionPayloadV1, ForkchoiceState, ForkchoiceUpdated,
+    OptimismPayloadAttributes, PayloadId, PayloadStatus, PayloadStatusEnum,
+};
+use async_trait::async_trait;
+
+use crate::{
+    engine::{
+        EngineApi, EngineError, EnginePayload, EngineResult, ForkchoiceUpdatedVersion,
+        GetPayloadVersion, NewPayloadVersion, PayloadSource,
+    },
+    protocol::{l1_info::L1BlockInfoBedrock, BlockId, L1BlockInfoTx},
+};
+
+/// A call received by the [`MockEngine`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum EngineCall {
+    NewPayload(BlockId),
+    ForkchoiceUpdated(ForkchoiceState),
+    GetPayload(PayloadId),
+}
+
+/// An engine answering with scripted statuses, `VALID` once the script runs out, and recording
+/// the calls it receives.
+///
+/// Payload builds are started for forkchoice updates with attributes while payloads are queued
+/// with [`MockEngine::serve_payload`], and sealed into the queued payloads in order.
+#[derive(Debug, Default)]
+pub(crate) struct MockEngine {
+    responses: Mutex<VecDeque<EngineResult<PayloadStatusEnum>>>,
+    calls: Mutex<Vec<EngineCall>>,
+    payloads: Mutex<VecDeque<EnginePayload>>,
+    attributes: Mutex<Vec<OptimismPayloadAttributes>>,
+    latency: Duration,
+}
+
+impl MockEngine {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers every call after `latency`.
+    pub(crate) const fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Queues the answer of the next `engine_newPayload` or `engine_forkchoiceUpdated` call.
+    pub(crate) fn respond(&self, response: EngineResult<PayloadStatusEnum>) {
+        self.responses.lock().unwrap().push_back(response);
+    }
+
+    /// Queues the payload sealed by the next payload build.
+    pub(crate) fn serve_payload(&self, payload: EnginePayload) {
+        self.payloads.lock().unwrap().push_back(payload);
+    }
+
+    /// Returns the calls received so far.
+    pub(crate) fn calls(&self) -> Vec<EngineCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Returns the attributes of the payload builds requested so far.
+    pub(crate) fn attributes(&self) -> Vec<OptimismPayloadAttributes> {
+        self.attributes.lock().unwrap().clone()
+    }
+
+    async fn answer(&self, call: EngineCall) -> EngineResult<PayloadStatus> {
+        self.calls.lock().unwrap().push(call);
+        tokio::time::sleep(self.latency).await;
+        let response = self.responses.lock().unwrap().pop_front();
+        response.unwrap_or(Ok(PayloadStatusEnum::Valid)).map(PayloadStatus::from_status)
+    }
+}
+
+#[async_trait]
+impl EngineApi for MockEngine {
+    async fn new_payload(
+        &self,
+        _version: NewPayloadVersion,
+        payload: EnginePayload,
+    ) -> EngineResult<PayloadStatus> {
+        self.answer(EngineCall::NewPayload(payload.block_id())).await
+    }
+
+    async fn forkchoice_updated(
+        &self,
+        _version: ForkchoiceUpdatedVersion,
+        state: ForkchoiceState,
+        attributes: Option<OptimismPayloadAttributes>,
+    ) -> EngineResult<ForkchoiceUpdated> {
+        let status = self.answer(EngineCall::ForkchoiceUpdated(state)).await?;
+        let updated = ForkchoiceUpdated::new(status);
+        let Some(attributes) = attributes else { return Ok(updated) };
+        let mut builds = self.attributes.lock().unwrap();
+        builds.push(attributes);
+        if self.payloads.lock().unwrap().is_empty() {
+            return Ok(updated);
+        }
+        Ok(updated.with_payload_id(PayloadId::new((builds.len() as u64).to_be_bytes())))
+    }
+
+    async fn get_payload(
+        &self,
+        _version: GetPayloadVersion,
+        payload_id: PayloadId,
+    ) -> EngineResult<EnginePayload> {
+        self.calls.lock().unwrap().push(EngineCall::GetPayload(payload_id));
+        self.payloads.lock().unwrap().pop_front().ok_or(EngineError::UnknownPayload(payload_id))
+    }
+
+    async fn exchange_capabilities(&self, methods: Vec<String>) -> EngineResult<Vec<String>> {
+        Ok(methods)
+    }
+}
+
+/// A payload source serving the test blocks up to `tip`.
+#[derive(Debug)]
+pub(crate) struct MockSource {
+    pub(crate) tip: u64,
+}
+
+#[async_trait]
+impl PayloadSource for MockSource {
+    async fn tip(&self) -> eyre::Result<u64> {
+        Ok(self.tip)
+    }
+
+    async fn payload_by_number(&self, number: u64) -> eyre::Result<Option<EnginePayload>> {
+        Ok((number <= self.tip).then(|| payload(number)))
+    }
+}
+
+/// Returns the hash of the test block `number`.
+pub(crate) fn block_hash(number: u64) -> B256 {
+    B256::left_padding_from(&(number + 1).to_be_bytes())
+}
+
+/// Builds the payload of the test block `number`, on top of the test block before it, with an L1
+/// info deposit referencing L1 block `number / 6`.
+pub(crate) fn payload(number: u64) -> EnginePayload {
+    let config = crate::config::RollupConfig::from_registry(10).unwrap();
+    let info = L1BlockInfoTx::Bedrock(L1BlockInfoBedrock {
+        number: number / 6,
+        block_hash: B256::repeat_byte((number / 6) as u8),
+        sequence_number: number % 6,
+        ..Default::default()
+    });
+    let timestamp = config.genesis.l2_time + number * 2;
+    let deposit = info.to_deposit_tx(&config, timestamp).encoded_2718();
+    EnginePayload {
+        payload: ExecutionPayload::V1(ExecutionPayloadV1 {
+            parent_hash: block_hash(number.saturating_sub(1)),
+            fee_recipient: Address::ZERO,
+            state_root: B256::ZERO,
+            receipts_root: B256::ZERO,
+            logs_bloom: Default::default(),
+            prev_randao: B256::ZERO,
+            block_number: number,
+            gas_limit: 30_000_000,
+            gas_used: 0,
+            timestamp,
+            extra_data: Bytes::new(),
+            base_fee_per_gas: U256::from(1),
+            block_hash: block_hash(number),
+            transactions: vec![deposit],
+        }),
+        parent_beacon_block_root: None,
+        withdrawals_root: None,
+    }
+}
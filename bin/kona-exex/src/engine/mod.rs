@@ -0,0 +1,33 @@
+//! Engine API interaction with the L2 execution layer.
+
+mod api;
+pub use api::{EngineApi, EnginePayload};
+
+pub mod catch_up;
+pub use catch_up::{CatchUpConfig, CatchUpOutcome, CatchUpSubmitter, PayloadSource};
+
+mod client;
+pub use client::{EngineClient, JwtSecret, JWT_REFRESH_INTERVAL, JWT_SECRET_FILES};
+
+pub mod dry_run;
+pub use dry_run::{DryRunEngine, DryRunSummary};
+
+#[cfg(test)]
+pub(crate) mod mock;
+
+mod error;
+pub use error::{EngineError, EngineResult, INTERNAL_ERROR_CODE};
+
+pub mod governor;
+pub use governor::{DerivationGovernor, GovernedEngine, GovernorConfig};
+
+pub mod quirks;
+pub use quirks::{ClientVersion, ExecutionLayer, Quirk, QuirkTracker};
+
+mod rpc_source;
+pub use rpc_source::RpcPayloadSource;
+
+pub mod version;
+pub use version::{
+    EngineCapabilities, ForkchoiceUpdatedVersion, GetPayloadVersion, NewPayloadVersion,
+};
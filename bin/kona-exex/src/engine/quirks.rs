@@ -0,0 +1,341 @@
+//! Conformance of Engine API responses, and workarounds for the known deviations of execution
+//! layers.
+//!
+//! Payload statuses are decoded leniently as a [`RawPayloadStatus`], then normalized into the
+//! spec: statuses outside of it are mapped to the closest spec status, and `latestValidHash` is
+//! given its spec meaning. For a `VALID` payload it is the payload itself, for `SYNCING` and
+//! `ACCEPTED` it is null, and for `INVALID` it is the last valid ancestor or null if unknown,
+//! never the zero hash or the invalid block. Every deviation is a [`Quirk`], counted in
+//! `hera_engine_quirks_total` with the [`ExecutionLayer`] that answered, and logged on first
+//! occurrence.
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    sync::{Mutex, RwLock},
+};
+
+use alloy_primitives::B256;
+use alloy_rpc_types_engine::{ForkchoiceUpdated, PayloadId, PayloadStatus, PayloadStatusEnum};
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// An execution layer, as identified by the client code of `engine_getClientVersionV1`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ExecutionLayer {
+    /// op-geth, or geth.
+    OpGeth,
+    /// op-reth, or reth.
+    OpReth,
+    /// Another execution layer, with its client code.
+    Other(String),
+    /// Not identified, e.g. before `engine_getClientVersionV1` was called or if it is not
+    /// supported.
+    #[default]
+    Unknown,
+}
+
+impl ExecutionLayer {
+    /// Returns the execution layer with the given client code.
+    pub fn from_code(code: &str) -> Self {
+        match code {
+            "GE" => Self::OpGeth,
+            "RH" => Self::OpReth,
+            code => Self::Other(code.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ExecutionLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OpGeth => f.write_str("op-geth"),
+            Self::OpReth => f.write_str("op-reth"),
+            Self::Other(code) => f.write_str(code),
+            Self::Unknown => f.write_str("unknown"),
+        }
+    }
+}
+
+/// The version of a client, as exchanged through `engine_getClientVersionV1`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientVersion {
+    /// The two-letter client code.
+    pub code: String,
+    /// The client name.
+    pub name: String,
+    /// The client version.
+    pub version: String,
+    /// The first four bytes of the commit hash of the client build, hex encoded.
+    pub commit: String,
+}
+
+/// A deviation of an Engine API response from the spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Quirk {
+    /// A status outside of the spec, e.g. the `INVALID_BLOCK_HASH` of older versions of the
+    /// spec still returned by geth, or a status in lowercase.
+    NonStandardStatus,
+    /// An `INVALID` status without validation error.
+    MissingValidationError,
+    /// A `VALID` status without latest valid hash.
+    MissingLatestValidHash,
+    /// A latest valid hash along a `SYNCING` or `ACCEPTED` status, which leaves it unknown.
+    UnexpectedLatestValidHash,
+    /// The zero hash as latest valid hash of an `INVALID` status, which the spec reserves for
+    /// pre-merge ancestors, and which means unknown on chains without any.
+    ZeroLatestValidHash,
+    /// The invalid block itself as latest valid hash of an `INVALID` status.
+    InvalidLatestValidHash,
+}
+
+impl Quirk {
+    /// Returns the metric label of the quirk.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::NonStandardStatus => "non_standard_status",
+            Self::MissingValidationError => "missing_validation_error",
+            Self::MissingLatestValidHash => "missing_latest_valid_hash",
+            Self::UnexpectedLatestValidHash => "unexpected_latest_valid_hash",
+            Self::ZeroLatestValidHash => "zero_latest_valid_hash",
+            Self::InvalidLatestValidHash => "invalid_latest_valid_hash",
+        }
+    }
+}
+
+impl fmt::Display for Quirk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A payload status as returned by an execution layer, before normalization.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawPayloadStatus {
+    /// The status.
+    pub status: String,
+    /// The latest valid hash.
+    #[serde(default)]
+    pub latest_valid_hash: Option<B256>,
+    /// The validation error.
+    #[serde(default)]
+    pub validation_error: Option<String>,
+}
+
+impl RawPayloadStatus {
+    /// Normalizes the status of the block `block`, the inserted payload or the new head, into
+    /// the spec, returning the deviations worked around. Fails on statuses that cannot be
+    /// mapped to a spec status.
+    pub fn normalize(self, block: B256) -> Result<(PayloadStatus, Vec<Quirk>), String> {
+        let mut quirks = Vec::new();
+        let status = self.status.to_ascii_uppercase();
+        if status != self.status {
+            quirks.push(Quirk::NonStandardStatus);
+        }
+        let status = match status.as_str() {
+            "VALID" => PayloadStatusEnum::Valid,
+            "SYNCING" => PayloadStatusEnum::Syncing,
+            "ACCEPTED" => PayloadStatusEnum::Accepted,
+            "INVALID" | "INVALID_BLOCK_HASH" | "INVALID_TERMINAL_BLOCK" => {
+                if status != "INVALID" && !quirks.contains(&Quirk::NonStandardStatus) {
+                    quirks.push(Quirk::NonStandardStatus);
+                }
+                let validation_error = self.validation_error.unwrap_or_else(|| {
+                    quirks.push(Quirk::MissingValidationError);
+                    status.to_ascii_lowercase().replace('_', " ")
+                });
+                PayloadStatusEnum::Invalid { validation_error }
+            }
+            _ => return Err(format!("unknown payload status {}", self.status)),
+        };
+
+        let latest_valid_hash = match (&status, self.latest_valid_hash) {
+            (PayloadStatusEnum::Valid, None) => {
+                quirks.push(Quirk::MissingLatestValidHash);
+                Some(block)
+            }
+            (PayloadStatusEnum::Syncing | PayloadStatusEnum::Accepted, Some(_)) => {
+                quirks.push(Quirk::UnexpectedLatestValidHash);
+                None
+            }
+            (PayloadStatusEnum::Invalid { .. }, Some(hash)) if hash.is_zero() => {
+                quirks.push(Quirk::ZeroLatestValidHash);
+                None
+            }
+            (PayloadStatusEnum::Invalid { .. }, Some(hash)) if hash == block => {
+                quirks.push(Quirk::InvalidLatestValidHash);
+                None
+            }
+            (_, hash) => hash,
+        };
+        Ok((PayloadStatus::new(status, latest_valid_hash), quirks))
+    }
+}
+
+/// A forkchoice update result as returned by an execution layer, before normalization.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawForkchoiceUpdated {
+    /// The status of the new head.
+    pub payload_status: RawPayloadStatus,
+    /// The id of the payload build started, if any.
+    #[serde(default)]
+    pub payload_id: Option<PayloadId>,
+}
+
+impl RawForkchoiceUpdated {
+    /// Normalizes the result of an update to the head `head`, see
+    /// [`RawPayloadStatus::normalize`].
+    pub fn normalize(self, head: B256) -> Result<(ForkchoiceUpdated, Vec<Quirk>), String> {
+        let (status, quirks) = self.payload_status.normalize(head)?;
+        let mut updated = ForkchoiceUpdated::new(status);
+        updated.payload_id = self.payload_id;
+        Ok((updated, quirks))
+    }
+}
+
+/// Tracks the quirks of an execution layer, labeled with its identity once known.
+#[derive(Debug, Default)]
+pub struct QuirkTracker {
+    execution_layer: RwLock<ExecutionLayer>,
+    seen: Mutex<BTreeSet<Quirk>>,
+}
+
+impl QuirkTracker {
+    /// Returns the identified execution layer.
+    pub fn execution_layer(&self) -> ExecutionLayer {
+        self.execution_layer.read().unwrap().clone()
+    }
+
+    /// Sets the identified execution layer.
+    pub fn set_execution_layer(&self, execution_layer: ExecutionLayer) {
+        *self.execution_layer.write().unwrap() = execution_layer;
+    }
+
+    /// Records the quirks of a response to `method`, logging each on first occurrence.
+    pub fn record(&self, method: &'static str, quirks: &[Quirk]) {
+        if quirks.is_empty() {
+            return;
+        }
+        let client = self.execution_layer().to_string();
+        let mut seen = self.seen.lock().unwrap();
+        for quirk in quirks {
+            counter!(
+                "hera_engine_quirks_total",
+                "client" => client.clone(),
+                "quirk" => quirk.as_str()
+            )
+            .increment(1);
+            if seen.insert(*quirk) {
+                warn!(
+                    target: "hera::engine",
+                    %client,
+                    method,
+                    %quirk,
+                    "Execution layer deviates from the Engine API spec, working around it"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const BLOCK: B256 = B256::repeat_byte(0xbb);
+    const PARENT: B256 = B256::repeat_byte(0xaa);
+
+    fn normalize(response: serde_json::Value) -> (PayloadStatus, Vec<Quirk>) {
+        serde_json::from_value::<RawPayloadStatus>(response).unwrap().normalize(BLOCK).unwrap()
+    }
+
+    #[test]
+    fn conforming_responses_are_unchanged() {
+        for (response, expected) in [
+            (
+                json!({ "status": "VALID", "latestValidHash": BLOCK, "validationError": null }),
+                PayloadStatus::new(PayloadStatusEnum::Valid, Some(BLOCK)),
+            ),
+            (
+                json!({ "status": "SYNCING", "latestValidHash": null, "validationError": null }),
+                PayloadStatus::new(PayloadStatusEnum::Syncing, None),
+            ),
+            (
+                json!({ "status": "INVALID", "latestValidHash": PARENT, "validationError": "bad" }),
+                PayloadStatus::new(
+                    PayloadStatusEnum::Invalid { validation_error: "bad".to_string() },
+                    Some(PARENT),
+                ),
+            ),
+        ] {
+            assert_eq!(normalize(response), (expected, Vec::new()));
+        }
+    }
+
+    #[test]
+    fn works_around_op_geth_responses() {
+        // Block hash mismatches are still reported with the status of older spec versions.
+        let (status, quirks) = normalize(json!({
+            "status": "INVALID_BLOCK_HASH",
+            "latestValidHash": null,
+            "validationError": "blockhash mismatch, want 0xbb, got 0xcc",
+        }));
+        assert_eq!(
+            status.status.validation_error(),
+            Some("blockhash mismatch, want 0xbb, got 0xcc")
+        );
+        assert_eq!(status.latest_valid_hash, None);
+        assert_eq!(quirks, [Quirk::NonStandardStatus]);
+
+        // Payloads on top of an unknown ancestor are invalid with the zero hash.
+        let (status, quirks) = normalize(json!({
+            "status": "INVALID",
+            "latestValidHash": B256::ZERO,
+            "validationError": "links to previously rejected block",
+        }));
+        assert!(matches!(status.status, PayloadStatusEnum::Invalid { .. }));
+        assert_eq!(status.latest_valid_hash, None);
+        assert_eq!(quirks, [Quirk::ZeroLatestValidHash]);
+    }
+
+    #[test]
+    fn works_around_op_reth_responses() {
+        // The invalid block is reported as its own latest valid hash.
+        let (status, quirks) = normalize(json!({
+            "status": "INVALID",
+            "latestValidHash": BLOCK,
+            "validationError": null,
+        }));
+        assert_eq!(status.status.validation_error(), Some("invalid"));
+        assert_eq!(status.latest_valid_hash, None);
+        assert_eq!(quirks, [Quirk::MissingValidationError, Quirk::InvalidLatestValidHash]);
+
+        // Syncing forkchoice updates carry the current head.
+        let updated: RawForkchoiceUpdated = serde_json::from_value(json!({
+            "payloadStatus": { "status": "SYNCING", "latestValidHash": PARENT },
+            "payloadId": "0x0000000000000001",
+        }))
+        .unwrap();
+        let (updated, quirks) = updated.normalize(BLOCK).unwrap();
+        assert_eq!(updated.payload_status, PayloadStatus::new(PayloadStatusEnum::Syncing, None));
+        assert_eq!(updated.payload_id, Some(PayloadId::new(1u64.to_be_bytes())));
+        assert_eq!(quirks, [Quirk::UnexpectedLatestValidHash]);
+    }
+
+    #[test]
+    fn works_around_other_deviations() {
+        let (status, quirks) = normalize(json!({ "status": "valid" }));
+        assert_eq!(status, PayloadStatus::new(PayloadStatusEnum::Valid, Some(BLOCK)));
+        assert_eq!(quirks, [Quirk::NonStandardStatus, Quirk::MissingLatestValidHash]);
+
+        let raw: RawPayloadStatus = serde_json::from_value(json!({ "status": "UNKNOWN" })).unwrap();
+        assert!(raw.normalize(BLOCK).is_err());
+        assert_eq!(ExecutionLayer::from_code("GE"), ExecutionLayer::OpGeth);
+        assert_eq!(ExecutionLayer::from_code("NM").to_string(), "NM");
+    }
+}
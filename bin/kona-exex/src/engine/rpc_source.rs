@@ -0,0 +1,58 @@
+//! A [`PayloadSource`] reading the blocks of a trusted L2 node over RPC.
+
+use std::sync::Arc;
+
+use alloy_primitives::{Bytes, U64};
+use async_trait::async_trait;
+use eyre::{Result, WrapErr};
+use jsonrpsee::{core::client::ClientT, http_client::HttpClient, rpc_params};
+
+use crate::{
+    config::RollupConfig,
+    engine::{EnginePayload, PayloadSource},
+    era::EraBlock,
+};
+
+/// Serves the payloads of the blocks of a trusted L2 node, e.g. another node of the same
+/// operator or a sequencer's RPC, to fill the unsafe chain without gossip.
+///
+/// Blocks are read whole with `debug_getRawBlock`, which the node must expose.
+#[derive(Debug)]
+pub struct RpcPayloadSource {
+    client: HttpClient,
+    config: Arc<RollupConfig>,
+}
+
+impl RpcPayloadSource {
+    /// Creates a source reading blocks through `client`, building payloads for `config`.
+    pub const fn new(client: HttpClient, config: Arc<RollupConfig>) -> Self {
+        Self { client, config }
+    }
+}
+
+#[async_trait]
+impl PayloadSource for RpcPayloadSource {
+    async fn tip(&self) -> Result<u64> {
+        let tip: U64 = self
+            .client
+            .request("eth_blockNumber", rpc_params![])
+            .await
+            .wrap_err("failed to fetch the trusted L2 head")?;
+        Ok(tip.to())
+    }
+
+    async fn payload_by_number(&self, number: u64) -> Result<Option<EnginePayload>> {
+        let raw: Bytes = self
+            .client
+            .request("debug_getRawBlock", rpc_params![U64::from(number)])
+            .await
+            .wrap_err_with(|| format!("failed to fetch trusted L2 block {number}"))?;
+        // Blocks the node does not have yet come back empty.
+        if raw.is_empty() {
+            return Ok(None);
+        }
+        let block = EraBlock::decode(raw, None)
+            .wrap_err_with(|| format!("invalid trusted L2 block {number}"))?;
+        block.payload(&self.config).map(Some)
+    }
+}
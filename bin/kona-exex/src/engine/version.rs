@@ -0,0 +1,284 @@
+//! Engine API method version selection.
+//!
+//! The version of each Engine API method is selected from the hardforks active at the timestamp
+//! of the payload it carries, never from the current head: at an activation boundary the first
+//! payload of the new hardfork must already use the new method version, or the execution layer
+//! rejects it with an "unsupported fork" error.
+
+use std::collections::BTreeSet;
+
+use crate::config::RollupConfig;
+
+/// The version of `engine_newPayload` to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NewPayloadVersion {
+    /// `engine_newPayloadV2`, used before Ecotone.
+    V2,
+    /// `engine_newPayloadV3`, used from Ecotone until Isthmus.
+    V3,
+    /// `engine_newPayloadV4`, used from Isthmus onwards.
+    V4,
+}
+
+impl NewPayloadVersion {
+    /// Returns the version to use for a payload with the given timestamp.
+    pub fn from_timestamp(config: &RollupConfig, timestamp: u64) -> Self {
+        if config.is_isthmus_active(timestamp) {
+            Self::V4
+        } else if config.is_ecotone_active(timestamp) {
+            Self::V3
+        } else {
+            Self::V2
+        }
+    }
+
+    /// Returns the JSON-RPC method name.
+    pub const fn method(&self) -> &'static str {
+        match self {
+            Self::V2 => "engine_newPayloadV2",
+            Self::V3 => "engine_newPayloadV3",
+            Self::V4 => "engine_newPayloadV4",
+        }
+    }
+}
+
+/// The version of `engine_forkchoiceUpdated` to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ForkchoiceUpdatedVersion {
+    /// `engine_forkchoiceUpdatedV2`, used before Ecotone. Like op-node, V1 is never used: V2
+    /// accepts the attributes of every pre-Ecotone payload, with or without withdrawals.
+    V2,
+    /// `engine_forkchoiceUpdatedV3`, used from Ecotone onwards and for updates without
+    /// attributes.
+    V3,
+}
+
+impl ForkchoiceUpdatedVersion {
+    /// Returns the version to use for a forkchoice update carrying payload attributes with the
+    /// given timestamp, or no attributes if `None`.
+    ///
+    /// Updates without attributes always use V3, which execution layers accept regardless of
+    /// the active hardfork since no payload is built.
+    pub fn from_attributes_timestamp(config: &RollupConfig, timestamp: Option<u64>) -> Self {
+        match timestamp {
+            None => Self::V3,
+            Some(t) if config.is_ecotone_active(t) => Self::V3,
+            Some(_) => Self::V2,
+        }
+    }
+
+    /// Returns the JSON-RPC method name.
+    pub const fn method(&self) -> &'static str {
+        match self {
+            Self::V2 => "engine_forkchoiceUpdatedV2",
+            Self::V3 => "engine_forkchoiceUpdatedV3",
+        }
+    }
+}
+
+/// The version of `engine_getPayload` to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GetPayloadVersion {
+    /// `engine_getPayloadV2`, used before Ecotone.
+    V2,
+    /// `engine_getPayloadV3`, used from Ecotone until Isthmus.
+    V3,
+    /// `engine_getPayloadV4`, used from Isthmus onwards.
+    V4,
+}
+
+impl GetPayloadVersion {
+    /// Returns the version to use for a payload with the given timestamp.
+    pub fn from_timestamp(config: &RollupConfig, timestamp: u64) -> Self {
+        if config.is_isthmus_active(timestamp) {
+            Self::V4
+        } else if config.is_ecotone_active(timestamp) {
+            Self::V3
+        } else {
+            Self::V2
+        }
+    }
+
+    /// Returns the JSON-RPC method name.
+    pub const fn method(&self) -> &'static str {
+        match self {
+            Self::V2 => "engine_getPayloadV2",
+            Self::V3 => "engine_getPayloadV3",
+            Self::V4 => "engine_getPayloadV4",
+        }
+    }
+}
+
+/// The Engine API methods supported by an execution layer, as reported by
+/// `engine_exchangeCapabilities`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineCapabilities {
+    methods: BTreeSet<String>,
+}
+
+impl EngineCapabilities {
+    /// Creates the capabilities from the method names reported by the execution layer.
+    pub fn new(methods: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self { methods: methods.into_iter().map(Into::into).collect() }
+    }
+
+    /// Returns true if the execution layer supports the given method.
+    pub fn supports(&self, method: &str) -> bool {
+        self.methods.contains(method)
+    }
+
+    /// Returns the methods needed to follow the chain from `timestamp` onwards, including those
+    /// of every hardfork scheduled after it.
+    pub fn required_methods(config: &RollupConfig, timestamp: u64) -> BTreeSet<&'static str> {
+        let mut timestamps = vec![timestamp];
+        timestamps.extend(
+            [config.canyon_time, config.ecotone_time, config.isthmus_time]
+                .into_iter()
+                .flatten()
+                .filter(|t| *t > timestamp),
+        );
+
+        let mut methods = BTreeSet::new();
+        for t in timestamps {
+            methods.insert(NewPayloadVersion::from_timestamp(config, t).method());
+            methods.insert(GetPayloadVersion::from_timestamp(config, t).method());
+            methods.insert(
+                ForkchoiceUpdatedVersion::from_attributes_timestamp(config, Some(t)).method(),
+            );
+        }
+        methods.insert(ForkchoiceUpdatedVersion::V3.method());
+        methods
+    }
+
+    /// Returns the required methods the execution layer does not support. An empty result means
+    /// the execution layer can follow the chain across every scheduled activation boundary.
+    pub fn missing_methods(&self, config: &RollupConfig, timestamp: u64) -> Vec<&'static str> {
+        Self::required_methods(config, timestamp)
+            .into_iter()
+            .filter(|method| !self.supports(method))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{hardfork_matrix, Hardfork, TestConfigBuilder};
+
+    /// Builds a config with Canyon at 100, Ecotone at 200 and Isthmus at 300, keeping only the
+    /// hardforks enabled by the flags.
+    fn config(canyon: bool, ecotone: bool, isthmus: bool) -> RollupConfig {
+        let mut builder = TestConfigBuilder::new().activate(Hardfork::Regolith, 0);
+        if canyon {
+            builder = builder.activate_all([Hardfork::Canyon, Hardfork::Delta], 100);
+        }
+        if ecotone {
+            builder = builder.activate(Hardfork::Ecotone, 200);
+        }
+        if isthmus {
+            builder = builder.activate(Hardfork::Isthmus, 300);
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn version_matrix() {
+        use ForkchoiceUpdatedVersion as Fcu;
+        use GetPayloadVersion as Get;
+        use NewPayloadVersion as New;
+
+        // (canyon, ecotone, isthmus, timestamp, newPayload, forkchoiceUpdated, getPayload)
+        let matrix = [
+            (false, false, false, 0, New::V2, Fcu::V2, Get::V2),
+            (false, false, false, 1_000, New::V2, Fcu::V2, Get::V2),
+            (true, false, false, 98, New::V2, Fcu::V2, Get::V2),
+            (true, false, false, 100, New::V2, Fcu::V2, Get::V2),
+            (true, false, false, 1_000, New::V2, Fcu::V2, Get::V2),
+            (true, true, false, 198, New::V2, Fcu::V2, Get::V2),
+            (true, true, false, 200, New::V3, Fcu::V3, Get::V3),
+            (true, true, false, 1_000, New::V3, Fcu::V3, Get::V3),
+            (true, true, true, 298, New::V3, Fcu::V3, Get::V3),
+            (true, true, true, 300, New::V4, Fcu::V3, Get::V4),
+            (true, true, true, 1_000, New::V4, Fcu::V3, Get::V4),
+        ];
+
+        for (canyon, ecotone, isthmus, timestamp, new_payload, fcu, get_payload) in matrix {
+            let config = config(canyon, ecotone, isthmus);
+            let case = format!("canyon={canyon} ecotone={ecotone} isthmus={isthmus} t={timestamp}");
+            assert_eq!(
+                NewPayloadVersion::from_timestamp(&config, timestamp),
+                new_payload,
+                "{case}"
+            );
+            assert_eq!(
+                ForkchoiceUpdatedVersion::from_attributes_timestamp(&config, Some(timestamp)),
+                fcu,
+                "{case}"
+            );
+            assert_eq!(
+                GetPayloadVersion::from_timestamp(&config, timestamp),
+                get_payload,
+                "{case}"
+            );
+        }
+    }
+
+    #[test]
+    fn versions_change_at_the_first_block_of_their_hardfork() {
+        for boundary in hardfork_matrix(4) {
+            let (before, at) = (boundary.before().timestamp(), boundary.at().timestamp());
+            let new_payload = |t| NewPayloadVersion::from_timestamp(&boundary.config, t);
+            let get_payload = |t| GetPayloadVersion::from_timestamp(&boundary.config, t);
+            let upgrades = matches!(boundary.fork, Hardfork::Ecotone | Hardfork::Isthmus);
+            assert_eq!(new_payload(before) < new_payload(at), upgrades, "{}", boundary.fork);
+            assert_eq!(get_payload(before) < get_payload(at), upgrades, "{}", boundary.fork);
+        }
+    }
+
+    #[test]
+    fn forkchoice_update_without_attributes_uses_v3() {
+        for config in [config(false, false, false), config(true, true, true)] {
+            assert_eq!(
+                ForkchoiceUpdatedVersion::from_attributes_timestamp(&config, None),
+                ForkchoiceUpdatedVersion::V3
+            );
+        }
+    }
+
+    #[test]
+    fn required_methods_include_scheduled_hardforks() {
+        let config = config(true, true, true);
+        let required = EngineCapabilities::required_methods(&config, 150);
+        assert!(required.contains("engine_newPayloadV2"));
+        assert!(required.contains("engine_newPayloadV3"));
+        assert!(required.contains("engine_newPayloadV4"));
+        assert!(required.contains("engine_getPayloadV4"));
+        assert!(required.contains("engine_forkchoiceUpdatedV2"));
+
+        // Chains starting before Canyon still only need V2 updates.
+        let required = EngineCapabilities::required_methods(&self::config(false, false, false), 0);
+        assert!(required.contains("engine_forkchoiceUpdatedV2"));
+        assert!(!required.contains("engine_forkchoiceUpdatedV1"));
+
+        let required = EngineCapabilities::required_methods(&config, 300);
+        assert!(!required.contains("engine_newPayloadV3"));
+    }
+
+    #[test]
+    fn missing_methods_detects_unsupported_fork() {
+        let config = config(true, true, true);
+        let pre_isthmus_el = EngineCapabilities::new([
+            "engine_newPayloadV2",
+            "engine_newPayloadV3",
+            "engine_forkchoiceUpdatedV2",
+            "engine_forkchoiceUpdatedV3",
+            "engine_getPayloadV2",
+            "engine_getPayloadV3",
+        ]);
+        assert_eq!(
+            pre_isthmus_el.missing_methods(&config, 250),
+            vec!["engine_getPayloadV4", "engine_newPayloadV4"]
+        );
+        assert!(pre_isthmus_el.missing_methods(&self::config(true, true, false), 250).is_empty());
+    }
+}
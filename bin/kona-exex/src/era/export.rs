@@ -0,0 +1,121 @@
+//! Exporting the derived chain from the L2 execution layer to archive files.
+
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use alloy_primitives::{hex, Bytes, U64};
+use eyre::{ensure, Result, WrapErr};
+use jsonrpsee::{core::client::ClientT, http_client::HttpClient, rpc_params};
+use tracing::{debug, info};
+
+use crate::{
+    era::{EraBlock, EraWriter},
+    safedb::{SafeDb, SafeHeadAtL1},
+};
+
+/// The default number of L2 blocks per archive file, as in era1 archives.
+pub const DEFAULT_BLOCKS_PER_FILE: u64 = 8192;
+
+/// Exports L2 blocks read from the L2 execution layer to archive files, anchored to the L1
+/// blocks recorded in the safe head database.
+#[derive(Debug)]
+pub struct EraExporter {
+    client: HttpClient,
+    safe_db: Option<SafeDb>,
+    network: String,
+    blocks_per_file: u64,
+}
+
+impl EraExporter {
+    /// Creates an exporter reading blocks through `client`, naming files after `network`.
+    pub fn new(client: HttpClient, network: impl Into<String>) -> Self {
+        Self {
+            client,
+            safe_db: None,
+            network: network.into(),
+            blocks_per_file: DEFAULT_BLOCKS_PER_FILE,
+        }
+    }
+
+    /// Anchors blocks to the L1 blocks they were first derived from, as recorded in `safe_db`.
+    pub fn with_safe_db(mut self, safe_db: SafeDb) -> Self {
+        self.safe_db = Some(safe_db);
+        self
+    }
+
+    /// Splits the archive into files of `blocks_per_file` blocks, starting at multiples of it.
+    pub const fn with_blocks_per_file(mut self, blocks_per_file: u64) -> Self {
+        self.blocks_per_file = blocks_per_file;
+        self
+    }
+
+    /// Returns the file name of the archive `index` ending with the block `last`.
+    pub fn file_name(&self, index: u64, last: &EraBlock) -> String {
+        format!("{}-{index:05}-{}.era", self.network, hex::encode(&last.hash[..4]))
+    }
+
+    /// Exports the L2 blocks from `from` to `to` included into `dir`, returning the paths of
+    /// the files written.
+    ///
+    /// Files are written under a temporary name and renamed once complete, so that an
+    /// interrupted export leaves no partial archive behind.
+    pub async fn export(&self, from: u64, to: u64, dir: &Path) -> Result<Vec<PathBuf>> {
+        ensure!(self.blocks_per_file > 0, "archive files must hold at least one block");
+        ensure!(from <= to, "nothing to export from L2 block {from} to {to}");
+        fs::create_dir_all(dir).wrap_err_with(|| format!("failed to create {}", dir.display()))?;
+
+        let mut paths = Vec::new();
+        let mut start = from;
+        while start <= to {
+            let index = start / self.blocks_per_file;
+            let end = to.min((index + 1) * self.blocks_per_file - 1);
+            paths.push(self.export_file(index, start, end, dir).await?);
+            start = end + 1;
+        }
+        Ok(paths)
+    }
+
+    async fn export_file(&self, index: u64, from: u64, to: u64, dir: &Path) -> Result<PathBuf> {
+        let partial = dir.join(format!("{}-{index:05}.era.partial", self.network));
+        let file = File::create(&partial)
+            .wrap_err_with(|| format!("failed to create {}", partial.display()))?;
+        let mut era = EraWriter::new(BufWriter::new(file))?;
+        let (mut last, mut safe_head) = (None, None);
+        for number in from..=to {
+            let block = self.block(number, &mut safe_head).await?;
+            era.append(&block)?;
+            last = Some(block);
+        }
+        era.finish()?.into_inner()?.sync_all()?;
+
+        let last = last.expect("files hold at least one block");
+        let path = dir.join(self.file_name(index, &last));
+        fs::rename(&partial, &path)
+            .wrap_err_with(|| format!("failed to move archive to {}", path.display()))?;
+        info!(target: "hera::era", from, to, path = %path.display(), "Exported L2 blocks");
+        Ok(path)
+    }
+
+    /// Fetches the block `number`, anchored to the L1 block of the first safe head at or after
+    /// it, which is still `safe_head` when the previous block was fetched last.
+    async fn block(&self, number: u64, safe_head: &mut Option<SafeHeadAtL1>) -> Result<EraBlock> {
+        let raw: Bytes = self
+            .client
+            .request("debug_getRawBlock", rpc_params![U64::from(number)])
+            .await
+            .wrap_err_with(|| format!("failed to fetch L2 block {number}"))?;
+        if let Some(safe_db) = &self.safe_db {
+            if !safe_head.is_some_and(|at| at.safe_head.number >= number) {
+                *safe_head = safe_db.derived_from(number)?;
+            }
+        }
+        let l1_anchor = safe_head.map(|at| at.l1_block);
+        if l1_anchor.is_none() {
+            debug!(target: "hera::era", number, "No L1 anchor for L2 block");
+        }
+        EraBlock::decode(raw, l1_anchor).wrap_err_with(|| format!("invalid L2 block {number}"))
+    }
+}
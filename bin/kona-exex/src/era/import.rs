@@ -0,0 +1,515 @@
+//! Importing archives into the L2 execution layer ahead of derivation.
+//!
+//! Syncing a chain from genesis derives every block from L1, which takes days on long chains.
+//! The [`EraImporter`] inserts the blocks of archives written by other nodes instead, checked
+//! against the rollup config, and moves the safe head up to the last imported block whose output
+//! root matches one trusted by the operator: derivation then resumes from there, consolidating
+//! the imported blocks past it.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::BufReader,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use alloy_primitives::B256;
+use alloy_rpc_types_engine::ForkchoiceState;
+use async_trait::async_trait;
+use eyre::{bail, ensure, eyre, Result, WrapErr};
+use metrics::counter;
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::RollupConfig,
+    engine::{
+        CatchUpConfig, CatchUpSubmitter, EngineApi, EnginePayload, ForkchoiceUpdatedVersion,
+        PayloadSource,
+    },
+    era::{read_era, EraBlock},
+    output::OutputRootCache,
+    protocol::{BlockId, L2BlockInfo},
+    safedb::SafeDb,
+};
+
+/// An output root trusted by the operator, e.g. as proposed to the `L2OutputOracle`, parsed
+/// from `<l2 block number>:<output root>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrustedOutput {
+    /// The L2 block the output is at.
+    pub number: u64,
+    /// The output root.
+    pub root: B256,
+}
+
+impl FromStr for TrustedOutput {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (number, root) = s
+            .split_once(':')
+            .ok_or_else(|| eyre!("invalid output {s}, expected <block number>:<output root>"))?;
+        let number = number.trim().parse().wrap_err_with(|| format!("invalid block in {s}"))?;
+        let root = root.trim().parse().wrap_err_with(|| format!("invalid output root in {s}"))?;
+        Ok(Self { number, root })
+    }
+}
+
+/// The result of an import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportOutcome {
+    /// The last imported block, the unsafe head after the import.
+    pub head: L2BlockInfo,
+    /// The safe head after the import, the last imported block covered by a trusted output root.
+    pub safe: L2BlockInfo,
+    /// Number of imported blocks.
+    pub imported: u64,
+    /// Number of archive files blocks were imported from.
+    pub files: usize,
+    /// Number of trusted output roots checked.
+    pub verified_outputs: usize,
+}
+
+/// Imports archive files into the L2 execution layer.
+#[derive(Debug)]
+pub struct EraImporter {
+    config: Arc<RollupConfig>,
+    engine: Arc<dyn EngineApi>,
+    settings: CatchUpConfig,
+    outputs: Option<Arc<OutputRootCache>>,
+    trusted: BTreeMap<u64, B256>,
+    safe_db: Option<Arc<SafeDb>>,
+}
+
+impl EraImporter {
+    /// Creates an importer inserting blocks of the chain of `config` into `engine`.
+    pub fn new(config: Arc<RollupConfig>, engine: Arc<dyn EngineApi>) -> Self {
+        Self {
+            config,
+            engine,
+            settings: CatchUpConfig { forkchoice_interval: 256, ..Default::default() },
+            outputs: None,
+            trusted: BTreeMap::new(),
+            safe_db: None,
+        }
+    }
+
+    /// Checks the output roots computed by `outputs` at the blocks of `trusted` once imported.
+    pub fn with_trusted_outputs(
+        mut self,
+        outputs: Arc<OutputRootCache>,
+        trusted: impl IntoIterator<Item = TrustedOutput>,
+    ) -> Self {
+        self.outputs = Some(outputs);
+        self.trusted.extend(trusted.into_iter().map(|output| (output.number, output.root)));
+        self
+    }
+
+    /// Records the L1 anchors of the imported blocks in `safe_db`.
+    pub fn with_safe_db(mut self, safe_db: Arc<SafeDb>) -> Self {
+        self.safe_db = Some(safe_db);
+        self
+    }
+
+    /// Imports the blocks of the archive files in `dir` on top of the safe head `head`,
+    /// skipping the blocks at or below it.
+    ///
+    /// Files are imported in name order, which is block order for files named by the exporter.
+    /// After each of them, the safe head moves to the last imported block with a trusted output
+    /// root, if any, so that an interrupted import resumes from there: an output root commits
+    /// to the block hash, hence to every block before it. A file whose blocks do not check out
+    /// fails the import before any of them is inserted.
+    pub async fn import(&self, dir: &Path, head: L2BlockInfo) -> Result<ImportOutcome> {
+        let paths = archive_files(dir)?;
+        ensure!(!paths.is_empty(), "no archive files in {}", dir.display());
+        if head.block_info.number == self.config.genesis.l2.number {
+            ensure!(
+                head.block_info.hash == self.config.genesis.l2.hash,
+                "L2 genesis {} does not match the rollup config",
+                head.block_info
+            );
+        }
+
+        // Only derivation finalizes blocks: the finalized head stays at the starting safe head.
+        let finalized = head.block_info.hash;
+        let mut outcome =
+            ImportOutcome { head, safe: head, imported: 0, files: 0, verified_outputs: 0 };
+        // The imported blocks past the safe head.
+        let mut unsafe_blocks = Vec::new();
+        for path in &paths {
+            let file = File::open(path)
+                .wrap_err_with(|| format!("failed to open archive {}", path.display()))?;
+            let size = file.metadata()?.len();
+            let blocks = read_era(BufReader::new(file), size)
+                .wrap_err_with(|| format!("invalid archive {}", path.display()))?;
+            let imported = self
+                .import_blocks(blocks, finalized, &mut unsafe_blocks, &mut outcome)
+                .await
+                .wrap_err_with(|| format!("failed to import archive {}", path.display()))?;
+            if imported > 0 {
+                outcome.files += 1;
+                info!(
+                    target: "hera::era",
+                    path = %path.display(),
+                    imported,
+                    head = %outcome.head.block_info,
+                    safe = %outcome.safe.block_info,
+                    "Imported L2 blocks"
+                );
+            }
+        }
+        if outcome.safe != outcome.head {
+            warn!(
+                target: "hera::era",
+                head = %outcome.head.block_info,
+                safe = %outcome.safe.block_info,
+                "No trusted output root covers the last imported blocks, deriving them from L1"
+            );
+        }
+        Ok(outcome)
+    }
+
+    /// Checks and inserts the blocks after the head of `outcome`, returning how many were.
+    /// `unsafe_blocks` holds the blocks imported past the safe head so far.
+    async fn import_blocks(
+        &self,
+        blocks: Vec<EraBlock>,
+        finalized: B256,
+        unsafe_blocks: &mut Vec<EraBlock>,
+        outcome: &mut ImportOutcome,
+    ) -> Result<u64> {
+        let head = outcome.head.block_info;
+        if let Some(block) = blocks.iter().find(|block| block.number == head.number) {
+            ensure!(block.hash == head.hash, "archive is not on the chain of the safe head {head}");
+        }
+        let blocks: Vec<_> =
+            blocks.into_iter().filter(|block| block.number > head.number).collect();
+        let (Some(first), Some(last)) = (blocks.first(), blocks.last()) else {
+            debug!(target: "hera::era", %head, "Archive is at or below the safe head");
+            return Ok(0);
+        };
+        ensure!(
+            first.number == head.number + 1,
+            "archive starts at L2 block {}, after the safe head {head}",
+            first.number
+        );
+        let range = first.number..=last.number;
+
+        let mut parent = head.hash;
+        let mut payloads = Vec::with_capacity(blocks.len());
+        for block in &blocks {
+            let payload = block.payload(&self.config)?;
+            self.check(&payload, parent)?;
+            parent = payload.block_id().hash;
+            payloads.push(payload);
+        }
+
+        let count = payloads.len() as u64;
+        let source = Arc::new(ArchivePayloads { first: first.number, payloads });
+        let state = ForkchoiceState {
+            head_block_hash: head.hash,
+            safe_block_hash: outcome.safe.block_info.hash,
+            finalized_block_hash: finalized,
+        };
+        let submitter =
+            CatchUpSubmitter::new(self.engine.clone(), self.config.clone(), self.settings);
+        let inserted = submitter.sync(source.clone(), state, range.clone()).await?;
+        let new_head = inserted.head.filter(|_| inserted.inserted == count).ok_or_else(|| {
+            eyre!("engine inserted {} of {count} archived blocks", inserted.inserted)
+        })?;
+        counter!("hera_era_imported_blocks_total").increment(count);
+
+        let verified = self.check_outputs(range.clone()).await?;
+        outcome.verified_outputs += verified.len();
+        outcome.head = new_head;
+        outcome.imported += count;
+        unsafe_blocks.extend(blocks);
+        if let Some(safe) = verified.last() {
+            let index = (safe - range.start()) as usize;
+            let safe = source.payloads[index].l2_block_info()?;
+            self.checkpoint(new_head, safe, finalized, unsafe_blocks).await?;
+            unsafe_blocks.retain(|block| block.number > safe.block_info.number);
+            outcome.safe = safe;
+        }
+        Ok(count)
+    }
+
+    /// Checks `payload` against the rollup config and its `parent`.
+    fn check(&self, payload: &EnginePayload, parent: B256) -> Result<()> {
+        let block = payload.block_id();
+        ensure!(payload.parent_hash() == parent, "L2 block {block} does not build on {parent}");
+        let timestamp = self.config.l2_block_timestamp(block.number);
+        ensure!(
+            payload.timestamp() == timestamp,
+            "L2 block {block} has timestamp {}, expected {timestamp}",
+            payload.timestamp()
+        );
+        // Every block opens with the L1 info deposit of its L1 origin.
+        payload.l2_block_info()?;
+        Ok(())
+    }
+
+    /// Checks the trusted output roots within `range`, returning the blocks they are at.
+    async fn check_outputs(&self, range: RangeInclusive<u64>) -> Result<Vec<u64>> {
+        let Some(outputs) = &self.outputs else { return Ok(Vec::new()) };
+        let mut checked = Vec::new();
+        for (number, trusted) in self.trusted.range(range) {
+            let output = outputs.output_at_block(*number).await?;
+            if output.output_root != *trusted {
+                bail!(
+                    "output root {} of imported L2 block {number} does not match the trusted \
+                     output root {trusted}",
+                    output.output_root
+                );
+            }
+            debug!(target: "hera::era", number, output_root = %trusted, "Output root checks out");
+            checked.push(*number);
+        }
+        Ok(checked)
+    }
+
+    /// Moves the safe head to `safe` below the unsafe `head`, keeping the `finalized` head, and
+    /// records the L1 anchors of the imported `blocks` up to it.
+    async fn checkpoint(
+        &self,
+        head: L2BlockInfo,
+        safe: L2BlockInfo,
+        finalized: B256,
+        blocks: &[EraBlock],
+    ) -> Result<()> {
+        let state = ForkchoiceState {
+            head_block_hash: head.block_info.hash,
+            safe_block_hash: safe.block_info.hash,
+            finalized_block_hash: finalized,
+        };
+        let updated =
+            self.engine.forkchoice_updated(ForkchoiceUpdatedVersion::V3, state, None).await?;
+        let status = updated.payload_status.status;
+        if !status.is_valid() {
+            bail!("engine did not accept the imported safe head {}: {status}", safe.block_info);
+        }
+
+        let Some(safe_db) = &self.safe_db else { return Ok(()) };
+        // The safe head at an L1 block is the last L2 block derived from it.
+        let covered = blocks.iter().take_while(|block| block.number <= safe.block_info.number);
+        for (i, block) in covered.enumerate() {
+            let Some(anchor) = block.l1_anchor else { continue };
+            if blocks.get(i + 1).and_then(|next| next.l1_anchor) != Some(anchor) {
+                safe_db.record(anchor, BlockId::new(block.hash, block.number))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the archive files in `dir`, in name order.
+fn archive_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries =
+        fs::read_dir(dir).wrap_err_with(|| format!("failed to list {}", dir.display()))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "era") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// The payloads of the blocks of an archive file, from `first` on.
+#[derive(Debug)]
+struct ArchivePayloads {
+    first: u64,
+    payloads: Vec<EnginePayload>,
+}
+
+#[async_trait]
+impl PayloadSource for ArchivePayloads {
+    async fn tip(&self) -> Result<u64> {
+        Ok(self.first + self.payloads.len() as u64 - 1)
+    }
+
+    async fn payload_by_number(&self, number: u64) -> Result<Option<EnginePayload>> {
+        let index = number.checked_sub(self.first).and_then(|index| usize::try_from(index).ok());
+        Ok(index.and_then(|index| self.payloads.get(index)).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_consensus::Header;
+    use alloy_primitives::{Address, Bytes};
+
+    use super::*;
+    use crate::{
+        engine::mock::{EngineCall, MockEngine},
+        era::{tests::raw_block, EraWriter},
+        output::{output_root_v0, BlockRoots, L2StateProvider, DEFAULT_OUTPUT_CACHE_SIZE},
+        protocol::{l1_info::L1BlockInfoBedrock, BlockInfo, L1BlockInfoTx},
+        storage::MemoryStorage,
+    };
+
+    const STATE_ROOT: B256 = B256::repeat_byte(0x11);
+    const WITHDRAWALS_ROOT: B256 = B256::repeat_byte(0x22);
+
+    /// The L2 state once `blocks` are imported, with constant state and withdrawals roots.
+    #[derive(Debug)]
+    struct ImportedState(Vec<EraBlock>);
+
+    #[async_trait]
+    impl L2StateProvider for ImportedState {
+        async fn block_by_number(&self, number: u64) -> Result<(L2BlockInfo, BlockRoots)> {
+            let block = &self.0[number as usize];
+            let info = L2BlockInfo {
+                block_info: BlockInfo::new(block.hash, number, B256::ZERO, 0),
+                ..Default::default()
+            };
+            Ok((
+                info,
+                BlockRoots { state_root: STATE_ROOT, withdrawals_root: Some(WITHDRAWALS_ROOT) },
+            ))
+        }
+
+        async fn storage_root(&self, _address: Address, _block_hash: B256) -> Result<B256> {
+            unreachable!("the withdrawals root is in the header")
+        }
+    }
+
+    fn trusted_output(block: &EraBlock) -> TrustedOutput {
+        let root = output_root_v0(STATE_ROOT, WITHDRAWALS_ROOT, block.hash);
+        TrustedOutput { number: block.number, root }
+    }
+
+    /// Returns a chain of `count` blocks on top of a genesis block, anchored to L1 block 100 +
+    /// their number / 2, for the chain of `config` whose genesis it sets.
+    fn chain(config: &mut RollupConfig, count: u64) -> Vec<EraBlock> {
+        let genesis = Header { timestamp: config.genesis.l2_time, ..Default::default() };
+        config.genesis.l2 = BlockId::new(genesis.hash_slow(), 0);
+        let mut blocks = vec![EraBlock::decode(raw_block(&genesis, &[]), None).unwrap()];
+        for number in 1..=count {
+            let timestamp = config.l2_block_timestamp(number);
+            let info = L1BlockInfoTx::Bedrock(L1BlockInfoBedrock {
+                number: number / 2,
+                sequence_number: number % 2,
+                ..Default::default()
+            });
+            let deposit: Bytes = info.to_deposit_tx(config, timestamp).encoded_2718();
+            let header = Header {
+                parent_hash: blocks.last().unwrap().hash,
+                number,
+                timestamp,
+                ..Default::default()
+            };
+            let anchor = BlockId::new(B256::repeat_byte(number as u8 / 2), 100 + number / 2);
+            blocks.push(EraBlock::decode(raw_block(&header, &[deposit]), Some(anchor)).unwrap());
+        }
+        blocks
+    }
+
+    fn write_archive(dir: &Path, name: &str, blocks: &[EraBlock]) {
+        let mut era = EraWriter::new(Vec::new()).unwrap();
+        for block in blocks {
+            era.append(block).unwrap();
+        }
+        fs::write(dir.join(name), era.finish().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn imports_archives_on_top_of_the_safe_head() {
+        let dir = std::env::temp_dir().join(format!("hera-era-import-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut config = RollupConfig::from_registry(10).unwrap();
+        let blocks = chain(&mut config, 5);
+        write_archive(&dir, "test-00000.era", &blocks[..3]);
+        write_archive(&dir, "test-00001.era", &blocks[3..]);
+        let genesis = L2BlockInfo {
+            block_info: crate::protocol::BlockInfo::new(
+                blocks[0].hash,
+                0,
+                B256::ZERO,
+                config.genesis.l2_time,
+            ),
+            ..Default::default()
+        };
+
+        let safe_heads = |engine: &MockEngine| {
+            let mut safe: Vec<_> = engine
+                .calls()
+                .into_iter()
+                .filter_map(|call| match call {
+                    EngineCall::ForkchoiceUpdated(state) => Some(state.safe_block_hash),
+                    _ => None,
+                })
+                .filter(|safe| *safe != blocks[0].hash)
+                .collect();
+            safe.dedup();
+            safe
+        };
+
+        // Without trusted output roots, the safe head stays put.
+        let engine = Arc::new(MockEngine::new());
+        let importer = EraImporter::new(Arc::new(config.clone()), engine.clone());
+        let outcome = importer.import(&dir, genesis).await.unwrap();
+        assert_eq!(outcome.imported, 5);
+        assert_eq!(outcome.head.block_info.hash, blocks[5].hash);
+        assert_eq!(outcome.safe, genesis);
+        assert!(safe_heads(&engine).is_empty());
+
+        // The safe head moves to the block of the trusted output root, in the second file.
+        let engine = Arc::new(MockEngine::new());
+        let safe_db = Arc::new(SafeDb::new(Arc::new(MemoryStorage::new())));
+        let outputs = Arc::new(OutputRootCache::new(
+            Arc::new(ImportedState(blocks.clone())),
+            DEFAULT_OUTPUT_CACHE_SIZE,
+        ));
+        let importer = EraImporter::new(Arc::new(config.clone()), engine.clone())
+            .with_trusted_outputs(outputs.clone(), [trusted_output(&blocks[4])])
+            .with_safe_db(safe_db.clone());
+        let outcome = importer.import(&dir, genesis).await.unwrap();
+        assert_eq!(outcome.imported, 5);
+        assert_eq!(outcome.files, 2);
+        assert_eq!(outcome.verified_outputs, 1);
+        assert_eq!(outcome.head.block_info.hash, blocks[5].hash);
+        assert_eq!(outcome.head.l1_origin.number, 2);
+        assert_eq!(outcome.safe.block_info.hash, blocks[4].hash);
+        assert_eq!(safe_heads(&engine), [blocks[4].hash]);
+        // The L1 anchors of blocks up to the safe head are recorded, but for the L1 block the
+        // next imported block was derived from too.
+        let at = safe_db.safe_head_at(101).unwrap().unwrap();
+        assert_eq!(at.safe_head, BlockId::new(blocks[3].hash, 3));
+        assert_eq!(safe_db.safe_head_at(102).unwrap().unwrap().safe_head.number, 3);
+
+        // Importing again is a no-op.
+        let again = importer.import(&dir, outcome.head).await.unwrap();
+        assert_eq!(again.imported, 0);
+
+        // An imported block off the trusted output root fails the import.
+        let mut wrong = trusted_output(&blocks[2]);
+        wrong.root = B256::repeat_byte(0xff);
+        let importer = EraImporter::new(Arc::new(config.clone()), Arc::new(MockEngine::new()))
+            .with_trusted_outputs(outputs, [wrong]);
+        let err = importer.import(&dir, genesis).await.unwrap_err();
+        assert!(format!("{err:#}").contains("does not match the trusted"), "{err:#}");
+
+        // Blocks off the schedule of the rollup config are rejected before any is inserted.
+        config.block_time += 1;
+        let engine = Arc::new(MockEngine::new());
+        let importer = EraImporter::new(Arc::new(config), engine.clone());
+        let err = importer.import(&dir, genesis).await.unwrap_err();
+        assert!(format!("{err:#}").contains("has timestamp"), "{err:#}");
+        assert!(engine.calls().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parses_trusted_outputs() {
+        let root = B256::repeat_byte(1);
+        let output: TrustedOutput = format!("120:{root}").parse().unwrap();
+        assert_eq!(output, TrustedOutput { number: 120, root });
+        assert!("120".parse::<TrustedOutput>().is_err());
+    }
+}
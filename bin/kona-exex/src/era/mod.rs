@@ -0,0 +1,373 @@
+//! Era-style archives of the derived L2 chain.
+//!
+//! Derived blocks are exported to files in the e2store format of era and era1 archives: a
+//! sequence of records, each an 8-byte header of a 2-byte type, a 4-byte little-endian length
+//! and 2 reserved zero bytes, followed by the record data. A file starts with a version record,
+//! holds a raw block record per L2 block, each followed by the L1 block it was first derived
+//! from when known, and ends with an index of the offsets of the block records.
+//!
+//! Archives are stored long-term off the node, and bootstrap other nodes without deriving the
+//! chain from L1 again. They are written with `hera export-era`, and imported on startup with
+//! `--hera.import.era-dir`.
+
+use std::io::{self, Read, Write};
+
+use alloy_consensus::Header;
+use alloy_eips::eip4895::Withdrawal;
+use alloy_primitives::{Bytes, B256, U256};
+use alloy_rlp::Decodable;
+use alloy_rpc_types_engine::{
+    ExecutionPayload, ExecutionPayloadV1, ExecutionPayloadV2, ExecutionPayloadV3,
+};
+use eyre::{bail, ensure, eyre, Result, WrapErr};
+
+use crate::{config::RollupConfig, engine::EnginePayload, protocol::BlockId};
+
+mod export;
+pub use export::{EraExporter, DEFAULT_BLOCKS_PER_FILE};
+
+mod import;
+pub use import::{EraImporter, ImportOutcome, TrustedOutput};
+
+/// The type of the version record starting every file.
+pub const VERSION: [u8; 2] = [0x65, 0x32];
+/// The type of the record of an RLP-encoded L2 block, as returned by `debug_getRawBlock`.
+pub const L2_BLOCK: [u8; 2] = [0x48, 0x01];
+/// The type of the record of the L1 block the preceding L2 block was first derived from: the
+/// L1 block hash, then its little-endian number.
+pub const L1_ANCHOR: [u8; 2] = [0x48, 0x02];
+/// The type of the block index ending every file: the little-endian number of the first block,
+/// the offsets of the block records relative to the index record, then the block count.
+pub const BLOCK_INDEX: [u8; 2] = [0x66, 0x32];
+
+/// The size of a record header.
+const HEADER_SIZE: u64 = 8;
+
+/// A derived L2 block, as archived.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EraBlock {
+    /// The block hash.
+    pub hash: B256,
+    /// The block number.
+    pub number: u64,
+    /// The RLP-encoded block.
+    pub raw: Bytes,
+    /// The L1 block the block was first derived from, if known.
+    pub l1_anchor: Option<BlockId>,
+}
+
+impl EraBlock {
+    /// Decodes the header of the RLP-encoded block `raw`, derived from `l1_anchor`.
+    pub fn decode(raw: Bytes, l1_anchor: Option<BlockId>) -> Result<Self> {
+        let mut buf = raw.as_ref();
+        let list = alloy_rlp::Header::decode(&mut buf).wrap_err("invalid raw block")?;
+        ensure!(list.list, "raw block is not an RLP list");
+        let header = Header::decode(&mut buf).wrap_err("invalid raw block header")?;
+        Ok(Self { hash: header.hash_slow(), number: header.number, raw, l1_anchor })
+    }
+
+    /// Returns the execution payload inserting the block, for the chain of `config`.
+    pub fn payload(&self, config: &RollupConfig) -> Result<EnginePayload> {
+        let number = self.number;
+        let mut buf = self.raw.as_ref();
+        alloy_rlp::Header::decode(&mut buf)?;
+        let header = Header::decode(&mut buf)?;
+        let mut list = rlp_list(&mut buf).wrap_err_with(|| format!("L2 block {number}"))?;
+        let mut transactions = Vec::new();
+        while !list.is_empty() {
+            // Legacy transactions are RLP lists, typed ones are strings wrapping their envelope.
+            let start = list;
+            let item = alloy_rlp::Header::decode(&mut list)?;
+            ensure!(list.len() >= item.payload_length, "L2 block {number} is truncated");
+            let tx = if item.list {
+                &start[..start.len() - list.len() + item.payload_length]
+            } else {
+                &list[..item.payload_length]
+            };
+            transactions.push(Bytes::copy_from_slice(tx));
+            list = &list[item.payload_length..];
+        }
+        rlp_list(&mut buf).wrap_err_with(|| format!("L2 block {number} ommers"))?;
+        let withdrawals = header
+            .withdrawals_root
+            .map(|_| Vec::<Withdrawal>::decode(&mut buf))
+            .transpose()
+            .wrap_err_with(|| format!("invalid withdrawals in L2 block {number}"))?;
+
+        let word = |value: u128| {
+            u64::try_from(value).map_err(|_| eyre!("L2 block {number} has an oversized field"))
+        };
+        let v1 = ExecutionPayloadV1 {
+            parent_hash: header.parent_hash,
+            fee_recipient: header.beneficiary,
+            state_root: header.state_root,
+            receipts_root: header.receipts_root,
+            logs_bloom: header.logs_bloom,
+            prev_randao: header.mix_hash,
+            block_number: number,
+            gas_limit: word(header.gas_limit)?,
+            gas_used: word(header.gas_used)?,
+            timestamp: header.timestamp,
+            extra_data: header.extra_data,
+            base_fee_per_gas: U256::from(header.base_fee_per_gas.unwrap_or_default()),
+            block_hash: self.hash,
+            transactions,
+        };
+        let payload = match (withdrawals, header.blob_gas_used) {
+            (None, _) => ExecutionPayload::V1(v1),
+            (Some(withdrawals), None) => {
+                ExecutionPayload::V2(ExecutionPayloadV2 { payload_inner: v1, withdrawals })
+            }
+            (Some(withdrawals), Some(blob_gas_used)) => ExecutionPayload::V3(ExecutionPayloadV3 {
+                payload_inner: ExecutionPayloadV2 { payload_inner: v1, withdrawals },
+                blob_gas_used: word(blob_gas_used)?,
+                excess_blob_gas: word(header.excess_blob_gas.unwrap_or_default())?,
+            }),
+        };
+        // The withdrawals root commits to the `L2ToL1MessagePasser` storage from Isthmus onwards.
+        let withdrawals_root =
+            header.withdrawals_root.filter(|_| config.is_isthmus_active(header.timestamp));
+        Ok(EnginePayload {
+            payload,
+            parent_beacon_block_root: header.parent_beacon_block_root,
+            withdrawals_root,
+        })
+    }
+}
+
+/// Reads the next item of `buf`, which must be a list, returning its payload.
+fn rlp_list<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let header = alloy_rlp::Header::decode(buf)?;
+    ensure!(header.list, "expected an RLP list");
+    ensure!(buf.len() >= header.payload_length, "truncated RLP list");
+    let (list, rest) = buf.split_at(header.payload_length);
+    *buf = rest;
+    Ok(list)
+}
+
+/// Writes the records of an archive file.
+#[derive(Debug)]
+pub struct EraWriter<W> {
+    writer: W,
+    position: u64,
+    first: Option<u64>,
+    offsets: Vec<u64>,
+}
+
+impl<W: Write> EraWriter<W> {
+    /// Starts a file in `writer`, writing its version record.
+    pub fn new(writer: W) -> Result<Self> {
+        let mut era = Self { writer, position: 0, first: None, offsets: Vec::new() };
+        era.record(VERSION, &[])?;
+        Ok(era)
+    }
+
+    /// Appends `block`, which must follow the previously appended one.
+    pub fn append(&mut self, block: &EraBlock) -> Result<()> {
+        let expected = self.first.map(|first| first + self.offsets.len() as u64);
+        if let Some(expected) = expected.filter(|expected| *expected != block.number) {
+            bail!("expected L2 block {expected} in the archive, got {}", block.number);
+        }
+        self.first.get_or_insert(block.number);
+        self.offsets.push(self.position);
+        self.record(L2_BLOCK, &block.raw)?;
+        if let Some(anchor) = block.l1_anchor {
+            let mut data = [0; 40];
+            data[..32].copy_from_slice(anchor.hash.as_slice());
+            data[32..].copy_from_slice(&anchor.number.to_le_bytes());
+            self.record(L1_ANCHOR, &data)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of blocks appended.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Returns true if no block was appended.
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Writes the block index, returning the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        let index = self.position;
+        let mut data = Vec::with_capacity(16 + 8 * self.offsets.len());
+        data.extend_from_slice(&self.first.unwrap_or_default().to_le_bytes());
+        for offset in &self.offsets {
+            data.extend_from_slice(&(*offset as i64 - index as i64).to_le_bytes());
+        }
+        data.extend_from_slice(&(self.offsets.len() as u64).to_le_bytes());
+        self.record(BLOCK_INDEX, &data)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn record(&mut self, kind: [u8; 2], data: &[u8]) -> Result<()> {
+        let length = u32::try_from(data.len()).wrap_err("archive record too large")?;
+        let mut header = [0; HEADER_SIZE as usize];
+        header[..2].copy_from_slice(&kind);
+        header[2..6].copy_from_slice(&length.to_le_bytes());
+        self.writer.write_all(&header)?;
+        self.writer.write_all(data)?;
+        self.position += HEADER_SIZE + data.len() as u64;
+        Ok(())
+    }
+}
+
+/// Reads the blocks of an archive file of `size` bytes, checking them against its block index.
+pub fn read_era(mut reader: impl Read, size: u64) -> Result<Vec<EraBlock>> {
+    let mut blocks: Vec<EraBlock> = Vec::new();
+    let mut offsets = Vec::new();
+    let mut position = 0;
+    let mut index = None;
+    while let Some((kind, data)) = read_record(&mut reader, size - position)? {
+        ensure!(index.is_none(), "archive has records after its block index");
+        let record = position;
+        position += HEADER_SIZE + data.len() as u64;
+        match kind {
+            VERSION if record == 0 => {}
+            _ if record == 0 => bail!("archive does not start with a version record"),
+            L2_BLOCK => {
+                offsets.push(record);
+                blocks.push(EraBlock::decode(data.into(), None)?);
+            }
+            L1_ANCHOR => {
+                let block = blocks.last_mut().filter(|block| block.l1_anchor.is_none());
+                let Some(block) = block else { bail!("L1 anchor without an L2 block") };
+                ensure!(data.len() == 40, "L1 anchor of block {} is malformed", block.number);
+                let number = u64::from_le_bytes(data[32..].try_into().unwrap());
+                block.l1_anchor = Some(BlockId::new(B256::from_slice(&data[..32]), number));
+            }
+            BLOCK_INDEX => index = Some((record, data)),
+            // Unknown records are skipped, as e2store readers do.
+            _ => {}
+        }
+    }
+
+    let Some((at, index)) = index else { bail!("archive has no block index") };
+    ensure!(index.len() == 16 + 8 * blocks.len(), "block index does not match the blocks");
+    let word = |i: usize| u64::from_le_bytes(index[i * 8..i * 8 + 8].try_into().unwrap());
+    let count = word(index.len() / 8 - 1);
+    ensure!(count == blocks.len() as u64, "block index counts {count} blocks");
+    for (i, (block, offset)) in blocks.iter().zip(&offsets).enumerate() {
+        let number = word(0)
+            .checked_add(i as u64)
+            .ok_or_else(|| eyre!("block index starts past the last L2 block number"))?;
+        ensure!(block.number == number, "expected L2 block {number}, got {}", block.number);
+        let indexed = at.checked_add_signed(word(i + 1) as i64);
+        ensure!(indexed == Some(*offset), "block index is off for block {number}");
+    }
+    Ok(blocks)
+}
+
+/// Reads the next record within the `remaining` bytes of the file, or `None` at its end.
+fn read_record(reader: &mut impl Read, remaining: u64) -> Result<Option<([u8; 2], Vec<u8>)>> {
+    let mut header = [0; HEADER_SIZE as usize];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    ensure!(header[6..] == [0, 0], "archive record has non-zero reserved bytes");
+    let length = u32::from_le_bytes(header[2..6].try_into().unwrap());
+    ensure!(
+        HEADER_SIZE + u64::from(length) <= remaining,
+        "archive record of {length} bytes overruns the file"
+    );
+    let mut data = vec![0; length as usize];
+    reader.read_exact(&mut data).wrap_err("truncated archive record")?;
+    Ok(Some(([header[0], header[1]], data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_rlp::Encodable;
+
+    use super::*;
+
+    /// Returns the RLP-encoded block of `header` with the typed `transactions`, no ommers and
+    /// withdrawals if the header commits to them.
+    pub(crate) fn raw_block(header: &Header, transactions: &[Bytes]) -> Bytes {
+        let mut txs = Vec::new();
+        for tx in transactions {
+            tx.as_ref().encode(&mut txs);
+        }
+        let mut payload = Vec::new();
+        header.encode(&mut payload);
+        alloy_rlp::Header { list: true, payload_length: txs.len() }.encode(&mut payload);
+        payload.extend_from_slice(&txs);
+        payload.push(alloy_rlp::EMPTY_LIST_CODE);
+        if header.withdrawals_root.is_some() {
+            payload.push(alloy_rlp::EMPTY_LIST_CODE);
+        }
+        let mut raw = Vec::new();
+        alloy_rlp::Header { list: true, payload_length: payload.len() }.encode(&mut raw);
+        raw.extend_from_slice(&payload);
+        raw.into()
+    }
+
+    fn empty_block(number: u64) -> Bytes {
+        raw_block(&Header { number, ..Default::default() }, &[])
+    }
+
+    #[test]
+    fn roundtrips_archived_blocks() {
+        let anchor = BlockId::new(B256::repeat_byte(1), 100);
+        let blocks = [
+            EraBlock::decode(empty_block(8), Some(anchor)).unwrap(),
+            EraBlock::decode(empty_block(9), None).unwrap(),
+            EraBlock::decode(empty_block(10), Some(anchor)).unwrap(),
+        ];
+        assert_eq!(blocks[0].hash, Header { number: 8, ..Default::default() }.hash_slow());
+
+        let mut era = EraWriter::new(Vec::new()).unwrap();
+        for block in &blocks {
+            era.append(block).unwrap();
+        }
+        let err = era.append(&blocks[0]).unwrap_err();
+        assert!(err.to_string().contains("expected L2 block 11"), "{err}");
+        let file = era.finish().unwrap();
+        assert_eq!(file[..8], [0x65, 0x32, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(read_era(file.as_slice(), file.len() as u64).unwrap(), blocks);
+
+        let config = RollupConfig::from_registry(10).unwrap();
+        let header = Header {
+            number: 11,
+            timestamp: 7,
+            withdrawals_root: Some(B256::repeat_byte(2)),
+            blob_gas_used: Some(0),
+            excess_blob_gas: Some(0),
+            parent_beacon_block_root: Some(B256::repeat_byte(3)),
+            ..Default::default()
+        };
+        let tx = Bytes::from_static(&[0x7e, 0xc0]);
+        let block = EraBlock::decode(raw_block(&header, std::slice::from_ref(&tx)), None).unwrap();
+        let payload = block.payload(&config).unwrap();
+        assert_eq!(payload.block_id(), BlockId::new(header.hash_slow(), 11));
+        assert_eq!(payload.payload.as_v1().transactions, [tx]);
+        assert!(matches!(payload.payload, ExecutionPayload::V3(_)));
+        assert_eq!(payload.parent_beacon_block_root, header.parent_beacon_block_root);
+        // Isthmus is not active yet at that timestamp.
+        assert_eq!(payload.withdrawals_root, None);
+
+        // A truncated file has no index.
+        let truncated = &file[..file.len() - 8];
+        assert!(read_era(truncated, truncated.len() as u64).is_err());
+        assert!(read_era(&file[8..], file.len() as u64 - 8).is_err());
+
+        // A record longer than the file is refused before it is read.
+        let mut oversized = file.clone();
+        oversized[10..14].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = read_era(oversized.as_slice(), oversized.len() as u64).unwrap_err();
+        assert!(err.to_string().contains("overruns the file"), "{err}");
+
+        // An index whose offsets or numbers overflow is refused.
+        for (at, word) in [(file.len() - 32, i64::MIN as u64), (file.len() - 40, u64::MAX)] {
+            let mut corrupt = file.clone();
+            corrupt[at..at + 8].copy_from_slice(&word.to_le_bytes());
+            assert!(read_era(corrupt.as_slice(), corrupt.len() as u64).is_err());
+        }
+    }
+}
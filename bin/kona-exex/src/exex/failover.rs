@@ -0,0 +1,300 @@
+//! Failing over to a secondary L1 source when the notification stream stalls.
+//!
+//! A wedged host node stops sending chain notifications, and derivation with them. The
+//! [`L1Failover`] forwards the notifications of the primary stream to derivation, and once none
+//! arrived for its stall timeout, follows a secondary L1 RPC with an [`L1Watcher`] from the last
+//! block it forwarded, until the primary resumes.
+//!
+//! Every notification is checked against the chain forwarded so far, so that derivation sees a
+//! single consistent chain across switches: blocks the secondary forwarded already are dropped
+//! from the notifications of the resumed primary, and blocks the secondary forwarded on a fork
+//! the primary does not follow are reverted. A notification that does not build on the forwarded
+//! chain at all means the sources disagree beyond repair, and stops the failover.
+
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use eyre::{bail, eyre, Result};
+use metrics::{counter, gauge};
+use tokio::{sync::mpsc, time::Instant};
+use tracing::{debug, info, warn};
+
+use crate::{
+    exex::{ChainNotification, L1Watcher, DEFAULT_L1_POLL_INTERVAL},
+    l1::ChainProvider,
+    protocol::BlockInfo,
+};
+
+/// The default time without primary notifications after which the secondary source is followed.
+pub const DEFAULT_FAILOVER_STALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The number of forwarded blocks kept to check notifications against.
+const FORWARDED_HISTORY: usize = 1024;
+
+/// Forwards the notifications of a primary stream, failing over to a secondary L1 source while
+/// the primary is stalled.
+#[derive(Debug)]
+pub struct L1Failover {
+    secondary: Arc<dyn ChainProvider>,
+    stall_timeout: Duration,
+    interval: Duration,
+    outcome: bool,
+    /// The watcher of the secondary source, while failed over.
+    watcher: Option<L1Watcher>,
+    /// The canonical blocks forwarded last, oldest first.
+    chain: VecDeque<BlockInfo>,
+}
+
+impl L1Failover {
+    /// Creates a failover forwarding the blocks after `start`, following `secondary` once the
+    /// primary sent nothing for `stall_timeout`.
+    pub fn new(
+        secondary: Arc<dyn ChainProvider>,
+        start: BlockInfo,
+        stall_timeout: Duration,
+    ) -> Self {
+        Self {
+            secondary,
+            stall_timeout,
+            interval: DEFAULT_L1_POLL_INTERVAL,
+            outcome: false,
+            watcher: None,
+            chain: VecDeque::from([start]),
+        }
+    }
+
+    /// Polls the secondary source every `interval` while failed over.
+    pub const fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Attaches the execution outcome of the committed blocks to the notifications of the
+    /// secondary source, as the primary does.
+    pub const fn with_execution_outcome(mut self, outcome: bool) -> Self {
+        self.outcome = outcome;
+        self
+    }
+
+    /// Returns the last block forwarded as canonical.
+    pub fn head(&self) -> BlockInfo {
+        *self.chain.back().expect("the forwarded chain is never empty")
+    }
+
+    /// Returns true while the secondary source is followed.
+    pub const fn is_failed_over(&self) -> bool {
+        self.watcher.is_some()
+    }
+
+    /// Returns the forwarded block at `number`, if still kept.
+    fn forwarded(&self, number: u64) -> Option<BlockInfo> {
+        let index = number.checked_sub(self.chain.front()?.number)?;
+        self.chain.get(usize::try_from(index).ok()?).copied()
+    }
+
+    /// Reverts the forwarded blocks from `number` onwards, returning them oldest first.
+    fn revert_from(&mut self, number: u64) -> Vec<BlockInfo> {
+        let mut reverted = Vec::new();
+        while self.chain.len() > 1 && self.head().number >= number {
+            reverted.push(self.chain.pop_back().expect("checked above"));
+        }
+        reverted.reverse();
+        reverted
+    }
+
+    /// Turns a notification of either source into the change it makes to the forwarded chain,
+    /// or `None` if it was forwarded already.
+    pub fn reconcile(
+        &mut self,
+        mut notification: ChainNotification,
+    ) -> Result<Option<ChainNotification>> {
+        let Some(first) = notification.committed.first().copied() else {
+            // Only reverts: undo them if their blocks were forwarded, the secondary may have
+            // moved to the new fork already.
+            let Some(oldest) = notification.reverted.first() else { return Ok(None) };
+            if self.forwarded(oldest.number) != Some(*oldest) || self.chain.len() == 1 {
+                return Ok(None);
+            }
+            let reverted = self.revert_from(oldest.number);
+            return Ok(Some(ChainNotification { reverted, ..Default::default() }));
+        };
+
+        let Some(parent) = first.number.checked_sub(1).and_then(|number| self.forwarded(number))
+        else {
+            counter!("hera_l1_failover_inconsistencies_total").increment(1);
+            bail!("L1 block {first} does not connect to the forwarded chain at {}", self.head());
+        };
+        if parent.hash != first.parent_hash {
+            counter!("hera_l1_failover_inconsistencies_total").increment(1);
+            bail!("L1 sources disagree: {first} does not build on the forwarded block {parent}");
+        }
+
+        let forwarded = notification
+            .committed
+            .iter()
+            .take_while(|block| self.forwarded(block.number) == Some(**block))
+            .count();
+        if forwarded == notification.committed.len() {
+            debug!(target: "hera::exex", head = %first, "L1 blocks forwarded already");
+            return Ok(None);
+        }
+        let committed = notification.committed.split_off(forwarded);
+        if let Some(outcome) = &mut notification.outcome {
+            outcome.headers.drain(..forwarded.min(outcome.headers.len()));
+            outcome.receipts.drain(..forwarded.min(outcome.receipts.len()));
+        }
+        let reverted = self.revert_from(committed[0].number);
+        if !reverted.is_empty() {
+            let reverted = reverted.len();
+            debug!(target: "hera::exex", reverted, "Reverting forwarded L1 blocks");
+        }
+        for block in &committed {
+            self.chain.push_back(*block);
+            if self.chain.len() > FORWARDED_HISTORY {
+                self.chain.pop_front();
+            }
+        }
+        Ok(Some(ChainNotification { reverted, committed, outcome: notification.outcome }))
+    }
+
+    /// Polls the secondary source, starting to follow it if not yet.
+    async fn poll_secondary(&mut self) -> Result<Option<ChainNotification>> {
+        let head = self.head();
+        let watcher = self.watcher.get_or_insert_with(|| {
+            warn!(target: "hera::exex", %head, "L1 notifications stalled, following the secondary");
+            counter!("hera_l1_failover_activations_total").increment(1);
+            gauge!("hera_l1_failover_active").set(1.0);
+            L1Watcher::new(self.secondary.clone(), head, self.interval)
+                .with_execution_outcome(self.outcome)
+        });
+        match watcher.poll().await {
+            Ok(Some(notification)) => self.reconcile(notification),
+            Ok(None) => Ok(None),
+            Err(err) if err.is_recoverable() => {
+                warn!(target: "hera::exex", %err, "Failed to poll the secondary L1");
+                Ok(None)
+            }
+            Err(err) => Err(eyre!(err).wrap_err("failed to follow the secondary L1")),
+        }
+    }
+
+    /// Forwards the notifications of `primary` to `notifications` until either is closed,
+    /// following the secondary source while the primary is stalled.
+    pub async fn run(
+        &mut self,
+        primary: &mut mpsc::Receiver<ChainNotification>,
+        notifications: mpsc::Sender<ChainNotification>,
+    ) -> Result<()> {
+        gauge!("hera_l1_failover_active").set(0.0);
+        let mut last_primary = Instant::now();
+        loop {
+            let deadline = if self.is_failed_over() {
+                Instant::now() + self.interval
+            } else {
+                last_primary + self.stall_timeout
+            };
+            let notification = tokio::select! {
+                received = primary.recv() => {
+                    let Some(notification) = received else { return Ok(()) };
+                    last_primary = Instant::now();
+                    if self.watcher.take().is_some() {
+                        let head = self.head();
+                        info!(target: "hera::exex", %head, "L1 notifications resumed");
+                        gauge!("hera_l1_failover_active").set(0.0);
+                    }
+                    self.reconcile(notification)?
+                }
+                _ = tokio::time::sleep_until(deadline) => self.poll_secondary().await?,
+            };
+            if let Some(notification) = notification {
+                if notifications.send(notification).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::B256;
+
+    use super::*;
+    use crate::l1::mock::MockL1;
+
+    fn notification(reverted: &[BlockInfo], committed: &[BlockInfo]) -> ChainNotification {
+        ChainNotification {
+            reverted: reverted.to_vec(),
+            committed: committed.to_vec(),
+            outcome: None,
+        }
+    }
+
+    /// Returns a block of a fork off `parent`.
+    fn fork(parent: BlockInfo) -> BlockInfo {
+        BlockInfo::new(B256::repeat_byte(0xf0), parent.number + 1, parent.hash, parent.timestamp)
+    }
+
+    #[test]
+    fn reconciles_the_resumed_primary_with_the_forwarded_chain() {
+        let l1 = MockL1::new(0, 10);
+        let block = |number| l1.block(number);
+        let mut failover =
+            L1Failover::new(Arc::new(MockL1::default()), block(0), DEFAULT_FAILOVER_STALL_TIMEOUT);
+
+        // The secondary forwarded blocks 1 to 3, then a fork block 4.
+        failover.reconcile(notification(&[], &[block(1), block(2), block(3)])).unwrap().unwrap();
+        failover.reconcile(notification(&[], &[fork(block(3))])).unwrap().unwrap();
+
+        // The primary resumes with blocks 2 to 5: 2 and 3 were forwarded, the fork is reverted.
+        let resumed = failover
+            .reconcile(notification(&[], &[block(2), block(3), block(4), block(5)]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(resumed.reverted, [fork(block(3))]);
+        assert_eq!(resumed.committed, [block(4), block(5)]);
+        assert_eq!(failover.head(), block(5));
+        assert_eq!(failover.reconcile(notification(&[], &[block(5)])).unwrap(), None);
+
+        // Reverts of blocks never forwarded are dropped.
+        assert_eq!(failover.reconcile(notification(&[fork(block(5))], &[])).unwrap(), None);
+        let reverted = failover.reconcile(notification(&[block(5)], &[])).unwrap().unwrap();
+        assert_eq!(reverted.reverted, [block(5)]);
+        assert_eq!(failover.head(), block(4));
+
+        // Blocks that do not connect to the forwarded chain stop the failover.
+        let err = failover.reconcile(notification(&[], &[block(7)])).unwrap_err();
+        assert!(err.to_string().contains("does not connect"), "{err}");
+        let err = failover.reconcile(notification(&[], &[fork(fork(block(3)))])).unwrap_err();
+        assert!(err.to_string().contains("disagree"), "{err}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn follows_the_secondary_while_the_primary_is_stalled() {
+        let l1 = Arc::new(MockL1::new(0, 4));
+        let stall_timeout = Duration::from_secs(30);
+        let mut failover = L1Failover::new(l1.clone(), l1.block(0), stall_timeout)
+            .with_poll_interval(Duration::from_secs(1));
+        let (primary_tx, mut primary) = mpsc::channel(4);
+        let (notifications, mut forwarded) = mpsc::channel(4);
+        primary_tx.send(notification(&[], &[l1.block(1)])).await.unwrap();
+
+        let run = tokio::spawn(async move {
+            failover.run(&mut primary, notifications).await.unwrap();
+            failover
+        });
+        assert_eq!(forwarded.recv().await.unwrap().committed, [l1.block(1)]);
+
+        // Nothing from the primary: the secondary serves blocks 2 and 3 after the stall timeout.
+        let started = Instant::now();
+        assert_eq!(forwarded.recv().await.unwrap().committed, [l1.block(2), l1.block(3)]);
+        assert!(started.elapsed() >= stall_timeout);
+
+        // The primary resumes with what was forwarded already.
+        primary_tx.send(notification(&[], &[l1.block(2), l1.block(3)])).await.unwrap();
+        drop(primary_tx);
+        let failover = run.await.unwrap();
+        assert!(!failover.is_failed_over());
+        assert_eq!(failover.head(), l1.block(3));
+        assert!(forwarded.recv().await.is_none());
+    }
+}
@@ -0,0 +1,338 @@
+//! Handling of the L1 chain notifications reth sends to the ExEx.
+
+use std::{fmt, str::FromStr, sync::Arc};
+
+use eyre::{bail, eyre, Result, WrapErr};
+use metrics::counter;
+use tokio::sync::watch;
+use tracing::{debug, error, trace, warn};
+
+mod failover;
+pub use failover::{L1Failover, DEFAULT_FAILOVER_STALL_TIMEOUT};
+
+mod outcome;
+pub use outcome::ExecutionOutcome;
+
+mod reorg;
+pub use reorg::{PendingReorg, ReorgGuard, DEFAULT_MAX_REORG_DEPTH};
+
+pub mod wal;
+pub use wal::{NotificationGap, NotificationRecord, NotificationWal};
+
+mod watcher;
+pub use watcher::{L1Watcher, DEFAULT_L1_POLL_INTERVAL};
+
+use crate::{
+    altda::DaChallengeWatcher,
+    clock::{unix_now, ClockMonitor, ClockSource},
+    derive::Pipeline,
+    driver::Driver,
+    l1::{retention_boundary, BufferedChainProvider, ChainProvider},
+    protocol::BlockInfo,
+    rpc::SyncStatus,
+    supervisor::Readiness,
+    validation::AttributesValidator,
+};
+
+/// How chain notifications are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NotificationMode {
+    /// Derive from every committed L1 block, replaying the notifications reth kept in its
+    /// write-ahead log since the last finished height.
+    #[default]
+    Full,
+    /// Only follow the L1 head, without deriving. Every notification is marked finished right
+    /// away, so reth neither replays history nor holds back pruning for Hera, which then only
+    /// tracks the unsafe L2 head reported by other components, e.g. for monitoring or relaying.
+    HeadOnly,
+}
+
+impl fmt::Display for NotificationMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Full => "full",
+            Self::HeadOnly => "head-only",
+        })
+    }
+}
+
+impl FromStr for NotificationMode {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "full" => Ok(Self::Full),
+            "head-only" => Ok(Self::HeadOnly),
+            _ => bail!("unknown notification mode {s}, expected full or head-only"),
+        }
+    }
+}
+
+/// An update of the L1 chain, as notified by reth to ExExes: the blocks it reverted and the ones
+/// it committed, each oldest first. A reorg reverts and commits blocks at once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainNotification {
+    /// The blocks reverted.
+    pub reverted: Vec<BlockInfo>,
+    /// The blocks committed.
+    pub committed: Vec<BlockInfo>,
+    /// The execution outcome of the committed blocks, if the notification carries it.
+    pub outcome: Option<ExecutionOutcome>,
+}
+
+impl ChainNotification {
+    /// Returns the new tip of the L1 chain, if blocks were committed.
+    pub fn tip(&self) -> Option<BlockInfo> {
+        self.committed.last().copied()
+    }
+}
+
+/// Handles chain notifications according to a [`NotificationMode`].
+#[derive(Debug)]
+pub struct NotificationHandler<P, V> {
+    mode: NotificationMode,
+    driver: Driver<P, V>,
+    status: watch::Sender<SyncStatus>,
+    reorg_guard: Option<ReorgGuard>,
+    paranoid: bool,
+    readiness: Option<Readiness>,
+    buffer: Option<Arc<BufferedChainProvider>>,
+    clock: Option<Arc<ClockMonitor>>,
+    da_challenges: Option<DaChallengeWatcher>,
+    wal: Option<(NotificationWal, Arc<dyn ChainProvider>)>,
+}
+
+impl<P: Pipeline, V: AttributesValidator> NotificationHandler<P, V> {
+    /// Creates a handler, deriving with `driver` in [`NotificationMode::Full`] mode.
+    pub fn new(mode: NotificationMode, driver: Driver<P, V>) -> Self {
+        let status = driver.status_sender();
+        Self {
+            mode,
+            driver,
+            status,
+            reorg_guard: None,
+            paranoid: false,
+            readiness: None,
+            buffer: None,
+            clock: None,
+            da_challenges: None,
+            wal: None,
+        }
+    }
+
+    /// Holds derivation back on L1 reorgs deeper than the maximum depth of `guard`, until they
+    /// are confirmed.
+    pub fn with_reorg_guard(mut self, guard: ReorgGuard) -> Self {
+        self.reorg_guard = Some(guard);
+        self
+    }
+
+    /// Verifies the execution outcome of every committed chain against the block headers before
+    /// deriving from it, failing on the first mismatch.
+    ///
+    /// This catches execution bugs of the L1 node before its receipts corrupt derivation, at the
+    /// cost of rebuilding the receipts trie of every L1 block.
+    pub const fn with_paranoid(mut self, paranoid: bool) -> Self {
+        self.paranoid = paranoid;
+        self
+    }
+
+    /// Finishes no height until every service of `readiness` is ready, so that reth keeps the
+    /// notifications of a node that is still starting in its write-ahead log.
+    pub fn with_readiness(mut self, readiness: Readiness) -> Self {
+        self.readiness = Some(readiness);
+        self
+    }
+
+    /// Buffers the L1 data of committed blocks in `buffer`, pruning it behind the retention
+    /// boundary of the safe head as derivation advances.
+    pub fn with_buffer(mut self, buffer: Arc<BufferedChainProvider>) -> Self {
+        self.buffer = Some(buffer);
+        self
+    }
+
+    /// Records the timestamps of new L1 heads in `clock`.
+    pub fn with_clock(mut self, clock: Arc<ClockMonitor>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Watches the data availability challenges of an Alt-DA chain with `watcher`, deriving again
+    /// without the blocks derived from commitments whose challenge expired.
+    pub fn with_da_challenges(mut self, watcher: DaChallengeWatcher) -> Self {
+        self.da_challenges = Some(watcher);
+        self
+    }
+
+    /// Records every notification in `wal` before handling it. The L1 blocks missed since the
+    /// last recorded notification, e.g. while the node was down, are read from `l1` and handled
+    /// first, as a notification committing them, recorded once handled.
+    pub fn with_wal(mut self, wal: NotificationWal, l1: Arc<dyn ChainProvider>) -> Self {
+        self.wal = Some((wal, l1));
+        self
+    }
+
+    /// Returns the driver.
+    pub const fn driver(&mut self) -> &mut Driver<P, V> {
+        &mut self.driver
+    }
+
+    /// Handles a notification, returning the L1 height reth may consider finished by Hera, if it
+    /// changed.
+    ///
+    /// When deriving, this is the last block behind the [retention boundary](retention_boundary)
+    /// of the safe head: a reset reads L1 again from that boundary, so reth must keep the blocks
+    /// past it, and the buffer is pruned at the same height. Reverted blocks need no handling
+    /// here, the pipeline detects the reorg when reading L1.
+    /// While a deep reorg awaits confirmation, nothing is derived and no height is finished, and
+    /// no height is finished either until the services of the node are ready.
+    pub async fn handle(&mut self, notification: &ChainNotification) -> Result<Option<u64>> {
+        self.record(notification).await?;
+        let finished = self.derive(notification, true).await?;
+        if let Some(readiness) = &self.readiness {
+            if finished.is_some() && !readiness.is_ready() {
+                trace!(target: "hera::exex", pending = ?readiness.pending(), "Services not ready");
+                return Ok(None);
+            }
+        }
+        Ok(finished)
+    }
+
+    /// Records `notification` in the write-ahead log, if any, handling the L1 blocks missed
+    /// before it first.
+    async fn record(&mut self, notification: &ChainNotification) -> Result<()> {
+        let Some((wal, l1)) = &self.wal else { return Ok(()) };
+        if let Some(gap) = wal.gap(notification)? {
+            let missed = gap.blocks();
+            if !missed.is_empty() {
+                warn!(
+                    target: "hera::exex",
+                    from = missed.start(),
+                    to = missed.end(),
+                    "Missed chain notifications, backfilling from L1"
+                );
+            }
+            let mut committed = Vec::new();
+            for number in missed {
+                committed.push(l1.block_info_by_number(number).await?);
+            }
+            // The pipeline detects the reorg when reading L1, as for notified reverts.
+            let linked = gap.check(&committed).wrap_err("L1 disagrees with the notified chain")?;
+            if !linked {
+                counter!("hera_exex_missed_reorgs_total").increment(1);
+                warn!(
+                    target: "hera::exex",
+                    tip = %gap.tip,
+                    parent = %gap.parent,
+                    "L1 reorged below the logged tip while notifications were missed"
+                );
+            }
+            if !committed.is_empty() {
+                counter!("hera_exex_backfilled_blocks_total").increment(committed.len() as u64);
+                let missed = ChainNotification { reverted: Vec::new(), committed, outcome: None };
+                self.derive(&missed, false).await?;
+                let (wal, _) = self.wal.as_ref().expect("checked above");
+                wal.append(&missed)?;
+            }
+        }
+        let (wal, _) = self.wal.as_ref().expect("checked above");
+        wal.append(notification)
+    }
+
+    /// Handles a notification according to the mode, returning the finished height. Only
+    /// notifications from the host carry an execution outcome to `verify` in paranoid mode, not
+    /// the ones backfilling missed blocks from L1.
+    async fn derive(
+        &mut self,
+        notification: &ChainNotification,
+        verify: bool,
+    ) -> Result<Option<u64>> {
+        if let Some(buffer) = self.buffer.as_ref().filter(|_| self.mode == NotificationMode::Full) {
+            buffer.commit(notification);
+        }
+        let held = self.mode == NotificationMode::Full &&
+            self.reorg_guard.as_ref().is_some_and(|guard| guard.hold(notification));
+        let Some(tip) = notification.tip() else {
+            trace!(target: "hera::exex", reverted = notification.reverted.len(), "L1 chain reverted");
+            return Ok(None);
+        };
+        self.status.send_modify(|status| status.head_l1 = tip);
+        if let Some(clock) = &self.clock {
+            clock.record(ClockSource::L1, tip.timestamp, unix_now());
+        }
+
+        match self.mode {
+            NotificationMode::HeadOnly => {
+                trace!(target: "hera::exex", head = %tip, "Following L1 head");
+                Ok(Some(tip.number))
+            }
+            NotificationMode::Full => {
+                if held {
+                    return Ok(None);
+                }
+                if self.paranoid && verify {
+                    self.verify_outcome(notification)?;
+                }
+                self.check_challenges(notification).await?;
+                let advanced = self.driver.advance().await?;
+                let (origin, safe_head) = {
+                    let status = self.status.borrow();
+                    (status.current_l1, status.safe_l2)
+                };
+                let boundary = retention_boundary(self.driver.config(), &safe_head);
+                if let Some(buffer) = &self.buffer {
+                    buffer.prune(boundary);
+                }
+                debug!(
+                    target: "hera::exex",
+                    head = %tip, %origin, boundary, advanced, "Derived from L1"
+                );
+                Ok(Some(boundary.saturating_sub(1)))
+            }
+        }
+    }
+
+    /// Feeds the notified blocks to the challenge watcher, resetting the driver to before the
+    /// earliest commitment whose challenge expired if blocks were already derived from it.
+    async fn check_challenges(&mut self, notification: &ChainNotification) -> Result<()> {
+        let Some(watcher) = &mut self.da_challenges else { return Ok(()) };
+        if let Some(first) = notification.reverted.first() {
+            watcher.revert(first.number);
+        }
+        let mut expired = Vec::new();
+        for block in &notification.committed {
+            expired.extend(watcher.process(block).await?);
+        }
+        let current_l1 = self.status.borrow().current_l1.number;
+        let Some(earliest) = expired
+            .iter()
+            .filter(|commitment| commitment.inclusion_block <= current_l1)
+            .min_by_key(|commitment| commitment.inclusion_block)
+        else {
+            return Ok(());
+        };
+
+        warn!(
+            target: "hera::exex",
+            commitment = %earliest.commitment,
+            inclusion_block = earliest.inclusion_block,
+            "Challenge of derived data expired, reorging derived blocks"
+        );
+        counter!("hera_altda_reorgs_total").increment(1);
+        let (safe_head, l1_origin) = watcher.reset_target(earliest.inclusion_block).await?;
+        let reason = format!("challenge of commitment {} expired", earliest.commitment);
+        self.driver.reset(safe_head, l1_origin, &reason).await
+    }
+
+    /// Verifies the execution outcome of the committed blocks of `notification`.
+    fn verify_outcome(&self, notification: &ChainNotification) -> Result<()> {
+        let outcome = notification.outcome.as_ref().ok_or_else(|| {
+            eyre!("paranoid mode requires the execution outcome of committed chains")
+        })?;
+        outcome.verify(&notification.committed).inspect_err(|err| {
+            counter!("hera_l1_verification_failures_total", "kind" => "execution_outcome")
+                .increment(1);
+            error!(target: "hera::exex", %err, "L1 execution outcome does not match the headers");
+        })
+    }
+}
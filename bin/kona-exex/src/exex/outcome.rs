@@ -0,0 +1,43 @@
+//! Cross-validation of the execution outcome carried by committed chains.
+
+use alloy_consensus::Header;
+use eyre::{ensure, Result, WrapErr};
+
+use crate::{
+    l1::{verify::verify_receipts, L1Receipt},
+    protocol::BlockInfo,
+};
+
+/// The execution outcome of the blocks of a committed chain, as reth hands it to ExExes: the
+/// header of each block and the receipts produced executing it, oldest block first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionOutcome {
+    /// The headers of the committed blocks.
+    pub headers: Vec<Header>,
+    /// The receipts of each committed block, in transaction order.
+    pub receipts: Vec<Vec<L1Receipt>>,
+}
+
+impl ExecutionOutcome {
+    /// Checks that the outcome covers exactly the `committed` blocks, and that the receipts of
+    /// each block hash to its receipts root and add up to its logs bloom.
+    ///
+    /// A mismatch means the L1 node executed a block differently from the network, and its
+    /// receipts must not reach derivation.
+    pub fn verify(&self, committed: &[BlockInfo]) -> Result<()> {
+        ensure!(
+            self.headers.len() == committed.len() && self.receipts.len() == committed.len(),
+            "execution outcome covers {} headers and {} receipt sets for {} committed blocks",
+            self.headers.len(),
+            self.receipts.len(),
+            committed.len()
+        );
+        for ((block, header), receipts) in committed.iter().zip(&self.headers).zip(&self.receipts) {
+            let hash = header.hash_slow();
+            ensure!(hash == block.hash, "execution outcome has header {hash} for block {block}");
+            verify_receipts(header, receipts)
+                .wrap_err_with(|| format!("invalid execution outcome for block {block}"))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+//! Protection against L1 reorgs deeper than expected.
+
+use std::sync::{Arc, Mutex};
+
+use eyre::{eyre, Result};
+use metrics::gauge;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{exex::ChainNotification, protocol::BlockInfo};
+
+/// The default maximum depth of an L1 reorg handled without operator confirmation: two beacon
+/// chain epochs, past which the reverted blocks were finalized.
+pub const DEFAULT_MAX_REORG_DEPTH: u64 = 64;
+
+/// An L1 reorg deeper than the maximum depth, awaiting operator confirmation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingReorg {
+    /// The number of L1 blocks reverted.
+    pub depth: u64,
+    /// The oldest L1 block reverted.
+    pub first_reverted: BlockInfo,
+    /// The new L1 tip, if blocks were committed along with the revert.
+    pub new_tip: Option<BlockInfo>,
+}
+
+/// Holds derivation back on L1 reorgs deeper than a maximum depth until the operator confirms
+/// them through `admin_confirmReorg`.
+///
+/// Such a reorg is more likely a misbehaving L1 source than a real reorg, and unwinding the L2
+/// chain on it could roll back a lot of blocks. Cloned handles share the pending reorg, so the
+/// RPC server can confirm what the notification handler holds back.
+#[derive(Debug, Clone)]
+pub struct ReorgGuard {
+    max_depth: u64,
+    pending: Arc<Mutex<Option<PendingReorg>>>,
+}
+
+impl ReorgGuard {
+    /// Creates a guard holding back reorgs deeper than `max_depth` L1 blocks.
+    pub fn new(max_depth: u64) -> Self {
+        Self { max_depth, pending: Arc::default() }
+    }
+
+    /// Returns the maximum reorg depth handled without confirmation.
+    pub const fn max_depth(&self) -> u64 {
+        self.max_depth
+    }
+
+    /// Returns the reorg awaiting confirmation, if any.
+    pub fn pending(&self) -> Option<PendingReorg> {
+        *self.pending.lock().unwrap()
+    }
+
+    /// Confirms the pending reorg, letting derivation unwind. Fails if no reorg is pending.
+    pub fn confirm(&self) -> Result<PendingReorg> {
+        let reorg = self
+            .pending
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| eyre!("no reorg awaits confirmation"))?;
+        gauge!("hera_reorg_confirmation_pending").set(0.0);
+        info!(target: "hera::exex", depth = reorg.depth, "L1 reorg confirmed by the operator");
+        Ok(reorg)
+    }
+
+    /// Checks a notification, returning true if derivation must be held back, either on this
+    /// notification or on a reorg still awaiting confirmation.
+    pub(crate) fn hold(&self, notification: &ChainNotification) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.is_some() {
+            return true;
+        }
+        let depth = notification.reverted.len() as u64;
+        let Some(first_reverted) = notification.reverted.first().copied() else { return false };
+        if depth <= self.max_depth {
+            return false;
+        }
+
+        let reorg = PendingReorg { depth, first_reverted, new_tip: notification.tip() };
+        error!(
+            target: "hera::exex",
+            depth,
+            max_depth = self.max_depth,
+            %first_reverted,
+            "L1 reorg deeper than the maximum depth, halting derivation until confirmed with \
+             admin_confirmReorg"
+        );
+        gauge!("hera_reorg_confirmation_pending").set(1.0);
+        *pending = Some(reorg);
+        true
+    }
+}
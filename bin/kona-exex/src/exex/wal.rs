@@ -0,0 +1,263 @@
+//! A write-ahead log of the chain notifications received, to find the ones missed across
+//! crashes.
+//!
+//! Every notification is recorded before it is handled, reduced to the L1 block it builds on,
+//! the tip it leaves the chain at and how many blocks it reverted and committed. A notification
+//! building on a block past the logged tip shows that the blocks in between were never
+//! notified, e.g. because the host dropped notifications while Hera was down, and exactly those
+//! are backfilled from L1, checked to link by hash from the logged tip to that block: if they do
+//! not build on the logged tip, L1 reorged below it in the meantime. Notifications below the
+//! logged tip are replays, as sent by reth from its own write-ahead log after a restart, and need
+//! no backfill.
+
+use std::{ops::RangeInclusive, path::Path, sync::Arc};
+
+use alloy_primitives::B256;
+use eyre::{bail, ensure, Result, WrapErr};
+
+use crate::{
+    exex::ChainNotification,
+    protocol::{BlockId, BlockInfo},
+    storage::{SqliteStorage, Storage},
+};
+
+/// The table of notification records, keyed by big-endian sequence number.
+const NOTIFICATIONS: &str = "notifications";
+
+/// The number of records kept, older ones being dropped as new ones are appended.
+pub const DEFAULT_WAL_RECORDS: u64 = 100_000;
+
+/// The size of an encoded [`NotificationRecord`].
+const RECORD_SIZE: usize = 96;
+
+/// What the write-ahead log keeps of a chain notification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotificationRecord {
+    /// The block the notification builds on: the parent of the first block reverted, or of the
+    /// first block committed if none was reverted.
+    pub parent: BlockId,
+    /// The tip of the chain after the notification.
+    pub tip: BlockId,
+    /// The number of blocks reverted.
+    pub reverted: u64,
+    /// The number of blocks committed.
+    pub committed: u64,
+}
+
+impl NotificationRecord {
+    /// Returns the record of `notification`, `None` if it neither reverts nor commits blocks.
+    pub fn new(notification: &ChainNotification) -> Option<Self> {
+        let first = notification.reverted.first().or_else(|| notification.committed.first())?;
+        let parent = BlockId::new(first.parent_hash, first.number.saturating_sub(1));
+        Some(Self {
+            parent,
+            tip: notification.tip().map_or(parent, |tip| tip.id()),
+            reverted: notification.reverted.len() as u64,
+            committed: notification.committed.len() as u64,
+        })
+    }
+
+    fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut out = [0; RECORD_SIZE];
+        out[..32].copy_from_slice(self.parent.hash.as_slice());
+        out[32..40].copy_from_slice(&self.parent.number.to_be_bytes());
+        out[40..72].copy_from_slice(self.tip.hash.as_slice());
+        out[72..80].copy_from_slice(&self.tip.number.to_be_bytes());
+        out[80..88].copy_from_slice(&self.reverted.to_be_bytes());
+        out[88..].copy_from_slice(&self.committed.to_be_bytes());
+        out
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        ensure!(bytes.len() == RECORD_SIZE, "notification record has {} bytes", bytes.len());
+        let number = |range: RangeInclusive<usize>| {
+            u64::from_be_bytes(bytes[range].try_into().expect("8 bytes"))
+        };
+        Ok(Self {
+            parent: BlockId::new(B256::from_slice(&bytes[..32]), number(32..=39)),
+            tip: BlockId::new(B256::from_slice(&bytes[40..72]), number(72..=79)),
+            reverted: number(80..=87),
+            committed: number(88..=95),
+        })
+    }
+}
+
+/// The L1 blocks never notified between the logged tip and the block a notification builds on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotificationGap {
+    /// The tip of the chain after the last recorded notification.
+    pub tip: BlockId,
+    /// The block the next notification builds on.
+    pub parent: BlockId,
+}
+
+impl NotificationGap {
+    /// Returns the numbers of the missed blocks, empty if the notification builds on a block at
+    /// the height of the logged tip.
+    pub const fn blocks(&self) -> RangeInclusive<u64> {
+        self.tip.number + 1..=self.parent.number
+    }
+
+    /// Checks that the missed `blocks` link by hash up to the block the notification builds on,
+    /// returning whether they build on the logged tip too: if not, L1 reorged below the logged
+    /// tip while notifications were missed.
+    pub fn check(&self, blocks: &[BlockInfo]) -> Result<bool> {
+        let Some(first) = blocks.first() else { return Ok(self.tip == self.parent) };
+        for pair in blocks.windows(2) {
+            if pair[1].parent_hash != pair[0].hash {
+                bail!("missed L1 block {} does not build on {}", pair[1].id(), pair[0].id());
+            }
+        }
+        let last = blocks[blocks.len() - 1].id();
+        ensure!(last == self.parent, "missed L1 blocks end at {last}, not at {}", self.parent);
+        Ok(first.parent_hash == self.tip.hash)
+    }
+}
+
+/// The write-ahead log of chain notifications.
+#[derive(Debug)]
+pub struct NotificationWal {
+    storage: Arc<dyn Storage>,
+    retained: u64,
+}
+
+impl NotificationWal {
+    /// Creates a log in `storage`.
+    pub fn new(storage: Arc<dyn Storage>) -> Self {
+        Self { storage, retained: DEFAULT_WAL_RECORDS }
+    }
+
+    /// Opens the log in the SQLite database at `path`, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let storage = SqliteStorage::open(path).wrap_err_with(|| {
+            format!("failed to open notification write-ahead log {}", path.display())
+        })?;
+        Ok(Self::new(Arc::new(storage)))
+    }
+
+    /// Keeps the last `retained` records only.
+    pub fn with_retained(mut self, retained: u64) -> Self {
+        self.retained = retained.max(1);
+        self
+    }
+
+    /// Returns the last record with its sequence number, if any.
+    pub fn last(&self) -> Result<Option<(u64, NotificationRecord)>> {
+        let entry = self
+            .storage
+            .floor(NOTIFICATIONS, &u64::MAX.to_be_bytes())
+            .wrap_err("failed to read the last notification record")?;
+        let Some((key, value)) = entry else { return Ok(None) };
+        let sequence = u64::from_be_bytes(
+            key.as_slice().try_into().wrap_err("malformed notification record key")?,
+        );
+        Ok(Some((sequence, NotificationRecord::decode(&value)?)))
+    }
+
+    /// Returns the L1 blocks never notified before `notification`, if it builds on a block past
+    /// the logged tip or on another block at its height.
+    pub fn gap(&self, notification: &ChainNotification) -> Result<Option<NotificationGap>> {
+        let (Some(record), Some((_, last))) = (NotificationRecord::new(notification), self.last()?)
+        else {
+            return Ok(None);
+        };
+        let missed = record.parent.number > last.tip.number ||
+            (record.parent.number == last.tip.number && record.parent != last.tip);
+        Ok(missed.then_some(NotificationGap { tip: last.tip, parent: record.parent }))
+    }
+
+    /// Records `notification`, dropping the records past the retained ones.
+    pub fn append(&self, notification: &ChainNotification) -> Result<()> {
+        let Some(record) = NotificationRecord::new(notification) else { return Ok(()) };
+        let sequence = self.last()?.map_or(0, |(sequence, _)| sequence + 1);
+        self.storage
+            .put(NOTIFICATIONS, &[(&sequence.to_be_bytes(), &record.encode())])
+            .wrap_err_with(|| format!("failed to record notification up to {}", record.tip))?;
+        if let Some(dropped) = sequence.checked_sub(self.retained) {
+            self.storage
+                .remove(NOTIFICATIONS, &[&dropped.to_be_bytes()])
+                .wrap_err("failed to drop old notification records")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    /// The blocks of a chain whose hashes are their numbers.
+    fn block(number: u64) -> BlockInfo {
+        let hash = |number: u64| B256::left_padding_from(&number.to_be_bytes());
+        BlockInfo::new(hash(number), number, hash(number.saturating_sub(1)), number * 12)
+    }
+
+    fn notification(
+        reverted: impl IntoIterator<Item = u64>,
+        committed: impl IntoIterator<Item = u64>,
+    ) -> ChainNotification {
+        ChainNotification {
+            reverted: reverted.into_iter().map(block).collect(),
+            committed: committed.into_iter().map(block).collect(),
+            outcome: None,
+        }
+    }
+
+    #[test]
+    fn finds_blocks_missed_since_the_logged_tip() {
+        let wal = NotificationWal::new(Arc::new(MemoryStorage::new())).with_retained(2);
+        let first = notification([], 10..=12);
+        assert_eq!(wal.gap(&first).unwrap(), None);
+        wal.append(&first).unwrap();
+
+        // A reorg of the tip continues the log, a replay of older blocks too.
+        let reorg = notification(12..=12, 12..=13);
+        assert_eq!(wal.gap(&reorg).unwrap(), None);
+        wal.append(&reorg).unwrap();
+        assert_eq!(wal.gap(&notification([], 5..=6)).unwrap(), None);
+
+        let (sequence, last) = wal.last().unwrap().unwrap();
+        assert_eq!(sequence, 1);
+        assert_eq!(
+            last,
+            NotificationRecord {
+                parent: block(11).id(),
+                tip: block(13).id(),
+                reverted: 1,
+                committed: 2
+            }
+        );
+
+        // Blocks 14 to 16 were never notified.
+        let gap = wal.gap(&notification([], 17..=18)).unwrap().unwrap();
+        assert_eq!(gap, NotificationGap { tip: block(13).id(), parent: block(16).id() });
+        assert_eq!(gap.blocks(), 14..=16);
+        assert_eq!(wal.gap(&notification(17..=17, 17..=17)).unwrap(), Some(gap));
+        let missed: Vec<_> = (14..=16).map(block).collect();
+        assert!(gap.check(&missed).unwrap());
+
+        // L1 reorged below the logged tip in the meantime.
+        let mut reorged = missed.clone();
+        reorged[0].parent_hash = B256::repeat_byte(0xee);
+        assert!(!gap.check(&reorged).unwrap());
+        // The missed blocks do not link up to the block the notification builds on.
+        reorged[1].parent_hash = B256::repeat_byte(0xee);
+        assert!(gap.check(&reorged).is_err());
+        assert!(gap.check(&missed[..2]).is_err());
+
+        // A notification building on another block at the height of the logged tip.
+        let mut other = notification([], 14..=14);
+        other.committed[0].parent_hash = B256::repeat_byte(0xee);
+        let gap = wal.gap(&other).unwrap().unwrap();
+        assert!(gap.blocks().is_empty());
+        assert!(!gap.check(&[]).unwrap());
+
+        wal.append(&notification([], 14..=14)).unwrap();
+        wal.append(&notification([], 15..=15)).unwrap();
+        assert!(wal.storage.get(NOTIFICATIONS, &1u64.to_be_bytes()).unwrap().is_none());
+        assert!(wal.storage.get(NOTIFICATIONS, &2u64.to_be_bytes()).unwrap().is_some());
+        assert_eq!(wal.last().unwrap().unwrap().0, 3);
+    }
+}
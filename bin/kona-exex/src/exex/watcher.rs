@@ -0,0 +1,259 @@
+//! Chain notifications for Hera running outside of reth.
+//!
+//! Without a host node sending ExEx notifications, the [`L1Watcher`] polls an L1
+//! [`ChainProvider`] for new blocks and turns them into the [`ChainNotification`]s reth would
+//! have sent, detecting reorgs by walking back the blocks it notified until the canonical chain
+//! builds on one of them again.
+
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use eyre::{eyre, Result};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::{
+    exex::{ChainNotification, ExecutionOutcome},
+    l1::{ChainProvider, ProviderError, ProviderResult},
+    protocol::BlockInfo,
+    supervisor::ReadyHandle,
+};
+
+/// The default interval between two polls of the L1 head.
+pub const DEFAULT_L1_POLL_INTERVAL: Duration = Duration::from_secs(4);
+
+/// The maximum number of blocks committed by a single notification while catching up.
+const MAX_COMMITTED_BLOCKS: usize = 64;
+
+/// The number of notified blocks kept to find the fork point of reorgs.
+const WATCHED_HISTORY: usize = 1024;
+
+/// Polls an L1 provider for new blocks, notifying them as reth would.
+#[derive(Debug)]
+pub struct L1Watcher {
+    l1: Arc<dyn ChainProvider>,
+    interval: Duration,
+    outcome: bool,
+    ready: Option<ReadyHandle>,
+    /// The canonical blocks notified last, oldest first.
+    chain: VecDeque<BlockInfo>,
+}
+
+impl L1Watcher {
+    /// Creates a watcher notifying the blocks after `start`, polling `l1` every `interval`.
+    pub fn new(l1: Arc<dyn ChainProvider>, start: BlockInfo, interval: Duration) -> Self {
+        Self { l1, interval, outcome: false, ready: None, chain: VecDeque::from([start]) }
+    }
+
+    /// Attaches the execution outcome of the committed blocks to every notification, as read
+    /// from the provider.
+    pub const fn with_execution_outcome(mut self, outcome: bool) -> Self {
+        self.outcome = outcome;
+        self
+    }
+
+    /// Reports the watcher ready through `ready` once it reached the provider.
+    pub fn set_ready(&mut self, ready: ReadyHandle) {
+        self.ready = Some(ready);
+    }
+
+    /// Returns the last block notified as canonical.
+    pub fn head(&self) -> BlockInfo {
+        *self.chain.back().expect("the watched chain is never empty")
+    }
+
+    /// Polls the provider once, returning the blocks reverted and committed since the last
+    /// poll, if any.
+    pub async fn poll(&mut self) -> ProviderResult<Option<ChainNotification>> {
+        let mut reverted = Vec::new();
+        let mut committed: Vec<BlockInfo> = Vec::new();
+        while committed.len() < MAX_COMMITTED_BLOCKS {
+            let head = self.head();
+            let block = match self.l1.block_info_by_number(head.number + 1).await {
+                Ok(block) => block,
+                Err(ProviderError::NotFound(_)) => break,
+                Err(err) => return Err(err),
+            };
+            if block.parent_hash != head.hash {
+                if !committed.is_empty() {
+                    // The chain reorged while polling, the next poll reverts what was committed.
+                    break;
+                }
+                if self.chain.len() == 1 {
+                    return Err(ProviderError::InvalidData(eyre!(
+                        "L1 reorg past the {} blocks watched, {block} does not build on {head}",
+                        WATCHED_HISTORY
+                    )));
+                }
+                reverted.push(self.chain.pop_back().expect("checked above"));
+                continue;
+            }
+            committed.push(block);
+            self.chain.push_back(block);
+            if self.chain.len() > WATCHED_HISTORY {
+                self.chain.pop_front();
+            }
+        }
+        if reverted.is_empty() && committed.is_empty() {
+            return Ok(None);
+        }
+        reverted.reverse();
+
+        let outcome = if self.outcome && !committed.is_empty() {
+            let mut outcome = ExecutionOutcome::default();
+            for block in &committed {
+                outcome.headers.push(self.l1.header_by_hash(block.hash).await?);
+                outcome.receipts.push(self.l1.receipts_by_hash(block.hash).await?);
+            }
+            Some(outcome)
+        } else {
+            None
+        };
+        Ok(Some(ChainNotification { reverted, committed, outcome }))
+    }
+
+    /// Polls the provider until `notifications` is closed, sending every change of the chain.
+    ///
+    /// Recoverable provider errors are retried on the next poll.
+    pub async fn run(&mut self, notifications: mpsc::Sender<ChainNotification>) -> Result<()> {
+        loop {
+            let polled = self.poll().await;
+            if let (Ok(_), Some(ready)) = (&polled, &self.ready) {
+                ready.ready();
+            }
+            let caught_up = match polled {
+                Ok(Some(notification)) => {
+                    let caught_up = notification.committed.len() < MAX_COMMITTED_BLOCKS;
+                    debug!(
+                        target: "hera::exex",
+                        reverted = notification.reverted.len(),
+                        committed = notification.committed.len(),
+                        head = %self.head(),
+                        "L1 chain updated"
+                    );
+                    if notifications.send(notification).await.is_err() {
+                        return Ok(());
+                    }
+                    caught_up
+                }
+                Ok(None) => true,
+                Err(err) if err.is_recoverable() => {
+                    warn!(target: "hera::exex", %err, "Failed to poll the L1 chain");
+                    true
+                }
+                Err(err) => return Err(eyre!(err).wrap_err("failed to follow the L1 chain")),
+            };
+            if caught_up {
+                tokio::time::sleep(self.interval).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use alloy_consensus::Header;
+    use alloy_primitives::B256;
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::l1::{L1Receipt, L1Transaction};
+
+    /// An L1 chain whose blocks are identified by a fork byte.
+    #[derive(Debug, Default)]
+    struct TestChain {
+        blocks: Mutex<Vec<BlockInfo>>,
+    }
+
+    fn block(number: u64, fork: u8, parent_fork: u8) -> BlockInfo {
+        let hash = |number: u64, fork: u8| {
+            let mut hash = B256::left_padding_from(&number.to_be_bytes());
+            hash[0] = fork;
+            hash
+        };
+        BlockInfo::new(hash(number, fork), number, hash(number.wrapping_sub(1), parent_fork), 0)
+    }
+
+    impl TestChain {
+        /// Replaces the blocks from `from` onwards with `len` blocks of `fork`.
+        fn extend(&self, from: u64, len: u64, fork: u8) {
+            let mut blocks = self.blocks.lock().unwrap();
+            blocks.truncate(from as usize);
+            for number in from..from + len {
+                let parent_fork = blocks.last().map_or(0, |parent: &BlockInfo| parent.hash[0]);
+                blocks.push(block(number, fork, parent_fork));
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ChainProvider for TestChain {
+        async fn header_by_hash(&self, hash: B256) -> ProviderResult<Header> {
+            Err(ProviderError::NotFound(format!("header {hash}")))
+        }
+
+        async fn block_info_by_number(&self, number: u64) -> ProviderResult<BlockInfo> {
+            let blocks = self.blocks.lock().unwrap();
+            blocks
+                .get(number as usize)
+                .copied()
+                .ok_or_else(|| ProviderError::NotFound(format!("L1 block {number}")))
+        }
+
+        async fn receipts_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Receipt>> {
+            Err(ProviderError::NotFound(format!("receipts {hash}")))
+        }
+
+        async fn transactions_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Transaction>> {
+            Err(ProviderError::NotFound(format!("transactions {hash}")))
+        }
+    }
+
+    fn numbers(blocks: &[BlockInfo]) -> Vec<u64> {
+        blocks.iter().map(|block| block.number).collect()
+    }
+
+    #[tokio::test]
+    async fn follows_the_chain_in_batches() {
+        let chain = Arc::new(TestChain::default());
+        chain.extend(0, 100, 0);
+        let start = chain.block_info_by_number(10).await.unwrap();
+        let mut watcher = L1Watcher::new(chain.clone(), start, DEFAULT_L1_POLL_INTERVAL);
+
+        let notification = watcher.poll().await.unwrap().unwrap();
+        assert!(notification.reverted.is_empty());
+        assert_eq!(numbers(&notification.committed), (11..75).collect::<Vec<_>>());
+        let notification = watcher.poll().await.unwrap().unwrap();
+        assert_eq!(numbers(&notification.committed), (75..100).collect::<Vec<_>>());
+        assert_eq!(watcher.poll().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn reverts_reorged_blocks() {
+        let chain = Arc::new(TestChain::default());
+        chain.extend(0, 20, 0);
+        let start = chain.block_info_by_number(0).await.unwrap();
+        let mut watcher = L1Watcher::new(chain.clone(), start, DEFAULT_L1_POLL_INTERVAL);
+        watcher.poll().await.unwrap().unwrap();
+
+        // Blocks 17 to 19 are replaced by a longer fork.
+        chain.extend(17, 5, 1);
+        let notification = watcher.poll().await.unwrap().unwrap();
+        assert_eq!(numbers(&notification.reverted), [17, 18, 19]);
+        assert_eq!(notification.reverted[0].hash[0], 0);
+        assert_eq!(numbers(&notification.committed), [17, 18, 19, 20, 21]);
+        assert!(notification.committed.iter().all(|block| block.hash[0] == 1));
+        assert_eq!(watcher.head(), chain.block_info_by_number(21).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn fails_past_the_watched_history() {
+        let chain = Arc::new(TestChain::default());
+        chain.extend(0, 5, 0);
+        let start = chain.block_info_by_number(4).await.unwrap();
+        let mut watcher = L1Watcher::new(chain.clone(), start, DEFAULT_L1_POLL_INTERVAL);
+        chain.extend(3, 3, 1);
+        assert!(!watcher.poll().await.unwrap_err().is_recoverable());
+    }
+}
@@ -0,0 +1,235 @@
+//! Tracking of the L2 fee parameters.
+//!
+//! The parameters of the L1 data fee and of the operator fee of every L2 block are set by its L1
+//! info deposit: the L1 base fees of its L1 origin, and the scalars of the `SystemConfig` in
+//! effect. The [`FeeTracker`] records them as derivation advances, so that fee estimation
+//! services can look up the values in effect at past blocks through `hera_feeParams`.
+//!
+//! The `GasPriceOracle` predeploy computes the L1 data fee from these parameters with the formula
+//! of the hardforks it was upgraded to. `hera_gasPriceOracleConfig` combines both, so that
+//! estimators reproduce the fee of past blocks without replaying the upgrade transactions.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use alloy_primitives::U256;
+use eyre::{eyre, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::RollupConfig,
+    protocol::{
+        l1_info::{L1BlockInfoEcotone, L1BlockInfoTx},
+        BlockId, TxDeposit,
+    },
+};
+
+/// The number of fee parameter changes kept by default, about a week of L1 blocks.
+pub const DEFAULT_FEE_HISTORY_LEN: usize = 50_000;
+
+/// The fee parameters of an L2 block, as set by its L1 info deposit.
+///
+/// Fields of parameters that do not exist in the protocol version of the block are `None`: the
+/// Bedrock overhead and scalar are replaced by the Ecotone scalars, and the operator fee only
+/// exists from Isthmus onwards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeParams {
+    /// The L1 origin of the block.
+    pub l1_origin: BlockId,
+    /// The base fee of the L1 origin.
+    pub l1_base_fee: u64,
+    /// The blob base fee of the L1 origin.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob_base_fee: Option<u128>,
+    /// The Bedrock L1 fee overhead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub l1_fee_overhead: Option<U256>,
+    /// The Bedrock L1 fee scalar.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub l1_fee_scalar: Option<U256>,
+    /// The Ecotone base fee scalar.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_fee_scalar: Option<u32>,
+    /// The Ecotone blob base fee scalar.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob_base_fee_scalar: Option<u32>,
+    /// The Isthmus operator fee scalar.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator_fee_scalar: Option<u32>,
+    /// The Isthmus operator fee constant.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator_fee_constant: Option<u64>,
+}
+
+impl FeeParams {
+    /// Decodes the fee parameters from the EIP-2718 encoded L1 info deposit of a block.
+    pub fn from_l1_info_tx(tx: &[u8]) -> Result<Self> {
+        let deposit = TxDeposit::decode_2718(&mut &tx[..])?;
+        let info = L1BlockInfoTx::decode_calldata(&deposit.input)?;
+        Ok(Self::from(&info))
+    }
+}
+
+impl From<&L1BlockInfoTx> for FeeParams {
+    fn from(info: &L1BlockInfoTx) -> Self {
+        let ecotone = |info: &L1BlockInfoEcotone| Self {
+            l1_origin: BlockId::new(info.block_hash, info.number),
+            l1_base_fee: info.base_fee,
+            blob_base_fee: Some(info.blob_base_fee),
+            base_fee_scalar: Some(info.base_fee_scalar),
+            blob_base_fee_scalar: Some(info.blob_base_fee_scalar),
+            ..Default::default()
+        };
+        match info {
+            L1BlockInfoTx::Bedrock(info) => Self {
+                l1_origin: BlockId::new(info.block_hash, info.number),
+                l1_base_fee: info.base_fee,
+                l1_fee_overhead: Some(info.l1_fee_overhead),
+                l1_fee_scalar: Some(info.l1_fee_scalar),
+                ..Default::default()
+            },
+            L1BlockInfoTx::Ecotone(info) => ecotone(info),
+            L1BlockInfoTx::Isthmus(info) | L1BlockInfoTx::Interop(info) => Self {
+                operator_fee_scalar: Some(info.operator_fee_scalar),
+                operator_fee_constant: Some(info.operator_fee_constant),
+                ..ecotone(&info.ecotone)
+            },
+        }
+    }
+}
+
+/// The configuration of the `GasPriceOracle` predeploy in effect for the transactions of an L2
+/// block: the fee formula it computes the L1 data fee with, and its parameters.
+///
+/// The upgrade deposits of a hardfork activation block switch the formula before the
+/// transactions of the block, except for the Ecotone and Isthmus ones: the `L1Block` values they
+/// read are only set by the L1 info deposit of the next block, so the oracle falls back to the
+/// previous formula until then.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GasPriceOracleConfig {
+    /// Whether the oracle computes the Ecotone L1 data fee, from the blob base fee.
+    pub is_ecotone: bool,
+    /// Whether the oracle computes the Fjord L1 data fee, from the `FastLZ` size estimate.
+    pub is_fjord: bool,
+    /// Whether the oracle charges the Isthmus operator fee.
+    pub is_isthmus: bool,
+    /// The fee parameters set by the L1 info deposit of the block.
+    #[serde(flatten)]
+    pub params: FeeParams,
+}
+
+impl GasPriceOracleConfig {
+    /// Returns the oracle configuration of the L2 block with timestamp `timestamp` and fee
+    /// parameters `params`.
+    pub fn new(config: &RollupConfig, timestamp: u64, params: FeeParams) -> Self {
+        Self {
+            is_ecotone: config.is_ecotone_active(timestamp) && params.base_fee_scalar.is_some(),
+            is_fjord: config.is_fjord_active(timestamp),
+            is_isthmus: config.is_isthmus_active(timestamp) && params.operator_fee_scalar.is_some(),
+            params,
+        }
+    }
+}
+
+/// The fee parameters recorded so far: only the blocks where they changed are stored.
+#[derive(Debug, Default)]
+struct FeeHistory {
+    changes: BTreeMap<u64, FeeParams>,
+    latest: Option<u64>,
+}
+
+/// Records the fee parameters of derived L2 blocks, keeping a bounded history of their changes.
+///
+/// Cloned handles share the history, so the driver can record what the RPC server serves.
+#[derive(Debug, Clone)]
+pub struct FeeTracker {
+    history: Arc<Mutex<FeeHistory>>,
+    max_changes: usize,
+}
+
+impl Default for FeeTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_FEE_HISTORY_LEN)
+    }
+}
+
+impl FeeTracker {
+    /// Creates a tracker keeping the last `max_changes` changes of the fee parameters.
+    pub fn new(max_changes: usize) -> Self {
+        Self { history: Arc::default(), max_changes: max_changes.max(1) }
+    }
+
+    /// Records the fee parameters of the L2 block `number`, decoded from its EIP-2718 encoded
+    /// L1 info deposit.
+    pub fn record_l1_info_tx(&self, number: u64, tx: Option<&[u8]>) -> Result<()> {
+        let tx = tx.ok_or_else(|| eyre!("L2 block {number} has no L1 info deposit"))?;
+        let params = FeeParams::from_l1_info_tx(tx)
+            .wrap_err_with(|| format!("invalid L1 info deposit in L2 block {number}"))?;
+        self.record(number, params);
+        Ok(())
+    }
+
+    /// Records the fee parameters of the L2 block `number`.
+    ///
+    /// Blocks are expected in order: recording a block at or below the latest one, as after a
+    /// reorg, drops the history past it first.
+    pub fn record(&self, number: u64, params: FeeParams) {
+        let mut history = self.history.lock().unwrap();
+        if history.latest.is_some_and(|latest| number <= latest) {
+            history.changes.split_off(&number);
+        }
+        if history.changes.last_key_value().map(|(_, last)| last) != Some(&params) {
+            history.changes.insert(number, params);
+            if history.changes.len() > self.max_changes {
+                history.changes.pop_first();
+            }
+        }
+        history.latest = Some(number);
+    }
+
+    /// Returns the fee parameters of the L2 block `number`, if it is within the recorded history.
+    pub fn at(&self, number: u64) -> Option<FeeParams> {
+        let history = self.history.lock().unwrap();
+        if history.latest.map_or(true, |latest| number > latest) {
+            return None;
+        }
+        history.changes.range(..=number).next_back().map(|(_, params)| *params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switches_the_oracle_formula_after_activation_blocks() {
+        let mut config = RollupConfig::from_registry(10).unwrap();
+        config.genesis.l2 = BlockId::new(Default::default(), 0);
+        config.genesis.l2_time = 0;
+        config.block_time = 2;
+        config.ecotone_time = Some(100);
+        config.fjord_time = Some(200);
+        config.isthmus_time = None;
+        let bedrock = FeeParams { l1_fee_scalar: Some(U256::from(684_000)), ..Default::default() };
+        let ecotone = FeeParams { base_fee_scalar: Some(1_368), ..Default::default() };
+
+        let at = |number: u64, params| {
+            let oracle =
+                GasPriceOracleConfig::new(&config, config.l2_block_timestamp(number), params);
+            (oracle.is_ecotone, oracle.is_fjord)
+        };
+        assert_eq!(at(49, bedrock), (false, false));
+        // The Ecotone activation block still has a Bedrock L1 info deposit.
+        assert_eq!(at(50, bedrock), (false, false));
+        assert_eq!(at(51, ecotone), (true, false));
+        assert_eq!(at(100, ecotone), (true, true));
+
+        let json = serde_json::to_value(GasPriceOracleConfig::new(&config, 102, ecotone)).unwrap();
+        assert_eq!(json["isEcotone"], true);
+        assert_eq!(json["baseFeeScalar"], 1_368);
+    }
+}
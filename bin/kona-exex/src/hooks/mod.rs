@@ -0,0 +1,133 @@
+//! Hooks notifying applications embedding Hera of L2 chain events.
+//!
+//! Indexers, bridges and other applications running Hera in-process register callbacks on
+//! [`ChainHooks`], which then follows the sync status of the node and calls them as the unsafe,
+//! safe and finalized heads move, and when the unsafe chain reorgs.
+
+use std::fmt;
+
+use tokio::sync::watch;
+
+use crate::{protocol::L2BlockInfo, rpc::SyncStatus};
+
+/// A callback on a new head.
+type HeadHook = Box<dyn Fn(L2BlockInfo) + Send + Sync>;
+
+/// A callback on a reorg.
+type ReorgHook = Box<dyn Fn(L2Reorg) + Send + Sync>;
+
+/// A reorg of the unsafe L2 chain: the new head does not build on the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct L2Reorg {
+    /// The unsafe head before the reorg.
+    pub old: L2BlockInfo,
+    /// The unsafe head after the reorg.
+    pub new: L2BlockInfo,
+}
+
+impl L2Reorg {
+    /// Returns the reorg from `old` to `new`, if `new` does not extend the chain of `old`.
+    ///
+    /// Only a head moving back, replaced at the same height, or advanced by a single block on
+    /// another parent is known to be a reorg: heads advancing by several blocks at once, as when
+    /// derivation catches up, are assumed to extend the chain.
+    pub fn detect(old: L2BlockInfo, new: L2BlockInfo) -> Option<Self> {
+        let (old_number, new_number) = (old.block_info.number, new.block_info.number);
+        let reorged = if new_number <= old_number {
+            new != old
+        } else {
+            new_number == old_number + 1 && new.block_info.parent_hash != old.block_info.hash
+        };
+        reorged.then_some(Self { old, new })
+    }
+
+    /// Returns the number of blocks the unsafe head moved back by, counting a head replaced at
+    /// the same height or the next one as one.
+    pub fn depth(&self) -> u64 {
+        self.old.block_info.number.saturating_sub(self.new.block_info.number).max(1)
+    }
+}
+
+/// Callbacks on L2 chain events, registered by embedders.
+///
+/// Hooks run on the task following the sync status, in registration order: they should return
+/// quickly, handing any slow work off to tasks of their own.
+#[derive(Default)]
+pub struct ChainHooks {
+    unsafe_head: Vec<HeadHook>,
+    safe_head: Vec<HeadHook>,
+    finalized: Vec<HeadHook>,
+    reorg: Vec<ReorgHook>,
+}
+
+impl fmt::Debug for ChainHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChainHooks")
+            .field("unsafe_head", &self.unsafe_head.len())
+            .field("safe_head", &self.safe_head.len())
+            .field("finalized", &self.finalized.len())
+            .field("reorg", &self.reorg.len())
+            .finish()
+    }
+}
+
+impl ChainHooks {
+    /// Creates a registry without hooks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `hook` with every new unsafe head.
+    pub fn on_unsafe_head(mut self, hook: impl Fn(L2BlockInfo) + Send + Sync + 'static) -> Self {
+        self.unsafe_head.push(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` with every new safe head.
+    pub fn on_safe_head(mut self, hook: impl Fn(L2BlockInfo) + Send + Sync + 'static) -> Self {
+        self.safe_head.push(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` with every new finalized head.
+    pub fn on_finalized(mut self, hook: impl Fn(L2BlockInfo) + Send + Sync + 'static) -> Self {
+        self.finalized.push(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` on every reorg of the unsafe chain, before the unsafe head hooks are called
+    /// with the new head.
+    pub fn on_reorg(mut self, hook: impl Fn(L2Reorg) + Send + Sync + 'static) -> Self {
+        self.reorg.push(Box::new(hook));
+        self
+    }
+
+    /// Calls the hooks of the events between two sync statuses.
+    pub fn dispatch(&self, old: &SyncStatus, new: &SyncStatus) {
+        if new.unsafe_l2 != old.unsafe_l2 {
+            if let Some(reorg) = L2Reorg::detect(old.unsafe_l2, new.unsafe_l2) {
+                self.reorg.iter().for_each(|hook| hook(reorg));
+            }
+            self.unsafe_head.iter().for_each(|hook| hook(new.unsafe_l2));
+        }
+        if new.safe_l2 != old.safe_l2 {
+            self.safe_head.iter().for_each(|hook| hook(new.safe_l2));
+        }
+        if new.finalized_l2 != old.finalized_l2 {
+            self.finalized.iter().for_each(|hook| hook(new.finalized_l2));
+        }
+    }
+
+    /// Follows the sync status, e.g. from [`Driver::subscribe_status`], calling the hooks until
+    /// it is dropped.
+    ///
+    /// [`Driver::subscribe_status`]: crate::driver::Driver::subscribe_status
+    pub async fn run(self, mut status: watch::Receiver<SyncStatus>) {
+        let mut last = status.borrow_and_update().clone();
+        while status.changed().await.is_ok() {
+            let current = status.borrow_and_update().clone();
+            self.dispatch(&last, &current);
+            last = current;
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! A [`ChainProvider`] buffering the L1 data carried by chain notifications.
+//!
+//! Notifications with an execution outcome carry the headers and receipts derivation reads next,
+//! so they are kept in memory instead of being read again. Blocks are pruned once they fall
+//! behind the [retention boundary](retention_boundary) of the safe head, the same boundary the
+//! finished height reported to reth follows, so that neither drops data the other still needs.
+//!
+//! With a lag, the buffer also hides the blocks close to the notified tip from derivation, so
+//! that shallow reorgs are over by the time derivation reads the blocks they touch.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use alloy_consensus::Header;
+use alloy_primitives::B256;
+use async_trait::async_trait;
+use metrics::gauge;
+
+use crate::{
+    config::RollupConfig,
+    exex::ChainNotification,
+    l1::{ChainProvider, L1Receipt, L1Transaction, ProviderError, ProviderResult},
+    protocol::{BlockInfo, L2BlockInfo},
+};
+
+/// Returns the first L1 block derivation may read again when resuming from `safe_head`.
+///
+/// A reset reads channels from a channel timeout before the L1 origin of the safe head, and the
+/// batches of its epoch may have been posted up to a sequencing window before that.
+pub fn retention_boundary(config: &RollupConfig, safe_head: &L2BlockInfo) -> u64 {
+    let depth = config.channel_timeout(safe_head.block_info.timestamp) + config.seq_window_size;
+    safe_head.l1_origin.number.saturating_sub(depth)
+}
+
+/// A buffered L1 block.
+#[derive(Debug)]
+struct BufferedBlock {
+    info: BlockInfo,
+    header: Header,
+    receipts: Vec<L1Receipt>,
+}
+
+/// Serves the headers and receipts of committed blocks from memory, and everything else from an
+/// inner provider.
+#[derive(Debug)]
+pub struct BufferedChainProvider {
+    inner: Arc<dyn ChainProvider>,
+    blocks: Mutex<BTreeMap<u64, BufferedBlock>>,
+    lag: u64,
+    tip: Mutex<Option<u64>>,
+}
+
+impl BufferedChainProvider {
+    /// Creates an empty buffer in front of `inner`.
+    pub fn new(inner: Arc<dyn ChainProvider>) -> Self {
+        Self { inner, blocks: Mutex::default(), lag: 0, tip: Mutex::new(None) }
+    }
+
+    /// Keeps derivation `lag` blocks behind the tip of the notified L1 chain: blocks past the
+    /// [lagged head](Self::lagged_head) are not found, as if not produced yet.
+    ///
+    /// This delays the unsafe head by `lag` L1 blocks, but reorgs at most `lag` blocks deep
+    /// revert nothing derivation read, sparing the pipeline resets they would cause.
+    pub const fn with_lag(mut self, lag: u64) -> Self {
+        self.lag = lag;
+        self
+    }
+
+    /// Returns the last L1 block derivation may read, `None` if it may read any block, i.e.
+    /// without lag or before the first notification.
+    pub fn lagged_head(&self) -> Option<u64> {
+        let tip = (*self.tip.lock().unwrap())?;
+        (self.lag > 0).then(|| tip.saturating_sub(self.lag))
+    }
+
+    /// Drops the blocks reverted by `notification`, and buffers the blocks it commits if it
+    /// carries their execution outcome.
+    pub fn commit(&self, notification: &ChainNotification) {
+        let mut blocks = self.blocks.lock().unwrap();
+        for reverted in &notification.reverted {
+            blocks.remove(&reverted.number);
+        }
+        let mut tip = self.tip.lock().unwrap();
+        if let Some(first) = notification.reverted.first() {
+            *tip = Some(first.number.saturating_sub(1));
+        }
+        if let Some(last) = notification.tip() {
+            *tip = Some(last.number);
+        }
+        if let Some(outcome) = &notification.outcome {
+            let committed = notification.committed.iter().zip(&outcome.headers);
+            for ((info, header), receipts) in committed.zip(&outcome.receipts) {
+                let block = BufferedBlock {
+                    info: *info,
+                    header: header.clone(),
+                    receipts: receipts.clone(),
+                };
+                blocks.insert(info.number, block);
+            }
+        }
+        gauge!("hera_l1_buffered_blocks").set(blocks.len() as f64);
+    }
+
+    /// Drops the blocks below `boundary`.
+    pub fn prune(&self, boundary: u64) {
+        let mut blocks = self.blocks.lock().unwrap();
+        *blocks = blocks.split_off(&boundary);
+        gauge!("hera_l1_buffered_blocks").set(blocks.len() as f64);
+    }
+
+    /// Returns the number of buffered blocks.
+    pub fn len(&self) -> usize {
+        self.blocks.lock().unwrap().len()
+    }
+
+    /// Returns true if no block is buffered.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.lock().unwrap().is_empty()
+    }
+
+    fn find<T>(&self, hash: B256, read: impl FnOnce(&BufferedBlock) -> T) -> Option<T> {
+        self.blocks.lock().unwrap().values().rev().find(|block| block.info.hash == hash).map(read)
+    }
+}
+
+#[async_trait]
+impl ChainProvider for BufferedChainProvider {
+    async fn header_by_hash(&self, hash: B256) -> ProviderResult<Header> {
+        match self.find(hash, |block| block.header.clone()) {
+            Some(header) => Ok(header),
+            None => self.inner.header_by_hash(hash).await,
+        }
+    }
+
+    async fn block_info_by_number(&self, number: u64) -> ProviderResult<BlockInfo> {
+        if let Some(head) = self.lagged_head().filter(|head| number > *head) {
+            return Err(ProviderError::NotFound(format!(
+                "L1 block {number} past lagged head {head}"
+            )));
+        }
+        let info = self.blocks.lock().unwrap().get(&number).map(|block| block.info);
+        match info {
+            Some(info) => Ok(info),
+            None => self.inner.block_info_by_number(number).await,
+        }
+    }
+
+    async fn receipts_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Receipt>> {
+        match self.find(hash, |block| block.receipts.clone()) {
+            Some(receipts) => Ok(receipts),
+            None => self.inner.receipts_by_hash(hash).await,
+        }
+    }
+
+    async fn transactions_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Transaction>> {
+        self.inner.transactions_by_hash(hash).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::B256;
+
+    use super::*;
+    use crate::{exex::ExecutionOutcome, l1::mock::MockL1, protocol::BlockId};
+
+    fn notification(reverted: Vec<BlockInfo>, committed: Vec<BlockInfo>) -> ChainNotification {
+        let headers =
+            committed.iter().map(|block| Header { number: block.number, ..Default::default() });
+        let outcome = ExecutionOutcome {
+            headers: headers.collect(),
+            receipts: vec![Vec::new(); committed.len()],
+        };
+        ChainNotification { reverted, committed, outcome: Some(outcome) }
+    }
+
+    #[tokio::test]
+    async fn serves_committed_blocks_until_pruned() {
+        let l1 = MockL1::new(0, 4);
+        let inner = l1.block(1);
+        let buffer = BufferedChainProvider::new(Arc::new(l1));
+
+        let blocks: Vec<_> = (10..13)
+            .map(|n| BlockInfo::new(B256::repeat_byte(n as u8), n, B256::ZERO, n))
+            .collect();
+        buffer.commit(&notification(Vec::new(), blocks.clone()));
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.header_by_hash(blocks[1].hash).await.unwrap().number, 11);
+        assert_eq!(buffer.block_info_by_number(12).await.unwrap(), blocks[2]);
+        assert!(buffer.receipts_by_hash(blocks[0].hash).await.unwrap().is_empty());
+        assert_eq!(buffer.block_info_by_number(1).await.unwrap(), inner);
+
+        let reorged = BlockInfo::new(B256::repeat_byte(0xaa), 12, blocks[1].hash, 12);
+        buffer.commit(&notification(vec![blocks[2]], vec![reorged]));
+        assert_eq!(buffer.block_info_by_number(12).await.unwrap(), reorged);
+        assert!(buffer.header_by_hash(blocks[2].hash).await.is_err());
+
+        buffer.prune(11);
+        assert_eq!(buffer.len(), 2);
+        assert!(buffer.block_info_by_number(10).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn hides_blocks_within_the_lag_of_the_tip() {
+        let l1 = MockL1::new(0, 20);
+        let blocks: Vec<_> = (10..=15).map(|n| l1.block(n)).collect();
+        let buffer = BufferedChainProvider::new(Arc::new(l1)).with_lag(3);
+        assert!(buffer.lagged_head().is_none());
+        assert_eq!(buffer.block_info_by_number(15).await.unwrap(), blocks[5]);
+
+        buffer.commit(&notification(Vec::new(), blocks.clone()));
+        assert_eq!(buffer.lagged_head(), Some(12));
+        assert_eq!(buffer.block_info_by_number(12).await.unwrap(), blocks[2]);
+        assert!(matches!(buffer.block_info_by_number(13).await, Err(ProviderError::NotFound(_))));
+
+        buffer.commit(&notification(blocks[4..].to_vec(), Vec::new()));
+        assert_eq!(buffer.lagged_head(), Some(10));
+        let reorged = BlockInfo::new(B256::repeat_byte(0xaa), 14, blocks[3].hash, 14);
+        buffer.commit(&notification(Vec::new(), vec![reorged]));
+        assert_eq!(buffer.lagged_head(), Some(11));
+    }
+
+    #[test]
+    fn retains_a_channel_timeout_and_sequencing_window_behind_the_safe_head() {
+        let config = RollupConfig::from_registry(10).unwrap();
+        let block = BlockInfo::new(B256::ZERO, 100, B256::ZERO, 0);
+        let safe_head = L2BlockInfo::new(block, BlockId::new(B256::ZERO, 5_000), 0);
+        let depth = config.channel_timeout + config.seq_window_size;
+        assert_eq!(retention_boundary(&config, &safe_head), 5_000 - depth);
+
+        let genesis = L2BlockInfo::new(block, BlockId::new(B256::ZERO, 10), 0);
+        assert_eq!(retention_boundary(&config, &genesis), 0);
+    }
+}
@@ -0,0 +1,331 @@
+//! A [`ChainProvider`] reading from a local database of the L1 chain.
+//!
+//! Hera ships no [`L1Database`] implementation: reth is not a dependency of this crate. A node
+//! embedding Hera next to its L1 client implements the trait over that client's storage, for
+//! example reth's `ProviderFactory`, and hands it to the [`DatabaseChainProvider`].
+//!
+//! Derivation cannot proceed without the receipts of the L1 blocks it reads, so a host pruning
+//! receipts must either keep them for the blocks derivation may read again, or come with a
+//! fallback RPC to backfill them. [`DatabaseChainProvider::check_pruning`] tells which at
+//! startup, and reads of pruned receipts fail instead of waiting for data that never shows up.
+
+use std::sync::Arc;
+
+use alloy_consensus::Header;
+use alloy_primitives::B256;
+use async_trait::async_trait;
+use eyre::{bail, Result, WrapErr};
+use metrics::{counter, gauge};
+use tracing::{trace, warn};
+
+use crate::{
+    l1::{ChainProvider, L1Receipt, L1Transaction, ProviderError, ProviderResult},
+    protocol::BlockInfo,
+};
+
+/// Read access to a canonical L1 chain stored locally, to be implemented by the embedding node.
+///
+/// These are the reads derivation needs, a subset of what reth's `ProviderFactory` offers:
+/// implemented over such a provider, it serves historical headers and receipts without any
+/// external L1 RPC. Reads are blocking database accesses, and return `None` for data the store
+/// does not have, such as blocks past its tip or pruned receipts.
+pub trait L1Database: std::fmt::Debug + Send + Sync {
+    /// Returns the hash of the canonical block at `number`.
+    fn canonical_hash(&self, number: u64) -> Result<Option<B256>>;
+
+    /// Returns the header of the block with the given hash.
+    fn header(&self, hash: B256) -> Result<Option<Header>>;
+
+    /// Returns the receipts of the block with the given hash, in transaction order.
+    fn receipts(&self, hash: B256) -> Result<Option<Vec<L1Receipt>>>;
+
+    /// Returns the transactions of the block with the given hash, in block order.
+    fn transactions(&self, hash: B256) -> Result<Option<Vec<L1Transaction>>>;
+
+    /// Returns the first block whose receipts the store keeps if it prunes older ones, as
+    /// follows from reth's receipts prune mode and the current tip.
+    ///
+    /// Receipts only kept for the contracts of reth's receipts log filter count as pruned: the
+    /// batch inbox is no contract, and deposits alone are not enough to derive.
+    fn receipts_pruned_before(&self) -> Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+/// Reads L1 data from an [`L1Database`], optionally falling back to another provider for
+/// data the database does not have.
+#[derive(Debug)]
+pub struct DatabaseChainProvider {
+    db: Arc<dyn L1Database>,
+    fallback: Option<Arc<dyn ChainProvider>>,
+}
+
+impl DatabaseChainProvider {
+    /// Creates a provider reading from `db`.
+    pub fn new(db: Arc<dyn L1Database>) -> Self {
+        Self { db, fallback: None }
+    }
+
+    /// Serves reads missing from the database, such as pruned receipts, from `fallback`.
+    pub fn with_fallback(mut self, fallback: Arc<dyn ChainProvider>) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    /// Runs a blocking database read off the async runtime.
+    async fn read<T, F>(&self, read: F) -> ProviderResult<Option<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn L1Database) -> Result<Option<T>> + Send + 'static,
+    {
+        let db = self.db.clone();
+        let result = tokio::task::spawn_blocking(move || read(db.as_ref()))
+            .await
+            .wrap_err("L1 database read panicked")
+            .and_then(|result| result.wrap_err("L1 database read failed"))
+            .map_err(ProviderError::Transport)?;
+        if result.is_none() {
+            counter!("hera_l1_db_misses_total").increment(1);
+        }
+        Ok(result)
+    }
+
+    /// Checks that the receipts derivation may read from L1 block `from` onwards are available,
+    /// either kept by the database or served by the fallback, e.g. with `from` the
+    /// [retention boundary](crate::l1::retention_boundary) of the safe head.
+    ///
+    /// Fails when the database pruned some of them without a fallback, so that a node embedded
+    /// in a pruned host stops right away rather than stalling on the first pruned block.
+    pub async fn check_pruning(&self, from: u64) -> Result<()> {
+        let pruned_before =
+            self.read(|db| db.receipts_pruned_before()).await.map_err(eyre::Report::new)?;
+        let Some(pruned_before) = pruned_before.filter(|pruned_before| *pruned_before > from)
+        else {
+            return Ok(());
+        };
+        gauge!("hera_l1_db_receipts_pruned_before").set(pruned_before as f64);
+        if self.fallback.is_none() {
+            bail!(
+                "the L1 node prunes receipts before block {pruned_before}, but derivation reads \
+                 them from block {from}: keep receipts in the L1 node or configure an L1 RPC to \
+                 backfill them"
+            );
+        }
+        warn!(
+            target: "hera::l1",
+            pruned_before,
+            from,
+            "L1 node prunes receipts derivation reads, backfilling them from the L1 RPC"
+        );
+        Ok(())
+    }
+
+    fn fallback(&self, what: &str) -> ProviderResult<&dyn ChainProvider> {
+        trace!(target: "hera::l1", what, "Falling back from the L1 database");
+        self.fallback
+            .as_deref()
+            .ok_or_else(|| ProviderError::NotFound(format!("{what} in the L1 database")))
+    }
+}
+
+#[async_trait]
+impl ChainProvider for DatabaseChainProvider {
+    async fn header_by_hash(&self, hash: B256) -> ProviderResult<Header> {
+        match self.read(move |db| db.header(hash)).await? {
+            Some(header) => Ok(header),
+            None => self.fallback(&format!("L1 header {hash}"))?.header_by_hash(hash).await,
+        }
+    }
+
+    async fn block_info_by_number(&self, number: u64) -> ProviderResult<BlockInfo> {
+        let header = self
+            .read(move |db| {
+                let Some(hash) = db.canonical_hash(number)? else { return Ok(None) };
+                Ok(db.header(hash)?.map(|header| (hash, header)))
+            })
+            .await?;
+        match header {
+            Some((hash, header)) => {
+                Ok(BlockInfo::new(hash, number, header.parent_hash, header.timestamp))
+            }
+            None => {
+                self.fallback(&format!("L1 block {number}"))?.block_info_by_number(number).await
+            }
+        }
+    }
+
+    async fn receipts_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Receipt>> {
+        match self.read(move |db| db.receipts(hash)).await? {
+            Some(receipts) => Ok(receipts),
+            None if self.fallback.is_some() => {
+                self.fallback(&format!("receipts of L1 block {hash}"))?.receipts_by_hash(hash).await
+            }
+            None => {
+                let pruned = self
+                    .read(move |db| {
+                        let Some(pruned_before) = db.receipts_pruned_before()? else {
+                            return Ok(None);
+                        };
+                        let header = db.header(hash)?;
+                        Ok(header.filter(|header| header.number < pruned_before).map(|_| ()))
+                    })
+                    .await?;
+                let what = format!("receipts of L1 block {hash}");
+                Err(match pruned {
+                    Some(()) => ProviderError::Pruned(what),
+                    None => ProviderError::NotFound(format!("{what} in the L1 database")),
+                })
+            }
+        }
+    }
+
+    async fn transactions_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Transaction>> {
+        match self.read(move |db| db.transactions(hash)).await? {
+            Some(transactions) => Ok(transactions),
+            None => {
+                self.fallback(&format!("transactions of L1 block {hash}"))?
+                    .transactions_by_hash(hash)
+                    .await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eyre::ensure;
+
+    use super::*;
+    use crate::l1::mock::MockL1;
+
+    /// A database holding the blocks of `l1` up to `tip`, with the receipts of the blocks before
+    /// `pruned` pruned.
+    #[derive(Debug)]
+    struct PrunedDatabase {
+        l1: MockL1,
+        tip: u64,
+        pruned: u64,
+        failing: bool,
+    }
+
+    impl PrunedDatabase {
+        fn number(&self, hash: B256) -> Result<Option<u64>> {
+            ensure!(!self.failing, "database closed");
+            Ok(self
+                .l1
+                .header(hash)?
+                .map(|header| header.number)
+                .filter(|number| *number <= self.tip))
+        }
+    }
+
+    impl L1Database for PrunedDatabase {
+        fn canonical_hash(&self, number: u64) -> Result<Option<B256>> {
+            ensure!(!self.failing, "database closed");
+            if number > self.tip {
+                return Ok(None);
+            }
+            self.l1.canonical_hash(number)
+        }
+
+        fn header(&self, hash: B256) -> Result<Option<Header>> {
+            if self.number(hash)?.is_none() {
+                return Ok(None);
+            }
+            self.l1.header(hash)
+        }
+
+        fn receipts(&self, hash: B256) -> Result<Option<Vec<L1Receipt>>> {
+            if !self.number(hash)?.is_some_and(|number| number >= self.pruned) {
+                return Ok(None);
+            }
+            self.l1.receipts(hash)
+        }
+
+        fn transactions(&self, hash: B256) -> Result<Option<Vec<L1Transaction>>> {
+            if self.number(hash)?.is_none() {
+                return Ok(None);
+            }
+            self.l1.transactions(hash)
+        }
+
+        fn receipts_pruned_before(&self) -> Result<Option<u64>> {
+            Ok((self.pruned > 0).then_some(self.pruned))
+        }
+    }
+
+    /// A provider over the first two of three L1 blocks, with the receipts of block 0 pruned.
+    fn provider(failing: bool) -> DatabaseChainProvider {
+        let db = PrunedDatabase { l1: chain(), tip: 1, pruned: 1, failing };
+        DatabaseChainProvider::new(Arc::new(db))
+    }
+
+    /// Three L1 blocks, the last one with a transaction.
+    fn chain() -> MockL1 {
+        let mut l1 = MockL1::new(1_000, 2);
+        l1.push_block(vec![L1Transaction::default()]);
+        l1
+    }
+
+    #[tokio::test]
+    async fn reads_from_the_database() {
+        let l1 = chain();
+        let provider = provider(false);
+        assert_eq!(provider.block_info_by_number(1).await.unwrap(), l1.block(1));
+        let header = provider.header_by_hash(l1.block(1).hash).await.unwrap();
+        assert_eq!(header.hash_slow(), l1.block(1).hash);
+        assert!(provider.receipts_by_hash(l1.block(1).hash).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn missing_data_without_fallback_is_not_found() {
+        let l1 = chain();
+        let provider = provider(false);
+        assert!(matches!(provider.block_info_by_number(2).await, Err(ProviderError::NotFound(_))));
+        assert!(matches!(
+            provider.receipts_by_hash(l1.block(0).hash).await,
+            Err(ProviderError::Pruned(_))
+        ));
+        assert!(matches!(
+            provider.receipts_by_hash(l1.block(2).hash).await,
+            Err(ProviderError::NotFound(_))
+        ));
+        assert!(matches!(
+            provider.transactions_by_hash(l1.block(2).hash).await,
+            Err(ProviderError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn falls_back_for_missing_data() {
+        let l1 = chain();
+        let provider = provider(false).with_fallback(Arc::new(chain()));
+        assert_eq!(provider.block_info_by_number(2).await.unwrap(), l1.block(2));
+        let header = provider.header_by_hash(l1.block(2).hash).await.unwrap();
+        assert_eq!(header.number, 2);
+        assert_eq!(provider.transactions_by_hash(l1.block(2).hash).await.unwrap().len(), 1);
+        // Pruned receipts of a block the database still has are read from the fallback.
+        assert!(provider.receipts_by_hash(l1.block(0).hash).await.unwrap().is_empty());
+        let receipts = provider.receipts_by_hash(l1.block(2).hash).await.unwrap();
+        assert_eq!(receipts.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn checks_receipts_are_available_from_the_retention_boundary() {
+        provider(false).check_pruning(1).await.unwrap();
+        let err = provider(false).check_pruning(0).await.unwrap_err();
+        assert!(err.to_string().contains("prunes receipts before block 1"), "{err}");
+        provider(false).with_fallback(Arc::new(chain())).check_pruning(0).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn database_failures_do_not_fall_back() {
+        let l1 = chain();
+        let provider = provider(true).with_fallback(Arc::new(chain()));
+        assert!(matches!(provider.block_info_by_number(0).await, Err(ProviderError::Transport(_))));
+        assert!(matches!(
+            provider.header_by_hash(l1.block(0).hash).await,
+            Err(ProviderError::Transport(_))
+        ));
+    }
+}
@@ -0,0 +1,47 @@
+//! Errors of [`ChainProvider`](crate::l1::ChainProvider)s.
+
+use std::fmt;
+
+use eyre::Report;
+
+/// The result of a [`ChainProvider`](crate::l1::ChainProvider) read.
+pub type ProviderResult<T> = Result<T, ProviderError>;
+
+/// An error reading L1 data.
+#[derive(Debug)]
+pub enum ProviderError {
+    /// The data is not known to the provider, e.g. a block past its tip or pruned receipts.
+    NotFound(String),
+    /// The provider could not be reached, or failed to answer.
+    Transport(Report),
+    /// The provider answered with data that is malformed or does not match its block header.
+    InvalidData(Report),
+    /// The data was pruned by the L1 node, and no other provider is configured to serve it.
+    Pruned(String),
+    /// The provider refused a request as too large, e.g. an `eth_getLogs` query over more blocks
+    /// or returning more logs than it allows. A smaller request may succeed.
+    LimitExceeded(Report),
+}
+
+impl ProviderError {
+    /// Returns true if the read may succeed when retried: missing data may show up as L1
+    /// advances, and transport failures are usually transient. Invalid and pruned data are not
+    /// expected to come back without switching providers.
+    pub const fn is_recoverable(&self) -> bool {
+        !matches!(self, Self::InvalidData(_) | Self::Pruned(_))
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(what) => write!(f, "{what} not found"),
+            Self::Transport(err) => write!(f, "L1 provider request failed: {err:#}"),
+            Self::InvalidData(err) => write!(f, "L1 provider returned invalid data: {err:#}"),
+            Self::Pruned(what) => write!(f, "{what} pruned by the L1 node"),
+            Self::LimitExceeded(err) => write!(f, "L1 provider limit exceeded: {err:#}"),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
@@ -0,0 +1,62 @@
+//! A [`ChainProvider`] falling back to a secondary L1 node.
+
+use std::sync::Arc;
+
+use alloy_consensus::Header;
+use alloy_primitives::B256;
+use async_trait::async_trait;
+use metrics::counter;
+use tracing::debug;
+
+use crate::{
+    l1::{ChainProvider, L1Receipt, L1Transaction, ProviderResult},
+    protocol::BlockInfo,
+};
+
+/// Reads from a primary provider, and from a secondary one when the primary fails, e.g. while
+/// the host node is wedged and derivation follows a [failover](crate::exex::L1Failover) source.
+#[derive(Debug)]
+pub struct FallbackChainProvider {
+    primary: Arc<dyn ChainProvider>,
+    secondary: Arc<dyn ChainProvider>,
+}
+
+impl FallbackChainProvider {
+    /// Creates a provider reading from `secondary` what `primary` fails to serve.
+    pub fn new(primary: Arc<dyn ChainProvider>, secondary: Arc<dyn ChainProvider>) -> Self {
+        Self { primary, secondary }
+    }
+}
+
+/// Returns the result of `read` on the primary provider, or on the secondary one if it failed.
+macro_rules! fallback {
+    ($self:ident, $what:literal, $read:ident($arg:expr)) => {
+        match $self.primary.$read($arg).await {
+            Ok(value) => Ok(value),
+            Err(err) => {
+                debug!(target: "hera::l1", %err, what = $what, "Reading from the secondary L1");
+                counter!("hera_l1_fallback_reads_total", "read" => $what).increment(1);
+                $self.secondary.$read($arg).await
+            }
+        }
+    };
+}
+
+#[async_trait]
+impl ChainProvider for FallbackChainProvider {
+    async fn header_by_hash(&self, hash: B256) -> ProviderResult<Header> {
+        fallback!(self, "header", header_by_hash(hash))
+    }
+
+    async fn block_info_by_number(&self, number: u64) -> ProviderResult<BlockInfo> {
+        fallback!(self, "block_info", block_info_by_number(number))
+    }
+
+    async fn receipts_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Receipt>> {
+        fallback!(self, "receipts", receipts_by_hash(hash))
+    }
+
+    async fn transactions_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Transaction>> {
+        fallback!(self, "transactions", transactions_by_hash(hash))
+    }
+}
@@ -0,0 +1,297 @@
+//! A [`ChainProvider`] skipping the receipts of L1 blocks without events derivation reads.
+//!
+//! Derivation reads the receipts of every L1 block it traverses for deposits and system config
+//! updates, although few blocks carry any. When backfilling from an L1 RPC, one
+//! `eth_getBlockReceipts` request per block dominates the time spent. The
+//! [`LogScanningChainProvider`] instead asks which blocks emitted logs of the watched contracts
+//! with `eth_getLogs` over ranges of blocks, and answers with no receipts for the others.
+//!
+//! Ranges are only scanned up to the finalized L1 block, so that the answer of a scan holds for
+//! good. Providers cap the ranges and results of `eth_getLogs` differently: the range shrinks
+//! when a query is refused, and grows back as queries succeed.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    ops::RangeInclusive,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use alloy_consensus::Header;
+use alloy_primitives::{Address, B256};
+use async_trait::async_trait;
+use metrics::{counter, gauge};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::{
+    l1::{ChainProvider, L1Receipt, L1Transaction, ProviderError, ProviderResult},
+    protocol::{BlockId, BlockInfo},
+};
+
+/// The default number of L1 blocks scanned by the first `eth_getLogs` query.
+pub const DEFAULT_LOG_SCAN_RANGE: u64 = 1_000;
+
+/// The default maximum number of L1 blocks scanned by one `eth_getLogs` query.
+pub const DEFAULT_MAX_LOG_SCAN_RANGE: u64 = 10_000;
+
+/// The number of scanned L1 blocks remembered behind the last scanned one, beyond the
+/// sequencing window of the chains of the superchain so that the receipts of epochs read again
+/// by the attributes are skipped too.
+const RETAINED_BLOCKS: u64 = 16_384;
+
+/// The minimum time between two reads of the finalized L1 block, about an L1 slot.
+const FINALIZED_REFRESH_INTERVAL: Duration = Duration::from_secs(12);
+
+/// Where to find the L1 blocks emitting logs, e.g. an L1 execution layer RPC.
+#[async_trait]
+pub trait LogSource: std::fmt::Debug + Send + Sync {
+    /// Returns the number of the finalized L1 block.
+    async fn finalized_number(&self) -> ProviderResult<u64>;
+
+    /// Returns the blocks in `from..=to` with logs emitted by any of `addresses`, in order.
+    ///
+    /// Fails with [`ProviderError::LimitExceeded`] if the range is too large for the source.
+    async fn log_blocks(
+        &self,
+        from: u64,
+        to: u64,
+        addresses: &[Address],
+    ) -> ProviderResult<Vec<BlockId>>;
+}
+
+/// What is known of the scanned L1 blocks.
+#[derive(Debug)]
+struct Scan {
+    /// The scanned blocks, all finalized.
+    range: Option<RangeInclusive<u64>>,
+    /// The numbers of the scanned blocks with logs of the watched contracts.
+    with_logs: BTreeSet<u64>,
+    /// The numbers of the scanned blocks read through this provider, by hash.
+    seen: HashMap<B256, u64>,
+    /// The number of blocks of the next query.
+    query_range: u64,
+    /// The finalized block, with when it was read.
+    finalized: Option<(u64, Instant)>,
+}
+
+/// Serves the receipts of L1 blocks from an inner provider, except for finalized blocks a
+/// [`LogSource`] reports without logs of the watched contracts, whose receipts are empty.
+///
+/// Blocks are scanned as they are read by number, as derivation traverses L1. The watched
+/// contracts must cover every log read from the receipts served: this provider is not fit for a
+/// pipeline whose [batcher transaction filter](crate::derive::BatcherTxFilter) needs the receipts
+/// of batcher transactions.
+#[derive(Debug)]
+pub struct LogScanningChainProvider {
+    inner: Arc<dyn ChainProvider>,
+    logs: Arc<dyn LogSource>,
+    addresses: Vec<Address>,
+    max_range: u64,
+    scan: Mutex<Scan>,
+}
+
+impl LogScanningChainProvider {
+    /// Creates a provider in front of `inner`, scanning `logs` for the logs of `addresses`.
+    pub fn new(
+        inner: Arc<dyn ChainProvider>,
+        logs: Arc<dyn LogSource>,
+        addresses: impl IntoIterator<Item = Address>,
+    ) -> Self {
+        let scan = Scan {
+            range: None,
+            with_logs: BTreeSet::new(),
+            seen: HashMap::new(),
+            query_range: DEFAULT_LOG_SCAN_RANGE,
+            finalized: None,
+        };
+        Self {
+            inner,
+            logs,
+            addresses: addresses.into_iter().collect(),
+            max_range: DEFAULT_MAX_LOG_SCAN_RANGE,
+            scan: Mutex::new(scan),
+        }
+    }
+
+    /// Scans at most `max_range` blocks per `eth_getLogs` query, starting with as many.
+    pub fn with_max_range(mut self, max_range: u64) -> Self {
+        self.max_range = max_range.max(1);
+        self.scan.get_mut().query_range = self.max_range.min(DEFAULT_LOG_SCAN_RANGE);
+        self
+    }
+
+    /// Scans the blocks from `number` onwards unless already scanned, returning whether `number`
+    /// was scanned.
+    async fn scan_from(&self, scan: &mut Scan, number: u64) -> ProviderResult<bool> {
+        if scan.range.as_ref().is_some_and(|range| range.contains(&number)) {
+            return Ok(true);
+        }
+        let finalized = match scan.finalized {
+            Some((finalized, read))
+                if number <= finalized || read.elapsed() < FINALIZED_REFRESH_INTERVAL =>
+            {
+                finalized
+            }
+            _ => {
+                let finalized = self.logs.finalized_number().await?;
+                scan.finalized = Some((finalized, Instant::now()));
+                finalized
+            }
+        };
+        if number > finalized {
+            return Ok(false);
+        }
+
+        let mut to = finalized.min(number + scan.query_range - 1);
+        let blocks = loop {
+            match self.logs.log_blocks(number, to, &self.addresses).await {
+                Ok(blocks) => break blocks,
+                Err(ProviderError::LimitExceeded(err)) if to > number => {
+                    scan.query_range = (to - number).div_ceil(2);
+                    to = number + scan.query_range - 1;
+                    let range = scan.query_range;
+                    debug!(target: "hera::l1", %err, range, "Shrinking L1 log scans");
+                }
+                Err(err) => return Err(err),
+            }
+        };
+        if to - number + 1 == scan.query_range {
+            scan.query_range = (scan.query_range * 2).min(self.max_range);
+        }
+        gauge!("hera_l1_log_scan_range").set(scan.query_range as f64);
+
+        // Extend the scanned range if contiguous, start over otherwise.
+        let start = match &scan.range {
+            Some(range) if *range.end() + 1 == number => *range.start(),
+            _ => {
+                scan.with_logs.clear();
+                scan.seen.clear();
+                number
+            }
+        };
+        let start = start.max((to + 1).saturating_sub(RETAINED_BLOCKS));
+        scan.with_logs = scan.with_logs.split_off(&start);
+        scan.seen.retain(|_, seen| *seen >= start);
+        scan.with_logs.extend(blocks.iter().map(|block| block.number));
+        scan.range = Some(start..=to);
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl ChainProvider for LogScanningChainProvider {
+    async fn header_by_hash(&self, hash: B256) -> ProviderResult<Header> {
+        self.inner.header_by_hash(hash).await
+    }
+
+    async fn block_info_by_number(&self, number: u64) -> ProviderResult<BlockInfo> {
+        let info = self.inner.block_info_by_number(number).await?;
+        let mut scan = self.scan.lock().await;
+        match self.scan_from(&mut scan, number).await {
+            Ok(true) => {
+                scan.seen.insert(info.hash, number);
+            }
+            Ok(false) => {}
+            // Receipts are read whole until the next scan.
+            Err(err) => warn!(target: "hera::l1", %err, number, "Failed to scan L1 logs"),
+        }
+        Ok(info)
+    }
+
+    async fn receipts_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Receipt>> {
+        let skip = {
+            let scan = self.scan.lock().await;
+            scan.seen.get(&hash).is_some_and(|number| !scan.with_logs.contains(number))
+        };
+        if skip {
+            counter!("hera_l1_skipped_receipts_total").increment(1);
+            return Ok(Vec::new());
+        }
+        self.inner.receipts_by_hash(hash).await
+    }
+
+    async fn transactions_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Transaction>> {
+        self.inner.transactions_by_hash(hash).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex as StdMutex;
+
+    use alloy_primitives::{Log, LogData};
+    use eyre::eyre;
+
+    use super::*;
+    use crate::l1::mock::MockL1;
+
+    const WATCHED: Address = Address::repeat_byte(0xdd);
+
+    /// Reports the blocks of a [`MockL1`] with logs, refusing ranges over `max_range` blocks.
+    #[derive(Debug)]
+    struct MockLogs {
+        blocks: Vec<BlockId>,
+        finalized: u64,
+        max_range: u64,
+        queries: StdMutex<Vec<(u64, u64)>>,
+    }
+
+    #[async_trait]
+    impl LogSource for MockLogs {
+        async fn finalized_number(&self) -> ProviderResult<u64> {
+            Ok(self.finalized)
+        }
+
+        async fn log_blocks(
+            &self,
+            from: u64,
+            to: u64,
+            addresses: &[Address],
+        ) -> ProviderResult<Vec<BlockId>> {
+            assert_eq!(addresses, [WATCHED]);
+            self.queries.lock().unwrap().push((from, to));
+            if to - from + 1 > self.max_range {
+                return Err(ProviderError::LimitExceeded(eyre!("block range too large")));
+            }
+            let scanned = self.blocks.iter().filter(|block| (from..=to).contains(&block.number));
+            Ok(scanned.copied().collect())
+        }
+    }
+
+    fn log(address: Address) -> Log {
+        Log { address, data: LogData::new_unchecked(vec![B256::ZERO], Default::default()) }
+    }
+
+    #[tokio::test]
+    async fn skips_receipts_of_finalized_blocks_without_logs() {
+        // Blocks 2 and 5 emit logs of the watched contract, block 3 of another one.
+        let mut l1 = MockL1::new(0, 2);
+        l1.push_logs(vec![log(WATCHED)]);
+        l1.push_logs(vec![log(Address::repeat_byte(0xee))]);
+        l1.push_block(Vec::new());
+        l1.push_logs(vec![log(WATCHED)]);
+        l1.push_logs(Vec::new());
+        let blocks: Vec<_> = (0..7).map(|n| l1.block(n)).collect();
+        let logs = Arc::new(MockLogs {
+            blocks: vec![blocks[2].id(), blocks[5].id()],
+            finalized: 5,
+            max_range: 4,
+            queries: StdMutex::default(),
+        });
+        let provider =
+            LogScanningChainProvider::new(Arc::new(l1), logs.clone(), [WATCHED]).with_max_range(8);
+
+        for block in &blocks {
+            assert_eq!(provider.block_info_by_number(block.number).await.unwrap(), *block);
+        }
+        let mut receipts = Vec::new();
+        for block in &blocks {
+            receipts.push(provider.receipts_by_hash(block.hash).await.unwrap().len());
+        }
+        // Past the finalized block 5, receipts are read whole.
+        assert_eq!(receipts, [0, 0, 1, 0, 0, 1, 1]);
+        // A query over 6 blocks is refused and retried over 3, up to the finalized block.
+        assert_eq!(*logs.queries.lock().unwrap(), [(0, 5), (0, 2), (3, 5)]);
+    }
+}
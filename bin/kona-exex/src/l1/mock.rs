@@ -0,0 +1,160 @@
+//! An in-memory [`ChainProvider`] and [`L1Database`] for tests.
+
+use alloy_consensus::{Eip658Value, Header};
+use alloy_primitives::{Bloom, Log, B256};
+use async_trait::async_trait;
+use eyre::Result;
+
+use crate::{
+    config::RollupConfig,
+    l1::{
+        verify::receipts_root, ChainProvider, L1Database, L1Receipt, L1Transaction, ProviderError,
+        ProviderResult,
+    },
+    protocol::{BlockId, BlockInfo},
+};
+
+/// Time between the test L1 blocks.
+pub(crate) const L1_BLOCK_TIME: u64 = 12;
+
+/// An L1 chain whose headers commit to its receipts, so it can be served through a
+/// [`VerifyingChainProvider`](crate::l1::VerifyingChainProvider).
+#[derive(Debug, Default)]
+pub(crate) struct MockL1 {
+    headers: Vec<Header>,
+    blocks: Vec<BlockInfo>,
+    transactions: Vec<Vec<L1Transaction>>,
+    receipts: Vec<Vec<L1Receipt>>,
+}
+
+impl MockL1 {
+    /// Creates a chain of `count` empty blocks, the first one at `timestamp`.
+    pub(crate) fn new(timestamp: u64, count: u64) -> Self {
+        let mut l1 = Self::default();
+        l1.headers.reserve(count as usize);
+        for _ in 0..count {
+            l1.push_block_at(timestamp, Vec::new(), Vec::new());
+        }
+        l1
+    }
+
+    /// Appends a block with the given transactions, each with a successful receipt.
+    pub(crate) fn push_block(&mut self, transactions: Vec<L1Transaction>) {
+        let timestamp = self.blocks.first().map_or(0, |first| first.timestamp);
+        let receipts = transactions
+            .iter()
+            .map(|_| {
+                let mut receipt = L1Receipt::default();
+                receipt.receipt.receipt.status = Eip658Value::Eip658(true);
+                receipt
+            })
+            .collect();
+        self.push_block_at(timestamp, transactions, receipts);
+    }
+
+    /// Appends a block with a single successful transaction emitting `logs`.
+    pub(crate) fn push_logs(&mut self, logs: Vec<Log>) {
+        self.push_tx_logs(L1Transaction::default(), logs);
+    }
+
+    /// Appends a block with the single successful transaction `tx` emitting `logs`.
+    pub(crate) fn push_tx_logs(&mut self, tx: L1Transaction, logs: Vec<Log>) {
+        let timestamp = self.blocks.first().map_or(0, |first| first.timestamp);
+        let mut receipt = L1Receipt::default();
+        receipt.receipt.receipt.status = Eip658Value::Eip658(true);
+        receipt.receipt.logs_bloom = logs.iter().collect();
+        receipt.receipt.receipt.logs = logs;
+        self.push_block_at(timestamp, vec![tx], vec![receipt]);
+    }
+
+    fn push_block_at(
+        &mut self,
+        first_timestamp: u64,
+        transactions: Vec<L1Transaction>,
+        receipts: Vec<L1Receipt>,
+    ) {
+        let number = self.blocks.len() as u64;
+        let parent_hash = self.blocks.last().map(|parent| parent.hash).unwrap_or_default();
+        let header = Header {
+            parent_hash,
+            number,
+            timestamp: first_timestamp + number * L1_BLOCK_TIME,
+            mix_hash: B256::with_last_byte(number as u8),
+            receipts_root: receipts_root(&receipts),
+            logs_bloom: receipts.iter().fold(Bloom::default(), |mut bloom, receipt| {
+                bloom.accrue_bloom(&receipt.receipt.logs_bloom);
+                bloom
+            }),
+            gas_limit: 30_000_000,
+            base_fee_per_gas: Some(1_000_000_000),
+            ..Default::default()
+        };
+        let hash = header.hash_slow();
+        self.blocks.push(BlockInfo::new(hash, number, parent_hash, header.timestamp));
+        self.headers.push(header);
+        self.transactions.push(transactions);
+        self.receipts.push(receipts);
+    }
+
+    /// Returns the block at `number`.
+    pub(crate) fn block(&self, number: u64) -> BlockInfo {
+        self.blocks[number as usize]
+    }
+
+    /// Returns OP Mainnet's rollup config, with the genesis anchored at the first block.
+    pub(crate) fn rollup_config(&self) -> RollupConfig {
+        let mut config = RollupConfig::from_registry(10).unwrap();
+        let first = self.block(0);
+        config.genesis.l1 = first.id();
+        config.genesis.l2 = BlockId::new(B256::repeat_byte(0x20), 0);
+        config.genesis.l2_time = first.timestamp;
+        config
+    }
+
+    fn index(&self, hash: B256) -> ProviderResult<usize> {
+        self.blocks
+            .iter()
+            .position(|block| block.hash == hash)
+            .ok_or_else(|| ProviderError::NotFound(format!("L1 block {hash}")))
+    }
+}
+
+#[async_trait]
+impl ChainProvider for MockL1 {
+    async fn header_by_hash(&self, hash: B256) -> ProviderResult<Header> {
+        Ok(self.headers[self.index(hash)?].clone())
+    }
+
+    async fn block_info_by_number(&self, number: u64) -> ProviderResult<BlockInfo> {
+        self.blocks
+            .get(number as usize)
+            .copied()
+            .ok_or_else(|| ProviderError::NotFound(format!("L1 block {number}")))
+    }
+
+    async fn receipts_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Receipt>> {
+        Ok(self.receipts[self.index(hash)?].clone())
+    }
+
+    async fn transactions_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Transaction>> {
+        Ok(self.transactions[self.index(hash)?].clone())
+    }
+}
+
+impl L1Database for MockL1 {
+    fn canonical_hash(&self, number: u64) -> Result<Option<B256>> {
+        Ok(self.blocks.get(number as usize).map(|block| block.hash))
+    }
+
+    fn header(&self, hash: B256) -> Result<Option<Header>> {
+        Ok(self.index(hash).ok().map(|index| self.headers[index].clone()))
+    }
+
+    fn receipts(&self, hash: B256) -> Result<Option<Vec<L1Receipt>>> {
+        Ok(self.index(hash).ok().map(|index| self.receipts[index].clone()))
+    }
+
+    fn transactions(&self, hash: B256) -> Result<Option<Vec<L1Transaction>>> {
+        Ok(self.index(hash).ok().map(|index| self.transactions[index].clone()))
+    }
+}
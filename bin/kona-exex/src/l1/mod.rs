@@ -0,0 +1,67 @@
+//! The L1 chain, as consumed by derivation.
+
+use alloy_consensus::Header;
+use alloy_primitives::{Address, Bytes, B256};
+use async_trait::async_trait;
+
+use crate::protocol::BlockInfo;
+
+mod buffered;
+pub use buffered::{retention_boundary, BufferedChainProvider};
+
+mod database;
+pub use database::{DatabaseChainProvider, L1Database};
+
+mod error;
+pub use error::{ProviderError, ProviderResult};
+
+mod fallback;
+pub use fallback::FallbackChainProvider;
+
+mod logs;
+pub use logs::{
+    LogScanningChainProvider, LogSource, DEFAULT_LOG_SCAN_RANGE, DEFAULT_MAX_LOG_SCAN_RANGE,
+};
+
+#[cfg(test)]
+pub(crate) mod mock;
+
+mod receipt;
+pub use receipt::L1Receipt;
+
+mod rpc;
+pub use rpc::RpcChainProvider;
+
+pub mod verify;
+pub use verify::VerifyingChainProvider;
+
+/// The fields of an L1 transaction that derivation reads.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct L1Transaction {
+    /// The transaction hash.
+    pub hash: B256,
+    /// The sender.
+    pub from: Address,
+    /// The recipient, `None` for contract creations.
+    pub to: Option<Address>,
+    /// The calldata.
+    pub input: Bytes,
+    /// The versioned hashes of the blobs carried by the transaction, if any.
+    pub blob_versioned_hashes: Vec<B256>,
+}
+
+/// A source of L1 blocks, transactions and receipts.
+#[async_trait]
+pub trait ChainProvider: std::fmt::Debug + Send + Sync {
+    /// Returns the header of the L1 block with the given hash.
+    async fn header_by_hash(&self, hash: B256) -> ProviderResult<Header>;
+
+    /// Returns the canonical L1 block at `number`.
+    async fn block_info_by_number(&self, number: u64) -> ProviderResult<BlockInfo>;
+
+    /// Returns the receipts of the L1 block with the given hash, in transaction order.
+    async fn receipts_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Receipt>>;
+
+    /// Returns the transactions of the L1 block with the given hash, in block order.
+    async fn transactions_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Transaction>>;
+}
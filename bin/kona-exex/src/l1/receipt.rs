@@ -0,0 +1,55 @@
+//! L1 transaction receipts of any transaction type.
+
+use alloy_consensus::{ReceiptEnvelope, ReceiptWithBloom};
+use alloy_primitives::Log;
+use alloy_rlp::Encodable;
+
+/// An L1 transaction receipt, with the EIP-2718 type of its transaction.
+///
+/// Unlike [`ReceiptEnvelope`], receipts of any transaction type are represented, including
+/// types introduced by later L1 hardforks such as the EIP-7702 set-code transactions of Pectra.
+/// Derivation only reads the status and logs of receipts, which every type shares, so new
+/// transaction types never stall it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct L1Receipt {
+    /// The EIP-2718 transaction type, 0 for legacy transactions.
+    pub tx_type: u8,
+    /// The receipt and its logs bloom.
+    pub receipt: ReceiptWithBloom,
+}
+
+impl L1Receipt {
+    /// Returns whether the transaction succeeded.
+    pub const fn status(&self) -> bool {
+        self.receipt.receipt.status.coerce_status()
+    }
+
+    /// Returns the logs emitted by the transaction.
+    pub fn logs(&self) -> &[Log] {
+        &self.receipt.receipt.logs
+    }
+
+    /// Returns the EIP-2718 encoding of the receipt, as committed to in the receipts trie.
+    pub fn encoded_2718(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        if self.tx_type != 0 {
+            out.push(self.tx_type);
+        }
+        self.receipt.encode(&mut out);
+        out
+    }
+}
+
+impl From<ReceiptEnvelope> for L1Receipt {
+    fn from(envelope: ReceiptEnvelope) -> Self {
+        let tx_type = envelope.tx_type() as u8;
+        let receipt = match envelope {
+            ReceiptEnvelope::Legacy(receipt) |
+            ReceiptEnvelope::Eip2930(receipt) |
+            ReceiptEnvelope::Eip1559(receipt) |
+            ReceiptEnvelope::Eip4844(receipt) => receipt,
+            envelope => envelope.as_receipt_with_bloom().cloned().unwrap_or_default(),
+        };
+        Self { tx_type, receipt }
+    }
+}
@@ -0,0 +1,481 @@
+//! A [`ChainProvider`] backed by an L1 execution layer JSON-RPC.
+
+use alloy_consensus::{Eip658Value, Header, Receipt, ReceiptWithBloom};
+use alloy_primitives::{Address, Bloom, Bytes, B256, U128, U64, U8};
+use alloy_rpc_types_eth::Block;
+use async_trait::async_trait;
+use eyre::{eyre, Report, Result, WrapErr};
+use jsonrpsee::{
+    core::{client::ClientT, ClientError},
+    http_client::{HttpClient, HttpClientBuilder},
+    rpc_params,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    l1::{ChainProvider, L1Receipt, L1Transaction, LogSource, ProviderError, ProviderResult},
+    protocol::{BlockId, BlockInfo},
+};
+
+/// The JSON-RPC error code of requests past the limits of the provider, from EIP-1474.
+const LIMIT_EXCEEDED_CODE: i32 = -32005;
+
+/// Fragments of the errors providers answer `eth_getLogs` queries past their limits with, for
+/// those not using [`LIMIT_EXCEEDED_CODE`]: geth and reth cap the block range and the number of
+/// logs, hosted providers also the size of the response.
+const LOG_LIMIT_MESSAGES: [&str; 5] = ["range", "more than", "exceed", "limit", "too many"];
+
+/// Reads L1 data from an L1 execution layer RPC.
+#[derive(Debug)]
+pub struct RpcChainProvider {
+    client: HttpClient,
+}
+
+impl RpcChainProvider {
+    /// Creates a provider reading from the L1 RPC at `url`.
+    pub fn new(url: &str) -> Result<Self> {
+        let client = HttpClientBuilder::default()
+            .build(url)
+            .wrap_err_with(|| format!("invalid L1 RPC URL {url}"))?;
+        Ok(Self { client })
+    }
+
+    /// Creates a provider reading through `client`, e.g. shared from
+    /// [`HttpClients`](crate::endpoint::HttpClients).
+    pub const fn from_client(client: HttpClient) -> Self {
+        Self { client }
+    }
+
+    async fn block_by_hash<T: DeserializeOwned>(
+        &self,
+        hash: B256,
+        full: bool,
+    ) -> ProviderResult<T> {
+        let block: Option<T> = self
+            .client
+            .request("eth_getBlockByHash", rpc_params![hash, full])
+            .await
+            .map_err(|err| request_error(err, format!("L1 block {hash}")))?;
+        block.ok_or_else(|| ProviderError::NotFound(format!("L1 block {hash}")))
+    }
+}
+
+/// Classifies a failed request for `what`: answers that do not deserialize are invalid data,
+/// anything else is a transport failure.
+fn request_error(err: ClientError, what: String) -> ProviderError {
+    let invalid = matches!(err, ClientError::ParseError(_));
+    let err = Report::new(err).wrap_err(format!("failed to fetch {what}"));
+    if invalid {
+        ProviderError::InvalidData(err)
+    } else {
+        ProviderError::Transport(err)
+    }
+}
+
+/// Classifies a failed `eth_getLogs` query over `from..=to`, telling the queries refused as too
+/// large apart so that they are retried over fewer blocks.
+fn logs_error(err: ClientError, from: u64, to: u64) -> ProviderError {
+    let limited = match &err {
+        ClientError::Call(call) => {
+            let message = call.message().to_lowercase();
+            call.code() == LIMIT_EXCEEDED_CODE ||
+                LOG_LIMIT_MESSAGES.iter().any(|fragment| message.contains(fragment))
+        }
+        _ => false,
+    };
+    if limited {
+        let err = Report::new(err).wrap_err(format!("L1 logs of blocks {from} to {to}"));
+        return ProviderError::LimitExceeded(err);
+    }
+    request_error(err, format!("L1 logs of blocks {from} to {to}"))
+}
+
+/// The block of a log, the only fields read from `eth_getLogs`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcLogBlock {
+    block_hash: B256,
+    block_number: U64,
+    #[serde(default)]
+    removed: bool,
+}
+
+/// The transactions of an L1 block, reduced to the fields derivation reads so that transactions
+/// of any type can be deserialized.
+#[derive(Deserialize)]
+struct RpcBlockTransactions {
+    transactions: Vec<RpcTransaction>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcTransaction {
+    hash: B256,
+    from: Address,
+    to: Option<Address>,
+    input: Bytes,
+    #[serde(default)]
+    blob_versioned_hashes: Vec<B256>,
+}
+
+/// An L1 receipt, reduced to its consensus fields so that receipts of any transaction type can
+/// be deserialized.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcReceipt {
+    #[serde(rename = "type")]
+    tx_type: U8,
+    /// The status, from Byzantium onwards.
+    status: Option<U64>,
+    /// The post-transaction state root, before Byzantium.
+    root: Option<B256>,
+    cumulative_gas_used: U128,
+    logs: Vec<alloy_rpc_types_eth::Log>,
+    logs_bloom: Bloom,
+}
+
+impl From<RpcReceipt> for L1Receipt {
+    fn from(receipt: RpcReceipt) -> Self {
+        Self {
+            tx_type: receipt.tx_type.to(),
+            receipt: ReceiptWithBloom {
+                receipt: Receipt {
+                    status: match (receipt.status, receipt.root) {
+                        (Some(status), _) => Eip658Value::Eip658(status == U64::from(1)),
+                        (None, Some(root)) => Eip658Value::PostState(root),
+                        (None, None) => Eip658Value::Eip658(false),
+                    },
+                    cumulative_gas_used: receipt.cumulative_gas_used.to(),
+                    logs: receipt.logs.into_iter().map(|log| log.inner).collect(),
+                },
+                logs_bloom: receipt.logs_bloom,
+            },
+        }
+    }
+}
+
+/// Converts an RPC block header into a consensus header.
+///
+/// From Pectra, L1 headers commit to the EIP-7685 execution layer requests in `requestsHash`,
+/// which the RPC types only know by its draft name `requestsRoot`. The field is taken over so
+/// that the header hashes to the block hash.
+fn into_consensus_header(block: Block) -> ProviderResult<Header> {
+    let requests_hash = block
+        .other
+        .get_deserialized::<B256>("requestsHash")
+        .transpose()
+        .wrap_err("invalid L1 block requests hash")
+        .map_err(ProviderError::InvalidData)?;
+    let mut header = Header::try_from(block.header)
+        .map_err(|err| ProviderError::InvalidData(Report::new(err)))?;
+    header.requests_root = header.requests_root.or(requests_hash);
+    Ok(header)
+}
+
+#[async_trait]
+impl ChainProvider for RpcChainProvider {
+    async fn header_by_hash(&self, hash: B256) -> ProviderResult<Header> {
+        into_consensus_header(self.block_by_hash(hash, false).await?)
+    }
+
+    async fn block_info_by_number(&self, number: u64) -> ProviderResult<BlockInfo> {
+        let block: Option<Block> = self
+            .client
+            .request("eth_getBlockByNumber", rpc_params![U64::from(number), false])
+            .await
+            .map_err(|err| request_error(err, format!("L1 block {number}")))?;
+        let header =
+            block.ok_or_else(|| ProviderError::NotFound(format!("L1 block {number}")))?.header;
+        let hash = header
+            .hash
+            .ok_or_else(|| ProviderError::InvalidData(eyre!("L1 block {number} has no hash")))?;
+        Ok(BlockInfo::new(hash, number, header.parent_hash, header.timestamp))
+    }
+
+    async fn receipts_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Receipt>> {
+        let receipts: Option<Vec<RpcReceipt>> = self
+            .client
+            .request("eth_getBlockReceipts", rpc_params![hash])
+            .await
+            .map_err(|err| request_error(err, format!("receipts of L1 block {hash}")))?;
+        let receipts = receipts
+            .ok_or_else(|| ProviderError::NotFound(format!("receipts of L1 block {hash}")))?;
+        Ok(receipts.into_iter().map(Into::into).collect())
+    }
+
+    async fn transactions_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Transaction>> {
+        let block: RpcBlockTransactions = self.block_by_hash(hash, true).await?;
+        Ok(block
+            .transactions
+            .into_iter()
+            .map(|tx| L1Transaction {
+                hash: tx.hash,
+                from: tx.from,
+                to: tx.to,
+                input: tx.input,
+                blob_versioned_hashes: tx.blob_versioned_hashes,
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl LogSource for RpcChainProvider {
+    async fn finalized_number(&self) -> ProviderResult<u64> {
+        let block: Option<Block> = self
+            .client
+            .request("eth_getBlockByNumber", rpc_params!["finalized", false])
+            .await
+            .map_err(|err| request_error(err, "finalized L1 block".to_string()))?;
+        let block = block.ok_or_else(|| ProviderError::NotFound("finalized L1 block".into()))?;
+        block
+            .header
+            .number
+            .ok_or_else(|| ProviderError::InvalidData(eyre!("finalized L1 block has no number")))
+    }
+
+    async fn log_blocks(
+        &self,
+        from: u64,
+        to: u64,
+        addresses: &[Address],
+    ) -> ProviderResult<Vec<BlockId>> {
+        let filter = serde_json::json!({
+            "fromBlock": U64::from(from),
+            "toBlock": U64::from(to),
+            "address": addresses,
+        });
+        let logs: Vec<RpcLogBlock> = self
+            .client
+            .request("eth_getLogs", rpc_params![filter])
+            .await
+            .map_err(|err| logs_error(err, from, to))?;
+        let mut blocks: Vec<BlockId> = Vec::new();
+        for log in logs.into_iter().filter(|log| !log.removed) {
+            let block = BlockId::new(log.block_hash, log.block_number.to());
+            if blocks.last() != Some(&block) {
+                blocks.push(block);
+            }
+        }
+        Ok(blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{address, b256, bytes, Bloom, B64, U256};
+    use serde_json::json;
+
+    use super::*;
+
+    const REQUESTS_HASH: B256 =
+        b256!("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+
+    /// A Prague L1 header, with blobs past the Cancun maximum of 6 per block.
+    fn prague_header() -> Header {
+        Header {
+            parent_hash: b256!("0101010101010101010101010101010101010101010101010101010101010101"),
+            ommers_hash: b256!("1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347"),
+            beneficiary: address!("95222290dd7278aa3ddd389cc1e1d165cc4bafe5"),
+            state_root: b256!("0202020202020202020202020202020202020202020202020202020202020202"),
+            transactions_root: b256!(
+                "0303030303030303030303030303030303030303030303030303030303030303"
+            ),
+            receipts_root: b256!(
+                "0404040404040404040404040404040404040404040404040404040404040404"
+            ),
+            logs_bloom: Bloom::default(),
+            difficulty: U256::ZERO,
+            number: 22_431_084,
+            gas_limit: 36_000_000,
+            gas_used: 12_345_678,
+            timestamp: 1_746_612_311,
+            extra_data: bytes!("6265617665726275696c642e6f7267"),
+            mix_hash: b256!("0505050505050505050505050505050505050505050505050505050505050505"),
+            nonce: B64::ZERO,
+            base_fee_per_gas: Some(1_000_000_000),
+            withdrawals_root: Some(b256!(
+                "0606060606060606060606060606060606060606060606060606060606060606"
+            )),
+            blob_gas_used: Some(9 * 131_072),
+            excess_blob_gas: Some(0),
+            parent_beacon_block_root: Some(b256!(
+                "0707070707070707070707070707070707070707070707070707070707070707"
+            )),
+            requests_root: Some(REQUESTS_HASH),
+        }
+    }
+
+    fn prague_block(header: &Header) -> serde_json::Value {
+        json!({
+            "hash": header.hash_slow(),
+            "parentHash": header.parent_hash,
+            "sha3Uncles": header.ommers_hash,
+            "miner": header.beneficiary,
+            "stateRoot": header.state_root,
+            "transactionsRoot": header.transactions_root,
+            "receiptsRoot": header.receipts_root,
+            "logsBloom": header.logs_bloom,
+            "difficulty": "0x0",
+            "number": format!("{:#x}", header.number),
+            "gasLimit": format!("{:#x}", header.gas_limit),
+            "gasUsed": format!("{:#x}", header.gas_used),
+            "timestamp": format!("{:#x}", header.timestamp),
+            "extraData": header.extra_data,
+            "mixHash": header.mix_hash,
+            "nonce": "0x0000000000000000",
+            "baseFeePerGas": "0x3b9aca00",
+            "withdrawalsRoot": header.withdrawals_root,
+            "blobGasUsed": "0x120000",
+            "excessBlobGas": "0x0",
+            "parentBeaconBlockRoot": header.parent_beacon_block_root,
+            "requestsHash": REQUESTS_HASH,
+            "size": "0x2a3c",
+            "uncles": [],
+            "withdrawals": [],
+            "transactions": [],
+        })
+    }
+
+    #[test]
+    fn prague_header_commits_to_requests_hash() {
+        let expected = prague_header();
+        let block: Block = serde_json::from_value(prague_block(&expected)).unwrap();
+        let hash = block.header.hash.unwrap();
+
+        let header = into_consensus_header(block).unwrap();
+        assert_eq!(header, expected);
+        assert_eq!(header.hash_slow(), hash);
+    }
+
+    #[test]
+    fn cancun_header_has_no_requests_hash() {
+        let mut expected = prague_header();
+        expected.requests_root = None;
+        let mut block = prague_block(&expected);
+        block.as_object_mut().unwrap().remove("requestsHash");
+        let block: Block = serde_json::from_value(block).unwrap();
+        let hash = block.header.hash.unwrap();
+
+        let header = into_consensus_header(block).unwrap();
+        assert_eq!(header.requests_root, None);
+        assert_eq!(header.hash_slow(), hash);
+    }
+
+    #[test]
+    fn receipts_of_any_transaction_type() {
+        let log = json!({
+            "address": "0x00000000219ab540356cbb839cbe05303d7705fa",
+            "topics": ["0x649bbc62d0e31342afea4e5cd82d4049e7e1ee912fc0889aa790803be39038c5"],
+            "data": "0x",
+            "blockNumber": "0x1564a6c",
+            "transactionHash": "0x0808080808080808080808080808080808080808080808080808080808080808",
+            "transactionIndex": "0x2",
+            "blockHash": "0x0909090909090909090909090909090909090909090909090909090909090909",
+            "logIndex": "0x0",
+            "removed": false,
+        });
+        let receipt = |tx_type: &str, status: &str, logs: serde_json::Value| {
+            json!({
+                "type": tx_type,
+                "status": status,
+                "cumulativeGasUsed": "0x5208",
+                "logs": logs,
+                "logsBloom": Bloom::default(),
+                "transactionHash": "0x0808080808080808080808080808080808080808080808080808080808080808",
+                "transactionIndex": "0x0",
+                "blockHash": "0x0909090909090909090909090909090909090909090909090909090909090909",
+                "blockNumber": "0x1564a6c",
+                "gasUsed": "0x5208",
+                "effectiveGasPrice": "0x3b9aca00",
+                "from": "0x95222290dd7278aa3ddd389cc1e1d165cc4bafe5",
+                "to": "0x00000000219ab540356cbb839cbe05303d7705fa",
+                "contractAddress": null,
+            })
+        };
+        let receipts: Vec<RpcReceipt> = serde_json::from_value(json!([
+            receipt("0x2", "0x1", json!([])),
+            receipt("0x3", "0x1", json!([])),
+            // EIP-7702 set-code transaction, from Pectra.
+            receipt("0x4", "0x1", json!([log])),
+            receipt("0x4", "0x0", json!([])),
+        ]))
+        .unwrap();
+        let receipts: Vec<L1Receipt> = receipts.into_iter().map(Into::into).collect();
+
+        assert_eq!(receipts.iter().map(|r| r.tx_type).collect::<Vec<_>>(), [2, 3, 4, 4]);
+        assert_eq!(
+            receipts.iter().map(L1Receipt::status).collect::<Vec<_>>(),
+            [true, true, true, false]
+        );
+        assert_eq!(receipts[2].logs().len(), 1);
+        assert_eq!(
+            receipts[2].logs()[0].address,
+            address!("00000000219ab540356cbb839cbe05303d7705fa")
+        );
+        assert_eq!(receipts[2].encoded_2718()[0], 4);
+    }
+
+    #[test]
+    fn tells_log_limits_apart() {
+        let call = |code: i32, message: &str| {
+            ClientError::Call(jsonrpsee::types::ErrorObject::owned(code, message, None::<()>))
+        };
+        let limited = |err| matches!(logs_error(err, 1, 2), ProviderError::LimitExceeded(_));
+
+        assert!(limited(call(-32005, "query returned more than 10000 results")));
+        assert!(limited(call(-32602, "query exceeds max block range 100000")));
+        assert!(limited(call(-32000, "Log response size exceeded")));
+        assert!(!limited(call(-32000, "header not found")));
+        assert!(!limited(ClientError::RequestTimeout));
+    }
+
+    #[test]
+    fn transactions_of_any_transaction_type() {
+        let blob_hashes: Vec<B256> = (1..=9u8).map(B256::repeat_byte).collect();
+        let block: RpcBlockTransactions = serde_json::from_value(json!({
+            "transactions": [
+                {
+                    "type": "0x4",
+                    "hash": "0x0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a",
+                    "from": "0x95222290dd7278aa3ddd389cc1e1d165cc4bafe5",
+                    "to": "0x95222290dd7278aa3ddd389cc1e1d165cc4bafe5",
+                    "input": "0x",
+                    "nonce": "0x1",
+                    "value": "0x0",
+                    "gas": "0x186a0",
+                    "maxFeePerGas": "0x3b9aca00",
+                    "maxPriorityFeePerGas": "0x1",
+                    "chainId": "0x1",
+                    "accessList": [],
+                    "authorizationList": [{
+                        "chainId": "0x0",
+                        "address": "0x63c0c19a282a1b52b07dd5a65b58948a07dae32b",
+                        "nonce": "0x2",
+                        "yParity": "0x1",
+                        "r": "0x1",
+                        "s": "0x2",
+                    }],
+                    "v": "0x1",
+                    "yParity": "0x1",
+                    "r": "0x1",
+                    "s": "0x2",
+                },
+                {
+                    "type": "0x3",
+                    "hash": "0x0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b",
+                    "from": "0x6887246668a3b87f54deb3b94ba47a6f63f32985",
+                    "to": "0xff00000000000000000000000000000000000010",
+                    "input": "0x",
+                    "maxFeePerBlobGas": "0x1",
+                    "blobVersionedHashes": blob_hashes,
+                },
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(block.transactions.len(), 2);
+        assert!(block.transactions[0].blob_versioned_hashes.is_empty());
+        assert_eq!(block.transactions[1].blob_versioned_hashes, blob_hashes);
+    }
+}
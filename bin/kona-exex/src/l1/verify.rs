@@ -0,0 +1,116 @@
+//! Verification of L1 data against the block header committing to it.
+
+use std::sync::Arc;
+
+use alloy_consensus::Header;
+use alloy_primitives::{Bloom, B256};
+use alloy_rlp::Encodable;
+use alloy_trie::{HashBuilder, Nibbles};
+use async_trait::async_trait;
+use eyre::{ensure, eyre, Result, WrapErr};
+use metrics::counter;
+
+use crate::{
+    l1::{ChainProvider, L1Receipt, L1Transaction, ProviderError, ProviderResult},
+    protocol::BlockInfo,
+};
+
+/// Returns the root of the receipts trie: the EIP-2718 encoded receipts keyed by the RLP
+/// encoding of their index.
+pub fn receipts_root(receipts: &[L1Receipt]) -> B256 {
+    let mut leaves: Vec<_> = receipts
+        .iter()
+        .enumerate()
+        .map(|(index, receipt)| {
+            let mut key = Vec::new();
+            index.encode(&mut key);
+            (Nibbles::unpack(key), receipt.encoded_2718())
+        })
+        .collect();
+    leaves.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut builder = HashBuilder::default();
+    for (key, value) in leaves {
+        builder.add_leaf(key, &value);
+    }
+    builder.root()
+}
+
+/// Checks that `receipts` are the receipts of the block `header`: each receipt's bloom matches
+/// its logs, the blooms add up to the header's bloom, and the receipts hash to the header's
+/// receipts root.
+pub fn verify_receipts(header: &Header, receipts: &[L1Receipt]) -> Result<()> {
+    let mut block_bloom = Bloom::default();
+    for (index, receipt) in receipts.iter().enumerate() {
+        let mut bloom = Bloom::default();
+        for log in receipt.logs() {
+            bloom.accrue_log(log);
+        }
+        ensure!(
+            receipt.receipt.logs_bloom == bloom,
+            "receipt {index} has a logs bloom not matching its logs"
+        );
+        block_bloom.accrue_bloom(&bloom);
+    }
+    ensure!(block_bloom == header.logs_bloom, "receipt blooms do not match the header logs bloom");
+
+    let root = receipts_root(receipts);
+    ensure!(
+        root == header.receipts_root,
+        "receipts root {root} does not match the header receipts root {}",
+        header.receipts_root
+    );
+    Ok(())
+}
+
+/// Wraps a [`ChainProvider`], verifying every header against its hash and every set of receipts
+/// against its header before returning them.
+///
+/// A faulty or malicious upstream can then at worst withhold data, but never feed forged
+/// deposits or system config updates into derivation.
+#[derive(Debug)]
+pub struct VerifyingChainProvider {
+    inner: Arc<dyn ChainProvider>,
+}
+
+impl VerifyingChainProvider {
+    /// Wraps `inner`.
+    pub fn new(inner: Arc<dyn ChainProvider>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl ChainProvider for VerifyingChainProvider {
+    async fn header_by_hash(&self, hash: B256) -> ProviderResult<Header> {
+        let header = self.inner.header_by_hash(hash).await?;
+        let actual = header.hash_slow();
+        if actual != hash {
+            counter!("hera_l1_verification_failures_total", "kind" => "header").increment(1);
+            return Err(ProviderError::InvalidData(eyre!(
+                "L1 provider returned header {actual} for block {hash}"
+            )));
+        }
+        Ok(header)
+    }
+
+    async fn block_info_by_number(&self, number: u64) -> ProviderResult<BlockInfo> {
+        self.inner.block_info_by_number(number).await
+    }
+
+    async fn receipts_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Receipt>> {
+        let header = self.header_by_hash(hash).await?;
+        let receipts = self.inner.receipts_by_hash(hash).await?;
+        verify_receipts(&header, &receipts)
+            .inspect_err(|_| {
+                counter!("hera_l1_verification_failures_total", "kind" => "receipts").increment(1);
+            })
+            .wrap_err_with(|| format!("invalid receipts for L1 block {hash}"))
+            .map_err(ProviderError::InvalidData)?;
+        Ok(receipts)
+    }
+
+    async fn transactions_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Transaction>> {
+        self.inner.transactions_by_hash(hash).await
+    }
+}
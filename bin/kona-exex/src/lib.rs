@@ -0,0 +1,45 @@
+//! Hera: a rollup node for the OP Stack, running as a reth Execution Extension.
+
+#![doc(issue_tracker_base_url = "https://github.com/paradigmxyz/op-rs/issues/")]
+#![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
+
+pub mod alert;
+pub mod altda;
+pub mod audit;
+pub mod batcher;
+pub mod blobs;
+pub mod cli;
+pub mod clock;
+pub mod config;
+pub mod daemon;
+pub mod derive;
+pub mod driver;
+pub mod endpoint;
+pub mod engine;
+pub mod era;
+pub mod exex;
+pub mod fees;
+pub mod hooks;
+pub mod l1;
+pub mod logging;
+pub mod mempool;
+pub mod monitor;
+pub mod node;
+pub mod output;
+pub mod p2p;
+pub mod prometheus;
+pub mod protocol;
+pub mod rpc;
+pub mod safedb;
+pub mod sequencer;
+pub mod shadow;
+#[cfg(test)]
+pub(crate) mod sim;
+pub mod stats;
+pub mod storage;
+pub mod supervisor;
+pub mod tenant;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+pub mod validation;
+pub mod version;
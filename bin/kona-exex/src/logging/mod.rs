@@ -0,0 +1,97 @@
+//! Logging, with levels adjustable while the node runs.
+//!
+//! The log filter is installed behind a reload layer, so operators can raise the level of a
+//! target through `admin_setLogLevel` during an incident, e.g. `hera::derive` to `trace`, and
+//! lower it again afterwards, without restarting the node and losing derivation progress.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use eyre::{bail, eyre, Result, WrapErr};
+use tracing::info;
+use tracing_subscriber::{
+    filter::LevelFilter, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry,
+};
+
+/// The filter used when `RUST_LOG` is not set.
+pub const DEFAULT_LOG_FILTER: &str = "info";
+
+/// Changes the log filter of the global subscriber installed with [`init`].
+#[derive(Debug, Clone)]
+pub struct LogFilterHandle {
+    handle: reload::Handle<EnvFilter, Registry>,
+    directives: Arc<Mutex<Directives>>,
+}
+
+/// The filter set at startup, and the levels set for targets since.
+#[derive(Debug, Clone)]
+struct Directives {
+    base: String,
+    levels: BTreeMap<String, LevelFilter>,
+}
+
+impl Directives {
+    fn filter(&self) -> Result<EnvFilter> {
+        let filter = self.to_string();
+        EnvFilter::try_new(&filter).wrap_err_with(|| format!("invalid log filter {filter}"))
+    }
+}
+
+impl fmt::Display for Directives {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.base)?;
+        for (target, level) in &self.levels {
+            write!(f, ",{target}={level}")?;
+        }
+        Ok(())
+    }
+}
+
+impl LogFilterHandle {
+    /// Sets the level of the logs of `target` and the targets nested under it, keeping the
+    /// levels of other targets.
+    pub fn set_level(&self, target: &str, level: &str) -> Result<()> {
+        if target.is_empty() || target.contains(|c: char| c.is_whitespace() || ",=[]".contains(c)) {
+            bail!("invalid log target {target:?}");
+        }
+        let level = LevelFilter::from_str(level).map_err(|_| {
+            eyre!("invalid log level {level:?}, expected off, error, warn, info, debug or trace")
+        })?;
+
+        let mut directives = self.directives.lock().unwrap();
+        let mut updated = directives.clone();
+        updated.levels.insert(target.to_string(), level);
+        self.handle.reload(updated.filter()?).wrap_err("failed to update the log filter")?;
+        *directives = updated;
+        drop(directives);
+
+        info!(target: "hera::logging", log_target = target, %level, "Set log level");
+        Ok(())
+    }
+
+    /// Returns the current log filter, in `RUST_LOG` syntax.
+    pub fn filter(&self) -> String {
+        self.directives.lock().unwrap().to_string()
+    }
+}
+
+/// Installs the global subscriber, logging to stdout with the filter in `RUST_LOG`, or
+/// [`DEFAULT_LOG_FILTER`].
+pub fn init() -> Result<LogFilterHandle> {
+    let base = std::env::var(EnvFilter::DEFAULT_ENV)
+        .ok()
+        .filter(|filter| EnvFilter::try_new(filter).is_ok())
+        .unwrap_or_else(|| DEFAULT_LOG_FILTER.to_string());
+    let directives = Directives { base, levels: BTreeMap::new() };
+    let (filter, handle) = reload::Layer::new(directives.filter()?);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .try_init()
+        .wrap_err("failed to install the log subscriber")?;
+    Ok(LogFilterHandle { handle, directives: Arc::new(Mutex::new(directives)) })
+}
@@ -1,7 +1,188 @@
+//! The Hera rollup node binary.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::{Parser, Subcommand};
 use eyre::Result;
+use kona_exex::{
+    cli::{
+        AuditCommand, BenchCommand, BlobCommand, ConfigCommand, DecodeBatchCommand,
+        ExportEraCommand, HeraArgs, MonitorCommand, StatsCommand, ValidateAttributesCommand,
+    },
+    daemon::{self, NotifyState, PidFile, StopSignal},
+    logging,
+    node::HeraNode,
+    prometheus::PrometheusRecorder,
+    version::{LONG_VERSION, SHORT_VERSION},
+};
+use tracing::{info, warn};
+
+/// The Hera command line interface.
+#[derive(Debug, Parser)]
+#[command(author, version = SHORT_VERSION, long_version = LONG_VERSION, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    hera: HeraArgs,
+}
+
+/// Standalone tools, run instead of the node.
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Inspect batcher blobs.
+    Blob(BlobCommand),
+    /// Print the events of an audit log.
+    Audit(AuditCommand),
+    /// Print the hourly derivation throughput recorded by the node.
+    Stats(StatsCommand),
+    /// Inspect rollup config files.
+    Config(ConfigCommand),
+    /// Decode a batcher transaction into its frames, channels and batches.
+    DecodeBatch(DecodeBatchCommand),
+    /// Benchmark derivation over synthetic batcher data.
+    Bench(BenchCommand),
+    /// Validate attributes derived by another node, without deriving.
+    ValidateAttributes(ValidateAttributesCommand),
+    /// Check the output roots proposed on L1 against locally computed outputs.
+    Monitor(MonitorCommand),
+    /// Export the derived chain to era-style archive files.
+    ExportEra(ExportEraCommand),
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    println!("Hello, kona!");
-    Ok(())
+    let log_filter = logging::init()?;
+
+    let cli = Cli::parse();
+    match &cli.command {
+        Some(Command::Audit(command)) => return command.run(),
+        Some(Command::Stats(command)) => return command.run(),
+        Some(Command::Config(command)) => return command.run(&cli.hera.overrides),
+        _ => {}
+    }
+
+    info!(target: "hera", version = SHORT_VERSION, "Starting Hera");
+    let config = cli.hera.rollup_config()?;
+    info!(target: "hera", l2_chain_id = config.l2_chain_id, "Loaded rollup config");
+
+    let datadir = cli.hera.datadir()?;
+    cli.hera.log_summary(&config, datadir.as_ref());
+
+    match cli.command {
+        Some(Command::Blob(command)) => return command.run(&config).await,
+        Some(Command::DecodeBatch(command)) => return command.run(&config).await,
+        Some(Command::Bench(command)) => {
+            let batcher = cli.hera.batcher.batcher_config(&config)?;
+            return command.run(&config, batcher).await;
+        }
+        Some(Command::ValidateAttributes(command)) => return command.run(&cli.hera).await,
+        Some(Command::Monitor(command)) => return command.run(&cli.hera, &config).await,
+        Some(Command::ExportEra(command)) => {
+            return command.run(&cli.hera, &config, datadir.as_ref()).await;
+        }
+        _ => {}
+    }
+
+    match cli.hera.metrics_addr {
+        Some(addr) => {
+            PrometheusRecorder::new().install(addr).await?;
+        }
+        None if cli.hera.metrics_runtime => {
+            warn!(target: "hera", "Runtime metrics are not served without --hera.metrics.addr");
+        }
+        None => {}
+    }
+
+    let daemon = cli.hera.daemon;
+    let binary = daemon.then(daemon::current_binary).transpose()?;
+    let pid_file = cli.hera.daemon_pid_file.as_ref().map(PidFile::create).transpose()?;
+
+    let (listeners, mapping) = if cli.hera.p2p.enabled {
+        let listeners = cli.hera.p2p.listen().await?;
+        let mapping = cli.hera.p2p.port_mapping(&listeners).await?;
+        let identity = cli.hera.p2p.identity(
+            datadir.as_ref(),
+            config.l2_chain_id,
+            &listeners,
+            mapping.as_ref(),
+        )?;
+        info!(
+            target: "hera",
+            enr = %identity.enr,
+            node_id = %identity.enr.node_id()?,
+            "Loaded p2p identity"
+        );
+        (Some(listeners), mapping)
+    } else {
+        (None, None)
+    };
+
+    let node = HeraNode::launch(&cli.hera, config, datadir.as_ref(), log_filter, None).await?;
+    if daemon {
+        let readiness = node.readiness();
+        tokio::spawn(async move {
+            readiness.wait_ready().await;
+            notify(NotifyState::Ready);
+        });
+    }
+    let node = node.run();
+    let upgrade = AtomicBool::new(false);
+    let shutdown = async {
+        let signal = if daemon {
+            daemon::stop_signal().await
+        } else {
+            tokio::signal::ctrl_c().await.map(|()| StopSignal::Shutdown).map_err(Into::into)
+        };
+        match signal {
+            Ok(StopSignal::Upgrade) => {
+                info!(target: "hera", "Upgrading");
+                upgrade.store(true, Ordering::Relaxed);
+                notify(NotifyState::Reloading);
+            }
+            Ok(StopSignal::Shutdown) => {
+                info!(target: "hera", "Shutting down");
+                if daemon {
+                    notify(NotifyState::Stopping);
+                }
+            }
+            Err(err) => {
+                warn!(target: "hera", %err, "Failed to listen for the shutdown signal");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    let result = match mapping {
+        Some(mapping) => {
+            tokio::select! {
+                result = node => {
+                    mapping.remove().await;
+                    result
+                }
+                () = mapping.clone().keep_alive(shutdown) => Ok(()),
+            }
+        }
+        None => {
+            tokio::select! {
+                result = node => result,
+                () = shutdown => Ok(()),
+            }
+        }
+    };
+    drop(listeners);
+
+    if let Some(binary) = binary.filter(|_| upgrade.load(Ordering::Relaxed)) {
+        // The PID file names this process, which keeps its ID across the upgrade.
+        std::mem::forget(pid_file);
+        daemon::reexec(&binary)?;
+    }
+    result
+}
+
+/// Reports `state` to the service manager, if running under one.
+fn notify(state: NotifyState) {
+    if let Err(err) = daemon::notify(&[state]) {
+        warn!(target: "hera", %err, "Failed to notify the service manager");
+    }
 }
@@ -0,0 +1,304 @@
+//! Mempool preview: speculative tracking of batcher transactions not yet included on L1.
+//!
+//! The [`MempoolPreview`] polls pending L1 transactions, keeps those sent by the batcher to the
+//! batch inbox, and decodes their frames early. The highest L2 block covered by the complete
+//! channels found is published in the sync status as
+//! [`speculative_safe_l2`](crate::rpc::SyncStatus::speculative_safe_l2). Nothing derived here is
+//! ever sent to the execution layer.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use alloy_primitives::{Address, Bytes, B256};
+use async_trait::async_trait;
+use eyre::{Result, WrapErr};
+use jsonrpsee::{
+    core::client::ClientT,
+    http_client::{HttpClient, HttpClientBuilder},
+    rpc_params,
+};
+use metrics::{counter, gauge};
+use serde::Deserialize;
+use tokio::sync::watch;
+use tracing::{debug, trace, warn};
+
+use crate::{
+    config::RollupConfig,
+    protocol::{
+        channel::{
+            decompress_channel, MAX_RLP_BYTES_PER_CHANNEL_BEDROCK, MAX_RLP_BYTES_PER_CHANNEL_FJORD,
+        },
+        Batch, BatchReader, BlockInfo, Channel, ChannelId, Frame,
+    },
+    rpc::{SpeculativeL2Block, SyncStatus},
+};
+
+/// A transaction pending in the L1 mempool.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingTx {
+    /// The transaction hash.
+    pub hash: B256,
+    /// The sender.
+    pub from: Address,
+    /// The recipient, `None` for contract creations.
+    pub to: Option<Address>,
+    /// The calldata.
+    pub input: Bytes,
+    /// The versioned hashes of the blobs carried by the transaction, if any.
+    #[serde(default)]
+    pub blob_versioned_hashes: Vec<B256>,
+}
+
+/// A source of pending L1 transactions, such as reth's transaction pool or an L1 RPC.
+#[async_trait]
+pub trait PendingTxSource: std::fmt::Debug + Send + Sync {
+    /// Returns the transactions currently pending in the mempool.
+    async fn pending_transactions(&self) -> Result<Vec<PendingTx>>;
+}
+
+/// Reads pending transactions from the `pending` block of an L1 RPC.
+#[derive(Debug)]
+pub struct RpcPendingTxSource {
+    client: HttpClient,
+}
+
+impl RpcPendingTxSource {
+    /// Creates a source reading from the L1 RPC at `url`.
+    pub fn new(url: &str) -> Result<Self> {
+        let client = HttpClientBuilder::default()
+            .build(url)
+            .wrap_err_with(|| format!("invalid L1 RPC URL {url}"))?;
+        Ok(Self { client })
+    }
+
+    /// Creates a source reading through `client`, e.g. shared from
+    /// [`HttpClients`](crate::endpoint::HttpClients).
+    pub const fn from_client(client: HttpClient) -> Self {
+        Self { client }
+    }
+}
+
+#[derive(Deserialize)]
+struct PendingBlock {
+    transactions: Vec<PendingTx>,
+}
+
+#[async_trait]
+impl PendingTxSource for RpcPendingTxSource {
+    async fn pending_transactions(&self) -> Result<Vec<PendingTx>> {
+        let block: Option<PendingBlock> = self
+            .client
+            .request("eth_getBlockByNumber", rpc_params!["pending", true])
+            .await
+            .wrap_err("failed to fetch the pending L1 block")?;
+        Ok(block.map(|block| block.transactions).unwrap_or_default())
+    }
+}
+
+/// Tracks pending batcher transactions and publishes the speculative safe head they imply.
+#[derive(Debug)]
+pub struct MempoolPreview {
+    config: Arc<RollupConfig>,
+    source: Arc<dyn PendingTxSource>,
+    batcher: Address,
+    interval: Duration,
+}
+
+impl MempoolPreview {
+    /// Creates a preview of the batches sent by `batcher`, polling `source` every `interval`.
+    pub fn new(
+        config: Arc<RollupConfig>,
+        source: Arc<dyn PendingTxSource>,
+        batcher: Address,
+        interval: Duration,
+    ) -> Self {
+        Self { config, source, batcher, interval }
+    }
+
+    /// Polls the mempool every interval forever, updating the speculative head of `status`.
+    pub async fn run(self, status: watch::Sender<SyncStatus>) {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            let safe_head = status.borrow().safe_l2;
+            match self.poll(safe_head.block_info.timestamp).await {
+                Ok(speculative) => status.send_if_modified(|status| {
+                    let modified = status.speculative_safe_l2 != speculative;
+                    status.speculative_safe_l2 = speculative;
+                    modified
+                }),
+                Err(err) => {
+                    warn!(target: "hera::mempool", %err, "Mempool preview failed");
+                    counter!("hera_mempool_preview_errors_total").increment(1);
+                    continue;
+                }
+            };
+        }
+    }
+
+    /// Decodes the pending batcher transactions once, returning the highest L2 block past the
+    /// safe head timestamp they cover.
+    pub async fn poll(&self, safe_timestamp: u64) -> Result<Option<SpeculativeL2Block>> {
+        let pending = self.source.pending_transactions().await?;
+        let batcher_txs = pending
+            .iter()
+            .filter(|tx| tx.from == self.batcher && tx.to == Some(self.config.batch_inbox_address));
+
+        // Channels are rebuilt from scratch on every poll: transactions leaving the mempool
+        // simply drop out, whether they were included or replaced.
+        let mut channels: HashMap<ChannelId, (Channel, B256)> = HashMap::new();
+        let mut blob_txs = 0;
+        for tx in batcher_txs {
+            if !tx.blob_versioned_hashes.is_empty() {
+                // Blob contents are not gossiped with the transaction, only the commitments.
+                blob_txs += 1;
+                continue;
+            }
+            let frames = match Frame::parse_frames(&tx.input) {
+                Ok(frames) => frames,
+                Err(err) => {
+                    trace!(target: "hera::mempool", tx = %tx.hash, %err, "Skipping batcher tx");
+                    continue;
+                }
+            };
+            for frame in frames {
+                let (channel, last_tx) = channels
+                    .entry(frame.id)
+                    .or_insert_with(|| (Channel::new(frame.id, BlockInfo::default()), tx.hash));
+                if channel.add_frame(frame, BlockInfo::default()).is_ok() {
+                    *last_tx = tx.hash;
+                }
+            }
+        }
+        gauge!("hera_mempool_pending_blob_batcher_txs").set(blob_txs as f64);
+        gauge!("hera_mempool_pending_channels").set(channels.len() as f64);
+
+        let mut speculative: Option<SpeculativeL2Block> = None;
+        for (channel, tx_hash) in channels.values() {
+            let Some(data) = channel.frame_data() else { continue };
+            for block in self.decode_channel(&data, *tx_hash, safe_timestamp) {
+                if block.timestamp > safe_timestamp &&
+                    speculative.map_or(true, |s| block.number > s.number)
+                {
+                    speculative = Some(block);
+                }
+            }
+        }
+        if let Some(block) = speculative {
+            debug!(target: "hera::mempool", number = block.number, "Speculative safe head");
+            gauge!("hera_mempool_speculative_safe_l2").set(block.number as f64);
+        }
+        Ok(speculative)
+    }
+
+    /// Returns the L2 blocks covered by the batches of a complete channel.
+    ///
+    /// The channel format follows the hardforks active at the L2 block after the safe head, the
+    /// first the channel can be derived into, rather than the wall clock: a lagging node must
+    /// decode pending channels the way derivation will once it catches up.
+    fn decode_channel(
+        &self,
+        data: &[u8],
+        batcher_tx: B256,
+        safe_timestamp: u64,
+    ) -> Vec<SpeculativeL2Block> {
+        let fjord = self.config.is_fjord_active(safe_timestamp + self.config.block_time);
+        let max_size =
+            if fjord { MAX_RLP_BYTES_PER_CHANNEL_FJORD } else { MAX_RLP_BYTES_PER_CHANNEL_BEDROCK };
+        let decompressed = match decompress_channel(data, max_size, fjord) {
+            Ok(decompressed) => decompressed,
+            Err(err) => {
+                trace!(target: "hera::mempool", %err, "Skipping undecodable channel");
+                return Vec::new();
+            }
+        };
+
+        let genesis = &self.config.genesis;
+        let block = |timestamp: u64, l1_origin_number| SpeculativeL2Block {
+            number: genesis.l2.number +
+                timestamp.saturating_sub(genesis.l2_time) / self.config.block_time,
+            timestamp,
+            l1_origin_number,
+            batcher_tx,
+        };
+
+        let mut blocks = Vec::new();
+        for batch in BatchReader::new(&decompressed).map_while(Result::ok) {
+            match batch {
+                Batch::Single(batch) => blocks.push(block(batch.timestamp, batch.epoch_num)),
+                Batch::Span(raw) => {
+                    let Ok(span) = raw.derive(
+                        self.config.block_time,
+                        genesis.l2_time,
+                        self.config.l2_chain_id,
+                    ) else {
+                        continue;
+                    };
+                    if let Some(last) = span.elements.last() {
+                        blocks.push(block(last.timestamp, last.epoch_num));
+                    }
+                }
+            }
+        }
+        blocks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        batcher::{Compression, CompressionAlgo},
+        protocol::{frame::DERIVATION_VERSION_0, SingleBatch},
+    };
+
+    #[derive(Debug)]
+    struct Pending(Vec<PendingTx>);
+
+    #[async_trait]
+    impl PendingTxSource for Pending {
+        async fn pending_transactions(&self) -> Result<Vec<PendingTx>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    const BATCHER: Address = Address::repeat_byte(0xba);
+
+    /// A batcher transaction carrying a single batch at `timestamp` in a brotli channel.
+    fn brotli_batcher_tx(config: &RollupConfig, timestamp: u64) -> PendingTx {
+        let batch = Batch::Single(SingleBatch { timestamp, epoch_num: 3, ..Default::default() });
+        let mut encoded = Vec::new();
+        alloy_rlp::Encodable::encode(&batch.encode()[..], &mut encoded);
+        let compressed =
+            Compression::new(CompressionAlgo::Brotli, 5).unwrap().compress(&encoded).unwrap();
+        let frame =
+            Frame { id: ChannelId([9; 16]), number: 0, data: compressed.into(), is_last: true };
+        let mut input = vec![DERIVATION_VERSION_0];
+        input.extend(frame.encode());
+        PendingTx {
+            hash: B256::repeat_byte(1),
+            from: BATCHER,
+            to: Some(config.batch_inbox_address),
+            input: input.into(),
+            blob_versioned_hashes: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn follows_fjord_at_the_safe_head() {
+        let mut config = RollupConfig::from_registry(10).unwrap();
+        let genesis = config.genesis.l2_time;
+        config.fjord_time = Some(genesis + 1_000);
+        let config = Arc::new(config);
+        let tx = brotli_batcher_tx(&config, genesis + 2_000);
+        let source = Arc::new(Pending(vec![tx]));
+        let preview = MempoolPreview::new(config.clone(), source, BATCHER, Duration::from_secs(1));
+
+        // Before Fjord brotli channels are invalid, whatever the wall clock says.
+        assert_eq!(preview.poll(genesis + 500).await.unwrap(), None);
+
+        let block = preview.poll(genesis + 1_500).await.unwrap().unwrap();
+        assert_eq!(block.number, config.genesis.l2.number + 1_000);
+        assert_eq!(block.l1_origin_number, 3);
+    }
+}
@@ -0,0 +1,353 @@
+//! Monitoring of the output roots proposed on L1, as a lightweight replacement for faultmon.
+//!
+//! The [`OutputMonitor`] follows L1 block by block and decodes the `OutputProposed` events of
+//! the chain's `L2OutputOracle`. Every proposed root is checked against the output computed
+//! locally from the L2 execution layer, and mismatches are reported as metrics, error logs and
+//! alerts. L1 blocks are only read once they have enough confirmations, so proposals are never
+//! checked twice across L1 reorgs.
+
+use std::{sync::Arc, time::Duration};
+
+use alloy_primitives::{b256, Address, Log, B256, U256};
+use eyre::{ensure, Result, WrapErr};
+use metrics::{counter, gauge};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    alert::{Alert, WebhookAlerter},
+    l1::{ChainProvider, L1Receipt, ProviderError},
+    output::OutputRootCache,
+    protocol::BlockInfo,
+};
+
+/// Topic of the `OutputProposed(bytes32,uint256,uint256,uint256)` event of the
+/// `L2OutputOracle`.
+pub const OUTPUT_PROPOSED_TOPIC: B256 =
+    b256!("a7aaf2512769da4e444e3de247be2564225c2e7a8f74cfe528e46e17d24868e2");
+
+/// Default number of L1 blocks built on top of a block before its proposals are checked.
+pub const DEFAULT_MONITOR_CONFIRMATIONS: u64 = 10;
+
+/// Default time between two polls of L1 for a new confirmed block.
+pub const DEFAULT_MONITOR_POLL_INTERVAL: Duration = Duration::from_secs(12);
+
+/// An output root proposed to the `L2OutputOracle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputProposal {
+    /// The proposed output root.
+    pub output_root: B256,
+    /// The index of the output in the oracle.
+    pub l2_output_index: u64,
+    /// The L2 block the output is proposed for.
+    pub l2_block_number: u64,
+    /// The L1 block the proposal was included in.
+    pub l1_block: BlockInfo,
+}
+
+impl OutputProposal {
+    /// Decodes a proposal from an `OutputProposed` event included in `l1_block`.
+    ///
+    /// The root, output index and L2 block number are indexed; the data only holds the L1
+    /// timestamp of the proposal.
+    pub fn from_log(log: &Log, l1_block: BlockInfo) -> Result<Self> {
+        let topics = log.topics();
+        ensure!(topics.len() == 4, "expected 4 output proposal topics, got {}", topics.len());
+        ensure!(topics[0] == OUTPUT_PROPOSED_TOPIC, "not an output proposal event");
+        let index = |topic: &B256, name: &str| {
+            u64::try_from(U256::from_be_bytes(topic.0))
+                .wrap_err_with(|| format!("output proposal {name} overflows u64"))
+        };
+        Ok(Self {
+            output_root: topics[1],
+            l2_output_index: index(&topics[2], "index")?,
+            l2_block_number: index(&topics[3], "L2 block number")?,
+            l1_block,
+        })
+    }
+}
+
+/// Decodes the output proposals of the `oracle` in the receipts of `l1_block`, in log order.
+pub fn decode_proposals(
+    receipts: &[L1Receipt],
+    oracle: Address,
+    l1_block: BlockInfo,
+) -> Result<Vec<OutputProposal>> {
+    receipts
+        .iter()
+        .filter(|receipt| receipt.status())
+        .flat_map(|receipt| receipt.logs())
+        .filter(|log| log.address == oracle && log.topics().first() == Some(&OUTPUT_PROPOSED_TOPIC))
+        .map(|log| {
+            OutputProposal::from_log(log, l1_block)
+                .wrap_err_with(|| format!("invalid output proposal in L1 block {l1_block}"))
+        })
+        .collect()
+}
+
+/// A proposal checked against the locally computed output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProposalCheck {
+    /// The proposal.
+    pub proposal: OutputProposal,
+    /// The output root computed locally at the L2 block of the proposal.
+    pub local: B256,
+}
+
+impl ProposalCheck {
+    /// Returns true if the proposed root is the local one.
+    pub fn is_valid(&self) -> bool {
+        self.proposal.output_root == self.local
+    }
+}
+
+/// Checks the output roots proposed on L1 against locally computed outputs.
+#[derive(Debug)]
+pub struct OutputMonitor {
+    l1: Arc<dyn ChainProvider>,
+    outputs: Arc<OutputRootCache>,
+    oracle: Address,
+    next: u64,
+    confirmations: u64,
+    poll_interval: Duration,
+    alerter: Option<Arc<WebhookAlerter>>,
+}
+
+impl OutputMonitor {
+    /// Creates a monitor of the proposals to `oracle`, starting at the L1 block `from_block`.
+    pub fn new(
+        l1: Arc<dyn ChainProvider>,
+        outputs: Arc<OutputRootCache>,
+        oracle: Address,
+        from_block: u64,
+    ) -> Self {
+        Self {
+            l1,
+            outputs,
+            oracle,
+            next: from_block,
+            confirmations: DEFAULT_MONITOR_CONFIRMATIONS,
+            poll_interval: DEFAULT_MONITOR_POLL_INTERVAL,
+            alerter: None,
+        }
+    }
+
+    /// Sets the number of L1 blocks built on top of a block before its proposals are checked.
+    pub const fn with_confirmations(mut self, confirmations: u64) -> Self {
+        self.confirmations = confirmations;
+        self
+    }
+
+    /// Sets the time between two polls of L1 for a new confirmed block.
+    pub const fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Alerts every mismatching proposal through `alerter`.
+    pub fn with_alerter(mut self, alerter: Arc<WebhookAlerter>) -> Self {
+        self.alerter = Some(alerter);
+        self
+    }
+
+    /// Returns the number of the next L1 block to check.
+    pub const fn next_block(&self) -> u64 {
+        self.next
+    }
+
+    /// Checks the proposals of L1 blocks as they are confirmed, forever.
+    pub async fn run(mut self) {
+        info!(
+            target: "hera::monitor",
+            oracle = %self.oracle,
+            from = self.next,
+            "Monitoring output proposals"
+        );
+        loop {
+            match self.step().await {
+                Ok(Some(_)) => continue,
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(
+                        target: "hera::monitor",
+                        %err,
+                        block = self.next,
+                        "Output monitoring failed"
+                    );
+                    counter!("hera_monitor_errors_total").increment(1);
+                }
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Checks the proposals of the next L1 block if it is confirmed, returning `None` if it is
+    /// not. The block is retried on failure, e.g. while the L2 execution layer has not synced
+    /// the proposed block yet.
+    pub async fn step(&mut self) -> Result<Option<Vec<ProposalCheck>>> {
+        match self.l1.block_info_by_number(self.next + self.confirmations).await {
+            Ok(_) => {}
+            Err(ProviderError::NotFound(_)) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        let block = self.l1.block_info_by_number(self.next).await?;
+        let checks = self.check_block(block).await?;
+        gauge!("hera_monitor_l1_block").set(block.number as f64);
+        self.next += 1;
+        Ok(Some(checks))
+    }
+
+    /// Checks the proposals included in the L1 `block`.
+    pub async fn check_block(&self, block: BlockInfo) -> Result<Vec<ProposalCheck>> {
+        let receipts = self.l1.receipts_by_hash(block.hash).await?;
+        let mut checks = Vec::new();
+        for proposal in decode_proposals(&receipts, self.oracle, block)? {
+            let number = proposal.l2_block_number;
+            let output =
+                self.outputs.output_at_block(number).await.wrap_err_with(|| {
+                    format!("failed to compute the output at L2 block {number}")
+                })?;
+            checks.push(ProposalCheck { proposal, local: output.output_root });
+        }
+
+        for check in &checks {
+            let proposal = &check.proposal;
+            gauge!("hera_monitor_l2_block").set(proposal.l2_block_number as f64);
+            if check.is_valid() {
+                counter!("hera_monitor_proposals_total", "outcome" => "valid").increment(1);
+                debug!(
+                    target: "hera::monitor",
+                    l2_block = proposal.l2_block_number,
+                    root = %proposal.output_root,
+                    "Proposed output root is valid"
+                );
+                continue;
+            }
+            counter!("hera_monitor_proposals_total", "outcome" => "invalid").increment(1);
+            error!(
+                target: "hera::monitor",
+                l1_block = %proposal.l1_block,
+                l2_block = proposal.l2_block_number,
+                index = proposal.l2_output_index,
+                proposed = %proposal.output_root,
+                local = %check.local,
+                "Proposed output root does not match the local output"
+            );
+            if let Some(alerter) = &self.alerter {
+                alerter.notify(Alert::OutputMismatch {
+                    l1_block: proposal.l1_block.number,
+                    l2_block: proposal.l2_block_number,
+                    proposed: proposal.output_root,
+                    local: check.local,
+                });
+            }
+        }
+        Ok(checks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{keccak256, LogData};
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::{
+        l1::mock::MockL1,
+        output::{output_root_v0, BlockRoots, L2StateProvider, DEFAULT_OUTPUT_CACHE_SIZE},
+        protocol::{BlockId, L2BlockInfo},
+    };
+
+    const ORACLE: Address = Address::repeat_byte(0x0c);
+
+    /// An L2 chain whose block `n` has the hash `n`, with constant state and storage roots.
+    #[derive(Debug)]
+    struct TestState;
+
+    #[async_trait]
+    impl L2StateProvider for TestState {
+        async fn block_by_number(&self, number: u64) -> Result<(L2BlockInfo, BlockRoots)> {
+            eyre::ensure!(number <= 100, "L2 block {number} is not synced");
+            let hash = B256::left_padding_from(&number.to_be_bytes());
+            let block = L2BlockInfo::new(
+                BlockInfo::new(hash, number, B256::ZERO, 0),
+                BlockId::default(),
+                0,
+            );
+            let roots = BlockRoots {
+                state_root: B256::repeat_byte(0x11),
+                withdrawals_root: Some(B256::repeat_byte(0x22)),
+            };
+            Ok((block, roots))
+        }
+
+        async fn storage_root(&self, _address: Address, _block_hash: B256) -> Result<B256> {
+            unreachable!("the withdrawals root is in the header")
+        }
+    }
+
+    fn local_root(number: u64) -> B256 {
+        output_root_v0(
+            B256::repeat_byte(0x11),
+            B256::repeat_byte(0x22),
+            B256::left_padding_from(&number.to_be_bytes()),
+        )
+    }
+
+    fn proposal_log(address: Address, root: B256, index: u64, l2_block: u64) -> Log {
+        let topics = vec![
+            OUTPUT_PROPOSED_TOPIC,
+            root,
+            B256::left_padding_from(&index.to_be_bytes()),
+            B256::left_padding_from(&l2_block.to_be_bytes()),
+        ];
+        let data = B256::left_padding_from(&1_000u64.to_be_bytes());
+        Log { address, data: LogData::new(topics, data.into()).unwrap() }
+    }
+
+    fn monitor(l1: MockL1) -> OutputMonitor {
+        let outputs =
+            Arc::new(OutputRootCache::new(Arc::new(TestState), DEFAULT_OUTPUT_CACHE_SIZE));
+        OutputMonitor::new(Arc::new(l1), outputs, ORACLE, 1).with_confirmations(1)
+    }
+
+    #[test]
+    fn computes_the_event_topic() {
+        assert_eq!(
+            OUTPUT_PROPOSED_TOPIC,
+            keccak256("OutputProposed(bytes32,uint256,uint256,uint256)")
+        );
+    }
+
+    #[tokio::test]
+    async fn checks_proposals_of_confirmed_blocks() {
+        let mut l1 = MockL1::new(1_000, 1);
+        l1.push_logs(vec![
+            proposal_log(ORACLE, local_root(10), 0, 10),
+            proposal_log(Address::ZERO, B256::ZERO, 0, 10),
+            proposal_log(ORACLE, B256::repeat_byte(0xee), 1, 20),
+        ]);
+        l1.push_logs(vec![proposal_log(ORACLE, local_root(30), 2, 30)]);
+        let mut monitor = monitor(l1);
+
+        let checks = monitor.step().await.unwrap().unwrap();
+        assert_eq!(checks.len(), 2);
+        assert!(checks[0].is_valid());
+        assert_eq!(checks[1].proposal.l2_output_index, 1);
+        assert_eq!(checks[1].local, local_root(20));
+        assert!(!checks[1].is_valid());
+
+        // The last block has no confirmation yet.
+        assert_eq!(monitor.step().await.unwrap(), None);
+        assert_eq!(monitor.next_block(), 2);
+    }
+
+    #[tokio::test]
+    async fn retries_proposals_of_unsynced_blocks() {
+        let mut l1 = MockL1::new(1_000, 1);
+        l1.push_logs(vec![proposal_log(ORACLE, local_root(200), 0, 200)]);
+        l1.push_logs(Vec::new());
+        let mut monitor = monitor(l1);
+        assert!(monitor.step().await.is_err());
+        assert_eq!(monitor.next_block(), 1);
+    }
+}
@@ -0,0 +1,606 @@
+//! Assembly of the Hera node from its command line arguments.
+//!
+//! [`HeraNode::launch`] builds every component enabled by the [`HeraArgs`] and spawns them under
+//! a [`Supervisor`]: derivation fed by the [`L1Watcher`], the RPC server, and the optional
+//! sequencer, shadow comparison, mempool preview, stall alerts and blob backfill.
+//!
+//! The core services start in dependency order: the L1 watcher, then derivation, the validation
+//! provider, the RPC server and last the sequencer. No height is finished to reth before all of
+//! them are ready.
+
+use std::{sync::Arc, time::Duration};
+
+use eyre::{bail, eyre, Result, WrapErr};
+use tokio::{
+    runtime::Handle,
+    sync::{mpsc, Mutex},
+    time::{Interval, MissedTickBehavior},
+};
+use tracing::{info, warn};
+
+use crate::{
+    alert::StallMonitor,
+    altda::{ChallengeWindows, DaChallengeWatcher},
+    audit::AuditLog,
+    blobs::{BeaconClient, BlobBackfill, BlobFetcher, BlobProvider, CachedBlobProvider},
+    cli::HeraArgs,
+    config::RollupConfig,
+    derive::{DerivationPipeline, PipelineBuilder, StatefulAttributesBuilder},
+    driver::{export_lag, DerivationStart, Driver, DEFAULT_LAG_INTERVAL},
+    endpoint::HttpClients,
+    engine::{DerivationGovernor, EngineApi, EngineClient, GovernorConfig, RpcPayloadSource},
+    era::EraImporter,
+    exex::{
+        ChainNotification, L1Failover, L1Watcher, NotificationHandler, NotificationMode, ReorgGuard,
+    },
+    fees::FeeTracker,
+    l1::{
+        BufferedChainProvider, ChainProvider, FallbackChainProvider, LogScanningChainProvider,
+        RpcChainProvider,
+    },
+    logging::LogFilterHandle,
+    mempool::{MempoolPreview, RpcPendingTxSource},
+    output::{
+        L2StateProvider, OutputRootCache, RpcL2StateProvider, WithdrawalProver,
+        DEFAULT_OUTPUT_CACHE_SIZE,
+    },
+    rpc::{
+        AdminLogRpc, AdminReorgRpc, AdminRpc, HeraDebugRpc, HeraFeeRpc, HeraRpcModules,
+        HeraWithdrawalRpc, HostRpcModules, RollupNodeRpc, SafeDbRpc, TxForwarder,
+    },
+    sequencer::{ConductorClient, Sequencer},
+    shadow::ShadowComparator,
+    storage::DataDir,
+    supervisor::{
+        Readiness, ReadyHandle, RestartConfig, RestartPolicy, RuntimeMonitor, ServiceStage,
+        Supervisor, DEFAULT_RUNTIME_METRICS_INTERVAL,
+    },
+    validation::{CachingValidator, RpcAttributesValidator, DEFAULT_VALIDATION_CACHE_SIZE},
+};
+
+/// The number of chain notifications buffered between the L1 watcher and derivation.
+const NOTIFICATION_BUFFER: usize = 64;
+
+type Handler = NotificationHandler<DerivationPipeline, CachingValidator<RpcAttributesValidator>>;
+
+/// A running Hera node.
+#[derive(Debug)]
+pub struct HeraNode {
+    supervisor: Supervisor,
+}
+
+impl HeraNode {
+    /// Builds the components enabled by `args` and spawns their tasks.
+    ///
+    /// Hera's RPC namespaces are attached to `host` with `--hera.rpc.attach-to-reth`, which then
+    /// requires running inside a reth node, and served on `--hera.rpc.addr` otherwise.
+    pub async fn launch(
+        args: &HeraArgs,
+        config: RollupConfig,
+        datadir: Option<&DataDir>,
+        log_filter: LogFilterHandle,
+        host: Option<&mut dyn HostRpcModules>,
+    ) -> Result<Self> {
+        let config = Arc::new(config);
+        let (l1_rpc, l2_rpc, engine) =
+            (args.l1_rpc_endpoint(), args.l2_rpc_endpoint(), args.l2_engine_endpoint());
+        let l1_rpc = required(l1_rpc.as_ref(), "--hera.l1-rpc-url")?;
+        let l2_rpc = required(l2_rpc.as_ref(), "--hera.l2-rpc-url")?;
+        let engine = required(engine.as_ref(), "--hera.l2-engine-url")?;
+        let (jwt, jwt_path) = args.l2_engine_jwt_secret()?;
+        if args.l2_engine_jwt_secret.is_none() {
+            info!(target: "hera", path = %jwt_path.display(), "Discovered L2 engine JWT secret");
+        }
+
+        // Subsystems talking to the same endpoint share its connections.
+        let clients = HttpClients::default();
+        let l1_logs = Arc::new(RpcChainProvider::from_client(clients.rpc(l1_rpc)?));
+        let l1: Arc<dyn ChainProvider> = l1_logs.clone();
+        let l2 = Arc::new(
+            RpcL2StateProvider::from_client(clients.rpc(l2_rpc)?, config.genesis.clone())
+                .with_isthmus_time(config.isthmus_time),
+        );
+        let engine = Arc::new(EngineClient::from_endpoint(engine.clone(), jwt.clone())?);
+        match engine.identify().await {
+            Ok(execution_layer) => {
+                info!(target: "hera", %execution_layer, "Identified L2 execution layer")
+            }
+            Err(err) => warn!(target: "hera", %err, "Failed to identify the L2 execution layer"),
+        }
+
+        let safe_db = args.safe_db(datadir)?.map(Arc::new);
+        if let Some(dir) = &args.import_era_dir {
+            let (head, _) = l2.block_by_number(l2.safe_head_number().await?).await?;
+            let outputs = Arc::new(OutputRootCache::new(l2.clone(), DEFAULT_OUTPUT_CACHE_SIZE));
+            let mut importer = EraImporter::new(config.clone(), engine.clone())
+                .with_trusted_outputs(outputs, args.import_output_roots.iter().copied());
+            if let Some(safe_db) = &safe_db {
+                importer = importer.with_safe_db(safe_db.clone());
+            }
+            let outcome = importer.import(dir, head).await?;
+            info!(
+                target: "hera",
+                head = %outcome.head,
+                safe = %outcome.safe,
+                imported = outcome.imported,
+                files = outcome.files,
+                verified_outputs = outcome.verified_outputs,
+                "Imported L2 archives"
+            );
+        }
+
+        // Start on top of the safe head of the execution layer, unless forced elsewhere.
+        let start = match args.derivation_start() {
+            Some(start) => start,
+            None => DerivationStart { l2_block: l2.safe_head_number().await?, l1_origin: None },
+        };
+        let (safe_head, l1_origin) = start.resolve(l2.as_ref(), l1.as_ref()).await?;
+
+        // Derivation reads what the primary L1 fails to serve from the failover RPC, if any.
+        let failover_l1 = match &args.l1_failover_rpc_url {
+            Some(url) => {
+                let client = clients.rpc(&args.endpoint(url, &[]))?;
+                Some(Arc::new(RpcChainProvider::from_client(client)) as Arc<dyn ChainProvider>)
+            }
+            None => None,
+        };
+        let derivation_l1 = match &failover_l1 {
+            Some(secondary) => Arc::new(FallbackChainProvider::new(l1.clone(), secondary.clone())),
+            None => l1.clone(),
+        };
+        let derivation_l1 = if args.l1_log_scan_enabled {
+            let addresses = [config.deposit_contract_address, config.l1_system_config_address];
+            let addresses = addresses.into_iter().chain(config.da_challenge_address);
+            let scanner = LogScanningChainProvider::new(derivation_l1, l1_logs, addresses)
+                .with_max_range(args.l1_log_scan_max_range);
+            Arc::new(scanner)
+        } else {
+            derivation_l1
+        };
+
+        let blob_cache = args.blob_cache(datadir)?.map(Arc::new);
+        let buffer = Arc::new(BufferedChainProvider::new(derivation_l1).with_lag(args.exex_lag));
+        let mut pipeline = PipelineBuilder::new(config.clone(), buffer.clone())
+            .l2_chain_provider(l2.clone())
+            .decompression(args.decompression_workers, args.decompression_queue_size);
+        if let Some(endpoint) = args.l1_beacon_endpoint() {
+            let beacon =
+                BeaconClient::new(endpoint.url.clone()).with_http_client(clients.rest(&endpoint)?);
+            let mut blobs: Arc<dyn BlobProvider> = Arc::new(beacon);
+            if let Some(cache) = &blob_cache {
+                blobs = Arc::new(CachedBlobProvider::new(cache.clone(), blobs));
+            }
+            pipeline = pipeline.blob_fetcher(BlobFetcher::new(blobs));
+        } else if let Some(ecotone_time) = config.ecotone_time {
+            warn!(
+                target: "hera",
+                ecotone_time,
+                "No --hera.l1-beacon-url, derivation halts at the first batcher blob"
+            );
+        }
+        let pipeline = pipeline.build()?;
+        let channel_bank = pipeline.channel_bank().subscribe();
+        let pipeline_status = pipeline.subscribe_status();
+
+        let alerter = args.alerter().map(Arc::new);
+        let audit = args.audit_log(datadir).map(AuditLog::open).transpose()?.map(Arc::new);
+        let stats = args.throughput_stats(datadir)?.map(Arc::new);
+        let fees = FeeTracker::default();
+        let validator = CachingValidator::new(
+            RpcAttributesValidator::from_client(clients.rpc(l2_rpc)?),
+            DEFAULT_VALIDATION_CACHE_SIZE,
+        );
+        let mut driver =
+            Driver::new(config.clone(), pipeline, validator, engine.clone(), safe_head)
+                .with_fee_tracker(fees.clone())
+                .with_validation_failure_policy(args.validation_on_failure)
+                .with_l1_provider(l1.clone());
+        if args.dry_run {
+            driver = driver.with_dry_run(engine.clone());
+        }
+        let governor = Arc::new(DerivationGovernor::new(GovernorConfig::default()));
+        driver = driver.with_governor(governor);
+        if let Some(audit) = &audit {
+            driver = driver.with_audit_log(audit.clone());
+        }
+        if let Some(alerter) = &alerter {
+            driver = driver.with_alerter(alerter.clone());
+        }
+        if let Some(safe_db) = &safe_db {
+            driver = driver.with_safe_db(safe_db.clone());
+        }
+        if let Some(stats) = stats {
+            driver = driver.with_throughput_stats(stats);
+        }
+        let unsafe_sync = match &args.unsafe_sync_rpc_url {
+            Some(url) => {
+                let client = clients.rpc(&args.endpoint(url, &[]))?;
+                driver = driver
+                    .with_unsafe_sync(Arc::new(RpcPayloadSource::new(client, config.clone())));
+                Some(Duration::from_secs(args.unsafe_sync_interval))
+            }
+            None => None,
+        };
+        driver.reset(safe_head, l1_origin, "startup").await?;
+        let status = driver.status_sender();
+
+        let mut supervisor = args.supervisor()?.with_chain_id(config.l2_chain_id);
+        let reorg_guard = ReorgGuard::new(args.max_reorg_depth);
+        let clock = Arc::new(args.clock_monitor());
+        let handler = NotificationHandler::new(args.exex_mode, driver)
+            .with_reorg_guard(reorg_guard.clone())
+            .with_paranoid(args.exex_paranoid)
+            .with_readiness(supervisor.readiness())
+            .with_buffer(buffer.clone())
+            .with_clock(clock.clone());
+        let handler = match args.notification_wal(datadir)? {
+            Some(wal) => {
+                if let Some((_, last)) = wal.last()? {
+                    info!(target: "hera", tip = %last.tip, "Resuming notification write-ahead log");
+                }
+                handler.with_wal(wal, l1.clone())
+            }
+            None => handler,
+        };
+        let handler = match (ChallengeWindows::from_config(&config), &safe_db) {
+            (Some(_), Some(safe_db)) => {
+                let l1 = buffer as Arc<dyn ChainProvider>;
+                let watcher = DaChallengeWatcher::new(&config, l1, l2.clone(), safe_db.clone())?;
+                handler.with_da_challenges(watcher)
+            }
+            (Some(_), None) => {
+                warn!(target: "hera", "Alt-DA challenges need the safe head database, ignoring");
+                handler
+            }
+            (None, _) if config.is_alt_da_enabled() => {
+                warn!(target: "hera", "Alt-DA windows unset, ignoring challenges");
+                handler
+            }
+            (None, _) => handler,
+        };
+
+        let (notifications, receiver) = mpsc::channel(NOTIFICATION_BUFFER);
+        if args.metrics_runtime {
+            let mut monitor = RuntimeMonitor::default()
+                .with_runtime("main", Handle::current())
+                .with_channel("notifications", &notifications);
+            if let Some(workers) = supervisor.worker_runtime() {
+                monitor = monitor.with_runtime("workers", workers);
+            }
+            let monitor = Arc::new(monitor);
+            supervisor.spawn("runtime-metrics", move || {
+                let monitor = monitor.clone();
+                Box::pin(async move {
+                    monitor.run(DEFAULT_RUNTIME_METRICS_INTERVAL).await;
+                    Ok(())
+                })
+            });
+        }
+        let poll_interval = Duration::from_secs(args.l1_poll_interval);
+        // The execution outcome feeds the buffer derivation reads from.
+        let outcome = args.exex_mode == NotificationMode::Full;
+        let watched = match failover_l1 {
+            Some(secondary) => {
+                let (watched, primary) = mpsc::channel(NOTIFICATION_BUFFER);
+                let stall_timeout = Duration::from_secs(args.l1_failover_stall_timeout);
+                let failover = L1Failover::new(secondary, l1_origin, stall_timeout)
+                    .with_poll_interval(poll_interval)
+                    .with_execution_outcome(outcome);
+                let failover = Arc::new(Mutex::new(failover));
+                let primary = Arc::new(Mutex::new(primary));
+                supervisor.spawn_service("l1-failover", ServiceStage::Providers, move |ready| {
+                    let (failover, primary) = (failover.clone(), primary.clone());
+                    let notifications = notifications.clone();
+                    Box::pin(async move {
+                        ready.ready();
+                        failover.lock().await.run(&mut *primary.lock().await, notifications).await
+                    })
+                });
+                watched
+            }
+            None => notifications,
+        };
+        let watcher =
+            L1Watcher::new(l1.clone(), l1_origin, poll_interval).with_execution_outcome(outcome);
+        let watcher = Arc::new(Mutex::new(watcher));
+        supervisor.spawn_service("l1-watcher", ServiceStage::Providers, move |ready| {
+            let (watcher, notifications) = (watcher.clone(), watched.clone());
+            Box::pin(async move {
+                let mut watcher = watcher.lock().await;
+                watcher.set_ready(ready);
+                watcher.run(notifications).await
+            })
+        });
+        let handler = Arc::new(Mutex::new(handler));
+        let receiver = Arc::new(Mutex::new(receiver));
+        let mut restarted = false;
+        supervisor.spawn_service("derivation", ServiceStage::Pipeline, move |ready| {
+            let (handler, receiver) = (handler.clone(), receiver.clone());
+            let resync = std::mem::replace(&mut restarted, true);
+            Box::pin(async move {
+                let (handler, receiver) = (&mut *handler.lock().await, &mut *receiver.lock().await);
+                derive(handler, receiver, resync, unsafe_sync, ready).await
+            })
+        });
+        let validation_l2 = l2.clone();
+        supervisor.spawn_service("validation", ServiceStage::Validation, move |ready| {
+            let l2 = validation_l2.clone();
+            Box::pin(async move {
+                // Attributes are validated against the L2 execution layer, which must answer.
+                l2.safe_head_number().await.wrap_err("L2 execution layer unreachable")?;
+                ready.ready();
+                Ok(())
+            })
+        });
+
+        let outputs = Arc::new(OutputRootCache::new(l2.clone(), DEFAULT_OUTPUT_CACHE_SIZE));
+        let mut rollup_node = RollupNodeRpc::new(status.subscribe(), outputs.clone());
+        let mut modules = HeraRpcModules::new();
+        if let Some(safe_db) = safe_db {
+            rollup_node = rollup_node.with_safe_db(safe_db.clone());
+            modules = modules.with_safe_db(SafeDbRpc::new(safe_db))?;
+        }
+        let mut modules = modules
+            .with_rollup_node(rollup_node)?
+            .with_debug(HeraDebugRpc::new(channel_bank, pipeline_status))?
+            .with_build_info()?
+            .with_fee_params(HeraFeeRpc::new(fees, config.clone()))?
+            .with_withdrawal_proofs(HeraWithdrawalRpc::new(
+                WithdrawalProver::new(l2.clone(), outputs.clone()),
+                status.subscribe(),
+            ))?
+            .with_log_level(AdminLogRpc::new(log_filter))?
+            .with_reorg_confirmation(AdminReorgRpc::new(reorg_guard))?;
+        if let Some(url) = &args.tx_forward_sequencer_url {
+            modules =
+                modules.with_tx_forwarder(TxForwarder::new(url, &args.tx_forward_backup_urls)?)?;
+        }
+
+        let sequencer = if args.sequencer_enabled {
+            let attributes = StatefulAttributesBuilder::new(config.clone(), l1.clone());
+            let mut sequencer = Sequencer::new(
+                config.clone(),
+                engine.clone() as Arc<dyn EngineApi>,
+                Box::new(attributes),
+                l1.clone(),
+                status.clone(),
+            )
+            .with_clock(clock);
+            if let Some(url) = &args.conductor_rpc_url {
+                sequencer = sequencer.with_conductor(Arc::new(ConductorClient::new(url.as_str())?));
+            } else {
+                sequencer = sequencer.with_active(!args.sequencer_stopped);
+            }
+            if let Some(builder) = args.sequencer_builder(&jwt)? {
+                let fallback = args.sequencer_builder_fallback;
+                info!(target: "hera::sequencer", %fallback, "Delegating block building to builder");
+                sequencer = sequencer.with_builder(builder);
+            }
+            if let Some(throttle) = args.sequencer_throttle()? {
+                sequencer = sequencer.with_throttle(throttle);
+            }
+            if let Some(audit) = &audit {
+                sequencer = sequencer.with_audit_log(audit.clone());
+            }
+            modules = modules.with_admin(AdminRpc::new(sequencer.handle()))?;
+            Some(sequencer)
+        } else {
+            None
+        };
+
+        modules = modules.in_chain_scope(config.l2_chain_id);
+        if args.rpc_chain_prefix {
+            modules = modules.with_chain_prefix(config.l2_chain_id);
+        }
+        match (args.rpc_attach_to_reth, host) {
+            (true, Some(host)) => modules.attach_to(host)?,
+            (true, None) => bail!("--hera.rpc.attach-to-reth requires running inside a reth node"),
+            (false, _) => {
+                let (addr, mut modules) = (args.rpc_addr, Some(modules));
+                spawn_service_once(&mut supervisor, "rpc", ServiceStage::Rpc, move |ready| {
+                    let modules = modules.take();
+                    Box::pin(async move {
+                        let modules = modules.ok_or_else(|| eyre!("RPC server already ran"))?;
+                        let server = modules.serve(addr).await?;
+                        ready.ready();
+                        // Dropping the handle when the node stops also stops the server.
+                        server.stopped().await;
+                        bail!("RPC server stopped")
+                    })
+                });
+            }
+        }
+
+        if let Some(sequencer) = sequencer {
+            let mut sequencer = Some(sequencer);
+            spawn_service_once(&mut supervisor, "sequencer", ServiceStage::Gossip, move |ready| {
+                let sequencer = sequencer.take();
+                Box::pin(async move {
+                    let sequencer = sequencer.ok_or_else(|| eyre!("sequencer already ran"))?;
+                    ready.ready();
+                    sequencer.run().await;
+                    Ok(())
+                })
+            });
+        }
+
+        if let Some(url) = args.shadow_op_node_url.clone() {
+            let interval = Duration::from_secs(args.shadow_interval);
+            let (status, alerter) = (status.subscribe(), alerter.clone());
+            // Fail at startup on an invalid URL rather than on every restart.
+            ShadowComparator::new(url.as_str(), status.clone(), interval)?;
+            supervisor.spawn("shadow", move || {
+                let shadow =
+                    ShadowComparator::new(url.as_str(), status.clone(), interval).map(|shadow| {
+                        let shadow = shadow.with_output_roots(outputs.clone());
+                        match &alerter {
+                            Some(alerter) => shadow.with_alerter(alerter.clone()),
+                            None => shadow,
+                        }
+                    });
+                Box::pin(async move {
+                    shadow?.run().await;
+                    Ok(())
+                })
+            });
+        }
+
+        if let Some(url) = &args.mempool_preview_l1_rpc_url {
+            let batcher = config
+                .genesis
+                .system_config
+                .as_ref()
+                .map(|system_config| system_config.batcher_address)
+                .ok_or_else(|| eyre!("mempool preview needs the genesis system config"))?;
+            let endpoint = args.endpoint(url, &[]);
+            let source = Arc::new(RpcPendingTxSource::from_client(clients.rpc(&endpoint)?));
+            let interval = Duration::from_secs(args.mempool_preview_interval);
+            let (config, status) = (config.clone(), status.clone());
+            supervisor.spawn("mempool-preview", move || {
+                let preview =
+                    MempoolPreview::new(config.clone(), source.clone(), batcher, interval);
+                let status = status.clone();
+                Box::pin(async move {
+                    preview.run(status).await;
+                    Ok(())
+                })
+            });
+        }
+
+        if let Some(alerter) = alerter {
+            let timeout = Duration::from_secs(args.alert_stall_timeout);
+            let status = status.subscribe();
+            supervisor.spawn("stall-monitor", move || {
+                let monitor = StallMonitor::new(status.clone(), timeout, alerter.clone());
+                Box::pin(async move {
+                    monitor.run().await;
+                    Ok(())
+                })
+            });
+        }
+
+        let lag_status = status.subscribe();
+        supervisor.spawn("lag", move || {
+            let status = lag_status.clone();
+            Box::pin(async move {
+                export_lag(status, DEFAULT_LAG_INTERVAL).await;
+                Ok(())
+            })
+        });
+
+        if let (Some(url), Some(cache)) = (&args.blob_archiver_url, blob_cache) {
+            let from = args.blob_backfill_from_block.unwrap_or(l1_origin.number);
+            let backfill = Arc::new(BlobBackfill::new(
+                l1,
+                Arc::new(
+                    BeaconClient::new(url.clone())
+                        .with_http_client(clients.rest(&args.endpoint(url, &[]))?),
+                ),
+                cache,
+                config.batch_inbox_address,
+            ));
+            let status = status.subscribe();
+            spawn_backfill(&mut supervisor, backfill, from, status);
+        }
+
+        info!(target: "hera", safe_head = %safe_head, l1_origin = %l1_origin, "Launched node");
+        Ok(Self { supervisor })
+    }
+
+    /// Returns the readiness of the services of the node.
+    pub fn readiness(&self) -> Readiness {
+        self.supervisor.readiness()
+    }
+
+    /// Runs the node until one of its tasks fails for good.
+    pub async fn run(self) -> Result<()> {
+        self.supervisor.run().await
+    }
+}
+
+/// Returns the value of a flag the node cannot run without.
+fn required<'a, T>(value: Option<&'a T>, flag: &str) -> Result<&'a T> {
+    value.ok_or_else(|| eyre!("{flag} is required to run the node"))
+}
+
+/// Spawns a service that cannot be rebuilt once started, failing the node if it ever stops.
+fn spawn_service_once<F>(
+    supervisor: &mut Supervisor,
+    name: &'static str,
+    stage: ServiceStage,
+    task: F,
+) where
+    F: FnMut(ReadyHandle) -> crate::supervisor::TaskFuture + Send + 'static,
+{
+    let config = RestartConfig { policy: RestartPolicy::Never, ..Default::default() };
+    supervisor.spawn_service_with_config(name, stage, config, task);
+}
+
+/// Backfills the blob cache from `from` up to the L1 head, once it is known.
+fn spawn_backfill(
+    supervisor: &mut Supervisor,
+    backfill: Arc<BlobBackfill>,
+    from: u64,
+    status: tokio::sync::watch::Receiver<crate::rpc::SyncStatus>,
+) {
+    supervisor.spawn_worker("blob-backfill", move || {
+        let (backfill, mut status) = (backfill.clone(), status.clone());
+        Box::pin(async move {
+            let head = status
+                .wait_for(|status| status.head_l1.number > 0)
+                .await
+                .wrap_err("node stopped before the L1 head was known")?
+                .head_l1;
+            let stored = backfill.run(from, head.number).await?;
+            info!(target: "hera::blobs", stored, to = head.number, "Finished blob backfill");
+            Ok(())
+        })
+    });
+}
+
+/// Derives from every chain notification until the watcher stops. After a restart, the
+/// pipeline is first reset to the safe head, as the failed step may have dropped attributes.
+async fn derive(
+    handler: &mut Handler,
+    notifications: &mut mpsc::Receiver<ChainNotification>,
+    resync: bool,
+    unsafe_sync: Option<Duration>,
+    ready: ReadyHandle,
+) -> Result<()> {
+    if resync {
+        let resynced = handler.driver().resync("derivation restarted").await;
+        resynced.wrap_err("failed to resync derivation")?;
+    }
+    ready.ready();
+    // The unsafe chain is filled between notifications, by the task owning the driver.
+    let mut poll = unsafe_sync.map(|period| {
+        let mut poll = tokio::time::interval(period);
+        poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        poll
+    });
+    loop {
+        tokio::select! {
+            received = notifications.recv() => {
+                let Some(notification) = received else { break };
+                handler.handle(&notification).await?;
+            }
+            _ = tick(poll.as_mut()) => {
+                if let Err(err) = handler.driver().sync_unsafe().await {
+                    warn!(target: "hera", %err, "Failed to fill the unsafe chain");
+                }
+            }
+        }
+    }
+    warn!(target: "hera", "L1 watcher stopped");
+    Ok(())
+}
+
+/// Waits for the next tick of `interval`, forever without one.
+async fn tick(interval: Option<&mut Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
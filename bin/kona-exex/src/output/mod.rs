@@ -0,0 +1,331 @@
+//! L2 output roots, computed without an archive L2 node.
+//!
+//! An output root commits to the state root of an L2 block, the storage root of the
+//! `L2ToL1MessagePasser` predeploy and the block hash. The state root is taken from the payloads
+//! Hera inserts into the engine when available, so only the storage root has to be fetched, with
+//! `eth_getProof`. From Isthmus onwards the block header commits to the storage root in its
+//! withdrawals root, and no proof is needed at all. Computed outputs are cached.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use alloy_primitives::{address, keccak256, Address, B256};
+use async_trait::async_trait;
+use eyre::{ensure, Result};
+use metrics::counter;
+use tracing::trace;
+
+use crate::{engine::EnginePayload, protocol::L2BlockInfo, shadow::OutputRootSource};
+
+mod provider;
+pub use provider::RpcL2StateProvider;
+
+mod withdrawal;
+pub use withdrawal::{
+    OutputRootProof, StorageProof, Withdrawal, WithdrawalProof, WithdrawalProver,
+    WithdrawalProvider, MESSAGE_PASSED_TOPIC,
+};
+
+/// The version of the output root format.
+pub const OUTPUT_ROOT_VERSION: B256 = B256::ZERO;
+
+/// The address of the `L2ToL1MessagePasser` predeploy.
+pub const L2_TO_L1_MESSAGE_PASSER_ADDRESS: Address =
+    address!("4200000000000000000000000000000000000016");
+
+/// The default number of outputs and state roots kept in an [`OutputRootCache`].
+pub const DEFAULT_OUTPUT_CACHE_SIZE: usize = 1024;
+
+/// Computes a version 0 output root.
+pub fn output_root_v0(state_root: B256, withdrawal_storage_root: B256, block_hash: B256) -> B256 {
+    let mut preimage = [0u8; 128];
+    preimage[..32].copy_from_slice(OUTPUT_ROOT_VERSION.as_slice());
+    preimage[32..64].copy_from_slice(state_root.as_slice());
+    preimage[64..96].copy_from_slice(withdrawal_storage_root.as_slice());
+    preimage[96..].copy_from_slice(block_hash.as_slice());
+    keccak256(preimage)
+}
+
+/// An L2 output at a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Output {
+    /// The block the output is computed at.
+    pub block: L2BlockInfo,
+    /// The state root of the block.
+    pub state_root: B256,
+    /// The storage root of the `L2ToL1MessagePasser` predeploy.
+    pub withdrawal_storage_root: B256,
+    /// The output root.
+    pub output_root: B256,
+}
+
+/// The roots of an L2 block header that outputs commit to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRoots {
+    /// The state root of the block.
+    pub state_root: B256,
+    /// The storage root of the `L2ToL1MessagePasser` predeploy, committed to in the withdrawals
+    /// root of the header from Isthmus onwards.
+    pub withdrawals_root: Option<B256>,
+}
+
+impl From<&EnginePayload> for BlockRoots {
+    fn from(payload: &EnginePayload) -> Self {
+        Self { state_root: payload.state_root(), withdrawals_root: payload.withdrawals_root }
+    }
+}
+
+/// The L2 execution layer state needed to compute outputs.
+#[async_trait]
+pub trait L2StateProvider: std::fmt::Debug + Send + Sync {
+    /// Returns the L2 block at `number` and the roots of its header.
+    async fn block_by_number(&self, number: u64) -> Result<(L2BlockInfo, BlockRoots)>;
+
+    /// Returns the storage root of `address` at the block with the given hash.
+    async fn storage_root(&self, address: Address, block_hash: B256) -> Result<B256>;
+}
+
+/// Computes and caches output roots.
+#[derive(Debug)]
+pub struct OutputRootCache {
+    provider: Arc<dyn L2StateProvider>,
+    roots: Mutex<BTreeMap<u64, (B256, BlockRoots)>>,
+    outputs: Mutex<BTreeMap<u64, Output>>,
+    capacity: usize,
+}
+
+impl OutputRootCache {
+    /// Creates a cache fetching missing data from `provider`, keeping at most `capacity` entries.
+    pub fn new(provider: Arc<dyn L2StateProvider>, capacity: usize) -> Self {
+        Self {
+            provider,
+            roots: Mutex::new(BTreeMap::new()),
+            outputs: Mutex::new(BTreeMap::new()),
+            capacity,
+        }
+    }
+
+    /// Records the header roots of a payload inserted into the engine.
+    ///
+    /// A payload replacing a cached block evicts everything cached from that block onwards.
+    pub fn record_payload(&self, payload: &EnginePayload) {
+        let block = payload.block_id();
+        self.evict_reorged(block.number, block.hash);
+        let mut roots = self.roots.lock().unwrap();
+        roots.insert(block.number, (block.hash, payload.into()));
+        evict(&mut roots, self.capacity);
+    }
+
+    /// Drops the cached roots and outputs from block `number` onwards if they were computed for
+    /// another block than `hash`, which was reorged out along with its descendants.
+    fn evict_reorged(&self, number: u64, hash: B256) {
+        let mut roots = self.roots.lock().unwrap();
+        let mut outputs = self.outputs.lock().unwrap();
+        let reorged = roots.get(&number).is_some_and(|(cached, _)| *cached != hash) ||
+            outputs.get(&number).is_some_and(|output| output.block.block_info.hash != hash);
+        if reorged {
+            trace!(target: "hera::output", number, %hash, "Evicting reorged outputs");
+            roots.split_off(&number);
+            outputs.split_off(&number);
+        }
+    }
+
+    /// Returns the output at the given L2 block number.
+    pub async fn output_at_block(&self, number: u64) -> Result<Output> {
+        if let Some(output) = self.outputs.lock().unwrap().get(&number) {
+            counter!("hera_output_cache_hits_total").increment(1);
+            return Ok(*output);
+        }
+        let (block, roots) = self.provider.block_by_number(number).await?;
+        self.compute(block, Some(roots)).await
+    }
+
+    /// Returns the output at `block`.
+    pub async fn output_at(&self, block: &L2BlockInfo) -> Result<Output> {
+        let number = block.block_info.number;
+        if let Some(output) = self.outputs.lock().unwrap().get(&number) {
+            if output.block.block_info.hash == block.block_info.hash {
+                counter!("hera_output_cache_hits_total").increment(1);
+                return Ok(*output);
+            }
+        }
+        self.compute(*block, None).await
+    }
+
+    async fn compute(&self, block: L2BlockInfo, roots: Option<BlockRoots>) -> Result<Output> {
+        counter!("hera_output_cache_misses_total").increment(1);
+        let hash = block.block_info.hash;
+        let number = block.block_info.number;
+
+        let recorded = self
+            .roots
+            .lock()
+            .unwrap()
+            .get(&number)
+            .filter(|(recorded_hash, _)| *recorded_hash == hash)
+            .map(|(_, roots)| *roots);
+        let BlockRoots { state_root, withdrawals_root } = match recorded.or(roots) {
+            Some(roots) => roots,
+            None => {
+                let (fetched, roots) = self.provider.block_by_number(number).await?;
+                ensure!(
+                    fetched.block_info.hash == hash,
+                    "L2 block {number} is {}, expected {hash}",
+                    fetched.block_info.hash
+                );
+                roots
+            }
+        };
+        let withdrawal_storage_root = match withdrawals_root {
+            Some(root) => root,
+            None => self.provider.storage_root(L2_TO_L1_MESSAGE_PASSER_ADDRESS, hash).await?,
+        };
+
+        let output = Output {
+            block,
+            state_root,
+            withdrawal_storage_root,
+            output_root: output_root_v0(state_root, withdrawal_storage_root, hash),
+        };
+        trace!(target: "hera::output", %block, root = %output.output_root, "Computed output root");
+        self.evict_reorged(number, hash);
+        let mut outputs = self.outputs.lock().unwrap();
+        outputs.insert(number, output);
+        evict(&mut outputs, self.capacity);
+        Ok(output)
+    }
+}
+
+#[async_trait]
+impl OutputRootSource for OutputRootCache {
+    async fn output_root_at(&self, block: &L2BlockInfo) -> Result<B256> {
+        Ok(self.output_at(block).await?.output_root)
+    }
+}
+
+/// Drops the lowest block numbers until `map` holds at most `capacity` entries.
+fn evict<V>(map: &mut BTreeMap<u64, V>, capacity: usize) {
+    while map.len() > capacity {
+        map.pop_first();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::b256;
+
+    use super::*;
+    use crate::{
+        engine::mock::{block_hash, payload},
+        protocol::{BlockId, BlockInfo},
+    };
+
+    /// An L2 chain whose block hashes start with `fork`, counting the proofs it serves.
+    #[derive(Debug, Default)]
+    struct TestState {
+        fork: Mutex<u8>,
+        proofs: Mutex<u64>,
+    }
+
+    impl TestState {
+        fn block(&self, number: u64) -> L2BlockInfo {
+            let mut hash = B256::left_padding_from(&number.to_be_bytes());
+            hash[0] = *self.fork.lock().unwrap();
+            L2BlockInfo::new(BlockInfo::new(hash, number, B256::ZERO, 0), BlockId::default(), 0)
+        }
+
+        fn proofs(&self) -> u64 {
+            *self.proofs.lock().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl L2StateProvider for TestState {
+        async fn block_by_number(&self, number: u64) -> Result<(L2BlockInfo, BlockRoots)> {
+            let roots = BlockRoots { state_root: B256::repeat_byte(0x11), withdrawals_root: None };
+            Ok((self.block(number), roots))
+        }
+
+        async fn storage_root(&self, address: Address, _block_hash: B256) -> Result<B256> {
+            assert_eq!(address, L2_TO_L1_MESSAGE_PASSER_ADDRESS);
+            *self.proofs.lock().unwrap() += 1;
+            Ok(B256::repeat_byte(0x22))
+        }
+    }
+
+    #[test]
+    fn computes_known_output_root() {
+        let root = output_root_v0(
+            B256::repeat_byte(0x11),
+            B256::repeat_byte(0x22),
+            B256::repeat_byte(0x33),
+        );
+        assert_eq!(root, b256!("d50bf2ff34ced71be0d2f0be7c2433c6b39d9c3b16c95daf1ed6f24b7578a3b2"));
+    }
+
+    #[tokio::test]
+    async fn caches_outputs() {
+        let state = Arc::new(TestState::default());
+        let cache = OutputRootCache::new(state.clone(), DEFAULT_OUTPUT_CACHE_SIZE);
+
+        let output = cache.output_at_block(10).await.unwrap();
+        assert_eq!(output.block, state.block(10));
+        assert_eq!(
+            output.output_root,
+            output_root_v0(
+                output.state_root,
+                output.withdrawal_storage_root,
+                output.block.block_info.hash
+            )
+        );
+        assert_eq!(cache.output_at_block(10).await.unwrap(), output);
+        assert_eq!(cache.output_at(&state.block(10)).await.unwrap(), output);
+        assert_eq!(state.proofs(), 1);
+    }
+
+    #[tokio::test]
+    async fn reads_isthmus_storage_root_from_the_header() {
+        let state = Arc::new(TestState::default());
+        let cache = OutputRootCache::new(state.clone(), DEFAULT_OUTPUT_CACHE_SIZE);
+        let mut payload = payload(5);
+        payload.withdrawals_root = Some(B256::repeat_byte(0x44));
+        cache.record_payload(&payload);
+
+        let block = L2BlockInfo::new(
+            BlockInfo::new(block_hash(5), 5, block_hash(4), 0),
+            BlockId::default(),
+            0,
+        );
+        let output = cache.output_at(&block).await.unwrap();
+        assert_eq!(output.withdrawal_storage_root, B256::repeat_byte(0x44));
+        assert_eq!(state.proofs(), 0);
+    }
+
+    #[tokio::test]
+    async fn evicts_reorged_outputs() {
+        let state = Arc::new(TestState::default());
+        let cache = OutputRootCache::new(state.clone(), DEFAULT_OUTPUT_CACHE_SIZE);
+        for number in 4..=6 {
+            cache.output_at_block(number).await.unwrap();
+        }
+
+        // Block 5 is replaced, taking block 6 with it but leaving block 4.
+        *state.fork.lock().unwrap() = 1;
+        cache.record_payload(&payload(5));
+        assert_eq!(cache.output_at_block(4).await.unwrap().block.block_info.hash[0], 0);
+        assert_eq!(cache.output_at_block(6).await.unwrap().block, state.block(6));
+        assert_eq!(state.proofs(), 4);
+    }
+
+    #[test]
+    fn evicts_the_lowest_blocks_past_capacity() {
+        let cache = OutputRootCache::new(Arc::new(TestState::default()), 2);
+        for number in 1..=3 {
+            cache.record_payload(&payload(number));
+        }
+        let roots = cache.roots.lock().unwrap();
+        assert_eq!(roots.keys().copied().collect::<Vec<_>>(), [2, 3]);
+    }
+}
@@ -0,0 +1,208 @@
+//! An [`L2StateProvider`] backed by the L2 execution layer's JSON-RPC.
+
+use alloy_primitives::{Address, Bytes, Log, B256, U256, U64};
+use async_trait::async_trait;
+use eyre::{eyre, Result, WrapErr};
+use jsonrpsee::{
+    core::client::ClientT,
+    http_client::{HttpClient, HttpClientBuilder},
+    rpc_params,
+};
+use serde::Deserialize;
+
+use crate::{
+    config::{ChainGenesis, SystemConfig},
+    derive::L2ChainProvider,
+    output::{BlockRoots, L2StateProvider, StorageProof, WithdrawalProvider},
+    protocol::{BlockInfo, L1BlockInfoTx, L2BlockInfo},
+};
+
+/// Reads L2 blocks and proofs from an L2 execution layer RPC.
+#[derive(Debug)]
+pub struct RpcL2StateProvider {
+    client: HttpClient,
+    genesis: ChainGenesis,
+    isthmus_time: Option<u64>,
+}
+
+impl RpcL2StateProvider {
+    /// Creates a provider reading from the L2 RPC at `url`.
+    pub fn new(url: &str, genesis: ChainGenesis) -> Result<Self> {
+        let client = HttpClientBuilder::default()
+            .build(url)
+            .wrap_err_with(|| format!("invalid L2 RPC URL {url}"))?;
+        Ok(Self { client, genesis, isthmus_time: None })
+    }
+
+    /// Creates a provider reading through `client`, e.g. shared from
+    /// [`HttpClients`](crate::endpoint::HttpClients).
+    pub const fn from_client(client: HttpClient, genesis: ChainGenesis) -> Self {
+        Self { client, genesis, isthmus_time: None }
+    }
+
+    /// Reads the `L2ToL1MessagePasser` storage root from the header withdrawals root of blocks
+    /// from `isthmus_time` onwards. Earlier headers carry the root of an empty withdrawals list.
+    pub const fn with_isthmus_time(mut self, isthmus_time: Option<u64>) -> Self {
+        self.isthmus_time = isthmus_time;
+        self
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcBlock {
+    hash: B256,
+    number: U64,
+    parent_hash: B256,
+    timestamp: U64,
+    gas_limit: U64,
+    state_root: B256,
+    #[serde(default)]
+    withdrawals_root: Option<B256>,
+    transactions: Vec<RpcTransaction>,
+}
+
+#[derive(Deserialize)]
+struct RpcTransaction {
+    input: Bytes,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcAccountProof {
+    storage_hash: B256,
+    #[serde(default)]
+    storage_proof: Vec<RpcStorageProof>,
+}
+
+#[derive(Deserialize)]
+struct RpcStorageProof {
+    value: U256,
+    proof: Vec<Bytes>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcReceipt {
+    block_number: U64,
+    logs: Vec<alloy_rpc_types_eth::Log>,
+}
+
+impl RpcL2StateProvider {
+    async fn block(&self, number: u64) -> Result<RpcBlock> {
+        let block: Option<RpcBlock> = self
+            .client
+            .request("eth_getBlockByNumber", rpc_params![U64::from(number), true])
+            .await
+            .wrap_err_with(|| format!("failed to fetch L2 block {number}"))?;
+        block.ok_or_else(|| eyre!("L2 block {number} not found"))
+    }
+
+    /// Returns the number of the safe head of the execution layer, the genesis block until it
+    /// was first set.
+    pub async fn safe_head_number(&self) -> Result<u64> {
+        #[derive(Deserialize)]
+        struct RpcHeader {
+            number: U64,
+        }
+
+        let header: Option<RpcHeader> = self
+            .client
+            .request("eth_getBlockByNumber", rpc_params!["safe", false])
+            .await
+            .wrap_err("failed to fetch the L2 safe head")?;
+        Ok(header.map_or(self.genesis.l2.number, |header| header.number.to()))
+    }
+
+    /// Decodes the L1 info transaction of a post-genesis block.
+    fn l1_info(block: &RpcBlock) -> Result<L1BlockInfoTx> {
+        // The first transaction of every post-genesis block is the L1 info deposit.
+        let number = block.number;
+        let tx = block.transactions.first().ok_or_else(|| eyre!("L2 block {number} is empty"))?;
+        L1BlockInfoTx::decode_calldata(&tx.input)
+            .wrap_err_with(|| format!("invalid L1 info tx in L2 block {number}"))
+    }
+}
+
+#[async_trait]
+impl L2ChainProvider for RpcL2StateProvider {
+    async fn system_config_by_number(&self, number: u64) -> Result<SystemConfig> {
+        if number == self.genesis.l2.number {
+            return self
+                .genesis
+                .system_config
+                .clone()
+                .ok_or_else(|| eyre!("rollup config has no genesis system config"));
+        }
+        let block = self.block(number).await?;
+        Ok(Self::l1_info(&block)?.system_config(block.gas_limit.to()))
+    }
+}
+
+#[async_trait]
+impl L2StateProvider for RpcL2StateProvider {
+    async fn block_by_number(&self, number: u64) -> Result<(L2BlockInfo, BlockRoots)> {
+        let block = self.block(number).await?;
+        let info =
+            BlockInfo::new(block.hash, block.number.to(), block.parent_hash, block.timestamp.to());
+        let l2_info = if info.number == self.genesis.l2.number {
+            L2BlockInfo::new(info, self.genesis.l1, 0)
+        } else {
+            let l1_info = Self::l1_info(&block)?;
+            L2BlockInfo::new(info, l1_info.id(), l1_info.sequence_number())
+        };
+        let isthmus = self.isthmus_time.is_some_and(|time| info.timestamp >= time);
+        let roots = BlockRoots {
+            state_root: block.state_root,
+            withdrawals_root: block.withdrawals_root.filter(|_| isthmus),
+        };
+        Ok((l2_info, roots))
+    }
+
+    async fn storage_root(&self, address: Address, block_hash: B256) -> Result<B256> {
+        let block = serde_json::json!({ "blockHash": block_hash });
+        let proof: RpcAccountProof = self
+            .client
+            .request("eth_getProof", rpc_params![address, Vec::<B256>::new(), block])
+            .await
+            .wrap_err_with(|| format!("failed to fetch proof of {address} at {block_hash}"))?;
+        Ok(proof.storage_hash)
+    }
+}
+
+#[async_trait]
+impl WithdrawalProvider for RpcL2StateProvider {
+    async fn transaction_logs(&self, tx_hash: B256) -> Result<(u64, Vec<Log>)> {
+        let receipt: Option<RpcReceipt> = self
+            .client
+            .request("eth_getTransactionReceipt", rpc_params![tx_hash])
+            .await
+            .wrap_err_with(|| format!("failed to fetch the receipt of L2 transaction {tx_hash}"))?;
+        let receipt = receipt.ok_or_else(|| eyre!("L2 transaction {tx_hash} not found"))?;
+        Ok((receipt.block_number.to(), receipt.logs.into_iter().map(|log| log.inner).collect()))
+    }
+
+    async fn storage_proof(
+        &self,
+        address: Address,
+        slot: B256,
+        block_hash: B256,
+    ) -> Result<StorageProof> {
+        let block = serde_json::json!({ "blockHash": block_hash });
+        let proof: RpcAccountProof = self
+            .client
+            .request("eth_getProof", rpc_params![address, vec![slot], block])
+            .await
+            .wrap_err_with(|| format!("failed to fetch proof of {address} at {block_hash}"))?;
+        let slot_proof = proof
+            .storage_proof
+            .into_iter()
+            .next()
+            .ok_or_else(|| eyre!("proof of {address} at {block_hash} misses slot {slot}"))?;
+        Ok(StorageProof {
+            storage_root: proof.storage_hash,
+            value: slot_proof.value,
+            proof: slot_proof.proof,
+        })
+    }
+}
@@ -0,0 +1,370 @@
+//! Proofs of withdrawals, as submitted to the `OptimismPortal` to prove a withdrawal on L1.
+//!
+//! A withdrawal is initiated on L2 by a `MessagePassed` event of the `L2ToL1MessagePasser`,
+//! which records its hash in the `sentMessages` mapping. Proving it on L1 takes the preimage of
+//! an output root proposed at or after the withdrawal's block, and a proof of the mapping slot
+//! against the predeploy's storage root committed to in that output.
+
+use std::sync::Arc;
+
+use alloy_primitives::{b256, keccak256, Address, Bytes, Log, B256, U256};
+use async_trait::async_trait;
+use eyre::{bail, ensure, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    output::{OutputRootCache, L2_TO_L1_MESSAGE_PASSER_ADDRESS, OUTPUT_ROOT_VERSION},
+    protocol::L2BlockInfo,
+};
+
+/// Topic of the `MessagePassed(uint256,address,address,uint256,uint256,bytes,bytes32)` event of
+/// the `L2ToL1MessagePasser`.
+pub const MESSAGE_PASSED_TOPIC: B256 =
+    b256!("02a52367d10742d8032712c1bb8e0144ff1ec5ffda1ed7d70bb05a2744955054");
+
+/// A withdrawal transaction, as hashed by the `L2ToL1MessagePasser` and relayed by the
+/// `OptimismPortal`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Withdrawal {
+    /// The nonce of the message.
+    pub nonce: U256,
+    /// The L2 sender of the message.
+    pub sender: Address,
+    /// The L1 target of the message.
+    pub target: Address,
+    /// The ETH value sent along.
+    pub value: U256,
+    /// The gas limit of the L1 call.
+    pub gas_limit: U256,
+    /// The calldata of the L1 call.
+    pub data: Bytes,
+}
+
+impl Withdrawal {
+    /// Decodes a withdrawal from a `MessagePassed` event, checking the hash it carries.
+    pub fn from_log(log: &Log) -> Result<Self> {
+        let topics = log.topics();
+        ensure!(topics.len() == 4, "expected 4 message passed topics, got {}", topics.len());
+        ensure!(topics[0] == MESSAGE_PASSED_TOPIC, "not a message passed event");
+
+        // The data ABI-encodes the value, the gas limit, the calldata and the withdrawal hash.
+        let data = log.data.data.as_ref();
+        ensure!(data.len() >= 160 && data.len() % 32 == 0, "invalid message passed data length");
+        let word = |index: usize| &data[index * 32..(index + 1) * 32];
+        let offset = U256::from_be_slice(word(2));
+        ensure!(offset == U256::from(128), "invalid message passed data offset {offset}");
+        let len = U256::from_be_slice(word(4));
+        let len = usize::try_from(len).ok().filter(|len| 160 + len <= data.len());
+        let Some(len) = len else { bail!("message passed calldata exceeds the log data") };
+
+        let withdrawal = Self {
+            nonce: U256::from_be_bytes(topics[1].0),
+            sender: Address::from_word(topics[2]),
+            target: Address::from_word(topics[3]),
+            value: U256::from_be_slice(word(0)),
+            gas_limit: U256::from_be_slice(word(1)),
+            data: Bytes::copy_from_slice(&data[160..160 + len]),
+        };
+        let hash = B256::from_slice(word(3));
+        ensure!(
+            withdrawal.hash() == hash,
+            "withdrawal hashes to {}, but the event carries {hash}",
+            withdrawal.hash()
+        );
+        Ok(withdrawal)
+    }
+
+    /// Returns the hash of the withdrawal, the ABI encoding of its fields.
+    pub fn hash(&self) -> B256 {
+        let padded = self.data.len().div_ceil(32) * 32;
+        let mut encoded = Vec::with_capacity(7 * 32 + padded);
+        encoded.extend_from_slice(&self.nonce.to_be_bytes::<32>());
+        encoded.extend_from_slice(self.sender.into_word().as_slice());
+        encoded.extend_from_slice(self.target.into_word().as_slice());
+        encoded.extend_from_slice(&self.value.to_be_bytes::<32>());
+        encoded.extend_from_slice(&self.gas_limit.to_be_bytes::<32>());
+        encoded.extend_from_slice(&U256::from(6 * 32).to_be_bytes::<32>());
+        encoded.extend_from_slice(&U256::from(self.data.len()).to_be_bytes::<32>());
+        encoded.extend_from_slice(&self.data);
+        encoded.resize(7 * 32 + padded, 0);
+        keccak256(encoded)
+    }
+
+    /// Returns the slot of the withdrawal in the `sentMessages` mapping, the first storage
+    /// variable of the `L2ToL1MessagePasser`.
+    pub fn storage_slot(&self) -> B256 {
+        let mut preimage = [0u8; 64];
+        preimage[..32].copy_from_slice(self.hash().as_slice());
+        keccak256(preimage)
+    }
+}
+
+/// A proof of a storage slot of an account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageProof {
+    /// The storage root of the account.
+    pub storage_root: B256,
+    /// The value of the slot.
+    pub value: U256,
+    /// The trie nodes from the storage root to the slot.
+    pub proof: Vec<Bytes>,
+}
+
+/// The L2 execution layer data needed to prove withdrawals.
+#[async_trait]
+pub trait WithdrawalProvider: std::fmt::Debug + Send + Sync {
+    /// Returns the number of the L2 block including the transaction `tx_hash`, and the logs
+    /// the transaction emitted.
+    async fn transaction_logs(&self, tx_hash: B256) -> Result<(u64, Vec<Log>)>;
+
+    /// Returns the proof of `slot` of `address` at the block with the given hash.
+    async fn storage_proof(
+        &self,
+        address: Address,
+        slot: B256,
+        block_hash: B256,
+    ) -> Result<StorageProof>;
+}
+
+/// The preimage of an output root, as taken by the `OptimismPortal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputRootProof {
+    /// The output root version.
+    pub version: B256,
+    /// The state root of the block.
+    pub state_root: B256,
+    /// The storage root of the `L2ToL1MessagePasser` predeploy.
+    pub message_passer_storage_root: B256,
+    /// The hash of the block.
+    pub latest_blockhash: B256,
+}
+
+/// Everything needed to prove a withdrawal on L1 against the output at an L2 block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WithdrawalProof {
+    /// The withdrawal transaction.
+    pub withdrawal: Withdrawal,
+    /// The hash of the withdrawal.
+    pub withdrawal_hash: B256,
+    /// The slot of the withdrawal in the `L2ToL1MessagePasser` storage.
+    pub storage_slot: B256,
+    /// The L2 block the output is computed at.
+    pub l2_block: L2BlockInfo,
+    /// The output root to prove the withdrawal against.
+    pub output_root: B256,
+    /// The preimage of the output root.
+    pub output_root_proof: OutputRootProof,
+    /// The trie nodes from the `L2ToL1MessagePasser` storage root to the withdrawal slot.
+    pub withdrawal_proof: Vec<Bytes>,
+}
+
+/// Assembles the proofs of withdrawals.
+#[derive(Debug)]
+pub struct WithdrawalProver {
+    provider: Arc<dyn WithdrawalProvider>,
+    outputs: Arc<OutputRootCache>,
+}
+
+impl WithdrawalProver {
+    /// Creates a prover reading from `provider`, computing outputs with `outputs`.
+    pub fn new(provider: Arc<dyn WithdrawalProvider>, outputs: Arc<OutputRootCache>) -> Self {
+        Self { provider, outputs }
+    }
+
+    /// Returns the proofs of the withdrawals initiated by the L2 transaction `tx_hash`, usually
+    /// a single one, against the output at the L2 block `l2_block`.
+    pub async fn prove(&self, tx_hash: B256, l2_block: u64) -> Result<Vec<WithdrawalProof>> {
+        let (number, logs) = self.provider.transaction_logs(tx_hash).await?;
+        ensure!(
+            number <= l2_block,
+            "transaction {tx_hash} is in L2 block {number}, after the output block {l2_block}"
+        );
+        let withdrawals = logs
+            .iter()
+            .filter(|log| {
+                log.address == L2_TO_L1_MESSAGE_PASSER_ADDRESS &&
+                    log.topics().first() == Some(&MESSAGE_PASSED_TOPIC)
+            })
+            .map(Withdrawal::from_log)
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(!withdrawals.is_empty(), "transaction {tx_hash} initiated no withdrawal");
+
+        let output = self.outputs.output_at_block(l2_block).await?;
+        let block_hash = output.block.block_info.hash;
+        let mut proofs = Vec::with_capacity(withdrawals.len());
+        for withdrawal in withdrawals {
+            let slot = withdrawal.storage_slot();
+            let proof = self
+                .provider
+                .storage_proof(L2_TO_L1_MESSAGE_PASSER_ADDRESS, slot, block_hash)
+                .await?;
+            ensure!(
+                proof.storage_root == output.withdrawal_storage_root,
+                "proof of L2 block {l2_block} is against storage root {}, expected {}",
+                proof.storage_root,
+                output.withdrawal_storage_root
+            );
+            ensure!(!proof.value.is_zero(), "withdrawal {} is not sent", withdrawal.hash());
+            proofs.push(WithdrawalProof {
+                withdrawal_hash: withdrawal.hash(),
+                storage_slot: slot,
+                withdrawal,
+                l2_block: output.block,
+                output_root: output.output_root,
+                output_root_proof: OutputRootProof {
+                    version: OUTPUT_ROOT_VERSION,
+                    state_root: output.state_root,
+                    message_passer_storage_root: output.withdrawal_storage_root,
+                    latest_blockhash: block_hash,
+                },
+                withdrawal_proof: proof.proof,
+            });
+        }
+        Ok(proofs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::LogData;
+
+    use super::*;
+    use crate::{
+        output::{output_root_v0, BlockRoots, L2StateProvider, DEFAULT_OUTPUT_CACHE_SIZE},
+        protocol::{BlockId, BlockInfo},
+    };
+
+    const STORAGE_ROOT: B256 = B256::repeat_byte(0x22);
+
+    fn withdrawal() -> Withdrawal {
+        Withdrawal {
+            nonce: U256::from(1) << 240 | U256::from(7),
+            sender: Address::repeat_byte(0x01),
+            target: Address::repeat_byte(0x02),
+            value: U256::from(1_000),
+            gas_limit: U256::from(100_000),
+            data: Bytes::from_static(b"a withdrawal of more than a word"),
+        }
+    }
+
+    fn message_passed(withdrawal: &Withdrawal) -> Log {
+        let topics = vec![
+            MESSAGE_PASSED_TOPIC,
+            withdrawal.nonce.into(),
+            withdrawal.sender.into_word(),
+            withdrawal.target.into_word(),
+        ];
+        let mut data = Vec::new();
+        data.extend_from_slice(&withdrawal.value.to_be_bytes::<32>());
+        data.extend_from_slice(&withdrawal.gas_limit.to_be_bytes::<32>());
+        data.extend_from_slice(&U256::from(128).to_be_bytes::<32>());
+        data.extend_from_slice(withdrawal.hash().as_slice());
+        data.extend_from_slice(&U256::from(withdrawal.data.len()).to_be_bytes::<32>());
+        data.extend_from_slice(&withdrawal.data);
+        data.resize(data.len().div_ceil(32) * 32, 0);
+        Log {
+            address: L2_TO_L1_MESSAGE_PASSER_ADDRESS,
+            data: LogData::new(topics, data.into()).unwrap(),
+        }
+    }
+
+    /// An L2 chain whose block `n` has the hash `n`, where transaction `n` initiated the test
+    /// withdrawal in block `n`.
+    #[derive(Debug)]
+    struct TestChain;
+
+    #[async_trait]
+    impl L2StateProvider for TestChain {
+        async fn block_by_number(&self, number: u64) -> Result<(L2BlockInfo, BlockRoots)> {
+            let hash = B256::left_padding_from(&number.to_be_bytes());
+            let block = L2BlockInfo::new(
+                BlockInfo::new(hash, number, B256::ZERO, 0),
+                BlockId::default(),
+                0,
+            );
+            let roots = BlockRoots {
+                state_root: B256::repeat_byte(0x11),
+                withdrawals_root: Some(STORAGE_ROOT),
+            };
+            Ok((block, roots))
+        }
+
+        async fn storage_root(&self, _address: Address, _block_hash: B256) -> Result<B256> {
+            unreachable!("the withdrawals root is in the header")
+        }
+    }
+
+    #[async_trait]
+    impl WithdrawalProvider for TestChain {
+        async fn transaction_logs(&self, tx_hash: B256) -> Result<(u64, Vec<Log>)> {
+            let number = U256::from_be_bytes(tx_hash.0).to();
+            Ok((number, vec![message_passed(&withdrawal())]))
+        }
+
+        async fn storage_proof(
+            &self,
+            address: Address,
+            slot: B256,
+            _block_hash: B256,
+        ) -> Result<StorageProof> {
+            assert_eq!(address, L2_TO_L1_MESSAGE_PASSER_ADDRESS);
+            assert_eq!(slot, withdrawal().storage_slot());
+            Ok(StorageProof {
+                storage_root: STORAGE_ROOT,
+                value: U256::from(1),
+                proof: vec![Bytes::from_static(&[0xc0])],
+            })
+        }
+    }
+
+    #[test]
+    fn computes_the_event_topic() {
+        assert_eq!(
+            MESSAGE_PASSED_TOPIC,
+            keccak256("MessagePassed(uint256,address,address,uint256,uint256,bytes,bytes32)")
+        );
+    }
+
+    #[test]
+    fn decodes_message_passed_events() {
+        let withdrawal = withdrawal();
+        let log = message_passed(&withdrawal);
+        assert_eq!(Withdrawal::from_log(&log).unwrap(), withdrawal);
+
+        let mut tampered = log;
+        let mut data = tampered.data.data.to_vec();
+        data[31] ^= 1;
+        tampered.data = LogData::new(tampered.topics().to_vec(), data.into()).unwrap();
+        let err = Withdrawal::from_log(&tampered).unwrap_err();
+        assert!(err.to_string().contains("hashes to"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn proves_withdrawals_against_an_output() {
+        let chain = Arc::new(TestChain);
+        let outputs = Arc::new(OutputRootCache::new(chain.clone(), DEFAULT_OUTPUT_CACHE_SIZE));
+        let prover = WithdrawalProver::new(chain, outputs);
+
+        let tx_hash = B256::with_last_byte(5);
+        let proofs = prover.prove(tx_hash, 8).await.unwrap();
+        assert_eq!(proofs.len(), 1);
+        let proof = &proofs[0];
+        assert_eq!(proof.withdrawal, withdrawal());
+        assert_eq!(proof.l2_block.block_info.number, 8);
+        let preimage = proof.output_root_proof;
+        assert_eq!(
+            proof.output_root,
+            output_root_v0(
+                preimage.state_root,
+                preimage.message_passer_storage_root,
+                preimage.latest_blockhash
+            )
+        );
+
+        let err = prover.prove(tx_hash, 4).await.unwrap_err();
+        assert!(err.to_string().contains("after the output block"), "{err}");
+    }
+}
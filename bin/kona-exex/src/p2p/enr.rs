@@ -0,0 +1,330 @@
+//! Ethereum Node Records (EIP-778), advertising the node to discv5 peers.
+//!
+//! A record is the RLP list of a signature, a sequence number and sorted key-value pairs, shared
+//! as `enr:` followed by its URL-safe base64 encoding. Hera signs its records with the `v4`
+//! identity scheme, over secp256k1, and advertises the OP Stack chain it gossips blocks of under
+//! the `opstack` key.
+
+use std::{collections::BTreeMap, fmt, net::Ipv4Addr, str::FromStr};
+
+use alloy_primitives::{keccak256, Bytes, B256};
+use alloy_rlp::{Decodable, Encodable, Header};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use eyre::{bail, ensure, eyre, Result, WrapErr};
+use k256::ecdsa::{signature::hazmat::PrehashVerifier, Signature, SigningKey, VerifyingKey};
+
+/// Maximum size of an encoded record.
+pub const MAX_ENR_SIZE: usize = 300;
+
+/// Prefix of the text form of a record.
+const ENR_PREFIX: &str = "enr:";
+
+/// Version of the `opstack` entry.
+const OPSTACK_ENR_VERSION: u64 = 0;
+
+/// A signed Ethereum Node Record.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Enr {
+    seq: u64,
+    /// The RLP encoded value of each key.
+    pairs: BTreeMap<Vec<u8>, Bytes>,
+    signature: [u8; 64],
+}
+
+impl Enr {
+    /// Returns the sequence number, increased on every change of the record.
+    pub const fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Returns the public key of the node.
+    pub fn public_key(&self) -> Result<VerifyingKey> {
+        let key = self.value::<Bytes>(b"secp256k1")?.ok_or_else(|| eyre!("record has no key"))?;
+        VerifyingKey::from_sec1_bytes(&key).map_err(|_| eyre!("invalid record public key"))
+    }
+
+    /// Returns the node ID: the hash of the uncompressed public key.
+    pub fn node_id(&self) -> Result<B256> {
+        Ok(keccak256(&self.public_key()?.to_encoded_point(false).as_bytes()[1..]))
+    }
+
+    /// Returns the advertised IPv4 address.
+    pub fn ip4(&self) -> Result<Option<Ipv4Addr>> {
+        let Some(ip) = self.value::<Bytes>(b"ip")? else { return Ok(None) };
+        let octets: [u8; 4] = ip.as_ref().try_into().map_err(|_| eyre!("invalid record ip"))?;
+        Ok(Some(octets.into()))
+    }
+
+    /// Returns the advertised TCP port, of libp2p.
+    pub fn tcp4(&self) -> Result<Option<u16>> {
+        self.value(b"tcp")
+    }
+
+    /// Returns the advertised UDP port, of discv5.
+    pub fn udp4(&self) -> Result<Option<u16>> {
+        self.value(b"udp")
+    }
+
+    /// Returns the L2 chain ID of the `opstack` entry.
+    pub fn l2_chain_id(&self) -> Result<Option<u64>> {
+        let Some(data) = self.value::<Bytes>(b"opstack")? else { return Ok(None) };
+        let mut data = data.as_ref();
+        let chain_id = get_uvarint(&mut data)?;
+        let version = get_uvarint(&mut data)?;
+        ensure!(version == OPSTACK_ENR_VERSION, "unsupported opstack entry version {version}");
+        Ok(Some(chain_id))
+    }
+
+    /// Returns true if the record has the same entries and key as `other`, regardless of their
+    /// sequence numbers.
+    pub fn same_content(&self, other: &Self) -> bool {
+        self.pairs == other.pairs
+    }
+
+    /// Encodes the record into its RLP form.
+    pub fn encode(&self) -> Vec<u8> {
+        encode_record(Some(&self.signature), self.seq, &self.pairs)
+    }
+
+    /// Decodes a record from its RLP form, checking its signature.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        ensure!(data.len() <= MAX_ENR_SIZE, "record larger than {MAX_ENR_SIZE} bytes");
+        let mut buf = data;
+        let header = Header::decode(&mut buf)?;
+        ensure!(header.list && header.payload_length == buf.len(), "record is not an RLP list");
+
+        let signature = Bytes::decode(&mut buf)?;
+        let signature: [u8; 64] =
+            signature.as_ref().try_into().map_err(|_| eyre!("invalid record signature size"))?;
+        let seq = u64::decode(&mut buf)?;
+        let mut pairs = BTreeMap::new();
+        let mut last_key: Option<Vec<u8>> = None;
+        while !buf.is_empty() {
+            let key = Bytes::decode(&mut buf)?.to_vec();
+            ensure!(!buf.is_empty(), "record key {} has no value", String::from_utf8_lossy(&key));
+            ensure!(last_key.as_ref().map_or(true, |last| *last < key), "record keys not sorted");
+            let value = take_raw(&mut buf)?;
+            last_key = Some(key.clone());
+            pairs.insert(key, value);
+        }
+
+        let enr = Self { seq, pairs, signature };
+        let id = enr.value::<Bytes>(b"id")?;
+        ensure!(id.is_some_and(|id| id.as_ref() == b"v4"), "unsupported record identity scheme");
+        let hash = keccak256(encode_record(None, enr.seq, &enr.pairs));
+        let signature =
+            Signature::from_slice(&signature).map_err(|_| eyre!("invalid record signature"))?;
+        enr.public_key()?
+            .verify_prehash(hash.as_slice(), &signature)
+            .map_err(|_| eyre!("invalid record signature"))?;
+        Ok(enr)
+    }
+
+    /// Decodes the value of `key`.
+    fn value<T: Decodable>(&self, key: &[u8]) -> Result<Option<T>> {
+        self.pairs
+            .get(key)
+            .map(|value| {
+                T::decode(&mut value.as_ref()).wrap_err_with(|| {
+                    format!("invalid record entry {}", String::from_utf8_lossy(key))
+                })
+            })
+            .transpose()
+    }
+}
+
+impl fmt::Display for Enr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{ENR_PREFIX}{}", URL_SAFE_NO_PAD.encode(self.encode()))
+    }
+}
+
+impl fmt::Debug for Enr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl FromStr for Enr {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some(data) = s.strip_prefix(ENR_PREFIX) else { bail!("record must start with enr:") };
+        let data = URL_SAFE_NO_PAD
+            .decode(data.trim_end_matches('='))
+            .map_err(|err| eyre!("invalid record encoding: {err}"))?;
+        Self::decode(&data)
+    }
+}
+
+/// Builds the record of the local node.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EnrBuilder {
+    seq: u64,
+    ip4: Option<Ipv4Addr>,
+    tcp4: Option<u16>,
+    udp4: Option<u16>,
+    l2_chain_id: Option<u64>,
+}
+
+impl EnrBuilder {
+    /// Creates a builder of a record with sequence number `seq`.
+    pub fn new(seq: u64) -> Self {
+        Self { seq, ..Default::default() }
+    }
+
+    /// Sets the sequence number.
+    pub const fn with_seq(mut self, seq: u64) -> Self {
+        self.seq = seq;
+        self
+    }
+
+    /// Advertises the IPv4 address `ip`.
+    pub const fn with_ip4(mut self, ip: Ipv4Addr) -> Self {
+        self.ip4 = Some(ip);
+        self
+    }
+
+    /// Advertises the libp2p TCP port `port`.
+    pub const fn with_tcp4(mut self, port: u16) -> Self {
+        self.tcp4 = Some(port);
+        self
+    }
+
+    /// Advertises the discv5 UDP port `port`.
+    pub const fn with_udp4(mut self, port: u16) -> Self {
+        self.udp4 = Some(port);
+        self
+    }
+
+    /// Advertises the OP Stack chain `l2_chain_id`.
+    pub const fn with_l2_chain_id(mut self, l2_chain_id: u64) -> Self {
+        self.l2_chain_id = Some(l2_chain_id);
+        self
+    }
+
+    /// Signs the record with the node's identity `key`.
+    pub fn sign(&self, key: &SigningKey) -> Result<Enr> {
+        let mut pairs = BTreeMap::new();
+        let mut insert = |key: &[u8], value: &dyn Encodable| {
+            pairs.insert(key.to_vec(), alloy_rlp::encode(value).into());
+        };
+        insert(b"id", &b"v4".as_slice());
+        insert(b"secp256k1", &key.verifying_key().to_encoded_point(true).as_bytes());
+        if let Some(ip) = self.ip4 {
+            insert(b"ip", &ip.octets().as_slice());
+        }
+        if let Some(port) = self.tcp4 {
+            insert(b"tcp", &port);
+        }
+        if let Some(port) = self.udp4 {
+            insert(b"udp", &port);
+        }
+        if let Some(chain_id) = self.l2_chain_id {
+            let mut opstack = Vec::new();
+            put_uvarint(&mut opstack, chain_id);
+            put_uvarint(&mut opstack, OPSTACK_ENR_VERSION);
+            insert(b"opstack", &opstack.as_slice());
+        }
+
+        let hash = keccak256(encode_record(None, self.seq, &pairs));
+        let (signature, _) =
+            key.sign_prehash_recoverable(hash.as_slice()).wrap_err("failed to sign record")?;
+        let enr = Enr { seq: self.seq, pairs, signature: signature.to_bytes().into() };
+        ensure!(enr.encode().len() <= MAX_ENR_SIZE, "record larger than {MAX_ENR_SIZE} bytes");
+        Ok(enr)
+    }
+}
+
+/// Encodes a record, or its signed content without the signature.
+fn encode_record(
+    signature: Option<&[u8; 64]>,
+    seq: u64,
+    pairs: &BTreeMap<Vec<u8>, Bytes>,
+) -> Vec<u8> {
+    let mut payload = Vec::new();
+    if let Some(signature) = signature {
+        signature.as_slice().encode(&mut payload);
+    }
+    seq.encode(&mut payload);
+    for (key, value) in pairs {
+        key.as_slice().encode(&mut payload);
+        payload.extend_from_slice(value);
+    }
+    let mut out = Vec::with_capacity(payload.len() + 3);
+    Header { list: true, payload_length: payload.len() }.encode(&mut out);
+    out.extend(payload);
+    out
+}
+
+/// Takes the next RLP item off `buf`, still encoded.
+fn take_raw(buf: &mut &[u8]) -> Result<Bytes> {
+    let start = *buf;
+    let header = Header::decode(buf)?;
+    ensure!(buf.len() >= header.payload_length, "record entry too short");
+    *buf = &buf[header.payload_length..];
+    Ok(Bytes::copy_from_slice(&start[..start.len() - buf.len()]))
+}
+
+fn put_uvarint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn get_uvarint(data: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = data.split_first().ok_or_else(|| eyre!("truncated uvarint"))?;
+        *data = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            return Ok(value);
+        }
+    }
+    bail!("uvarint overflows u64")
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::b256;
+
+    use super::*;
+
+    /// The example record of EIP-778.
+    const EIP_778_ENR: &str = "enr:-IS4QHCYrYZbAKWCBRlAy5zzaDZXJBGkcnh4MHcBFZntXNFrdvJjX04jRzjzCBOonrkTfj499SZuOh8R33Ls8RRcy5wBgmlkgnY0gmlwhH8AAAGJc2VjcDI1NmsxoQPKY0yuDUmstAHYpMa2_oxVtw0RW_QAdpzBQA8yWM0xOIN1ZHCCdl8";
+
+    #[test]
+    fn signs_and_decodes_eip_778_record() {
+        let key = SigningKey::from_slice(
+            b256!("b71c71a67e1177ad4e901695e1b4b9ee17ae16c6668d313eac2f96dbcda3f291").as_slice(),
+        )
+        .unwrap();
+        let enr =
+            EnrBuilder::new(1).with_ip4(Ipv4Addr::LOCALHOST).with_udp4(30303).sign(&key).unwrap();
+        assert_eq!(enr.to_string(), EIP_778_ENR);
+
+        let decoded: Enr = EIP_778_ENR.parse().unwrap();
+        assert_eq!(decoded, enr);
+        assert_eq!(
+            decoded.node_id().unwrap(),
+            b256!("a448f24c6d18e575453db13171562b71999873db5b286df957af199ec94617f7")
+        );
+        assert_eq!(decoded.ip4().unwrap(), Some(Ipv4Addr::LOCALHOST));
+        assert_eq!(decoded.udp4().unwrap(), Some(30303));
+        assert_eq!(decoded.tcp4().unwrap(), None);
+
+        let opstack = EnrBuilder::new(2).with_tcp4(9222).with_l2_chain_id(8453).sign(&key).unwrap();
+        let decoded: Enr = opstack.to_string().parse().unwrap();
+        assert_eq!(decoded.l2_chain_id().unwrap(), Some(8453));
+        assert_eq!(decoded.tcp4().unwrap(), Some(9222));
+
+        // Any change to the content voids the signature.
+        let mut tampered = enr.encode();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(Enr::decode(&tampered).is_err());
+    }
+}
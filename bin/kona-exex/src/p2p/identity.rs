@@ -0,0 +1,120 @@
+//! The node's p2p identity: its secp256k1 key and the record advertising it.
+
+use std::{fs, io::ErrorKind, path::Path};
+
+use alloy_primitives::{hex, B256};
+use eyre::{eyre, Result, WrapErr};
+use k256::ecdsa::SigningKey;
+use tracing::{info, warn};
+
+use super::{Enr, EnrBuilder};
+
+/// The identity of the node on the p2p network.
+#[derive(Debug, Clone)]
+pub struct P2pIdentity {
+    /// The secp256k1 key identifying the node.
+    pub key: SigningKey,
+    /// The signed record advertising the node.
+    pub enr: Enr,
+}
+
+impl P2pIdentity {
+    /// Loads the identity key from `key_path`, generating it on first start, and signs the record
+    /// of `builder`.
+    ///
+    /// With `enr_path`, the record is also persisted, so that its sequence number keeps
+    /// increasing across restarts whenever its content changes, as peers only replace records
+    /// they know of with higher sequence numbers. Without `key_path`, the identity is ephemeral.
+    pub fn load(
+        key_path: Option<&Path>,
+        enr_path: Option<&Path>,
+        builder: EnrBuilder,
+    ) -> Result<Self> {
+        let key = match key_path {
+            Some(path) => load_or_create_key(path)?,
+            None => {
+                warn!(target: "hera::p2p", "No p2p key path, using an ephemeral identity");
+                random_key()
+            }
+        };
+        let Some(enr_path) = enr_path else {
+            return Ok(Self { enr: builder.sign(&key)?, key });
+        };
+
+        let previous = match fs::read_to_string(enr_path) {
+            Ok(enr) => match enr.trim().parse::<Enr>() {
+                Ok(enr) => Some(enr),
+                Err(err) => {
+                    warn!(
+                        target: "hera::p2p",
+                        path = %enr_path.display(),
+                        %err,
+                        "Ignoring invalid persisted record"
+                    );
+                    None
+                }
+            },
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => {
+                return Err(err).wrap_err_with(|| format!("failed to read {}", enr_path.display()))
+            }
+        };
+        let seq = previous.as_ref().map_or(1, Enr::seq);
+        let enr = builder.with_seq(seq).sign(&key)?;
+        let enr = match previous {
+            Some(previous) if previous.same_content(&enr) => previous,
+            Some(_) => builder.with_seq(seq + 1).sign(&key)?,
+            None => enr,
+        };
+        fs::write(enr_path, format!("{enr}\n"))
+            .wrap_err_with(|| format!("failed to write {}", enr_path.display()))?;
+        Ok(Self { key, enr })
+    }
+}
+
+/// Loads the hex encoded identity key at `path`, generating and storing a new one if there is
+/// none yet.
+pub fn load_or_create_key(path: &Path) -> Result<SigningKey> {
+    match fs::read_to_string(path) {
+        Ok(key) => {
+            let key = hex::decode(key.trim())
+                .ok()
+                .and_then(|key| SigningKey::from_slice(&key).ok())
+                .ok_or_else(|| eyre!("invalid p2p key in {}", path.display()))?;
+            Ok(key)
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            let key = random_key();
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .wrap_err_with(|| format!("failed to create {}", parent.display()))?;
+            }
+            write_private(path, &hex::encode(key.to_bytes()))
+                .wrap_err_with(|| format!("failed to write p2p key {}", path.display()))?;
+            info!(target: "hera::p2p", path = %path.display(), "Generated p2p identity key");
+            Ok(key)
+        }
+        Err(err) => Err(err).wrap_err_with(|| format!("failed to read {}", path.display())),
+    }
+}
+
+/// Generates a random identity key.
+fn random_key() -> SigningKey {
+    loop {
+        // Virtually every 32-byte string is a valid key.
+        if let Ok(key) = SigningKey::from_slice(B256::random().as_slice()) {
+            return key;
+        }
+    }
+}
+
+/// Writes `contents` to a new file at `path` only readable by its owner.
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())
+}
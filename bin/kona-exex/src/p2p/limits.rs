@@ -0,0 +1,341 @@
+//! Protection of block gossip against floods.
+//!
+//! The [`GossipGuard`] runs before a message is decompressed and decoded: it rate limits every
+//! peer and the node as a whole, rejects messages whose compressed or announced decompressed size
+//! exceeds the gossip limit, and rejects blocks too far from the local clock to be worth
+//! verifying. The timestamps of decoded blocks also feed the estimate of the local clock's skew.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use alloy_primitives::B256;
+use metrics::counter;
+use tokio::time::Instant;
+use tracing::debug;
+
+use crate::{
+    clock::{ClockMonitor, ClockSource},
+    p2p::snappy,
+};
+
+/// Maximum size of a gossip message, compressed or decompressed, as in op-node.
+pub const MAX_GOSSIP_SIZE: usize = 10 * (1 << 20);
+
+/// Default number of messages per second accepted from a peer.
+pub const DEFAULT_PEER_MESSAGE_RATE: u32 = 10;
+
+/// Default number of messages per second accepted from all peers.
+pub const DEFAULT_GLOBAL_MESSAGE_RATE: u32 = 100;
+
+/// Default number of seconds a block timestamp may be ahead of the local clock.
+pub const DEFAULT_MAX_FUTURE_BLOCK_TIME: u64 = 5;
+
+/// Default number of seconds a block timestamp may be behind the local clock.
+pub const DEFAULT_MAX_PAST_BLOCK_TIME: u64 = 60;
+
+/// Number of peers tracked before the buckets of idle peers are dropped.
+const MAX_TRACKED_PEERS: usize = 1_024;
+
+/// Settings of the [`GossipGuard`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GossipLimits {
+    /// Messages per second accepted from a peer. Peers may burst up to twice as many.
+    pub peer_rate: u32,
+    /// Messages per second accepted from all peers. Bursts may reach twice as many.
+    pub global_rate: u32,
+    /// Maximum size of a message, compressed or decompressed.
+    pub max_message_size: usize,
+    /// Seconds a block timestamp may be ahead of the local clock.
+    pub max_future_time: u64,
+    /// Seconds a block timestamp may be behind the local clock.
+    pub max_past_time: u64,
+}
+
+impl Default for GossipLimits {
+    fn default() -> Self {
+        Self {
+            peer_rate: DEFAULT_PEER_MESSAGE_RATE,
+            global_rate: DEFAULT_GLOBAL_MESSAGE_RATE,
+            max_message_size: MAX_GOSSIP_SIZE,
+            max_future_time: DEFAULT_MAX_FUTURE_BLOCK_TIME,
+            max_past_time: DEFAULT_MAX_PAST_BLOCK_TIME,
+        }
+    }
+}
+
+/// Why a message was rejected by the [`GossipGuard`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// The peer sent more messages than its rate allows.
+    PeerRateLimited,
+    /// All peers together sent more messages than the global rate allows.
+    GlobalRateLimited,
+    /// The message, compressed or decompressed, exceeds the maximum size.
+    TooLarge {
+        /// The size of the message.
+        size: usize,
+        /// The maximum size.
+        max: usize,
+    },
+    /// The message does not start with a valid snappy length preamble.
+    InvalidCompression,
+    /// The block timestamp is too far ahead of the local clock.
+    TooNew {
+        /// The block timestamp.
+        timestamp: u64,
+        /// The local time.
+        now: u64,
+    },
+    /// The block timestamp is too far behind the local clock.
+    TooOld {
+        /// The block timestamp.
+        timestamp: u64,
+        /// The local time.
+        now: u64,
+    },
+}
+
+impl Rejection {
+    /// Returns the kind of the rejection.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::PeerRateLimited => "peer_rate_limited",
+            Self::GlobalRateLimited => "global_rate_limited",
+            Self::TooLarge { .. } => "too_large",
+            Self::InvalidCompression => "invalid_compression",
+            Self::TooNew { .. } => "too_new",
+            Self::TooOld { .. } => "too_old",
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PeerRateLimited => f.write_str("peer exceeded its message rate"),
+            Self::GlobalRateLimited => f.write_str("global message rate exceeded"),
+            Self::TooLarge { size, max } => write!(f, "message of {size} bytes exceeds {max}"),
+            Self::InvalidCompression => f.write_str("invalid snappy length preamble"),
+            Self::TooNew { timestamp, now } => write!(f, "block at {timestamp} is ahead of {now}"),
+            Self::TooOld { timestamp, now } => write!(f, "block at {timestamp} is behind {now}"),
+        }
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+/// A token bucket holding up to twice its rate, refilled continuously.
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn full(rate: u32, now: Instant) -> Self {
+        Self { tokens: Self::capacity(rate), updated: now }
+    }
+
+    fn capacity(rate: u32) -> f64 {
+        f64::from(rate) * 2.0
+    }
+
+    fn refill(&mut self, rate: u32, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = elapsed.mul_add(f64::from(rate), self.tokens).min(Self::capacity(rate));
+        self.updated = now;
+    }
+
+    fn try_take(&mut self, rate: u32, now: Instant) -> bool {
+        self.refill(rate, now);
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+#[derive(Debug)]
+struct GuardState {
+    global: TokenBucket,
+    peers: HashMap<B256, TokenBucket>,
+}
+
+/// Screens gossip messages before they are decompressed and decoded.
+#[derive(Debug)]
+pub struct GossipGuard {
+    limits: GossipLimits,
+    state: Mutex<GuardState>,
+    clock: Option<Arc<ClockMonitor>>,
+}
+
+impl GossipGuard {
+    /// Creates a new guard.
+    pub fn new(limits: GossipLimits) -> Self {
+        let global = TokenBucket::full(limits.global_rate, Instant::now());
+        Self {
+            limits,
+            state: Mutex::new(GuardState { global, peers: HashMap::new() }),
+            clock: None,
+        }
+    }
+
+    /// Records the timestamps of decoded blocks in `clock`.
+    pub fn with_clock(mut self, clock: Arc<ClockMonitor>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Checks a compressed message received from the peer `peer`, consuming from its rate and
+    /// the global one.
+    pub fn check_message(&self, peer: B256, data: &[u8]) -> Result<(), Rejection> {
+        self.record(self.admit(peer, data))
+    }
+
+    /// Checks the timestamp of a decoded block against the local time `now`.
+    pub fn check_timestamp(&self, timestamp: u64, now: u64) -> Result<(), Rejection> {
+        if let Some(clock) = &self.clock {
+            clock.record(ClockSource::Gossip, timestamp, now);
+        }
+        let result = if timestamp > now.saturating_add(self.limits.max_future_time) {
+            Err(Rejection::TooNew { timestamp, now })
+        } else if timestamp < now.saturating_sub(self.limits.max_past_time) {
+            Err(Rejection::TooOld { timestamp, now })
+        } else {
+            Ok(())
+        };
+        self.record(result)
+    }
+
+    /// Drops the rate of a disconnected peer.
+    pub fn forget_peer(&self, peer: &B256) {
+        self.state.lock().unwrap().peers.remove(peer);
+    }
+
+    fn admit(&self, peer: B256, data: &[u8]) -> Result<(), Rejection> {
+        let max = self.limits.max_message_size;
+        if data.len() > max {
+            return Err(Rejection::TooLarge { size: data.len(), max });
+        }
+        let (size, _) = snappy::decoded_len(data).ok_or(Rejection::InvalidCompression)?;
+        if size > max {
+            return Err(Rejection::TooLarge { size, max });
+        }
+
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let GossipLimits { peer_rate, global_rate, .. } = self.limits;
+        if !state.peers.contains_key(&peer) && state.peers.len() >= MAX_TRACKED_PEERS {
+            // Peers with a full bucket are indistinguishable from new ones.
+            state.peers.retain(|_, bucket| {
+                bucket.refill(peer_rate, now);
+                bucket.tokens < TokenBucket::capacity(peer_rate)
+            });
+        }
+        // The peer rate is checked first, so that a flooding peer does not drain the global one.
+        let bucket = state.peers.entry(peer).or_insert_with(|| TokenBucket::full(peer_rate, now));
+        if !bucket.try_take(peer_rate, now) {
+            return Err(Rejection::PeerRateLimited);
+        }
+        if !state.global.try_take(global_rate, now) {
+            return Err(Rejection::GlobalRateLimited);
+        }
+        Ok(())
+    }
+
+    fn record(&self, result: Result<(), Rejection>) -> Result<(), Rejection> {
+        if let Err(rejection) = &result {
+            counter!("hera_p2p_rejected_messages_total", "reason" => rejection.kind()).increment(1);
+            debug!(target: "hera::p2p", %rejection, "Rejected gossip message");
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// A snappy block announcing `len` decompressed bytes.
+    fn message(len: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut len = len;
+        while len >= 0x80 {
+            out.push(len as u8 | 0x80);
+            len >>= 7;
+        }
+        out.push(len as u8);
+        out
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limits_peers_and_the_node() {
+        let guard =
+            GossipGuard::new(GossipLimits { peer_rate: 2, global_rate: 3, ..Default::default() });
+        let (a, b) = (B256::repeat_byte(1), B256::repeat_byte(2));
+
+        // Peers burst up to twice their rate, then are limited on their own.
+        for _ in 0..4 {
+            guard.check_message(a, &message(100)).unwrap();
+        }
+        assert_eq!(guard.check_message(a, &message(100)), Err(Rejection::PeerRateLimited));
+        guard.check_message(b, &message(100)).unwrap();
+        guard.check_message(b, &message(100)).unwrap();
+        assert_eq!(guard.check_message(b, &message(100)), Err(Rejection::GlobalRateLimited));
+
+        // Half a second refills one message of each peer, and 1.5 of the node.
+        tokio::time::advance(Duration::from_millis(500)).await;
+        guard.check_message(a, &message(100)).unwrap();
+        assert_eq!(guard.check_message(a, &message(100)), Err(Rejection::PeerRateLimited));
+        assert_eq!(guard.check_message(b, &message(100)), Err(Rejection::GlobalRateLimited));
+
+        guard.forget_peer(&a);
+        assert!(!guard.state.lock().unwrap().peers.contains_key(&a));
+    }
+
+    #[test]
+    fn rejects_oversized_messages_before_decompression() {
+        let guard =
+            GossipGuard::new(GossipLimits { max_message_size: 1_000, ..Default::default() });
+        let peer = B256::repeat_byte(1);
+
+        guard.check_message(peer, &message(1_000)).unwrap();
+        assert_eq!(
+            guard.check_message(peer, &message(1_001)),
+            Err(Rejection::TooLarge { size: 1_001, max: 1_000 })
+        );
+        assert_eq!(
+            guard.check_message(peer, &[0; 1_001]),
+            Err(Rejection::TooLarge { size: 1_001, max: 1_000 })
+        );
+        assert_eq!(guard.check_message(peer, &[]), Err(Rejection::InvalidCompression));
+        assert_eq!(guard.check_message(peer, &[0xff; 6]), Err(Rejection::InvalidCompression));
+        assert_eq!(
+            guard.check_message(peer, &[0xff, 0xff, 0xff, 0xff, 0x1f]),
+            Err(Rejection::InvalidCompression)
+        );
+    }
+
+    #[test]
+    fn rejects_blocks_far_from_the_clock() {
+        let guard = GossipGuard::new(GossipLimits::default());
+        let now = 1_000_000;
+
+        guard.check_timestamp(now + 5, now).unwrap();
+        guard.check_timestamp(now - 60, now).unwrap();
+        assert_eq!(
+            guard.check_timestamp(now + 6, now),
+            Err(Rejection::TooNew { timestamp: now + 6, now })
+        );
+        assert_eq!(
+            guard.check_timestamp(now - 61, now),
+            Err(Rejection::TooOld { timestamp: now - 61, now })
+        );
+    }
+}
@@ -0,0 +1,68 @@
+//! The sockets of the p2p stack.
+
+use std::net::{IpAddr, SocketAddr};
+
+use eyre::{Result, WrapErr};
+use tokio::net::{TcpListener, UdpSocket};
+use tracing::info;
+
+/// The bound sockets of the p2p stack: the libp2p TCP listener and the discv5 UDP socket. The
+/// QUIC transport of libp2p is not offered.
+///
+/// Ports are bound before the node record is signed, so that the record only advertises ports
+/// the node serves, and a port of 0 advertises the one picked by the system.
+#[derive(Debug)]
+pub struct P2pListeners {
+    /// The libp2p TCP listener.
+    pub tcp: TcpListener,
+    /// The discv5 UDP socket.
+    pub udp: UdpSocket,
+}
+
+impl P2pListeners {
+    /// Binds the TCP port `tcp` and the UDP port `udp` on `ip`.
+    pub async fn bind(ip: IpAddr, tcp: u16, udp: u16) -> Result<Self> {
+        let tcp = TcpListener::bind(SocketAddr::new(ip, tcp))
+            .await
+            .wrap_err_with(|| format!("failed to listen on TCP port {tcp}"))?;
+        let udp = UdpSocket::bind(SocketAddr::new(ip, udp))
+            .await
+            .wrap_err_with(|| format!("failed to bind UDP port {udp}"))?;
+        let listeners = Self { tcp, udp };
+        info!(
+            target: "hera::p2p",
+            tcp = %listeners.tcp.local_addr()?,
+            udp = %listeners.udp.local_addr()?,
+            "Listening for peers"
+        );
+        Ok(listeners)
+    }
+
+    /// Returns the bound TCP port.
+    pub fn tcp_port(&self) -> Result<u16> {
+        Ok(self.tcp.local_addr()?.port())
+    }
+
+    /// Returns the bound UDP port.
+    pub fn udp_port(&self) -> Result<u16> {
+        Ok(self.udp.local_addr()?.port())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn binds_system_picked_ports() {
+        let listeners = P2pListeners::bind(Ipv4Addr::LOCALHOST.into(), 0, 0).await.unwrap();
+        assert_ne!(listeners.tcp_port().unwrap(), 0);
+        assert_ne!(listeners.udp_port().unwrap(), 0);
+
+        // Ports in use are reported instead of advertised.
+        let taken = listeners.tcp_port().unwrap();
+        assert!(P2pListeners::bind(Ipv4Addr::LOCALHOST.into(), taken, 0).await.is_err());
+    }
+}
@@ -0,0 +1,349 @@
+//! Block gossip between OP Stack nodes.
+//!
+//! Nodes discover each other through discv5, advertising themselves with an [`Enr`] signed by
+//! their persistent [`P2pIdentity`] and listening on the [`P2pListeners`]. Behind a NAT, a
+//! [`PortMapping`] forwards the listening ports through UPnP.
+//!
+//! The sequencer publishes every unsafe block on the blocks topic of its chain, signed with the
+//! unsafe block signer key registered in the L1 `SystemConfig`. A message is the snappy
+//! compressed 65-byte signature followed by the payload bytes: the SSZ encoded execution payload,
+//! preceded by the parent beacon block root from Ecotone onwards. The signature is over the hash of
+//! the blocks signing domain, the chain ID and the hash of the payload bytes, so that it cannot be
+//! replayed on another chain or for another message type.
+//!
+//! [`SignedPayloadEnvelope`] covers both directions: signing and encoding blocks to publish, and
+//! decoding and verifying blocks received from peers. Every hardfork changing the payload
+//! encoding moves blocks to a new topic version; [`BlockTopics`] selects the versions to subscribe
+//! to, keeping both sides of an activation subscribed while the network transitions. Before a
+//! message is decoded, the [`GossipGuard`] rate limits its sender and bounds its size.
+
+use std::fmt;
+
+use alloy_primitives::{keccak256, Address, Bytes, Signature, B256, U256};
+use eyre::{ensure, eyre, Result, WrapErr};
+use k256::ecdsa::SigningKey;
+
+use crate::{
+    config::{RollupConfig, SystemConfig},
+    engine::EnginePayload,
+};
+
+mod enr;
+pub use enr::{Enr, EnrBuilder, MAX_ENR_SIZE};
+
+mod identity;
+pub use identity::{load_or_create_key, P2pIdentity};
+
+mod limits;
+pub use limits::{
+    GossipGuard, GossipLimits, Rejection, DEFAULT_GLOBAL_MESSAGE_RATE,
+    DEFAULT_MAX_FUTURE_BLOCK_TIME, DEFAULT_MAX_PAST_BLOCK_TIME, DEFAULT_PEER_MESSAGE_RATE,
+    MAX_GOSSIP_SIZE,
+};
+
+mod listen;
+pub use listen::P2pListeners;
+
+mod nat;
+pub use nat::{Gateway, PortMapping, PortProtocol, DEFAULT_UPNP_TIMEOUT, UPNP_LEASE_DURATION};
+
+pub mod snappy;
+
+mod ssz;
+
+mod topics;
+pub use topics::{BlockTopics, TopicChanges, DEFAULT_TOPIC_TRANSITION_WINDOW};
+
+/// The signing domain of blocks, prefixing the signed message.
+pub const SIGNING_DOMAIN_BLOCKS_V1: B256 = B256::ZERO;
+
+/// Size of the signature prefixing a gossiped block.
+pub const SIGNATURE_SIZE: usize = 65;
+
+/// The version of the blocks topic, which sets the payload encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BlockTopicVersion {
+    /// V1 payloads, before Canyon.
+    V1,
+    /// V2 payloads with withdrawals, from Canyon until Ecotone.
+    V2,
+    /// V3 payloads with blob gas fields and the parent beacon block root, from Ecotone until
+    /// Isthmus.
+    V3,
+    /// V4 payloads with the withdrawals root, from Isthmus onwards.
+    V4,
+}
+
+impl BlockTopicVersion {
+    /// All versions, in activation order.
+    pub const ALL: [Self; 4] = [Self::V1, Self::V2, Self::V3, Self::V4];
+
+    /// Returns the version of the topic carrying the block with the given timestamp.
+    pub fn from_timestamp(config: &RollupConfig, timestamp: u64) -> Self {
+        if config.is_isthmus_active(timestamp) {
+            Self::V4
+        } else if config.is_ecotone_active(timestamp) {
+            Self::V3
+        } else if config.is_canyon_active(timestamp) {
+            Self::V2
+        } else {
+            Self::V1
+        }
+    }
+
+    /// Returns the name of the gossipsub topic of `chain_id`.
+    pub fn topic(&self, chain_id: u64) -> String {
+        let index = match self {
+            Self::V1 => 0,
+            Self::V2 => 1,
+            Self::V3 => 2,
+            Self::V4 => 3,
+        };
+        format!("/optimism/{chain_id}/{index}/blocks")
+    }
+
+    /// Returns true if the payload bytes start with the parent beacon block root.
+    const fn has_parent_beacon_block_root(&self) -> bool {
+        matches!(self, Self::V3 | Self::V4)
+    }
+}
+
+impl fmt::Display for BlockTopicVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::V1 => "v1",
+            Self::V2 => "v2",
+            Self::V3 => "v3",
+            Self::V4 => "v4",
+        })
+    }
+}
+
+/// Returns the hash signed by the sequencer for the payload bytes of a block of `chain_id`.
+pub fn block_signing_hash(chain_id: u64, payload_bytes: &[u8]) -> B256 {
+    let mut message = [0; 96];
+    message[..32].copy_from_slice(SIGNING_DOMAIN_BLOCKS_V1.as_slice());
+    message[32..64].copy_from_slice(&U256::from(chain_id).to_be_bytes::<32>());
+    message[64..].copy_from_slice(keccak256(payload_bytes).as_slice());
+    keccak256(message)
+}
+
+/// An execution payload signed by the sequencer, as gossiped on the blocks topic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedPayloadEnvelope {
+    /// The topic version, setting the payload encoding.
+    pub version: BlockTopicVersion,
+    /// The payload, with the parent beacon block root from V3 onwards.
+    pub payload: EnginePayload,
+    /// The signature of the sequencer over the payload bytes.
+    pub signature: Signature,
+    /// The signed payload bytes, as received or as encoded when signing. The signature is
+    /// checked over them rather than over a re-encoding of the payload, like op-node does.
+    pub payload_bytes: Bytes,
+}
+
+impl SignedPayloadEnvelope {
+    /// Signs `payload` for the blocks topic of `version` of the chain `chain_id`, with the
+    /// unsafe block signer `key`.
+    pub fn sign(
+        payload: EnginePayload,
+        version: BlockTopicVersion,
+        chain_id: u64,
+        key: &SigningKey,
+    ) -> Result<Self> {
+        let payload_bytes = encode_payload_bytes(&payload, version)?;
+        let hash = block_signing_hash(chain_id, &payload_bytes);
+        let (signature, recid) =
+            key.sign_prehash_recoverable(hash.as_slice()).wrap_err("failed to sign payload")?;
+        let signature = Signature::from((signature, recid));
+        Ok(Self { version, payload, signature, payload_bytes: payload_bytes.into() })
+    }
+
+    /// Encodes the envelope into a gossip message: the signature, then the payload bytes.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(SIGNATURE_SIZE + self.payload_bytes.len());
+        out.extend_from_slice(&self.signature.r().to_be_bytes::<32>());
+        out.extend_from_slice(&self.signature.s().to_be_bytes::<32>());
+        out.push(self.signature.v().y_parity_byte());
+        out.extend_from_slice(&self.payload_bytes);
+        out
+    }
+
+    /// Decodes a gossip message of the blocks topic of `version`. The signature is not checked,
+    /// see [`Self::verify`].
+    pub fn decode(data: &[u8], version: BlockTopicVersion) -> Result<Self> {
+        ensure!(data.len() > SIGNATURE_SIZE, "block message too short");
+        let (signature, payload_bytes) = data.split_at(SIGNATURE_SIZE);
+        ensure!(signature[64] <= 1, "invalid signature recovery byte {}", signature[64]);
+        let signature = Signature::from_bytes_and_parity(&signature[..64], signature[64] as u64)
+            .map_err(|err| eyre!("invalid block signature: {err}"))?;
+
+        let mut payload = payload_bytes;
+        let mut parent_beacon_block_root = None;
+        if version.has_parent_beacon_block_root() {
+            ensure!(payload.len() >= 32, "{version} block message has no parent beacon root");
+            parent_beacon_block_root = Some(B256::from_slice(&payload[..32]));
+            payload = &payload[32..];
+        }
+        let payload =
+            EnginePayload { parent_beacon_block_root, ..ssz::decode_payload(payload, version)? };
+        Ok(Self {
+            version,
+            payload,
+            signature,
+            payload_bytes: Bytes::copy_from_slice(payload_bytes),
+        })
+    }
+
+    /// Returns the address that signed the payload bytes of the envelope for the chain
+    /// `chain_id`.
+    pub fn recover_signer(&self, chain_id: u64) -> Result<Address> {
+        self.signature
+            .recover_address_from_prehash(&block_signing_hash(chain_id, &self.payload_bytes))
+            .map_err(|err| eyre!("invalid block signature: {err}"))
+    }
+
+    /// Checks that the envelope was signed for the chain `chain_id` by the unsafe block signer of
+    /// `system_config`.
+    pub fn verify(&self, chain_id: u64, system_config: &SystemConfig) -> Result<()> {
+        let expected = system_config
+            .unsafe_block_signer
+            .ok_or_else(|| eyre!("unsafe block signer is unknown"))?;
+        let signer = self.recover_signer(chain_id)?;
+        ensure!(
+            signer == expected,
+            "block {} signed by {signer}, not by the unsafe block signer {expected}",
+            self.payload.block_id()
+        );
+        Ok(())
+    }
+}
+
+/// Encodes the signed payload bytes of a block: the parent beacon block root from V3 onwards,
+/// then the SSZ encoded payload.
+fn encode_payload_bytes(payload: &EnginePayload, version: BlockTopicVersion) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    if version.has_parent_beacon_block_root() {
+        let root = payload
+            .parent_beacon_block_root
+            .ok_or_else(|| eyre!("{version} payload has no parent beacon block root"))?;
+        out.extend_from_slice(root.as_slice());
+    }
+    out.extend(ssz::encode_payload(payload, version)?);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_rpc_types_engine::{
+        ExecutionPayload, ExecutionPayloadV1, ExecutionPayloadV2, ExecutionPayloadV3,
+    };
+    use alloy_rpc_types_eth::Withdrawal;
+
+    use super::*;
+
+    /// A payload in the encoding of the blocks topic of `version`.
+    fn payload(version: BlockTopicVersion) -> EnginePayload {
+        let v1 = ExecutionPayloadV1 {
+            parent_hash: B256::repeat_byte(1),
+            fee_recipient: Address::repeat_byte(9),
+            state_root: B256::repeat_byte(10),
+            receipts_root: B256::repeat_byte(11),
+            logs_bloom: Default::default(),
+            prev_randao: B256::repeat_byte(12),
+            block_number: 100,
+            gas_limit: 30_000_000,
+            gas_used: 21_000,
+            timestamp: 1_720_000_000,
+            extra_data: Bytes::from_static(b"hera"),
+            base_fee_per_gas: U256::from(1_000_000_000),
+            block_hash: B256::repeat_byte(2),
+            transactions: vec![
+                Bytes::from_static(&[0x7e, 1, 2]),
+                Bytes::new(),
+                Bytes::from(vec![2; 100]),
+            ],
+        };
+        let withdrawal = Withdrawal {
+            index: 1,
+            validator_index: 2,
+            address: Address::repeat_byte(3),
+            amount: 4,
+        };
+        let v2 = ExecutionPayloadV2 { payload_inner: v1.clone(), withdrawals: vec![withdrawal] };
+        let v3 =
+            ExecutionPayloadV3 { payload_inner: v2.clone(), blob_gas_used: 5, excess_blob_gas: 6 };
+        let (payload, parent_beacon_block_root, withdrawals_root) = match version {
+            BlockTopicVersion::V1 => (ExecutionPayload::V1(v1), None, None),
+            BlockTopicVersion::V2 => (ExecutionPayload::V2(v2), None, None),
+            BlockTopicVersion::V3 => (ExecutionPayload::V3(v3), Some(B256::repeat_byte(7)), None),
+            BlockTopicVersion::V4 => {
+                (ExecutionPayload::V3(v3), Some(B256::repeat_byte(7)), Some(B256::repeat_byte(8)))
+            }
+        };
+        EnginePayload { payload, parent_beacon_block_root, withdrawals_root }
+    }
+
+    fn key() -> SigningKey {
+        SigningKey::from_slice(&[0x11; 32]).unwrap()
+    }
+
+    #[test]
+    fn signed_envelope_roundtrips_and_verifies() {
+        let signer = Address::from_private_key(&key());
+        let system_config =
+            SystemConfig { unsafe_block_signer: Some(signer), ..Default::default() };
+
+        for version in BlockTopicVersion::ALL {
+            let envelope =
+                SignedPayloadEnvelope::sign(payload(version), version, 10, &key()).unwrap();
+            let message = envelope.encode();
+            let decoded = SignedPayloadEnvelope::decode(&message, version).unwrap();
+            assert_eq!(decoded, envelope, "{version}");
+            assert_eq!(decoded.payload_bytes, message[SIGNATURE_SIZE..], "{version}");
+            decoded.verify(10, &system_config).unwrap();
+
+            // Signatures do not carry over to other chains.
+            assert!(decoded.verify(8453, &system_config).is_err());
+        }
+    }
+
+    #[test]
+    fn verifies_the_received_bytes() {
+        let signer = Address::from_private_key(&key());
+        let version = BlockTopicVersion::V3;
+        let envelope = SignedPayloadEnvelope::sign(payload(version), version, 10, &key()).unwrap();
+
+        // The signature covers the bytes as received, whatever they decode to.
+        let mut decoded = SignedPayloadEnvelope::decode(&envelope.encode(), version).unwrap();
+        decoded.payload.parent_beacon_block_root = None;
+        assert_eq!(decoded.recover_signer(10).unwrap(), signer);
+
+        // A byte changed in transit changes the signer.
+        let mut message = envelope.encode();
+        *message.last_mut().unwrap() ^= 1;
+        let tampered = SignedPayloadEnvelope::decode(&message, version).unwrap();
+        assert_ne!(tampered.recover_signer(10).unwrap(), signer);
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        let version = BlockTopicVersion::V3;
+        let message =
+            SignedPayloadEnvelope::sign(payload(version), version, 10, &key()).unwrap().encode();
+
+        let short = SignedPayloadEnvelope::decode(&message[..SIGNATURE_SIZE], version);
+        assert!(short.unwrap_err().to_string().contains("too short"));
+        let no_root = SignedPayloadEnvelope::decode(&message[..SIGNATURE_SIZE + 31], version);
+        assert!(no_root.unwrap_err().to_string().contains("no parent beacon root"));
+        assert!(SignedPayloadEnvelope::decode(&message[..message.len() - 1], version).is_err());
+
+        let mut bad_recovery = message.clone();
+        bad_recovery[64] = 27;
+        let err = SignedPayloadEnvelope::decode(&bad_recovery, version).unwrap_err();
+        assert!(err.to_string().contains("invalid signature recovery byte 27"), "{err}");
+
+        // A V3 message is not a valid V1 message.
+        assert!(SignedPayloadEnvelope::decode(&message, BlockTopicVersion::V1).is_err());
+    }
+}
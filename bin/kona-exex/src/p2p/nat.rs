@@ -0,0 +1,294 @@
+//! NAT traversal through UPnP port mappings.
+//!
+//! Home routers rarely forward the p2p ports on their own, leaving nodes behind them unreachable
+//! for gossip. When enabled, Hera discovers the Internet Gateway Device of the local network over
+//! SSDP, asks it to forward the listening ports to this host and advertises the gateway's
+//! external address in its record. Mappings are leased, renewed for as long as the node runs and
+//! removed when it shuts down.
+
+use std::{
+    fmt,
+    future::Future,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use eyre::{bail, ensure, eyre, Result, WrapErr};
+use reqwest::Client;
+use tokio::{net::UdpSocket, time::timeout};
+use tracing::{info, warn};
+use url::Url;
+
+/// The SSDP multicast address.
+const SSDP_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)), 1900);
+
+/// The device type searched for over SSDP.
+const GATEWAY_DEVICE: &str = "urn:schemas-upnp-org:device:InternetGatewayDevice:1";
+
+/// The gateway services able to map ports, in order of preference.
+const WAN_SERVICES: [&str; 2] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+/// The default time to wait for the gateway to answer.
+pub const DEFAULT_UPNP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The lease duration of port mappings, renewed at half of it.
+pub const UPNP_LEASE_DURATION: Duration = Duration::from_secs(3600);
+
+/// The transport protocol of a port mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortProtocol {
+    /// TCP, of libp2p.
+    Tcp,
+    /// UDP, of discv5.
+    Udp,
+}
+
+impl fmt::Display for PortProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Tcp => "TCP",
+            Self::Udp => "UDP",
+        })
+    }
+}
+
+/// An Internet Gateway Device found on the local network.
+#[derive(Debug, Clone)]
+pub struct Gateway {
+    client: Client,
+    /// The URL of the port mapping service.
+    control_url: Url,
+    /// The type of the port mapping service.
+    service: &'static str,
+    /// The address of this host on the gateway's network.
+    local_ip: Ipv4Addr,
+}
+
+impl Gateway {
+    /// Searches the local network for a gateway, waiting up to `wait` for it to answer.
+    pub async fn discover(wait: Duration) -> Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+        let search = format!(
+            "M-SEARCH * HTTP/1.1\r\nHOST: {SSDP_ADDR}\r\nST: {GATEWAY_DEVICE}\r\n\
+             MAN: \"ssdp:discover\"\r\nMX: 2\r\n\r\n"
+        );
+        socket
+            .send_to(search.as_bytes(), SSDP_ADDR)
+            .await
+            .wrap_err("failed to send SSDP search")?;
+
+        let mut buf = [0; 2048];
+        let (len, from) = timeout(wait, socket.recv_from(&mut buf))
+            .await
+            .map_err(|_| eyre!("no UPnP gateway answered within {wait:?}"))??;
+        let answer = String::from_utf8_lossy(&buf[..len]);
+        let location =
+            ssdp_location(&answer).ok_or_else(|| eyre!("SSDP answer of {from} has no location"))?;
+        let location = Url::parse(location).wrap_err("invalid gateway description URL")?;
+
+        let client = Client::builder().timeout(wait).build()?;
+        let description = client
+            .get(location.clone())
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await
+            .wrap_err("failed to fetch gateway description")?;
+        let (service, control_path) = WAN_SERVICES
+            .iter()
+            .find_map(|service| control_url(&description, service).map(|path| (*service, path)))
+            .ok_or_else(|| eyre!("gateway at {location} cannot map ports"))?;
+        let control_url = location.join(control_path).wrap_err("invalid gateway control URL")?;
+
+        // The address the gateway reaches this host at is the one routing to it.
+        let probe = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        probe.connect(from)?;
+        let IpAddr::V4(local_ip) = probe.local_addr()?.ip() else {
+            bail!("gateway {from} is not reachable over IPv4");
+        };
+        Ok(Self { client, control_url, service, local_ip })
+    }
+
+    /// Returns the external address of the gateway.
+    pub async fn external_ip(&self) -> Result<IpAddr> {
+        let response = self.call("GetExternalIPAddress", "").await?;
+        let ip = xml_value(&response, "NewExternalIPAddress")
+            .ok_or_else(|| eyre!("gateway did not return its external address"))?;
+        ip.trim().parse().wrap_err_with(|| format!("invalid gateway external address {ip}"))
+    }
+
+    /// Forwards `port` of the gateway to the same port of this host, for `lease`.
+    pub async fn add_port_mapping(
+        &self,
+        protocol: PortProtocol,
+        port: u16,
+        lease: Duration,
+    ) -> Result<()> {
+        let args = format!(
+            "<NewRemoteHost></NewRemoteHost><NewExternalPort>{port}</NewExternalPort>\
+             <NewProtocol>{protocol}</NewProtocol><NewInternalPort>{port}</NewInternalPort>\
+             <NewInternalClient>{}</NewInternalClient><NewEnabled>1</NewEnabled>\
+             <NewPortMappingDescription>hera</NewPortMappingDescription>\
+             <NewLeaseDuration>{}</NewLeaseDuration>",
+            self.local_ip,
+            lease.as_secs()
+        );
+        self.call("AddPortMapping", &args)
+            .await
+            .wrap_err_with(|| format!("failed to map {protocol} port {port}"))?;
+        Ok(())
+    }
+
+    /// Removes the forwarding of `port`.
+    pub async fn delete_port_mapping(&self, protocol: PortProtocol, port: u16) -> Result<()> {
+        let args = format!(
+            "<NewRemoteHost></NewRemoteHost><NewExternalPort>{port}</NewExternalPort>\
+             <NewProtocol>{protocol}</NewProtocol>"
+        );
+        self.call("DeletePortMapping", &args)
+            .await
+            .wrap_err_with(|| format!("failed to unmap {protocol} port {port}"))?;
+        Ok(())
+    }
+
+    /// Calls the `action` of the port mapping service with the XML arguments `args`.
+    async fn call(&self, action: &str, args: &str) -> Result<String> {
+        let body = format!(
+            "<?xml version=\"1.0\"?>\
+             <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+             s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\"><s:Body>\
+             <u:{action} xmlns:u=\"{}\">{args}</u:{action}></s:Body></s:Envelope>",
+            self.service
+        );
+        let response = self
+            .client
+            .post(self.control_url.clone())
+            .header("Content-Type", "text/xml; charset=\"utf-8\"")
+            .header("SOAPAction", format!("\"{}#{action}\"", self.service))
+            .body(body)
+            .send()
+            .await?;
+        let status = response.status();
+        let text = response.text().await?;
+        ensure!(
+            status.is_success(),
+            "gateway rejected {action} with {status}: {}",
+            xml_value(&text, "errorDescription").unwrap_or("no description")
+        );
+        Ok(text)
+    }
+}
+
+/// Ports forwarded by the gateway, advertised under its external address.
+#[derive(Debug, Clone)]
+pub struct PortMapping {
+    gateway: Gateway,
+    ports: Vec<(PortProtocol, u16)>,
+    /// The external address of the gateway.
+    pub external_ip: IpAddr,
+}
+
+impl PortMapping {
+    /// Discovers the gateway of the local network and forwards `ports` to this host.
+    pub async fn map(ports: Vec<(PortProtocol, u16)>, wait: Duration) -> Result<Self> {
+        let gateway = Gateway::discover(wait).await?;
+        for (protocol, port) in &ports {
+            gateway.add_port_mapping(*protocol, *port, UPNP_LEASE_DURATION).await?;
+        }
+        let external_ip = gateway.external_ip().await?;
+        info!(
+            target: "hera::p2p",
+            %external_ip,
+            ports = ?ports,
+            "Mapped p2p ports through UPnP"
+        );
+        Ok(Self { gateway, ports, external_ip })
+    }
+
+    /// Renews the mappings before their lease expires until `shutdown` completes, then removes
+    /// them.
+    pub async fn keep_alive(self, shutdown: impl Future<Output = ()>) {
+        let mut interval = tokio::time::interval(UPNP_LEASE_DURATION / 2);
+        interval.tick().await;
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                () = &mut shutdown => break,
+            }
+            for (protocol, port) in &self.ports {
+                if let Err(err) =
+                    self.gateway.add_port_mapping(*protocol, *port, UPNP_LEASE_DURATION).await
+                {
+                    warn!(target: "hera::p2p", %err, "Failed to renew UPnP port mapping");
+                }
+            }
+        }
+        self.remove().await;
+    }
+
+    /// Removes the mappings from the gateway.
+    pub async fn remove(&self) {
+        for (protocol, port) in &self.ports {
+            if let Err(err) = self.gateway.delete_port_mapping(*protocol, *port).await {
+                warn!(target: "hera::p2p", %err, "Failed to remove UPnP port mapping");
+            }
+        }
+        info!(target: "hera::p2p", ports = ?self.ports, "Removed UPnP port mappings");
+    }
+}
+
+/// Returns the description URL of an SSDP search answer.
+fn ssdp_location(answer: &str) -> Option<&str> {
+    answer.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim().eq_ignore_ascii_case("location").then(|| value.trim())
+    })
+}
+
+/// Returns the control URL of `service` in a device description.
+fn control_url<'a>(description: &'a str, service: &str) -> Option<&'a str> {
+    description
+        .split("<service>")
+        .find(|block| xml_value(block, "serviceType").map(str::trim) == Some(service))
+        .and_then(|block| xml_value(block, "controlURL"))
+        .map(str::trim)
+}
+
+/// Returns the text of the first `tag` element, regardless of its namespace prefix.
+fn xml_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("{tag}>"))? + tag.len() + 1;
+    let end = start + xml[start..].find("</")?;
+    Some(&xml[start..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_gateway_answers() {
+        let answer = "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=120\r\n\
+                      Location: http://192.168.1.1:5000/rootDesc.xml\r\n\
+                      ST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\r\n";
+        assert_eq!(ssdp_location(answer), Some("http://192.168.1.1:5000/rootDesc.xml"));
+
+        let description = "<root><device><serviceList>\
+            <service><serviceType>urn:schemas-upnp-org:service:WANCommonInterfaceConfig:1\
+            </serviceType><controlURL>/ctl/CmnIfCfg</controlURL></service>\
+            <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>\
+            <controlURL>/ctl/IPConn</controlURL></service>\
+            </serviceList></device></root>";
+        assert_eq!(control_url(description, WAN_SERVICES[0]), Some("/ctl/IPConn"));
+        assert_eq!(control_url(description, WAN_SERVICES[1]), None);
+
+        let response = "<s:Envelope><s:Body><u:GetExternalIPAddressResponse>\
+            <NewExternalIPAddress>203.0.113.7</NewExternalIPAddress>\
+            </u:GetExternalIPAddressResponse></s:Body></s:Envelope>";
+        assert_eq!(xml_value(response, "NewExternalIPAddress"), Some("203.0.113.7"));
+    }
+}
@@ -0,0 +1,297 @@
+//! The snappy block format compressing gossip messages.
+//!
+//! A block is the varint encoded decompressed length, followed by literals and back-references
+//! into the output. Gossip messages are untrusted, so decompression bounds the output before
+//! allocating it and checks every element against the input and the output.
+
+use eyre::{bail, ensure, eyre, Result};
+
+/// Minimum length of a back-reference emitted by [`compress`].
+const MIN_MATCH: usize = 4;
+
+/// Maximum distance of a back-reference emitted by [`compress`], fitting a 2-byte offset.
+const MAX_OFFSET: usize = u16::MAX as usize;
+
+/// Number of bits of the hash table of [`compress`].
+const TABLE_BITS: u32 = 14;
+
+/// Returns the decompressed length announced by the preamble of a block, an unsigned varint of
+/// at most 32 bits, and the elements following it.
+pub fn decoded_len(data: &[u8]) -> Option<(usize, &[u8])> {
+    let mut len = 0u64;
+    for (i, byte) in data.iter().take(5).enumerate() {
+        len |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            let len = u32::try_from(len).ok()?;
+            return Some((len as usize, &data[i + 1..]));
+        }
+    }
+    None
+}
+
+/// Decompresses a block of at most `max_len` decompressed bytes.
+pub fn decompress(data: &[u8], max_len: usize) -> Result<Vec<u8>> {
+    let (len, mut input) = decoded_len(data).ok_or_else(|| eyre!("invalid snappy preamble"))?;
+    ensure!(len <= max_len, "snappy block of {len} bytes exceeds {max_len}");
+
+    let mut out = Vec::with_capacity(len);
+    while let Some((&tag, rest)) = input.split_first() {
+        input = rest;
+        let (n, offset) = match tag & 3 {
+            0 => {
+                let mut n = usize::from(tag >> 2);
+                if n >= 60 {
+                    let width = n - 59;
+                    n = read_le(&mut input, width)?;
+                }
+                let n = n + 1;
+                ensure!(n <= input.len(), "snappy literal of {n} bytes overflows the input");
+                ensure!(n <= len - out.len(), "snappy literal overflows the decompressed length");
+                out.extend_from_slice(&input[..n]);
+                input = &input[n..];
+                continue;
+            }
+            1 => {
+                let low = read_le(&mut input, 1)?;
+                (4 + usize::from((tag >> 2) & 7), usize::from(tag >> 5) << 8 | low)
+            }
+            2 => (1 + usize::from(tag >> 2), read_le(&mut input, 2)?),
+            _ => (1 + usize::from(tag >> 2), read_le(&mut input, 4)?),
+        };
+        ensure!(offset != 0 && offset <= out.len(), "invalid snappy copy offset {offset}");
+        ensure!(n <= len - out.len(), "snappy copy overflows the decompressed length");
+        // Copies may overlap their own output, repeating the last `offset` bytes.
+        let start = out.len() - offset;
+        for i in start..start + n {
+            out.push(out[i]);
+        }
+    }
+    ensure!(out.len() == len, "snappy block decompressed to {} bytes, not {len}", out.len());
+    Ok(out)
+}
+
+/// Compresses `data` into a block.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 6 + 16);
+    let mut len = data.len() as u64;
+    while len >= 0x80 {
+        out.push(len as u8 | 0x80);
+        len >>= 7;
+    }
+    out.push(len as u8);
+
+    let mut table = vec![usize::MAX; 1 << TABLE_BITS];
+    let (mut literal, mut i) = (0, 0);
+    while i + MIN_MATCH <= data.len() {
+        let key = u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+        let slot = (key.wrapping_mul(0x1e35_a7bd) >> (32 - TABLE_BITS)) as usize;
+        let candidate = std::mem::replace(&mut table[slot], i);
+        if candidate >= i ||
+            i - candidate > MAX_OFFSET ||
+            data[candidate..candidate + 4] != data[i..i + 4]
+        {
+            i += 1;
+            continue;
+        }
+        let mut n = MIN_MATCH;
+        while i + n < data.len() && data[candidate + n] == data[i + n] {
+            n += 1;
+        }
+        emit_literal(&mut out, &data[literal..i]);
+        emit_copy(&mut out, i - candidate, n);
+        i += n;
+        literal = i;
+    }
+    emit_literal(&mut out, &data[literal..]);
+    out
+}
+
+/// Reads a little-endian integer of `width` bytes.
+fn read_le(input: &mut &[u8], width: usize) -> Result<usize> {
+    if input.len() < width {
+        bail!("truncated snappy element");
+    }
+    let value = input[..width].iter().rev().fold(0, |value, &byte| value << 8 | usize::from(byte));
+    *input = &input[width..];
+    Ok(value)
+}
+
+fn emit_literal(out: &mut Vec<u8>, literal: &[u8]) {
+    let Some(n) = literal.len().checked_sub(1) else { return };
+    if n < 60 {
+        out.push((n as u8) << 2);
+    } else {
+        let bytes = n.to_le_bytes();
+        let width = (usize::BITS - n.leading_zeros()).div_ceil(8) as usize;
+        out.push((59 + width as u8) << 2);
+        out.extend_from_slice(&bytes[..width]);
+    }
+    out.extend_from_slice(literal);
+}
+
+/// Emits copies of `n` bytes from `offset` back, at most 64 bytes at a time, leaving at least
+/// four bytes for the last one.
+fn emit_copy(out: &mut Vec<u8>, offset: usize, mut n: usize) {
+    while n > 0 {
+        let chunk = if n >= 68 {
+            64
+        } else if n > 64 {
+            60
+        } else {
+            n
+        };
+        if (4..12).contains(&chunk) && offset < 2048 {
+            out.push(1 | ((chunk - 4) as u8) << 2 | ((offset >> 8) as u8) << 5);
+            out.push(offset as u8);
+        } else {
+            out.push(2 | ((chunk - 1) as u8) << 2);
+            out.extend_from_slice(&(offset as u16).to_le_bytes());
+        }
+        n -= chunk;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::hex;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::p2p::{BlockTopicVersion, SignedPayloadEnvelope, MAX_GOSSIP_SIZE};
+
+    /// Gossiped blocks of each topic version with their timestamps, as compressed by the `snap`
+    /// crate, the Rust port of the reference implementation, in the envelope tests of
+    /// op-alloy.
+    const REFERENCE_BLOCKS: [(&str, BlockTopicVersion, u64); 4] = [
+        (
+            "bd04f043128457c6ccf35128497167442bcc0f8cce78cda8b366e6a12e526d938d1e4c1046acffffbfc542\
+             a7e212bb7d80d3a4b2f84f7b196d935398a24eb84c519789b401000000fe0300fe0300fe0300fe0300fe03\
+             00fe0300a203000c4a8fd56621ad04fc0101067601008ce60be0005b220117c32c0f3b394b346c2aa42cfa\
+             8157cd41f891aa0bec485a62fc010000",
+            BlockTopicVersion::V1,
+            1_725_271_882,
+        ),
+        (
+            "c104f0433805080eb36c0b130a7cc1dc74c3f721af4e249aa6f61bb89d1557143e971bb738a3f3b98df7c4\
+             57e74048e9d2d7e5cd82bb45e3760467e2270e9db86d1271a700000000fe0300fe0300fe0300fe0300fe03\
+             00fe0300a203000c6b89d46525ad000205067201009cda69cb5b9b73fc4eb2458b37d37f04ff507fe6c9cd\
+             2ab704a05ea9dae3cd61760002000000020000",
+            BlockTopicVersion::V2,
+            1_708_427_627,
+        ),
+        (
+            "f104f0434442b9eb38b259f5b23826e6b623e829d2fb878dac70187a1aecf42a3f9bedfd29793d1fcb5822\
+             324be0d3e12340a95855553a65d64b83e5579dffb31470df5d010000006a03000412346a1d00fe0100fe01\
+             00fe0100fe0100fe0100fe01004201000cc588d465219504100201067601007cfece77b89685f60e3663b6\
+             e0faf2de0734674eb91339700c4858c773a8ff921e014401043e0100",
+            BlockTopicVersion::V3,
+            1_708_427_461,
+        ),
+        (
+            "9105f043cee25401b6853202950d1d8a082f31a80c4fef5782c049a731f5d104b1b9b9aa7618605b420438\
+             ae98b44c8aaaebd482854473c2ae57c079286bb634bece5210000000006a03000412346a1d00fe0100fe01\
+             00fe0100fe0100fe0100fe01004201000c5766d26721950430020106f6010001440104b60100049876",
+            BlockTopicVersion::V4,
+            1_741_842_007,
+        ),
+    ];
+
+    #[test]
+    fn decodes_reference_blocks() {
+        for (block, version, timestamp) in REFERENCE_BLOCKS {
+            let block = hex::decode(block).unwrap();
+            let data = decompress(&block, MAX_GOSSIP_SIZE).unwrap();
+            let envelope = SignedPayloadEnvelope::decode(&data, version).unwrap();
+            assert_eq!(envelope.payload.payload.timestamp(), timestamp);
+            assert_eq!(envelope.encode(), data);
+            assert_eq!(decompress(&compress(&data), data.len()).unwrap(), data);
+
+            // Truncated, or announcing another length, the block is rejected.
+            assert!(decompress(&block[..block.len() - 1], MAX_GOSSIP_SIZE).is_err());
+            let mut longer = block.clone();
+            longer[0] += 1;
+            assert!(decompress(&longer, MAX_GOSSIP_SIZE).is_err());
+            assert!(decompress(&block, data.len() - 1).is_err());
+        }
+        // Blocks shorter than the reference minimum for back-references are a single literal.
+        assert_eq!(compress(b"hello"), [5, 4 << 2, b'h', b'e', b'l', b'l', b'o']);
+    }
+
+    #[test]
+    fn decodes_every_element() {
+        // "abcd", then copies with 1, 2 and 4-byte offsets, and a literal with a 1-byte length.
+        let mut block = vec![75, 3 << 2];
+        block.extend_from_slice(b"abcd");
+        block.extend_from_slice(&[1, 4]);
+        block.extend_from_slice(&[2 | 3 << 2, 2, 0]);
+        block.extend_from_slice(&[3 | 1 << 2, 12, 0, 0, 0]);
+        block.extend_from_slice(&[60 << 2, 60]);
+        block.extend(std::iter::repeat(b'x').take(61));
+        let mut expected = b"abcdabcdcdcdab".to_vec();
+        expected.extend(std::iter::repeat(b'x').take(61));
+        assert_eq!(decompress(&block, 100).unwrap(), expected);
+    }
+
+    #[test]
+    fn compresses_repetitions() {
+        let data: Vec<u8> = (0..10_000).map(|i| (i % 7) as u8).collect();
+        let block = compress(&data);
+        assert!(block.len() < 500, "{}", block.len());
+        assert_eq!(decompress(&block, data.len()).unwrap(), data);
+        assert_eq!(compress(&[]), [0]);
+        assert_eq!(decompress(&[0], 0).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn rejects_malformed_blocks() {
+        let cases: [(&[u8], &str); 9] = [
+            (&[], "preamble"),
+            (&[0x80, 0x80, 0x80, 0x80, 0x80], "preamble"),
+            // A length past 32 bits.
+            (&[0xff, 0xff, 0xff, 0xff, 0x1f], "preamble"),
+            // A copy before any output.
+            (&[4, 1, 1], "offset 1"),
+            (&[5, 4 << 2, 1], "overflows the input"),
+            (&[1, 1 << 2, 1, 2], "overflows the decompressed length"),
+            (&[4, 0, 1, 1, 5], "offset"),
+            (&[4, 0, 1, 2], "truncated"),
+            (&[2, 0, 1], "decompressed to 1 bytes, not 2"),
+        ];
+        for (block, expected) in cases {
+            let err = decompress(block, 100).unwrap_err().to_string();
+            assert!(err.contains(expected), "{block:?}: {err}");
+        }
+        let err = decompress(&compress(&[1; 101]), 100).unwrap_err();
+        assert!(err.to_string().contains("exceeds 100"), "{err}");
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrips(data in prop::collection::vec(0u8..4, 0..5_000)) {
+            prop_assert_eq!(decompress(&compress(&data), data.len()).unwrap(), data);
+        }
+
+        #[test]
+        fn roundtrips_random_bytes(data in prop::collection::vec(any::<u8>(), 0..5_000)) {
+            prop_assert_eq!(decompress(&compress(&data), data.len()).unwrap(), data);
+        }
+
+        #[test]
+        fn never_panics_on_random_blocks(block in prop::collection::vec(any::<u8>(), 0..512)) {
+            let _ = decompress(&block, 1 << 16);
+        }
+
+        #[test]
+        fn never_panics_on_corrupted_blocks(
+            data in prop::collection::vec(0u8..4, 1..2_000),
+            corruptions in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+        ) {
+            let mut block = compress(&data);
+            for (index, byte) in corruptions {
+                let index = index.index(block.len());
+                block[index] = byte;
+            }
+            let _ = decompress(&block, 1 << 16);
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! SSZ encoding of execution payloads, as carried by block gossip messages.
+//!
+//! Each payload version appends fields to the container of the previous one: V2 the
+//! withdrawals, V3 the blob gas fields and V4, the Isthmus payload of the OP Stack, the
+//! withdrawals root. Variable-size fields are referenced by 4-byte offsets in the fixed part.
+
+use alloy_primitives::{Address, Bloom, Bytes, B256, U256};
+use alloy_rpc_types_engine::{
+    ExecutionPayload, ExecutionPayloadV1, ExecutionPayloadV2, ExecutionPayloadV3,
+};
+use alloy_rpc_types_eth::Withdrawal;
+use eyre::{ensure, eyre, Result};
+
+use crate::{engine::EnginePayload, p2p::BlockTopicVersion};
+
+/// Size of the fixed part of a V1 payload, including the offsets of the extra data and of the
+/// transactions.
+const FIXED_PART_V1: usize = 508;
+
+/// Size of an SSZ offset.
+const OFFSET_SIZE: usize = 4;
+
+/// Size of an encoded withdrawal.
+const WITHDRAWAL_SIZE: usize = 44;
+
+/// Maximum size of the extra data of a block.
+const MAX_EXTRA_DATA_SIZE: usize = 32;
+
+/// Returns the size of the fixed part of a payload of `version`.
+const fn fixed_part_size(version: BlockTopicVersion) -> usize {
+    match version {
+        BlockTopicVersion::V1 => FIXED_PART_V1,
+        BlockTopicVersion::V2 => FIXED_PART_V1 + OFFSET_SIZE,
+        BlockTopicVersion::V3 => FIXED_PART_V1 + OFFSET_SIZE + 16,
+        BlockTopicVersion::V4 => FIXED_PART_V1 + OFFSET_SIZE + 16 + 32,
+    }
+}
+
+/// Encodes the execution payload of `payload` in the SSZ container of `version`.
+pub(crate) fn encode_payload(
+    payload: &EnginePayload,
+    version: BlockTopicVersion,
+) -> Result<Vec<u8>> {
+    let v1 = payload.payload.as_v1();
+    let withdrawals = payload.payload.as_v2().map(|v2| &v2.withdrawals);
+    let v3 = payload.payload.as_v3();
+    ensure!(
+        withdrawals.is_some() == (version >= BlockTopicVersion::V2),
+        "{version} payloads must carry withdrawals exactly from Canyon onwards"
+    );
+    ensure!(
+        v3.is_some() == (version >= BlockTopicVersion::V3),
+        "{version} payloads must carry blob gas fields exactly from Ecotone onwards"
+    );
+    ensure!(v1.extra_data.len() <= MAX_EXTRA_DATA_SIZE, "extra data too large");
+
+    let fixed = fixed_part_size(version);
+    let transactions_size: usize = v1.transactions.iter().map(|tx| OFFSET_SIZE + tx.len()).sum();
+    let withdrawals_size = withdrawals.map_or(0, |w| w.len() * WITHDRAWAL_SIZE);
+    let mut out =
+        Vec::with_capacity(fixed + v1.extra_data.len() + transactions_size + withdrawals_size);
+
+    out.extend_from_slice(v1.parent_hash.as_slice());
+    out.extend_from_slice(v1.fee_recipient.as_slice());
+    out.extend_from_slice(v1.state_root.as_slice());
+    out.extend_from_slice(v1.receipts_root.as_slice());
+    out.extend_from_slice(v1.logs_bloom.as_slice());
+    out.extend_from_slice(v1.prev_randao.as_slice());
+    for field in [v1.block_number, v1.gas_limit, v1.gas_used, v1.timestamp] {
+        out.extend_from_slice(&field.to_le_bytes());
+    }
+    let extra_data_offset = fixed;
+    put_offset(&mut out, extra_data_offset)?;
+    out.extend_from_slice(&v1.base_fee_per_gas.to_le_bytes::<32>());
+    out.extend_from_slice(v1.block_hash.as_slice());
+    let transactions_offset = extra_data_offset + v1.extra_data.len();
+    put_offset(&mut out, transactions_offset)?;
+    if withdrawals.is_some() {
+        put_offset(&mut out, transactions_offset + transactions_size)?;
+    }
+    if let Some(v3) = v3 {
+        out.extend_from_slice(&v3.blob_gas_used.to_le_bytes());
+        out.extend_from_slice(&v3.excess_blob_gas.to_le_bytes());
+    }
+    if version == BlockTopicVersion::V4 {
+        let root =
+            payload.withdrawals_root.ok_or_else(|| eyre!("V4 payload has no withdrawals root"))?;
+        out.extend_from_slice(root.as_slice());
+    }
+    debug_assert_eq!(out.len(), fixed);
+
+    out.extend_from_slice(&v1.extra_data);
+    let mut offset = OFFSET_SIZE * v1.transactions.len();
+    for tx in &v1.transactions {
+        put_offset(&mut out, offset)?;
+        offset += tx.len();
+    }
+    for tx in &v1.transactions {
+        out.extend_from_slice(tx);
+    }
+    for withdrawal in withdrawals.into_iter().flatten() {
+        out.extend_from_slice(&withdrawal.index.to_le_bytes());
+        out.extend_from_slice(&withdrawal.validator_index.to_le_bytes());
+        out.extend_from_slice(withdrawal.address.as_slice());
+        out.extend_from_slice(&withdrawal.amount.to_le_bytes());
+    }
+    Ok(out)
+}
+
+/// Decodes an execution payload from the SSZ container of `version`. The parent beacon block
+/// root is not part of the container, and left empty.
+pub(crate) fn decode_payload(data: &[u8], version: BlockTopicVersion) -> Result<EnginePayload> {
+    let fixed = fixed_part_size(version);
+    ensure!(data.len() >= fixed, "{version} payload shorter than its fixed part");
+    let mut reader = Reader { data: &data[..fixed] };
+
+    let parent_hash = B256::from_slice(reader.take(32));
+    let fee_recipient = Address::from_slice(reader.take(20));
+    let state_root = B256::from_slice(reader.take(32));
+    let receipts_root = B256::from_slice(reader.take(32));
+    let logs_bloom = Bloom::from_slice(reader.take(256));
+    let prev_randao = B256::from_slice(reader.take(32));
+    let block_number = reader.u64();
+    let gas_limit = reader.u64();
+    let gas_used = reader.u64();
+    let timestamp = reader.u64();
+    let extra_data_offset = reader.offset();
+    let base_fee_per_gas = U256::from_le_slice(reader.take(32));
+    let block_hash = B256::from_slice(reader.take(32));
+    let transactions_offset = reader.offset();
+    let withdrawals_offset = (version >= BlockTopicVersion::V2).then(|| reader.offset());
+    let blob_gas = (version >= BlockTopicVersion::V3).then(|| (reader.u64(), reader.u64()));
+    let withdrawals_root =
+        (version == BlockTopicVersion::V4).then(|| B256::from_slice(reader.take(32)));
+
+    let end = data.len();
+    let withdrawals_start = withdrawals_offset.unwrap_or(end);
+    ensure!(
+        extra_data_offset == fixed &&
+            extra_data_offset <= transactions_offset &&
+            transactions_offset <= withdrawals_start &&
+            withdrawals_start <= end,
+        "invalid {version} payload offsets"
+    );
+    let extra_data = &data[extra_data_offset..transactions_offset];
+    ensure!(extra_data.len() <= MAX_EXTRA_DATA_SIZE, "extra data too large");
+    let transactions = decode_transactions(&data[transactions_offset..withdrawals_start])?;
+
+    let v1 = ExecutionPayloadV1 {
+        parent_hash,
+        fee_recipient,
+        state_root,
+        receipts_root,
+        logs_bloom,
+        prev_randao,
+        block_number,
+        gas_limit,
+        gas_used,
+        timestamp,
+        extra_data: Bytes::copy_from_slice(extra_data),
+        base_fee_per_gas,
+        block_hash,
+        transactions,
+    };
+    let payload = match withdrawals_offset {
+        None => ExecutionPayload::V1(v1),
+        Some(offset) => {
+            let v2 = ExecutionPayloadV2 {
+                payload_inner: v1,
+                withdrawals: decode_withdrawals(&data[offset..])?,
+            };
+            match blob_gas {
+                None => ExecutionPayload::V2(v2),
+                Some((blob_gas_used, excess_blob_gas)) => {
+                    ExecutionPayload::V3(ExecutionPayloadV3 {
+                        payload_inner: v2,
+                        blob_gas_used,
+                        excess_blob_gas,
+                    })
+                }
+            }
+        }
+    };
+    Ok(EnginePayload { payload, parent_beacon_block_root: None, withdrawals_root })
+}
+
+/// Decodes a list of transactions: the offsets of every transaction, then their data.
+fn decode_transactions(data: &[u8]) -> Result<Vec<Bytes>> {
+    if data.is_empty() {
+        return Ok(Vec::new());
+    }
+    ensure!(data.len() >= OFFSET_SIZE, "transaction list too short");
+    let first = Reader { data }.offset();
+    ensure!(
+        first % OFFSET_SIZE == 0 && first >= OFFSET_SIZE && first <= data.len(),
+        "invalid first transaction offset {first}"
+    );
+    let mut offsets = Reader { data: &data[..first] };
+    let mut starts: Vec<usize> = (0..first / OFFSET_SIZE).map(|_| offsets.offset()).collect();
+    starts.push(data.len());
+    starts
+        .windows(2)
+        .map(|window| {
+            let (start, end) = (window[0], window[1]);
+            ensure!(start <= end && end <= data.len(), "invalid transaction offset {start}");
+            Ok(Bytes::copy_from_slice(&data[start..end]))
+        })
+        .collect()
+}
+
+/// Decodes a list of withdrawals, each of a fixed size.
+fn decode_withdrawals(data: &[u8]) -> Result<Vec<Withdrawal>> {
+    ensure!(data.len() % WITHDRAWAL_SIZE == 0, "invalid withdrawals size {}", data.len());
+    Ok(data
+        .chunks_exact(WITHDRAWAL_SIZE)
+        .map(|data| {
+            let mut reader = Reader { data };
+            Withdrawal {
+                index: reader.u64(),
+                validator_index: reader.u64(),
+                address: Address::from_slice(reader.take(20)),
+                amount: reader.u64(),
+            }
+        })
+        .collect())
+}
+
+fn put_offset(out: &mut Vec<u8>, offset: usize) -> Result<()> {
+    let offset = u32::try_from(offset).map_err(|_| eyre!("payload too large"))?;
+    out.extend_from_slice(&offset.to_le_bytes());
+    Ok(())
+}
+
+/// Reads the fixed-size fields of a container, whose size was checked beforehand.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> &'a [u8] {
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        head
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take(8).try_into().unwrap())
+    }
+
+    fn offset(&mut self) -> usize {
+        u32::from_le_bytes(self.take(OFFSET_SIZE).try_into().unwrap()) as usize
+    }
+}
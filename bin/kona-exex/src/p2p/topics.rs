@@ -0,0 +1,193 @@
+//! Selection of the blocks topics to subscribe to across hardforks.
+//!
+//! Each hardfork changing the payload encoding moves blocks to a new topic version. Clocks and
+//! block timestamps of peers drift around the activation, so the topics of both sides stay
+//! subscribed for a transition window before and after it.
+
+use std::collections::BTreeSet;
+
+use eyre::{ensure, eyre, Result};
+
+use crate::{
+    config::RollupConfig,
+    p2p::{snappy, BlockTopicVersion, SignedPayloadEnvelope, MAX_GOSSIP_SIZE},
+};
+
+/// Default time before and after a topic change during which both topics are subscribed, in
+/// seconds.
+pub const DEFAULT_TOPIC_TRANSITION_WINDOW: u64 = 60;
+
+/// The topics to subscribe to and to leave after an update of the [`BlockTopics`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TopicChanges {
+    /// Topics entering their transition window.
+    pub subscribe: Vec<String>,
+    /// Topics past their transition window.
+    pub unsubscribe: Vec<String>,
+}
+
+impl TopicChanges {
+    /// Returns true if the subscriptions are unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.subscribe.is_empty() && self.unsubscribe.is_empty()
+    }
+}
+
+/// Tracks the blocks topics of a chain to subscribe to, and decodes the messages received on
+/// them.
+#[derive(Debug, Clone)]
+pub struct BlockTopics {
+    config: RollupConfig,
+    transition_window: u64,
+    subscribed: BTreeSet<BlockTopicVersion>,
+}
+
+impl BlockTopics {
+    /// Creates the topics of the chain of `config`, with nothing subscribed yet.
+    pub const fn new(config: RollupConfig) -> Self {
+        Self {
+            config,
+            transition_window: DEFAULT_TOPIC_TRANSITION_WINDOW,
+            subscribed: BTreeSet::new(),
+        }
+    }
+
+    /// Sets the time before and after a topic change during which both topics are subscribed,
+    /// in seconds.
+    pub const fn with_transition_window(mut self, seconds: u64) -> Self {
+        self.transition_window = seconds;
+        self
+    }
+
+    /// Returns the topic versions to subscribe to at `timestamp`: the one of blocks built now,
+    /// and the ones of blocks built within the transition window around it.
+    pub fn active(&self, timestamp: u64) -> BTreeSet<BlockTopicVersion> {
+        let first = BlockTopicVersion::from_timestamp(
+            &self.config,
+            timestamp.saturating_sub(self.transition_window),
+        );
+        let last = BlockTopicVersion::from_timestamp(
+            &self.config,
+            timestamp.saturating_add(self.transition_window),
+        );
+        BlockTopicVersion::ALL.into_iter().filter(|v| (first..=last).contains(v)).collect()
+    }
+
+    /// Returns the topic versions currently subscribed.
+    pub const fn subscribed(&self) -> &BTreeSet<BlockTopicVersion> {
+        &self.subscribed
+    }
+
+    /// Moves the subscriptions to the topics active at `timestamp`, returning the topics to
+    /// subscribe to and to leave.
+    pub fn update(&mut self, timestamp: u64) -> TopicChanges {
+        let active = self.active(timestamp);
+        let chain_id = self.config.l2_chain_id;
+        let changes = TopicChanges {
+            subscribe: active.difference(&self.subscribed).map(|v| v.topic(chain_id)).collect(),
+            unsubscribe: self.subscribed.difference(&active).map(|v| v.topic(chain_id)).collect(),
+        };
+        self.subscribed = active;
+        changes
+    }
+
+    /// Returns the version of a blocks topic of the chain, if `topic` is one.
+    pub fn version(&self, topic: &str) -> Option<BlockTopicVersion> {
+        BlockTopicVersion::ALL.into_iter().find(|v| v.topic(self.config.l2_chain_id) == topic)
+    }
+
+    /// Returns the topic to publish `envelope` on and the compressed message.
+    pub fn encode(&self, envelope: &SignedPayloadEnvelope) -> (String, Vec<u8>) {
+        (envelope.version.topic(self.config.l2_chain_id), snappy::compress(&envelope.encode()))
+    }
+
+    /// Decompresses and decodes a message received on `topic`, in the encoding of its version.
+    /// The payload must belong on that topic: a block is only accepted on the topic of the
+    /// hardfork active at its timestamp, even while the previous topic is still subscribed.
+    pub fn decode(&self, topic: &str, data: &[u8]) -> Result<SignedPayloadEnvelope> {
+        let version = self.version(topic).ok_or_else(|| eyre!("unknown blocks topic {topic}"))?;
+        let data = snappy::decompress(data, MAX_GOSSIP_SIZE)?;
+        let envelope = SignedPayloadEnvelope::decode(&data, version)?;
+        let timestamp = envelope.payload.payload.timestamp();
+        let expected = BlockTopicVersion::from_timestamp(&self.config, timestamp);
+        ensure!(
+            expected == version,
+            "block {} at {timestamp} received on the {version} topic, expected {expected}",
+            envelope.payload.block_id()
+        );
+        Ok(envelope)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_rpc_types_engine::ExecutionPayload;
+    use k256::ecdsa::SigningKey;
+
+    use super::*;
+    use crate::engine::mock::payload;
+
+    const ECOTONE: u64 = 1_800_000_000;
+    const ISTHMUS: u64 = 1_900_000_000;
+
+    /// OP Mainnet, activating Ecotone and Isthmus at the test timestamps.
+    fn topics() -> BlockTopics {
+        let mut config = RollupConfig::from_registry(10).unwrap();
+        config.ecotone_time = Some(ECOTONE);
+        config.fjord_time = Some(ECOTONE);
+        config.granite_time = Some(ECOTONE);
+        config.holocene_time = Some(ECOTONE);
+        config.isthmus_time = Some(ISTHMUS);
+        BlockTopics::new(config)
+    }
+
+    #[test]
+    fn subscribes_around_activations() {
+        use BlockTopicVersion::*;
+
+        let mut topics = topics();
+        let changes = topics.update(ECOTONE - 61);
+        assert_eq!(changes.subscribe, ["/optimism/10/1/blocks"]);
+        assert!(changes.unsubscribe.is_empty());
+
+        // The next topic is joined ahead of the activation, and the previous one kept after it.
+        let changes = topics.update(ECOTONE - 60);
+        assert_eq!(changes.subscribe, ["/optimism/10/2/blocks"]);
+        assert_eq!(topics.subscribed(), &BTreeSet::from([V2, V3]));
+        assert!(topics.update(ECOTONE + 59).is_empty());
+        let changes = topics.update(ECOTONE + 60);
+        assert!(changes.subscribe.is_empty());
+        assert_eq!(changes.unsubscribe, ["/optimism/10/1/blocks"]);
+
+        assert_eq!(topics.active(ISTHMUS), BTreeSet::from([V3, V4]));
+        assert_eq!(topics.with_transition_window(0).active(ISTHMUS), BTreeSet::from([V4]));
+    }
+
+    #[test]
+    fn decodes_on_the_topic_of_the_payload() {
+        let topics = topics();
+        assert_eq!(topics.version("/optimism/10/2/blocks"), Some(BlockTopicVersion::V3));
+        assert_eq!(topics.version("/optimism/8453/2/blocks"), None);
+
+        // The test blocks are built right after genesis, before Canyon.
+        let key = SigningKey::from_slice(&[0x11; 32]).unwrap();
+        let envelope =
+            SignedPayloadEnvelope::sign(payload(1), BlockTopicVersion::V1, 10, &key).unwrap();
+        let (topic, message) = topics.encode(&envelope);
+        assert_eq!(topic, "/optimism/10/0/blocks");
+        assert_eq!(topics.decode(&topic, &message).unwrap(), envelope);
+        assert!(topics.decode("/optimism/10/1/blocks", &message).is_err());
+        assert!(topics.decode("/optimism/10/9/blocks", &message).is_err());
+        assert!(topics.decode(&topic, &envelope.encode()).is_err());
+
+        // A V1 payload built after Ecotone is rejected on the V1 topic.
+        let mut late = payload(1);
+        if let ExecutionPayload::V1(payload) = &mut late.payload {
+            payload.timestamp = ECOTONE;
+        }
+        let envelope = SignedPayloadEnvelope::sign(late, BlockTopicVersion::V1, 10, &key).unwrap();
+        let (topic, message) = topics.encode(&envelope);
+        let err = topics.decode(&topic, &message).unwrap_err();
+        assert!(err.to_string().contains("expected v3"), "{err}");
+    }
+}
@@ -0,0 +1,343 @@
+//! Exporting the metrics of a standalone node for Prometheus to scrape.
+//!
+//! Hosts running Hera as an ExEx export its metrics with their own recorder. A standalone node
+//! installs the [`PrometheusRecorder`] instead, wrapped in [`ChainLabels`], and serves the
+//! metrics in the Prometheus text format, which `OpenMetrics` scrapers read too, on
+//! `--hera.metrics.addr`.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use eyre::{eyre, Result, WrapErr};
+use metrics::{
+    Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing::{debug, info};
+
+use crate::tenant::ChainLabels;
+
+/// The upper bounds of the histogram buckets, in seconds for the durations most histograms
+/// record, as in the default buckets of Prometheus clients.
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Maximum size of a scrape request.
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// Time a scraper has to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The cumulative buckets, count and sum of the values recorded in a histogram.
+/// The kind of a metric name, with the labels and samples of each of its series.
+type Family<'a> = (&'a str, Vec<(String, Vec<String>)>);
+
+#[derive(Debug)]
+struct Buckets {
+    buckets: [AtomicU64; BUCKETS.len()],
+    count: AtomicU64,
+    /// The bits of the sum.
+    sum: AtomicU64,
+}
+
+impl Default for Buckets {
+    fn default() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0f64.to_bits()),
+        }
+    }
+}
+
+impl HistogramFn for Buckets {
+    fn record(&self, value: f64) {
+        for (bound, bucket) in BUCKETS.iter().zip(&self.buckets) {
+            if value <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let _ = self.sum.fetch_update(Ordering::AcqRel, Ordering::Relaxed, |sum| {
+            Some((f64::from_bits(sum) + value).to_bits())
+        });
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    counters: Mutex<HashMap<Key, Arc<AtomicU64>>>,
+    /// The bits of the gauge values.
+    gauges: Mutex<HashMap<Key, Arc<AtomicU64>>>,
+    histograms: Mutex<HashMap<Key, Arc<Buckets>>>,
+    descriptions: Mutex<HashMap<String, SharedString>>,
+}
+
+/// Records metrics in memory and renders them in the Prometheus text format.
+#[derive(Debug, Clone, Default)]
+pub struct PrometheusRecorder {
+    registry: Arc<Registry>,
+}
+
+impl PrometheusRecorder {
+    /// Creates an empty recorder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Installs the recorder wrapped in [`ChainLabels`] as the global one, and serves its
+    /// metrics on `addr` in a task, returning the bound address.
+    pub async fn install(self, addr: SocketAddr) -> Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .wrap_err_with(|| format!("failed to listen for metrics scrapes on {addr}"))?;
+        let addr = listener.local_addr()?;
+        metrics::set_global_recorder(ChainLabels::new(self.clone()))
+            .map_err(|_| eyre!("a metrics recorder is already installed"))?;
+        tokio::spawn(self.serve(listener));
+        info!(target: "hera::prometheus", %addr, "Serving metrics");
+        Ok(addr)
+    }
+
+    /// Answers the scrapes of `listener` with the recorded metrics.
+    pub async fn serve(self, listener: TcpListener) {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    debug!(target: "hera::prometheus", %err, "Failed to accept a scrape");
+                    continue;
+                }
+            };
+            let recorder = self.clone();
+            tokio::spawn(async move {
+                if let Err(err) = recorder.respond(stream).await {
+                    debug!(target: "hera::prometheus", %err, "Failed to answer a scrape");
+                }
+            });
+        }
+    }
+
+    /// Answers a single HTTP/1 request on `stream`, closing it afterwards.
+    async fn respond(&self, mut stream: TcpStream) -> Result<()> {
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        tokio::time::timeout(REQUEST_TIMEOUT, async {
+            while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                let read = stream.read(&mut buf).await?;
+                eyre::ensure!(read > 0, "connection closed before the end of the request");
+                request.extend_from_slice(&buf[..read]);
+                eyre::ensure!(request.len() <= MAX_REQUEST_SIZE, "request too large");
+            }
+            Ok(())
+        })
+        .await
+        .wrap_err("scrape request timed out")??;
+
+        let line = request.split(|byte| *byte == b'\r').next().unwrap_or_default();
+        let mut parts = line.split(|byte| *byte == b' ');
+        let (method, path) = (parts.next(), parts.next());
+        let (status, body) = match (method, path) {
+            (Some(b"GET"), Some(b"/metrics" | b"/")) => ("200 OK", self.render()),
+            (Some(b"GET"), _) => ("404 Not Found", String::new()),
+            _ => ("405 Method Not Allowed", String::new()),
+        };
+        let head = format!(
+            "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body.as_bytes()).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+
+    /// Renders the recorded metrics in the Prometheus text format, grouped by name.
+    pub fn render(&self) -> String {
+        let registry = &self.registry;
+        let mut families: BTreeMap<String, Family<'_>> = BTreeMap::new();
+        let mut add = |key: &Key, kind, samples| {
+            let family = families.entry(key.name().to_string()).or_insert((kind, Vec::new()));
+            family.1.push((labels(key, None), samples));
+        };
+        for (key, counter) in registry.counters.lock().unwrap().iter() {
+            let value = counter.load(Ordering::Relaxed);
+            add(key, "counter", vec![format!("{}{} {value}", key.name(), labels(key, None))]);
+        }
+        for (key, gauge) in registry.gauges.lock().unwrap().iter() {
+            let value = number(f64::from_bits(gauge.load(Ordering::Relaxed)));
+            add(key, "gauge", vec![format!("{}{} {value}", key.name(), labels(key, None))]);
+        }
+        for (key, histogram) in registry.histograms.lock().unwrap().iter() {
+            let name = key.name();
+            let count = histogram.count.load(Ordering::Relaxed);
+            let mut samples = Vec::new();
+            for (bound, bucket) in BUCKETS.iter().zip(&histogram.buckets) {
+                let le = Some(number(*bound));
+                let value = bucket.load(Ordering::Relaxed);
+                samples.push(format!("{name}_bucket{} {value}", labels(key, le)));
+            }
+            samples.push(format!("{name}_bucket{} {count}", labels(key, Some("+Inf".into()))));
+            let sum = number(f64::from_bits(histogram.sum.load(Ordering::Relaxed)));
+            samples.push(format!("{name}_sum{} {sum}", labels(key, None)));
+            samples.push(format!("{name}_count{} {count}", labels(key, None)));
+            add(key, "histogram", samples);
+        }
+
+        let descriptions = registry.descriptions.lock().unwrap();
+        let mut out = String::new();
+        for (name, (kind, mut series)) in families {
+            if let Some(help) = descriptions.get(&name) {
+                let help = help.replace('\\', r"\\").replace('\n', r"\n");
+                let _ = writeln!(out, "# HELP {name} {help}");
+            }
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            series.sort();
+            for sample in series.into_iter().flat_map(|(_, samples)| samples) {
+                let _ = writeln!(out, "{sample}");
+            }
+        }
+        out
+    }
+
+    fn describe(&self, key: KeyName, description: SharedString) {
+        self.registry.descriptions.lock().unwrap().insert(key.as_str().to_string(), description);
+    }
+}
+
+impl Recorder for PrometheusRecorder {
+    fn describe_counter(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.describe(key, description);
+    }
+
+    fn describe_gauge(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.describe(key, description);
+    }
+
+    fn describe_histogram(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.describe(key, description);
+    }
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        let mut counters = self.registry.counters.lock().unwrap();
+        Counter::from_arc(counters.entry(key.clone()).or_default().clone())
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        let mut gauges = self.registry.gauges.lock().unwrap();
+        Gauge::from_arc(gauges.entry(key.clone()).or_default().clone())
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        let mut histograms = self.registry.histograms.lock().unwrap();
+        Histogram::from_arc(histograms.entry(key.clone()).or_default().clone())
+    }
+}
+
+/// Renders the labels of `key`, with the bucket bound `le` of a histogram sample if any.
+fn labels(key: &Key, le: Option<String>) -> String {
+    let escape = |value: &str| value.replace('\\', r"\\").replace('"', "\\\"").replace('\n', r"\n");
+    let mut labels: Vec<_> = key
+        .labels()
+        .map(|label| format!("{}=\"{}\"", label.key(), escape(label.value())))
+        .collect();
+    labels.extend(le.map(|le| format!("le=\"{le}\"")));
+    if labels.is_empty() {
+        return String::new();
+    }
+    format!("{{{}}}", labels.join(","))
+}
+
+/// Renders a sample value as Prometheus parses it.
+fn number(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics::{counter, describe_counter, gauge, histogram};
+
+    use super::*;
+    use crate::tenant::sync_scope;
+
+    #[test]
+    fn renders_metrics_in_the_prometheus_format() {
+        let recorder = PrometheusRecorder::new();
+        let labelled = ChainLabels::new(recorder.clone());
+        metrics::with_local_recorder(&labelled, || {
+            describe_counter!("hera_test_total", "Test events.");
+            counter!("hera_test_total", "reason" => "a\"b").increment(2);
+            sync_scope(10, || counter!("hera_test_total", "reason" => "c").increment(1));
+            gauge!("hera_test_lag").set(1.5);
+            gauge!("hera_test_infinite").set(f64::INFINITY);
+            histogram!("hera_test_seconds").record(0.2);
+            histogram!("hera_test_seconds").record(20.0);
+        });
+
+        let expected = [
+            "# TYPE hera_test_infinite gauge",
+            "hera_test_infinite +Inf",
+            "# TYPE hera_test_lag gauge",
+            "hera_test_lag 1.5",
+            "# TYPE hera_test_seconds histogram",
+            r#"hera_test_seconds_bucket{le="0.005"} 0"#,
+            r#"hera_test_seconds_bucket{le="0.01"} 0"#,
+            r#"hera_test_seconds_bucket{le="0.025"} 0"#,
+            r#"hera_test_seconds_bucket{le="0.05"} 0"#,
+            r#"hera_test_seconds_bucket{le="0.1"} 0"#,
+            r#"hera_test_seconds_bucket{le="0.25"} 1"#,
+            r#"hera_test_seconds_bucket{le="0.5"} 1"#,
+            r#"hera_test_seconds_bucket{le="1"} 1"#,
+            r#"hera_test_seconds_bucket{le="2.5"} 1"#,
+            r#"hera_test_seconds_bucket{le="5"} 1"#,
+            r#"hera_test_seconds_bucket{le="10"} 1"#,
+            r#"hera_test_seconds_bucket{le="+Inf"} 2"#,
+            "hera_test_seconds_sum 20.2",
+            "hera_test_seconds_count 2",
+            "# HELP hera_test_total Test events.",
+            "# TYPE hera_test_total counter",
+            r#"hera_test_total{reason="a\"b"} 2"#,
+            r#"hera_test_total{reason="c",chain_id="10"} 1"#,
+        ];
+        assert_eq!(recorder.render().lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[tokio::test]
+    async fn serves_scrapes() {
+        let recorder = PrometheusRecorder::new();
+        metrics::with_local_recorder(&recorder, || counter!("hera_test_total").increment(3));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(recorder.serve(listener));
+
+        let scrape = |request: &'static str| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+        let response = scrape("GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.ends_with("\r\n\r\n# TYPE hera_test_total counter\nhera_test_total 3\n"));
+        let response = scrape("GET /other HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"), "{response}");
+    }
+}
@@ -0,0 +1,113 @@
+//! Batches, the L2 block contents posted to L1 by the batcher.
+
+use alloy_rlp::Decodable;
+use eyre::{bail, Result};
+
+mod single;
+pub use single::SingleBatch;
+
+mod span;
+pub use span::{
+    RawSpanBatch, SpanBatch, SpanBatchElement, SpanBatchTransaction, MAX_SPAN_BATCH_ELEMENT_COUNT,
+};
+
+/// The type byte of a [`SingleBatch`].
+pub const SINGLE_BATCH_TYPE: u8 = 0x00;
+
+/// The type byte of a span batch, introduced in Delta.
+pub const SPAN_BATCH_TYPE: u8 = 0x01;
+
+/// A batch decoded from a channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Batch {
+    /// A batch of a single L2 block.
+    Single(SingleBatch),
+    /// A batch spanning a range of L2 blocks, in its raw encoded form.
+    Span(RawSpanBatch),
+}
+
+impl Batch {
+    /// Decodes a batch from its type byte followed by the type-specific encoding.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let Some((&batch_type, mut payload)) = data.split_first() else { bail!("empty batch") };
+        match batch_type {
+            SINGLE_BATCH_TYPE => Ok(Self::Single(SingleBatch::decode(&mut payload)?)),
+            SPAN_BATCH_TYPE => Ok(Self::Span(RawSpanBatch::decode(payload)?)),
+            t => bail!("unknown batch type {t}"),
+        }
+    }
+
+    /// Encodes the batch as its type byte followed by the type-specific encoding.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Self::Single(batch) => {
+                out.push(SINGLE_BATCH_TYPE);
+                alloy_rlp::Encodable::encode(batch, &mut out);
+            }
+            Self::Span(batch) => {
+                out.push(SPAN_BATCH_TYPE);
+                batch.encode(&mut out);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{Bytes, B256};
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn roundtrips_batches() {
+        let single = Batch::Single(SingleBatch {
+            parent_hash: B256::repeat_byte(1),
+            epoch_num: 5,
+            epoch_hash: B256::repeat_byte(2),
+            timestamp: 1_000,
+            transactions: vec![Bytes::from_static(b"tx")],
+        });
+        let span = Batch::Span(RawSpanBatch {
+            origin_bits: vec![false],
+            block_tx_counts: vec![0],
+            ..Default::default()
+        });
+        for batch in [single, span] {
+            assert_eq!(Batch::decode(&batch.encode()).unwrap(), batch);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_batches() {
+        assert!(Batch::decode(&[]).is_err());
+        assert!(Batch::decode(&[0x02, 0xc0]).unwrap_err().to_string().contains("unknown"));
+        assert!(Batch::decode(&[SINGLE_BATCH_TYPE, 0xc1]).is_err());
+    }
+
+    fn arb_single_batch() -> impl Strategy<Value = SingleBatch> {
+        let transactions = prop::collection::vec(prop::collection::vec(any::<u8>(), 0..128), 0..8);
+        (any::<[u8; 32]>(), any::<u64>(), any::<[u8; 32]>(), any::<u64>(), transactions).prop_map(
+            |(parent_hash, epoch_num, epoch_hash, timestamp, transactions)| SingleBatch {
+                parent_hash: parent_hash.into(),
+                epoch_num,
+                epoch_hash: epoch_hash.into(),
+                timestamp,
+                transactions: transactions.into_iter().map(Bytes::from).collect(),
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrips_random_single_batches(batch in arb_single_batch()) {
+            let batch = Batch::Single(batch);
+            let encoded = batch.encode();
+            let decoded = Batch::decode(&encoded).unwrap();
+            prop_assert_eq!(decoded.encode(), encoded);
+            prop_assert_eq!(decoded, batch);
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Single batches, holding the transactions of one L2 block.
+
+use alloy_primitives::{Bytes, B256};
+use alloy_rlp::{RlpDecodable, RlpEncodable};
+
+use crate::protocol::BlockId;
+
+/// A batch of a single L2 block.
+#[derive(Debug, Clone, Default, PartialEq, Eq, RlpEncodable, RlpDecodable)]
+pub struct SingleBatch {
+    /// Hash of the parent L2 block.
+    pub parent_hash: B256,
+    /// Number of the L1 origin of the block.
+    pub epoch_num: u64,
+    /// Hash of the L1 origin of the block.
+    pub epoch_hash: B256,
+    /// Timestamp of the block.
+    pub timestamp: u64,
+    /// The EIP-2718 encoded transactions of the block, excluding deposits.
+    pub transactions: Vec<Bytes>,
+}
+
+impl SingleBatch {
+    /// Returns the L1 origin of the block.
+    pub const fn epoch(&self) -> BlockId {
+        BlockId::new(self.epoch_hash, self.epoch_num)
+    }
+}
@@ -0,0 +1,766 @@
+//! Span batches, introduced in Delta, which encode a range of L2 blocks column-wise.
+//!
+//! [`RawSpanBatch`] is the wire format. [`SpanBatch`] is the derived form with per-block
+//! timestamps, L1 origins and fully encoded transactions.
+
+use alloy_primitives::{Address, Bytes, TxKind, U256};
+use alloy_rlp::{Buf, Decodable, Encodable, Header};
+use eyre::{bail, ensure, eyre, Result};
+
+/// Maximum number of blocks, and of transactions, in a span batch.
+pub const MAX_SPAN_BATCH_ELEMENT_COUNT: u64 = 10_000_000;
+
+const LEGACY_TX_TYPE: u8 = 0x00;
+const EIP2930_TX_TYPE: u8 = 0x01;
+const EIP1559_TX_TYPE: u8 = 0x02;
+
+/// A transaction of a span batch, with all of its columns gathered.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanBatchTransaction {
+    /// The EIP-2718 transaction type: legacy, EIP-2930 or EIP-1559.
+    pub tx_type: u8,
+    /// The sender nonce.
+    pub nonce: u64,
+    /// The gas limit.
+    pub gas: u64,
+    /// The recipient, or `None` for contract creations.
+    pub to: Option<Address>,
+    /// The transferred value.
+    pub value: U256,
+    /// The gas price, or the max fee per gas for EIP-1559 transactions.
+    pub gas_price: U256,
+    /// The max priority fee per gas, for EIP-1559 transactions.
+    pub max_priority_fee_per_gas: U256,
+    /// The calldata.
+    pub data: Bytes,
+    /// The RLP-encoded access list, for EIP-2930 and EIP-1559 transactions.
+    pub access_list: Bytes,
+    /// The signature y-parity.
+    pub y_parity: bool,
+    /// The signature `r` value.
+    pub r: U256,
+    /// The signature `s` value.
+    pub s: U256,
+    /// Whether a legacy transaction is replay-protected with EIP-155.
+    pub protected: bool,
+}
+
+impl SpanBatchTransaction {
+    /// Encodes the transaction in its EIP-2718 form for the given chain.
+    pub fn to_enveloped(&self, chain_id: u64) -> Result<Bytes> {
+        let to = self.to.map_or(TxKind::Create, TxKind::Call);
+        let mut fields = Vec::new();
+        let mut out = Vec::new();
+        match self.tx_type {
+            LEGACY_TX_TYPE => {
+                let v = if self.protected {
+                    chain_id * 2 + 35 + u64::from(self.y_parity)
+                } else {
+                    27 + u64::from(self.y_parity)
+                };
+                self.nonce.encode(&mut fields);
+                self.gas_price.encode(&mut fields);
+                self.gas.encode(&mut fields);
+                to.encode(&mut fields);
+                self.value.encode(&mut fields);
+                self.data.encode(&mut fields);
+                v.encode(&mut fields);
+            }
+            EIP2930_TX_TYPE => {
+                out.push(EIP2930_TX_TYPE);
+                chain_id.encode(&mut fields);
+                self.nonce.encode(&mut fields);
+                self.gas_price.encode(&mut fields);
+                self.gas.encode(&mut fields);
+                to.encode(&mut fields);
+                self.value.encode(&mut fields);
+                self.data.encode(&mut fields);
+                fields.extend_from_slice(&self.access_list);
+                self.y_parity.encode(&mut fields);
+            }
+            EIP1559_TX_TYPE => {
+                out.push(EIP1559_TX_TYPE);
+                chain_id.encode(&mut fields);
+                self.nonce.encode(&mut fields);
+                self.max_priority_fee_per_gas.encode(&mut fields);
+                self.gas_price.encode(&mut fields);
+                self.gas.encode(&mut fields);
+                to.encode(&mut fields);
+                self.value.encode(&mut fields);
+                self.data.encode(&mut fields);
+                fields.extend_from_slice(&self.access_list);
+                self.y_parity.encode(&mut fields);
+            }
+            t => bail!("unsupported span batch transaction type {t}"),
+        }
+        self.r.encode(&mut fields);
+        self.s.encode(&mut fields);
+        Header { list: true, payload_length: fields.len() }.encode(&mut out);
+        out.extend_from_slice(&fields);
+        Ok(out.into())
+    }
+
+    /// Decodes an EIP-2718 encoded legacy, EIP-2930 or EIP-1559 transaction.
+    pub fn from_enveloped(tx: &[u8]) -> Result<Self> {
+        let Some(&first) = tx.first() else { bail!("empty transaction") };
+        let (tx_type, mut buf) =
+            if first >= 0xc0 { (LEGACY_TX_TYPE, tx) } else { (first, &tx[1..]) };
+        let header = Header::decode(&mut buf)?;
+        ensure!(header.list, "transaction is not an RLP list");
+
+        let mut out = Self { tx_type, ..Default::default() };
+        let to_address = |kind: TxKind| match kind {
+            TxKind::Create => None,
+            TxKind::Call(to) => Some(to),
+        };
+        match tx_type {
+            LEGACY_TX_TYPE => {
+                out.nonce = u64::decode(&mut buf)?;
+                out.gas_price = U256::decode(&mut buf)?;
+                out.gas = u64::decode(&mut buf)?;
+                out.to = to_address(TxKind::decode(&mut buf)?);
+                out.value = U256::decode(&mut buf)?;
+                out.data = Bytes::decode(&mut buf)?;
+                let v = u64::decode(&mut buf)?;
+                match v {
+                    27 | 28 => out.y_parity = v == 28,
+                    v if v >= 35 => {
+                        out.protected = true;
+                        out.y_parity = (v - 35) % 2 == 1;
+                    }
+                    v => bail!("invalid legacy transaction v {v}"),
+                }
+            }
+            EIP2930_TX_TYPE | EIP1559_TX_TYPE => {
+                let _chain_id = u64::decode(&mut buf)?;
+                out.nonce = u64::decode(&mut buf)?;
+                if tx_type == EIP1559_TX_TYPE {
+                    out.max_priority_fee_per_gas = U256::decode(&mut buf)?;
+                }
+                out.gas_price = U256::decode(&mut buf)?;
+                out.gas = u64::decode(&mut buf)?;
+                out.to = to_address(TxKind::decode(&mut buf)?);
+                out.value = U256::decode(&mut buf)?;
+                out.data = Bytes::decode(&mut buf)?;
+                out.access_list = raw_rlp_item(&mut buf)?;
+                out.y_parity = bool::decode(&mut buf)?;
+            }
+            t => bail!("unsupported span batch transaction type {t}"),
+        }
+        out.r = U256::decode(&mut buf)?;
+        out.s = U256::decode(&mut buf)?;
+        Ok(out)
+    }
+
+    /// Encodes the type-specific `tx_data` column entry.
+    fn encode_data(&self, out: &mut Vec<u8>) {
+        let mut fields = Vec::new();
+        self.value.encode(&mut fields);
+        match self.tx_type {
+            EIP1559_TX_TYPE => {
+                self.max_priority_fee_per_gas.encode(&mut fields);
+                self.gas_price.encode(&mut fields);
+            }
+            _ => self.gas_price.encode(&mut fields),
+        }
+        self.data.encode(&mut fields);
+        if self.tx_type != LEGACY_TX_TYPE {
+            fields.extend_from_slice(&self.access_list);
+            out.push(self.tx_type);
+        }
+        Header { list: true, payload_length: fields.len() }.encode(out);
+        out.extend_from_slice(&fields);
+    }
+
+    /// Decodes a type-specific `tx_data` column entry into a transaction with the remaining
+    /// columns unset.
+    fn decode_data(buf: &mut &[u8]) -> Result<Self> {
+        let Some(&first) = buf.first() else { bail!("span batch tx data truncated") };
+        let tx_type = if first >= 0xc0 {
+            LEGACY_TX_TYPE
+        } else {
+            buf.advance(1);
+            first
+        };
+        ensure!(tx_type <= EIP1559_TX_TYPE, "unsupported span batch transaction type {tx_type}");
+
+        let header = Header::decode(buf)?;
+        ensure!(header.list, "span batch tx data is not an RLP list");
+        ensure!(buf.len() >= header.payload_length, "span batch tx data truncated");
+        let (mut fields, rest) = buf.split_at(header.payload_length);
+        *buf = rest;
+
+        let mut tx = Self { tx_type, value: U256::decode(&mut fields)?, ..Default::default() };
+        if tx_type == EIP1559_TX_TYPE {
+            tx.max_priority_fee_per_gas = U256::decode(&mut fields)?;
+        }
+        tx.gas_price = U256::decode(&mut fields)?;
+        tx.data = Bytes::decode(&mut fields)?;
+        if tx_type != LEGACY_TX_TYPE {
+            tx.access_list = raw_rlp_item(&mut fields)?;
+        }
+        ensure!(fields.is_empty(), "trailing bytes in span batch tx data");
+        Ok(tx)
+    }
+}
+
+/// The wire format of a span batch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RawSpanBatch {
+    /// Timestamp of the first block, relative to the L2 genesis.
+    pub rel_timestamp: u64,
+    /// Number of the L1 origin of the last block.
+    pub l1_origin_num: u64,
+    /// First 20 bytes of the parent hash of the first block.
+    pub parent_check: [u8; 20],
+    /// First 20 bytes of the L1 origin hash of the last block.
+    pub l1_origin_check: [u8; 20],
+    /// For each block, whether its L1 origin differs from the previous block's.
+    pub origin_bits: Vec<bool>,
+    /// Number of transactions of each block.
+    pub block_tx_counts: Vec<u64>,
+    /// The transactions of all blocks.
+    pub txs: Vec<SpanBatchTransaction>,
+}
+
+impl RawSpanBatch {
+    /// Returns the number of blocks in the span.
+    pub fn block_count(&self) -> usize {
+        self.block_tx_counts.len()
+    }
+
+    /// Decodes a span batch, without its type byte.
+    pub fn decode(mut buf: &[u8]) -> Result<Self> {
+        let buf = &mut buf;
+        let rel_timestamp = read_uvarint(buf)?;
+        let l1_origin_num = read_uvarint(buf)?;
+        let parent_check = read_array(buf)?;
+        let l1_origin_check = read_array(buf)?;
+
+        let block_count = read_uvarint(buf)?;
+        ensure!(block_count > 0, "span batch has no blocks");
+        ensure!(block_count <= MAX_SPAN_BATCH_ELEMENT_COUNT, "span batch has too many blocks");
+        let origin_bits = read_bitlist(buf, block_count as usize)?;
+        let block_tx_counts =
+            (0..block_count).map(|_| read_uvarint(buf)).collect::<Result<Vec<_>>>()?;
+        let total = block_tx_counts.iter().try_fold(0u64, |acc, n| acc.checked_add(*n));
+        let total = total.filter(|n| *n <= MAX_SPAN_BATCH_ELEMENT_COUNT);
+        let Some(total) = total.map(|n| n as usize) else { bail!("span batch has too many txs") };
+
+        let creation_bits = read_bitlist(buf, total)?;
+        let y_parity_bits = read_bitlist(buf, total)?;
+        // Every transaction takes at least 64 bytes of signature, bound the allocation by that.
+        ensure!(buf.len() / 64 >= total, "span batch truncated: {total} txs announced");
+        let mut signatures = Vec::with_capacity(total);
+        for _ in 0..total {
+            let r = U256::from_be_bytes(read_array::<32>(buf)?);
+            let s = U256::from_be_bytes(read_array::<32>(buf)?);
+            signatures.push((r, s));
+        }
+        let mut tos = Vec::new();
+        for _ in creation_bits.iter().filter(|creation| !**creation) {
+            tos.push(Address::from(read_array::<20>(buf)?));
+        }
+        let mut txs = (0..total)
+            .map(|_| SpanBatchTransaction::decode_data(buf))
+            .collect::<Result<Vec<_>>>()?;
+        let legacy_count = txs.iter().filter(|tx| tx.tx_type == LEGACY_TX_TYPE).count();
+
+        let mut tos = tos.into_iter();
+        for (i, tx) in txs.iter_mut().enumerate() {
+            tx.nonce = read_uvarint(buf)?;
+            tx.to = if creation_bits[i] { None } else { tos.next() };
+            tx.y_parity = y_parity_bits[i];
+            (tx.r, tx.s) = signatures[i];
+        }
+        for tx in &mut txs {
+            tx.gas = read_uvarint(buf)?;
+        }
+        let protected_bits = read_bitlist(buf, legacy_count)?;
+        let legacy = txs.iter_mut().filter(|tx| tx.tx_type == LEGACY_TX_TYPE);
+        for (tx, protected) in legacy.zip(protected_bits) {
+            tx.protected = protected;
+        }
+        ensure!(buf.is_empty(), "trailing bytes after span batch");
+
+        Ok(Self {
+            rel_timestamp,
+            l1_origin_num,
+            parent_check,
+            l1_origin_check,
+            origin_bits,
+            block_tx_counts,
+            txs,
+        })
+    }
+
+    /// Encodes the span batch, without its type byte.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        write_uvarint(out, self.rel_timestamp);
+        write_uvarint(out, self.l1_origin_num);
+        out.extend_from_slice(&self.parent_check);
+        out.extend_from_slice(&self.l1_origin_check);
+        write_uvarint(out, self.block_count() as u64);
+        write_bitlist(out, &self.origin_bits);
+        for count in &self.block_tx_counts {
+            write_uvarint(out, *count);
+        }
+
+        let creation_bits = self.txs.iter().map(|tx| tx.to.is_none()).collect::<Vec<_>>();
+        write_bitlist(out, &creation_bits);
+        write_bitlist(out, &self.txs.iter().map(|tx| tx.y_parity).collect::<Vec<_>>());
+        for tx in &self.txs {
+            out.extend_from_slice(&tx.r.to_be_bytes::<32>());
+            out.extend_from_slice(&tx.s.to_be_bytes::<32>());
+        }
+        for to in self.txs.iter().filter_map(|tx| tx.to) {
+            out.extend_from_slice(to.as_slice());
+        }
+        for tx in &self.txs {
+            tx.encode_data(out);
+        }
+        for tx in &self.txs {
+            write_uvarint(out, tx.nonce);
+        }
+        for tx in &self.txs {
+            write_uvarint(out, tx.gas);
+        }
+        let protected_bits = self
+            .txs
+            .iter()
+            .filter(|tx| tx.tx_type == LEGACY_TX_TYPE)
+            .map(|tx| tx.protected)
+            .collect::<Vec<_>>();
+        write_bitlist(out, &protected_bits);
+    }
+
+    /// Derives the per-block contents of the span.
+    pub fn derive(
+        &self,
+        block_time: u64,
+        genesis_timestamp: u64,
+        chain_id: u64,
+    ) -> Result<SpanBatch> {
+        let block_count = self.block_count();
+        ensure!(block_count > 0 && self.origin_bits.len() == block_count, "malformed span batch");
+
+        // The L1 origin of the last block is known, earlier ones are walked back from it.
+        let mut epochs = vec![self.l1_origin_num; block_count];
+        for i in (1..block_count).rev() {
+            epochs[i - 1] = epochs[i]
+                .checked_sub(u64::from(self.origin_bits[i]))
+                .ok_or_else(|| eyre!("span batch L1 origins go below block 0"))?;
+        }
+        let timestamp = |i: usize| {
+            block_time
+                .checked_mul(i as u64)
+                .and_then(|offset| offset.checked_add(self.rel_timestamp))
+                .and_then(|offset| offset.checked_add(genesis_timestamp))
+                .ok_or_else(|| eyre!("span batch timestamp overflows"))
+        };
+
+        let mut txs = self.txs.iter();
+        let mut elements = Vec::with_capacity(block_count);
+        for (i, count) in self.block_tx_counts.iter().enumerate() {
+            let transactions = (0..*count)
+                .map(|_| {
+                    let tx = txs.next().ok_or_else(|| eyre!("span batch tx count mismatch"))?;
+                    tx.to_enveloped(chain_id)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            elements.push(SpanBatchElement {
+                epoch_num: epochs[i],
+                timestamp: timestamp(i)?,
+                transactions,
+            });
+        }
+
+        Ok(SpanBatch {
+            parent_check: self.parent_check,
+            l1_origin_check: self.l1_origin_check,
+            elements,
+        })
+    }
+}
+
+/// One L2 block of a span batch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanBatchElement {
+    /// Number of the L1 origin of the block.
+    pub epoch_num: u64,
+    /// Timestamp of the block.
+    pub timestamp: u64,
+    /// The EIP-2718 encoded transactions of the block.
+    pub transactions: Vec<Bytes>,
+}
+
+/// A span batch with per-block contents.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanBatch {
+    /// First 20 bytes of the parent hash of the first block.
+    pub parent_check: [u8; 20],
+    /// First 20 bytes of the L1 origin hash of the last block.
+    pub l1_origin_check: [u8; 20],
+    /// The blocks of the span.
+    pub elements: Vec<SpanBatchElement>,
+}
+
+impl SpanBatch {
+    /// Returns the timestamp of the first block.
+    pub fn start_timestamp(&self) -> u64 {
+        self.elements.first().map(|e| e.timestamp).unwrap_or_default()
+    }
+
+    /// Returns the timestamp of the last block.
+    pub fn final_timestamp(&self) -> u64 {
+        self.elements.last().map(|e| e.timestamp).unwrap_or_default()
+    }
+
+    /// Converts the span batch into its wire format.
+    pub fn to_raw(&self, genesis_timestamp: u64) -> Result<RawSpanBatch> {
+        let Some(first) = self.elements.first() else { bail!("empty span batch") };
+        let last = self.elements.last().unwrap();
+        let mut raw = RawSpanBatch {
+            rel_timestamp: first
+                .timestamp
+                .checked_sub(genesis_timestamp)
+                .ok_or_else(|| eyre!("span batch starts before the genesis"))?,
+            l1_origin_num: last.epoch_num,
+            parent_check: self.parent_check,
+            l1_origin_check: self.l1_origin_check,
+            ..Default::default()
+        };
+        let mut prev_epoch = first.epoch_num;
+        for (i, element) in self.elements.iter().enumerate() {
+            raw.origin_bits.push(i > 0 && element.epoch_num != prev_epoch);
+            prev_epoch = element.epoch_num;
+            raw.block_tx_counts.push(element.transactions.len() as u64);
+            for tx in &element.transactions {
+                raw.txs.push(SpanBatchTransaction::from_enveloped(tx)?);
+            }
+        }
+        Ok(raw)
+    }
+}
+
+/// Reads a complete RLP item, header included, without decoding it.
+fn raw_rlp_item(buf: &mut &[u8]) -> Result<Bytes> {
+    let mut peek = *buf;
+    let header = Header::decode(&mut peek)?;
+    let len = buf.len() - peek.len() + header.payload_length;
+    ensure!(buf.len() >= len, "RLP item truncated");
+    let item = Bytes::copy_from_slice(&buf[..len]);
+    buf.advance(len);
+    Ok(item)
+}
+
+fn read_array<const N: usize>(buf: &mut &[u8]) -> Result<[u8; N]> {
+    ensure!(buf.len() >= N, "span batch truncated");
+    let array = buf[..N].try_into().unwrap();
+    buf.advance(N);
+    Ok(array)
+}
+
+/// Reads an unsigned LEB128 varint, as written by Go's `binary.PutUvarint`.
+fn read_uvarint(buf: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let Some(&byte) = buf.first() else { bail!("varint truncated") };
+        buf.advance(1);
+        ensure!(i < 9 || byte <= 1, "varint overflows u64");
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    bail!("varint overflows u64")
+}
+
+fn write_uvarint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads a bitlist of `len` bits, stored as a big-endian integer of `ceil(len / 8)` bytes where
+/// bit `i` is the `i`-th least significant bit.
+fn read_bitlist(buf: &mut &[u8], len: usize) -> Result<Vec<bool>> {
+    let bytes = len.div_ceil(8);
+    ensure!(buf.len() >= bytes, "span batch bitlist truncated");
+    let (data, rest) = buf.split_at(bytes);
+    *buf = rest;
+    if len % 8 != 0 && bytes > 0 {
+        ensure!(data[0] >> (len % 8) == 0, "span batch bitlist has bits past its length");
+    }
+    Ok((0..len).map(|i| data[bytes - 1 - i / 8] >> (i % 8) & 1 == 1).collect())
+}
+
+fn write_bitlist(out: &mut Vec<u8>, bits: &[bool]) {
+    let bytes = bits.len().div_ceil(8);
+    let mut data = vec![0u8; bytes];
+    for (i, bit) in bits.iter().enumerate() {
+        if *bit {
+            data[bytes - 1 - i / 8] |= 1 << (i % 8);
+        }
+    }
+    out.extend_from_slice(&data);
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::address;
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn tx(tx_type: u8, to: Option<Address>, protected: bool) -> SpanBatchTransaction {
+        SpanBatchTransaction {
+            tx_type,
+            nonce: 7,
+            gas: 21_000,
+            to,
+            value: U256::from(1_000),
+            gas_price: U256::from(2_000_000_000u64),
+            max_priority_fee_per_gas: U256::from(u64::from(tx_type == EIP1559_TX_TYPE) * 100),
+            data: Bytes::from_static(b"calldata"),
+            // An empty access list.
+            access_list: if tx_type == LEGACY_TX_TYPE { Bytes::new() } else { [0xc0][..].into() },
+            y_parity: true,
+            r: U256::from(11),
+            s: U256::from(12),
+            protected,
+        }
+    }
+
+    fn raw() -> RawSpanBatch {
+        let to = Some(address!("4200000000000000000000000000000000000016"));
+        RawSpanBatch {
+            rel_timestamp: 10,
+            l1_origin_num: 20,
+            parent_check: [1; 20],
+            l1_origin_check: [2; 20],
+            origin_bits: vec![false, true, false],
+            block_tx_counts: vec![2, 0, 2],
+            txs: vec![
+                tx(LEGACY_TX_TYPE, to, true),
+                tx(LEGACY_TX_TYPE, None, false),
+                tx(EIP2930_TX_TYPE, to, false),
+                tx(EIP1559_TX_TYPE, None, false),
+            ],
+        }
+    }
+
+    #[test]
+    fn roundtrips_raw_span_batches() {
+        let raw = raw();
+        let mut encoded = Vec::new();
+        raw.encode(&mut encoded);
+        assert_eq!(RawSpanBatch::decode(&encoded).unwrap(), raw);
+    }
+
+    #[test]
+    fn roundtrips_through_derived_form() {
+        let raw = raw();
+        let span = raw.derive(2, 1_000, 10).unwrap();
+        let epochs = span.elements.iter().map(|e| e.epoch_num).collect::<Vec<_>>();
+        assert_eq!(epochs, [19, 20, 20]);
+        assert_eq!((span.start_timestamp(), span.final_timestamp()), (1_010, 1_014));
+        assert_eq!(span.to_raw(1_000).unwrap(), raw);
+    }
+
+    #[test]
+    fn roundtrips_enveloped_transactions() {
+        for tx in raw().txs {
+            let enveloped = tx.to_enveloped(10).unwrap();
+            assert_eq!(SpanBatchTransaction::from_enveloped(&enveloped).unwrap(), tx);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_and_trailing_data() {
+        let mut encoded = Vec::new();
+        raw().encode(&mut encoded);
+        for len in [0, 10, 50, encoded.len() - 1] {
+            assert!(RawSpanBatch::decode(&encoded[..len]).is_err(), "length {len}");
+        }
+        encoded.push(0);
+        assert!(RawSpanBatch::decode(&encoded).unwrap_err().to_string().contains("trailing"));
+    }
+
+    #[test]
+    fn rejects_huge_counts_without_allocating() {
+        let mut encoded = Vec::new();
+        write_uvarint(&mut encoded, 0);
+        write_uvarint(&mut encoded, 0);
+        encoded.extend_from_slice(&[0; 40]);
+        write_uvarint(&mut encoded, 1);
+        write_bitlist(&mut encoded, &[false]);
+        write_uvarint(&mut encoded, MAX_SPAN_BATCH_ELEMENT_COUNT);
+        // Bitlists for every announced transaction, but no signatures.
+        let bitlist = (MAX_SPAN_BATCH_ELEMENT_COUNT as usize).div_ceil(8);
+        encoded.resize(encoded.len() + 2 * bitlist, 0);
+        let err = RawSpanBatch::decode(&encoded).unwrap_err();
+        assert!(err.to_string().contains("txs announced"), "{err}");
+
+        let mut too_many = Vec::new();
+        write_uvarint(&mut too_many, 0);
+        write_uvarint(&mut too_many, 0);
+        too_many.extend_from_slice(&[0; 40]);
+        write_uvarint(&mut too_many, MAX_SPAN_BATCH_ELEMENT_COUNT + 1);
+        assert!(RawSpanBatch::decode(&too_many).unwrap_err().to_string().contains("too many"));
+    }
+
+    #[test]
+    fn rejects_invalid_varints() {
+        assert!(read_uvarint(&mut &[0x80][..]).is_err());
+        assert!(read_uvarint(&mut &[0xff; 10][..]).is_err());
+        let mut encoded = Vec::new();
+        write_uvarint(&mut encoded, u64::MAX);
+        assert_eq!(read_uvarint(&mut &encoded[..]).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn rejects_out_of_range_derivations() {
+        // Every block changes L1 origin, starting below block 0.
+        let raw = RawSpanBatch {
+            l1_origin_num: 1,
+            origin_bits: vec![false, true, true],
+            block_tx_counts: vec![0; 3],
+            ..Default::default()
+        };
+        assert!(raw.derive(2, 0, 10).unwrap_err().to_string().contains("below block 0"));
+
+        let raw = RawSpanBatch {
+            rel_timestamp: u64::MAX - 1,
+            origin_bits: vec![false; 2],
+            block_tx_counts: vec![0; 2],
+            ..Default::default()
+        };
+        assert!(raw.derive(2, 0, 10).unwrap_err().to_string().contains("overflows"));
+
+        let span = SpanBatch {
+            elements: vec![SpanBatchElement { timestamp: 5, ..Default::default() }],
+            ..Default::default()
+        };
+        assert!(span.to_raw(10).is_err());
+    }
+
+    fn arb_u256() -> impl Strategy<Value = U256> {
+        prop_oneof![
+            any::<u64>().prop_map(U256::from),
+            any::<[u8; 32]>().prop_map(U256::from_be_bytes)
+        ]
+    }
+
+    fn arb_tx() -> impl Strategy<Value = SpanBatchTransaction> {
+        let to = prop::option::of(any::<[u8; 20]>().prop_map(Address::from));
+        let data = prop::collection::vec(any::<u8>(), 0..64);
+        let access_list = prop::collection::vec(prop::collection::vec(any::<u8>(), 0..40), 0..3);
+        (
+            (0..=EIP1559_TX_TYPE, any::<u64>(), any::<u64>(), to, arb_u256(), arb_u256()),
+            (arb_u256(), data, access_list, any::<bool>(), arb_u256(), arb_u256(), any::<bool>()),
+        )
+            .prop_map(
+                |(
+                    (tx_type, nonce, gas, to, value, gas_price),
+                    (tip, data, access_list, y_parity, r, s, protected),
+                )| {
+                    let mut tx = SpanBatchTransaction {
+                        tx_type,
+                        nonce,
+                        gas,
+                        to,
+                        value,
+                        gas_price,
+                        data: data.into(),
+                        y_parity,
+                        r,
+                        s,
+                        ..Default::default()
+                    };
+                    if tx_type == LEGACY_TX_TYPE {
+                        tx.protected = protected;
+                    } else {
+                        let access_list =
+                            access_list.into_iter().map(Bytes::from).collect::<Vec<_>>();
+                        tx.access_list = alloy_rlp::encode(access_list).into();
+                    }
+                    if tx_type == EIP1559_TX_TYPE {
+                        tx.max_priority_fee_per_gas = tip;
+                    }
+                    tx
+                },
+            )
+    }
+
+    /// Span batches of up to 8 blocks of up to 3 transactions each, the L1 origin of the first
+    /// block being unchanged as in the batches built from [`SpanBatch::to_raw`].
+    fn arb_raw() -> impl Strategy<Value = RawSpanBatch> {
+        let blocks = prop::collection::vec((any::<bool>(), 0..4u64), 1..8);
+        (0..u64::MAX / 2, 8..u64::MAX, any::<[u8; 20]>(), any::<[u8; 20]>(), blocks).prop_flat_map(
+            |(rel_timestamp, l1_origin_num, parent_check, l1_origin_check, blocks)| {
+                let total = blocks.iter().map(|(_, count)| *count as usize).sum::<usize>();
+                prop::collection::vec(arb_tx(), total).prop_map(move |txs| {
+                    let mut origin_bits = blocks.iter().map(|(bit, _)| *bit).collect::<Vec<_>>();
+                    origin_bits[0] = false;
+                    RawSpanBatch {
+                        rel_timestamp,
+                        l1_origin_num,
+                        parent_check,
+                        l1_origin_check,
+                        origin_bits,
+                        block_tx_counts: blocks.iter().map(|(_, count)| *count).collect(),
+                        txs,
+                    }
+                })
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrips_random_raw_span_batches(raw in arb_raw()) {
+            let mut encoded = Vec::new();
+            raw.encode(&mut encoded);
+            let decoded = RawSpanBatch::decode(&encoded).unwrap();
+            let mut reencoded = Vec::new();
+            decoded.encode(&mut reencoded);
+            prop_assert_eq!(reencoded, encoded);
+            prop_assert_eq!(decoded, raw);
+        }
+
+        #[test]
+        fn roundtrips_random_span_batches_through_derived_form(
+            raw in arb_raw(),
+            chain_id in 1..u64::MAX / 4,
+        ) {
+            let span = raw.derive(2, 1_000, chain_id).unwrap();
+            prop_assert_eq!(span.elements.len(), raw.block_count());
+            prop_assert_eq!(span.to_raw(1_000).unwrap(), raw);
+        }
+
+        #[test]
+        fn roundtrips_random_enveloped_transactions(tx in arb_tx(), chain_id in 1..u64::MAX / 4) {
+            let enveloped = tx.to_enveloped(chain_id).unwrap();
+            let decoded = SpanBatchTransaction::from_enveloped(&enveloped).unwrap();
+            prop_assert_eq!(decoded.to_enveloped(chain_id).unwrap(), enveloped);
+            prop_assert_eq!(decoded, tx);
+        }
+
+        #[test]
+        fn roundtrips_random_varints(value in any::<u64>()) {
+            let mut encoded = Vec::new();
+            write_uvarint(&mut encoded, value);
+            prop_assert_eq!(read_uvarint(&mut &encoded[..]).unwrap(), value);
+        }
+
+        #[test]
+        fn roundtrips_random_bitlists(bits in prop::collection::vec(any::<bool>(), 0..64)) {
+            let mut encoded = Vec::new();
+            write_bitlist(&mut encoded, &bits);
+            prop_assert_eq!(read_bitlist(&mut &encoded[..], bits.len()).unwrap(), bits);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Block identifiers and references.
+
+use alloy_primitives::B256;
+use serde::{Deserialize, Serialize};
+
+/// Identifies a block by its hash and number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BlockId {
+    /// The block hash.
+    pub hash: B256,
+    /// The block number.
+    pub number: u64,
+}
+
+impl BlockId {
+    /// Creates a new [`BlockId`].
+    pub const fn new(hash: B256, number: u64) -> Self {
+        Self { hash, number }
+    }
+}
+
+impl std::fmt::Display for BlockId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{} ({})", self.number, self.hash)
+    }
+}
+
+/// A reference to a block, with the fields derivation needs to link it to its parent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockInfo {
+    /// The block hash.
+    pub hash: B256,
+    /// The block number.
+    pub number: u64,
+    /// The parent block hash.
+    pub parent_hash: B256,
+    /// The block timestamp.
+    pub timestamp: u64,
+}
+
+impl BlockInfo {
+    /// Creates a new [`BlockInfo`].
+    pub const fn new(hash: B256, number: u64, parent_hash: B256, timestamp: u64) -> Self {
+        Self { hash, number, parent_hash, timestamp }
+    }
+
+    /// Returns the id of the block.
+    pub const fn id(&self) -> BlockId {
+        BlockId::new(self.hash, self.number)
+    }
+
+    /// Returns the id of the parent block.
+    pub const fn parent_id(&self) -> BlockId {
+        BlockId::new(self.parent_hash, self.number.saturating_sub(1))
+    }
+}
+
+impl std::fmt::Display for BlockInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.id().fmt(f)
+    }
+}
+
+/// A reference to an L2 block, including its L1 origin and position in the epoch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct L2BlockInfo {
+    /// The L2 block.
+    #[serde(flatten)]
+    pub block_info: BlockInfo,
+    /// The L1 origin of the L2 block.
+    #[serde(rename = "l1origin")]
+    pub l1_origin: BlockId,
+    /// The sequence number of the L2 block within its epoch.
+    #[serde(rename = "sequenceNumber")]
+    pub seq_num: u64,
+}
+
+impl L2BlockInfo {
+    /// Creates a new [`L2BlockInfo`].
+    pub const fn new(block_info: BlockInfo, l1_origin: BlockId, seq_num: u64) -> Self {
+        Self { block_info, l1_origin, seq_num }
+    }
+}
+
+impl std::fmt::Display for L2BlockInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.block_info.fmt(f)
+    }
+}
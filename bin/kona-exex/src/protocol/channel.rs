@@ -0,0 +1,382 @@
+//! Channels, reassembled from frames into a compressed stream of batches.
+
+use std::{collections::BTreeMap, fmt, io::Read};
+
+use alloy_primitives::Bytes;
+use alloy_rlp::Header;
+use eyre::{bail, ensure, Result};
+
+use crate::protocol::{
+    batch::Batch,
+    frame::{ChannelId, Frame, FRAME_OVERHEAD},
+    BlockInfo,
+};
+
+/// Maximum decompressed size of a channel before Fjord.
+pub const MAX_RLP_BYTES_PER_CHANNEL_BEDROCK: u64 = 10_000_000;
+
+/// Maximum decompressed size of a channel once Fjord is active.
+pub const MAX_RLP_BYTES_PER_CHANNEL_FJORD: u64 = 100_000_000;
+
+/// The version byte of brotli-compressed channels, from Fjord onwards.
+pub const CHANNEL_VERSION_BROTLI: u8 = 0x01;
+
+/// Why a frame was not added to a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    /// The frame belongs to another channel.
+    WrongChannel {
+        /// The channel of the frame.
+        frame: ChannelId,
+        /// The channel it was added to.
+        channel: ChannelId,
+    },
+    /// A frame with the same number was already received.
+    Duplicate(u16),
+    /// The frame follows the last frame of the channel.
+    AfterLast {
+        /// The frame number.
+        number: u16,
+        /// The number of the last frame.
+        last: u16,
+    },
+    /// The frame closes a channel already closed.
+    SecondLast(u16),
+}
+
+impl FrameError {
+    /// Returns the kind of the error.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::WrongChannel { .. } => "wrong_channel",
+            Self::Duplicate(_) => "duplicate",
+            Self::AfterLast { .. } => "after_last",
+            Self::SecondLast(_) => "second_last",
+        }
+    }
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongChannel { frame, channel } => {
+                write!(f, "frame of channel {frame} added to channel {channel}")
+            }
+            Self::Duplicate(number) => write!(f, "duplicate frame {number}"),
+            Self::AfterLast { number, last } => write!(f, "frame {number} after last frame {last}"),
+            Self::SecondLast(number) => write!(f, "second last frame {number}"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+/// A channel being reassembled from its frames.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    id: ChannelId,
+    open_block: BlockInfo,
+    highest_l1_inclusion: BlockInfo,
+    frames: BTreeMap<u16, Frame>,
+    last_frame_number: Option<u16>,
+    size: usize,
+}
+
+impl Channel {
+    /// Creates an empty channel, first seen in `open_block`.
+    pub const fn new(id: ChannelId, open_block: BlockInfo) -> Self {
+        Self {
+            id,
+            open_block,
+            highest_l1_inclusion: open_block,
+            frames: BTreeMap::new(),
+            last_frame_number: None,
+            size: 0,
+        }
+    }
+
+    /// Adds a frame included in `l1_block` to the channel.
+    pub fn add_frame(&mut self, frame: Frame, l1_block: BlockInfo) -> Result<(), FrameError> {
+        if frame.id != self.id {
+            return Err(FrameError::WrongChannel { frame: frame.id, channel: self.id });
+        }
+        if self.frames.contains_key(&frame.number) {
+            return Err(FrameError::Duplicate(frame.number));
+        }
+        if let Some(last) = self.last_frame_number {
+            if frame.number > last {
+                return Err(FrameError::AfterLast { number: frame.number, last });
+            }
+            if frame.is_last {
+                return Err(FrameError::SecondLast(frame.number));
+            }
+        }
+
+        if frame.is_last {
+            // Frames past the closing one are pruned, like op-node does. The closing frame is not
+            // in the channel yet, so splitting at it keeps exactly the frames before it.
+            let pruned = self.frames.split_off(&frame.number);
+            self.size -= pruned.values().map(Frame::size).sum::<usize>();
+            self.last_frame_number = Some(frame.number);
+        }
+        if l1_block.number > self.highest_l1_inclusion.number {
+            self.highest_l1_inclusion = l1_block;
+        }
+        self.size += frame.size();
+        self.frames.insert(frame.number, frame);
+        Ok(())
+    }
+
+    /// Returns the channel id.
+    pub const fn id(&self) -> ChannelId {
+        self.id
+    }
+
+    /// Returns the L1 block the channel was opened in.
+    pub const fn open_block(&self) -> BlockInfo {
+        self.open_block
+    }
+
+    /// Returns the highest L1 block any frame of the channel was included in.
+    pub const fn highest_l1_inclusion(&self) -> BlockInfo {
+        self.highest_l1_inclusion
+    }
+
+    /// Returns the summed encoded size of the frames.
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the frames received so far, ordered by frame number.
+    pub fn frames(&self) -> impl Iterator<Item = &Frame> {
+        self.frames.values()
+    }
+
+    /// Returns the highest frame number received, if any.
+    pub fn highest_frame_number(&self) -> Option<u16> {
+        self.frames.keys().next_back().copied()
+    }
+
+    /// Returns the number of frames received.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns true if the last frame was received.
+    pub const fn is_closed(&self) -> bool {
+        self.last_frame_number.is_some()
+    }
+
+    /// Returns true if every frame up to the last one was received.
+    pub fn is_ready(&self) -> bool {
+        self.last_frame_number.is_some_and(|last| self.frames.len() == last as usize + 1)
+    }
+
+    /// Returns the concatenated data of the frames, if the channel is ready.
+    pub fn frame_data(&self) -> Option<Bytes> {
+        if !self.is_ready() {
+            return None;
+        }
+        // Channels of a single frame, the common case, are handed out without a copy.
+        if self.frames.len() == 1 {
+            return self.frames.values().next().map(|frame| frame.data.clone());
+        }
+        let mut data = Vec::with_capacity(self.size - self.frames.len() * FRAME_OVERHEAD);
+        for frame in self.frames.values() {
+            data.extend_from_slice(&frame.data);
+        }
+        Some(data.into())
+    }
+}
+
+/// Decompresses channel data, with at most `max_size` bytes of output.
+///
+/// Before Fjord channels are always zlib-compressed. From Fjord onwards the first byte tells the
+/// format apart: zlib streams start with a CMF byte whose low nibble is 8 or 15, while brotli
+/// streams are prefixed with [`CHANNEL_VERSION_BROTLI`].
+pub fn decompress_channel(data: &[u8], max_size: u64, fjord: bool) -> Result<Vec<u8>> {
+    let Some(&first) = data.first() else { bail!("empty channel data") };
+    let mut out = Vec::new();
+    match first {
+        b if b & 0x0F == 0x08 || b & 0x0F == 0x0F => {
+            // Truncated streams are accepted: everything decoded so far is kept, like op-node.
+            let limit = max_size as usize;
+            match miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(data, limit) {
+                Ok(decoded) => out = decoded,
+                Err(err) if !err.output.is_empty() => out = err.output,
+                Err(err) => bail!("failed to decompress zlib channel: {:?}", err.status),
+            }
+        }
+        CHANNEL_VERSION_BROTLI if fjord => {
+            let reader = brotli::Decompressor::new(&data[1..], 4096);
+            if let Err(err) = reader.take(max_size).read_to_end(&mut out) {
+                if out.is_empty() {
+                    bail!("failed to decompress brotli channel: {err}");
+                }
+            }
+        }
+        b => bail!("unsupported channel compression type {b:#04x}"),
+    }
+    Ok(out)
+}
+
+/// Reads the batches of a decompressed channel: a sequence of RLP strings, each holding an
+/// encoded batch.
+#[derive(Debug)]
+pub struct BatchReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BatchReader<'a> {
+    /// Creates a reader over decompressed channel data.
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+}
+
+impl Iterator for BatchReader<'_> {
+    type Item = Result<Batch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let mut buf = self.data;
+        let result = Header::decode(&mut buf).map_err(eyre::Report::from).and_then(|header| {
+            ensure!(!header.list, "channel batch is an RLP list");
+            ensure!(buf.len() >= header.payload_length, "channel batch truncated");
+            let batch = Batch::decode(&buf[..header.payload_length]);
+            buf = &buf[header.payload_length..];
+            batch
+        });
+        // Stop at the first error: the rest of the channel cannot be framed reliably.
+        self.data = if result.is_ok() { buf } else { &[] };
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        batcher::{Compression, CompressionAlgo},
+        protocol::batch::SingleBatch,
+    };
+
+    const ID: ChannelId = ChannelId([1; 16]);
+
+    fn frame(number: u16, data: &'static [u8], is_last: bool) -> Frame {
+        Frame { id: ID, number, data: Bytes::from_static(data), is_last }
+    }
+
+    fn l1_block(number: u64) -> BlockInfo {
+        BlockInfo { number, ..Default::default() }
+    }
+
+    #[test]
+    fn reassembles_frames_out_of_order() {
+        let mut channel = Channel::new(ID, l1_block(1));
+        channel.add_frame(frame(2, b"!", true), l1_block(1)).unwrap();
+        assert!(channel.is_closed() && !channel.is_ready());
+        channel.add_frame(frame(0, b"hello", false), l1_block(2)).unwrap();
+        channel.add_frame(frame(1, b" world", false), l1_block(3)).unwrap();
+
+        assert!(channel.is_ready());
+        assert_eq!(channel.frame_data().unwrap(), Bytes::from_static(b"hello world!"));
+        assert_eq!(channel.highest_l1_inclusion().number, 3);
+        assert_eq!(channel.size(), 3 * FRAME_OVERHEAD + 12);
+    }
+
+    #[test]
+    fn rejects_invalid_frames() {
+        let mut channel = Channel::new(ID, l1_block(1));
+        channel.add_frame(frame(0, b"a", false), l1_block(1)).unwrap();
+        assert_eq!(
+            channel.add_frame(frame(0, b"a", false), l1_block(1)),
+            Err(FrameError::Duplicate(0))
+        );
+        let other = Frame { id: ChannelId([2; 16]), ..frame(1, b"b", false) };
+        assert_eq!(
+            channel.add_frame(other, l1_block(1)),
+            Err(FrameError::WrongChannel { frame: ChannelId([2; 16]), channel: ID })
+        );
+
+        channel.add_frame(frame(3, b"d", false), l1_block(1)).unwrap();
+        channel.add_frame(frame(2, b"c", true), l1_block(1)).unwrap();
+        // Frames past the last one are pruned, and no longer accepted.
+        assert_eq!(channel.frame_count(), 2);
+        assert_eq!(channel.size(), 2 * FRAME_OVERHEAD + 2);
+        assert_eq!(
+            channel.add_frame(frame(4, b"e", false), l1_block(1)),
+            Err(FrameError::AfterLast { number: 4, last: 2 })
+        );
+        assert_eq!(
+            channel.add_frame(frame(1, b"b", true), l1_block(1)),
+            Err(FrameError::SecondLast(1))
+        );
+    }
+
+    #[test]
+    fn closes_on_the_highest_frame_number() {
+        let mut channel = Channel::new(ID, l1_block(1));
+        channel.add_frame(frame(u16::MAX, b"z", true), l1_block(1)).unwrap();
+        assert!(channel.is_closed());
+    }
+
+    #[test]
+    fn roundtrips_compressed_batches() {
+        let batches = (0..3u64)
+            .map(|i| {
+                Batch::Single(SingleBatch {
+                    epoch_num: i,
+                    timestamp: 100 + 2 * i,
+                    transactions: vec![Bytes::from(vec![i as u8; 40])],
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut encoded = Vec::new();
+        for batch in &batches {
+            alloy_rlp::Encodable::encode(&batch.encode()[..], &mut encoded);
+        }
+
+        for (algo, fjord) in [(CompressionAlgo::Zlib, false), (CompressionAlgo::Brotli, true)] {
+            let compressed =
+                Compression::new(algo, algo.default_level()).unwrap().compress(&encoded).unwrap();
+            let data =
+                decompress_channel(&compressed, MAX_RLP_BYTES_PER_CHANNEL_FJORD, fjord).unwrap();
+            let decoded = BatchReader::new(&data).collect::<Result<Vec<_>>>().unwrap();
+            assert_eq!(decoded, batches, "{algo}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_channel_data() {
+        assert!(decompress_channel(&[], MAX_RLP_BYTES_PER_CHANNEL_BEDROCK, true).is_err());
+        assert!(decompress_channel(&[0x02, 0], MAX_RLP_BYTES_PER_CHANNEL_BEDROCK, true).is_err());
+        // Brotli channels are only valid from Fjord onwards.
+        let brotli = Compression::new(CompressionAlgo::Brotli, 1).unwrap();
+        let compressed = brotli.compress(b"data").unwrap();
+        assert!(decompress_channel(&compressed, MAX_RLP_BYTES_PER_CHANNEL_BEDROCK, false).is_err());
+
+        // Output past the size limit is cut off.
+        let zlib = Compression::new(CompressionAlgo::Zlib, 9).unwrap();
+        let compressed = zlib.compress(&[0; 1000]).unwrap();
+        assert_eq!(decompress_channel(&compressed, 100, false).unwrap().len(), 100);
+    }
+
+    #[test]
+    fn stops_reading_at_the_first_invalid_batch() {
+        let batch = Batch::Single(SingleBatch::default()).encode();
+        let mut data = Vec::new();
+        alloy_rlp::Encodable::encode(&batch[..], &mut data);
+        // A list where a string is expected, then a valid batch that is never reached.
+        data.push(0xc0);
+        alloy_rlp::Encodable::encode(&batch[..], &mut data);
+
+        let mut reader = BatchReader::new(&data);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+}
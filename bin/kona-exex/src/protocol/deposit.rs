@@ -0,0 +1,279 @@
+//! Deposit transactions.
+
+use alloy_primitives::{address, b256, keccak256, Address, Bytes, Log, TxKind, B256, U256};
+use alloy_rlp::{Buf, BufMut, Decodable, Encodable, Header};
+use eyre::{bail, ensure, Result, WrapErr};
+
+use crate::l1::L1Receipt;
+
+/// The EIP-2718 transaction type of deposit transactions.
+pub const DEPOSIT_TX_TYPE: u8 = 0x7E;
+
+/// The sender of L1 info deposit transactions.
+pub const L1_INFO_DEPOSITOR_ADDRESS: Address = address!("deaddeaddeaddeaddeaddeaddeaddeaddead0001");
+
+/// The `L1Block` predeploy that receives L1 info deposit transactions.
+pub const L1_BLOCK_ADDRESS: Address = address!("4200000000000000000000000000000000000015");
+
+/// Topic of the `TransactionDeposited(address,address,uint256,bytes)` event of the
+/// `OptimismPortal`.
+pub const DEPOSIT_EVENT_TOPIC: B256 =
+    b256!("b3813568d9991fc951961fcb4c784893574240a28925604d09fc577c55bb7c32");
+
+/// The only supported version of the deposit event encoding.
+pub const DEPOSIT_EVENT_VERSION_0: B256 = B256::ZERO;
+
+/// The domain of a deposit source hash, separating the different kinds of deposits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepositSourceDomain {
+    /// A user deposit, identified by the L1 block hash and log index of its event.
+    User {
+        /// Hash of the L1 block containing the deposit event.
+        l1_block_hash: B256,
+        /// Index of the deposit event log in the L1 block.
+        log_index: u64,
+    },
+    /// An L1 info deposit, identified by the L1 origin hash and the L2 sequence number.
+    L1Info {
+        /// Hash of the L1 origin block.
+        l1_block_hash: B256,
+        /// Sequence number of the L2 block within its epoch.
+        sequence_number: u64,
+    },
+    /// A network upgrade deposit, identified by a unique intent string.
+    Upgrade {
+        /// Hash of the upgrade intent.
+        intent_hash: B256,
+    },
+}
+
+impl DepositSourceDomain {
+    /// Returns the source hash of the deposit: `keccak256(domain ++ keccak256(identifier))`.
+    pub fn source_hash(&self) -> B256 {
+        let (domain, id_hash) = match *self {
+            Self::User { l1_block_hash, log_index } => (
+                0u8,
+                keccak256(
+                    [l1_block_hash.as_slice(), &U256::from(log_index).to_be_bytes::<32>()].concat(),
+                ),
+            ),
+            Self::L1Info { l1_block_hash, sequence_number } => (
+                1,
+                keccak256(
+                    [l1_block_hash.as_slice(), &U256::from(sequence_number).to_be_bytes::<32>()]
+                        .concat(),
+                ),
+            ),
+            Self::Upgrade { intent_hash } => (2, intent_hash),
+        };
+        let mut preimage = [0u8; 64];
+        preimage[31] = domain;
+        preimage[32..].copy_from_slice(id_hash.as_slice());
+        keccak256(preimage)
+    }
+}
+
+/// A deposit transaction, of EIP-2718 type `0x7E`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxDeposit {
+    /// Uniquely identifies the origin of the deposit.
+    pub source_hash: B256,
+    /// The sender of the deposit.
+    pub from: Address,
+    /// The recipient of the deposit, or [`TxKind::Create`] for contract creations.
+    pub to: TxKind,
+    /// The ETH minted on L2, if any.
+    pub mint: Option<u128>,
+    /// The ETH value transferred to the recipient.
+    pub value: U256,
+    /// The gas limit of the deposit.
+    pub gas_limit: u64,
+    /// Whether the deposit is a system transaction, which is only allowed before Regolith.
+    pub is_system_transaction: bool,
+    /// The calldata of the deposit.
+    pub input: Bytes,
+}
+
+impl TxDeposit {
+    fn fields_len(&self) -> usize {
+        self.source_hash.length() +
+            self.from.length() +
+            self.to.length() +
+            self.mint.unwrap_or_default().length() +
+            self.value.length() +
+            self.gas_limit.length() +
+            self.is_system_transaction.length() +
+            self.input.length()
+    }
+
+    /// Encodes the transaction in its EIP-2718 form: the type byte followed by the RLP list.
+    pub fn encode_2718(&self, out: &mut dyn BufMut) {
+        out.put_u8(DEPOSIT_TX_TYPE);
+        Header { list: true, payload_length: self.fields_len() }.encode(out);
+        self.source_hash.encode(out);
+        self.from.encode(out);
+        self.to.encode(out);
+        self.mint.unwrap_or_default().encode(out);
+        self.value.encode(out);
+        self.gas_limit.encode(out);
+        self.is_system_transaction.encode(out);
+        self.input.encode(out);
+    }
+
+    /// Returns the EIP-2718 encoding of the transaction.
+    pub fn encoded_2718(&self) -> Bytes {
+        let mut out = Vec::with_capacity(1 + self.fields_len() + 4);
+        self.encode_2718(&mut out);
+        out.into()
+    }
+
+    /// Decodes a transaction from its EIP-2718 form.
+    pub fn decode_2718(buf: &mut &[u8]) -> Result<Self> {
+        if buf.first() != Some(&DEPOSIT_TX_TYPE) {
+            bail!("not a deposit transaction");
+        }
+        buf.advance(1);
+        let header = Header::decode(buf)?;
+        if !header.list {
+            bail!("deposit transaction is not an RLP list");
+        }
+        let remaining = buf.len();
+        let tx = Self {
+            source_hash: Decodable::decode(buf)?,
+            from: Decodable::decode(buf)?,
+            to: Decodable::decode(buf)?,
+            mint: Some(u128::decode(buf)?).filter(|mint| *mint != 0),
+            value: Decodable::decode(buf)?,
+            gas_limit: Decodable::decode(buf)?,
+            is_system_transaction: Decodable::decode(buf)?,
+            input: Decodable::decode(buf)?,
+        };
+        if remaining - buf.len() != header.payload_length {
+            bail!("deposit transaction length mismatch");
+        }
+        Ok(tx)
+    }
+
+    /// Returns the transaction hash: the keccak256 hash of the EIP-2718 encoding.
+    pub fn tx_hash(&self) -> B256 {
+        keccak256(self.encoded_2718())
+    }
+
+    /// Decodes a user deposit from a `TransactionDeposited` event, emitted at `log_index` in the
+    /// L1 block `l1_block_hash`.
+    ///
+    /// The event data ABI-encodes a single `bytes` value, tightly packing the mint, value, gas
+    /// limit, contract creation flag and calldata of the deposit.
+    pub fn from_deposit_log(log: &Log, l1_block_hash: B256, log_index: u64) -> Result<Self> {
+        let topics = log.topics();
+        ensure!(topics.len() == 4, "expected 4 deposit event topics, got {}", topics.len());
+        ensure!(topics[0] == DEPOSIT_EVENT_TOPIC, "not a deposit event");
+        ensure!(topics[3] == DEPOSIT_EVENT_VERSION_0, "unsupported deposit version {}", topics[3]);
+
+        let data = log.data.data.as_ref();
+        ensure!(data.len() >= 64 && data.len() % 32 == 0, "invalid deposit event data length");
+        let offset = U256::from_be_slice(&data[..32]);
+        ensure!(offset == U256::from(32), "invalid deposit event data offset {offset}");
+        let len = U256::from_be_slice(&data[32..64]);
+        let len = usize::try_from(len).ok().filter(|len| 64 + len <= data.len());
+        let Some(len) = len else { bail!("deposit event data length exceeds the log data") };
+        ensure!(len > 32 + 32 + 8, "deposit event opaque data too short: {len} bytes");
+        let opaque = &data[64..64 + len];
+
+        let mint = U256::from_be_slice(&opaque[..32]);
+        let mint = u128::try_from(mint).wrap_err("deposit mint overflows u128")?;
+        let is_creation = match opaque[72] {
+            0 => false,
+            1 => true,
+            b => bail!("invalid deposit contract creation flag {b}"),
+        };
+        Ok(Self {
+            source_hash: DepositSourceDomain::User { l1_block_hash, log_index }.source_hash(),
+            from: Address::from_word(topics[1]),
+            to: if is_creation {
+                TxKind::Create
+            } else {
+                TxKind::Call(Address::from_word(topics[2]))
+            },
+            mint: Some(mint).filter(|mint| *mint != 0),
+            value: U256::from_be_slice(&opaque[32..64]),
+            gas_limit: u64::from_be_bytes(opaque[64..72].try_into().unwrap()),
+            is_system_transaction: false,
+            input: Bytes::copy_from_slice(&opaque[73..]),
+        })
+    }
+}
+
+/// Decodes the user deposits emitted by `deposit_contract` in the receipts of an L1 block, as
+/// EIP-2718 encoded transactions in log order.
+pub fn decode_deposits(
+    receipts: &[L1Receipt],
+    deposit_contract: Address,
+    l1_block_hash: B256,
+) -> Result<Vec<Bytes>> {
+    let mut deposits = Vec::new();
+    let mut log_index = 0;
+    for receipt in receipts {
+        for log in receipt.logs() {
+            let index = log_index;
+            log_index += 1;
+            if !receipt.status() ||
+                log.address != deposit_contract ||
+                log.topics().first() != Some(&DEPOSIT_EVENT_TOPIC)
+            {
+                continue;
+            }
+            let deposit =
+                TxDeposit::from_deposit_log(log, l1_block_hash, index).wrap_err_with(|| {
+                    format!("invalid deposit log {index} in L1 block {l1_block_hash}")
+                })?;
+            deposits.push(deposit.encoded_2718());
+        }
+    }
+    Ok(deposits)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn arb_deposit() -> impl Strategy<Value = TxDeposit> {
+        let to = prop::option::of(any::<[u8; 20]>().prop_map(Address::from));
+        (
+            (any::<[u8; 32]>(), any::<[u8; 20]>(), to, prop::option::of(1..=u128::MAX)),
+            (
+                any::<[u8; 32]>(),
+                any::<u64>(),
+                any::<bool>(),
+                prop::collection::vec(any::<u8>(), 0..128),
+            ),
+        )
+            .prop_map(
+                |((source_hash, from, to, mint), (value, gas_limit, is_system, input))| TxDeposit {
+                    source_hash: source_hash.into(),
+                    from: from.into(),
+                    to: to.map_or(TxKind::Create, TxKind::Call),
+                    mint,
+                    value: U256::from_be_bytes(value),
+                    gas_limit,
+                    is_system_transaction: is_system,
+                    input: input.into(),
+                },
+            )
+    }
+
+    proptest! {
+        /// A zero mint encodes like no mint, so only non-zero mints are generated.
+        #[test]
+        fn roundtrips_random_deposits(tx in arb_deposit()) {
+            let encoded = tx.encoded_2718();
+            let mut buf = &encoded[..];
+            let decoded = TxDeposit::decode_2718(&mut buf).unwrap();
+            prop_assert!(buf.is_empty());
+            prop_assert_eq!(decoded.encoded_2718(), encoded);
+            prop_assert_eq!(decoded, tx);
+        }
+    }
+}
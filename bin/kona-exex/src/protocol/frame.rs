@@ -0,0 +1,215 @@
+//! Channel frames, the unit of batcher data posted to L1.
+
+use alloy_primitives::{hex, Bytes, FixedBytes};
+use eyre::{bail, ensure, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The version byte prefixing the frames of a batcher transaction.
+pub const DERIVATION_VERSION_0: u8 = 0;
+
+/// Length of the fixed part of an encoded frame: channel id, frame number, data length and the
+/// `is_last` flag.
+pub const FRAME_OVERHEAD: usize = 16 + 2 + 4 + 1;
+
+/// Maximum length of the data of a single frame.
+pub const MAX_FRAME_LEN: usize = 1_000_000;
+
+/// A channel identifier.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChannelId(pub [u8; 16]);
+
+impl std::fmt::Debug for ChannelId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl std::fmt::Display for ChannelId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl Serialize for ChannelId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        FixedBytes(self.0).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ChannelId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        FixedBytes::<16>::deserialize(deserializer).map(|id| Self(id.0))
+    }
+}
+
+/// A frame of a channel.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Frame {
+    /// The channel the frame belongs to.
+    pub id: ChannelId,
+    /// The index of the frame within the channel.
+    pub number: u16,
+    /// The frame data.
+    pub data: Bytes,
+    /// Whether this is the last frame of the channel.
+    pub is_last: bool,
+}
+
+impl Frame {
+    /// Returns the encoded length of the frame.
+    pub fn size(&self) -> usize {
+        FRAME_OVERHEAD + self.data.len()
+    }
+
+    /// Encodes the frame.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.size());
+        out.extend_from_slice(&self.id.0);
+        out.extend_from_slice(&self.number.to_be_bytes());
+        out.extend_from_slice(&(self.data.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.data);
+        out.push(u8::from(self.is_last));
+        out
+    }
+
+    /// Decodes a frame from the start of `data`, returning it with the number of bytes read.
+    ///
+    /// The frame data is a slice of `data`, sharing its buffer rather than copying it.
+    pub fn decode(data: &Bytes) -> Result<(usize, Self)> {
+        ensure!(data.len() >= FRAME_OVERHEAD, "frame too short: {} bytes", data.len());
+
+        let id = ChannelId(data[..16].try_into().unwrap());
+        let number = u16::from_be_bytes(data[16..18].try_into().unwrap());
+        let len = u32::from_be_bytes(data[18..22].try_into().unwrap()) as usize;
+        ensure!(len <= MAX_FRAME_LEN, "frame data too large: {len} bytes");
+
+        let end = 22 + len;
+        ensure!(data.len() > end, "frame data truncated: expected {len} bytes");
+        let is_last = match data[end] {
+            0 => false,
+            1 => true,
+            b => bail!("invalid frame is_last byte {b}"),
+        };
+
+        Ok((end + 1, Self { id, number, data: data.slice(22..end), is_last }))
+    }
+
+    /// Parses the frames of a batcher transaction's data: a version byte followed by one or more
+    /// frames.
+    ///
+    /// Like op-node, the whole transaction is rejected if any frame is malformed. The frames share
+    /// the buffer of `data`.
+    pub fn parse_frames(data: &Bytes) -> Result<Vec<Self>> {
+        let Some(&version) = data.first() else { bail!("empty batcher data") };
+        ensure!(version == DERIVATION_VERSION_0, "unsupported derivation version {version}");
+
+        let mut frames = Vec::new();
+        let mut rest = data.slice(1..);
+        while !rest.is_empty() {
+            let (read, frame) = Self::decode(&rest)?;
+            frames.push(frame);
+            rest = rest.slice(read..);
+        }
+        ensure!(!frames.is_empty(), "batcher data contains no frames");
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn frame(number: u16, data: &[u8], is_last: bool) -> Frame {
+        Frame { id: ChannelId([7; 16]), number, data: Bytes::copy_from_slice(data), is_last }
+    }
+
+    #[test]
+    fn roundtrips_frames() {
+        let frames = [frame(0, b"hello", false), frame(1, b"", false), frame(2, b"world", true)];
+        let mut data = vec![DERIVATION_VERSION_0];
+        for frame in &frames {
+            let encoded = frame.encode();
+            assert_eq!(encoded.len(), frame.size());
+            data.extend(encoded);
+        }
+        assert_eq!(Frame::parse_frames(&data.into()).unwrap(), frames);
+    }
+
+    #[test]
+    fn rejects_invalid_frames() {
+        let encoded = frame(0, b"data", true).encode();
+
+        let short = Bytes::copy_from_slice(&encoded[..FRAME_OVERHEAD - 1]);
+        assert!(Frame::decode(&short).unwrap_err().to_string().contains("too short"));
+
+        let truncated = Bytes::copy_from_slice(&encoded[..encoded.len() - 1]);
+        assert!(Frame::decode(&truncated).unwrap_err().to_string().contains("truncated"));
+
+        let mut bad_flag = encoded.clone();
+        *bad_flag.last_mut().unwrap() = 2;
+        assert!(Frame::decode(&bad_flag.into()).unwrap_err().to_string().contains("is_last"));
+
+        let mut too_large = encoded;
+        too_large[18..22].copy_from_slice(&(MAX_FRAME_LEN as u32 + 1).to_be_bytes());
+        assert!(Frame::decode(&too_large.into()).unwrap_err().to_string().contains("too large"));
+    }
+
+    #[test]
+    fn rejects_invalid_batcher_data() {
+        let encoded = frame(0, b"data", true).encode();
+        assert!(Frame::parse_frames(&Bytes::new()).is_err());
+        assert!(Frame::parse_frames(&Bytes::from_static(&[DERIVATION_VERSION_0])).is_err());
+
+        let mut data = vec![1];
+        data.extend_from_slice(&encoded);
+        assert!(Frame::parse_frames(&data.into()).unwrap_err().to_string().contains("version"));
+
+        // A trailing partial frame rejects the whole transaction.
+        let mut data = vec![DERIVATION_VERSION_0];
+        data.extend_from_slice(&encoded);
+        data.extend_from_slice(&encoded[..10]);
+        assert!(Frame::parse_frames(&data.into()).is_err());
+    }
+
+    fn arb_frame() -> impl Strategy<Value = Frame> {
+        (any::<[u8; 16]>(), any::<u16>(), prop::collection::vec(any::<u8>(), 0..256), any::<bool>())
+            .prop_map(|(id, number, data, is_last)| Frame {
+                id: ChannelId(id),
+                number,
+                data: data.into(),
+                is_last,
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrips_random_frames(frame in arb_frame()) {
+            let encoded = frame.encode();
+            prop_assert_eq!(encoded.len(), frame.size());
+            let (read, decoded) = Frame::decode(&encoded.clone().into()).unwrap();
+            prop_assert_eq!(read, encoded.len());
+            prop_assert_eq!(decoded.encode(), encoded);
+            prop_assert_eq!(decoded, frame);
+        }
+
+        #[test]
+        fn roundtrips_random_batcher_data(frames in prop::collection::vec(arb_frame(), 1..8)) {
+            let mut data = vec![DERIVATION_VERSION_0];
+            for frame in &frames {
+                data.extend(frame.encode());
+            }
+            prop_assert_eq!(Frame::parse_frames(&data.into()).unwrap(), frames);
+        }
+
+        /// Channel ids serialize as 0x-prefixed hex, like op-node's `ChannelID`.
+        #[test]
+        fn roundtrips_channel_ids_through_serde(id in any::<[u8; 16]>()) {
+            let id = ChannelId(id);
+            let json = serde_json::to_string(&id).unwrap();
+            prop_assert_eq!(&json, &format!("\"0x{id}\""));
+            prop_assert_eq!(serde_json::from_str::<ChannelId>(&json).unwrap(), id);
+        }
+    }
+}
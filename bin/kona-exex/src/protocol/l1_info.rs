@@ -0,0 +1,526 @@
+//! The L1 info deposit transaction, the first transaction of every L2 block.
+//!
+//! Its calldata has one encoding per protocol version: Bedrock ABI-encodes the values, while
+//! Ecotone and later hardforks tightly pack them. The version is selected from the hardforks
+//! active at the L2 block timestamp, with each hardfork's activation block still using the
+//! encoding of the previous version.
+
+use alloy_consensus::Header;
+use alloy_primitives::{Address, Bytes, TxKind, B256, U256};
+use eyre::{bail, Result};
+
+use crate::{
+    config::{RollupConfig, SystemConfig},
+    protocol::{
+        deposit::{DepositSourceDomain, TxDeposit, L1_BLOCK_ADDRESS, L1_INFO_DEPOSITOR_ADDRESS},
+        BlockId,
+    },
+};
+
+/// Selector of `setL1BlockValues(uint64,uint64,uint256,bytes32,uint64,bytes32,uint256,uint256)`.
+pub const L1_INFO_BEDROCK_SELECTOR: [u8; 4] = [0x01, 0x5d, 0x8e, 0xb9];
+/// Selector of `setL1BlockValuesEcotone()`.
+pub const L1_INFO_ECOTONE_SELECTOR: [u8; 4] = [0x44, 0x0a, 0x5e, 0x20];
+/// Selector of `setL1BlockValuesIsthmus()`.
+pub const L1_INFO_ISTHMUS_SELECTOR: [u8; 4] = [0x09, 0x89, 0x99, 0xbe];
+/// Selector of `setL1BlockValuesInterop()`.
+pub const L1_INFO_INTEROP_SELECTOR: [u8; 4] = [0x76, 0x0e, 0xe0, 0x4d];
+
+/// Length of the Bedrock L1 info calldata.
+pub const L1_INFO_BEDROCK_LEN: usize = 4 + 32 * 8;
+/// Length of the Ecotone L1 info calldata.
+pub const L1_INFO_ECOTONE_LEN: usize = 4 + 4 + 4 + 8 + 8 + 8 + 32 + 32 + 32 + 32;
+/// Length of the Isthmus and Interop L1 info calldata.
+pub const L1_INFO_ISTHMUS_LEN: usize = L1_INFO_ECOTONE_LEN + 4 + 8;
+
+/// Gas limit of the L1 info deposit before Regolith.
+pub const L1_INFO_GAS_LIMIT_BEDROCK: u64 = 150_000_000;
+/// Gas limit of the L1 info deposit once Regolith is active.
+pub const L1_INFO_GAS_LIMIT_REGOLITH: u64 = 1_000_000;
+
+/// The L1 info values of the Bedrock encoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct L1BlockInfoBedrock {
+    /// The L1 origin block number.
+    pub number: u64,
+    /// The L1 origin block timestamp.
+    pub time: u64,
+    /// The L1 origin base fee.
+    pub base_fee: u64,
+    /// The L1 origin block hash.
+    pub block_hash: B256,
+    /// The L2 block's sequence number within its epoch.
+    pub sequence_number: u64,
+    /// The batcher address from the system config.
+    pub batcher_address: Address,
+    /// The L1 fee overhead from the system config.
+    pub l1_fee_overhead: U256,
+    /// The L1 fee scalar from the system config.
+    pub l1_fee_scalar: U256,
+}
+
+/// The L1 info values of the Ecotone encoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct L1BlockInfoEcotone {
+    /// The L1 origin block number.
+    pub number: u64,
+    /// The L1 origin block timestamp.
+    pub time: u64,
+    /// The L1 origin base fee.
+    pub base_fee: u64,
+    /// The L1 origin block hash.
+    pub block_hash: B256,
+    /// The L2 block's sequence number within its epoch.
+    pub sequence_number: u64,
+    /// The batcher address from the system config.
+    pub batcher_address: Address,
+    /// The L1 origin blob base fee.
+    pub blob_base_fee: u128,
+    /// The blob base fee scalar from the system config.
+    pub blob_base_fee_scalar: u32,
+    /// The base fee scalar from the system config.
+    pub base_fee_scalar: u32,
+}
+
+/// The L1 info values of the Isthmus encoding, which extends Ecotone with the operator fee.
+///
+/// Interop uses the same layout under its own selector.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct L1BlockInfoIsthmus {
+    /// The Ecotone values.
+    pub ecotone: L1BlockInfoEcotone,
+    /// The operator fee scalar from the system config.
+    pub operator_fee_scalar: u32,
+    /// The operator fee constant from the system config.
+    pub operator_fee_constant: u64,
+}
+
+/// The L1 info deposit transaction values, in the encoding of the active protocol version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum L1BlockInfoTx {
+    /// The Bedrock encoding, used until the first block after Ecotone activation.
+    Bedrock(L1BlockInfoBedrock),
+    /// The Ecotone encoding, used until the first block after Isthmus activation.
+    Ecotone(L1BlockInfoEcotone),
+    /// The Isthmus encoding, used until the first block after Interop activation.
+    Isthmus(L1BlockInfoIsthmus),
+    /// The Interop encoding.
+    Interop(L1BlockInfoIsthmus),
+}
+
+impl L1BlockInfoTx {
+    /// Builds the L1 info values for an L2 block at `l2_block_time` with the given L1 origin.
+    pub fn try_new(
+        config: &RollupConfig,
+        system_config: &SystemConfig,
+        sequence_number: u64,
+        l1_header: &Header,
+        l2_block_time: u64,
+    ) -> Result<Self> {
+        let Ok(base_fee) = u64::try_from(l1_header.base_fee_per_gas.unwrap_or_default()) else {
+            bail!("L1 base fee of block {} overflows u64", l1_header.number);
+        };
+        let block_hash = l1_header.hash_slow();
+
+        if !config.is_ecotone_active(l2_block_time) ||
+            config.is_ecotone_activation_block(l2_block_time)
+        {
+            return Ok(Self::Bedrock(L1BlockInfoBedrock {
+                number: l1_header.number,
+                time: l1_header.timestamp,
+                base_fee,
+                block_hash,
+                sequence_number,
+                batcher_address: system_config.batcher_address,
+                l1_fee_overhead: system_config.overhead,
+                l1_fee_scalar: system_config.scalar,
+            }));
+        }
+
+        let (base_fee_scalar, blob_base_fee_scalar) = system_config.ecotone_scalars()?;
+        let ecotone = L1BlockInfoEcotone {
+            number: l1_header.number,
+            time: l1_header.timestamp,
+            base_fee,
+            block_hash,
+            sequence_number,
+            batcher_address: system_config.batcher_address,
+            // Pre-Dencun L1 blocks have no blob base fee, which is then set to 1.
+            blob_base_fee: l1_header.blob_fee().unwrap_or(1),
+            blob_base_fee_scalar,
+            base_fee_scalar,
+        };
+        if !config.is_isthmus_active(l2_block_time) ||
+            config.is_isthmus_activation_block(l2_block_time)
+        {
+            return Ok(Self::Ecotone(ecotone));
+        }
+
+        let isthmus = L1BlockInfoIsthmus {
+            ecotone,
+            operator_fee_scalar: system_config.operator_fee_scalar.unwrap_or_default(),
+            operator_fee_constant: system_config.operator_fee_constant.unwrap_or_default(),
+        };
+        if config.is_interop_active(l2_block_time) &&
+            !config.is_interop_activation_block(l2_block_time)
+        {
+            Ok(Self::Interop(isthmus))
+        } else {
+            Ok(Self::Isthmus(isthmus))
+        }
+    }
+
+    /// Decodes the L1 info values from the calldata of an L1 info deposit transaction.
+    pub fn decode_calldata(data: &[u8]) -> Result<Self> {
+        let Some(selector) = data.get(..4) else { bail!("L1 info calldata too short") };
+        match selector {
+            s if s == L1_INFO_BEDROCK_SELECTOR => {
+                L1BlockInfoBedrock::decode_calldata(data).map(Self::Bedrock)
+            }
+            s if s == L1_INFO_ECOTONE_SELECTOR => {
+                L1BlockInfoEcotone::decode_calldata(data).map(Self::Ecotone)
+            }
+            s if s == L1_INFO_ISTHMUS_SELECTOR => {
+                L1BlockInfoIsthmus::decode_calldata(data).map(Self::Isthmus)
+            }
+            s if s == L1_INFO_INTEROP_SELECTOR => {
+                L1BlockInfoIsthmus::decode_calldata(data).map(Self::Interop)
+            }
+            s => bail!("unknown L1 info selector 0x{}", alloy_primitives::hex::encode(s)),
+        }
+    }
+
+    /// Encodes the L1 info values into the calldata of the L1 info deposit transaction.
+    pub fn encode_calldata(&self) -> Bytes {
+        match self {
+            Self::Bedrock(info) => info.encode_calldata(),
+            Self::Ecotone(info) => {
+                let mut out = Vec::with_capacity(L1_INFO_ECOTONE_LEN);
+                out.extend_from_slice(&L1_INFO_ECOTONE_SELECTOR);
+                info.encode_packed(&mut out);
+                out.into()
+            }
+            Self::Isthmus(info) => info.encode_calldata(L1_INFO_ISTHMUS_SELECTOR),
+            Self::Interop(info) => info.encode_calldata(L1_INFO_INTEROP_SELECTOR),
+        }
+    }
+
+    /// Builds the L1 info deposit transaction for an L2 block at `l2_block_time`.
+    pub fn to_deposit_tx(&self, config: &RollupConfig, l2_block_time: u64) -> TxDeposit {
+        let regolith = config.is_regolith_active(l2_block_time);
+        TxDeposit {
+            source_hash: DepositSourceDomain::L1Info {
+                l1_block_hash: self.block_hash(),
+                sequence_number: self.sequence_number(),
+            }
+            .source_hash(),
+            from: L1_INFO_DEPOSITOR_ADDRESS,
+            to: TxKind::Call(L1_BLOCK_ADDRESS),
+            mint: None,
+            value: U256::ZERO,
+            gas_limit: if regolith {
+                L1_INFO_GAS_LIMIT_REGOLITH
+            } else {
+                L1_INFO_GAS_LIMIT_BEDROCK
+            },
+            is_system_transaction: !regolith,
+            input: self.encode_calldata(),
+        }
+    }
+
+    /// Returns the L1 origin block id.
+    pub const fn id(&self) -> BlockId {
+        match self {
+            Self::Bedrock(info) => BlockId::new(info.block_hash, info.number),
+            Self::Ecotone(info) => BlockId::new(info.block_hash, info.number),
+            Self::Isthmus(info) | Self::Interop(info) => {
+                BlockId::new(info.ecotone.block_hash, info.ecotone.number)
+            }
+        }
+    }
+
+    /// Returns the L1 origin block hash.
+    pub const fn block_hash(&self) -> B256 {
+        self.id().hash
+    }
+
+    /// Returns the sequence number of the L2 block within its epoch.
+    pub const fn sequence_number(&self) -> u64 {
+        match self {
+            Self::Bedrock(info) => info.sequence_number,
+            Self::Ecotone(info) => info.sequence_number,
+            Self::Isthmus(info) | Self::Interop(info) => info.ecotone.sequence_number,
+        }
+    }
+
+    /// Returns the batcher address.
+    pub const fn batcher_address(&self) -> Address {
+        match self {
+            Self::Bedrock(info) => info.batcher_address,
+            Self::Ecotone(info) => info.batcher_address,
+            Self::Isthmus(info) | Self::Interop(info) => info.ecotone.batcher_address,
+        }
+    }
+
+    /// Returns the system config these values were built from, given the L2 block gas limit
+    /// which the L1 info transaction does not carry.
+    ///
+    /// From Ecotone onwards the scalars are returned in the version 1 `scalar` word.
+    pub fn system_config(&self, gas_limit: u64) -> SystemConfig {
+        let ecotone_scalar = |info: &L1BlockInfoEcotone| {
+            let mut scalar = [0u8; 32];
+            scalar[0] = 1;
+            scalar[24..28].copy_from_slice(&info.blob_base_fee_scalar.to_be_bytes());
+            scalar[28..].copy_from_slice(&info.base_fee_scalar.to_be_bytes());
+            U256::from_be_bytes(scalar)
+        };
+        match self {
+            Self::Bedrock(info) => SystemConfig {
+                batcher_address: info.batcher_address,
+                overhead: info.l1_fee_overhead,
+                scalar: info.l1_fee_scalar,
+                gas_limit,
+                ..Default::default()
+            },
+            Self::Ecotone(info) => SystemConfig {
+                batcher_address: info.batcher_address,
+                scalar: ecotone_scalar(info),
+                gas_limit,
+                ..Default::default()
+            },
+            Self::Isthmus(info) | Self::Interop(info) => SystemConfig {
+                batcher_address: info.ecotone.batcher_address,
+                scalar: ecotone_scalar(&info.ecotone),
+                gas_limit,
+                operator_fee_scalar: Some(info.operator_fee_scalar),
+                operator_fee_constant: Some(info.operator_fee_constant),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl L1BlockInfoBedrock {
+    fn encode_calldata(&self) -> Bytes {
+        let mut out = Vec::with_capacity(L1_INFO_BEDROCK_LEN);
+        out.extend_from_slice(&L1_INFO_BEDROCK_SELECTOR);
+        out.extend_from_slice(&U256::from(self.number).to_be_bytes::<32>());
+        out.extend_from_slice(&U256::from(self.time).to_be_bytes::<32>());
+        out.extend_from_slice(&U256::from(self.base_fee).to_be_bytes::<32>());
+        out.extend_from_slice(self.block_hash.as_slice());
+        out.extend_from_slice(&U256::from(self.sequence_number).to_be_bytes::<32>());
+        out.extend_from_slice(self.batcher_address.into_word().as_slice());
+        out.extend_from_slice(&self.l1_fee_overhead.to_be_bytes::<32>());
+        out.extend_from_slice(&self.l1_fee_scalar.to_be_bytes::<32>());
+        out.into()
+    }
+
+    fn decode_calldata(data: &[u8]) -> Result<Self> {
+        if data.len() != L1_INFO_BEDROCK_LEN {
+            bail!("invalid Bedrock L1 info length {}", data.len());
+        }
+        let word = |i: usize| &data[4 + i * 32..4 + (i + 1) * 32];
+        let uint64 = |i: usize| -> Result<u64> {
+            let value = U256::from_be_slice(word(i));
+            value.try_into().map_err(|_| eyre::eyre!("L1 info word {i} overflows u64"))
+        };
+        Ok(Self {
+            number: uint64(0)?,
+            time: uint64(1)?,
+            base_fee: uint64(2)?,
+            block_hash: B256::from_slice(word(3)),
+            sequence_number: uint64(4)?,
+            batcher_address: Address::from_word(B256::from_slice(word(5))),
+            l1_fee_overhead: U256::from_be_slice(word(6)),
+            l1_fee_scalar: U256::from_be_slice(word(7)),
+        })
+    }
+}
+
+impl L1BlockInfoEcotone {
+    fn encode_packed(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.base_fee_scalar.to_be_bytes());
+        out.extend_from_slice(&self.blob_base_fee_scalar.to_be_bytes());
+        out.extend_from_slice(&self.sequence_number.to_be_bytes());
+        out.extend_from_slice(&self.time.to_be_bytes());
+        out.extend_from_slice(&self.number.to_be_bytes());
+        out.extend_from_slice(&U256::from(self.base_fee).to_be_bytes::<32>());
+        out.extend_from_slice(&U256::from(self.blob_base_fee).to_be_bytes::<32>());
+        out.extend_from_slice(self.block_hash.as_slice());
+        out.extend_from_slice(self.batcher_address.into_word().as_slice());
+    }
+
+    fn decode_calldata(data: &[u8]) -> Result<Self> {
+        if data.len() != L1_INFO_ECOTONE_LEN {
+            bail!("invalid Ecotone L1 info length {}", data.len());
+        }
+        Self::decode_packed(&data[4..])
+    }
+
+    fn decode_packed(data: &[u8]) -> Result<Self> {
+        let u32_at = |at: usize| u32::from_be_bytes(data[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_be_bytes(data[at..at + 8].try_into().unwrap());
+        let Ok(base_fee) = U256::from_be_slice(&data[32..64]).try_into() else {
+            bail!("L1 info base fee overflows u64");
+        };
+        let Ok(blob_base_fee) = U256::from_be_slice(&data[64..96]).try_into() else {
+            bail!("L1 info blob base fee overflows u128");
+        };
+        Ok(Self {
+            base_fee_scalar: u32_at(0),
+            blob_base_fee_scalar: u32_at(4),
+            sequence_number: u64_at(8),
+            time: u64_at(16),
+            number: u64_at(24),
+            base_fee,
+            blob_base_fee,
+            block_hash: B256::from_slice(&data[96..128]),
+            batcher_address: Address::from_word(B256::from_slice(&data[128..160])),
+        })
+    }
+}
+
+impl L1BlockInfoIsthmus {
+    fn encode_calldata(&self, selector: [u8; 4]) -> Bytes {
+        let mut out = Vec::with_capacity(L1_INFO_ISTHMUS_LEN);
+        out.extend_from_slice(&selector);
+        self.ecotone.encode_packed(&mut out);
+        out.extend_from_slice(&self.operator_fee_scalar.to_be_bytes());
+        out.extend_from_slice(&self.operator_fee_constant.to_be_bytes());
+        out.into()
+    }
+
+    fn decode_calldata(data: &[u8]) -> Result<Self> {
+        if data.len() != L1_INFO_ISTHMUS_LEN {
+            bail!("invalid Isthmus L1 info length {}", data.len());
+        }
+        let ecotone = L1BlockInfoEcotone::decode_packed(&data[4..L1_INFO_ECOTONE_LEN])?;
+        let operator = &data[L1_INFO_ECOTONE_LEN..];
+        Ok(Self {
+            ecotone,
+            operator_fee_scalar: u32::from_be_bytes(operator[..4].try_into().unwrap()),
+            operator_fee_constant: u64::from_be_bytes(operator[4..].try_into().unwrap()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, b256, keccak256};
+
+    fn config() -> RollupConfig {
+        let mut config: RollupConfig = RollupConfig::from_registry(10).unwrap();
+        config.block_time = 2;
+        config.regolith_time = Some(0);
+        config.ecotone_time = Some(100);
+        config.isthmus_time = Some(200);
+        config.interop_time = Some(300);
+        config
+    }
+
+    fn system_config() -> SystemConfig {
+        SystemConfig {
+            batcher_address: address!("6887246668a3b87f54deb3b94ba47a6f63f32985"),
+            overhead: U256::from(188),
+            scalar: U256::from_be_bytes(
+                b256!("010000000000000000000000000000000000000000000000000c5fc500000558").0,
+            ),
+            gas_limit: 30_000_000,
+            operator_fee_scalar: Some(7),
+            operator_fee_constant: Some(1_000),
+            ..Default::default()
+        }
+    }
+
+    fn l1_header() -> Header {
+        Header {
+            number: 20_000_000,
+            timestamp: 1_717_000_000,
+            base_fee_per_gas: Some(7_000_000_000),
+            excess_blob_gas: Some(4_000_000),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn selectors_match_signatures() {
+        let selector = |sig: &str| -> [u8; 4] { keccak256(sig)[..4].try_into().unwrap() };
+        assert_eq!(
+            selector(
+                "setL1BlockValues(uint64,uint64,uint256,bytes32,uint64,bytes32,uint256,uint256)"
+            ),
+            L1_INFO_BEDROCK_SELECTOR
+        );
+        assert_eq!(selector("setL1BlockValuesEcotone()"), L1_INFO_ECOTONE_SELECTOR);
+        assert_eq!(selector("setL1BlockValuesIsthmus()"), L1_INFO_ISTHMUS_SELECTOR);
+        assert_eq!(selector("setL1BlockValuesInterop()"), L1_INFO_INTEROP_SELECTOR);
+    }
+
+    #[test]
+    fn selects_version_from_active_hardforks() {
+        let (config, system_config, header) = (config(), system_config(), l1_header());
+        let version = |time| match L1BlockInfoTx::try_new(&config, &system_config, 0, &header, time)
+            .unwrap()
+        {
+            L1BlockInfoTx::Bedrock(_) => "bedrock",
+            L1BlockInfoTx::Ecotone(_) => "ecotone",
+            L1BlockInfoTx::Isthmus(_) => "isthmus",
+            L1BlockInfoTx::Interop(_) => "interop",
+        };
+
+        assert_eq!(version(98), "bedrock");
+        // Each activation block still uses the previous encoding.
+        assert_eq!(version(100), "bedrock");
+        assert_eq!(version(102), "ecotone");
+        assert_eq!(version(200), "ecotone");
+        assert_eq!(version(202), "isthmus");
+        assert_eq!(version(300), "isthmus");
+        assert_eq!(version(302), "interop");
+    }
+
+    #[test]
+    fn roundtrip_all_versions() {
+        let (config, system_config, header) = (config(), system_config(), l1_header());
+        for (time, len) in [
+            (50, L1_INFO_BEDROCK_LEN),
+            (150, L1_INFO_ECOTONE_LEN),
+            (250, L1_INFO_ISTHMUS_LEN),
+            (350, L1_INFO_ISTHMUS_LEN),
+        ] {
+            let info = L1BlockInfoTx::try_new(&config, &system_config, 3, &header, time).unwrap();
+            let calldata = info.encode_calldata();
+            assert_eq!(calldata.len(), len);
+            assert_eq!(L1BlockInfoTx::decode_calldata(&calldata).unwrap(), info);
+
+            let deposit = info.to_deposit_tx(&config, time);
+            let encoded = deposit.encoded_2718();
+            let decoded = TxDeposit::decode_2718(&mut encoded.as_ref()).unwrap();
+            assert_eq!(decoded, deposit);
+            assert_eq!(L1BlockInfoTx::decode_calldata(&decoded.input).unwrap(), info);
+        }
+    }
+
+    #[test]
+    fn ecotone_values() {
+        let (config, system_config, header) = (config(), system_config(), l1_header());
+        let L1BlockInfoTx::Isthmus(info) =
+            L1BlockInfoTx::try_new(&config, &system_config, 5, &header, 250).unwrap()
+        else {
+            panic!("expected Isthmus L1 info");
+        };
+        assert_eq!(info.ecotone.base_fee_scalar, 0x558);
+        assert_eq!(info.ecotone.blob_base_fee_scalar, 0xc5fc5);
+        assert_eq!(info.ecotone.blob_base_fee, header.blob_fee().unwrap());
+        assert_eq!(info.ecotone.sequence_number, 5);
+        assert_eq!(info.operator_fee_scalar, 7);
+        assert_eq!(info.operator_fee_constant, 1_000);
+    }
+
+    #[test]
+    fn rejects_malformed_calldata() {
+        assert!(L1BlockInfoTx::decode_calldata(&[]).is_err());
+        assert!(L1BlockInfoTx::decode_calldata(&[0xde, 0xad, 0xbe, 0xef]).is_err());
+        assert!(L1BlockInfoTx::decode_calldata(&L1_INFO_ECOTONE_SELECTOR).is_err());
+    }
+}
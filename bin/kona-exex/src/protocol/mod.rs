@@ -0,0 +1,19 @@
+//! Core OP Stack protocol types shared across the derivation pipeline.
+
+mod block;
+pub use block::{BlockId, BlockInfo, L2BlockInfo};
+
+pub mod batch;
+pub use batch::{Batch, RawSpanBatch, SingleBatch, SpanBatch};
+
+pub mod channel;
+pub use channel::{BatchReader, Channel, FrameError};
+
+pub mod deposit;
+pub use deposit::{decode_deposits, DepositSourceDomain, TxDeposit};
+
+pub mod frame;
+pub use frame::{ChannelId, Frame};
+
+pub mod l1_info;
+pub use l1_info::L1BlockInfoTx;
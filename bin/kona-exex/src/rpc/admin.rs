@@ -0,0 +1,100 @@
+//! Hera's implementation of the `admin_*` namespace: sequencer, log level and reorg
+//! control.
+
+use alloy_primitives::B256;
+use async_trait::async_trait;
+use jsonrpsee::{
+    core::RpcResult,
+    types::{
+        error::{INTERNAL_ERROR_CODE, INVALID_PARAMS_CODE},
+        ErrorObjectOwned,
+    },
+};
+
+use crate::{
+    exex::{PendingReorg, ReorgGuard},
+    logging::LogFilterHandle,
+    rpc::{AdminApiServer, AdminLogApiServer, AdminReorgApiServer},
+    sequencer::SequencerHandle,
+};
+
+/// Serves the `admin_*` namespace, letting op-conductor start and stop the sequencer.
+#[derive(Debug)]
+pub struct AdminRpc {
+    sequencer: SequencerHandle,
+}
+
+impl AdminRpc {
+    /// Creates the RPC handler controlling `sequencer`.
+    pub const fn new(sequencer: SequencerHandle) -> Self {
+        Self { sequencer }
+    }
+}
+
+fn internal_error(err: eyre::Report) -> ErrorObjectOwned {
+    ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, err.to_string(), None::<()>)
+}
+
+#[async_trait]
+impl AdminApiServer for AdminRpc {
+    async fn start_sequencer(&self, head: B256) -> RpcResult<()> {
+        self.sequencer.start(head).map_err(internal_error)
+    }
+
+    async fn stop_sequencer(&self) -> RpcResult<B256> {
+        self.sequencer.stop().map_err(internal_error)
+    }
+
+    async fn sequencer_active(&self) -> RpcResult<bool> {
+        Ok(self.sequencer.is_active())
+    }
+}
+
+/// Serves `admin_setLogLevel`, changing the log filter of the running node.
+#[derive(Debug)]
+pub struct AdminLogRpc {
+    filter: LogFilterHandle,
+}
+
+impl AdminLogRpc {
+    /// Creates the RPC handler changing the log filter through `filter`.
+    pub const fn new(filter: LogFilterHandle) -> Self {
+        Self { filter }
+    }
+}
+
+#[async_trait]
+impl AdminLogApiServer for AdminLogRpc {
+    async fn set_log_level(&self, target: String, level: String) -> RpcResult<()> {
+        self.filter.set_level(&target, &level).map_err(|err| {
+            ErrorObjectOwned::owned(INVALID_PARAMS_CODE, format!("{err:#}"), None::<()>)
+        })
+    }
+}
+
+/// Serves `admin_pendingReorg` and `admin_confirmReorg`, releasing derivation held back on a deep
+/// L1 reorg.
+#[derive(Debug)]
+pub struct AdminReorgRpc {
+    guard: ReorgGuard,
+}
+
+impl AdminReorgRpc {
+    /// Creates the RPC handler confirming the reorgs held back by `guard`.
+    pub const fn new(guard: ReorgGuard) -> Self {
+        Self { guard }
+    }
+}
+
+#[async_trait]
+impl AdminReorgApiServer for AdminReorgRpc {
+    async fn pending_reorg(&self) -> RpcResult<Option<PendingReorg>> {
+        Ok(self.guard.pending())
+    }
+
+    async fn confirm_reorg(&self) -> RpcResult<PendingReorg> {
+        self.guard.confirm().map_err(|err| {
+            ErrorObjectOwned::owned(INVALID_PARAMS_CODE, err.to_string(), None::<()>)
+        })
+    }
+}
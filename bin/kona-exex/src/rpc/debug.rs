@@ -0,0 +1,51 @@
+//! Hera's implementation of the `hera_*` debug namespace.
+
+use async_trait::async_trait;
+use jsonrpsee::core::RpcResult;
+use tokio::sync::watch;
+
+use crate::{
+    derive::{ChannelBankSnapshot, FrameSummary, PipelineStatus},
+    protocol::ChannelId,
+    rpc::HeraDebugApiServer,
+};
+
+/// Serves the `hera_*` debug namespace from the live state of the pipeline and its channel bank.
+#[derive(Debug)]
+pub struct HeraDebugRpc {
+    channel_bank: watch::Receiver<ChannelBankSnapshot>,
+    pipeline: watch::Receiver<PipelineStatus>,
+}
+
+impl HeraDebugRpc {
+    /// Creates the RPC handler, following the channel bank through `channel_bank` and the
+    /// pipeline through `pipeline`.
+    pub const fn new(
+        channel_bank: watch::Receiver<ChannelBankSnapshot>,
+        pipeline: watch::Receiver<PipelineStatus>,
+    ) -> Self {
+        Self { channel_bank, pipeline }
+    }
+}
+
+#[async_trait]
+impl HeraDebugApiServer for HeraDebugRpc {
+    async fn channel_bank(&self) -> RpcResult<ChannelBankSnapshot> {
+        Ok(self.channel_bank.borrow().clone())
+    }
+
+    async fn frames(&self, channel: Option<ChannelId>) -> RpcResult<Vec<FrameSummary>> {
+        Ok(self
+            .channel_bank
+            .borrow()
+            .channels
+            .iter()
+            .filter(|summary| channel.map_or(true, |id| id == summary.id))
+            .flat_map(|summary| summary.frames.iter().cloned())
+            .collect())
+    }
+
+    async fn pipeline_status(&self) -> RpcResult<PipelineStatus> {
+        Ok(self.pipeline.borrow().clone())
+    }
+}
@@ -0,0 +1,42 @@
+//! Hera's implementation of `hera_feeParams` and `hera_gasPriceOracleConfig`.
+
+use std::sync::Arc;
+
+use alloy_primitives::U64;
+use async_trait::async_trait;
+use jsonrpsee::core::RpcResult;
+
+use crate::{
+    config::RollupConfig,
+    fees::{FeeParams, FeeTracker, GasPriceOracleConfig},
+    rpc::HeraFeeApiServer,
+};
+
+/// Serves `hera_feeParams` and `hera_gasPriceOracleConfig` from the fee parameters recorded by
+/// the driver.
+#[derive(Debug)]
+pub struct HeraFeeRpc {
+    fees: FeeTracker,
+    config: Arc<RollupConfig>,
+}
+
+impl HeraFeeRpc {
+    /// Creates the RPC handler, serving the history recorded in `fees` for the chain of `config`.
+    pub const fn new(fees: FeeTracker, config: Arc<RollupConfig>) -> Self {
+        Self { fees, config }
+    }
+}
+
+#[async_trait]
+impl HeraFeeApiServer for HeraFeeRpc {
+    async fn fee_params(&self, block: U64) -> RpcResult<Option<FeeParams>> {
+        Ok(self.fees.at(block.to()))
+    }
+
+    async fn gas_price_oracle_config(&self, block: U64) -> RpcResult<Option<GasPriceOracleConfig>> {
+        let number = block.to();
+        Ok(self.fees.at(number).map(|params| {
+            GasPriceOracleConfig::new(&self.config, self.config.l2_block_timestamp(number), params)
+        }))
+    }
+}
@@ -0,0 +1,79 @@
+//! Hera's `eth_sendRawTransaction` proxy, forwarding user transactions to the sequencer.
+
+use alloy_primitives::{Bytes, B256};
+use async_trait::async_trait;
+use eyre::{Result, WrapErr};
+use jsonrpsee::{
+    core::{client::Error as ClientError, RpcResult},
+    http_client::{HttpClient, HttpClientBuilder},
+    types::{error::INTERNAL_ERROR_CODE, ErrorObjectOwned},
+};
+use metrics::counter;
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::{
+    rpc::{EthTxApiClient, EthTxApiServer},
+    tenant,
+};
+
+/// Serves `eth_sendRawTransaction` by forwarding transactions to the sequencer's execution
+/// client, and replicating them to backup sequencers in the background.
+///
+/// The sequencer's answer is returned to the caller as is, including its errors. Backups are
+/// best effort: their failures are only logged.
+#[derive(Debug)]
+pub struct TxForwarder {
+    sequencer: HttpClient,
+    backups: Vec<(Url, HttpClient)>,
+}
+
+impl TxForwarder {
+    /// Creates a proxy forwarding to the execution client RPC at `sequencer` and replicating to
+    /// the `backups`.
+    pub fn new(sequencer: &Url, backups: &[Url]) -> Result<Self> {
+        let client = |url: &Url| {
+            HttpClientBuilder::default()
+                .build(url.as_str())
+                .wrap_err_with(|| format!("invalid transaction forwarding URL {url}"))
+        };
+        Ok(Self {
+            sequencer: client(sequencer)?,
+            backups: backups
+                .iter()
+                .map(|url| Ok((url.clone(), client(url)?)))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    fn replicate(&self, tx: &Bytes) {
+        for (url, client) in &self.backups {
+            let (url, client, tx) = (url.clone(), client.clone(), tx.clone());
+            tenant::spawn(async move {
+                if let Err(err) = client.send_raw_transaction(tx).await {
+                    debug!(target: "hera::rpc", %url, %err, "Backup sequencer rejected transaction");
+                    counter!("hera_tx_forward_backup_errors_total").increment(1);
+                }
+            });
+        }
+    }
+}
+
+#[async_trait]
+impl EthTxApiServer for TxForwarder {
+    async fn send_raw_transaction(&self, tx: Bytes) -> RpcResult<B256> {
+        counter!("hera_tx_forward_requests_total").increment(1);
+        self.replicate(&tx);
+        self.sequencer.send_raw_transaction(tx).await.map_err(|err| {
+            counter!("hera_tx_forward_errors_total").increment(1);
+            match err {
+                // Forward the sequencer's own rejection, e.g. "nonce too low", untouched.
+                ClientError::Call(err) => err,
+                err => {
+                    warn!(target: "hera::rpc", %err, "Failed to forward transaction to sequencer");
+                    ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, err.to_string(), None::<()>)
+                }
+            }
+        })
+    }
+}
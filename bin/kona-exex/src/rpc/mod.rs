@@ -0,0 +1,225 @@
+//! JSON-RPC namespaces served and consumed by Hera.
+
+use alloy_primitives::{Bytes, B256, U64};
+use jsonrpsee::{core::RpcResult, proc_macros::rpc};
+
+use crate::{
+    derive::{ChannelBankSnapshot, FrameSummary, L2AttributesWithParent, PipelineStatus},
+    exex::PendingReorg,
+    fees::{FeeParams, GasPriceOracleConfig},
+    output::WithdrawalProof,
+    protocol::ChannelId,
+    version::BuildInfo,
+};
+
+mod admin;
+pub use admin::{AdminLogRpc, AdminReorgRpc, AdminRpc};
+
+mod debug;
+pub use debug::HeraDebugRpc;
+
+mod fees;
+pub use fees::HeraFeeRpc;
+
+mod forward;
+pub use forward::TxForwarder;
+
+mod modules;
+pub use modules::{HeraRpcModules, HostRpcModules};
+
+mod safedb;
+pub use safedb::SafeDbRpc;
+
+mod server;
+pub use server::RollupNodeRpc;
+
+mod validation;
+pub use validation::HeraValidationRpc;
+
+mod version;
+pub use version::HeraBuildRpc;
+
+mod withdrawal;
+pub use withdrawal::HeraWithdrawalRpc;
+
+pub mod types;
+pub use types::{
+    ExecutionPayloadEnvelope, OutputResponse, SafeHeadResponse, SpeculativeL2Block, SyncStatus,
+    ValidationResponse,
+};
+
+/// The `optimism_*` rollup node namespace, served by op-node and Hera.
+#[rpc(server, client, namespace = "optimism")]
+pub trait RollupNodeApi {
+    /// Returns the sync status of the node.
+    #[method(name = "syncStatus")]
+    async fn sync_status(&self) -> RpcResult<SyncStatus>;
+
+    /// Returns the L2 output at the given block number.
+    #[method(name = "outputAtBlock")]
+    async fn output_at_block(&self, block_number: U64) -> RpcResult<OutputResponse>;
+
+    /// Returns the latest safe head derived from an L1 block at or before `l1_block_number`, and
+    /// that L1 block.
+    #[method(name = "safeHeadAtL1Block")]
+    async fn safe_head_at_l1_block(&self, l1_block_number: U64) -> RpcResult<SafeHeadResponse>;
+
+    /// Returns the version of the node.
+    #[method(name = "version")]
+    async fn version(&self) -> RpcResult<String>;
+}
+
+/// The `safedb_*` namespace, relating L2 safe heads to the L1 blocks they were derived from, as
+/// used by fault proof tooling such as op-challenger.
+#[rpc(server, client, namespace = "safedb")]
+pub trait SafeDbApi {
+    /// Returns the latest safe head derived from an L1 block at or before `l1_block_number`, and
+    /// that L1 block, or `None` if nothing was recorded that early.
+    #[method(name = "safeHeadAtL1Block")]
+    async fn safe_head_at_l1_block(
+        &self,
+        l1_block_number: U64,
+    ) -> RpcResult<Option<SafeHeadResponse>>;
+
+    /// Returns the L1 block the L2 block `l2_block_number` was first derived from, with the safe
+    /// head derived from it, or `None` if the block is not safe yet.
+    #[method(name = "derivedFrom")]
+    async fn derived_from(&self, l2_block_number: U64) -> RpcResult<Option<SafeHeadResponse>>;
+}
+
+/// The `hera_*` debug namespace, exposing derivation internals.
+#[rpc(server, client, namespace = "hera")]
+pub trait HeraDebugApi {
+    /// Returns the channels currently buffered in the channel bank.
+    #[method(name = "channelBank")]
+    async fn channel_bank(&self) -> RpcResult<ChannelBankSnapshot>;
+
+    /// Returns the frames buffered in the channel bank, optionally of a single channel.
+    #[method(name = "frames")]
+    async fn frames(&self, channel: Option<ChannelId>) -> RpcResult<Vec<FrameSummary>>;
+
+    /// Returns the L1 block each stage of the pipeline is at, what each stage buffers, and the
+    /// last reset and step failure of the pipeline.
+    #[method(name = "pipelineStatus")]
+    async fn pipeline_status(&self) -> RpcResult<PipelineStatus>;
+}
+
+/// The fee parameter history of the `hera_*` namespace, for fee estimation services.
+#[rpc(server, client, namespace = "hera")]
+pub trait HeraFeeApi {
+    /// Returns the fee parameters in effect at the L2 block `block`, or `None` if the block is
+    /// outside the history recorded by the node.
+    #[method(name = "feeParams")]
+    async fn fee_params(&self, block: U64) -> RpcResult<Option<FeeParams>>;
+
+    /// Returns the `GasPriceOracle` configuration in effect for the transactions of the L2 block
+    /// `block`, or `None` if the block is outside the history recorded by the node.
+    #[method(name = "gasPriceOracleConfig")]
+    async fn gas_price_oracle_config(&self, block: U64) -> RpcResult<Option<GasPriceOracleConfig>>;
+}
+
+/// The withdrawal proofs of the `hera_*` namespace, for proving withdrawals on L1.
+#[rpc(server, client, namespace = "hera")]
+pub trait HeraWithdrawalApi {
+    /// Returns the proofs of the withdrawals initiated by the L2 transaction `tx_hash` against
+    /// the output at the L2 block `block`, by default the finalized head, which the output
+    /// proposed on L1 must commit to.
+    #[method(name = "getWithdrawalProof")]
+    async fn get_withdrawal_proof(
+        &self,
+        tx_hash: B256,
+        block: Option<U64>,
+    ) -> RpcResult<Vec<WithdrawalProof>>;
+}
+
+/// The external validation of the `hera_*` namespace, served in validation-only mode.
+#[rpc(server, client, namespace = "hera")]
+pub trait HeraValidationApi {
+    /// Validates payload attributes derived by another node against the canonical L2 chain.
+    #[method(name = "validateAttributes")]
+    async fn validate_attributes(
+        &self,
+        attributes: L2AttributesWithParent,
+    ) -> RpcResult<ValidationResponse>;
+}
+
+/// The build information of the `hera_*` namespace, served whether or not debugging is enabled.
+#[rpc(server, client, namespace = "hera")]
+pub trait HeraBuildApi {
+    /// Returns the version, git commit, build profile and features of the running binary.
+    #[method(name = "buildInfo")]
+    async fn build_info(&self) -> RpcResult<BuildInfo>;
+}
+
+/// The `admin_*` namespace controlling the sequencer, as used by op-conductor.
+#[rpc(server, client, namespace = "admin")]
+pub trait AdminApi {
+    /// Starts the sequencer on top of the unsafe head, which must have the given hash.
+    #[method(name = "startSequencer")]
+    async fn start_sequencer(&self, head: B256) -> RpcResult<()>;
+
+    /// Stops the sequencer, returning the hash of the last unsafe block.
+    #[method(name = "stopSequencer")]
+    async fn stop_sequencer(&self) -> RpcResult<B256>;
+
+    /// Returns whether the sequencer is active.
+    #[method(name = "sequencerActive")]
+    async fn sequencer_active(&self) -> RpcResult<bool>;
+}
+
+/// The log level control of the `admin_*` namespace, served whether or not Hera sequences.
+#[rpc(server, client, namespace = "admin")]
+pub trait AdminLogApi {
+    /// Sets the level of the logs of `target` and the targets nested under it, e.g.
+    /// `hera::derive` to `trace`.
+    #[method(name = "setLogLevel")]
+    async fn set_log_level(&self, target: String, level: String) -> RpcResult<()>;
+}
+
+/// The reorg confirmation of the `admin_*` namespace, for L1 reorgs deeper than
+/// `--hera.max-reorg-depth`.
+#[rpc(server, client, namespace = "admin")]
+pub trait AdminReorgApi {
+    /// Returns the L1 reorg awaiting confirmation, if any.
+    #[method(name = "pendingReorg")]
+    async fn pending_reorg(&self) -> RpcResult<Option<PendingReorg>>;
+
+    /// Confirms the pending L1 reorg, letting derivation unwind and resume. Fails if no reorg is
+    /// pending.
+    #[method(name = "confirmReorg")]
+    async fn confirm_reorg(&self) -> RpcResult<PendingReorg>;
+}
+
+/// The `miner_*` namespace of the sequencer's execution client, as used by op-batcher to
+/// throttle block building.
+#[rpc(client, namespace = "miner")]
+pub trait MinerApi {
+    /// Sets the maximum data availability size of a transaction and of a block, 0 meaning
+    /// unlimited. Returns whether the limits were set.
+    #[method(name = "setMaxDASize")]
+    async fn set_max_da_size(&self, max_tx_size: U64, max_block_size: U64) -> RpcResult<bool>;
+}
+
+/// The transaction submission subset of the `eth_*` namespace, proxied by Hera to the sequencer.
+#[rpc(server, client, namespace = "eth")]
+pub trait EthTxApi {
+    /// Submits a signed, EIP-2718 encoded transaction, returning its hash.
+    #[method(name = "sendRawTransaction")]
+    async fn send_raw_transaction(&self, tx: Bytes) -> RpcResult<B256>;
+}
+
+/// The `conductor_*` namespace of op-conductor, coordinating sequencers of an HA cluster.
+#[rpc(client, namespace = "conductor")]
+pub trait ConductorApi {
+    /// Returns whether the conductor's sequencer is the leader of the cluster.
+    #[method(name = "leader")]
+    async fn leader(&self) -> RpcResult<bool>;
+
+    /// Returns whether the conductor is actively managing its sequencer.
+    #[method(name = "active")]
+    async fn active(&self) -> RpcResult<bool>;
+
+    /// Commits an unsafe payload to the cluster's replicated log before it is published.
+    #[method(name = "commitUnsafePayload")]
+    async fn commit_unsafe_payload(&self, payload: ExecutionPayloadEnvelope) -> RpcResult<()>;
+}
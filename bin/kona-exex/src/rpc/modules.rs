@@ -0,0 +1,250 @@
+//! Assembly of Hera's RPC namespaces, served on a port of their own or attached to the RPC server
+//! of the host reth node.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use eyre::{Result, WrapErr};
+use jsonrpsee::{
+    core::server::MethodCallback,
+    server::{Server, ServerHandle},
+    RpcModule,
+};
+use tracing::info;
+
+use crate::{
+    rpc::{
+        AdminApiServer, AdminLogApiServer, AdminLogRpc, AdminReorgApiServer, AdminReorgRpc,
+        AdminRpc, EthTxApiServer, HeraBuildApiServer, HeraBuildRpc, HeraDebugApiServer,
+        HeraDebugRpc, HeraFeeApiServer, HeraFeeRpc, HeraValidationApiServer, HeraValidationRpc,
+        HeraWithdrawalApiServer, HeraWithdrawalRpc, RollupNodeApiServer, RollupNodeRpc,
+        SafeDbApiServer, SafeDbRpc, TxForwarder,
+    },
+    tenant,
+};
+
+/// The RPC modules of the host reth node, as handed to ExExes by the node builder's
+/// `extend_rpc_modules` hook.
+///
+/// reth is not a dependency of this crate: the binary installing Hera in a reth node implements
+/// this over reth's `TransportRpcModules` and passes it to
+/// [`HeraNode::launch`](crate::node::HeraNode::launch).
+pub trait HostRpcModules {
+    /// Merges `module` into every transport the host serves its configured namespaces on,
+    /// failing if one of its methods is already registered.
+    fn merge_configured(&mut self, module: RpcModule<()>) -> Result<()>;
+}
+
+/// Hera's RPC namespaces, merged into a single module.
+#[derive(Debug)]
+pub struct HeraRpcModules {
+    module: RpcModule<()>,
+}
+
+impl Default for HeraRpcModules {
+    fn default() -> Self {
+        Self { module: RpcModule::new(()) }
+    }
+}
+
+impl HeraRpcModules {
+    /// Creates an empty set of namespaces.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the `optimism_*` namespace.
+    pub fn with_rollup_node(self, rpc: RollupNodeRpc) -> Result<Self> {
+        self.merge(rpc.into_rpc(), "optimism")
+    }
+
+    /// Adds the `safedb_*` namespace.
+    pub fn with_safe_db(self, rpc: SafeDbRpc) -> Result<Self> {
+        self.merge(rpc.into_rpc(), "safedb")
+    }
+
+    /// Adds the `hera_*` debug namespace.
+    pub fn with_debug(self, rpc: HeraDebugRpc) -> Result<Self> {
+        self.merge(rpc.into_rpc(), "hera")
+    }
+
+    /// Adds `hera_buildInfo`.
+    pub fn with_build_info(self) -> Result<Self> {
+        self.merge(HeraBuildRpc.into_rpc(), "hera")
+    }
+
+    /// Adds `hera_feeParams` and `hera_gasPriceOracleConfig`.
+    pub fn with_fee_params(self, rpc: HeraFeeRpc) -> Result<Self> {
+        self.merge(rpc.into_rpc(), "hera")
+    }
+
+    /// Adds `hera_getWithdrawalProof`.
+    pub fn with_withdrawal_proofs(self, rpc: HeraWithdrawalRpc) -> Result<Self> {
+        self.merge(rpc.into_rpc(), "hera")
+    }
+
+    /// Adds `hera_validateAttributes`.
+    pub fn with_validation(self, rpc: HeraValidationRpc) -> Result<Self> {
+        self.merge(rpc.into_rpc(), "hera")
+    }
+
+    /// Adds the `admin_*` sequencer namespace.
+    pub fn with_admin(self, rpc: AdminRpc) -> Result<Self> {
+        self.merge(rpc.into_rpc(), "admin")
+    }
+
+    /// Adds `admin_setLogLevel`.
+    pub fn with_log_level(self, rpc: AdminLogRpc) -> Result<Self> {
+        self.merge(rpc.into_rpc(), "admin")
+    }
+
+    /// Adds `admin_pendingReorg` and `admin_confirmReorg`.
+    pub fn with_reorg_confirmation(self, rpc: AdminReorgRpc) -> Result<Self> {
+        self.merge(rpc.into_rpc(), "admin")
+    }
+
+    /// Adds the `eth_sendRawTransaction` proxy. It conflicts with the `eth_*` namespace of the
+    /// host, so it can only be served on Hera's own port.
+    pub fn with_tx_forwarder(self, forwarder: TxForwarder) -> Result<Self> {
+        self.merge(forwarder.into_rpc(), "eth")
+    }
+
+    fn merge<C: Send + Sync + 'static>(
+        mut self,
+        module: RpcModule<C>,
+        namespace: &str,
+    ) -> Result<Self> {
+        self.module
+            .merge(module)
+            .wrap_err_with(|| format!("failed to register the {namespace} namespace"))?;
+        Ok(self)
+    }
+
+    /// Runs every registered method in the scope of the chain `chain_id`, so that the metrics
+    /// they record are labelled with it.
+    pub fn in_chain_scope(self, chain_id: u64) -> Self {
+        self.map_methods(|name, callback| {
+            let callback = match callback {
+                MethodCallback::Sync(method) => MethodCallback::Sync(Arc::new(
+                    move |id, params, max_response_size, extensions| {
+                        tenant::sync_scope(chain_id, || {
+                            method(id, params, max_response_size, extensions)
+                        })
+                    },
+                )),
+                MethodCallback::Async(method) => MethodCallback::Async(Arc::new(
+                    move |id, params, connection, max_response_size, extensions| {
+                        let call = method(id, params, connection, max_response_size, extensions);
+                        Box::pin(tenant::scope(chain_id, call))
+                    },
+                )),
+                callback => callback,
+            };
+            (name, callback)
+        })
+    }
+
+    /// Prefixes every registered method with `chain<chain_id>_`, e.g.
+    /// `chain10_optimism_syncStatus`, so that the namespaces of several chains can be served
+    /// side by side.
+    pub fn with_chain_prefix(self, chain_id: u64) -> Self {
+        self.map_methods(|name, callback| {
+            let name: &'static str = Box::leak(format!("chain{chain_id}_{name}").into_boxed_str());
+            (name, callback)
+        })
+    }
+
+    fn map_methods(
+        mut self,
+        f: impl Fn(&'static str, MethodCallback) -> (&'static str, MethodCallback),
+    ) -> Self {
+        let mut module = RpcModule::new(());
+        let names: Vec<_> = self.module.method_names().collect();
+        for name in names {
+            let callback = self.module.remove_method(name).expect("registered method");
+            let (name, callback) = f(name, callback);
+            module.verify_and_insert(name, callback).expect("unique method names");
+        }
+        self.module = module;
+        self
+    }
+
+    /// Returns the names of the registered methods.
+    pub fn method_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.module.method_names()
+    }
+
+    /// Registers the namespaces on the RPC server of the host reth node, next to its own.
+    pub fn attach_to(self, host: &mut dyn HostRpcModules) -> Result<()> {
+        let methods = self.module.method_names().count();
+        host.merge_configured(self.module)
+            .wrap_err("failed to attach Hera's RPC namespaces to the reth RPC server")?;
+        info!(target: "hera::rpc", methods, "Attached RPC namespaces to the reth RPC server");
+        Ok(())
+    }
+
+    /// Serves the namespaces over HTTP and WebSocket on a port of their own.
+    pub async fn serve(self, addr: SocketAddr) -> Result<ServerHandle> {
+        let server = Server::builder()
+            .build(addr)
+            .await
+            .wrap_err_with(|| format!("failed to bind the RPC server to {addr}"))?;
+        let addr = server.local_addr().unwrap_or(addr);
+        info!(target: "hera::rpc", %addr, "Started RPC server");
+        Ok(server.start(self.module))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A host serving `module`, refusing methods it already has like reth does.
+    struct Host {
+        module: RpcModule<()>,
+    }
+
+    impl HostRpcModules for Host {
+        fn merge_configured(&mut self, module: RpcModule<()>) -> Result<()> {
+            self.module.merge(module)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn attaches_to_the_host() {
+        let mut host = Host { module: RpcModule::new(()) };
+        HeraRpcModules::new().with_build_info().unwrap().attach_to(&mut host).unwrap();
+        assert!(host.module.method_names().any(|name| name == "hera_buildInfo"));
+    }
+
+    #[tokio::test]
+    async fn routes_methods_to_their_chain() {
+        let mut module = RpcModule::new(());
+        module.register_method("test_sync", |_, _, _| tenant::current()).unwrap();
+        module.register_async_method("test_async", |_, _, _| async { tenant::current() }).unwrap();
+        let modules = HeraRpcModules { module }.in_chain_scope(10).with_chain_prefix(10);
+
+        let mut names: Vec<_> = modules.method_names().collect();
+        names.sort_unstable();
+        assert_eq!(names, ["chain10_test_async", "chain10_test_sync"]);
+        for method in names {
+            let chain: Option<u64> = modules.module.call(method, [(); 0]).await.unwrap();
+            assert_eq!(chain, Some(10), "{method}");
+        }
+        assert!(modules.module.call::<_, Option<u64>>("test_sync", [(); 0]).await.is_err());
+    }
+
+    #[test]
+    fn reports_namespace_conflicts() {
+        let err = HeraRpcModules::new().with_build_info().unwrap().with_build_info().unwrap_err();
+        assert_eq!(err.to_string(), "failed to register the hera namespace");
+
+        let mut host = Host { module: HeraRpcModules::new().with_build_info().unwrap().module };
+        let err =
+            HeraRpcModules::new().with_build_info().unwrap().attach_to(&mut host).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to attach Hera's RPC namespaces to the reth RPC server"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! Hera's implementation of the `safedb_*` namespace.
+
+use std::sync::Arc;
+
+use alloy_primitives::U64;
+use async_trait::async_trait;
+use jsonrpsee::{
+    core::RpcResult,
+    types::{error::INTERNAL_ERROR_CODE, ErrorObjectOwned},
+};
+
+use crate::{
+    rpc::{SafeDbApiServer, SafeHeadResponse},
+    safedb::SafeDb,
+};
+
+/// Serves the `safedb_*` namespace from the safe head database.
+#[derive(Debug)]
+pub struct SafeDbRpc {
+    safe_db: Arc<SafeDb>,
+}
+
+impl SafeDbRpc {
+    /// Creates the RPC handler.
+    pub const fn new(safe_db: Arc<SafeDb>) -> Self {
+        Self { safe_db }
+    }
+}
+
+fn internal_error(message: impl ToString) -> ErrorObjectOwned {
+    ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, message.to_string(), None::<()>)
+}
+
+#[async_trait]
+impl SafeDbApiServer for SafeDbRpc {
+    async fn safe_head_at_l1_block(
+        &self,
+        l1_block_number: U64,
+    ) -> RpcResult<Option<SafeHeadResponse>> {
+        let safe_head = self.safe_db.safe_head_at(l1_block_number.to()).map_err(internal_error)?;
+        Ok(safe_head.map(Into::into))
+    }
+
+    async fn derived_from(&self, l2_block_number: U64) -> RpcResult<Option<SafeHeadResponse>> {
+        let safe_head = self.safe_db.derived_from(l2_block_number.to()).map_err(internal_error)?;
+        Ok(safe_head.map(Into::into))
+    }
+}
@@ -0,0 +1,76 @@
+//! Hera's implementation of the `optimism_*` namespace.
+
+use std::sync::Arc;
+
+use alloy_primitives::U64;
+use async_trait::async_trait;
+use jsonrpsee::{
+    core::RpcResult,
+    types::{error::INTERNAL_ERROR_CODE, ErrorObjectOwned},
+};
+use tokio::sync::watch;
+
+use crate::{
+    output::{OutputRootCache, OUTPUT_ROOT_VERSION},
+    rpc::{OutputResponse, RollupNodeApiServer, SafeHeadResponse, SyncStatus},
+    safedb::SafeDb,
+    version::SHORT_VERSION,
+};
+
+/// Serves the `optimism_*` namespace from the driver's sync status and an output root cache.
+#[derive(Debug)]
+pub struct RollupNodeRpc {
+    status: watch::Receiver<SyncStatus>,
+    outputs: Arc<OutputRootCache>,
+    safe_db: Option<Arc<SafeDb>>,
+}
+
+impl RollupNodeRpc {
+    /// Creates the RPC handler.
+    pub const fn new(status: watch::Receiver<SyncStatus>, outputs: Arc<OutputRootCache>) -> Self {
+        Self { status, outputs, safe_db: None }
+    }
+
+    /// Serves `optimism_safeHeadAtL1Block` from `safe_db`.
+    pub fn with_safe_db(mut self, safe_db: Arc<SafeDb>) -> Self {
+        self.safe_db = Some(safe_db);
+        self
+    }
+}
+
+fn internal_error(message: impl ToString) -> ErrorObjectOwned {
+    ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, message.to_string(), None::<()>)
+}
+
+#[async_trait]
+impl RollupNodeApiServer for RollupNodeRpc {
+    async fn sync_status(&self) -> RpcResult<SyncStatus> {
+        Ok(self.status.borrow().clone())
+    }
+
+    async fn output_at_block(&self, block_number: U64) -> RpcResult<OutputResponse> {
+        let output =
+            self.outputs.output_at_block(block_number.to()).await.map_err(internal_error)?;
+        Ok(OutputResponse {
+            version: OUTPUT_ROOT_VERSION,
+            output_root: output.output_root,
+            block_ref: output.block,
+            withdrawal_storage_root: output.withdrawal_storage_root,
+            state_root: output.state_root,
+            sync_status: self.status.borrow().clone(),
+        })
+    }
+
+    async fn safe_head_at_l1_block(&self, l1_block_number: U64) -> RpcResult<SafeHeadResponse> {
+        let safe_db =
+            self.safe_db.as_ref().ok_or_else(|| internal_error("safe head database disabled"))?;
+        let safe_head = safe_db.safe_head_at(l1_block_number.to()).map_err(internal_error)?;
+        safe_head.map(Into::into).ok_or_else(|| {
+            internal_error(format!("no safe head recorded at or before L1 block {l1_block_number}"))
+        })
+    }
+
+    async fn version(&self) -> RpcResult<String> {
+        Ok(SHORT_VERSION.to_string())
+    }
+}
@@ -0,0 +1,138 @@
+//! Types of the RPC namespaces served and consumed by Hera, compatible with op-node and
+//! op-conductor.
+
+use alloy_primitives::B256;
+use alloy_rpc_types_engine::ExecutionPayload;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    engine::EnginePayload,
+    protocol::{BlockId, BlockInfo, L2BlockInfo},
+    safedb::SafeHeadAtL1,
+    validation::ValidationOutcome,
+};
+
+/// The sync status of a rollup node, as returned by `optimism_syncStatus`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncStatus {
+    /// The L1 block the derivation pipeline is currently reading from.
+    pub current_l1: BlockInfo,
+    /// The finalized L1 block the derivation pipeline is aware of.
+    pub current_l1_finalized: BlockInfo,
+    /// The L1 head.
+    pub head_l1: BlockInfo,
+    /// The safe L1 block.
+    pub safe_l1: BlockInfo,
+    /// The finalized L1 block.
+    pub finalized_l1: BlockInfo,
+    /// The unsafe L2 head.
+    pub unsafe_l2: L2BlockInfo,
+    /// The safe L2 head.
+    pub safe_l2: L2BlockInfo,
+    /// The finalized L2 head.
+    pub finalized_l2: L2BlockInfo,
+    /// The L2 block derivation is currently processing, which may not be safe yet.
+    #[serde(default)]
+    pub pending_safe_l2: L2BlockInfo,
+    /// The highest L2 block covered by batcher transactions still pending in the L1 mempool.
+    ///
+    /// This is speculative: the batches may never be included, or be included but rejected by
+    /// derivation. Only reported when the mempool preview is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speculative_safe_l2: Option<SpeculativeL2Block>,
+}
+
+/// An L2 block decoded from unconfirmed L1 batcher data, ahead of the safe head.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeculativeL2Block {
+    /// The L2 block number, computed from the batch timestamp.
+    pub number: u64,
+    /// The L2 block timestamp.
+    pub timestamp: u64,
+    /// Number of the L1 origin of the block.
+    pub l1_origin_number: u64,
+    /// Hash of the pending batcher transaction completing the batch.
+    pub batcher_tx: B256,
+}
+
+/// An L2 output at a block, as returned by `optimism_outputAtBlock`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputResponse {
+    /// The output root version.
+    pub version: B256,
+    /// The output root.
+    pub output_root: B256,
+    /// The L2 block the output is computed at.
+    pub block_ref: L2BlockInfo,
+    /// The storage root of the `L2ToL1MessagePasser` predeploy.
+    pub withdrawal_storage_root: B256,
+    /// The state root of the block.
+    pub state_root: B256,
+    /// The sync status of the node at the time of the request.
+    pub sync_status: SyncStatus,
+}
+
+/// An execution payload with its parent beacon block root, as committed to op-conductor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionPayloadEnvelope {
+    /// The parent beacon block root, from Ecotone onwards.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_beacon_block_root: Option<B256>,
+    /// The execution payload.
+    pub execution_payload: ExecutionPayload,
+}
+
+impl From<EnginePayload> for ExecutionPayloadEnvelope {
+    fn from(payload: EnginePayload) -> Self {
+        Self {
+            parent_beacon_block_root: payload.parent_beacon_block_root,
+            execution_payload: payload.payload,
+        }
+    }
+}
+
+/// The L2 safe head as of an L1 block, as returned by `optimism_safeHeadAtL1Block`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeHeadResponse {
+    /// The L1 block the safe head was derived from.
+    pub l1_block: BlockId,
+    /// The L2 safe head.
+    pub safe_head: BlockId,
+}
+
+impl From<SafeHeadAtL1> for SafeHeadResponse {
+    fn from(safe_head: SafeHeadAtL1) -> Self {
+        Self { l1_block: safe_head.l1_block, safe_head: safe_head.safe_head }
+    }
+}
+
+/// The outcome of `hera_validateAttributes`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationResponse {
+    /// Whether the attributes produce the canonical block.
+    pub valid: bool,
+    /// The canonical block produced by valid attributes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block: Option<L2BlockInfo>,
+    /// Why invalid attributes do not match the canonical chain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl From<ValidationOutcome> for ValidationResponse {
+    fn from(outcome: ValidationOutcome) -> Self {
+        match outcome {
+            ValidationOutcome::Valid(block) => {
+                Self { valid: true, block: Some(block), reason: None }
+            }
+            ValidationOutcome::Invalid(reason) => {
+                Self { valid: false, block: None, reason: Some(reason) }
+            }
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! Hera's implementation of `hera_validateAttributes`.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use jsonrpsee::{
+    core::RpcResult,
+    types::{error::INTERNAL_ERROR_CODE, ErrorObjectOwned},
+};
+
+use crate::{
+    derive::L2AttributesWithParent,
+    rpc::{HeraValidationApiServer, ValidationResponse},
+    validation::{ExternalValidation, RpcAttributesValidator},
+};
+
+/// Serves `hera_validateAttributes`, validating attributes submitted by another node.
+#[derive(Debug)]
+pub struct HeraValidationRpc {
+    validation: Arc<ExternalValidation<RpcAttributesValidator>>,
+}
+
+impl HeraValidationRpc {
+    /// Creates the RPC handler validating attributes through `validation`.
+    pub const fn new(validation: Arc<ExternalValidation<RpcAttributesValidator>>) -> Self {
+        Self { validation }
+    }
+}
+
+#[async_trait]
+impl HeraValidationApiServer for HeraValidationRpc {
+    async fn validate_attributes(
+        &self,
+        attributes: L2AttributesWithParent,
+    ) -> RpcResult<ValidationResponse> {
+        self.validation.validate(&attributes).await.map(ValidationResponse::from).map_err(|err| {
+            ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, err.to_string(), None::<()>)
+        })
+    }
+}
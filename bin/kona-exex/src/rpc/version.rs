@@ -0,0 +1,17 @@
+//! Hera's implementation of `hera_buildInfo`.
+
+use async_trait::async_trait;
+use jsonrpsee::core::RpcResult;
+
+use crate::{rpc::HeraBuildApiServer, version::BuildInfo};
+
+/// Serves `hera_buildInfo` from the build information embedded in the binary.
+#[derive(Debug, Default)]
+pub struct HeraBuildRpc;
+
+#[async_trait]
+impl HeraBuildApiServer for HeraBuildRpc {
+    async fn build_info(&self) -> RpcResult<BuildInfo> {
+        Ok(BuildInfo::current())
+    }
+}
@@ -0,0 +1,45 @@
+//! Hera's implementation of `hera_getWithdrawalProof`.
+
+use alloy_primitives::{B256, U64};
+use async_trait::async_trait;
+use jsonrpsee::{
+    core::RpcResult,
+    types::{error::INTERNAL_ERROR_CODE, ErrorObjectOwned},
+};
+use tokio::sync::watch;
+
+use crate::{
+    output::{WithdrawalProof, WithdrawalProver},
+    rpc::{HeraWithdrawalApiServer, SyncStatus},
+};
+
+/// Serves `hera_getWithdrawalProof`, proving withdrawals against the outputs of the node.
+#[derive(Debug)]
+pub struct HeraWithdrawalRpc {
+    prover: WithdrawalProver,
+    status: watch::Receiver<SyncStatus>,
+}
+
+impl HeraWithdrawalRpc {
+    /// Creates the RPC handler, proving against the finalized head of `status` by default.
+    pub const fn new(prover: WithdrawalProver, status: watch::Receiver<SyncStatus>) -> Self {
+        Self { prover, status }
+    }
+}
+
+#[async_trait]
+impl HeraWithdrawalApiServer for HeraWithdrawalRpc {
+    async fn get_withdrawal_proof(
+        &self,
+        tx_hash: B256,
+        block: Option<U64>,
+    ) -> RpcResult<Vec<WithdrawalProof>> {
+        let block = match block {
+            Some(block) => block.to(),
+            None => self.status.borrow().finalized_l2.block_info.number,
+        };
+        self.prover.prove(tx_hash, block).await.map_err(|err| {
+            ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, format!("{err:#}"), None::<()>)
+        })
+    }
+}
@@ -0,0 +1,154 @@
+//! The safe head database: the L2 safe head at each L1 block.
+//!
+//! Every time derivation advances the safe head, the L1 block whose batcher data it was derived
+//! from is recorded. `optimism_safeHeadAtL1Block` then tells which L2 blocks were safe as of a
+//! given L1 block, as op-proposer and the fault proof tooling need to pick the L2 blocks they
+//! make claims about. The `safedb_*` namespace also answers the other way around, telling which
+//! L1 block an L2 block was first derived from.
+
+use std::{path::Path, sync::Arc};
+
+use alloy_primitives::B256;
+use eyre::{ensure, Result, WrapErr};
+
+use crate::{
+    protocol::BlockId,
+    storage::{SqliteStorage, Storage},
+};
+
+/// The table of safe heads, keyed by big-endian L1 block number, holding the L1 block hash, then
+/// the safe head hash and big-endian number.
+pub(crate) const SAFE_HEADS: &str = "safe_heads";
+
+/// The L2 safe head as of an L1 block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SafeHeadAtL1 {
+    /// The L1 block the safe head was derived from.
+    pub l1_block: BlockId,
+    /// The L2 safe head.
+    pub safe_head: BlockId,
+}
+
+/// An index of the L2 safe head at each L1 block, written as derivation advances.
+#[derive(Debug)]
+pub struct SafeDb {
+    storage: Arc<dyn Storage>,
+}
+
+impl SafeDb {
+    /// Creates a database in `storage`.
+    pub fn new(storage: Arc<dyn Storage>) -> Self {
+        Self { storage }
+    }
+
+    /// Opens the database in the SQLite database at `path`, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let storage = SqliteStorage::open(path)
+            .wrap_err_with(|| format!("failed to open safe head database {}", path.display()))?;
+        Ok(Self::new(Arc::new(storage)))
+    }
+
+    /// Records `safe_head` as the safe head derived from the L1 block `l1_block`, replacing the
+    /// safe head recorded for it so far.
+    pub fn record(&self, l1_block: BlockId, safe_head: BlockId) -> Result<()> {
+        let mut value = [0; 72];
+        value[..32].copy_from_slice(l1_block.hash.as_slice());
+        value[32..64].copy_from_slice(safe_head.hash.as_slice());
+        value[64..].copy_from_slice(&safe_head.number.to_be_bytes());
+        self.storage
+            .put(SAFE_HEADS, &[(&l1_block.number.to_be_bytes(), &value)])
+            .wrap_err_with(|| format!("failed to record safe head {safe_head}"))
+    }
+
+    /// Forgets the safe heads derived from L1 blocks at or after `l1_number`, e.g. when
+    /// derivation is reset to an earlier L1 block.
+    pub fn truncate(&self, l1_number: u64) -> Result<()> {
+        self.storage
+            .truncate(SAFE_HEADS, &l1_number.to_be_bytes())
+            .wrap_err_with(|| format!("failed to truncate safe heads from L1 block {l1_number}"))
+    }
+
+    /// Returns the latest safe head derived from an L1 block at or before `l1_number`.
+    pub fn safe_head_at(&self, l1_number: u64) -> Result<Option<SafeHeadAtL1>> {
+        let entry = self
+            .storage
+            .floor(SAFE_HEADS, &l1_number.to_be_bytes())
+            .wrap_err_with(|| format!("failed to read safe head at L1 block {l1_number}"))?;
+        let Some((key, value)) = entry else { return Ok(None) };
+        ensure!(
+            key.len() == 8 && value.len() == 72,
+            "safe head at L1 block {l1_number} is malformed"
+        );
+
+        let number = |bytes: &[u8]| u64::from_be_bytes(bytes.try_into().unwrap());
+        Ok(Some(SafeHeadAtL1 {
+            l1_block: BlockId::new(B256::from_slice(&value[..32]), number(&key)),
+            safe_head: BlockId::new(B256::from_slice(&value[32..64]), number(&value[64..])),
+        }))
+    }
+
+    /// Returns the first safe head at or after the L2 block `l2_number`, with the L1 block it
+    /// was derived from, i.e. the earliest L1 block as of which `l2_number` was safe.
+    ///
+    /// Safe heads only move forward with the L1 blocks they are derived from, so the entry is
+    /// found with a binary search over L1 block numbers.
+    pub fn derived_from(&self, l2_number: u64) -> Result<Option<SafeHeadAtL1>> {
+        let Some(latest) = self.safe_head_at(u64::MAX)? else { return Ok(None) };
+        if latest.safe_head.number < l2_number {
+            return Ok(None);
+        }
+        let (mut low, mut high) = (0, latest.l1_block.number);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.safe_head_at(mid)? {
+                Some(at) if at.safe_head.number >= l2_number => high = at.l1_block.number,
+                _ => low = mid + 1,
+            }
+        }
+        self.safe_head_at(high)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn id(byte: u8, number: u64) -> BlockId {
+        BlockId::new(B256::repeat_byte(byte), number)
+    }
+
+    #[test]
+    fn serves_latest_safe_head_at_or_before_l1_block() {
+        let db = SafeDb::new(Arc::new(MemoryStorage::new()));
+        db.record(id(1, 10), id(0xa, 100)).unwrap();
+        db.record(id(2, 12), id(0xb, 110)).unwrap();
+
+        assert_eq!(db.safe_head_at(9).unwrap(), None);
+        let at = |number| db.safe_head_at(number).unwrap().unwrap();
+        assert_eq!(at(11), SafeHeadAtL1 { l1_block: id(1, 10), safe_head: id(0xa, 100) });
+        assert_eq!(at(20).safe_head, id(0xb, 110));
+
+        db.truncate(11).unwrap();
+        assert_eq!(at(20).l1_block, id(1, 10));
+    }
+
+    #[test]
+    fn finds_the_l1_block_an_l2_block_was_first_derived_from() {
+        let db = SafeDb::new(Arc::new(MemoryStorage::new()));
+        assert_eq!(db.derived_from(0).unwrap(), None);
+        db.record(id(1, 10), id(0xa, 100)).unwrap();
+        db.record(id(2, 12), id(0xa, 100)).unwrap();
+        db.record(id(3, 17), id(0xb, 110)).unwrap();
+        db.record(id(4, 40), id(0xc, 120)).unwrap();
+
+        let l1_block = |number| db.derived_from(number).unwrap().map(|at| at.l1_block);
+        assert_eq!(l1_block(0), Some(id(1, 10)));
+        assert_eq!(l1_block(100), Some(id(1, 10)));
+        assert_eq!(l1_block(101), Some(id(3, 17)));
+        assert_eq!(l1_block(110), Some(id(3, 17)));
+        assert_eq!(l1_block(115), Some(id(4, 40)));
+        assert_eq!(l1_block(121), None);
+    }
+}
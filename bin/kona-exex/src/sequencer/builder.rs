@@ -0,0 +1,186 @@
+//! Delegation of block building to an external builder, as done by rollup-boost.
+//!
+//! The builder exposes the Engine API: every payload build started on the local engine is also
+//! started on the builder, and the builder's payload is sealed in place of the local one when
+//! it arrives in time and the local engine accepts it. The [`BuilderFallback`] policy decides
+//! whether the local payload is sealed when the builder fails.
+
+use std::{fmt, str::FromStr, sync::Arc, time::Duration};
+
+use alloy_primitives::B256;
+use alloy_rpc_types_engine::{ForkchoiceState, OptimismPayloadAttributes, PayloadId};
+use eyre::{bail, Result};
+use metrics::counter;
+use tracing::warn;
+
+use crate::engine::{
+    EngineApi, EngineError, EnginePayload, ForkchoiceUpdatedVersion, GetPayloadVersion,
+};
+
+/// Default time the builder has to answer a request.
+pub const DEFAULT_BUILDER_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// What the sequencer does when the external builder fails to provide a valid payload.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BuilderFallback {
+    /// Seal the payload built by the local engine.
+    #[default]
+    Local,
+    /// Seal nothing: the block fails and is retried, so that only the builder's blocks are
+    /// ever sequenced.
+    None,
+}
+
+impl fmt::Display for BuilderFallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Local => "local",
+            Self::None => "none",
+        })
+    }
+}
+
+impl FromStr for BuilderFallback {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "local" => Ok(Self::Local),
+            "none" => Ok(Self::None),
+            _ => bail!("unknown builder fallback {s}, expected local or none"),
+        }
+    }
+}
+
+/// Why the payload of the external builder was not sealed.
+#[derive(Debug)]
+pub enum BuilderError {
+    /// The builder did not answer in time.
+    Timeout,
+    /// The builder did not start a payload build.
+    NotStarted,
+    /// The builder failed to answer.
+    Engine(EngineError),
+    /// The builder's payload does not extend the unsafe head.
+    WrongParent {
+        /// The parent of the payload.
+        parent: B256,
+        /// The unsafe head.
+        head: B256,
+    },
+    /// The local engine rejected the builder's payload, or did not validate it.
+    Invalid(String),
+    /// The local engine failed to answer when inserting the builder's payload.
+    LocalEngine(EngineError),
+}
+
+impl BuilderError {
+    /// Returns the kind of the error.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Timeout => "timeout",
+            Self::NotStarted => "not_started",
+            Self::Engine(_) => "engine",
+            Self::WrongParent { .. } => "wrong_parent",
+            Self::Invalid(_) => "invalid",
+            Self::LocalEngine(_) => "local_engine",
+        }
+    }
+}
+
+impl fmt::Display for BuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout => f.write_str("builder timed out"),
+            Self::NotStarted => f.write_str("builder did not start a payload build"),
+            Self::Engine(err) => write!(f, "builder request failed: {err}"),
+            Self::WrongParent { parent, head } => {
+                write!(f, "builder payload extends {parent}, not the unsafe head {head}")
+            }
+            Self::Invalid(reason) => write!(f, "engine rejected the builder payload: {reason}"),
+            Self::LocalEngine(err) => write!(f, "failed to insert the builder payload: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for BuilderError {}
+
+/// An external block builder reached over the Engine API.
+#[derive(Debug)]
+pub struct ExternalBuilder {
+    engine: Arc<dyn EngineApi>,
+    timeout: Duration,
+    fallback: BuilderFallback,
+}
+
+impl ExternalBuilder {
+    /// Creates a builder answering the Engine API calls sent to `engine`.
+    pub fn new(engine: Arc<dyn EngineApi>) -> Self {
+        Self { engine, timeout: DEFAULT_BUILDER_TIMEOUT, fallback: BuilderFallback::default() }
+    }
+
+    /// Sets the time the builder has to answer a request.
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets what the sequencer does when the builder fails.
+    pub const fn with_fallback(mut self, fallback: BuilderFallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Returns what the sequencer does when the builder fails.
+    pub const fn fallback(&self) -> BuilderFallback {
+        self.fallback
+    }
+
+    /// Starts a payload build on the builder, returning its id.
+    pub async fn start(
+        &self,
+        version: ForkchoiceUpdatedVersion,
+        state: ForkchoiceState,
+        attributes: OptimismPayloadAttributes,
+    ) -> Result<PayloadId, BuilderError> {
+        let updated = tokio::time::timeout(
+            self.timeout,
+            self.engine.forkchoice_updated(version, state, Some(attributes)),
+        )
+        .await
+        .map_err(|_| BuilderError::Timeout)?
+        .map_err(BuilderError::Engine)?;
+        updated.payload_id.ok_or(BuilderError::NotStarted)
+    }
+
+    /// Retrieves the payload built by the builder, which must extend `head`.
+    pub async fn get_payload(
+        &self,
+        version: GetPayloadVersion,
+        payload_id: PayloadId,
+        head: B256,
+    ) -> Result<EnginePayload, BuilderError> {
+        let payload =
+            tokio::time::timeout(self.timeout, self.engine.get_payload(version, payload_id))
+                .await
+                .map_err(|_| BuilderError::Timeout)?
+                .map_err(BuilderError::Engine)?;
+        if payload.parent_hash() != head {
+            return Err(BuilderError::WrongParent { parent: payload.parent_hash(), head });
+        }
+        Ok(payload)
+    }
+
+    /// Records a failure of the builder, returning an error unless the fallback policy allows
+    /// sealing the local payload.
+    pub fn fail(&self, err: BuilderError) -> Result<()> {
+        counter!("hera_sequencer_builder_failures_total", "reason" => err.kind()).increment(1);
+        match self.fallback {
+            BuilderFallback::Local => {
+                warn!(target: "hera::sequencer", %err, "Falling back to the local payload");
+                Ok(())
+            }
+            BuilderFallback::None => bail!("{err}, and fallback to the local payload is disabled"),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! The op-conductor commit API.
+
+use async_trait::async_trait;
+use eyre::{Result, WrapErr};
+use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+
+use crate::{
+    engine::EnginePayload,
+    rpc::{ConductorApiClient, ExecutionPayloadEnvelope},
+};
+
+/// The conductor of a high-availability sequencer cluster, deciding which sequencer may publish
+/// blocks.
+#[async_trait]
+pub trait Conductor: std::fmt::Debug + Send + Sync {
+    /// Returns whether the local sequencer is the leader of the cluster.
+    async fn leader(&self) -> Result<bool>;
+
+    /// Commits a sealed payload to the cluster before it is inserted and gossiped. The payload
+    /// must not be published if the commit fails.
+    async fn commit_unsafe_payload(&self, payload: &EnginePayload) -> Result<()>;
+}
+
+/// A [`Conductor`] reached over the op-conductor JSON-RPC API.
+#[derive(Debug)]
+pub struct ConductorClient {
+    client: HttpClient,
+}
+
+impl ConductorClient {
+    /// Creates a client of the op-conductor RPC at `url`.
+    pub fn new(url: &str) -> Result<Self> {
+        let client = HttpClientBuilder::default()
+            .build(url)
+            .wrap_err_with(|| format!("invalid conductor RPC URL {url}"))?;
+        Ok(Self { client })
+    }
+
+    /// Returns whether the conductor is actively managing its sequencer.
+    pub async fn active(&self) -> Result<bool> {
+        ConductorApiClient::active(&self.client).await.wrap_err("conductor_active failed")
+    }
+}
+
+#[async_trait]
+impl Conductor for ConductorClient {
+    async fn leader(&self) -> Result<bool> {
+        ConductorApiClient::leader(&self.client).await.wrap_err("conductor_leader failed")
+    }
+
+    async fn commit_unsafe_payload(&self, payload: &EnginePayload) -> Result<()> {
+        let envelope = ExecutionPayloadEnvelope::from(payload.clone());
+        ConductorApiClient::commit_unsafe_payload(&self.client, envelope).await.wrap_err_with(
+            || format!("failed to commit payload {} to conductor", payload.block_id()),
+        )
+    }
+}
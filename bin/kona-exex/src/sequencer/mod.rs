@@ -0,0 +1,855 @@
+//! Block production: sequencing new unsafe L2 blocks on top of the unsafe head.
+//!
+//! When running in a high-availability cluster managed by op-conductor, the [`Sequencer`] only
+//! builds blocks while its conductor reports it as the leader, and commits every sealed payload
+//! to the conductor before inserting it. Leadership transfers are driven by the conductor
+//! through the `admin_*` namespace, see [`SequencerHandle`].
+//!
+//! Block building can be delegated to an [`ExternalBuilder`], as with rollup-boost: its payload
+//! is sealed when it arrives in time and the local engine validates it, and the local payload
+//! otherwise, unless the [`BuilderFallback`] policy forbids it.
+//!
+//! While the batcher lags behind, a [`DaThrottle`] can cap the transaction data of sequenced
+//! blocks, bounding the L1 data availability costs of the backlog.
+//!
+//! Embedders can filter and order the pool transactions of sequenced blocks with a
+//! [`TransactionPolicy`], the engine's own selection being kept by default.
+//!
+//! Blocks are paced by the local clock. Given a [`ClockMonitor`], the sequencer refuses to build
+//! blocks while the clock is skewed from the network by more than the maximum, rather than
+//! building blocks peers reject as too new.
+//!
+//! After a downtime longer than the maximum sequencer drift, the unsafe head lags so far behind
+//! L1 that blocks carrying user transactions would be invalid. The sequencer then recovers by
+//! building empty blocks, adopting each L1 origin as soon as its timestamp allows, until it is
+//! back within the drift of the wall clock.
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use alloy_primitives::B256;
+use alloy_rpc_types_engine::{
+    ForkchoiceState, OptimismPayloadAttributes, PayloadId, PayloadStatusEnum,
+};
+use eyre::{bail, ensure, eyre, Result};
+use metrics::{counter, gauge};
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+use crate::{
+    audit::{AuditEvent, AuditLog, HeadKind},
+    clock::ClockMonitor,
+    config::RollupConfig,
+    derive::AttributesBuilder,
+    engine::{
+        EngineApi, EnginePayload, ForkchoiceUpdatedVersion, GetPayloadVersion, NewPayloadVersion,
+    },
+    l1::ChainProvider,
+    protocol::{deposit::DEPOSIT_TX_TYPE, BlockInfo, L2BlockInfo},
+    rpc::SyncStatus,
+};
+
+mod builder;
+pub use builder::{BuilderError, BuilderFallback, ExternalBuilder, DEFAULT_BUILDER_TIMEOUT};
+
+mod conductor;
+pub use conductor::{Conductor, ConductorClient};
+
+mod policy;
+pub use policy::{PassthroughPolicy, TransactionPolicy};
+
+mod throttle;
+pub use throttle::{
+    DaLimiter, DaThrottle, MinerClient, ThrottleConfig, DEFAULT_THROTTLE_BLOCK_SIZE,
+    DEFAULT_THROTTLE_THRESHOLD, DEFAULT_THROTTLE_TX_SIZE,
+};
+
+/// Builds unsafe L2 blocks on top of the unsafe head of the sync status.
+#[derive(Debug)]
+pub struct Sequencer {
+    config: Arc<RollupConfig>,
+    engine: Arc<dyn EngineApi>,
+    attributes: Box<dyn AttributesBuilder>,
+    l1: Arc<dyn ChainProvider>,
+    conductor: Option<Arc<dyn Conductor>>,
+    builder: Option<ExternalBuilder>,
+    status: watch::Sender<SyncStatus>,
+    active: watch::Sender<bool>,
+    recovering: bool,
+    audit: Option<Arc<AuditLog>>,
+    throttle: Option<DaThrottle>,
+    policy: Arc<dyn TransactionPolicy>,
+    clock: Option<Arc<ClockMonitor>>,
+}
+
+impl Sequencer {
+    /// Creates a sequencer building on the unsafe head of `status` and reporting its blocks
+    /// into it. The sequencer starts active.
+    pub fn new(
+        config: Arc<RollupConfig>,
+        engine: Arc<dyn EngineApi>,
+        attributes: Box<dyn AttributesBuilder>,
+        l1: Arc<dyn ChainProvider>,
+        status: watch::Sender<SyncStatus>,
+    ) -> Self {
+        Self {
+            config,
+            engine,
+            attributes,
+            l1,
+            conductor: None,
+            builder: None,
+            status,
+            active: watch::Sender::new(true),
+            recovering: false,
+            audit: None,
+            throttle: None,
+            policy: Arc::new(PassthroughPolicy),
+            clock: None,
+        }
+    }
+
+    /// Makes the sequencer part of a cluster managed by `conductor`. The sequencer then starts
+    /// stopped, waiting for the conductor to start it once it is elected leader.
+    pub fn with_conductor(mut self, conductor: Arc<dyn Conductor>) -> Self {
+        self.conductor = Some(conductor);
+        self.active.send_replace(false);
+        self
+    }
+
+    /// Delegates block building to `builder`.
+    pub fn with_builder(mut self, builder: ExternalBuilder) -> Self {
+        self.builder = Some(builder);
+        self
+    }
+
+    /// Sets whether the sequencer starts active.
+    pub fn with_active(self, active: bool) -> Self {
+        self.active.send_replace(active);
+        self
+    }
+
+    /// Records every sequenced unsafe head in `audit`.
+    pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Throttles the transactions of sequenced blocks with `throttle` while the batcher lags.
+    pub fn with_throttle(mut self, throttle: DaThrottle) -> Self {
+        self.throttle = Some(throttle);
+        self
+    }
+
+    /// Selects the pool transactions of sequenced blocks with `policy`.
+    pub fn with_transaction_policy(mut self, policy: Arc<dyn TransactionPolicy>) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Refuses to build blocks while `clock` is skewed by more than its maximum.
+    pub fn with_clock(mut self, clock: Arc<ClockMonitor>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Returns a handle to start and stop the sequencer.
+    pub fn handle(&self) -> SequencerHandle {
+        SequencerHandle { active: self.active.clone(), status: self.status.clone() }
+    }
+
+    /// Builds a block every block time while active, forever.
+    pub async fn run(mut self) {
+        let mut active = self.active.subscribe();
+        loop {
+            if !*active.borrow_and_update() {
+                if active.changed().await.is_err() {
+                    return;
+                }
+                continue;
+            }
+            match self.build_block().await {
+                Ok(Some(block)) => debug!(target: "hera::sequencer", %block, "Sequenced block"),
+                Ok(None) => {
+                    tokio::time::sleep(Duration::from_secs(self.config.block_time)).await;
+                }
+                Err(err) => {
+                    warn!(target: "hera::sequencer", %err, "Failed to sequence block");
+                    counter!("hera_sequencer_errors_total").increment(1);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    }
+
+    /// Builds, seals and inserts the block following the unsafe head, waiting for its timestamp
+    /// to be reached before sealing it.
+    ///
+    /// Returns `None` without building anything if the conductor does not report this
+    /// sequencer as the leader, and fails if the local clock is skewed.
+    pub async fn build_block(&mut self) -> Result<Option<L2BlockInfo>> {
+        if let Some(clock) = &self.clock {
+            clock.check()?;
+        }
+        if let Some(conductor) = &self.conductor {
+            if !conductor.leader().await? {
+                debug!(target: "hera::sequencer", "Not the cluster leader, skipping block");
+                return Ok(None);
+            }
+        }
+
+        let (head, forkchoice) = {
+            let status = self.status.borrow();
+            let head = status.unsafe_l2;
+            let forkchoice = ForkchoiceState {
+                head_block_hash: head.block_info.hash,
+                safe_block_hash: status.safe_l2.block_info.hash,
+                finalized_block_hash: status.finalized_l2.block_info.hash,
+            };
+            (head, forkchoice)
+        };
+        let timestamp = head.block_info.timestamp + self.config.block_time;
+        let origin = self.select_origin(head, timestamp).await?;
+
+        let no_tx_pool = self.update_recovery(origin, timestamp);
+        let mut attributes = self.attributes.prepare_payload_attributes(head, origin.id()).await?;
+        attributes.no_tx_pool = Some(no_tx_pool);
+        let version =
+            ForkchoiceUpdatedVersion::from_attributes_timestamp(&self.config, Some(timestamp));
+        let local = self.engine.forkchoice_updated(version, forkchoice, Some(attributes.clone()));
+        let (updated, external) = match &self.builder {
+            Some(builder) => {
+                let (updated, external) =
+                    tokio::join!(local, builder.start(version, forkchoice, attributes.clone()));
+                (updated, Some(external))
+            }
+            None => (local.await, None),
+        };
+        let updated = updated?;
+        if let PayloadStatusEnum::Invalid { validation_error } = updated.payload_status.status {
+            bail!("engine rejected the unsafe head {head}: {validation_error}");
+        }
+        let payload_id =
+            updated.payload_id.ok_or_else(|| eyre!("engine did not start a payload build"))?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        if let Some(wait) = Duration::from_secs(timestamp).checked_sub(now) {
+            tokio::time::sleep(wait).await;
+        }
+        let (payload, inserted) = self.seal(head, timestamp, payload_id, external).await?;
+        let (payload, inserted) = if no_tx_pool {
+            (payload, inserted)
+        } else {
+            self.apply_policy(attributes, forkchoice, payload, inserted).await?
+        };
+        ensure!(payload.parent_hash() == head.block_info.hash, "payload does not extend {head}");
+
+        // The conductor must have the block in its log before anyone else sees it, otherwise a
+        // leadership transfer could fork the unsafe chain.
+        if let Some(conductor) = &self.conductor {
+            conductor.commit_unsafe_payload(&payload).await?;
+        }
+
+        let id = payload.block_id();
+        let size = payload.payload.as_v1().transactions.iter().map(|tx| tx.len() as u64).sum();
+        if !inserted {
+            let status = self
+                .engine
+                .new_payload(NewPayloadVersion::from_timestamp(&self.config, timestamp), payload)
+                .await?;
+            if let PayloadStatusEnum::Invalid { validation_error } = status.status {
+                bail!("engine rejected sequenced block {id}: {validation_error}");
+            }
+        }
+        let forkchoice = ForkchoiceState { head_block_hash: id.hash, ..forkchoice };
+        self.engine
+            .forkchoice_updated(
+                ForkchoiceUpdatedVersion::from_attributes_timestamp(&self.config, None),
+                forkchoice,
+                None,
+            )
+            .await?;
+
+        let seq_num = if head.l1_origin.number == origin.number { head.seq_num + 1 } else { 0 };
+        let block = L2BlockInfo::new(
+            BlockInfo::new(id.hash, id.number, head.block_info.hash, timestamp),
+            origin.id(),
+            seq_num,
+        );
+        self.status.send_modify(|status| status.unsafe_l2 = block);
+        counter!("hera_sequencer_blocks_total").increment(1);
+        if let Some(throttle) = &mut self.throttle {
+            let safe = self.status.borrow().safe_l2.block_info.number;
+            throttle.record(id.number, size, safe);
+            throttle.apply().await;
+        }
+        if no_tx_pool {
+            counter!("hera_sequencer_empty_blocks_total").increment(1);
+        }
+        if let Some(audit) = &self.audit {
+            if let Err(err) =
+                audit.record(&AuditEvent::HeadAdvanced { head: HeadKind::Unsafe, block })
+            {
+                warn!(target: "hera::sequencer", %err, "Failed to record audit event");
+            }
+        }
+        Ok(Some(block))
+    }
+
+    /// Returns the payload to seal on top of `head`: the one of the builder if it was built in
+    /// time and the local engine validates it, the local one `payload_id` otherwise. Returns
+    /// whether the payload was already inserted into the local engine.
+    ///
+    /// The builder's payload is inserted to be validated, but stays unknown to anyone else until
+    /// the forkchoice is updated to it.
+    async fn seal(
+        &self,
+        head: L2BlockInfo,
+        timestamp: u64,
+        payload_id: PayloadId,
+        external: Option<Result<PayloadId, BuilderError>>,
+    ) -> Result<(EnginePayload, bool)> {
+        let version = GetPayloadVersion::from_timestamp(&self.config, timestamp);
+        let local = self.engine.get_payload(version, payload_id);
+        let Some((builder, external)) = self.builder.as_ref().zip(external) else {
+            counter!("hera_sequencer_payloads_total", "source" => "local").increment(1);
+            return Ok((local.await?, false));
+        };
+
+        let head = head.block_info.hash;
+        let (local, external) =
+            tokio::join!(local, async { builder.get_payload(version, external?, head).await });
+        let external = match external {
+            Ok(payload) => {
+                let version = NewPayloadVersion::from_timestamp(&self.config, timestamp);
+                match self.engine.new_payload(version, payload.clone()).await {
+                    Ok(status) if status.status.is_valid() => Ok(payload),
+                    Ok(status) => match status.status {
+                        PayloadStatusEnum::Invalid { validation_error } => {
+                            Err(BuilderError::Invalid(validation_error))
+                        }
+                        status => Err(BuilderError::Invalid(format!("status {status}"))),
+                    },
+                    Err(err) => Err(BuilderError::LocalEngine(err)),
+                }
+            }
+            Err(err) => Err(err),
+        };
+        match external {
+            Ok(payload) => {
+                counter!("hera_sequencer_payloads_total", "source" => "builder").increment(1);
+                Ok((payload, true))
+            }
+            Err(err) => {
+                builder.fail(err)?;
+                counter!("hera_sequencer_payloads_total", "source" => "local").increment(1);
+                Ok((local?, false))
+            }
+        }
+    }
+
+    /// Applies the transaction policy to the pool transactions of `payload`, building the block
+    /// again with the deposits of `attributes` followed by the selected transactions if the
+    /// policy changed them. Returns whether the payload was already inserted into the local
+    /// engine.
+    async fn apply_policy(
+        &self,
+        mut attributes: OptimismPayloadAttributes,
+        forkchoice: ForkchoiceState,
+        payload: EnginePayload,
+        inserted: bool,
+    ) -> Result<(EnginePayload, bool)> {
+        let block = payload.payload.as_v1();
+        let candidates: Vec<_> = block
+            .transactions
+            .iter()
+            .filter(|tx| tx.first() != Some(&DEPOSIT_TX_TYPE))
+            .cloned()
+            .collect();
+        let selected = self.policy.select(block, candidates.clone());
+        if selected == candidates {
+            return Ok((payload, inserted));
+        }
+
+        let timestamp = block.timestamp;
+        debug!(
+            target: "hera::sequencer",
+            candidates = candidates.len(),
+            selected = selected.len(),
+            "Rebuilding block with the transactions selected by the policy"
+        );
+        counter!("hera_sequencer_policy_rebuilds_total").increment(1);
+        let mut transactions = attributes.transactions.take().unwrap_or_default();
+        transactions.extend(selected);
+        attributes.transactions = Some(transactions);
+        attributes.no_tx_pool = Some(true);
+        let version =
+            ForkchoiceUpdatedVersion::from_attributes_timestamp(&self.config, Some(timestamp));
+        let updated = self.engine.forkchoice_updated(version, forkchoice, Some(attributes)).await?;
+        if let PayloadStatusEnum::Invalid { validation_error } = updated.payload_status.status {
+            bail!(
+                "engine rejected the rebuild of block {}: {validation_error}",
+                block.block_number
+            );
+        }
+        let payload_id =
+            updated.payload_id.ok_or_else(|| eyre!("engine did not start a payload build"))?;
+        let version = GetPayloadVersion::from_timestamp(&self.config, timestamp);
+        Ok((self.engine.get_payload(version, payload_id).await?, false))
+    }
+
+    /// Selects the L1 origin of the block at `timestamp` following `head`: the next L1 block
+    /// once its timestamp is reached, the current origin otherwise.
+    ///
+    /// Past the maximum sequencer drift the next L1 block must be adopted as soon as its
+    /// timestamp is reached. Sequencing stalls until it is known, since only then can an empty
+    /// block keeping the current origin be proven valid.
+    async fn select_origin(&self, head: L2BlockInfo, timestamp: u64) -> Result<BlockInfo> {
+        let current = self.l1.block_info_by_number(head.l1_origin.number).await?;
+        ensure!(
+            current.hash == head.l1_origin.hash,
+            "L1 origin {} of the unsafe head was reorged",
+            head.l1_origin
+        );
+        let next = self.l1.block_info_by_number(current.number + 1).await.ok();
+        let drift = self.config.max_sequencer_drift(current.timestamp);
+        match next {
+            Some(next) if timestamp >= next.timestamp => {
+                ensure!(next.parent_hash == current.hash, "L1 reorg at {}", next.id());
+                Ok(next)
+            }
+            None if timestamp > current.timestamp + drift => {
+                bail!("sequencer drift exceeded, next L1 origin after {current} is not available")
+            }
+            _ => Ok(current),
+        }
+    }
+
+    /// Returns whether the block at `timestamp` with L1 origin `origin` must be built without
+    /// transactions from the pool, entering or leaving recovery as needed.
+    ///
+    /// Blocks past the sequencer drift of their origin may only contain deposits. Blocks lagging
+    /// the wall clock by more than the drift are also kept empty, as the sequencer is then
+    /// catching up after a downtime rather than serving users.
+    fn update_recovery(&mut self, origin: BlockInfo, timestamp: u64) -> bool {
+        let drift = self.config.max_sequencer_drift(origin.timestamp);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let past_drift = timestamp > origin.timestamp + drift;
+        let lagging = now > timestamp + drift;
+
+        let recovering = past_drift || lagging;
+        if recovering != self.recovering {
+            if recovering {
+                warn!(target: "hera::sequencer", timestamp, %origin, "Sequencer recovering, building empty blocks");
+            } else {
+                info!(target: "hera::sequencer", timestamp, %origin, "Sequencer recovered");
+            }
+            self.recovering = recovering;
+        }
+        gauge!("hera_sequencer_recovering").set(if recovering { 1.0 } else { 0.0 });
+        recovering
+    }
+}
+
+/// Starts and stops a [`Sequencer`], as done by op-conductor on leadership transfers.
+#[derive(Debug, Clone)]
+pub struct SequencerHandle {
+    active: watch::Sender<bool>,
+    status: watch::Sender<SyncStatus>,
+}
+
+impl SequencerHandle {
+    /// Returns whether the sequencer is active.
+    pub fn is_active(&self) -> bool {
+        *self.active.borrow()
+    }
+
+    /// Starts sequencing on top of the unsafe head, which must be `head`.
+    pub fn start(&self, head: B256) -> Result<()> {
+        ensure!(!self.is_active(), "sequencer already running");
+        let unsafe_head = self.status.borrow().unsafe_l2;
+        ensure!(
+            unsafe_head.block_info.hash == head,
+            "block hash {head} does not match the unsafe head {unsafe_head}"
+        );
+        info!(target: "hera::sequencer", %unsafe_head, "Starting sequencer");
+        self.active.send_replace(true);
+        Ok(())
+    }
+
+    /// Stops sequencing, returning the hash of the last unsafe block.
+    pub fn stop(&self) -> Result<B256> {
+        ensure!(self.is_active(), "sequencer not running");
+        self.active.send_replace(false);
+        let unsafe_head = self.status.borrow().unsafe_l2;
+        info!(target: "hera::sequencer", %unsafe_head, "Stopped sequencer");
+        Ok(unsafe_head.block_info.hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use alloy_primitives::{Address, Bytes};
+    use alloy_rpc_types_engine::{ExecutionPayload, ExecutionPayloadV1, PayloadAttributes};
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::{
+        config::SystemConfig,
+        engine::{
+            mock::{block_hash, payload, EngineCall, MockEngine},
+            EngineError,
+        },
+        l1::mock::MockL1,
+        protocol::BlockId,
+    };
+
+    /// Prepares attributes without any transaction.
+    #[derive(Debug)]
+    struct EmptyAttributes;
+
+    #[async_trait]
+    impl AttributesBuilder for EmptyAttributes {
+        async fn prepare_payload_attributes(
+            &mut self,
+            parent: L2BlockInfo,
+            _: BlockId,
+        ) -> Result<OptimismPayloadAttributes> {
+            Ok(OptimismPayloadAttributes {
+                payload_attributes: PayloadAttributes {
+                    timestamp: parent.block_info.timestamp + 2,
+                    prev_randao: B256::ZERO,
+                    suggested_fee_recipient: Address::ZERO,
+                    withdrawals: None,
+                    parent_beacon_block_root: None,
+                },
+                transactions: None,
+                no_tx_pool: None,
+                gas_limit: None,
+            })
+        }
+
+        fn reset(&mut self, _: SystemConfig) {}
+    }
+
+    #[derive(Debug, Default)]
+    struct MockConductor {
+        follower: bool,
+        failing: bool,
+        commits: Mutex<Vec<BlockId>>,
+    }
+
+    #[async_trait]
+    impl Conductor for MockConductor {
+        async fn leader(&self) -> Result<bool> {
+            Ok(!self.follower)
+        }
+
+        async fn commit_unsafe_payload(&self, payload: &EnginePayload) -> Result<()> {
+            ensure!(!self.failing, "conductor unavailable");
+            self.commits.lock().unwrap().push(payload.block_id());
+            Ok(())
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    /// A sequencer whose unsafe head is the test block 4 at `timestamp`, with the first block of
+    /// `l1` as its L1 origin. The engine seals the test block 5.
+    fn sequencer(l1: &Arc<MockL1>, timestamp: u64) -> (Sequencer, Arc<MockEngine>) {
+        sequencer_with(l1, timestamp, [payload(5)])
+    }
+
+    /// A sequencer as [`sequencer`], whose engine seals `payloads` in order.
+    fn sequencer_with(
+        l1: &Arc<MockL1>,
+        timestamp: u64,
+        payloads: impl IntoIterator<Item = EnginePayload>,
+    ) -> (Sequencer, Arc<MockEngine>) {
+        let head = L2BlockInfo::new(
+            BlockInfo::new(block_hash(4), 4, block_hash(3), timestamp),
+            l1.block(0).id(),
+            1,
+        );
+        let status = watch::Sender::new(SyncStatus { unsafe_l2: head, ..Default::default() });
+        let engine = Arc::new(MockEngine::new());
+        for payload in payloads {
+            engine.serve_payload(payload);
+        }
+        let sequencer = Sequencer::new(
+            Arc::new(RollupConfig::from_registry(10).unwrap()),
+            engine.clone(),
+            Box::new(EmptyAttributes),
+            l1.clone(),
+            status,
+        );
+        (sequencer, engine)
+    }
+
+    fn forkchoice(head: B256) -> ForkchoiceState {
+        ForkchoiceState { head_block_hash: head, ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn builds_on_the_unsafe_head() {
+        let l1 = Arc::new(MockL1::new(now() - 24, 3));
+        let (mut sequencer, engine) = sequencer(&l1, now() - 20);
+        let head = sequencer.status.borrow().unsafe_l2;
+
+        let block = sequencer.build_block().await.unwrap().unwrap();
+        let timestamp = head.block_info.timestamp + 2;
+        let expected = BlockInfo::new(block_hash(5), 5, block_hash(4), timestamp);
+        assert_eq!(block, L2BlockInfo::new(expected, l1.block(0).id(), 2));
+        assert_eq!(sequencer.status.borrow().unsafe_l2, block);
+
+        assert_eq!(
+            engine.calls(),
+            [
+                EngineCall::ForkchoiceUpdated(forkchoice(block_hash(4))),
+                EngineCall::GetPayload(PayloadId::new(1u64.to_be_bytes())),
+                EngineCall::NewPayload(payload(5).block_id()),
+                EngineCall::ForkchoiceUpdated(forkchoice(block_hash(5))),
+            ]
+        );
+        assert_eq!(engine.attributes()[0].no_tx_pool, Some(false));
+    }
+
+    #[tokio::test]
+    async fn adopts_the_next_origin_once_reached() {
+        let l1 = Arc::new(MockL1::new(now() - 24, 3));
+        let (mut sequencer, _) = sequencer(&l1, l1.block(1).timestamp - 2);
+
+        let block = sequencer.build_block().await.unwrap().unwrap();
+        assert_eq!(block.l1_origin, l1.block(1).id());
+        assert_eq!(block.seq_num, 0);
+    }
+
+    #[tokio::test]
+    async fn builds_empty_blocks_while_recovering() {
+        let l1 = Arc::new(MockL1::new(now() - 10_000, 3));
+        let (mut sequencer, engine) = sequencer(&l1, l1.block(0).timestamp);
+
+        sequencer.build_block().await.unwrap().unwrap();
+        assert_eq!(engine.attributes()[0].no_tx_pool, Some(true));
+        assert!(sequencer.recovering);
+    }
+
+    #[tokio::test]
+    async fn stalls_past_the_drift_without_the_next_origin() {
+        let l1 = Arc::new(MockL1::new(now() - 10_000, 1));
+        let origin = l1.block(0).timestamp;
+        let drift = RollupConfig::from_registry(10).unwrap().max_sequencer_drift(origin);
+        let (mut sequencer, engine) = sequencer(&l1, origin + drift);
+
+        let err = sequencer.build_block().await.unwrap_err();
+        assert!(err.to_string().contains("sequencer drift exceeded"), "{err}");
+        assert!(engine.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_a_reorged_origin() {
+        let l1 = Arc::new(MockL1::new(now() - 24, 3));
+        let (mut sequencer, _) = sequencer(&l1, now() - 20);
+        sequencer.status.send_modify(|status| status.unsafe_l2.l1_origin.hash = B256::ZERO);
+
+        let err = sequencer.build_block().await.unwrap_err();
+        assert!(err.to_string().contains("was reorged"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn only_the_leader_builds_and_commits_before_inserting() {
+        let l1 = Arc::new(MockL1::new(now() - 24, 3));
+
+        let (sequencer, engine) = sequencer(&l1, now() - 20);
+        let follower = Arc::new(MockConductor { follower: true, ..Default::default() });
+        let mut sequencer = sequencer.with_conductor(follower);
+        assert_eq!(sequencer.build_block().await.unwrap(), None);
+        assert!(engine.calls().is_empty());
+
+        let (sequencer, engine) = self::sequencer(&l1, now() - 20);
+        let failing = Arc::new(MockConductor { failing: true, ..Default::default() });
+        let mut sequencer = sequencer.with_conductor(failing);
+        let head = sequencer.status.borrow().unsafe_l2;
+        assert!(sequencer.build_block().await.is_err());
+        assert!(!engine.calls().iter().any(|call| matches!(call, EngineCall::NewPayload(_))));
+        assert_eq!(sequencer.status.borrow().unsafe_l2, head);
+
+        let (sequencer, _) = self::sequencer(&l1, now() - 20);
+        let leader = Arc::new(MockConductor::default());
+        let mut sequencer = sequencer.with_conductor(leader.clone());
+        sequencer.build_block().await.unwrap().unwrap();
+        assert_eq!(*leader.commits.lock().unwrap(), [payload(5).block_id()]);
+    }
+
+    /// The test block 5 as built by an external builder.
+    fn builder_payload() -> EnginePayload {
+        let mut payload = payload(5);
+        if let ExecutionPayload::V1(payload) = &mut payload.payload {
+            payload.block_hash = B256::repeat_byte(0xbb);
+        }
+        payload
+    }
+
+    /// A builder serving [`builder_payload`] after `latency`, with the given fallback policy.
+    fn external_builder(latency: Duration, fallback: BuilderFallback) -> ExternalBuilder {
+        let engine = MockEngine::new().with_latency(latency);
+        engine.serve_payload(builder_payload());
+        ExternalBuilder::new(Arc::new(engine))
+            .with_timeout(Duration::from_millis(100))
+            .with_fallback(fallback)
+    }
+
+    #[tokio::test]
+    async fn seals_the_payload_of_the_builder() {
+        let l1 = Arc::new(MockL1::new(now() - 24, 3));
+        let (sequencer, engine) = sequencer(&l1, now() - 20);
+        let builder = external_builder(Duration::ZERO, BuilderFallback::Local);
+        let mut sequencer = sequencer.with_builder(builder);
+
+        let block = sequencer.build_block().await.unwrap().unwrap();
+        let id = builder_payload().block_id();
+        assert_eq!(block.block_info.hash, id.hash);
+        assert_eq!(
+            engine.calls(),
+            [
+                EngineCall::ForkchoiceUpdated(forkchoice(block_hash(4))),
+                EngineCall::GetPayload(PayloadId::new(1u64.to_be_bytes())),
+                EngineCall::NewPayload(id),
+                EngineCall::ForkchoiceUpdated(forkchoice(id.hash)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_local_payload() {
+        let l1 = Arc::new(MockL1::new(now() - 24, 3));
+
+        // The builder is too slow.
+        let (sequencer, _) = sequencer(&l1, now() - 20);
+        let builder = external_builder(Duration::from_secs(1), BuilderFallback::Local);
+        let mut sequencer = sequencer.with_builder(builder);
+        let block = sequencer.build_block().await.unwrap().unwrap();
+        assert_eq!(block.block_info.hash, block_hash(5));
+
+        // The engine rejects the payload of the builder.
+        let (sequencer, engine) = self::sequencer(&l1, now() - 20);
+        engine.respond(Ok(PayloadStatusEnum::Valid));
+        engine.respond(Ok(PayloadStatusEnum::Invalid { validation_error: "bad".to_string() }));
+        let mut sequencer =
+            sequencer.with_builder(external_builder(Duration::ZERO, BuilderFallback::Local));
+        let block = sequencer.build_block().await.unwrap().unwrap();
+        assert_eq!(block.block_info.hash, block_hash(5));
+        assert_eq!(
+            engine.calls()[2..4],
+            [
+                EngineCall::NewPayload(builder_payload().block_id()),
+                EngineCall::NewPayload(payload(5).block_id()),
+            ]
+        );
+
+        // The engine does not validate the payload of the builder, or fails to answer.
+        for response in [
+            Ok(PayloadStatusEnum::Syncing),
+            Ok(PayloadStatusEnum::Accepted),
+            Err(EngineError::Transport(eyre::eyre!("connection reset"))),
+        ] {
+            let (sequencer, engine) = self::sequencer(&l1, now() - 20);
+            engine.respond(Ok(PayloadStatusEnum::Valid));
+            engine.respond(response);
+            let mut sequencer =
+                sequencer.with_builder(external_builder(Duration::ZERO, BuilderFallback::Local));
+            let block = sequencer.build_block().await.unwrap().unwrap();
+            assert_eq!(block.block_info.hash, block_hash(5));
+        }
+
+        // Without fallback, the block fails.
+        let (sequencer, engine) = self::sequencer(&l1, now() - 20);
+        let builder = external_builder(Duration::from_secs(1), BuilderFallback::None);
+        let mut sequencer = sequencer.with_builder(builder);
+        let head = sequencer.status.borrow().unsafe_l2;
+        let err = sequencer.build_block().await.unwrap_err();
+        assert!(err.to_string().contains("builder timed out"), "{err}");
+        assert!(!engine.calls().iter().any(|call| matches!(call, EngineCall::NewPayload(_))));
+        assert_eq!(sequencer.status.borrow().unsafe_l2, head);
+    }
+
+    /// Drops the transactions in the denylist.
+    #[derive(Debug)]
+    struct Denylist(Vec<Bytes>);
+
+    impl TransactionPolicy for Denylist {
+        fn select(&self, _: &ExecutionPayloadV1, candidates: Vec<Bytes>) -> Vec<Bytes> {
+            candidates.into_iter().filter(|tx| !self.0.contains(tx)).collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn rebuilds_blocks_with_the_transactions_selected_by_the_policy() {
+        let l1 = Arc::new(MockL1::new(now() - 24, 3));
+        let (allowed, denied) = (Bytes::from([0x02, 0xaa]), Bytes::from([0x02, 0xbb]));
+        let with_transactions = |hash, transactions: &[&Bytes]| {
+            let mut payload = payload(5);
+            if let ExecutionPayload::V1(payload) = &mut payload.payload {
+                payload.block_hash = hash;
+                payload.transactions.extend(transactions.iter().copied().cloned());
+            }
+            payload
+        };
+        let built = with_transactions(block_hash(5), &[&denied, &allowed]);
+        let rebuilt = with_transactions(B256::repeat_byte(0xcc), &[&allowed]);
+        let (sequencer, engine) = sequencer_with(&l1, now() - 20, [built, rebuilt.clone()]);
+        let policy = Arc::new(Denylist(vec![denied]));
+        let mut sequencer = sequencer.with_transaction_policy(policy);
+
+        let block = sequencer.build_block().await.unwrap().unwrap();
+        assert_eq!(block.block_info.hash, rebuilt.block_id().hash);
+        let attributes = engine.attributes();
+        assert_eq!(attributes[1].transactions, Some(vec![allowed]));
+        assert_eq!(attributes[1].no_tx_pool, Some(true));
+        assert_eq!(
+            engine.calls()[2..],
+            [
+                EngineCall::ForkchoiceUpdated(forkchoice(block_hash(4))),
+                EngineCall::GetPayload(PayloadId::new(2u64.to_be_bytes())),
+                EngineCall::NewPayload(rebuilt.block_id()),
+                EngineCall::ForkchoiceUpdated(forkchoice(rebuilt.block_id().hash)),
+            ]
+        );
+
+        // Blocks the policy keeps as built are sealed right away.
+        let (sequencer, engine) = sequencer_with(&l1, now() - 20, [payload(5)]);
+        let mut sequencer = sequencer.with_transaction_policy(Arc::new(Denylist(Vec::new())));
+        sequencer.build_block().await.unwrap().unwrap();
+        assert_eq!(engine.attributes().len(), 1);
+    }
+
+    #[test]
+    fn parses_builder_fallbacks() {
+        for fallback in [BuilderFallback::Local, BuilderFallback::None] {
+            assert_eq!(fallback.to_string().parse::<BuilderFallback>().unwrap(), fallback);
+        }
+        assert!("remote".parse::<BuilderFallback>().is_err());
+    }
+
+    #[test]
+    fn handle_starts_and_stops_on_the_unsafe_head() {
+        let l1 = Arc::new(MockL1::new(0, 1));
+        let (sequencer, _) = sequencer(&l1, 0);
+        let handle = sequencer.with_active(false).handle();
+
+        assert!(handle.stop().is_err());
+        let err = handle.start(B256::ZERO).unwrap_err();
+        assert!(err.to_string().contains("does not match the unsafe head"), "{err}");
+        handle.start(block_hash(4)).unwrap();
+        assert!(handle.is_active());
+        assert!(handle.start(block_hash(4)).is_err());
+        assert_eq!(handle.stop().unwrap(), block_hash(4));
+        assert!(!handle.is_active());
+    }
+}
@@ -0,0 +1,28 @@
+//! Filtering and ordering of the transactions of sequenced blocks.
+
+use std::fmt;
+
+use alloy_primitives::Bytes;
+use alloy_rpc_types_engine::ExecutionPayloadV1;
+
+/// Selects the transactions of sequenced blocks out of the candidates the engine picked from its
+/// pool, e.g. to enforce a denylist or a fee floor.
+///
+/// Candidates are the EIP-2718 encoded pool transactions of the block as built by the engine,
+/// deposits excluded. When the policy returns anything else, the block is built again with
+/// exactly the deposits followed by the selected transactions, so a policy may drop, reorder
+/// and insert transactions, but the engine fails the block if any of them is invalid.
+pub trait TransactionPolicy: fmt::Debug + Send + Sync {
+    /// Returns the transactions to include in `block`, in order, out of `candidates`.
+    fn select(&self, block: &ExecutionPayloadV1, candidates: Vec<Bytes>) -> Vec<Bytes>;
+}
+
+/// A policy including the transactions picked by the engine as they are.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PassthroughPolicy;
+
+impl TransactionPolicy for PassthroughPolicy {
+    fn select(&self, _: &ExecutionPayloadV1, candidates: Vec<Bytes>) -> Vec<Bytes> {
+        candidates
+    }
+}
@@ -0,0 +1,231 @@
+//! Throttling of block building on the L1 data availability backlog.
+
+use std::collections::VecDeque;
+
+use alloy_primitives::U64;
+use async_trait::async_trait;
+use eyre::{ensure, Result, WrapErr};
+use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+use metrics::gauge;
+use tracing::{info, warn};
+
+use crate::rpc::MinerApiClient;
+
+/// Default backlog past which block building is throttled, in bytes of unsafe transactions.
+pub const DEFAULT_THROTTLE_THRESHOLD: u64 = 1_000_000;
+
+/// Default maximum size of a transaction's data while throttled, as in op-batcher.
+pub const DEFAULT_THROTTLE_TX_SIZE: u64 = 300;
+
+/// Default maximum size of a block's transaction data while throttled, as in op-batcher.
+pub const DEFAULT_THROTTLE_BLOCK_SIZE: u64 = 21_000;
+
+/// Limits the data availability size of the transactions the execution client includes in the
+/// blocks it builds.
+#[async_trait]
+pub trait DaLimiter: std::fmt::Debug + Send + Sync {
+    /// Sets the maximum size of the data of a single transaction and of all transactions of a
+    /// block, 0 meaning unlimited.
+    async fn set_max_da_size(&self, max_tx_size: u64, max_block_size: u64) -> Result<()>;
+}
+
+/// A [`DaLimiter`] calling `miner_setMaxDASize` on the RPC of the sequencer's execution client,
+/// as op-batcher does.
+#[derive(Debug)]
+pub struct MinerClient {
+    client: HttpClient,
+}
+
+impl MinerClient {
+    /// Creates a client of the execution client RPC at `url`.
+    pub fn new(url: &str) -> Result<Self> {
+        let client = HttpClientBuilder::default()
+            .build(url)
+            .wrap_err_with(|| format!("invalid execution client RPC URL {url}"))?;
+        Ok(Self { client })
+    }
+}
+
+#[async_trait]
+impl DaLimiter for MinerClient {
+    async fn set_max_da_size(&self, max_tx_size: u64, max_block_size: u64) -> Result<()> {
+        let set = MinerApiClient::set_max_da_size(
+            &self.client,
+            U64::from(max_tx_size),
+            U64::from(max_block_size),
+        )
+        .await
+        .wrap_err("miner_setMaxDASize failed")?;
+        ensure!(set, "execution client refused the maximum DA size");
+        Ok(())
+    }
+}
+
+/// Thresholds and limits of a [`DaThrottle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThrottleConfig {
+    /// Backlog past which block building is throttled, in bytes of unsafe transactions.
+    pub threshold: u64,
+    /// Maximum size of a transaction's data while throttled.
+    pub max_tx_size: u64,
+    /// Maximum size of a block's transaction data while throttled.
+    pub max_block_size: u64,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_THROTTLE_THRESHOLD,
+            max_tx_size: DEFAULT_THROTTLE_TX_SIZE,
+            max_block_size: DEFAULT_THROTTLE_BLOCK_SIZE,
+        }
+    }
+}
+
+/// Throttles the transactions included in sequenced blocks while the batcher lags behind, so
+/// that the L1 data availability costs of catching up stay bounded.
+///
+/// The backlog is the transaction data of the sequenced blocks that are not safe yet, i.e. not
+/// yet posted by the batcher and derived back. Past the threshold, large transactions are left
+/// out and block data is capped through the [`DaLimiter`], until the backlog is back under the
+/// threshold. The gas limit is left alone: it is part of the derived attributes, and lowering it
+/// would make the unsafe blocks diverge from the safe chain.
+#[derive(Debug)]
+pub struct DaThrottle {
+    config: ThrottleConfig,
+    limiter: Box<dyn DaLimiter>,
+    pending: VecDeque<(u64, u64)>,
+    backlog: u64,
+    applied: Option<bool>,
+}
+
+impl DaThrottle {
+    /// Creates a throttle applying its limits through `limiter`.
+    pub fn new(config: ThrottleConfig, limiter: Box<dyn DaLimiter>) -> Self {
+        Self { config, limiter, pending: VecDeque::new(), backlog: 0, applied: None }
+    }
+
+    /// Returns the backlog, in bytes of unsafe transactions.
+    pub const fn backlog(&self) -> u64 {
+        self.backlog
+    }
+
+    /// Records the sequenced block `number` with `size` bytes of transactions, and forgets the
+    /// blocks up to the safe head `safe`.
+    pub fn record(&mut self, number: u64, size: u64, safe: u64) {
+        // Blocks at or past a new block were reorged out.
+        while self.pending.back().is_some_and(|(pending, _)| *pending >= number) {
+            self.backlog -= self.pending.pop_back().map_or(0, |(_, size)| size);
+        }
+        self.pending.push_back((number, size));
+        self.backlog += size;
+        while self.pending.front().is_some_and(|(pending, _)| *pending <= safe) {
+            self.backlog -= self.pending.pop_front().map_or(0, |(_, size)| size);
+        }
+        gauge!("hera_sequencer_da_backlog_bytes").set(self.backlog as f64);
+    }
+
+    /// Applies or lifts the limits according to the backlog, if they changed. Limits that failed
+    /// to apply are retried on the next call.
+    pub async fn apply(&mut self) {
+        let throttled = self.backlog > self.config.threshold;
+        if self.applied == Some(throttled) {
+            return;
+        }
+        let (max_tx_size, max_block_size) =
+            if throttled { (self.config.max_tx_size, self.config.max_block_size) } else { (0, 0) };
+        if let Err(err) = self.limiter.set_max_da_size(max_tx_size, max_block_size).await {
+            warn!(target: "hera::sequencer", %err, throttled, "Failed to update the DA throttle");
+            return;
+        }
+        if throttled {
+            warn!(
+                target: "hera::sequencer",
+                backlog = self.backlog,
+                threshold = self.config.threshold,
+                max_tx_size,
+                max_block_size,
+                "DA backlog over threshold, throttling transactions"
+            );
+        } else if self.applied.is_some() {
+            info!(target: "hera::sequencer", backlog = self.backlog, "DA backlog cleared, throttling lifted");
+        }
+        gauge!("hera_sequencer_throttled").set(if throttled { 1.0 } else { 0.0 });
+        self.applied = Some(throttled);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Records the limits it is set to, failing while `failing` is set.
+    #[derive(Debug, Default)]
+    struct MockLimiter {
+        failing: Mutex<bool>,
+        limits: Mutex<Vec<(u64, u64)>>,
+    }
+
+    #[async_trait]
+    impl DaLimiter for Arc<MockLimiter> {
+        async fn set_max_da_size(&self, max_tx_size: u64, max_block_size: u64) -> Result<()> {
+            ensure!(!*self.failing.lock().unwrap(), "execution client unavailable");
+            self.limits.lock().unwrap().push((max_tx_size, max_block_size));
+            Ok(())
+        }
+    }
+
+    fn throttle() -> (DaThrottle, Arc<MockLimiter>) {
+        let limiter = Arc::new(MockLimiter::default());
+        let config = ThrottleConfig { threshold: 100, max_tx_size: 10, max_block_size: 50 };
+        (DaThrottle::new(config, Box::new(limiter.clone())), limiter)
+    }
+
+    #[test]
+    fn backlog_drops_reorged_and_safe_blocks() {
+        let (mut throttle, _) = throttle();
+        throttle.record(1, 10, 0);
+        throttle.record(2, 20, 0);
+        throttle.record(3, 30, 0);
+        assert_eq!(throttle.backlog(), 60);
+
+        // Block 2 is rebuilt, so blocks 2 and 3 were reorged out.
+        throttle.record(2, 5, 0);
+        assert_eq!(throttle.backlog(), 15);
+
+        throttle.record(3, 40, 2);
+        assert_eq!(throttle.backlog(), 40);
+        throttle.record(4, 1, 4);
+        assert_eq!(throttle.backlog(), 0);
+    }
+
+    #[tokio::test]
+    async fn applies_limits_on_change_only() {
+        let (mut throttle, limiter) = throttle();
+        throttle.apply().await;
+        throttle.apply().await;
+        assert_eq!(*limiter.limits.lock().unwrap(), [(0, 0)]);
+
+        throttle.record(1, 101, 0);
+        throttle.apply().await;
+        throttle.apply().await;
+        throttle.record(2, 1, 1);
+        throttle.apply().await;
+        assert_eq!(*limiter.limits.lock().unwrap(), [(0, 0), (10, 50), (0, 0)]);
+    }
+
+    #[tokio::test]
+    async fn retries_failed_limits() {
+        let (mut throttle, limiter) = throttle();
+        throttle.record(1, 101, 0);
+        *limiter.failing.lock().unwrap() = true;
+        throttle.apply().await;
+        assert!(limiter.limits.lock().unwrap().is_empty());
+
+        *limiter.failing.lock().unwrap() = false;
+        throttle.apply().await;
+        assert_eq!(*limiter.limits.lock().unwrap(), [(10, 50)]);
+    }
+}
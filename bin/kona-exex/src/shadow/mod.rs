@@ -0,0 +1,304 @@
+//! Shadow mode: continuous comparison of Hera's chain view against an op-node instance.
+//!
+//! The [`ShadowComparator`] polls the op-node's sync status and, whenever op-node has reached
+//! Hera's safe or finalized head, checks that both agree on the block hash and, if Hera can
+//! compute it, the output root. Divergences are reported as metrics and structured logs.
+
+use std::{sync::Arc, time::Duration};
+
+use alloy_primitives::{B256, U64};
+use async_trait::async_trait;
+use eyre::{Result, WrapErr};
+use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+use metrics::{counter, gauge};
+use tokio::sync::watch;
+use tracing::{debug, error, warn};
+
+use crate::{
+    alert::{Alert, WebhookAlerter},
+    protocol::L2BlockInfo,
+    rpc::{RollupNodeApiClient, SyncStatus},
+};
+
+/// A source of Hera's own output roots.
+#[async_trait]
+pub trait OutputRootSource: std::fmt::Debug + Send + Sync {
+    /// Returns the output root at the given L2 block.
+    async fn output_root_at(&self, block: &L2BlockInfo) -> Result<B256>;
+}
+
+/// A divergence between Hera and op-node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Divergence {
+    /// The nodes disagree on the hash of a safe or finalized block.
+    BlockHash {
+        /// The head being compared, `safe` or `finalized`.
+        head: &'static str,
+        /// The block number.
+        number: u64,
+        /// Hera's block hash.
+        local: B256,
+        /// op-node's block hash.
+        remote: B256,
+    },
+    /// The nodes disagree on the output root of a block.
+    OutputRoot {
+        /// The head being compared, `safe` or `finalized`.
+        head: &'static str,
+        /// The block number.
+        number: u64,
+        /// Hera's output root.
+        local: B256,
+        /// op-node's output root.
+        remote: B256,
+    },
+}
+
+/// Compares Hera's heads against an op-node instance.
+#[derive(Debug)]
+pub struct ShadowComparator {
+    client: HttpClient,
+    local: watch::Receiver<SyncStatus>,
+    output_roots: Option<Arc<dyn OutputRootSource>>,
+    interval: Duration,
+    alerter: Option<Arc<WebhookAlerter>>,
+    alerted: Vec<Divergence>,
+}
+
+impl ShadowComparator {
+    /// Creates a comparator against the op-node RPC at `url`, following Hera's status through
+    /// `local`.
+    pub fn new(url: &str, local: watch::Receiver<SyncStatus>, interval: Duration) -> Result<Self> {
+        let client = HttpClientBuilder::default()
+            .build(url)
+            .wrap_err_with(|| format!("invalid op-node URL {url}"))?;
+        Ok(Self { client, local, output_roots: None, interval, alerter: None, alerted: Vec::new() })
+    }
+
+    /// Also compares output roots, computed locally by `source`.
+    pub fn with_output_roots(mut self, source: Arc<dyn OutputRootSource>) -> Self {
+        self.output_roots = Some(source);
+        self
+    }
+
+    /// Alerts every new divergence through `alerter`.
+    pub fn with_alerter(mut self, alerter: Arc<WebhookAlerter>) -> Self {
+        self.alerter = Some(alerter);
+        self
+    }
+
+    /// Runs the comparison every interval, forever.
+    pub async fn run(mut self) {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            if let Err(err) = self.compare().await {
+                warn!(target: "hera::shadow", %err, "Shadow comparison failed");
+                counter!("hera_shadow_errors_total").increment(1);
+            }
+        }
+    }
+
+    /// Compares the current heads once, returning the divergences found.
+    pub async fn compare(&mut self) -> Result<Vec<Divergence>> {
+        let remote = self.client.sync_status().await.wrap_err("failed to fetch op-node status")?;
+        let local = self.local.borrow_and_update().clone();
+
+        gauge!("hera_shadow_safe_lag_blocks")
+            .set(remote.safe_l2.block_info.number as f64 - local.safe_l2.block_info.number as f64);
+        gauge!("hera_shadow_finalized_lag_blocks").set(
+            remote.finalized_l2.block_info.number as f64 -
+                local.finalized_l2.block_info.number as f64,
+        );
+
+        let mut divergences = Vec::new();
+        for (head, local, remote) in [
+            ("safe", local.safe_l2, remote.safe_l2),
+            ("finalized", local.finalized_l2, remote.finalized_l2),
+        ] {
+            let number = local.block_info.number;
+            if number == 0 || remote.block_info.number < number {
+                // op-node has not reached this head yet, nothing to compare.
+                continue;
+            }
+            divergences.extend(self.compare_block(head, &local).await?);
+        }
+
+        for divergence in &divergences {
+            let kind = match divergence {
+                Divergence::BlockHash { .. } => "block_hash",
+                Divergence::OutputRoot { .. } => "output_root",
+            };
+            counter!("hera_shadow_divergences_total", "kind" => kind).increment(1);
+            error!(target: "hera::shadow", ?divergence, "Hera diverged from op-node");
+            if let Some(alerter) = &self.alerter {
+                // The same divergence is found again on every comparison until a head moves.
+                if !self.alerted.contains(divergence) {
+                    alerter.notify(Alert::Divergence(*divergence));
+                }
+            }
+        }
+        self.alerted.clone_from(&divergences);
+        if divergences.is_empty() {
+            debug!(target: "hera::shadow", safe = %local.safe_l2, "Hera agrees with op-node");
+        }
+        Ok(divergences)
+    }
+
+    async fn compare_block(
+        &self,
+        head: &'static str,
+        local: &L2BlockInfo,
+    ) -> Result<Option<Divergence>> {
+        let number = local.block_info.number;
+        let output = self
+            .client
+            .output_at_block(U64::from(number))
+            .await
+            .wrap_err_with(|| format!("failed to fetch op-node output at block {number}"))?;
+
+        if output.block_ref.block_info.hash != local.block_info.hash {
+            return Ok(Some(Divergence::BlockHash {
+                head,
+                number,
+                local: local.block_info.hash,
+                remote: output.block_ref.block_info.hash,
+            }));
+        }
+
+        if let Some(source) = &self.output_roots {
+            let root = source.output_root_at(local).await?;
+            if root != output.output_root {
+                return Ok(Some(Divergence::OutputRoot {
+                    head,
+                    number,
+                    local: root,
+                    remote: output.output_root,
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpsee::{
+        core::RpcResult,
+        server::{Server, ServerHandle},
+    };
+
+    use super::*;
+    use crate::{
+        protocol::{BlockId, BlockInfo},
+        rpc::{OutputResponse, RollupNodeApiServer, SafeHeadResponse},
+    };
+
+    /// An op-node whose safe and finalized heads are both `head`.
+    #[derive(Debug)]
+    struct OpNode {
+        head: L2BlockInfo,
+        output_root: B256,
+    }
+
+    #[async_trait]
+    impl RollupNodeApiServer for OpNode {
+        async fn sync_status(&self) -> RpcResult<SyncStatus> {
+            Ok(SyncStatus { safe_l2: self.head, finalized_l2: self.head, ..Default::default() })
+        }
+
+        async fn output_at_block(&self, block_number: U64) -> RpcResult<OutputResponse> {
+            assert_eq!(block_number.to::<u64>(), self.head.block_info.number);
+            Ok(OutputResponse {
+                version: B256::ZERO,
+                output_root: self.output_root,
+                block_ref: self.head,
+                withdrawal_storage_root: B256::ZERO,
+                state_root: B256::ZERO,
+                sync_status: SyncStatus::default(),
+            })
+        }
+
+        async fn safe_head_at_l1_block(&self, _: U64) -> RpcResult<SafeHeadResponse> {
+            unreachable!("not compared")
+        }
+
+        async fn version(&self) -> RpcResult<String> {
+            Ok("op-node".to_string())
+        }
+    }
+
+    #[derive(Debug)]
+    struct FixedRoot(B256);
+
+    #[async_trait]
+    impl OutputRootSource for FixedRoot {
+        async fn output_root_at(&self, _: &L2BlockInfo) -> Result<B256> {
+            Ok(self.0)
+        }
+    }
+
+    fn block(number: u64, hash: u8) -> L2BlockInfo {
+        let info = BlockInfo::new(B256::repeat_byte(hash), number, B256::ZERO, 0);
+        L2BlockInfo::new(info, BlockId::default(), 0)
+    }
+
+    /// Serves `op_node`, returning a comparator against it following `local`, and the server
+    /// handle keeping it up.
+    async fn comparator(op_node: OpNode, local: SyncStatus) -> (ShadowComparator, ServerHandle) {
+        let server = Server::builder().build("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", server.local_addr().unwrap());
+        let handle = server.start(op_node.into_rpc());
+        let (_, local) = watch::channel(local);
+        (ShadowComparator::new(&url, local, Duration::from_secs(1)).unwrap(), handle)
+    }
+
+    fn status(head: L2BlockInfo) -> SyncStatus {
+        SyncStatus { safe_l2: head, finalized_l2: head, ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn agrees_on_matching_heads() {
+        let op_node = OpNode { head: block(10, 1), output_root: B256::repeat_byte(7) };
+        let (shadow, _server) = comparator(op_node, status(block(10, 1))).await;
+        let mut shadow = shadow.with_output_roots(Arc::new(FixedRoot(B256::repeat_byte(7))));
+        assert_eq!(shadow.compare().await.unwrap(), []);
+    }
+
+    #[tokio::test]
+    async fn detects_block_hash_divergence() {
+        let op_node = OpNode { head: block(10, 2), output_root: B256::repeat_byte(7) };
+        let (mut shadow, _server) = comparator(op_node, status(block(10, 1))).await;
+        let divergence = |head| Divergence::BlockHash {
+            head,
+            number: 10,
+            local: B256::repeat_byte(1),
+            remote: B256::repeat_byte(2),
+        };
+        assert_eq!(shadow.compare().await.unwrap(), [divergence("safe"), divergence("finalized")]);
+    }
+
+    #[tokio::test]
+    async fn detects_output_root_divergence() {
+        let op_node = OpNode { head: block(10, 1), output_root: B256::repeat_byte(7) };
+        let local = SyncStatus { safe_l2: block(10, 1), ..Default::default() };
+        let (shadow, _server) = comparator(op_node, local).await;
+        let mut shadow = shadow.with_output_roots(Arc::new(FixedRoot(B256::repeat_byte(8))));
+        assert_eq!(
+            shadow.compare().await.unwrap(),
+            [Divergence::OutputRoot {
+                head: "safe",
+                number: 10,
+                local: B256::repeat_byte(8),
+                remote: B256::repeat_byte(7),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_heads_op_node_has_not_reached() {
+        let op_node = OpNode { head: block(10, 2), output_root: B256::ZERO };
+        let (mut shadow, _server) = comparator(op_node, status(block(11, 1))).await;
+        assert_eq!(shadow.compare().await.unwrap(), []);
+    }
+}
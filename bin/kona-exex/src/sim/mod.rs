@@ -0,0 +1,440 @@
+//! Deterministic simulation of a node on virtual time, for tests.
+//!
+//! A [`Simulation`] plays a script of L1 blocks being committed and of blob sidecars becoming
+//! available at instants of tokio's paused clock. Committed blocks are notified to a
+//! [`NotificationHandler`] as reth would, derivation reads them through the full pipeline, and
+//! the [`MockEngine`] answers the driver. Timeouts counted in L1 blocks, like channel timeouts
+//! and sequencing windows, and the ones counted in time, like stall detection, then play out the
+//! same way on every run, without waiting for them.
+//!
+//! Simulations run in `#[tokio::test(start_paused = true)]` tests. Channels are still
+//! decompressed on worker threads, which the paused clock does not wait for: scripts mixing
+//! batches with timers of their own are not deterministic.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use alloy_consensus::Header;
+use alloy_eips::eip4844::{kzg_to_versioned_hash, Bytes48};
+use alloy_primitives::{keccak256, B256};
+use alloy_rpc_types_engine::OptimismPayloadAttributes;
+use async_trait::async_trait;
+use eyre::Result;
+use tokio::{
+    sync::watch,
+    time::{sleep_until, Instant},
+};
+
+use crate::{
+    batcher::{ChannelOut, Compression, DataAvailability, TxData},
+    blobs::{BlobFetcher, BlobProvider, BlobSidecar},
+    config::RollupConfig,
+    derive::{DerivationPipeline, L2AttributesWithParent, PipelineBuilder},
+    driver::Driver,
+    engine::mock::MockEngine,
+    exex::{ChainNotification, NotificationHandler, NotificationMode},
+    l1::{mock::MockL1, ChainProvider, L1Receipt, L1Transaction, ProviderError, ProviderResult},
+    protocol::{
+        Batch, BlockInfo, ChannelId, Frame, L1BlockInfoTx, L2BlockInfo, SingleBatch, TxDeposit,
+    },
+    rpc::SyncStatus,
+    validation::{AttributesValidator, ValidationError, ValidationOutcome},
+};
+
+/// A step of a simulation script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Event {
+    /// The L1 block of this number is committed and notified.
+    L1Block(u64),
+    /// The blob sidecars of the L1 block of this number become available.
+    Blobs(u64),
+}
+
+/// An L1 chain built upfront, whose blocks are only served once the script committed them.
+#[derive(Debug)]
+struct ScriptedL1 {
+    chain: MockL1,
+    head: AtomicU64,
+}
+
+impl ScriptedL1 {
+    /// Returns the header of the committed block `hash`.
+    async fn committed(&self, hash: B256) -> ProviderResult<Header> {
+        let header = self.chain.header_by_hash(hash).await?;
+        if header.number > self.head.load(Ordering::SeqCst) {
+            return Err(ProviderError::NotFound(format!("L1 block {hash}")));
+        }
+        Ok(header)
+    }
+}
+
+#[async_trait]
+impl ChainProvider for ScriptedL1 {
+    async fn header_by_hash(&self, hash: B256) -> ProviderResult<Header> {
+        self.committed(hash).await
+    }
+
+    async fn block_info_by_number(&self, number: u64) -> ProviderResult<BlockInfo> {
+        if number > self.head.load(Ordering::SeqCst) {
+            return Err(ProviderError::NotFound(format!("L1 block {number}")));
+        }
+        self.chain.block_info_by_number(number).await
+    }
+
+    async fn receipts_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Receipt>> {
+        self.committed(hash).await?;
+        self.chain.receipts_by_hash(hash).await
+    }
+
+    async fn transactions_by_hash(&self, hash: B256) -> ProviderResult<Vec<L1Transaction>> {
+        self.committed(hash).await?;
+        self.chain.transactions_by_hash(hash).await
+    }
+}
+
+/// Blob sidecars by L1 block number, served once the script made them available.
+#[derive(Debug, Default)]
+struct ScriptedBlobs {
+    sidecars: BTreeMap<u64, Vec<BlobSidecar>>,
+    available: Mutex<Vec<u64>>,
+}
+
+#[async_trait]
+impl BlobProvider for ScriptedBlobs {
+    async fn blob_sidecars(&self, block: &BlockInfo, indices: &[u64]) -> Result<Vec<BlobSidecar>> {
+        if !self.available.lock().unwrap().contains(&block.number) {
+            let what = format!("blob sidecars of L1 block {block}");
+            return Err(ProviderError::NotFound(what).into());
+        }
+        let sidecars = self.sidecars.get(&block.number).into_iter().flatten();
+        Ok(sidecars.filter(|sidecar| indices.contains(&sidecar.index)).cloned().collect())
+    }
+}
+
+/// Accepts every derived block as canonical, recording the attributes it was derived from.
+#[derive(Debug, Clone, Default)]
+struct CanonicalValidator {
+    derived: Arc<Mutex<Vec<OptimismPayloadAttributes>>>,
+}
+
+#[async_trait]
+impl AttributesValidator for CanonicalValidator {
+    async fn validate(
+        &self,
+        attributes: &L2AttributesWithParent,
+    ) -> Result<ValidationOutcome, ValidationError> {
+        let l1_info = &attributes.attributes.transactions.as_ref().unwrap()[0];
+        let deposit = TxDeposit::decode_2718(&mut &l1_info[..]).unwrap();
+        let info = L1BlockInfoTx::decode_calldata(&deposit.input).unwrap();
+        let parent = attributes.parent.block_info;
+        let number = parent.number + 1;
+        let block = BlockInfo::new(
+            keccak256(number.to_be_bytes()),
+            number,
+            parent.hash,
+            attributes.attributes.payload_attributes.timestamp,
+        );
+        self.derived.lock().unwrap().push(attributes.attributes.clone());
+        Ok(ValidationOutcome::Valid(L2BlockInfo::new(block, info.id(), info.sequence_number())))
+    }
+}
+
+/// A node deriving from a scripted L1 chain on virtual time.
+#[derive(Debug)]
+pub(crate) struct Simulation {
+    handler: NotificationHandler<DerivationPipeline, CanonicalValidator>,
+    l1: Arc<ScriptedL1>,
+    blobs: Arc<ScriptedBlobs>,
+    engine: Arc<MockEngine>,
+    derived: Arc<Mutex<Vec<OptimismPayloadAttributes>>>,
+    script: BTreeMap<Duration, Vec<Event>>,
+    start: Instant,
+}
+
+impl Simulation {
+    /// Starts a node deriving on top of the genesis of `config`, from the L1 chain `l1` of which
+    /// only the genesis block is committed, with the blob sidecars of `blobs` by L1 block number.
+    pub(crate) async fn new(
+        config: RollupConfig,
+        l1: MockL1,
+        blobs: BTreeMap<u64, Vec<BlobSidecar>>,
+    ) -> Result<Self> {
+        let config = Arc::new(config);
+        let origin = l1.block(0);
+        let l1 = Arc::new(ScriptedL1 { chain: l1, head: AtomicU64::new(0) });
+        let blobs = Arc::new(ScriptedBlobs { sidecars: blobs, ..Default::default() });
+        let pipeline = PipelineBuilder::new(config.clone(), l1.clone())
+            .blob_fetcher(BlobFetcher::new(blobs.clone()))
+            .decompression(1, 1)
+            .build()?;
+        let validator = CanonicalValidator::default();
+        let derived = validator.derived.clone();
+        let engine = Arc::new(MockEngine::new());
+        let genesis = L2BlockInfo::new(
+            BlockInfo::new(config.genesis.l2.hash, 0, B256::ZERO, config.genesis.l2_time),
+            config.genesis.l1,
+            0,
+        );
+        let mut driver = Driver::new(config, pipeline, validator, engine.clone(), genesis);
+        driver.reset(genesis, origin, "startup").await?;
+        Ok(Self {
+            handler: NotificationHandler::new(NotificationMode::Full, driver),
+            l1,
+            blobs,
+            engine,
+            derived,
+            script: BTreeMap::new(),
+            start: Instant::now(),
+        })
+    }
+
+    /// Schedules `event` at `time` after the start of the simulation.
+    pub(crate) fn at(mut self, time: Duration, event: Event) -> Self {
+        self.script.entry(time).or_default().push(event);
+        self
+    }
+
+    /// Schedules the L1 blocks of `numbers` to be committed at the timestamps of their headers.
+    pub(crate) fn commit_l1_blocks(mut self, numbers: impl IntoIterator<Item = u64>) -> Self {
+        let genesis = self.l1.chain.block(0).timestamp;
+        for number in numbers {
+            let time = Duration::from_secs(self.l1.chain.block(number).timestamp - genesis);
+            self = self.at(time, Event::L1Block(number));
+        }
+        self
+    }
+
+    /// Plays the script up to `time` after the start of the simulation.
+    pub(crate) async fn run_until(&mut self, time: Duration) -> Result<()> {
+        while let Some(entry) = self.script.first_entry() {
+            if *entry.key() > time {
+                break;
+            }
+            let (at, events) = entry.remove_entry();
+            sleep_until(self.start + at).await;
+            for event in events {
+                self.apply(event).await?;
+            }
+        }
+        sleep_until(self.start + time).await;
+        Ok(())
+    }
+
+    async fn apply(&mut self, event: Event) -> Result<()> {
+        match event {
+            Event::L1Block(number) => {
+                self.l1.head.store(number, Ordering::SeqCst);
+                let block = self.l1.chain.block(number);
+                let notification =
+                    ChainNotification { committed: vec![block], ..Default::default() };
+                self.handler.handle(&notification).await?;
+            }
+            Event::Blobs(number) => self.blobs.available.lock().unwrap().push(number),
+        }
+        Ok(())
+    }
+
+    /// Returns a receiver of the sync status of the node.
+    pub(crate) fn status(&mut self) -> watch::Receiver<SyncStatus> {
+        self.handler.driver().subscribe_status()
+    }
+
+    /// Returns the safe head of the node.
+    pub(crate) fn safe_head(&mut self) -> L2BlockInfo {
+        self.handler.driver().cursor()
+    }
+
+    /// Returns the engine answering the node.
+    pub(crate) fn engine(&self) -> &MockEngine {
+        &self.engine
+    }
+
+    /// Returns the attributes of the blocks derived so far.
+    pub(crate) fn derived(&self) -> Vec<OptimismPayloadAttributes> {
+        self.derived.lock().unwrap().clone()
+    }
+}
+
+/// Returns the frames of a channel carrying `batch`, split into frames of at most `frame_size`
+/// encoded bytes.
+pub(crate) fn channel_frames(batch: SingleBatch, frame_size: usize) -> Vec<Frame> {
+    let mut channel = ChannelOut::new(ChannelId([1; 16]), Compression::default(), 100_000, 0);
+    assert!(channel.add_batch(&Batch::Single(batch)).unwrap());
+    channel.into_frames(frame_size).unwrap()
+}
+
+/// Returns a batcher transaction of the genesis batcher of `config` posting `frames` as calldata.
+pub(crate) fn calldata_tx(config: &RollupConfig, frames: Vec<Frame>) -> L1Transaction {
+    L1Transaction {
+        from: config.genesis.system_config.as_ref().unwrap().batcher_address,
+        to: Some(config.batch_inbox_address),
+        input: TxData { frames, data_availability: DataAvailability::Calldata }.calldata(),
+        ..Default::default()
+    }
+}
+
+/// Returns a batcher transaction of the genesis batcher of `config` posting `frames` as blobs,
+/// and the sidecars of the blobs. The commitments are made up, as only the versioned hashes of
+/// sidecars are checked.
+pub(crate) fn blob_tx(
+    config: &RollupConfig,
+    frames: Vec<Frame>,
+) -> (L1Transaction, Vec<BlobSidecar>) {
+    let blobs = TxData { frames, data_availability: DataAvailability::Blobs }.blobs().unwrap();
+    let sidecars: Vec<_> = blobs
+        .into_iter()
+        .enumerate()
+        .map(|(index, blob)| BlobSidecar {
+            index: index as u64,
+            blob,
+            kzg_commitment: Bytes48::from([0xc0 + index as u8; 48]),
+        })
+        .collect();
+    let tx = L1Transaction {
+        from: config.genesis.system_config.as_ref().unwrap().batcher_address,
+        to: Some(config.batch_inbox_address),
+        blob_versioned_hashes: sidecars
+            .iter()
+            .map(|sidecar| kzg_to_versioned_hash(sidecar.kzg_commitment.as_slice()))
+            .collect(),
+        ..Default::default()
+    };
+    (tx, sidecars)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::Bytes;
+    use url::Url;
+
+    use super::*;
+    use crate::{
+        alert::{Alert, StallMonitor, WebhookAlerter},
+        protocol::frame::FRAME_OVERHEAD,
+    };
+
+    const GENESIS_TIME: u64 = 1_700_000_000;
+
+    /// Returns a batch of one transaction for the first L2 block after the genesis of `config`.
+    fn first_batch(config: &RollupConfig) -> SingleBatch {
+        SingleBatch {
+            parent_hash: config.genesis.l2.hash,
+            epoch_num: 0,
+            epoch_hash: config.genesis.l1.hash,
+            timestamp: GENESIS_TIME + 2,
+            transactions: vec![Bytes::from_static(&[0x02, 0xc0])],
+        }
+    }
+
+    /// Derives from a chain posting the first batch in two frames, the second one `delay` L1
+    /// blocks after the first, and returns the transactions of the first derived block.
+    async fn first_block_transactions(delay: u64) -> usize {
+        let mut l1 = MockL1::new(GENESIS_TIME, 1);
+        let mut config = l1.rollup_config();
+        config.channel_timeout = 2;
+        config.seq_window_size = 4;
+        let channel_len = channel_frames(first_batch(&config), 100_000)[0].data.len();
+        let frames = channel_frames(first_batch(&config), FRAME_OVERHEAD + channel_len.div_ceil(2));
+        assert_eq!(frames.len(), 2);
+        for number in 1..=8 {
+            let frame = match number {
+                1 => Some(frames[0].clone()),
+                n if n == 1 + delay => Some(frames[1].clone()),
+                _ => None,
+            };
+            l1.push_block(
+                frame.map(|frame| calldata_tx(&config, vec![frame])).into_iter().collect(),
+            );
+        }
+
+        let mut sim =
+            Simulation::new(config, l1, BTreeMap::new()).await.unwrap().commit_l1_blocks(1..=8);
+        sim.run_until(Duration::from_secs(96)).await.unwrap();
+        sim.derived()[0].transactions.as_ref().unwrap().len()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drops_channels_past_their_timeout() {
+        // Completed within the timeout, the channel carries the first block.
+        assert_eq!(first_block_transactions(2).await, 2);
+        // Completed past it, the channel is dropped and the sequencing window expires.
+        assert_eq!(first_block_transactions(3).await, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn derives_empty_blocks_once_the_sequencing_window_expires() {
+        let l1 = MockL1::new(GENESIS_TIME, 8);
+        let mut config = l1.rollup_config();
+        config.seq_window_size = 4;
+        let mut sim =
+            Simulation::new(config, l1, BTreeMap::new()).await.unwrap().commit_l1_blocks(1..8);
+
+        sim.run_until(Duration::from_secs(59)).await.unwrap();
+        assert_eq!(sim.safe_head().block_info.number, 0);
+        sim.run_until(Duration::from_secs(60)).await.unwrap();
+        let safe_head = sim.safe_head();
+        assert_eq!(safe_head.block_info.number, 6);
+        assert_eq!(safe_head.l1_origin, sim.l1.chain.block(1).id());
+        assert!(sim.derived().iter().all(|block| block.transactions.as_ref().unwrap().len() == 1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_for_blobs_to_become_available() {
+        let mut l1 = MockL1::new(GENESIS_TIME, 1);
+        let mut config = l1.rollup_config();
+        config.ecotone_time = Some(0);
+        let (tx, sidecars) = blob_tx(&config, channel_frames(first_batch(&config), 100_000));
+        l1.push_block(vec![tx]);
+        l1.push_block(Vec::new());
+        l1.push_block(Vec::new());
+
+        let blobs = BTreeMap::from([(1, sidecars)]);
+        let mut sim = Simulation::new(config, l1, blobs)
+            .await
+            .unwrap()
+            .commit_l1_blocks(1..=3)
+            .at(Duration::from_secs(30), Event::Blobs(1));
+
+        // The L1 block is retried on every notification until its blobs show up.
+        sim.run_until(Duration::from_secs(35)).await.unwrap();
+        assert_eq!(sim.safe_head().block_info.number, 0);
+        sim.run_until(Duration::from_secs(36)).await.unwrap();
+        assert_eq!(sim.safe_head().block_info.number, 1);
+        assert_eq!(sim.derived()[0].transactions.as_ref().unwrap().len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn alerts_stalled_safe_heads() {
+        let l1 = MockL1::new(GENESIS_TIME, 16);
+        let mut config = l1.rollup_config();
+        config.seq_window_size = 10;
+        let mut sim =
+            Simulation::new(config, l1, BTreeMap::new()).await.unwrap().commit_l1_blocks(1..16);
+
+        let alerter = Arc::new(WebhookAlerter::new(Url::parse("http://localhost").unwrap()));
+        let monitor = StallMonitor::new(sim.status(), Duration::from_secs(50), alerter);
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+        let (start, stalls) = (sim.start, alerts.clone());
+        tokio::spawn(monitor.watch(move |alert| {
+            let Alert::Stall { duration, .. } = alert else { panic!("unexpected {alert:?}") };
+            stalls.lock().unwrap().push((start.elapsed(), duration));
+        }));
+
+        sim.run_until(Duration::from_secs(180)).await.unwrap();
+        assert!(sim.safe_head().block_info.number > 0);
+        assert!(!sim.engine().calls().is_empty());
+        assert_eq!(
+            *alerts.lock().unwrap(),
+            [
+                (Duration::from_secs(50), Duration::from_secs(50)),
+                (Duration::from_secs(100), Duration::from_secs(100)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Historical derivation throughput.
+//!
+//! Every derived block is accounted to the wall-clock hour it was derived in: the number of L2
+//! blocks and transactions, the L1 blocks derivation advanced through, and the bytes of
+//! sequenced transactions read from batcher data. The hourly totals survive restarts, so that
+//! `hera stats` can compare the throughput of releases and help sizing nodes.
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use alloy_primitives::Bytes;
+use eyre::{ensure, Result, WrapErr};
+use serde::Serialize;
+
+use crate::{
+    protocol::deposit::DEPOSIT_TX_TYPE,
+    storage::{SqliteStorage, Storage},
+};
+
+/// The table of hourly throughput, keyed by the big-endian number of the hour since the Unix
+/// epoch, holding the big-endian counters of [`HourlyThroughput`] in field order.
+pub(crate) const THROUGHPUT: &str = "throughput";
+
+/// The number of seconds in an hour.
+const HOUR: u64 = 3600;
+
+/// The derivation throughput of an hour.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct HourlyThroughput {
+    /// The Unix timestamp of the start of the hour, in seconds.
+    pub hour: u64,
+    /// The number of L2 blocks derived.
+    pub l2_blocks: u64,
+    /// The number of L2 transactions derived, deposits included.
+    pub l2_transactions: u64,
+    /// The number of L1 blocks derivation advanced through.
+    pub l1_blocks: u64,
+    /// The bytes of sequenced transactions read from batcher data.
+    pub batch_bytes: u64,
+}
+
+impl HourlyThroughput {
+    fn encode(&self) -> [u8; 32] {
+        let mut value = [0; 32];
+        value[..8].copy_from_slice(&self.l2_blocks.to_be_bytes());
+        value[8..16].copy_from_slice(&self.l2_transactions.to_be_bytes());
+        value[16..24].copy_from_slice(&self.l1_blocks.to_be_bytes());
+        value[24..].copy_from_slice(&self.batch_bytes.to_be_bytes());
+        value
+    }
+
+    fn decode(key: &[u8], value: &[u8]) -> Result<Self> {
+        ensure!(key.len() == 8 && value.len() == 32, "throughput entry is malformed");
+        let number = |bytes: &[u8]| u64::from_be_bytes(bytes.try_into().unwrap());
+        Ok(Self {
+            hour: number(key) * HOUR,
+            l2_blocks: number(&value[..8]),
+            l2_transactions: number(&value[8..16]),
+            l1_blocks: number(&value[16..24]),
+            batch_bytes: number(&value[24..]),
+        })
+    }
+
+    /// Adds the counters of `other` to these.
+    pub fn add(&mut self, other: &Self) {
+        self.l2_blocks += other.l2_blocks;
+        self.l2_transactions += other.l2_transactions;
+        self.l1_blocks += other.l1_blocks;
+        self.batch_bytes += other.batch_bytes;
+    }
+}
+
+/// The hour being accounted, and the last L1 block derived from.
+#[derive(Debug, Default)]
+struct Current {
+    hour: Option<HourlyThroughput>,
+    l1_block: Option<u64>,
+}
+
+/// Hourly derivation throughput, written as derivation advances.
+#[derive(Debug)]
+pub struct ThroughputStats {
+    storage: Arc<dyn Storage>,
+    current: Mutex<Current>,
+}
+
+impl ThroughputStats {
+    /// Creates the statistics in `storage`.
+    pub fn new(storage: Arc<dyn Storage>) -> Self {
+        Self { storage, current: Mutex::default() }
+    }
+
+    /// Opens the statistics in the SQLite database at `path`, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let storage = SqliteStorage::open(path)
+            .wrap_err_with(|| format!("failed to open throughput database {}", path.display()))?;
+        Ok(Self::new(Arc::new(storage)))
+    }
+
+    /// Accounts a block of `transactions` derived from the L1 block `l1_block` at the Unix
+    /// timestamp `now`, in seconds.
+    pub fn record(&self, now: u64, l1_block: u64, transactions: &[Bytes]) -> Result<()> {
+        let mut current = self.current.lock().unwrap();
+        let index = now / HOUR;
+        let mut hour = match current.hour {
+            Some(hour) if hour.hour == index * HOUR => hour,
+            _ => match self.storage.get(THROUGHPUT, &index.to_be_bytes())? {
+                Some(value) => HourlyThroughput::decode(&index.to_be_bytes(), &value)?,
+                None => HourlyThroughput { hour: index * HOUR, ..Default::default() },
+            },
+        };
+
+        hour.add(&HourlyThroughput {
+            l2_blocks: 1,
+            l2_transactions: transactions.len() as u64,
+            l1_blocks: current.l1_block.map_or(0, |last| l1_block.saturating_sub(last)),
+            batch_bytes: transactions
+                .iter()
+                .filter(|tx| tx.first() != Some(&DEPOSIT_TX_TYPE))
+                .map(|tx| tx.len() as u64)
+                .sum(),
+            ..Default::default()
+        });
+        self.storage
+            .put(THROUGHPUT, &[(&index.to_be_bytes(), &hour.encode())])
+            .wrap_err("failed to record derivation throughput")?;
+        *current = Current { hour: Some(hour), l1_block: Some(l1_block) };
+        Ok(())
+    }
+
+    /// Returns the throughput of the hours starting at or after the Unix timestamp `since`, in
+    /// seconds, oldest first. Hours nothing was derived in are skipped.
+    pub fn hours(&self, since: u64) -> Result<Vec<HourlyThroughput>> {
+        let mut hours = Vec::new();
+        let mut key = u64::MAX;
+        while let Some((found, value)) = self.storage.floor(THROUGHPUT, &key.to_be_bytes())? {
+            let hour = HourlyThroughput::decode(&found, &value)?;
+            if hour.hour < since {
+                break;
+            }
+            hours.push(hour);
+            match (hour.hour / HOUR).checked_sub(1) {
+                Some(previous) => key = previous,
+                None => break,
+            }
+        }
+        hours.reverse();
+        Ok(hours)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn accounts_blocks_to_their_hour() {
+        let stats = ThroughputStats::new(Arc::new(MemoryStorage::new()));
+        let deposit = Bytes::from_static(&[DEPOSIT_TX_TYPE, 1, 2]);
+        let tx = Bytes::from_static(&[0x02, 1, 2, 3, 4]);
+        stats.record(7200, 100, &[deposit.clone(), tx.clone()]).unwrap();
+        stats.record(7300, 102, std::slice::from_ref(&deposit)).unwrap();
+        stats.record(3 * HOUR + 5, 103, &[deposit, tx.clone(), tx]).unwrap();
+
+        assert_eq!(
+            stats.hours(0).unwrap(),
+            [
+                HourlyThroughput {
+                    hour: 7200,
+                    l2_blocks: 2,
+                    l2_transactions: 3,
+                    l1_blocks: 2,
+                    batch_bytes: 5,
+                },
+                HourlyThroughput {
+                    hour: 3 * HOUR,
+                    l2_blocks: 1,
+                    l2_transactions: 3,
+                    l1_blocks: 1,
+                    batch_bytes: 10,
+                },
+            ]
+        );
+        assert_eq!(stats.hours(7201).unwrap().len(), 1);
+
+        // A restarted node keeps adding to the hour.
+        let stats = ThroughputStats::new(stats.storage.clone());
+        stats.record(3 * HOUR + 10, 103, &[]).unwrap();
+        assert_eq!(stats.hours(3 * HOUR).unwrap()[0].l2_blocks, 2);
+    }
+}
@@ -0,0 +1,131 @@
+//! The layout of Hera's data directory.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{bail, Result, WrapErr};
+use tracing::info;
+
+/// The version of the data directory layout, stored in the `VERSION` file of each chain
+/// directory.
+pub const DATADIR_VERSION: u32 = 1;
+
+/// The name of the layout version marker.
+const VERSION_FILE: &str = "VERSION";
+
+/// Upgrades a chain directory from the layout version at their index to the next one.
+const MIGRATIONS: [fn(&Path) -> Result<()>; DATADIR_VERSION as usize] = [migrate_v0];
+
+/// The data directory of a chain: a subdirectory of `--hera.datadir` named after the L2 chain
+/// ID, so that nodes of several chains can share a data directory.
+///
+/// Each database in it also carries the version of its own schema, see
+/// [`SqliteStorage`](crate::storage::SqliteStorage).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataDir {
+    path: PathBuf,
+}
+
+impl DataDir {
+    /// Opens the data directory of the chain `l2_chain_id` in `datadir`, creating it if needed
+    /// and migrating it to the current layout version.
+    pub fn open(datadir: impl AsRef<Path>, l2_chain_id: u64) -> Result<Self> {
+        let path = datadir.as_ref().join(l2_chain_id.to_string());
+        fs::create_dir_all(&path)
+            .wrap_err_with(|| format!("failed to create data directory {}", path.display()))?;
+
+        let marker = path.join(VERSION_FILE);
+        let version = match fs::read_to_string(&marker) {
+            Ok(version) => version.trim().parse::<u32>().wrap_err_with(|| {
+                format!("invalid data directory version marker {}", marker.display())
+            })?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(err) => {
+                return Err(err).wrap_err_with(|| format!("failed to read {}", marker.display()))
+            }
+        };
+        if version > DATADIR_VERSION {
+            bail!(
+                "data directory {} has layout version {version}, this version of Hera only \
+                 supports up to {DATADIR_VERSION}",
+                path.display()
+            );
+        }
+
+        for (from, migrate) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            migrate(&path).wrap_err_with(|| {
+                format!("failed to migrate data directory {} from version {from}", path.display())
+            })?;
+            fs::write(&marker, format!("{}\n", from + 1))
+                .wrap_err_with(|| format!("failed to write {}", marker.display()))?;
+        }
+        if version < DATADIR_VERSION {
+            info!(
+                target: "hera::storage",
+                path = %path.display(),
+                from = version,
+                to = DATADIR_VERSION,
+                "Migrated data directory"
+            );
+        }
+        Ok(Self { path })
+    }
+
+    /// Returns the path of the chain directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of the database holding the node's checkpoints.
+    pub fn checkpoints(&self) -> PathBuf {
+        self.path.join("checkpoints.db")
+    }
+
+    /// Returns the path of the blob cache database.
+    pub fn blob_cache(&self) -> PathBuf {
+        self.path.join("blobs.db")
+    }
+
+    /// Returns the path of the directory holding the P2P peer store.
+    pub fn peerstore(&self) -> PathBuf {
+        self.path.join("peerstore")
+    }
+
+    /// Returns the path of the node's p2p identity key.
+    pub fn p2p_key(&self) -> PathBuf {
+        self.path.join("p2p_priv.txt")
+    }
+
+    /// Returns the path of the node's last signed record.
+    pub fn p2p_enr(&self) -> PathBuf {
+        self.path.join("p2p_enr.txt")
+    }
+
+    /// Returns the path of the audit log database.
+    pub fn audit_log(&self) -> PathBuf {
+        self.path.join("audit.db")
+    }
+
+    /// Returns the path of the safe head database.
+    pub fn safe_db(&self) -> PathBuf {
+        self.path.join("safedb.db")
+    }
+
+    /// Returns the path of the write-ahead log of chain notifications.
+    pub fn notification_wal(&self) -> PathBuf {
+        self.path.join("notifications.db")
+    }
+
+    /// Returns the path of the derivation throughput database.
+    pub fn stats(&self) -> PathBuf {
+        self.path.join("stats.db")
+    }
+}
+
+/// Lays out a new chain directory.
+fn migrate_v0(path: &Path) -> Result<()> {
+    fs::create_dir_all(path.join("peerstore"))?;
+    Ok(())
+}
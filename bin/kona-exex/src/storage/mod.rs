@@ -0,0 +1,143 @@
+//! Persistence of checkpoints and caches.
+//!
+//! Components that persist state, such as the [`BlobCache`](crate::blobs::BlobCache), store it
+//! through a [`Storage`]: a key-value store split into named tables. Hera stores to SQLite by
+//! default, tests can use the [`MemoryStorage`], and embedders can supply their own backend.
+//! Wrapping any of them in a [`ReadOnlyStorage`] serves existing data without ever writing to it.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    ops::Bound,
+    sync::{Arc, Mutex},
+};
+
+use eyre::{bail, Result};
+
+mod datadir;
+pub use datadir::{DataDir, DATADIR_VERSION};
+
+mod sqlite;
+pub use sqlite::{SqliteStorage, SQLITE_SCHEMA_VERSION};
+
+/// A key-value store split into named tables.
+pub trait Storage: fmt::Debug + Send + Sync {
+    /// Returns the value stored under `key` in `table`.
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Returns true if a value is stored under `key` in `table`.
+    fn contains(&self, table: &str, key: &[u8]) -> Result<bool> {
+        Ok(self.get(table, key)?.is_some())
+    }
+
+    /// Returns the entry of `table` with the greatest key at or below `key`, keys being ordered
+    /// bytewise.
+    fn floor(&self, table: &str, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>>;
+
+    /// Stores `entries` in `table` at once, replacing the values of existing keys.
+    fn put(&self, table: &str, entries: &[(&[u8], &[u8])]) -> Result<()>;
+
+    /// Removes the entries of `table` with keys at or above `from`.
+    fn truncate(&self, table: &str, from: &[u8]) -> Result<()>;
+
+    /// Removes the entries of `table` under `keys`, if any.
+    fn remove(&self, table: &str, keys: &[&[u8]]) -> Result<()>;
+}
+
+/// The entries of a table of the [`MemoryStorage`].
+type Table = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// A [`Storage`] kept in memory, lost on drop.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    tables: Mutex<BTreeMap<String, Table>>,
+}
+
+impl MemoryStorage {
+    /// Creates an empty storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.get(table).and_then(|table| table.get(key)).cloned())
+    }
+
+    fn floor(&self, table: &str, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.get(table).and_then(|table| {
+            let range = (Bound::Unbounded, Bound::Included(key));
+            table.range::<[u8], _>(range).next_back().map(|(k, v)| (k.clone(), v.clone()))
+        }))
+    }
+
+    fn put(&self, table: &str, entries: &[(&[u8], &[u8])]) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        let table = tables.entry(table.to_string()).or_default();
+        for (key, value) in entries {
+            table.insert(key.to_vec(), value.to_vec());
+        }
+        Ok(())
+    }
+
+    fn truncate(&self, table: &str, from: &[u8]) -> Result<()> {
+        if let Some(table) = self.tables.lock().unwrap().get_mut(table) {
+            table.split_off(from);
+        }
+        Ok(())
+    }
+
+    fn remove(&self, table: &str, keys: &[&[u8]]) -> Result<()> {
+        if let Some(table) = self.tables.lock().unwrap().get_mut(table) {
+            for key in keys {
+                table.remove(*key);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serves the data of another [`Storage`], rejecting every write.
+///
+/// Useful to share a cache filled by another node, or to replay against a snapshot without
+/// altering it.
+#[derive(Debug)]
+pub struct ReadOnlyStorage {
+    inner: Arc<dyn Storage>,
+}
+
+impl ReadOnlyStorage {
+    /// Wraps `inner`.
+    pub fn new(inner: Arc<dyn Storage>) -> Self {
+        Self { inner }
+    }
+}
+
+impl Storage for ReadOnlyStorage {
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.inner.get(table, key)
+    }
+
+    fn contains(&self, table: &str, key: &[u8]) -> Result<bool> {
+        self.inner.contains(table, key)
+    }
+
+    fn floor(&self, table: &str, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        self.inner.floor(table, key)
+    }
+
+    fn put(&self, table: &str, _: &[(&[u8], &[u8])]) -> Result<()> {
+        bail!("cannot write to table {table} of a read-only storage")
+    }
+
+    fn truncate(&self, table: &str, _: &[u8]) -> Result<()> {
+        bail!("cannot write to table {table} of a read-only storage")
+    }
+
+    fn remove(&self, table: &str, _: &[&[u8]]) -> Result<()> {
+        bail!("cannot write to table {table} of a read-only storage")
+    }
+}
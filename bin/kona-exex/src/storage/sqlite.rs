@@ -0,0 +1,165 @@
+//! A [`Storage`] in an SQLite database.
+
+use std::{path::Path, sync::Mutex};
+
+use eyre::{bail, Result, WrapErr};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use tracing::info;
+
+use crate::{blobs, storage::Storage};
+
+/// The version of the database schema, stored as the SQLite `user_version`.
+pub const SQLITE_SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS entries (
+    tbl TEXT NOT NULL,
+    key BLOB NOT NULL,
+    value BLOB NOT NULL,
+    PRIMARY KEY (tbl, key)
+);
+";
+
+/// A [`Storage`] in an SQLite database, with all tables in a single SQL table.
+#[derive(Debug)]
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    /// Opens the database at `path`, creating it if needed and migrating it to the current
+    /// schema version.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut conn = Connection::open(path)
+            .wrap_err_with(|| format!("failed to open storage {}", path.display()))?;
+        let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > SQLITE_SCHEMA_VERSION {
+            bail!(
+                "storage {} has schema version {version}, this version of Hera only supports up \
+                 to {SQLITE_SCHEMA_VERSION}",
+                path.display()
+            );
+        }
+
+        let tx = conn.transaction()?;
+        tx.execute_batch(SCHEMA).wrap_err("failed to create storage schema")?;
+        if version < 1 {
+            migrate_legacy_blob_cache(&tx).wrap_err("failed to migrate legacy blob cache")?;
+        }
+        tx.pragma_update(None, "user_version", SQLITE_SCHEMA_VERSION)?;
+        tx.commit().wrap_err_with(|| format!("failed to migrate storage {}", path.display()))?;
+        if version < SQLITE_SCHEMA_VERSION {
+            info!(
+                target: "hera::storage",
+                path = %path.display(),
+                from = version,
+                to = SQLITE_SCHEMA_VERSION,
+                "Migrated storage schema"
+            );
+        }
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+}
+
+/// Moves the tables of blob caches written before the [`Storage`] abstraction into the entries of
+/// the [`BlobCache`](crate::blobs::BlobCache).
+fn migrate_legacy_blob_cache(tx: &Transaction<'_>) -> Result<()> {
+    let legacy: bool = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'sidecars')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !legacy {
+        return Ok(());
+    }
+
+    {
+        let mut insert =
+            tx.prepare("INSERT OR IGNORE INTO entries (tbl, key, value) VALUES (?1, ?2, ?3)")?;
+        let mut sidecars =
+            tx.prepare("SELECT block_hash, blob_index, kzg_commitment, blob FROM sidecars")?;
+        let mut rows = sidecars.query([])?;
+        while let Some(row) = rows.next()? {
+            let mut key: Vec<u8> = row.get(0)?;
+            key.extend_from_slice(&(row.get::<_, i64>(1)? as u64).to_be_bytes());
+            let mut value: Vec<u8> = row.get(2)?;
+            value.extend_from_slice(&row.get::<_, Vec<u8>>(3)?);
+            insert.execute(params![blobs::cache::SIDECARS, key, value])?;
+        }
+
+        let mut checkpoints = tx.prepare("SELECT name, l1_number FROM checkpoints")?;
+        let mut rows = checkpoints.query([])?;
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(0)?;
+            let number = (row.get::<_, i64>(1)? as u64).to_be_bytes();
+            insert.execute(params![blobs::cache::CHECKPOINTS, name.as_bytes(), number])?;
+        }
+    }
+    tx.execute_batch("DROP TABLE sidecars; DROP TABLE checkpoints;")?;
+    Ok(())
+}
+
+impl Storage for SqliteStorage {
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare_cached("SELECT value FROM entries WHERE tbl = ?1 AND key = ?2")?;
+        stmt.query_row(params![table, key], |row| row.get(0))
+            .optional()
+            .wrap_err_with(|| format!("failed to read from table {table}"))
+    }
+
+    fn contains(&self, table: &str, key: &[u8]) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached("SELECT 1 FROM entries WHERE tbl = ?1 AND key = ?2")?;
+        stmt.exists(params![table, key])
+            .wrap_err_with(|| format!("failed to read from table {table}"))
+    }
+
+    fn floor(&self, table: &str, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            "SELECT key, value FROM entries WHERE tbl = ?1 AND key <= ?2 ORDER BY key DESC LIMIT 1",
+        )?;
+        stmt.query_row(params![table, key], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()
+            .wrap_err_with(|| format!("failed to read from table {table}"))
+    }
+
+    fn put(&self, table: &str, entries: &[(&[u8], &[u8])]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO entries (tbl, key, value) VALUES (?1, ?2, ?3) \
+                 ON CONFLICT (tbl, key) DO UPDATE SET value = excluded.value",
+            )?;
+            for (key, value) in entries {
+                stmt.execute(params![table, key, value])
+                    .wrap_err_with(|| format!("failed to write to table {table}"))?;
+            }
+        }
+        tx.commit().wrap_err_with(|| format!("failed to commit writes to table {table}"))
+    }
+
+    fn truncate(&self, table: &str, from: &[u8]) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM entries WHERE tbl = ?1 AND key >= ?2", params![table, from])
+            .wrap_err_with(|| format!("failed to truncate table {table}"))?;
+        Ok(())
+    }
+
+    fn remove(&self, table: &str, keys: &[&[u8]]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached("DELETE FROM entries WHERE tbl = ?1 AND key = ?2")?;
+            for key in keys {
+                stmt.execute(params![table, key])
+                    .wrap_err_with(|| format!("failed to remove from table {table}"))?;
+            }
+        }
+        tx.commit().wrap_err_with(|| format!("failed to commit removals from table {table}"))
+    }
+}
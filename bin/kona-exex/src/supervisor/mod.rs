@@ -0,0 +1,323 @@
+//! Supervision of the node's long-running tasks.
+//!
+//! The driver, RPC server, metrics server and other subsystems run as tasks spawned through a
+//! [`Supervisor`]. A task that panics or returns an error is restarted according to its
+//! [`RestartPolicy`]; once it exhausts its restarts the supervisor stops every task and reports the
+//! failure, so a crashed subsystem takes the whole node down instead of leaving it running without
+//! it.
+//!
+//! Services are spawned in a [`ServiceStage`] and only start once every service of the earlier
+//! stages reported being ready through its [`ReadyHandle`], so that e.g. the RPC server does not
+//! answer before derivation runs. A restarted service is not ready until it reports so again.
+//!
+//! With a [`WorkerRuntime`], the services of the provider and pipeline stages and the tasks
+//! spawned with [`Supervisor::spawn_worker`] run on threads of their own, so that catching up
+//! does not starve the executor of the host node.
+//!
+//! With [poll timing](Supervisor::with_poll_timing), tasks record how long their polls take, and
+//! a [`RuntimeMonitor`] exports the metrics of the runtimes and of the channels between stages.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use eyre::{bail, eyre, Result};
+use metrics::counter;
+use tokio::{
+    runtime::Handle,
+    task::{JoinError, JoinSet},
+};
+use tracing::{error, info, warn};
+
+use crate::tenant;
+
+mod readiness;
+pub use readiness::{Readiness, ReadyHandle, ServiceStage};
+
+mod monitor;
+use monitor::TimedTask;
+pub use monitor::{RuntimeMonitor, DEFAULT_RUNTIME_METRICS_INTERVAL, SLOW_POLL_THRESHOLD};
+
+mod runtime;
+pub use runtime::WorkerRuntime;
+
+/// The default number of times a task is restarted before the node is stopped.
+pub const DEFAULT_MAX_RESTARTS: u32 = 5;
+
+/// The default delay before the first restart of a task, doubled on every further restart.
+pub const DEFAULT_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The longest delay between two restarts of a task.
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A future run by a supervised task.
+pub type TaskFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+/// When a supervised task is restarted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Never restart the task: its first failure stops the node.
+    Never,
+    /// Restart the task when it panics or returns an error.
+    #[default]
+    OnFailure,
+    /// Restart the task whenever it exits, including when it returns successfully.
+    Always,
+}
+
+impl fmt::Display for RestartPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Never => "never",
+            Self::OnFailure => "on-failure",
+            Self::Always => "always",
+        })
+    }
+}
+
+impl FromStr for RestartPolicy {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "never" => Ok(Self::Never),
+            "on-failure" => Ok(Self::OnFailure),
+            "always" => Ok(Self::Always),
+            _ => bail!("unknown restart policy {s}, expected never, on-failure or always"),
+        }
+    }
+}
+
+/// How a supervised task is restarted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartConfig {
+    /// When the task is restarted.
+    pub policy: RestartPolicy,
+    /// The number of restarts after which a failing task stops the node.
+    pub max_restarts: u32,
+    /// The delay before the first restart, doubled on every further restart.
+    pub backoff: Duration,
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        Self {
+            policy: RestartPolicy::default(),
+            max_restarts: DEFAULT_MAX_RESTARTS,
+            backoff: DEFAULT_RESTART_BACKOFF,
+        }
+    }
+}
+
+/// Spawns tasks and restarts them when they crash.
+#[derive(Debug, Default)]
+pub struct Supervisor {
+    config: RestartConfig,
+    tasks: JoinSet<(&'static str, Result<()>)>,
+    crashes: Arc<Mutex<BTreeMap<&'static str, u64>>>,
+    chain_id: Option<u64>,
+    readiness: Readiness,
+    workers: Option<WorkerRuntime>,
+    poll_timing: bool,
+}
+
+impl Supervisor {
+    /// Creates a supervisor restarting tasks according to `config`, unless spawned with their own.
+    pub fn new(config: RestartConfig) -> Self {
+        Self { config, ..Default::default() }
+    }
+
+    /// Runs every task in the scope of the chain `chain_id`, see [`tenant`].
+    pub const fn with_chain_id(mut self, chain_id: u64) -> Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    /// Runs the heavy tasks on `workers` rather than on the runtime the supervisor runs on. The
+    /// runtime is shut down once the supervisor stops.
+    pub fn with_worker_runtime(mut self, workers: WorkerRuntime) -> Self {
+        self.workers = Some(workers);
+        self
+    }
+
+    /// Records the duration of the polls of every task in `hera_task_poll_seconds`.
+    pub const fn with_poll_timing(mut self) -> Self {
+        self.poll_timing = true;
+        self
+    }
+
+    /// Returns a handle to the worker runtime, if any.
+    pub fn worker_runtime(&self) -> Option<Handle> {
+        self.workers.as_ref().map(WorkerRuntime::handle)
+    }
+
+    /// Spawns the task `name`, running the future returned by `task` and running a new one on
+    /// every restart.
+    pub fn spawn<F>(&mut self, name: &'static str, task: F)
+    where
+        F: FnMut() -> TaskFuture + Send + 'static,
+    {
+        self.spawn_with_config(name, self.config, task);
+    }
+
+    /// Spawns the task `name` with its own restart config.
+    pub fn spawn_with_config<F>(&mut self, name: &'static str, config: RestartConfig, task: F)
+    where
+        F: FnMut() -> TaskFuture + Send + 'static,
+    {
+        self.spawn_supervised(name, config, None, task);
+    }
+
+    /// Spawns the heavy task `name` on the worker runtime, if any.
+    pub fn spawn_worker<F>(&mut self, name: &'static str, task: F)
+    where
+        F: FnMut() -> TaskFuture + Send + 'static,
+    {
+        let runtime = self.workers.as_ref().map(WorkerRuntime::handle);
+        self.spawn_supervised(name, self.config, runtime, task);
+    }
+
+    /// Spawns the task `name`, running each of its runs on `runtime` if set.
+    fn spawn_supervised<F>(
+        &mut self,
+        name: &'static str,
+        config: RestartConfig,
+        runtime: Option<Handle>,
+        mut task: F,
+    ) where
+        F: FnMut() -> TaskFuture + Send + 'static,
+    {
+        let crashes = self.crashes.clone();
+        let poll_timing = self.poll_timing;
+        let supervised = async move {
+            let mut restarts = 0;
+            loop {
+                // Run in a set of its own, so the task is aborted with the supervisor.
+                let mut run = JoinSet::new();
+                let future: TaskFuture =
+                    if poll_timing { Box::pin(TimedTask::new(name, task())) } else { task() };
+                match &runtime {
+                    Some(runtime) => run.spawn_on(tenant::inherit(future), runtime),
+                    None => run.spawn(tenant::inherit(future)),
+                };
+                let Some(joined) = run.join_next().await else { return (name, Ok(())) };
+                let reason = match joined {
+                    Ok(Ok(())) if config.policy != RestartPolicy::Always => {
+                        info!(target: "hera::supervisor", task = name, "Task finished");
+                        return (name, Ok(()));
+                    }
+                    Ok(Ok(())) => "exited".to_string(),
+                    Ok(Err(err)) => format!("{err:#}"),
+                    Err(err) if err.is_cancelled() => return (name, Ok(())),
+                    Err(err) => panic_message(err),
+                };
+
+                *crashes.lock().unwrap().entry(name).or_default() += 1;
+                counter!("hera_task_crashes_total", "task" => name).increment(1);
+                if config.policy == RestartPolicy::Never || restarts >= config.max_restarts {
+                    error!(target: "hera::supervisor", task = name, %reason, restarts, "Task failed");
+                    return (name, Err(eyre!("task {name} failed: {reason}")));
+                }
+
+                let backoff = config.backoff.saturating_mul(1 << restarts.min(16));
+                let backoff = backoff.min(MAX_RESTART_BACKOFF);
+                restarts += 1;
+                warn!(
+                    target: "hera::supervisor",
+                    task = name,
+                    %reason,
+                    restart = restarts,
+                    ?backoff,
+                    "Restarting task"
+                );
+                counter!("hera_task_restarts_total", "task" => name).increment(1);
+                tokio::time::sleep(backoff).await;
+            }
+        };
+        match self.chain_id {
+            Some(chain_id) => self.tasks.spawn(tenant::scope(chain_id, supervised)),
+            None => self.tasks.spawn(tenant::inherit(supervised)),
+        };
+    }
+
+    /// Spawns the service `name` of `stage`, starting it once the services of the earlier stages
+    /// are ready. The service reports its own readiness through the handle `task` is called
+    /// with.
+    pub fn spawn_service<F>(&mut self, name: &'static str, stage: ServiceStage, task: F)
+    where
+        F: FnMut(ReadyHandle) -> TaskFuture + Send + 'static,
+    {
+        self.spawn_service_with_config(name, stage, self.config, task);
+    }
+
+    /// Spawns the service `name` of `stage` with its own restart config. Services of the
+    /// provider and pipeline stages run on the worker runtime, if any.
+    pub fn spawn_service_with_config<F>(
+        &mut self,
+        name: &'static str,
+        stage: ServiceStage,
+        config: RestartConfig,
+        mut task: F,
+    ) where
+        F: FnMut(ReadyHandle) -> TaskFuture + Send + 'static,
+    {
+        let ready = self.readiness.register(name, stage);
+        let readiness = self.readiness.clone();
+        let runtime = self
+            .workers
+            .as_ref()
+            .filter(|_| stage <= ServiceStage::Pipeline)
+            .map(WorkerRuntime::handle);
+        self.spawn_supervised(name, config, runtime, move || {
+            ready.not_ready();
+            let (readiness, task) = (readiness.clone(), task(ready.clone()));
+            Box::pin(async move {
+                readiness.wait_for_stage(stage).await;
+                task.await
+            })
+        });
+    }
+
+    /// Returns the readiness of the services.
+    pub fn readiness(&self) -> Readiness {
+        self.readiness.clone()
+    }
+
+    /// Returns a handle to the number of times each task crashed, including crashes it was
+    /// restarted after.
+    pub fn crash_counts(&self) -> Arc<Mutex<BTreeMap<&'static str, u64>>> {
+        self.crashes.clone()
+    }
+
+    /// Waits for the tasks, returning once all of them finished or with the error of the first
+    /// that failed for good, after stopping the others.
+    pub async fn run(mut self) -> Result<()> {
+        while let Some(joined) = self.tasks.join_next().await {
+            let (name, result) = joined.map_err(|err| eyre!("supervisor task died: {err}"))?;
+            if let Err(err) = result {
+                error!(target: "hera::supervisor", task = name, "Stopping the node");
+                self.tasks.shutdown().await;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the message of a task panic.
+fn panic_message(err: JoinError) -> String {
+    let Ok(panic) = err.try_into_panic() else { return "task aborted".to_string() };
+    match panic.downcast::<String>() {
+        Ok(message) => format!("panicked: {message}"),
+        Err(panic) => match panic.downcast::<&'static str>() {
+            Ok(message) => format!("panicked: {message}"),
+            Err(_) => "panicked".to_string(),
+        },
+    }
+}
@@ -0,0 +1,165 @@
+//! Runtime metrics, to spot tasks starving the executor and stages falling behind each other.
+//!
+//! Tokio only records poll times with `--cfg tokio_unstable`, so supervised tasks time their own
+//! polls instead: a task holding its worker thread for long delays every other task of the
+//! runtime. The depths of the channels between stages tell which side of a channel is the slow
+//! one.
+
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use eyre::Result;
+use metrics::{counter, gauge, histogram};
+use tokio::{runtime::Handle, sync::mpsc};
+use tracing::debug;
+
+use crate::supervisor::TaskFuture;
+
+/// Default interval between two exports of the runtime metrics.
+pub const DEFAULT_RUNTIME_METRICS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Polls longer than this count as slow, blocking the worker thread they run on.
+pub const SLOW_POLL_THRESHOLD: Duration = Duration::from_millis(100);
+
+/// A supervised task recording the duration of its polls in `hera_task_poll_seconds`.
+pub(crate) struct TimedTask {
+    name: &'static str,
+    inner: TaskFuture,
+}
+
+impl TimedTask {
+    /// Times the polls of `inner`, the future of the task `name`.
+    pub(crate) fn new(name: &'static str, inner: TaskFuture) -> Self {
+        Self { name, inner }
+    }
+}
+
+impl Future for TimedTask {
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let started = Instant::now();
+        let poll = self.inner.as_mut().poll(cx);
+        let elapsed = started.elapsed();
+        histogram!("hera_task_poll_seconds", "task" => self.name).record(elapsed.as_secs_f64());
+        if elapsed > SLOW_POLL_THRESHOLD {
+            counter!("hera_task_slow_polls_total", "task" => self.name).increment(1);
+            debug!(target: "hera::supervisor", task = self.name, ?elapsed, "Slow task poll");
+        }
+        poll
+    }
+}
+
+/// Returns the number of queued messages of a channel, or `None` once it is closed.
+type DepthFn = Box<dyn Fn() -> Option<usize> + Send + Sync>;
+
+/// Exports the metrics of tokio runtimes and the depths of channels between stages.
+#[derive(Default)]
+pub struct RuntimeMonitor {
+    runtimes: Vec<(&'static str, Handle)>,
+    channels: Vec<(&'static str, usize, DepthFn)>,
+}
+
+impl fmt::Debug for RuntimeMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RuntimeMonitor")
+            .field("runtimes", &self.runtimes.iter().map(|(name, _)| name).collect::<Vec<_>>())
+            .field("channels", &self.channels.iter().map(|(name, ..)| name).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl RuntimeMonitor {
+    /// Exports the metrics of the runtime `name`.
+    pub fn with_runtime(mut self, name: &'static str, runtime: Handle) -> Self {
+        self.runtimes.push((name, runtime));
+        self
+    }
+
+    /// Exports the depth of the channel `name`. The monitor does not keep the channel open.
+    pub fn with_channel<T: Send + 'static>(
+        mut self,
+        name: &'static str,
+        sender: &mpsc::Sender<T>,
+    ) -> Self {
+        let capacity = sender.max_capacity();
+        let sender = sender.downgrade();
+        let depth = move || {
+            let sender = sender.upgrade()?;
+            Some(sender.max_capacity() - sender.capacity())
+        };
+        self.channels.push((name, capacity, Box::new(depth)));
+        self
+    }
+
+    /// Returns the number of queued messages of the channel `name`, or `None` if it is unknown or
+    /// closed.
+    pub fn channel_depth(&self, name: &str) -> Option<usize> {
+        self.channels.iter().find(|(channel, ..)| *channel == name).and_then(|(.., depth)| depth())
+    }
+
+    /// Sets the runtime and channel gauges.
+    pub fn export(&self) {
+        for (name, runtime) in &self.runtimes {
+            let metrics = runtime.metrics();
+            let workers = metrics.num_workers();
+            let busy = (0..workers).map(|worker| metrics.worker_total_busy_duration(worker));
+            gauge!("hera_runtime_workers", "runtime" => *name).set(workers as f64);
+            gauge!("hera_runtime_alive_tasks", "runtime" => *name)
+                .set(metrics.num_alive_tasks() as f64);
+            gauge!("hera_runtime_global_queue_depth", "runtime" => *name)
+                .set(metrics.global_queue_depth() as f64);
+            gauge!("hera_runtime_busy_seconds", "runtime" => *name)
+                .set(busy.sum::<Duration>().as_secs_f64());
+        }
+        for (name, _, depth) in &self.channels {
+            if let Some(depth) = depth() {
+                gauge!("hera_channel_depth", "channel" => *name).set(depth as f64);
+            }
+        }
+    }
+
+    /// Exports the metrics every `interval`, until the task is aborted.
+    pub async fn run(&self, interval: Duration) {
+        for (name, capacity, _) in &self.channels {
+            gauge!("hera_channel_capacity", "channel" => *name).set(*capacity as f64);
+        }
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            self.export();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_channel_depths_until_closed() {
+        let (sender, mut receiver) = mpsc::channel(4);
+        let monitor = RuntimeMonitor::default()
+            .with_runtime("main", Handle::current())
+            .with_channel("blocks", &sender);
+        assert_eq!(monitor.channel_depth("blocks"), Some(0));
+        sender.send(1).await.unwrap();
+        sender.send(2).await.unwrap();
+        assert_eq!(monitor.channel_depth("blocks"), Some(2));
+        receiver.recv().await.unwrap();
+        assert_eq!(monitor.channel_depth("blocks"), Some(1));
+        monitor.export();
+
+        // The monitor does not keep the channel open.
+        drop(sender);
+        receiver.recv().await.unwrap();
+        assert_eq!(receiver.recv().await, None);
+        assert_eq!(monitor.channel_depth("blocks"), None);
+        assert_eq!(monitor.channel_depth("unknown"), None);
+    }
+}
@@ -0,0 +1,151 @@
+//! Readiness of the node's services, gating their startup order.
+
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+use metrics::gauge;
+use tokio::sync::watch;
+use tracing::info;
+
+/// The startup stage of a service. A service starts once every service of the earlier stages is
+/// ready.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ServiceStage {
+    /// Services reading L1 and L2, such as the L1 watcher.
+    Providers,
+    /// Derivation.
+    Pipeline,
+    /// Validation of derived attributes.
+    Validation,
+    /// The RPC server.
+    Rpc,
+    /// Services producing and propagating blocks, such as the sequencer.
+    Gossip,
+}
+
+impl fmt::Display for ServiceStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Providers => "providers",
+            Self::Pipeline => "pipeline",
+            Self::Validation => "validation",
+            Self::Rpc => "rpc",
+            Self::Gossip => "gossip",
+        })
+    }
+}
+
+/// The readiness of the registered services, by name.
+type Services = BTreeMap<&'static str, (ServiceStage, bool)>;
+
+/// Tracks which services are ready.
+#[derive(Debug, Clone, Default)]
+pub struct Readiness {
+    services: Arc<watch::Sender<Services>>,
+}
+
+impl Readiness {
+    /// Registers the service `name` of `stage`, not ready until it reports so through the
+    /// returned handle.
+    pub fn register(&self, name: &'static str, stage: ServiceStage) -> ReadyHandle {
+        self.services.send_modify(|services| {
+            services.insert(name, (stage, false));
+        });
+        gauge!("hera_service_ready", "service" => name).set(0.0);
+        ReadyHandle { readiness: self.clone(), name }
+    }
+
+    /// Returns true if every registered service is ready.
+    pub fn is_ready(&self) -> bool {
+        self.services.borrow().values().all(|(_, ready)| *ready)
+    }
+
+    /// Returns the services that are not ready.
+    pub fn pending(&self) -> Vec<&'static str> {
+        let services = self.services.borrow();
+        services.iter().filter(|(_, (_, ready))| !ready).map(|(name, _)| *name).collect()
+    }
+
+    /// Waits until every registered service of the stages before `stage` is ready.
+    pub async fn wait_for_stage(&self, stage: ServiceStage) {
+        let mut services = self.services.subscribe();
+        // The sender lives as long as `self`, so waiting cannot fail.
+        let _ = services
+            .wait_for(|services| {
+                services.values().all(|(service_stage, ready)| *ready || *service_stage >= stage)
+            })
+            .await;
+    }
+
+    /// Waits until every registered service is ready.
+    pub async fn wait_ready(&self) {
+        let mut services = self.services.subscribe();
+        let _ = services.wait_for(|services| services.values().all(|(_, ready)| *ready)).await;
+    }
+
+    fn set(&self, name: &'static str, ready: bool) {
+        let changed = self.services.send_if_modified(|services| match services.get_mut(name) {
+            Some((_, current)) if *current != ready => {
+                *current = ready;
+                true
+            }
+            _ => false,
+        });
+        if changed {
+            gauge!("hera_service_ready", "service" => name).set(if ready { 1.0 } else { 0.0 });
+            info!(target: "hera::supervisor", service = name, ready, "Service readiness changed");
+        }
+    }
+}
+
+/// Reports the readiness of a registered service.
+#[derive(Debug, Clone)]
+pub struct ReadyHandle {
+    readiness: Readiness,
+    name: &'static str,
+}
+
+impl ReadyHandle {
+    /// Marks the service ready.
+    pub fn ready(&self) {
+        self.readiness.set(self.name, true);
+    }
+
+    /// Marks the service not ready, e.g. while it restarts.
+    pub fn not_ready(&self) {
+        self.readiness.set(self.name, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_for_the_earlier_stages() {
+        let readiness = Readiness::default();
+        let providers = readiness.register("l1-watcher", ServiceStage::Providers);
+        let pipeline = readiness.register("derivation", ServiceStage::Pipeline);
+        let rpc = readiness.register("rpc", ServiceStage::Rpc);
+        assert_eq!(readiness.pending(), ["derivation", "l1-watcher", "rpc"]);
+
+        // The providers start right away, the RPC server waits for derivation.
+        readiness.wait_for_stage(ServiceStage::Providers).await;
+        let waiting = tokio::spawn({
+            let readiness = readiness.clone();
+            async move { readiness.wait_for_stage(ServiceStage::Rpc).await }
+        });
+        providers.ready();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(!waiting.is_finished());
+        pipeline.ready();
+        waiting.await.unwrap();
+
+        assert!(!readiness.is_ready());
+        rpc.ready();
+        readiness.wait_ready().await;
+        pipeline.not_ready();
+        assert_eq!(readiness.pending(), ["derivation"]);
+    }
+}
@@ -0,0 +1,58 @@
+//! A dedicated runtime for Hera's heavy tasks.
+
+use eyre::{Result, WrapErr};
+use tokio::runtime::{Builder, Handle, Runtime};
+
+/// A multi-threaded runtime of its own, keeping derivation and L1 data fetching off the executor
+/// of the host node.
+#[derive(Debug)]
+pub struct WorkerRuntime {
+    runtime: Option<Runtime>,
+}
+
+impl WorkerRuntime {
+    /// Starts a runtime of `threads` worker threads.
+    pub fn new(threads: usize) -> Result<Self> {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(threads.max(1))
+            .thread_name("hera-worker")
+            .enable_all()
+            .build()
+            .wrap_err("failed to start the worker runtime")?;
+        Ok(Self { runtime: Some(runtime) })
+    }
+
+    /// Returns a handle to spawn tasks on the runtime.
+    pub fn handle(&self) -> Handle {
+        self.runtime.as_ref().expect("runtime is only taken on drop").handle().clone()
+    }
+}
+
+impl Drop for WorkerRuntime {
+    fn drop(&mut self) {
+        // Blocking until the tasks stop would panic when dropped from async code.
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::supervisor::Supervisor;
+
+    #[tokio::test]
+    async fn runs_heavy_tasks_on_the_workers() {
+        let mut supervisor =
+            Supervisor::default().with_worker_runtime(WorkerRuntime::new(1).unwrap());
+        supervisor.spawn_worker("worker", || {
+            Box::pin(async {
+                let thread = std::thread::current().name().map(str::to_string);
+                eyre::ensure!(thread.as_deref() == Some("hera-worker"), "ran on {thread:?}");
+                Ok(())
+            })
+        });
+        supervisor.run().await.unwrap();
+    }
+}
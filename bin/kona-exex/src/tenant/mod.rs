@@ -0,0 +1,163 @@
+//! Running the nodes of several L2 chains in one process, e.g. as ExExes of the same reth node.
+//!
+//! Every task of a node runs in the [`scope`] of its chain. The [`ChainLabels`] recorder labels
+//! the metrics recorded in a scope with its `chain_id`, so that dashboards can tell the chains
+//! apart, and [`HeraRpcModules`](crate::rpc::HeraRpcModules) runs RPC methods in the scope of
+//! their chain and can prefix them with its id.
+
+use std::future::Future;
+
+use metrics::{
+    Counter, Gauge, Histogram, Key, KeyName, Label, Metadata, Recorder, SharedString, Unit,
+};
+use tokio::task::JoinHandle;
+
+/// The label carrying the chain id of a metric.
+pub const CHAIN_ID_LABEL: &str = "chain_id";
+
+tokio::task_local! {
+    static CHAIN_ID: Option<u64>;
+}
+
+/// Runs `future` in the scope of the chain `chain_id`.
+pub fn scope<F: Future>(chain_id: u64, future: F) -> impl Future<Output = F::Output> {
+    CHAIN_ID.scope(Some(chain_id), future)
+}
+
+/// Runs `f` in the scope of the chain `chain_id`.
+pub fn sync_scope<T>(chain_id: u64, f: impl FnOnce() -> T) -> T {
+    CHAIN_ID.sync_scope(Some(chain_id), f)
+}
+
+/// Runs `future` in the scope of the current chain, if any. Spawned tasks do not inherit the
+/// scope they are spawned from otherwise.
+pub fn inherit<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    CHAIN_ID.scope(current(), future)
+}
+
+/// Returns the chain of the current scope, if any.
+pub fn current() -> Option<u64> {
+    CHAIN_ID.try_with(|chain_id| *chain_id).ok().flatten()
+}
+
+/// Spawns `future` in the scope of the current chain.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(inherit(future))
+}
+
+/// A metrics recorder adding the `chain_id` label to the metrics recorded in the scope of a
+/// chain, before handing them to the recorder it wraps.
+///
+/// Hosts running Hera wrap their recorder with it before installing it.
+#[derive(Debug)]
+pub struct ChainLabels<R> {
+    inner: R,
+}
+
+impl<R: Recorder> ChainLabels<R> {
+    /// Wraps `inner`.
+    pub const fn new(inner: R) -> Self {
+        Self { inner }
+    }
+
+    fn key(key: &Key) -> Key {
+        match current() {
+            Some(chain_id) => {
+                key.with_extra_labels(vec![Label::new(CHAIN_ID_LABEL, chain_id.to_string())])
+            }
+            None => key.clone(),
+        }
+    }
+}
+
+impl<R: Recorder> Recorder for ChainLabels<R> {
+    fn describe_counter(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key, unit, description);
+    }
+
+    fn describe_gauge(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key, unit, description);
+    }
+
+    fn describe_histogram(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key, unit, description);
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        self.inner.register_counter(&Self::key(key), metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        self.inner.register_gauge(&Self::key(key), metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        self.inner.register_histogram(&Self::key(key), metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use metrics::counter;
+
+    use super::*;
+
+    /// Records the keys of the registered counters.
+    #[derive(Debug, Default)]
+    struct Keys(Mutex<Vec<Key>>);
+
+    impl Recorder for &Keys {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            self.0.lock().unwrap().push(key.clone());
+            Counter::noop()
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    #[test]
+    fn labels_metrics_with_the_chain() {
+        let keys = Keys::default();
+        let recorder = ChainLabels::new(&keys);
+        metrics::with_local_recorder(&recorder, || {
+            counter!("hera_test_total", "reason" => "test").increment(1);
+            sync_scope(10, || counter!("hera_test_total", "reason" => "test").increment(1));
+        });
+
+        let keys = keys.0.into_inner().unwrap();
+        let labels: Vec<Vec<_>> = keys
+            .iter()
+            .map(|key| key.labels().map(|label| (label.key(), label.value())).collect())
+            .collect();
+        assert_eq!(
+            labels,
+            [vec![("reason", "test")], vec![("reason", "test"), ("chain_id", "10")]]
+        );
+    }
+
+    #[tokio::test]
+    async fn spawns_in_the_current_chain() {
+        assert_eq!(current(), None);
+        let spawned = scope(8453, async { spawn(async { current() }).await.unwrap() }).await;
+        assert_eq!(spawned, Some(8453));
+        assert_eq!(spawn(async { current() }).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,306 @@
+//! Rollup configs with chosen hardforks active, for tests of Hera and of crates embedding it.
+//!
+//! Available to Hera's own tests, and to other crates with the `test-utils` feature. A
+//! [`TestConfigBuilder`] activates any subset of the [`Hardfork`]s at any time, regardless of
+//! the order the protocol activates them in. The [`hardfork_matrix`] has an
+//! [`ActivationBoundary`] per hardfork, every earlier one active at genesis, so that a test can
+//! exercise each activation the same way, stepping over it with [`TimeTravel`].
+
+use std::fmt;
+
+use crate::config::RollupConfig;
+
+/// A hardfork after Bedrock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Hardfork {
+    /// Regolith.
+    Regolith,
+    /// Canyon.
+    Canyon,
+    /// Delta.
+    Delta,
+    /// Ecotone.
+    Ecotone,
+    /// Fjord.
+    Fjord,
+    /// Granite.
+    Granite,
+    /// Holocene.
+    Holocene,
+    /// Isthmus.
+    Isthmus,
+    /// Interop.
+    Interop,
+}
+
+impl Hardfork {
+    /// Every hardfork, in activation order.
+    pub const ALL: [Self; 9] = [
+        Self::Regolith,
+        Self::Canyon,
+        Self::Delta,
+        Self::Ecotone,
+        Self::Fjord,
+        Self::Granite,
+        Self::Holocene,
+        Self::Isthmus,
+        Self::Interop,
+    ];
+
+    /// Returns the name of the hardfork, as in [`RollupConfig::hardforks`].
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Regolith => "regolith",
+            Self::Canyon => "canyon",
+            Self::Delta => "delta",
+            Self::Ecotone => "ecotone",
+            Self::Fjord => "fjord",
+            Self::Granite => "granite",
+            Self::Holocene => "holocene",
+            Self::Isthmus => "isthmus",
+            Self::Interop => "interop",
+        }
+    }
+
+    /// Returns the activation timestamp of the hardfork in `config`.
+    pub const fn activation(self, config: &RollupConfig) -> Option<u64> {
+        config.hardforks()[self as usize].1
+    }
+
+    /// Returns true if the hardfork is active at `timestamp` in `config`.
+    pub fn is_active(self, config: &RollupConfig, timestamp: u64) -> bool {
+        self.activation(config).is_some_and(|time| timestamp >= time)
+    }
+
+    /// Schedules the hardfork at `time` in `config`, or unschedules it if `None`.
+    pub fn schedule(self, config: &mut RollupConfig, time: Option<u64>) {
+        *match self {
+            Self::Regolith => &mut config.regolith_time,
+            Self::Canyon => &mut config.canyon_time,
+            Self::Delta => &mut config.delta_time,
+            Self::Ecotone => &mut config.ecotone_time,
+            Self::Fjord => &mut config.fjord_time,
+            Self::Granite => &mut config.granite_time,
+            Self::Holocene => &mut config.holocene_time,
+            Self::Isthmus => &mut config.isthmus_time,
+            Self::Interop => &mut config.interop_time,
+        } = time;
+    }
+}
+
+impl fmt::Display for Hardfork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Builds the rollup config of OP Mainnet with only the chosen hardforks scheduled.
+#[derive(Debug, Clone)]
+pub struct TestConfigBuilder {
+    config: RollupConfig,
+}
+
+impl Default for TestConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestConfigBuilder {
+    /// Starts from the config of OP Mainnet with no hardfork scheduled.
+    pub fn new() -> Self {
+        let config = RollupConfig::from_registry(10).expect("OP Mainnet is in the registry");
+        Self::from_config(config).without_hardforks()
+    }
+
+    /// Starts from `config`, keeping its hardfork schedule.
+    pub const fn from_config(config: RollupConfig) -> Self {
+        Self { config }
+    }
+
+    /// Unschedules every hardfork.
+    pub fn without_hardforks(mut self) -> Self {
+        for fork in Hardfork::ALL {
+            fork.schedule(&mut self.config, None);
+        }
+        self
+    }
+
+    /// Schedules `fork` at `time`.
+    pub fn activate(mut self, fork: Hardfork, time: u64) -> Self {
+        fork.schedule(&mut self.config, Some(time));
+        self
+    }
+
+    /// Schedules every hardfork of `forks` at `time`.
+    pub fn activate_all(self, forks: impl IntoIterator<Item = Hardfork>, time: u64) -> Self {
+        forks.into_iter().fold(self, |builder, fork| builder.activate(fork, time))
+    }
+
+    /// Activates every hardfork of `forks` at genesis.
+    pub fn active_at_genesis(self, forks: impl IntoIterator<Item = Hardfork>) -> Self {
+        let genesis = self.config.genesis.l2_time;
+        self.activate_all(forks, genesis)
+    }
+
+    /// Activates every hardfork up to and including `fork` at genesis, as on a devnet started
+    /// on that hardfork.
+    pub fn active_up_to(self, fork: Hardfork) -> Self {
+        self.active_at_genesis(Hardfork::ALL.into_iter().filter(|other| *other <= fork))
+    }
+
+    /// Sets the L2 block time.
+    pub const fn with_block_time(mut self, block_time: u64) -> Self {
+        self.config.block_time = block_time;
+        self
+    }
+
+    /// Returns the config.
+    pub const fn build(self) -> RollupConfig {
+        self.config
+    }
+}
+
+/// A config where a hardfork activates at an L2 block after genesis, every hardfork before it
+/// being active at genesis and every one after it unscheduled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActivationBoundary {
+    /// The hardfork activating.
+    pub fork: Hardfork,
+    /// The config.
+    pub config: RollupConfig,
+    /// The number of the first L2 block with the hardfork active.
+    pub block: u64,
+}
+
+impl ActivationBoundary {
+    /// Activates `fork` at the L2 block `blocks` after genesis, which must be at least one.
+    pub fn new(fork: Hardfork, blocks: u64) -> Self {
+        assert!(blocks > 0, "hardforks activated at genesis have no boundary");
+        let builder = TestConfigBuilder::new();
+        let genesis = builder.config.genesis.l2.number;
+        let block = genesis + blocks;
+        let previous = Hardfork::ALL.into_iter().filter(|other| *other < fork);
+        let builder = builder.active_at_genesis(previous);
+        let time = builder.config.l2_block_timestamp(block);
+        Self { fork, config: builder.activate(fork, time).build(), block }
+    }
+
+    /// Returns the activation timestamp, that of the first L2 block with the hardfork active.
+    pub const fn time(&self) -> u64 {
+        self.config.l2_block_timestamp(self.block)
+    }
+
+    /// Returns the clock at the last L2 block before the activation.
+    pub const fn before(&self) -> TimeTravel<'_> {
+        TimeTravel::at_block(&self.config, self.block - 1)
+    }
+
+    /// Returns the clock at the first L2 block with the hardfork active.
+    pub const fn at(&self) -> TimeTravel<'_> {
+        TimeTravel::at_block(&self.config, self.block)
+    }
+}
+
+/// Returns the activation boundary of every hardfork, in activation order, each activating at
+/// the L2 block `blocks` after genesis.
+pub fn hardfork_matrix(blocks: u64) -> Vec<ActivationBoundary> {
+    Hardfork::ALL.into_iter().map(|fork| ActivationBoundary::new(fork, blocks)).collect()
+}
+
+/// A position in the L2 chain of a config, moved around in whole blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeTravel<'a> {
+    config: &'a RollupConfig,
+    block: u64,
+}
+
+impl<'a> TimeTravel<'a> {
+    /// Starts at the genesis of `config`.
+    pub const fn at_genesis(config: &'a RollupConfig) -> Self {
+        Self { config, block: config.genesis.l2.number }
+    }
+
+    /// Starts at the L2 block `number`.
+    pub const fn at_block(config: &'a RollupConfig, number: u64) -> Self {
+        Self { config, block: number }
+    }
+
+    /// Starts at the L2 block at or right after `timestamp`.
+    pub const fn at_time(config: &'a RollupConfig, timestamp: u64) -> Self {
+        let since = timestamp.saturating_sub(config.genesis.l2_time);
+        let blocks = since.div_ceil(config.block_time);
+        Self { config, block: config.genesis.l2.number + blocks }
+    }
+
+    /// Moves to the activation of `fork`, or `None` if it is not scheduled.
+    pub fn to_activation(self, fork: Hardfork) -> Option<Self> {
+        Some(Self::at_time(self.config, fork.activation(self.config)?))
+    }
+
+    /// Moves `blocks` L2 blocks forward.
+    pub const fn forward(self, blocks: u64) -> Self {
+        Self { block: self.block + blocks, ..self }
+    }
+
+    /// Moves `blocks` L2 blocks back, no further than genesis.
+    pub const fn back(self, blocks: u64) -> Self {
+        let genesis = self.config.genesis.l2.number;
+        let block = self.block.saturating_sub(blocks);
+        Self { block: if block < genesis { genesis } else { block }, ..self }
+    }
+
+    /// Returns the number of the current L2 block.
+    pub const fn block(&self) -> u64 {
+        self.block
+    }
+
+    /// Returns the timestamp of the current L2 block.
+    pub const fn timestamp(&self) -> u64 {
+        self.config.l2_block_timestamp(self.block)
+    }
+
+    /// Returns the hardforks active at the current L2 block, in activation order.
+    pub fn active_hardforks(&self) -> Vec<Hardfork> {
+        let timestamp = self.timestamp();
+        Hardfork::ALL.into_iter().filter(|fork| fork.is_active(self.config, timestamp)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_arbitrary_hardfork_subsets() {
+        let config = TestConfigBuilder::new()
+            .active_at_genesis([Hardfork::Regolith])
+            .activate(Hardfork::Isthmus, 500)
+            .build();
+        let names: Vec<_> = config.hardforks().iter().map(|(name, _)| *name).collect();
+        let forks: Vec<_> = Hardfork::ALL.iter().map(|fork| fork.name()).collect();
+        assert_eq!(names, forks);
+        assert_eq!(Hardfork::Regolith.activation(&config), Some(config.genesis.l2_time));
+        assert_eq!(config.isthmus_time, Some(500));
+        assert_eq!(config.ecotone_time, None);
+        assert!(config.is_isthmus_active(500) && !config.is_ecotone_active(500));
+    }
+
+    #[test]
+    fn every_boundary_activates_a_single_hardfork() {
+        for boundary in hardfork_matrix(10) {
+            let fork = boundary.fork;
+            boundary.config.check().unwrap();
+            let (before, at) = (boundary.before(), boundary.at());
+            assert_eq!(at.block(), before.forward(1).block());
+            assert_eq!(at.timestamp(), boundary.time());
+            assert_eq!(TimeTravel::at_genesis(&boundary.config).to_activation(fork), Some(at));
+
+            let mut expected: Vec<_> = Hardfork::ALL.into_iter().filter(|f| *f < fork).collect();
+            assert_eq!(before.active_hardforks(), expected, "{fork}");
+            expected.push(fork);
+            assert_eq!(at.active_hardforks(), expected, "{fork}");
+            assert_eq!(before.back(100).block(), boundary.config.genesis.l2.number);
+        }
+    }
+}
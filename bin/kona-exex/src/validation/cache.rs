@@ -0,0 +1,178 @@
+//! Caching of validation results, so that re-deriving the same attributes after a pipeline reset
+//! does not fetch the same canonical blocks again.
+
+use std::{collections::BTreeMap, sync::Mutex};
+
+use alloy_primitives::{keccak256, B256};
+use async_trait::async_trait;
+use metrics::counter;
+use tracing::trace;
+
+use crate::{
+    derive::L2AttributesWithParent,
+    protocol::L2BlockInfo,
+    validation::{AttributesValidator, ValidationError, ValidationOutcome},
+};
+
+/// The default number of L2 blocks whose validation results are cached.
+pub const DEFAULT_VALIDATION_CACHE_SIZE: usize = 1024;
+
+/// An [`AttributesValidator`] remembering the blocks that attributes were validated to produce.
+///
+/// A shallow reset derives again the attributes of blocks validated moments before, on top of the
+/// same parents. Attributes are identified by their hash together with the one of their parent,
+/// and only valid outcomes are cached: attributes building on a given parent always produce the
+/// same block, while invalid ones may turn valid once the canonical chain catches up. A block
+/// replacing a cached one at the same height evicts it.
+#[derive(Debug)]
+pub struct CachingValidator<V> {
+    inner: V,
+    blocks: Mutex<BTreeMap<u64, (B256, L2BlockInfo)>>,
+    capacity: usize,
+}
+
+impl<V> CachingValidator<V> {
+    /// Wraps `inner`, keeping the results of at most `capacity` blocks.
+    pub const fn new(inner: V, capacity: usize) -> Self {
+        Self { inner, blocks: Mutex::new(BTreeMap::new()), capacity }
+    }
+
+    /// Returns the number of cached results.
+    pub fn len(&self) -> usize {
+        self.blocks.lock().unwrap().len()
+    }
+
+    /// Returns true if no result is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Returns the key identifying `attributes` on top of their parent.
+fn cache_key(attributes: &L2AttributesWithParent) -> B256 {
+    let encoded = serde_json::to_vec(&(attributes.parent.block_info.hash, &attributes.attributes))
+        .expect("payload attributes serialize to JSON");
+    keccak256(encoded)
+}
+
+#[async_trait]
+impl<V: AttributesValidator> AttributesValidator for CachingValidator<V> {
+    async fn validate(
+        &self,
+        attributes: &L2AttributesWithParent,
+    ) -> Result<ValidationOutcome, ValidationError> {
+        let number = attributes.parent.block_info.number + 1;
+        let key = cache_key(attributes);
+        let cached = self
+            .blocks
+            .lock()
+            .unwrap()
+            .get(&number)
+            .filter(|(cached, _)| *cached == key)
+            .map(|(_, block)| *block);
+        if let Some(block) = cached {
+            counter!("hera_validation_cache_hits_total").increment(1);
+            trace!(target: "hera::validation", number, %key, "Validation result cached");
+            return Ok(ValidationOutcome::Valid(block));
+        }
+
+        counter!("hera_validation_cache_misses_total").increment(1);
+        let outcome = self.inner.validate(attributes).await?;
+        if let ValidationOutcome::Valid(block) = &outcome {
+            let mut blocks = self.blocks.lock().unwrap();
+            blocks.insert(number, (key, *block));
+            while blocks.len() > self.capacity {
+                blocks.pop_first();
+            }
+        }
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use alloy_primitives::Address;
+    use alloy_rpc_types_engine::{OptimismPayloadAttributes, PayloadAttributes};
+
+    use super::*;
+    use crate::protocol::{BlockId, BlockInfo};
+
+    /// Validates attributes as valid when they carry transactions, counting calls.
+    #[derive(Debug, Default)]
+    struct CountingValidator {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl AttributesValidator for CountingValidator {
+        async fn validate(
+            &self,
+            attributes: &L2AttributesWithParent,
+        ) -> Result<ValidationOutcome, ValidationError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if attributes.attributes.transactions.is_none() {
+                return Ok(ValidationOutcome::Invalid("no transactions".to_string()));
+            }
+            let parent = attributes.parent.block_info;
+            let block = BlockInfo::new(
+                B256::repeat_byte(parent.number as u8 + 1),
+                parent.number + 1,
+                parent.hash,
+                attributes.attributes.payload_attributes.timestamp,
+            );
+            Ok(ValidationOutcome::Valid(L2BlockInfo::new(block, BlockId::default(), 0)))
+        }
+    }
+
+    fn attributes(parent: u64, timestamp: u64) -> L2AttributesWithParent {
+        L2AttributesWithParent {
+            attributes: OptimismPayloadAttributes {
+                payload_attributes: PayloadAttributes {
+                    timestamp,
+                    prev_randao: B256::ZERO,
+                    suggested_fee_recipient: Address::ZERO,
+                    withdrawals: None,
+                    parent_beacon_block_root: None,
+                },
+                transactions: Some(Vec::new()),
+                no_tx_pool: Some(true),
+                gas_limit: Some(30_000_000),
+            },
+            parent: L2BlockInfo::new(
+                BlockInfo::new(B256::repeat_byte(parent as u8), parent, B256::ZERO, 0),
+                BlockId::default(),
+                0,
+            ),
+            derived_from: BlockInfo::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn caches_valid_outcomes() {
+        let validator = CachingValidator::new(CountingValidator::default(), 2);
+        let first = validator.validate(&attributes(1, 2)).await.unwrap();
+        assert_eq!(validator.validate(&attributes(1, 2)).await.unwrap(), first);
+        assert_eq!(validator.inner.calls.load(Ordering::SeqCst), 1);
+
+        // Other attributes for the same block replace the cached result.
+        validator.validate(&attributes(1, 3)).await.unwrap();
+        validator.validate(&attributes(1, 2)).await.unwrap();
+        assert_eq!(validator.inner.calls.load(Ordering::SeqCst), 3);
+
+        // Invalid outcomes are validated again.
+        let mut invalid = attributes(2, 4);
+        invalid.attributes.transactions = None;
+        validator.validate(&invalid).await.unwrap();
+        validator.validate(&invalid).await.unwrap();
+        assert_eq!(validator.inner.calls.load(Ordering::SeqCst), 5);
+        assert_eq!(validator.len(), 1);
+
+        // The lowest blocks are evicted past capacity.
+        for parent in 2..=3 {
+            validator.validate(&attributes(parent, parent + 1)).await.unwrap();
+        }
+        assert_eq!(validator.blocks.lock().unwrap().keys().copied().collect::<Vec<_>>(), [3, 4]);
+    }
+}
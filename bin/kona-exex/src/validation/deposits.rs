@@ -0,0 +1,148 @@
+//! Verification of the deposits included by the sequencer.
+//!
+//! The deposits of derived attributes are decoded from the `TransactionDeposited` events of
+//! their L1 origin, so they are the deposits the canonical block must carry, in the same order.
+//! Deposits are identified by their source hash, which commits to the L1 block and log index of
+//! their event. When a canonical block does not match its attributes, its deposits are compared
+//! to the derived ones to tell a sequencer censoring, reordering or injecting deposits apart
+//! from other divergences.
+
+use std::fmt;
+
+use alloy_primitives::{Bytes, B256};
+
+use crate::protocol::{deposit::DEPOSIT_TX_TYPE, TxDeposit};
+
+/// A deposit the canonical block does not carry as derived from L1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepositViolation {
+    /// A deposit emitted on L1 is missing from the block.
+    Censored {
+        /// The source hash of the deposit.
+        source_hash: B256,
+        /// The position of the deposit among the derived ones.
+        position: usize,
+    },
+    /// A deposit is included out of the order of its L1 event.
+    Reordered {
+        /// The source hash of the deposit.
+        source_hash: B256,
+        /// The position of the deposit among the derived ones.
+        expected: usize,
+        /// The position of the deposit among the included ones.
+        actual: usize,
+    },
+    /// The block carries a deposit that was not emitted on L1.
+    Injected {
+        /// The source hash of the deposit.
+        source_hash: B256,
+        /// The position of the deposit among the included ones.
+        position: usize,
+    },
+}
+
+impl DepositViolation {
+    /// Returns the kind of the violation.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Censored { .. } => "censored",
+            Self::Reordered { .. } => "reordered",
+            Self::Injected { .. } => "injected",
+        }
+    }
+}
+
+impl fmt::Display for DepositViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Censored { source_hash, position } => {
+                write!(f, "deposit {position} ({source_hash}) is censored")
+            }
+            Self::Reordered { source_hash, expected, actual } => {
+                write!(f, "deposit {expected} ({source_hash}) is included at position {actual}")
+            }
+            Self::Injected { source_hash, position } => {
+                write!(f, "deposit at position {position} ({source_hash}) was not emitted on L1")
+            }
+        }
+    }
+}
+
+/// Returns the source hashes of the deposits among EIP-2718 encoded `transactions`, skipping
+/// the ones that do not decode.
+pub fn deposit_source_hashes(transactions: &[Bytes]) -> Vec<B256> {
+    transactions
+        .iter()
+        .filter(|tx| tx.first() == Some(&DEPOSIT_TX_TYPE))
+        .filter_map(|tx| TxDeposit::decode_2718(&mut &tx[..]).ok())
+        .map(|deposit| deposit.source_hash)
+        .collect()
+}
+
+/// Compares the source hashes of the deposits included in a block with the `expected` ones
+/// derived from L1, returning every violation.
+pub fn check_deposits(expected: &[B256], included: &[B256]) -> Vec<DepositViolation> {
+    let mut violations: Vec<_> = expected
+        .iter()
+        .enumerate()
+        .filter(|(_, hash)| !included.contains(hash))
+        .map(|(position, &source_hash)| DepositViolation::Censored { source_hash, position })
+        .collect();
+
+    // Deposits present on both sides must keep their relative order.
+    let position = |hashes: &[B256], hash: &B256| hashes.iter().position(|h| h == hash);
+    let kept_expected = expected.iter().filter(|hash| included.contains(hash));
+    let kept_included = included.iter().filter(|hash| expected.contains(hash));
+    violations.extend(kept_expected.zip(kept_included).filter(|(e, i)| e != i).filter_map(
+        |(&source_hash, _)| {
+            Some(DepositViolation::Reordered {
+                source_hash,
+                expected: position(expected, &source_hash)?,
+                actual: position(included, &source_hash)?,
+            })
+        },
+    ));
+
+    violations.extend(
+        included
+            .iter()
+            .enumerate()
+            .filter(|(_, hash)| !expected.contains(hash))
+            .map(|(position, &source_hash)| DepositViolation::Injected { source_hash, position }),
+    );
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(n: u8) -> B256 {
+        B256::repeat_byte(n)
+    }
+
+    #[test]
+    fn classifies_violations() {
+        let expected = [hash(1), hash(2), hash(3), hash(4)];
+        assert!(check_deposits(&expected, &expected).is_empty());
+
+        let violations = check_deposits(&expected, &[hash(1), hash(4), hash(3), hash(9)]);
+        assert_eq!(
+            violations,
+            [
+                DepositViolation::Censored { source_hash: hash(2), position: 1 },
+                DepositViolation::Reordered { source_hash: hash(3), expected: 2, actual: 2 },
+                DepositViolation::Reordered { source_hash: hash(4), expected: 3, actual: 1 },
+                DepositViolation::Injected { source_hash: hash(9), position: 3 },
+            ]
+        );
+        assert_eq!(violations[0].to_string(), format!("deposit 1 ({}) is censored", hash(2)));
+    }
+
+    #[test]
+    fn reads_source_hashes_of_deposits() {
+        let deposit = TxDeposit { source_hash: hash(7), gas_limit: 21_000, ..Default::default() };
+        let transactions = [deposit.encoded_2718(), Bytes::from_static(&[0x02, 0xc0])];
+        assert_eq!(deposit_source_hashes(&transactions), [hash(7)]);
+    }
+}
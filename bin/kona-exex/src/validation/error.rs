@@ -0,0 +1,44 @@
+//! Errors of [`AttributesValidator`](crate::validation::AttributesValidator)s.
+
+use std::fmt;
+
+use eyre::Report;
+
+use crate::engine::EngineError;
+
+/// An error that kept derived attributes from being validated, as opposed to attributes found
+/// [`Invalid`](crate::validation::ValidationOutcome::Invalid).
+#[derive(Debug)]
+pub enum ValidationError {
+    /// The execution layer failed to build or insert the payload.
+    Engine(EngineError),
+    /// The canonical L2 chain could not be read.
+    L2Provider(Report),
+}
+
+impl ValidationError {
+    /// Returns true if validation may succeed when retried.
+    pub const fn is_recoverable(&self) -> bool {
+        match self {
+            Self::Engine(err) => err.is_recoverable(),
+            Self::L2Provider(_) => true,
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Engine(err) => err.fmt(f),
+            Self::L2Provider(err) => write!(f, "failed to read the L2 chain: {err:#}"),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl From<EngineError> for ValidationError {
+    fn from(err: EngineError) -> Self {
+        Self::Engine(err)
+    }
+}
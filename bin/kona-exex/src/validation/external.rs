@@ -0,0 +1,276 @@
+//! Validation of payload attributes derived by another node, without running derivation.
+//!
+//! In client-diff setups, a second rollup node derives attributes and streams them to Hera,
+//! which only checks them against the canonical L2 chain. Attributes are read as JSON lines from
+//! a file or a pipe by [`ExternalValidation::run`], or submitted one by one through
+//! `hera_validateAttributes`.
+
+use std::{sync::Mutex, time::Duration};
+
+use eyre::{Result, WrapErr};
+use metrics::counter;
+use serde::Serialize;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt},
+    time::Instant,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    derive::L2AttributesWithParent,
+    validation::{AttributesValidator, ValidationError, ValidationOutcome},
+};
+
+/// Default time to wait for the block of external attributes to be synced by the L2 execution
+/// layer.
+pub const DEFAULT_SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Time between two attempts at validating attributes whose block is not synced yet.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time between two reads of a followed stream at its end.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The number of external attributes validated so far, by outcome.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ValidationSummary {
+    /// Attributes producing the canonical block.
+    pub valid: u64,
+    /// Attributes not matching the canonical chain.
+    pub invalid: u64,
+    /// Attributes that could not be validated, e.g. because their block was not synced in time.
+    pub failed: u64,
+}
+
+impl ValidationSummary {
+    /// Returns the number of attributes validated.
+    pub const fn total(&self) -> u64 {
+        self.valid + self.invalid + self.failed
+    }
+}
+
+/// Validates attributes derived by another node with an [`AttributesValidator`].
+#[derive(Debug)]
+pub struct ExternalValidation<V> {
+    validator: V,
+    sync_timeout: Duration,
+    summary: Mutex<ValidationSummary>,
+}
+
+impl<V: AttributesValidator> ExternalValidation<V> {
+    /// Creates a new external validation with `validator`.
+    pub fn new(validator: V) -> Self {
+        Self { validator, sync_timeout: DEFAULT_SYNC_TIMEOUT, summary: Mutex::default() }
+    }
+
+    /// Sets how long recoverable validation errors are retried, e.g. while the L2 execution
+    /// layer syncs the block of the attributes.
+    pub const fn with_sync_timeout(mut self, timeout: Duration) -> Self {
+        self.sync_timeout = timeout;
+        self
+    }
+
+    /// Returns the number of attributes validated so far, by outcome.
+    pub fn summary(&self) -> ValidationSummary {
+        *self.summary.lock().unwrap()
+    }
+
+    /// Validates `attributes`, retrying recoverable errors until the sync timeout.
+    pub async fn validate(
+        &self,
+        attributes: &L2AttributesWithParent,
+    ) -> Result<ValidationOutcome, ValidationError> {
+        let deadline = Instant::now() + self.sync_timeout;
+        let result = loop {
+            match self.validator.validate(attributes).await {
+                Err(err) if err.is_recoverable() && Instant::now() < deadline => {
+                    debug!(target: "hera::validation", %err, "Retrying external attributes");
+                    tokio::time::sleep(RETRY_INTERVAL).await;
+                }
+                result => break result,
+            }
+        };
+
+        let number = attributes.parent.block_info.number + 1;
+        let mut summary = self.summary.lock().unwrap();
+        let outcome = match &result {
+            Ok(ValidationOutcome::Valid(block)) => {
+                debug!(target: "hera::validation", %block, "External attributes are valid");
+                summary.valid += 1;
+                "valid"
+            }
+            Ok(ValidationOutcome::Invalid(reason)) => {
+                warn!(
+                    target: "hera::validation",
+                    number,
+                    %reason,
+                    "External attributes are invalid"
+                );
+                summary.invalid += 1;
+                "invalid"
+            }
+            Err(err) => {
+                warn!(
+                    target: "hera::validation",
+                    number,
+                    %err,
+                    "Failed to validate external attributes"
+                );
+                summary.failed += 1;
+                "failed"
+            }
+        };
+        counter!("hera_external_validations_total", "outcome" => outcome).increment(1);
+        result
+    }
+
+    /// Validates the attributes of every JSON line of `reader`, until its end or, if `follow` is
+    /// set, forever, waiting for more lines like `tail -f`.
+    pub async fn run(
+        &self,
+        mut reader: impl AsyncBufRead + Unpin,
+        follow: bool,
+    ) -> Result<ValidationSummary> {
+        let mut line = String::new();
+        let mut number = 0;
+        loop {
+            let read = reader.read_line(&mut line).await.wrap_err("failed to read attributes")?;
+            if read == 0 && follow {
+                tokio::time::sleep(FOLLOW_INTERVAL).await;
+                continue;
+            }
+            // A followed stream may end in the middle of a line still being written.
+            if read > 0 && !line.ends_with('\n') && follow {
+                continue;
+            }
+            if read == 0 && line.is_empty() {
+                break;
+            }
+
+            number += 1;
+            if !line.trim().is_empty() {
+                let attributes: L2AttributesWithParent = serde_json::from_str(&line)
+                    .wrap_err_with(|| format!("invalid attributes on line {number}"))?;
+                // Failures are counted in the summary rather than stopping the run.
+                let _ = self.validate(&attributes).await;
+            }
+            line.clear();
+        }
+
+        let summary = self.summary();
+        info!(
+            target: "hera::validation",
+            valid = summary.valid,
+            invalid = summary.invalid,
+            failed = summary.failed,
+            "Validated external attributes"
+        );
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use alloy_primitives::B256;
+    use alloy_rpc_types_engine::{OptimismPayloadAttributes, PayloadAttributes};
+    use async_trait::async_trait;
+    use eyre::eyre;
+
+    use super::*;
+    use crate::protocol::{BlockInfo, L2BlockInfo};
+
+    /// Finds attributes building odd blocks invalid, and reports the block of the attributes on
+    /// top of block 10 as not synced `unsynced` times.
+    #[derive(Debug, Default)]
+    struct OddInvalid {
+        unsynced: AtomicU64,
+    }
+
+    #[async_trait]
+    impl AttributesValidator for OddInvalid {
+        async fn validate(
+            &self,
+            attributes: &L2AttributesWithParent,
+        ) -> Result<ValidationOutcome, ValidationError> {
+            let number = attributes.parent.block_info.number + 1;
+            if number == 11 &&
+                self.unsynced
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    .is_ok()
+            {
+                return Err(ValidationError::L2Provider(eyre!(
+                    "L2 block {number} is not synced yet"
+                )));
+            }
+            Ok(if number % 2 == 1 {
+                ValidationOutcome::Invalid(format!("block {number} is odd"))
+            } else {
+                ValidationOutcome::Valid(L2BlockInfo {
+                    block_info: BlockInfo { number, ..Default::default() },
+                    ..Default::default()
+                })
+            })
+        }
+    }
+
+    fn attributes(parent: u64) -> L2AttributesWithParent {
+        L2AttributesWithParent {
+            attributes: OptimismPayloadAttributes {
+                payload_attributes: PayloadAttributes {
+                    timestamp: 1_002,
+                    prev_randao: B256::ZERO,
+                    suggested_fee_recipient: Default::default(),
+                    withdrawals: None,
+                    parent_beacon_block_root: None,
+                },
+                transactions: None,
+                no_tx_pool: Some(true),
+                gas_limit: Some(30_000_000),
+            },
+            parent: L2BlockInfo::new(
+                BlockInfo::new(B256::repeat_byte(1), parent, B256::ZERO, 1_000),
+                Default::default(),
+                0,
+            ),
+            derived_from: BlockInfo::default(),
+        }
+    }
+
+    fn json_lines(parents: &[u64]) -> String {
+        parents
+            .iter()
+            .map(|&parent| serde_json::to_string(&attributes(parent)).unwrap() + "\n")
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn validates_a_stream_of_attributes() {
+        let validation = ExternalValidation::new(OddInvalid::default());
+        let stream = json_lines(&[1, 2, 3]) + "\n";
+        let summary = validation.run(stream.as_bytes(), false).await.unwrap();
+        assert_eq!(summary, ValidationSummary { valid: 2, invalid: 1, failed: 0 });
+
+        let err = validation.run(&b"{}\n"[..], false).await.unwrap_err();
+        assert!(err.to_string().contains("line 1"), "{err}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_for_the_block_to_sync() {
+        let validator = OddInvalid { unsynced: AtomicU64::new(3) };
+        let validation =
+            ExternalValidation::new(validator).with_sync_timeout(Duration::from_secs(5));
+        let start = Instant::now();
+        assert!(matches!(
+            validation.validate(&attributes(10)).await,
+            Ok(ValidationOutcome::Invalid(_))
+        ));
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+
+        // Past the timeout, the attributes are counted as failed.
+        validation.validator.unsynced.store(10, Ordering::SeqCst);
+        assert!(validation.validate(&attributes(10)).await.is_err());
+        assert_eq!(validation.summary(), ValidationSummary { valid: 0, invalid: 1, failed: 1 });
+    }
+}
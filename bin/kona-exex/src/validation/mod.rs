@@ -0,0 +1,83 @@
+//! Validation of derived payload attributes against the canonical L2 chain.
+
+use std::{fmt, str::FromStr};
+
+use async_trait::async_trait;
+use eyre::{bail, Result};
+
+use crate::{derive::L2AttributesWithParent, protocol::L2BlockInfo};
+
+mod cache;
+pub use cache::{CachingValidator, DEFAULT_VALIDATION_CACHE_SIZE};
+
+mod deposits;
+pub use deposits::{check_deposits, deposit_source_hashes, DepositViolation};
+
+mod error;
+pub use error::ValidationError;
+
+mod external;
+pub use external::{ExternalValidation, ValidationSummary, DEFAULT_SYNC_TIMEOUT};
+
+mod rpc;
+pub use rpc::RpcAttributesValidator;
+
+/// The outcome of validating derived attributes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationOutcome {
+    /// The attributes produce the canonical block, which is returned.
+    Valid(L2BlockInfo),
+    /// The attributes do not match the canonical chain.
+    Invalid(String),
+}
+
+/// Validates derived payload attributes.
+#[async_trait]
+pub trait AttributesValidator: Send + Sync {
+    /// Validates the attributes, returning the block they produce if they are canonical.
+    async fn validate(
+        &self,
+        attributes: &L2AttributesWithParent,
+    ) -> Result<ValidationOutcome, ValidationError>;
+}
+
+/// What the driver does when derived attributes fail validation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValidationFailurePolicy {
+    /// Panic, stopping the node.
+    Panic,
+    /// Halt derivation until resumed by the operator.
+    #[default]
+    Halt,
+    /// Log the failure and keep deriving, leaving the safe head where it is.
+    Log,
+    /// Reset the pipeline to the safe head and derive again.
+    Resync,
+}
+
+impl fmt::Display for ValidationFailurePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Panic => "panic",
+            Self::Halt => "halt",
+            Self::Log => "log",
+            Self::Resync => "resync",
+        })
+    }
+}
+
+impl FromStr for ValidationFailurePolicy {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "panic" => Ok(Self::Panic),
+            "halt" => Ok(Self::Halt),
+            "log" => Ok(Self::Log),
+            "resync" => Ok(Self::Resync),
+            _ => {
+                bail!("unknown validation failure policy {s}, expected panic, halt, log or resync")
+            }
+        }
+    }
+}
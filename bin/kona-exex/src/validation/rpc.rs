@@ -0,0 +1,342 @@
+//! An [`AttributesValidator`] checking derived attributes against the blocks of the L2
+//! execution layer's JSON-RPC.
+
+use alloy_primitives::{keccak256, Address, B256, U64};
+use async_trait::async_trait;
+use eyre::{eyre, Result, WrapErr};
+use jsonrpsee::{
+    core::client::ClientT,
+    http_client::{HttpClient, HttpClientBuilder},
+    rpc_params,
+};
+use metrics::counter;
+use serde::Deserialize;
+use tracing::{error, warn};
+
+use crate::{
+    derive::L2AttributesWithParent,
+    protocol::{BlockInfo, L1BlockInfoTx, L2BlockInfo, TxDeposit},
+    validation::{
+        check_deposits, deposit_source_hashes, AttributesValidator, DepositViolation,
+        ValidationError, ValidationOutcome,
+    },
+};
+
+/// Validates derived attributes against the canonical L2 chain of an execution layer synced by
+/// other means, such as unsafe blocks gossiped by the sequencer.
+///
+/// The canonical block on top of the attributes' parent must carry exactly the derived
+/// transactions, with the derived timestamp, fee recipient, randomness, gas limit and parent
+/// beacon block root. A block not synced yet is reported as a recoverable error, not as invalid
+/// attributes.
+#[derive(Debug)]
+pub struct RpcAttributesValidator {
+    client: HttpClient,
+}
+
+impl RpcAttributesValidator {
+    /// Creates a validator reading from the L2 RPC at `url`.
+    pub fn new(url: &str) -> Result<Self> {
+        let client = HttpClientBuilder::default()
+            .build(url)
+            .wrap_err_with(|| format!("invalid L2 RPC URL {url}"))?;
+        Ok(Self { client })
+    }
+
+    /// Creates a validator reading through `client`, e.g. shared from
+    /// [`HttpClients`](crate::endpoint::HttpClients).
+    pub const fn from_client(client: HttpClient) -> Self {
+        Self { client }
+    }
+
+    /// Returns the deposits of the canonical block `number` not included as derived in
+    /// `attributes`.
+    async fn deposit_violations(
+        &self,
+        attributes: &L2AttributesWithParent,
+        number: u64,
+    ) -> Result<Vec<DepositViolation>> {
+        let block: Option<DepositBlock> = self
+            .client
+            .request("eth_getBlockByNumber", rpc_params![U64::from(number), true])
+            .await
+            .wrap_err_with(|| format!("failed to fetch the transactions of L2 block {number}"))?;
+        let block = block.ok_or_else(|| eyre!("L2 block {number} not found"))?;
+        let expected = deposit_source_hashes(
+            attributes.attributes.transactions.as_deref().unwrap_or_default(),
+        );
+        let included: Vec<B256> =
+            block.transactions.iter().filter_map(|tx| tx.source_hash).collect();
+        Ok(check_deposits(&expected, &included))
+    }
+}
+
+/// The deposits of a canonical L2 block, identified by the source hash only deposit
+/// transactions carry.
+#[derive(Debug, Deserialize)]
+struct DepositBlock {
+    transactions: Vec<DepositTransaction>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DepositTransaction {
+    #[serde(default)]
+    source_hash: Option<B256>,
+}
+
+/// The fields of a canonical L2 block the attributes are checked against.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CanonicalBlock {
+    hash: B256,
+    number: U64,
+    parent_hash: B256,
+    timestamp: U64,
+    gas_limit: U64,
+    miner: Address,
+    mix_hash: B256,
+    #[serde(default)]
+    parent_beacon_block_root: Option<B256>,
+    transactions: Vec<B256>,
+}
+
+/// Checks the attributes against the canonical block built on top of their parent, returning
+/// the first mismatch.
+fn compare(attributes: &L2AttributesWithParent, block: &CanonicalBlock) -> Result<(), String> {
+    let parent = attributes.parent.block_info;
+    let payload = &attributes.attributes.payload_attributes;
+    if block.parent_hash != parent.hash {
+        return Err(format!("canonical block {} does not build on {parent}", block.hash));
+    }
+    if block.timestamp.to::<u64>() != payload.timestamp {
+        return Err(format!("timestamp {} differs from {}", payload.timestamp, block.timestamp));
+    }
+    if block.miner != payload.suggested_fee_recipient {
+        return Err(format!(
+            "fee recipient {} differs from {}",
+            payload.suggested_fee_recipient, block.miner
+        ));
+    }
+    if block.mix_hash != payload.prev_randao {
+        return Err(format!("prev randao {} differs from {}", payload.prev_randao, block.mix_hash));
+    }
+    if let Some(gas_limit) = attributes.attributes.gas_limit {
+        if block.gas_limit.to::<u64>() != gas_limit {
+            return Err(format!("gas limit {gas_limit} differs from {}", block.gas_limit));
+        }
+    }
+    if block.parent_beacon_block_root != payload.parent_beacon_block_root {
+        return Err(format!(
+            "parent beacon block root {:?} differs from {:?}",
+            payload.parent_beacon_block_root, block.parent_beacon_block_root
+        ));
+    }
+
+    let derived: Vec<B256> =
+        attributes.attributes.transactions.iter().flatten().map(keccak256).collect();
+    if derived.len() != block.transactions.len() {
+        return Err(format!(
+            "{} derived transactions, {} in the canonical block",
+            derived.len(),
+            block.transactions.len()
+        ));
+    }
+    if let Some((index, (derived, canonical))) =
+        derived.iter().zip(&block.transactions).enumerate().find(|(_, (d, c))| d != c)
+    {
+        return Err(format!("transaction {index} is {derived}, {canonical} in the canonical block"));
+    }
+    Ok(())
+}
+
+/// Returns the reference of the block the attributes build, with the L1 origin of their L1 info
+/// deposit.
+fn block_info(attributes: &L2AttributesWithParent, block: &CanonicalBlock) -> Result<L2BlockInfo> {
+    let tx = attributes
+        .attributes
+        .transactions
+        .as_ref()
+        .and_then(|txs| txs.first())
+        .ok_or_else(|| eyre!("attributes have no L1 info deposit"))?;
+    let info = TxDeposit::decode_2718(&mut &tx[..])
+        .and_then(|deposit| L1BlockInfoTx::decode_calldata(&deposit.input))
+        .wrap_err("invalid L1 info deposit in attributes")?;
+    let block_info =
+        BlockInfo::new(block.hash, block.number.to(), block.parent_hash, block.timestamp.to());
+    Ok(L2BlockInfo::new(block_info, info.id(), info.sequence_number()))
+}
+
+#[async_trait]
+impl AttributesValidator for RpcAttributesValidator {
+    async fn validate(
+        &self,
+        attributes: &L2AttributesWithParent,
+    ) -> Result<ValidationOutcome, ValidationError> {
+        let number = attributes.parent.block_info.number + 1;
+        let block: Option<CanonicalBlock> = self
+            .client
+            .request("eth_getBlockByNumber", rpc_params![U64::from(number), false])
+            .await
+            .map_err(|err| {
+                ValidationError::L2Provider(
+                    eyre!(err).wrap_err(format!("failed to fetch L2 block {number}")),
+                )
+            })?;
+        let block = block.ok_or_else(|| {
+            ValidationError::L2Provider(eyre!("L2 block {number} is not synced yet"))
+        })?;
+
+        if let Err(reason) = compare(attributes, &block) {
+            let violations = match self.deposit_violations(attributes, number).await {
+                Ok(violations) => violations,
+                Err(err) => {
+                    warn!(target: "hera::validation", %err, number, "Failed to check deposits");
+                    Vec::new()
+                }
+            };
+            if violations.is_empty() {
+                return Ok(ValidationOutcome::Invalid(reason));
+            }
+            for violation in &violations {
+                counter!("hera_deposit_violations_total", "kind" => violation.kind()).increment(1);
+            }
+            let violations: Vec<String> = violations.iter().map(ToString::to_string).collect();
+            error!(
+                target: "hera::validation",
+                number,
+                ?violations,
+                "Sequencer did not include the deposits emitted on L1"
+            );
+            return Ok(ValidationOutcome::Invalid(format!("{reason}: {}", violations.join(", "))));
+        }
+        match block_info(attributes, &block) {
+            Ok(block) => Ok(ValidationOutcome::Valid(block)),
+            Err(err) => Ok(ValidationOutcome::Invalid(format!("{err:#}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::Bytes;
+    use alloy_rpc_types_engine::{OptimismPayloadAttributes, PayloadAttributes};
+
+    use super::*;
+    use crate::{config::RollupConfig, protocol::l1_info::L1BlockInfoBedrock};
+
+    fn attributes() -> L2AttributesWithParent {
+        let config = RollupConfig::from_registry(10).unwrap();
+        let info = L1BlockInfoTx::Bedrock(L1BlockInfoBedrock {
+            number: 100,
+            block_hash: B256::repeat_byte(0xaa),
+            sequence_number: 2,
+            ..Default::default()
+        });
+        let parent = L2BlockInfo::new(
+            BlockInfo::new(B256::repeat_byte(1), 41, B256::ZERO, 1_000),
+            info.id(),
+            1,
+        );
+        L2AttributesWithParent {
+            attributes: OptimismPayloadAttributes {
+                payload_attributes: PayloadAttributes {
+                    timestamp: 1_002,
+                    prev_randao: B256::repeat_byte(2),
+                    suggested_fee_recipient: Address::repeat_byte(3),
+                    withdrawals: None,
+                    parent_beacon_block_root: None,
+                },
+                transactions: Some(vec![
+                    info.to_deposit_tx(&config, 1_002).encoded_2718(),
+                    Bytes::from_static(&[0x02, 0xc0]),
+                ]),
+                no_tx_pool: Some(true),
+                gas_limit: Some(30_000_000),
+            },
+            parent,
+            derived_from: BlockInfo::default(),
+        }
+    }
+
+    fn canonical(attributes: &L2AttributesWithParent) -> CanonicalBlock {
+        CanonicalBlock {
+            hash: B256::repeat_byte(9),
+            number: U64::from(42),
+            parent_hash: attributes.parent.block_info.hash,
+            timestamp: U64::from(1_002),
+            gas_limit: U64::from(30_000_000),
+            miner: Address::repeat_byte(3),
+            mix_hash: B256::repeat_byte(2),
+            parent_beacon_block_root: None,
+            transactions: attributes
+                .attributes
+                .transactions
+                .iter()
+                .flatten()
+                .map(keccak256)
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_matching_block() {
+        let attributes = attributes();
+        let block = canonical(&attributes);
+        assert_eq!(compare(&attributes, &block), Ok(()));
+
+        let info = block_info(&attributes, &block).unwrap();
+        assert_eq!(info.block_info.hash, B256::repeat_byte(9));
+        assert_eq!(info.block_info.number, 42);
+        assert_eq!(info.l1_origin.number, 100);
+        assert_eq!(info.seq_num, 2);
+    }
+
+    #[test]
+    fn reports_mismatches() {
+        let attributes = attributes();
+        type Mutation = fn(&mut CanonicalBlock);
+
+        let mismatches: [(Mutation, &str); 5] = [
+            (|block| block.parent_hash = B256::ZERO, "does not build on"),
+            (|block| block.timestamp = U64::from(1_004), "timestamp"),
+            (|block| block.gas_limit = U64::from(1), "gas limit"),
+            (|block| block.transactions.pop().map(drop).unwrap(), "derived transactions"),
+            (|block| block.transactions[1] = B256::ZERO, "transaction 1"),
+        ];
+        for (mutate, expected) in mismatches {
+            let mut block = canonical(&attributes);
+            mutate(&mut block);
+            let reason = compare(&attributes, &block).unwrap_err();
+            assert!(reason.contains(expected), "{reason}");
+        }
+    }
+
+    #[test]
+    fn decodes_canonical_block() {
+        let block: CanonicalBlock = serde_json::from_value(serde_json::json!({
+            "hash": B256::repeat_byte(9),
+            "number": "0x2a",
+            "parentHash": B256::repeat_byte(1),
+            "timestamp": "0x3ea",
+            "gasLimit": "0x1c9c380",
+            "miner": Address::repeat_byte(3),
+            "mixHash": B256::repeat_byte(2),
+            "transactions": [B256::repeat_byte(4)],
+            "stateRoot": B256::ZERO,
+        }))
+        .unwrap();
+        assert_eq!(block.number.to::<u64>(), 42);
+        assert_eq!(block.transactions, [B256::repeat_byte(4)]);
+
+        let block: DepositBlock = serde_json::from_value(serde_json::json!({
+            "transactions": [
+                { "type": "0x7e", "sourceHash": B256::repeat_byte(5) },
+                { "type": "0x2", "hash": B256::repeat_byte(6) },
+            ],
+        }))
+        .unwrap();
+        let source_hashes: Vec<_> = block.transactions.iter().map(|tx| tx.source_hash).collect();
+        assert_eq!(source_hashes, [Some(B256::repeat_byte(5)), None]);
+    }
+}
@@ -0,0 +1,60 @@
+//! Build information of the Hera binary.
+//!
+//! The git commit, build profile and enabled features are embedded at build time, and reported
+//! by `hera --version`, `optimism_version` and `hera_buildInfo`, so that bug reports identify
+//! the exact build they are about.
+
+use serde::{Deserialize, Serialize};
+
+/// The version of the crate.
+pub const CARGO_PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The git commit Hera was built from, or `unknown` if built outside of a git checkout.
+pub const GIT_SHA: &str = env!("HERA_GIT_SHA");
+
+/// The cargo profile Hera was built with, `debug` or `release`.
+pub const BUILD_PROFILE: &str = env!("HERA_BUILD_PROFILE");
+
+/// The comma-separated cargo features Hera was built with.
+pub const FEATURES: &str = env!("HERA_FEATURES");
+
+/// The version reported by `hera -V` and `optimism_version`: the crate version and abbreviated
+/// commit.
+pub const SHORT_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "-", env!("HERA_GIT_SHA_SHORT"));
+
+/// The version reported by `hera --version`, with every build detail.
+pub const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "\nCommit: ",
+    env!("HERA_GIT_SHA"),
+    "\nProfile: ",
+    env!("HERA_BUILD_PROFILE"),
+    "\nFeatures: ",
+    env!("HERA_FEATURES"),
+);
+
+/// The build information of the running binary.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildInfo {
+    /// The crate version.
+    pub version: String,
+    /// The git commit.
+    pub git_sha: String,
+    /// The cargo profile.
+    pub profile: String,
+    /// The enabled cargo features.
+    pub features: Vec<String>,
+}
+
+impl BuildInfo {
+    /// Returns the build information of the running binary.
+    pub fn current() -> Self {
+        Self {
+            version: CARGO_PKG_VERSION.to_string(),
+            git_sha: GIT_SHA.to_string(),
+            profile: BUILD_PROFILE.to_string(),
+            features: FEATURES.split(',').filter(|f| !f.is_empty()).map(String::from).collect(),
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! End-to-end derivation on a local L1 devnet: a reth dev node mines L1 blocks, a minimal deposit
+//! contract is deployed on it, a scripted batcher posts the batches of a known L2 chain, and the
+//! pipeline must derive that chain back, deposits included.
+//!
+//! Run it with a reth binary, taken from `HERA_DEVNET_RETH` or the `PATH`:
+//!
+//! ```sh
+//! HERA_DEVNET_RETH=<path to reth> cargo test -p kona-exex --features devnet --test devnet
+//! ```
+//!
+//! reth runs as a child process rather than in-process, so that Hera does not build against
+//! reth's dependency tree. In dev mode it mines a block every second and signs the transactions
+//! of its prefunded accounts, which the batcher sends with `eth_sendTransaction`.
+//!
+//! Batch derivation needs no contract on L1, only the batch inbox address. Deposits are emitted
+//! by a contract logging its calldata as a `TransactionDeposited` event, as the `OptimismPortal`
+//! does once it checked and encoded a deposit.
+
+#![cfg(feature = "devnet")]
+
+use std::{
+    net::TcpListener,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+
+use alloy_primitives::{address, keccak256, Address, Bytes, TxKind, B256, U256};
+use eyre::{bail, ensure, eyre, Result, WrapErr};
+use jsonrpsee::{
+    core::client::ClientT,
+    http_client::{HttpClient, HttpClientBuilder},
+    rpc_params,
+};
+use kona_exex::{
+    batcher::{ChannelOut, Compression, DataAvailability, TxData},
+    config::RollupConfig,
+    derive::{L2AttributesWithParent, Pipeline, PipelineBuilder, StepResult},
+    l1::{ChainProvider, RpcChainProvider},
+    protocol::{
+        deposit::{DEPOSIT_EVENT_TOPIC, DEPOSIT_EVENT_VERSION_0},
+        Batch, BlockId, BlockInfo, ChannelId, L2BlockInfo, SingleBatch, TxDeposit,
+    },
+};
+use serde_json::{json, Value};
+use tokio::time::Instant;
+
+/// The first prefunded account of reth's dev chain, posting batches and deposits.
+const DEV_ACCOUNT: Address = address!("f39Fd6e51aad88F6F4ce6aB8827279cffFb92266");
+
+/// The batch inbox of the devnet rollup.
+const BATCH_INBOX: Address = address!("ff00000000000000000000000000000000000901");
+
+/// The recipient of the deposit made on the devnet.
+const DEPOSIT_RECIPIENT: Address = address!("4242424242424242424242424242424242424242");
+
+/// The number of L2 blocks posted by the batcher.
+const L2_BLOCKS: u64 = 8;
+
+/// How long the devnet may take to start, or to include a transaction.
+const DEVNET_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long derivation may go without progress before it is considered stuck.
+const STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A reth dev node, killed and wiped when dropped.
+struct Devnet {
+    child: Child,
+    datadir: PathBuf,
+    url: String,
+    client: HttpClient,
+}
+
+impl Devnet {
+    /// Launches a reth dev node mining a block every second, and waits for its RPC.
+    async fn launch() -> Result<Self> {
+        let reth = std::env::var("HERA_DEVNET_RETH").unwrap_or_else(|_| "reth".to_string());
+        let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+        let datadir = std::env::temp_dir().join(format!("hera-devnet-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&datadir);
+        let child = Command::new(&reth)
+            .args(["node", "--dev", "--dev.block-time", "1s", "--ipcdisable"])
+            .args(["--http", "--http.addr", "127.0.0.1", "--http.api", "eth"])
+            .args(["--http.port", &port.to_string()])
+            .args(["--port", "0", "--discovery.port", "0", "--authrpc.port", "0"])
+            .arg("--datadir")
+            .arg(&datadir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .wrap_err_with(|| format!("failed to launch {reth}, set HERA_DEVNET_RETH"))?;
+        let url = format!("http://127.0.0.1:{port}");
+        let client = HttpClientBuilder::default().build(&url)?;
+        let devnet = Self { child, datadir, url, client };
+
+        let started = Instant::now();
+        while devnet.client.request::<Value, _>("eth_chainId", rpc_params![]).await.is_err() {
+            ensure!(started.elapsed() < DEVNET_TIMEOUT, "reth dev node did not start");
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        Ok(devnet)
+    }
+
+    /// Sends a transaction from the dev account and returns its receipt once included.
+    async fn send(&self, to: Option<Address>, input: Bytes) -> Result<Value> {
+        let tx = json!({ "from": DEV_ACCOUNT, "to": to, "input": input, "gas": "0x1e8480" });
+        let hash: B256 = self
+            .client
+            .request("eth_sendTransaction", rpc_params![tx])
+            .await
+            .wrap_err("failed to send devnet transaction")?;
+        let started = Instant::now();
+        loop {
+            let receipt: Option<Value> =
+                self.client.request("eth_getTransactionReceipt", rpc_params![hash]).await?;
+            if let Some(receipt) = receipt {
+                ensure!(receipt["status"] == "0x1", "devnet transaction {hash} reverted");
+                return Ok(receipt);
+            }
+            ensure!(started.elapsed() < DEVNET_TIMEOUT, "transaction {hash} not included");
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+}
+
+impl Drop for Devnet {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.datadir);
+    }
+}
+
+/// Returns the number of the block a transaction was included in, from its receipt.
+fn receipt_block(receipt: &Value) -> Result<u64> {
+    let number = receipt["blockNumber"].as_str().ok_or_else(|| eyre!("receipt without block"))?;
+    Ok(u64::from_str_radix(number.trim_start_matches("0x"), 16)?)
+}
+
+/// Returns the init code of the deposit contract, whose runtime code logs its calldata as a
+/// version 0 `TransactionDeposited` event from and to the caller.
+fn deposit_contract_code() -> Bytes {
+    let mut runtime = vec![
+        0x36, // CALLDATASIZE
+        0x60, 0x00, // PUSH1 0
+        0x60, 0x00, // PUSH1 0
+        0x37, // CALLDATACOPY
+        0x7f, // PUSH32 version
+    ];
+    runtime.extend_from_slice(DEPOSIT_EVENT_VERSION_0.as_slice());
+    runtime.extend_from_slice(&[
+        0x33, // CALLER, as recipient
+        0x33, // CALLER, as sender
+        0x7f, // PUSH32 topic
+    ]);
+    runtime.extend_from_slice(DEPOSIT_EVENT_TOPIC.as_slice());
+    runtime.extend_from_slice(&[
+        0x36, // CALLDATASIZE
+        0x60, 0x00, // PUSH1 0
+        0xa4, // LOG4
+        0x00, // STOP
+    ]);
+    let len = runtime.len() as u8;
+    // Copies the runtime code following these 12 bytes into memory, and returns it.
+    let mut init = vec![0x60, len, 0x80, 0x60, 12, 0x60, 0x00, 0x39, 0x60, 0x00, 0xf3, 0x00];
+    init.extend(runtime);
+    init.into()
+}
+
+/// Returns the ABI-encoded opaque data of a deposit of `gas_limit` gas calling `to`.
+fn deposit_data(to: Address, gas_limit: u64) -> Bytes {
+    let mut opaque = vec![0; 64];
+    opaque.extend_from_slice(&gas_limit.to_be_bytes());
+    opaque.push(0);
+    opaque.extend_from_slice(to.as_slice());
+    let mut data = U256::from(32).to_be_bytes::<32>().to_vec();
+    data.extend_from_slice(&U256::from(opaque.len()).to_be_bytes::<32>());
+    data.extend_from_slice(&opaque);
+    data.resize(data.len().next_multiple_of(32), 0);
+    data.into()
+}
+
+/// Returns the hash of the L2 block `number` of the scripted chain.
+fn l2_hash(config: &RollupConfig, number: u64) -> B256 {
+    if number == 0 {
+        return config.genesis.l2.hash;
+    }
+    keccak256(format!("devnet L2 block {number}"))
+}
+
+/// An L2 block of the scripted chain.
+struct ScriptedBlock {
+    block: L2BlockInfo,
+    batch: SingleBatch,
+    /// Whether the block opens the epoch of the deposit.
+    deposit: bool,
+}
+
+/// Scripts `L2_BLOCKS` blocks on top of the genesis, adopting each next L1 block as origin once
+/// its timestamp is reached, as a sequencer does.
+async fn script(
+    config: &RollupConfig,
+    l1: &dyn ChainProvider,
+    deposit_block: u64,
+) -> Result<Vec<ScriptedBlock>> {
+    let mut origin = l1.block_info_by_number(config.genesis.l1.number).await?;
+    let mut parent = L2BlockInfo::new(
+        BlockInfo::new(config.genesis.l2.hash, 0, B256::ZERO, config.genesis.l2_time),
+        origin.id(),
+        0,
+    );
+    let mut blocks = Vec::new();
+    while blocks.len() < L2_BLOCKS as usize || origin.number <= deposit_block {
+        let number = parent.block_info.number + 1;
+        let timestamp = parent.block_info.timestamp + config.block_time;
+        let next = loop {
+            match l1.block_info_by_number(origin.number + 1).await {
+                Ok(next) => break next,
+                Err(_) => tokio::time::sleep(Duration::from_millis(200)).await,
+            }
+        };
+        let deposit = next.timestamp <= timestamp && next.number == deposit_block;
+        let seq_num = if next.timestamp <= timestamp {
+            origin = next;
+            0
+        } else {
+            parent.seq_num + 1
+        };
+        let batch = SingleBatch {
+            parent_hash: parent.block_info.hash,
+            epoch_num: origin.number,
+            epoch_hash: origin.hash,
+            timestamp,
+            transactions: vec![Bytes::from(vec![0x02, 0xc0, number as u8])],
+        };
+        let block = L2BlockInfo::new(
+            BlockInfo::new(l2_hash(config, number), number, parent.block_info.hash, timestamp),
+            origin.id(),
+            seq_num,
+        );
+        blocks.push(ScriptedBlock { block, batch, deposit });
+        parent = block;
+    }
+    Ok(blocks)
+}
+
+/// Returns the calldata of a batcher transaction posting `blocks` in a single channel.
+fn batcher_calldata(blocks: &[ScriptedBlock]) -> Result<Bytes> {
+    let mut channel = ChannelOut::new(ChannelId([7; 16]), Compression::default(), 100_000, 0);
+    for block in blocks {
+        ensure!(channel.add_batch(&Batch::Single(block.batch.clone()))?, "channel is full");
+    }
+    let frames = channel.into_frames(100_000)?;
+    Ok(TxData { frames, data_availability: DataAvailability::Calldata }.calldata())
+}
+
+/// Steps `pipeline` until it derives the attributes of the block after `cursor`.
+async fn next_attributes(
+    pipeline: &mut impl Pipeline,
+    cursor: L2BlockInfo,
+) -> Result<L2AttributesWithParent> {
+    let mut progress = Instant::now();
+    loop {
+        match pipeline.step(cursor).await {
+            StepResult::PreparedAttributes => {
+                if let Some(attributes) = pipeline.next() {
+                    return Ok(attributes);
+                }
+            }
+            StepResult::AdvancedOrigin => progress = Instant::now(),
+            // At the L1 head, the pipeline waits for the next block.
+            StepResult::OriginAdvanceErr(err) => {
+                ensure!(
+                    progress.elapsed() < STALL_TIMEOUT,
+                    "derivation stalled on top of {cursor}: {err}"
+                );
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            StepResult::StepFailed(err) => bail!("derivation stopped on top of {cursor}: {err}"),
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn derives_the_batches_and_deposits_posted_on_a_devnet() -> Result<()> {
+    let devnet = Devnet::launch().await?;
+    let l1 = Arc::new(RpcChainProvider::new(&devnet.url)?);
+
+    // The rollup starts at the L1 block deploying its deposit contract.
+    let deployment = devnet.send(None, deposit_contract_code()).await?;
+    let deposit_contract: Address = serde_json::from_value(deployment["contractAddress"].clone())?;
+    let l1_genesis = l1.block_info_by_number(receipt_block(&deployment)?).await?;
+    let mut config = RollupConfig::from_registry(10).ok_or_else(|| eyre!("no OP Mainnet"))?;
+    config.genesis.l1 = l1_genesis.id();
+    config.genesis.l2 = BlockId::new(keccak256("devnet L2 genesis"), 0);
+    config.genesis.l2_time = l1_genesis.timestamp;
+    if let Some(system_config) = &mut config.genesis.system_config {
+        system_config.batcher_address = DEV_ACCOUNT;
+    }
+    config.batch_inbox_address = BATCH_INBOX;
+    config.deposit_contract_address = deposit_contract;
+    config.l1_chain_id = 1337;
+    let config = Arc::new(config);
+
+    let deposit = devnet.send(Some(deposit_contract), deposit_data(DEPOSIT_RECIPIENT, 100_000));
+    let deposit_block = receipt_block(&deposit.await?)?;
+    let blocks = script(&config, l1.as_ref(), deposit_block).await?;
+    devnet.send(Some(BATCH_INBOX), batcher_calldata(&blocks)?).await?;
+
+    let mut pipeline = PipelineBuilder::new(config.clone(), l1.clone()).build()?;
+    let mut cursor = L2BlockInfo::new(
+        BlockInfo::new(config.genesis.l2.hash, 0, B256::ZERO, config.genesis.l2_time),
+        l1_genesis.id(),
+        0,
+    );
+    pipeline.reset(cursor, l1_genesis).await?;
+    for expected in &blocks {
+        let derived = next_attributes(&mut pipeline, cursor).await?;
+        let number = expected.block.block_info.number;
+        ensure!(derived.parent == cursor, "L2 block {number} derived on top of {}", derived.parent);
+        let attributes = &derived.attributes;
+        ensure!(
+            attributes.payload_attributes.timestamp == expected.batch.timestamp,
+            "L2 block {number} derived at {}",
+            attributes.payload_attributes.timestamp
+        );
+
+        // The L1 info deposit, the user deposit opening its epoch, then the batch.
+        let transactions = attributes.transactions.clone().unwrap_or_default();
+        let (deposits, batched) = transactions.split_at(transactions.len() - 1);
+        ensure!(batched == expected.batch.transactions, "L2 block {number} has other transactions");
+        ensure!(
+            deposits.len() == 1 + usize::from(expected.deposit),
+            "L2 block {number} has {} deposits",
+            deposits.len()
+        );
+        if expected.deposit {
+            let deposit = TxDeposit::decode_2718(&mut deposits[1].as_ref())?;
+            ensure!(deposit.from == DEV_ACCOUNT, "deposit from {}", deposit.from);
+            ensure!(deposit.to == TxKind::Call(DEV_ACCOUNT), "deposit to {:?}", deposit.to);
+            ensure!(deposit.gas_limit == 100_000, "deposit of {} gas", deposit.gas_limit);
+            ensure!(deposit.input.ends_with(DEPOSIT_RECIPIENT.as_slice()), "deposit calldata");
+        }
+        cursor = expected.block;
+    }
+    Ok(())
+}
@@ -0,0 +1,786 @@
+//! Derivation regression suite: derives ranges of OP Mainnet and Base from L1 data and checks
+//! the derived blocks against the canonical L2 block hashes.
+//!
+//! Run it with `cargo test -p kona-exex --features regression --test regression`.
+//!
+//! A fixture holds the L1 and L2 RPC responses and blob sidecars read while deriving its range,
+//! replayed through the [`RpcChainProvider`] and [`RpcL2StateProvider`] so that the whole
+//! pipeline runs as in production, including the checks of L1 data against its headers.
+//!
+//! Derivation only decides part of an L2 block: each derived block is sealed with the execution
+//! outputs of the canonical block (state, receipts, gas used, base fee and extra data) and the
+//! rest taken from the derived attributes, so any difference in the derived transactions,
+//! timestamp, fee recipient, randomness, gas limit or roots changes the block hash.
+//!
+//! The `recorded` test replays every fixture checked in under `tests/fixtures/regression`, and
+//! fails if there is none. Fixtures are ranges of the live chains, recorded from archive
+//! endpoints with the ignored `record` test, which fails unless the range derives to the
+//! canonical chain:
+//!
+//! ```sh
+//! HERA_REGRESSION_FIXTURE=op-mainnet HERA_REGRESSION_CHAIN_ID=10 \
+//! HERA_REGRESSION_SAFE_HEAD=130000000 HERA_REGRESSION_BLOCKS=100 \
+//! HERA_REGRESSION_L1_RPC=<url> HERA_REGRESSION_L2_RPC=<url> HERA_REGRESSION_BEACON_URL=<url> \
+//! cargo test -p kona-exex --features regression --test regression record -- --ignored
+//! ```
+//!
+//! `HERA_REGRESSION_FIXTURE` restricts `recorded` to a single fixture.
+//!
+//! The `synthetic_*` tests are no regression coverage: they generate an L1 chain posting batches
+//! through Hera's own channel encoding on the chains' rollup configs, and check that the
+//! pipeline derives the L2 chain the test builds from the same batches. They catch breakage of
+//! the replay harness and of the pipeline's internal consistency only.
+
+#![cfg(feature = "regression")]
+
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use alloy_consensus::{Eip658Value, Header, Receipt, ReceiptWithBloom};
+use alloy_eips::eip4844::{Blob, Bytes48};
+use alloy_primitives::{address, keccak256, Address, Bloom, Bytes, B256, U128, U64};
+use alloy_rlp::Encodable;
+use alloy_rpc_types_engine::OptimismPayloadAttributes;
+use alloy_rpc_types_eth::Block;
+use alloy_trie::{HashBuilder, Nibbles, EMPTY_ROOT_HASH};
+use async_trait::async_trait;
+use eyre::{bail, ensure, eyre, Result, WrapErr};
+use jsonrpsee::{
+    core::{client::ClientT, params::ArrayParams},
+    http_client::{HttpClient, HttpClientBuilder},
+    rpc_params,
+    server::{Server, ServerHandle},
+    types::{error::INTERNAL_ERROR_CODE, ErrorObjectOwned},
+    RpcModule,
+};
+use kona_exex::{
+    batcher::{ChannelOut, Compression, DataAvailability, TxData, DEFAULT_TARGET_FRAME_SIZE},
+    blobs::{BeaconClient, BlobFetcher, BlobProvider, BlobSidecar},
+    config::{RollupConfig, SystemConfig},
+    derive::{Pipeline, PipelineBuilder, StepResult},
+    l1::{verify::receipts_root, ChainProvider, L1Receipt, RpcChainProvider},
+    output::{L2StateProvider, RpcL2StateProvider},
+    protocol::{
+        l1_info::L1BlockInfoEcotone, Batch, BlockInfo, ChannelId, L1BlockInfoTx, L2BlockInfo,
+        SingleBatch, TxDeposit,
+    },
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::time::Instant;
+
+/// How long derivation may go without progress before the range is considered stuck.
+const STALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The methods the providers call, served from the recorded responses.
+const METHODS: [&str; 3] = ["eth_getBlockByNumber", "eth_getBlockByHash", "eth_getBlockReceipts"];
+
+/// A recorded derivation range.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Fixture {
+    /// The L2 chain id.
+    chain_id: u64,
+    /// The number of the L2 safe head the range is derived on top of.
+    safe_head: u64,
+    /// The canonical hashes of the derived L2 blocks, from the block after the safe head.
+    blocks: Vec<B256>,
+    /// The L1 RPC responses.
+    l1: Vec<Recorded>,
+    /// The L2 RPC responses.
+    l2: Vec<Recorded>,
+    /// The blob sidecars, by L1 block hash.
+    blobs: BTreeMap<B256, Vec<RecordedSidecar>>,
+}
+
+impl Fixture {
+    fn path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/regression")
+            .join(format!("{name}.json"))
+    }
+
+    /// Returns the names of the fixtures checked in, sorted.
+    fn names() -> Result<Vec<String>> {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/regression");
+        let mut names = Vec::new();
+        if dir.is_dir() {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|extension| extension == "json") {
+                    let name = path.file_stem().and_then(|stem| stem.to_str());
+                    names.extend(name.map(str::to_string));
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn load(name: &str) -> Result<Self> {
+        let path = Self::path(name);
+        let json = std::fs::read(&path).wrap_err_with(|| {
+            format!("missing fixture {}, record it with the `record` test", path.display())
+        })?;
+        serde_json::from_slice(&json).wrap_err_with(|| format!("invalid fixture {name}"))
+    }
+
+    fn save(&self, name: &str) -> Result<()> {
+        let path = Self::path(name);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(&path, serde_json::to_vec(self)?)
+            .wrap_err_with(|| format!("failed to write fixture {}", path.display()))
+    }
+}
+
+/// A JSON-RPC call and its result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Recorded {
+    method: String,
+    params: Value,
+    result: Value,
+}
+
+impl Recorded {
+    fn new(method: &str, params: Value, result: Value) -> Self {
+        Self { method: method.to_string(), params, result }
+    }
+}
+
+/// A blob sidecar, without the beacon API encoding of its index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordedSidecar {
+    index: u64,
+    blob: Box<Blob>,
+    kzg_commitment: Bytes48,
+}
+
+/// Serves recorded JSON-RPC responses, recording the ones it misses from `upstream` if set.
+#[derive(Debug)]
+struct RecordedRpc {
+    records: Mutex<BTreeMap<String, Recorded>>,
+    upstream: Option<HttpClient>,
+}
+
+impl RecordedRpc {
+    fn new(records: Vec<Recorded>, upstream: Option<HttpClient>) -> Self {
+        let records = records
+            .into_iter()
+            .map(|record| (format!("{}{}", record.method, record.params), record))
+            .collect();
+        Self { records: Mutex::new(records), upstream }
+    }
+
+    fn records(&self) -> Vec<Recorded> {
+        self.records.lock().unwrap().values().cloned().collect()
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, String> {
+        let key = format!("{method}{params}");
+        if let Some(record) = self.records.lock().unwrap().get(&key) {
+            return Ok(record.result.clone());
+        }
+        let Some(upstream) = &self.upstream else { return Err(format!("{key} was not recorded")) };
+
+        let mut array = ArrayParams::new();
+        for param in params.as_array().into_iter().flatten() {
+            array.insert(param).map_err(|err| err.to_string())?;
+        }
+        let result: Value = upstream.request(method, array).await.map_err(|err| err.to_string())?;
+        let record = Recorded::new(method, params, result.clone());
+        self.records.lock().unwrap().insert(key, record);
+        Ok(result)
+    }
+
+    /// Serves the recorded responses on a local port, returning the URL to reach them.
+    async fn serve(self: Arc<Self>) -> Result<(String, ServerHandle)> {
+        let mut module = RpcModule::new(self);
+        for method in METHODS {
+            module.register_async_method(method, move |params, rpc, _| async move {
+                let params = params.parse::<Value>().unwrap_or(Value::Array(Vec::new()));
+                rpc.call(method, params)
+                    .await
+                    .map_err(|err| ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, err, None::<()>))
+            })?;
+        }
+        let server =
+            Server::builder().max_response_body_size(u32::MAX).build("127.0.0.1:0").await?;
+        let url = format!("http://{}", server.local_addr()?);
+        Ok((url, server.start(module)))
+    }
+}
+
+/// Serves recorded blob sidecars, recording the ones it misses from `upstream` if set.
+#[derive(Debug)]
+struct RecordedBlobs {
+    sidecars: Mutex<BTreeMap<B256, Vec<RecordedSidecar>>>,
+    upstream: Option<BeaconClient>,
+}
+
+impl RecordedBlobs {
+    const fn new(
+        sidecars: BTreeMap<B256, Vec<RecordedSidecar>>,
+        upstream: Option<BeaconClient>,
+    ) -> Self {
+        Self { sidecars: Mutex::new(sidecars), upstream }
+    }
+
+    fn sidecars(&self) -> BTreeMap<B256, Vec<RecordedSidecar>> {
+        self.sidecars.lock().unwrap().clone()
+    }
+
+    fn recorded(&self, block: B256, indices: &[u64]) -> Vec<BlobSidecar> {
+        let sidecars = self.sidecars.lock().unwrap();
+        let recorded = sidecars.get(&block).map(Vec::as_slice).unwrap_or_default();
+        recorded
+            .iter()
+            .filter(|sidecar| indices.contains(&sidecar.index))
+            .map(|sidecar| BlobSidecar {
+                index: sidecar.index,
+                blob: sidecar.blob.clone(),
+                kzg_commitment: sidecar.kzg_commitment,
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl BlobProvider for RecordedBlobs {
+    async fn blob_sidecars(&self, block: &BlockInfo, indices: &[u64]) -> Result<Vec<BlobSidecar>> {
+        let recorded = self.recorded(block.hash, indices);
+        if recorded.len() == indices.len() {
+            return Ok(recorded);
+        }
+        let Some(upstream) = &self.upstream else {
+            bail!("blobs {indices:?} of L1 block {block} were not recorded");
+        };
+
+        let fetched = upstream.blob_sidecars(block, indices).await?;
+        let mut sidecars = self.sidecars.lock().unwrap();
+        let stored = sidecars.entry(block.hash).or_default();
+        for sidecar in &fetched {
+            if !stored.iter().any(|stored| stored.index == sidecar.index) {
+                stored.push(RecordedSidecar {
+                    index: sidecar.index,
+                    blob: sidecar.blob.clone(),
+                    kzg_commitment: sidecar.kzg_commitment,
+                });
+            }
+        }
+        Ok(fetched)
+    }
+}
+
+/// The endpoints a range is derived from.
+#[derive(Debug)]
+struct Endpoints {
+    l1: Arc<RecordedRpc>,
+    l2: Arc<RecordedRpc>,
+    blobs: Arc<RecordedBlobs>,
+}
+
+/// Derives `blocks` L2 blocks on top of the L2 block `safe_head`, returning their hashes as
+/// sealed with the execution outputs of the canonical blocks.
+async fn derive(
+    chain_id: u64,
+    safe_head: u64,
+    blocks: usize,
+    endpoints: &Endpoints,
+) -> Result<Vec<B256>> {
+    let config = Arc::new(
+        RollupConfig::from_registry(chain_id)
+            .ok_or_else(|| eyre!("chain {chain_id} is not in the superchain registry"))?,
+    );
+    let (l1_url, _l1_server) = endpoints.l1.clone().serve().await?;
+    let (l2_url, _l2_server) = endpoints.l2.clone().serve().await?;
+    let l1 = Arc::new(RpcChainProvider::new(&l1_url)?);
+    let l2 = Arc::new(
+        RpcL2StateProvider::new(&l2_url, config.genesis.clone())?
+            .with_isthmus_time(config.isthmus_time),
+    );
+    let l2_client = HttpClientBuilder::default().max_response_size(u32::MAX).build(&l2_url)?;
+
+    let mut pipeline = PipelineBuilder::new(config.clone(), l1.clone())
+        .l2_chain_provider(l2.clone())
+        .blob_fetcher(BlobFetcher::new(endpoints.blobs.clone()))
+        .build()?;
+    let (mut cursor, _) = l2.block_by_number(safe_head).await?;
+    let l1_origin = l1.block_info_by_number(cursor.l1_origin.number).await?;
+    pipeline.reset(cursor, l1_origin).await?;
+
+    let mut hashes = Vec::with_capacity(blocks);
+    let mut progress = Instant::now();
+    while hashes.len() < blocks {
+        match pipeline.step(cursor).await {
+            StepResult::PreparedAttributes => progress = Instant::now(),
+            StepResult::AdvancedOrigin => {
+                progress = Instant::now();
+                continue;
+            }
+            // Past the last recorded L1 block, the pipeline waits for the channels still being
+            // decompressed.
+            StepResult::OriginAdvanceErr(err) => {
+                ensure!(
+                    progress.elapsed() < STALL_TIMEOUT,
+                    "derivation stalled on top of {cursor}: {err}"
+                );
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                continue;
+            }
+            StepResult::StepFailed(err) => bail!("derivation stopped on top of {cursor}: {err}"),
+        }
+        let Some(derived) = pipeline.next() else { continue };
+        let number = cursor.block_info.number + 1;
+        let canonical = canonical_header(&l2_client, number).await?;
+        let isthmus = config.is_isthmus_active(derived.attributes.payload_attributes.timestamp);
+        let header = seal(&derived.attributes, cursor.block_info.hash, &canonical, isthmus);
+        let hash = header.hash_slow();
+        cursor = next_cursor(&derived.attributes, &header, hash)
+            .wrap_err_with(|| format!("invalid attributes of L2 block {number}"))?;
+        hashes.push(hash);
+    }
+    Ok(hashes)
+}
+
+/// Returns the header of the canonical L2 block at `number`.
+async fn canonical_header(client: &HttpClient, number: u64) -> Result<Header> {
+    let block: Option<Block> = client
+        .request("eth_getBlockByNumber", rpc_params![U64::from(number), false])
+        .await
+        .wrap_err_with(|| format!("failed to fetch L2 block {number}"))?;
+    let block = block.ok_or_else(|| eyre!("L2 block {number} not found"))?;
+    // Isthmus headers commit to an empty list of execution layer requests.
+    let requests_hash = block.other.get_deserialized::<B256>("requestsHash").transpose()?;
+    let mut header = Header::try_from(block.header)?;
+    header.requests_root = header.requests_root.or(requests_hash);
+    Ok(header)
+}
+
+/// Seals the block built from `attributes` on top of `parent`, with the execution outputs of
+/// the canonical block.
+fn seal(
+    attributes: &OptimismPayloadAttributes,
+    parent: B256,
+    canonical: &Header,
+    isthmus: bool,
+) -> Header {
+    let payload = &attributes.payload_attributes;
+    let transactions = attributes.transactions.as_deref().unwrap_or_default();
+    Header {
+        parent_hash: parent,
+        beneficiary: payload.suggested_fee_recipient,
+        transactions_root: ordered_root(transactions),
+        timestamp: payload.timestamp,
+        mix_hash: payload.prev_randao,
+        gas_limit: attributes.gas_limit.map_or(canonical.gas_limit, u128::from),
+        // From Isthmus the withdrawals root is the `L2ToL1MessagePasser` storage root, an
+        // execution output.
+        withdrawals_root: payload.withdrawals.as_ref().map(|_| {
+            if isthmus {
+                canonical.withdrawals_root.unwrap_or_default()
+            } else {
+                EMPTY_ROOT_HASH
+            }
+        }),
+        parent_beacon_block_root: payload.parent_beacon_block_root,
+        ..canonical.clone()
+    }
+}
+
+/// Returns the root of the trie of `values` keyed by the RLP encoding of their index.
+fn ordered_root(values: &[Bytes]) -> B256 {
+    let mut leaves: Vec<_> = values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let mut key = Vec::new();
+            index.encode(&mut key);
+            (Nibbles::unpack(key), value)
+        })
+        .collect();
+    leaves.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut builder = HashBuilder::default();
+    for (key, value) in leaves {
+        builder.add_leaf(key, value);
+    }
+    builder.root()
+}
+
+/// Returns the reference to the derived block `hash`, read from its L1 info deposit.
+fn next_cursor(
+    attributes: &OptimismPayloadAttributes,
+    header: &Header,
+    hash: B256,
+) -> Result<L2BlockInfo> {
+    let first = attributes.transactions.as_ref().and_then(|txs| txs.first());
+    let first = first.ok_or_else(|| eyre!("no L1 info deposit"))?;
+    let deposit = TxDeposit::decode_2718(&mut first.as_ref())?;
+    let l1_info = L1BlockInfoTx::decode_calldata(&deposit.input)?;
+    let block = BlockInfo::new(hash, header.number, header.parent_hash, header.timestamp);
+    Ok(L2BlockInfo::new(block, l1_info.id(), l1_info.sequence_number()))
+}
+
+/// The time the generated ranges start at, between Granite and Holocene on both chains.
+const GENERATED_TIME: u64 = 1_730_000_000;
+
+/// The number of the L1 origin of the safe head of the generated ranges.
+const GENERATED_L1_ORIGIN: u64 = 21_000_000;
+
+/// The number of L2 blocks of the generated ranges, spanning three epochs.
+const GENERATED_BLOCKS: u64 = 10;
+
+/// The recipient of the L2 block fees, as the derivation specs set it.
+const SEQUENCER_FEE_VAULT: Address = address!("4200000000000000000000000000000000000011");
+
+/// The Ecotone fee scalars of the generated system config.
+const BASE_FEE_SCALAR: u32 = 1_368;
+const BLOB_BASE_FEE_SCALAR: u32 = 810_949;
+
+/// Builds the L1 and L2 chains of a generated fixture, and the RPC responses serving them.
+struct Generator {
+    config: RollupConfig,
+    /// The number and timestamp of the first L1 block.
+    l1_start: (u64, u64),
+    system_config: SystemConfig,
+    l1: Vec<Header>,
+    l1_records: Vec<Recorded>,
+    l2_records: Vec<Recorded>,
+}
+
+impl Generator {
+    /// Appends an L1 block 12 seconds after the previous one, with the batcher transaction
+    /// posting `batcher_data` if set.
+    fn push_l1(&mut self, batcher_data: Option<Bytes>) {
+        let parent = self.l1.last();
+        let (number, timestamp) =
+            parent.map_or(self.l1_start, |parent| (parent.number + 1, parent.timestamp + 12));
+        let (from, to) = (self.system_config.batcher_address, self.config.batch_inbox_address);
+        let transactions: Vec<_> = batcher_data
+            .into_iter()
+            .map(|input| json!({ "hash": keccak256(&input), "from": from, "to": to, "input": input }))
+            .collect();
+        let receipts: Vec<L1Receipt> = transactions
+            .iter()
+            .map(|_| L1Receipt {
+                tx_type: 2,
+                receipt: ReceiptWithBloom {
+                    receipt: Receipt {
+                        status: Eip658Value::Eip658(true),
+                        cumulative_gas_used: 21_000,
+                        logs: Vec::new(),
+                    },
+                    logs_bloom: Bloom::default(),
+                },
+            })
+            .collect();
+
+        let header = Header {
+            parent_hash: parent.map(Header::hash_slow).unwrap_or_default(),
+            number,
+            timestamp,
+            state_root: keccak256(format!("L1 state {number}")),
+            receipts_root: receipts_root(&receipts),
+            gas_limit: 30_000_000,
+            gas_used: 21_000 * receipts.len() as u128,
+            mix_hash: keccak256(format!("L1 randao {number}")),
+            base_fee_per_gas: Some(7_000_000_000),
+            withdrawals_root: Some(EMPTY_ROOT_HASH),
+            blob_gas_used: Some(0),
+            excess_blob_gas: Some(0),
+            parent_beacon_block_root: Some(keccak256(format!("beacon block {number}"))),
+            ..Default::default()
+        };
+        let hash = header.hash_slow();
+        let receipts: Vec<_> = receipts
+            .iter()
+            .map(|_| {
+                json!({
+                    "type": "0x2",
+                    "status": "0x1",
+                    "cumulativeGasUsed": "0x5208",
+                    "logs": [],
+                    "logsBloom": Bloom::default(),
+                })
+            })
+            .collect();
+        let hashes: Vec<_> = transactions.iter().map(|tx| tx["hash"].clone()).collect();
+        self.l1_records.extend([
+            Recorded::new(
+                "eth_getBlockByNumber",
+                json!([U64::from(number), false]),
+                block(&header, hashes.clone()),
+            ),
+            Recorded::new("eth_getBlockByHash", json!([hash, false]), block(&header, hashes)),
+            Recorded::new("eth_getBlockByHash", json!([hash, true]), block(&header, transactions)),
+            Recorded::new("eth_getBlockReceipts", json!([hash]), Value::Array(receipts)),
+        ]);
+        self.l1.push(header);
+    }
+
+    /// Builds the L2 block `number` at `timestamp` on top of `parent`, with the L1 info deposit
+    /// of the latest L1 block at or before `timestamp` followed by `transactions`. Returns the
+    /// block header with its batch.
+    fn push_l2(
+        &mut self,
+        number: u64,
+        timestamp: u64,
+        parent: B256,
+        transactions: Vec<Bytes>,
+    ) -> (Header, SingleBatch) {
+        let origin = self
+            .l1
+            .iter()
+            .rev()
+            .find(|header| header.timestamp <= timestamp)
+            .expect("L2 block before the L1 chain");
+        let l1_info = L1BlockInfoTx::Ecotone(L1BlockInfoEcotone {
+            number: origin.number,
+            time: origin.timestamp,
+            base_fee: origin.base_fee_per_gas.unwrap_or_default() as u64,
+            block_hash: origin.hash_slow(),
+            sequence_number: (timestamp - origin.timestamp) / self.config.block_time,
+            batcher_address: self.system_config.batcher_address,
+            blob_base_fee: 1,
+            blob_base_fee_scalar: BLOB_BASE_FEE_SCALAR,
+            base_fee_scalar: BASE_FEE_SCALAR,
+        });
+        let deposit = l1_info.to_deposit_tx(&self.config, timestamp);
+        let all: Vec<Bytes> =
+            std::iter::once(deposit.encoded_2718()).chain(transactions.iter().cloned()).collect();
+
+        let header = Header {
+            parent_hash: parent,
+            beneficiary: SEQUENCER_FEE_VAULT,
+            state_root: keccak256(format!("L2 state {number}")),
+            transactions_root: ordered_root(&all),
+            receipts_root: keccak256(format!("L2 receipts {number}")),
+            number,
+            gas_limit: self.system_config.gas_limit.into(),
+            gas_used: 21_000 * all.len() as u128,
+            timestamp,
+            mix_hash: origin.mix_hash,
+            base_fee_per_gas: Some(1_000),
+            withdrawals_root: Some(EMPTY_ROOT_HASH),
+            blob_gas_used: Some(0),
+            excess_blob_gas: Some(0),
+            parent_beacon_block_root: origin.parent_beacon_block_root,
+            ..Default::default()
+        };
+        let batch = SingleBatch {
+            parent_hash: parent,
+            epoch_num: origin.number,
+            epoch_hash: origin.hash_slow(),
+            timestamp,
+            transactions,
+        };
+
+        // The state provider reads the L1 info from the input of the full transactions.
+        let inputs = std::iter::once(json!({ "input": deposit.input }))
+            .chain(batch.transactions.iter().map(|_| json!({ "input": "0x" })))
+            .collect();
+        let hashes = all.iter().map(|tx| json!(keccak256(tx))).collect();
+        self.l2_records.extend([
+            Recorded::new(
+                "eth_getBlockByNumber",
+                json!([U64::from(number), true]),
+                block(&header, inputs),
+            ),
+            Recorded::new(
+                "eth_getBlockByNumber",
+                json!([U64::from(number), false]),
+                block(&header, hashes),
+            ),
+        ]);
+        (header, batch)
+    }
+}
+
+/// Returns the RPC block of `header`, with the given transactions.
+fn block(header: &Header, transactions: Vec<Value>) -> Value {
+    json!({
+        "hash": header.hash_slow(),
+        "parentHash": header.parent_hash,
+        "sha3Uncles": header.ommers_hash,
+        "miner": header.beneficiary,
+        "stateRoot": header.state_root,
+        "transactionsRoot": header.transactions_root,
+        "receiptsRoot": header.receipts_root,
+        "logsBloom": header.logs_bloom,
+        "difficulty": header.difficulty,
+        "number": U64::from(header.number),
+        "gasLimit": U128::from(header.gas_limit),
+        "gasUsed": U128::from(header.gas_used),
+        "timestamp": U64::from(header.timestamp),
+        "extraData": header.extra_data,
+        "mixHash": header.mix_hash,
+        "nonce": header.nonce,
+        "baseFeePerGas": header.base_fee_per_gas.map(U128::from),
+        "withdrawalsRoot": header.withdrawals_root,
+        "blobGasUsed": header.blob_gas_used.map(U128::from),
+        "excessBlobGas": header.excess_blob_gas.map(U128::from),
+        "parentBeaconBlockRoot": header.parent_beacon_block_root,
+        "uncles": [],
+        "withdrawals": [],
+        "transactions": transactions,
+    })
+}
+
+/// Generates a fixture on the rollup config of `chain_id`: an L1 chain whose only batcher
+/// transaction posts a channel of single batches, and the canonical L2 chain those batches
+/// derive to, built as the derivation specs define it rather than through the pipeline.
+///
+/// The L1 origin of the safe head is a channel timeout past the first L1 block, so that the
+/// pipeline reset reads the L1 chain from its start.
+fn generate(chain_id: u64) -> Result<Fixture> {
+    let config = RollupConfig::from_registry(chain_id)
+        .ok_or_else(|| eyre!("chain {chain_id} is not in the superchain registry"))?;
+    let genesis = &config.genesis;
+    let safe_head = genesis.l2.number + (GENERATED_TIME - genesis.l2_time) / config.block_time;
+    let time = |number: u64| genesis.l2_time + (number - genesis.l2.number) * config.block_time;
+    let safe_time = time(safe_head);
+    let blocks = safe_head + 1..=safe_head + GENERATED_BLOCKS;
+    let end_time = time(*blocks.end());
+
+    // The safe head is the fourth block of its epoch, and the range reaches two epochs further.
+    let origin_time = safe_time - 3 * config.block_time;
+    let first = GENERATED_L1_ORIGIN - config.channel_timeout(origin_time);
+    let mut generator = Generator {
+        l1_start: (first, origin_time - (GENERATED_L1_ORIGIN - first) * 12),
+        system_config: genesis.system_config.clone().unwrap_or_default(),
+        config: config.clone(),
+        l1: Vec::new(),
+        l1_records: Vec::new(),
+        l2_records: Vec::new(),
+    };
+    while generator.l1.last().map_or(true, |last| last.timestamp + 12 <= end_time) {
+        generator.push_l1(None);
+    }
+
+    let (safe, _) =
+        generator.push_l2(safe_head, safe_time, keccak256("safe head parent"), Vec::new());
+    let mut parent = safe.hash_slow();
+    let mut hashes = Vec::new();
+    let mut channel = ChannelOut::new(ChannelId([0x42; 16]), Compression::default(), 1_000_000, 0);
+    for number in blocks {
+        let transaction = [&[0x02][..], keccak256(number.to_be_bytes()).as_slice()].concat();
+        let (header, batch) =
+            generator.push_l2(number, time(number), parent, vec![transaction.into()]);
+        ensure!(channel.add_batch(&Batch::Single(batch))?, "generated channel is full");
+        parent = header.hash_slow();
+        hashes.push(parent);
+    }
+
+    // The batches are posted once the last epoch of the range is on L1.
+    let frames = channel.into_frames(DEFAULT_TARGET_FRAME_SIZE)?;
+    let data = TxData { frames, data_availability: DataAvailability::Calldata }.calldata();
+    generator.push_l1(Some(data));
+    generator.push_l1(None);
+
+    Ok(Fixture {
+        chain_id,
+        safe_head,
+        blocks: hashes,
+        l1: generator.l1_records,
+        l2: generator.l2_records,
+        blobs: BTreeMap::new(),
+    })
+}
+
+/// Derives the range `name` of `fixture`, checking it against the canonical hashes.
+async fn replay(name: &str, fixture: Fixture) -> Result<()> {
+    let endpoints = Endpoints {
+        l1: Arc::new(RecordedRpc::new(fixture.l1, None)),
+        l2: Arc::new(RecordedRpc::new(fixture.l2, None)),
+        blobs: Arc::new(RecordedBlobs::new(fixture.blobs, None)),
+    };
+    let derived =
+        derive(fixture.chain_id, fixture.safe_head, fixture.blocks.len(), &endpoints).await?;
+
+    for (i, (derived, canonical)) in derived.iter().zip(&fixture.blocks).enumerate() {
+        let number = fixture.safe_head + 1 + i as u64;
+        ensure!(
+            derived == canonical,
+            "{name}: L2 block {number} derived as {derived}, canonical block is {canonical}"
+        );
+    }
+    Ok(())
+}
+
+fn env<T: std::str::FromStr>(name: &str) -> Result<T>
+where
+    T::Err: std::fmt::Display,
+{
+    let value = std::env::var(name).wrap_err_with(|| format!("{name} is not set"))?;
+    value.parse().map_err(|err| eyre!("invalid {name}: {err}"))
+}
+
+#[tokio::test]
+async fn synthetic_op_mainnet_config() {
+    replay("synthetic op-mainnet", generate(10).unwrap()).await.unwrap();
+}
+
+#[tokio::test]
+async fn synthetic_base_config() {
+    replay("synthetic base", generate(8453).unwrap()).await.unwrap();
+}
+
+#[tokio::test]
+async fn recorded() {
+    let names = match env::<String>("HERA_REGRESSION_FIXTURE") {
+        Ok(name) => vec![name],
+        Err(_) => Fixture::names().unwrap(),
+    };
+    assert!(
+        !names.is_empty(),
+        "no fixture in tests/fixtures/regression, record them with the `record` test"
+    );
+    for name in names {
+        replay(&name, Fixture::load(&name).unwrap()).await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[ignore = "records a fixture from live endpoints"]
+async fn record() {
+    let name: String = env("HERA_REGRESSION_FIXTURE").unwrap();
+    let chain_id = env("HERA_REGRESSION_CHAIN_ID").unwrap();
+    let safe_head = env("HERA_REGRESSION_SAFE_HEAD").unwrap();
+    let blocks = env("HERA_REGRESSION_BLOCKS").unwrap();
+    let client = |var: &str| {
+        let url: String = env(var)?;
+        Ok::<_, eyre::Report>(HttpClientBuilder::default().max_response_size(u32::MAX).build(url)?)
+    };
+    let endpoints = Endpoints {
+        l1: Arc::new(RecordedRpc::new(Vec::new(), Some(client("HERA_REGRESSION_L1_RPC").unwrap()))),
+        l2: Arc::new(RecordedRpc::new(Vec::new(), Some(client("HERA_REGRESSION_L2_RPC").unwrap()))),
+        blobs: Arc::new(RecordedBlobs::new(
+            BTreeMap::new(),
+            Some(BeaconClient::new(env("HERA_REGRESSION_BEACON_URL").unwrap())),
+        )),
+    };
+
+    let derived = derive(chain_id, safe_head, blocks, &endpoints).await.unwrap();
+    let l2 = endpoints.l2.clone().serve().await.unwrap();
+    let l2_client = HttpClientBuilder::default().build(&l2.0).unwrap();
+    for (i, derived) in derived.iter().enumerate() {
+        let number = safe_head + 1 + i as u64;
+        let canonical = canonical_header(&l2_client, number).await.unwrap().hash_slow();
+        assert_eq!(*derived, canonical, "L2 block {number} does not derive to the canonical block");
+    }
+
+    let fixture = Fixture {
+        chain_id,
+        safe_head,
+        blocks: derived,
+        l1: endpoints.l1.records(),
+        l2: endpoints.l2.records(),
+        blobs: endpoints.blobs.sidecars(),
+    };
+    fixture.save(&name).unwrap();
+}